@@ -0,0 +1,20 @@
+#![no_main]
+
+// Run with: cargo +nightly fuzz run keyfile
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/keyfile.rs"]
+#[allow(dead_code)]
+mod keyfile;
+
+fuzz_target!(|data: &[u8]| {
+    // owner_keys.txt is read with read_to_string, so only valid UTF-8 gets this far
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    // Anything that parses must survive being written back out and read again.
+    if let Ok(keys) = keyfile::parse(text) {
+        assert_eq!(keyfile::parse(&keyfile::render(&keys)), Ok(keys));
+    }
+});
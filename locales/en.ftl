@@ -0,0 +1,246 @@
+# What the interactive nodes say, in English. Every message here needs one
+# with the same id in the other locales/*.ftl files (the i18n tests check).
+
+## Starting up
+
+config-problems = Configuration problems (run `config validate` for details):
+dry-run-banner = DRY RUN: DHT writes will be printed, not sent.
+chaos-banner = CHAOS MODE: { $chaos }
+latency-banner = INJECTED LATENCY: every DHT call held back { $min }..{ $max }
+menu =
+    Select Veilid configuration:
+      Press 1 - Default config
+      Press 2 - Alternate config
+      Press 3 - Member node
+starting-default = Starting DEFAULT node
+starting-alt = Starting ALTERNATE node
+starting-member = Starting MEMBER node
+menu-invalid = Invalid choice, try again.
+auditing-to = Auditing DHT operations to { $path }
+no-private-route = No private route, so nobody can send us their card: { $error }
+shutdown-complete = Shutdown complete (press enter)
+
+## Default node
+
+resumed-record = The last run didn't shut down cleanly, picking record { $key } back up
+reopen-failed = Couldn't re-open record { $key }, making a new one: { $error }
+recovery-keys-failed = Couldn't keep the record's keys for crash recovery: { $error }
+metadata-write-failed = Couldn't write the record's metadata to subkey 0: { $error }
+protocol-write-failed = Couldn't write the record's protocol version to subkey { $subkey }: { $error }
+route-published = Published our private route in subkey { $subkey }; the other nodes' :msg lines come in over it
+route-publish-failed = Couldn't publish our private route in subkey { $subkey }: { $error }
+route-renewed = Our private route died; a new one is in subkey { $subkey }
+peer-route = { $name }'s private route is in subkey { $subkey }; :msg goes over it
+peer-route-renewed = { $name } made a new private route; :msg goes over that now
+member-node-added = The member node ({ $key }) writes subkeys { $first }..={ $last }
+keyfile-loaded = txt file loaded
+dry-run-keyfile = [dry-run] would write RecordKey to { $path }
+keyfile-written = Owner keys written to { $path }
+keyfile-grants = It grants: { $grant }
+keyfile-holds-keys = It holds our keys now, so only give it to someone you'd let write as us
+share-codes =
+    Share code: { $code }
+           or:  { $key }
+dry-run-announce = [dry-run] would announce the record as '{ $label }' in the app index
+announced = Announced as '{ $label }' in the app index (slot { $slot })
+announce-failed = Couldn't announce the record: { $error }
+values-expire = Values expire { $secs }s after they're written
+default-intro =
+    (You can now open a second console to run the Alt Node)
+    Type text and press ENTER to write to the DHT
+    Type 'flood <subkey> <count>' to see how fast a subkey can be written
+    Type 'inbox' to read the mail others have left
+    Type 'help' for the commands
+    Or, Press Ctrl+C to exit
+new-letters = { $tag } { $count } new { $count ->
+        [one] letter
+       *[other] letters
+    }, type 'inbox' to read
+subkey-switched = Text goes to subkey { $subkey } from now on
+subkey-kept = Text still goes to subkey { $subkey }: { $error }
+subkey-reserved = Subkey { $subkey } is one of the owner subkeys the node keeps for itself; text goes to { $first } and up
+not-a-field = '{ $name }' isn't one of the shared fields ({ $fields })
+not-set = Not set: { $error }
+field-set = Set our { $name } (subkey { $subkey }); 'merge { $name }' shows everyone's
+field-set-failed = Couldn't set { $name }: { $error }
+no-inbox-subkeys = This record has no mailbox (inbox_subkeys is 0)
+flooding = Flooding subkey { $subkey } with { $count } writes...
+flood-usage = Usage: flood <subkey> <count>
+write-failed = Write to subkey { $subkey } failed: { $error }
+wrote-numbered = Wrote #{ $count } to subkey { $subkey }: { $text }
+
+## Alternate node
+
+resumed-alt = The last run didn't shut down cleanly, picking up { $state }
+enter-record = Enter a record key or share code (or just press ENTER to use { $file }):
+unreadable-encryption = The record's values use '{ $encryption }' encryption, which this build can't read.
+joining = Joining record { $code }
+following = Following subkeys { $first }..={ $last } as the key file suggests
+alt-ready = Alternate node ready
+opened-record = Opened record { $code }: { $key }
+granted = Granted: { $grant }
+fingerprint-warning =
+    !!! WARNING: this is not the record you were sent !!!
+    !!! The share code's fingerprint is { $wanted }, the record { $code } has { $found }.
+    !!! The key or code was mistyped, or something swapped it for another record.
+    !!! Check it with whoever sent it before trusting anything you read here.
+record-title = Record { $title }
+typed-subkeys = Typed subkeys: { $types }
+card-send-failed = Couldn't send our card to the record's owner: { $error }
+no-metadata-block = Record has no metadata block
+other-in-subkey-0 = Record has no metadata block (subkey 0 holds something else)
+metadata-read-failed = Couldn't read the record's metadata: { $error }
+protocol-read-failed = Couldn't read the record's protocol version, going on as if it had none: { $error }
+read-only-record = Read only: { $why }
+watch-active = DHT watch active
+watch-not-active = DHT watch not active: { $error }
+watching-again = Watching { $code } again
+watch-again-failed = Couldn't watch { $code } again: { $error }
+alt-intro =
+    Press ENTER to read/re-read the DHT
+    Type 'read <subkey>' to read one subkey (the rest are fetched behind it)
+    Type 'stats watch' and ENTER to see how the watch is doing
+    Type 'nick <public key> <name>' to label a writer
+    Type 'mail <text>' to leave the record's owner a message, even while they're away ('outbox' to see if it's been read)
+    Type 'watch <record>' to follow another record's changes too
+    Type 'feed [--record X] [--member NAME]' for one timeline of every change ('feed off' to stop)
+    Type 'help' for the commands
+    Press Ctrl+C to exit
+watch-died = { $tag } the watch died, press ENTER to re-read the DHT
+record-changed = { $tag } the record has changed, press ENTER to read it
+value-changed = { $tag } subkey { $subkey } (seq { $seq }, { $writer }): { $value }
+subkeys-changed = { $tag } subkeys { $subkeys } changed
+no-mailbox = This record has no mailbox
+seal-failed = Couldn't seal the letter: { $error }
+letter-left = Left it in inbox slot { $slot } ('outbox' shows when it's read)
+outbox-keep-failed = Couldn't keep it in the outbox, so it won't be resent if lost: { $error }
+send-failed = Couldn't send it: { $error }
+confirm-shared-write = { $code } wasn't made in this data folder, so it's someone else's. { $what }? y to go ahead, n to drop it
+shared-write-value = Write { $size } bytes to its subkey { $subkey }
+shared-write-lock = Change the lock on its subkey { $subkey }
+shared-write-publish = Publish to its subkey { $subkey } every { $secs }s
+shared-write-load = Load { $rows } row(s) into it
+shared-write-member = Write to it as this member node
+not-written-granted = Not written: { $error } (granted: { $grant })
+wrote-subkey = Wrote subkey { $subkey }
+subkey-write-failed = Couldn't write subkey { $subkey }: { $error }
+read-usage = Usage: read <subkey>
+subkeys-go-up-to = The record's subkeys go up to { $max }
+read-value = { $tag } subkey { $subkey } ({ $writer }): { $value }
+read-prefetched = { $tag } subkey { $subkey } ({ $writer }, prefetched): { $value }
+read-empty = { $tag } subkey { $subkey }: <no data>
+read-failed = { $tag } subkey { $subkey }: failed ({ $error })
+already-watching = Already watching { $code }
+open-failed = Couldn't open { $code }: { $error }
+watching-too = Watching { $code } too
+watch-failed = Couldn't watch { $code }: { $error }
+feed-off = Feed off
+feed-on = Following the feed ('feed off' to stop)
+nick-set = { $key } is now known as { $name }
+not-a-public-key = Not a public key: { $error }
+nick-usage = Usage: nick <public key> <name>
+reading-dht = Reading the DHT...
+refresh-hint = Press ENTER to refresh, Ctrl+C to exit
+
+## Member node
+
+member-key = Member key: { $key } (left in { $file } for the default node)
+member-no-record = No record to write to yet ({ $error }). Start the default node now: the record it makes will have subkeys for this member.
+member-no-room = record { $key } has no subkeys for this member's key, and the key file grants no writer; start the default node again for a record with room for it
+member-own-key = this member's own key
+member-granted-key = the writer key the key file grants
+member-writes-as = Writing as { $who } to subkeys { $subkeys } of { $key }
+member-help = Text goes to subkey { $subkey }; <subkey>:<text>, :sub <subkey>, :all <text> and field <name> <text> as at the default prompt. Ctrl+D to leave.
+
+## Both prompts
+
+ctrl-c = Ctrl+C received, shutting down...
+no-such-stats = No stats called '{ $what }' at this prompt (see `help`)
+bundle-written = Wrote { $path } (secrets left out), attach it to the issue
+bundle-failed = Couldn't write the bundle: { $error }
+metrics-save-failed = Couldn't save the call times for `stats trend`: { $error }
+not-written = Not written
+not-written-because = Not written: { $error }
+confirm-write = Write "{ $text }" to subkey { $subkey }? y to send it, n to drop it
+
+## Stats
+
+stats-no-watches = No watches active
+stats-watch-on = Watch on { $key }
+stats-watch-active = active
+stats-watch-dead = DEAD
+stats-watch-status = status:          { $status }
+stats-watch-running = running for:     { $secs }s
+stats-watch-changes = changes:         { $count }
+stats-watch-delay = avg delay:       { $delay }
+stats-watch-missed = missed (gaps):   { $count }
+stats-watch-out-of-order = out of order:    { $count }
+stats-watch-remaining = count remaining: { $count }
+stats-watch-subkey = subkey { $subkey }: { $changes } changes, last seq { $last }, { $missed } missed
+stats-no-delay = n/a (no writer timestamps)
+stats-no-traffic = Nothing written to or read from the DHT yet
+stats-col-feature = feature
+stats-col-writes = writes
+stats-col-written = written
+stats-col-reads = reads
+stats-col-read = read
+stats-col-total = total
+
+## Contacts
+
+no-contacts = No contacts yet
+contact-record = record { $code }
+contact-reachable = reachable
+contact-saved = Saved { $name }
+contact-save-failed = Couldn't save { $name }: { $error }
+contact-removed = Removed { $name }
+no-such-contact = No contact called { $name }
+contact-remove-failed = Couldn't remove { $name }: { $error }
+contact-remove-usage = Usage: contact remove <name>
+contact-add-usage = Usage: contact add <name> [--key KEY] [--record REC] [--route BLOB]
+contact-needs-value = { $flag } needs a value
+contact-unknown-option = Unknown option '{ $option }' (try --key, --record or --route)
+contact-unknown-command = Unknown contact command '{ $command }' (add, list or remove)
+
+## Conflicts
+
+conflict-lost = Subkey { $subkey } was changed by someone else while you wrote, so yours didn't go in:
+conflict-base = base
+conflict-yours = yours
+conflict-theirs = theirs
+conflict-merged = merged
+conflict-merged-with-conflicts = merged, with conflicts
+conflict-choices = Type m to write the merge, y to write yours over theirs, t to keep theirs, or e <text> to write your own
+conflict-kept-theirs = Kept theirs at subkey { $subkey }, nothing written
+conflict-merge-conflicted = The merge has conflicts; pick y or t, or type e <text> with your own
+
+## Relay
+
+relay-usage = Usage: relay [on|off|join <contact>|leave <contact>]
+relay-nothing-to-relay = This node doesn't watch the record, so has nothing to relay; `relay join` the node that does
+relay-on = Relay mode on: contacts who `relay join` us get each change we see
+relay-off = Relay mode off; stopped sending to { $count } joiner(s)
+relay-no-such-contact = No contact called { $name } (see `contact list`)
+relay-no-route = { $name } hasn't sent us a route to reach them on
+relay-no-own-route = We have no private route for the changes to come back on
+relay-unreachable = Couldn't reach { $name }: { $error }
+relay-asked-join = Asked { $name } to relay the record's changes; they show up as [relay { $name }]
+relay-asked-leave = Asked { $name } to stop relaying to us
+relay-on-no-joiners = Relay mode on; nobody has joined yet
+relay-on-sending-to = Relay mode on, sending to: { $names }
+relay-status-off = Relay mode off (`relay on` to pass our changes on)
+relay-joined = Joined: { $names }
+relay-not-joined = Not joined to any relay (`relay join <contact>`)
+relayed-watch-died = { $code }: the watch died; nothing more until it's placed again
+relayed-value = subkey { $subkey } (seq { $seq }, { $writer }): { $value }
+relayed-also-changed = also changed: { $subkeys }
+relay-join-stranger = [relay] '{ $name }' asked to join, but isn't a contact; ignored
+relay-join-while-off = [relay] { $name } asked to join, but relay mode is off (`relay on`)
+relay-join-bad-route = [relay] { $name } asked to join with a route we can't use: { $error }
+relay-join-no-route = [relay] { $name } asked to join without a route to send on
+relay-peer-joined = [relay] { $name } joined; they get each change we see from now on
+relay-leave-stranger = [relay] '{ $name }' asked to leave, but isn't a contact; ignored
+relay-peer-left = [relay] { $name } left
+relay-leave-not-joined = [relay] { $name } asked to leave, but hadn't joined
+relay-undecodable = [relay] got a relay message that doesn't decode
+relay-dropped = [relay] dropped { $name }, whose route stopped answering: { $error }
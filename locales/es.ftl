@@ -0,0 +1,246 @@
+# Lo que dicen los nodos interactivos, en español (--lang es). Los comandos
+# ('inbox', 'read <subkey>', ...) se escriben igual en todos los idiomas.
+
+## Al arrancar
+
+config-problems = Hay problemas en la configuración (ejecuta `config validate` para ver los detalles):
+dry-run-banner = SIMULACIÓN: las escrituras en la DHT se muestran, pero no se envían.
+chaos-banner = MODO CAOS: { $chaos }
+latency-banner = LATENCIA INYECTADA: cada llamada a la DHT se retiene { $min }..{ $max }
+menu =
+    Elige la configuración de Veilid:
+      Pulsa 1 - Configuración por defecto
+      Pulsa 2 - Configuración alternativa
+      Pulsa 3 - Nodo miembro
+starting-default = Arrancando el nodo POR DEFECTO
+starting-alt = Arrancando el nodo ALTERNATIVO
+starting-member = Arrancando el nodo MIEMBRO
+menu-invalid = Opción no válida, prueba otra vez.
+auditing-to = Las operaciones en la DHT se registran en { $path }
+no-private-route = No hay ruta privada, así que nadie puede enviarnos su tarjeta: { $error }
+shutdown-complete = Apagado completo (pulsa ENTER)
+
+## Nodo por defecto
+
+resumed-record = La última ejecución no se cerró bien, se retoma el registro { $key }
+reopen-failed = No se pudo volver a abrir el registro { $key }, se crea uno nuevo: { $error }
+recovery-keys-failed = No se pudieron guardar las claves del registro para recuperarlo tras un fallo: { $error }
+metadata-write-failed = No se pudieron escribir los metadatos del registro en la subclave 0: { $error }
+protocol-write-failed = No se pudo escribir la versión de protocolo del registro en la subclave { $subkey }: { $error }
+route-published = Ruta privada publicada en la subclave { $subkey }; las líneas :msg de los otros nodos llegan por ella
+route-publish-failed = No se pudo publicar nuestra ruta privada en la subclave { $subkey }: { $error }
+route-renewed = Nuestra ruta privada murió; hay una nueva en la subclave { $subkey }
+peer-route = La ruta privada de { $name } está en la subclave { $subkey }; :msg va por ella
+peer-route-renewed = { $name } hizo una nueva ruta privada; :msg va por ella ahora
+member-node-added = El nodo miembro ({ $key }) escribe las subclaves { $first }..={ $last }
+keyfile-loaded = archivo txt cargado
+dry-run-keyfile = [simulación] se escribiría la RecordKey en { $path }
+keyfile-written = Claves del propietario escritas en { $path }
+keyfile-grants = Concede: { $grant }
+keyfile-holds-keys = Ahora contiene nuestras claves: dáselo solo a quien dejarías escribir en nuestro nombre
+share-codes =
+    Código para compartir: { $code }
+                        o: { $key }
+dry-run-announce = [simulación] se anunciaría el registro como '{ $label }' en el índice de aplicaciones
+announced = Anunciado como '{ $label }' en el índice de aplicaciones (hueco { $slot })
+announce-failed = No se pudo anunciar el registro: { $error }
+values-expire = Los valores caducan { $secs }s después de escribirse
+default-intro =
+    (Ya puedes abrir una segunda consola para arrancar el nodo alternativo)
+    Escribe un texto y pulsa ENTER para escribirlo en la DHT
+    Escribe 'flood <subkey> <count>' para ver lo rápido que se puede escribir una subclave
+    Escribe 'inbox' para leer el correo que te han dejado
+    Escribe 'help' para ver los comandos
+    O pulsa Ctrl+C para salir
+new-letters = { $tag } { $count ->
+        [one] { $count } carta nueva
+       *[other] { $count } cartas nuevas
+    }, escribe 'inbox' para leerlas
+subkey-switched = A partir de ahora el texto va a la subclave { $subkey }
+subkey-kept = El texto sigue yendo a la subclave { $subkey }: { $error }
+subkey-reserved = La subclave { $subkey } es una de las subclaves del propietario que el nodo se reserva; el texto va a la { $first } y siguientes
+not-a-field = '{ $name }' no es uno de los campos compartidos ({ $fields })
+not-set = No se ha guardado: { $error }
+field-set = Guardado nuestro { $name } (subclave { $subkey }); 'merge { $name }' muestra los de todos
+field-set-failed = No se pudo guardar { $name }: { $error }
+no-inbox-subkeys = Este registro no tiene buzón (inbox_subkeys es 0)
+flooding = Inundando la subclave { $subkey } con { $count } escrituras...
+flood-usage = Uso: flood <subkey> <count>
+write-failed = Falló la escritura en la subclave { $subkey }: { $error }
+wrote-numbered = Escrito el n.º { $count } en la subclave { $subkey }: { $text }
+
+## Nodo alternativo
+
+resumed-alt = La última ejecución no se cerró bien, se retoma { $state }
+enter-record = Introduce una clave de registro o un código para compartir (o pulsa ENTER para usar { $file }):
+unreadable-encryption = Los valores del registro usan el cifrado '{ $encryption }', que esta versión no sabe leer.
+joining = Uniéndose al registro { $code }
+following = Siguiendo las subclaves { $first }..={ $last }, como sugiere el archivo de claves
+alt-ready = Nodo alternativo listo
+opened-record = Registro { $code } abierto: { $key }
+granted = Concedido: { $grant }
+fingerprint-warning =
+    !!! AVISO: este no es el registro que te enviaron !!!
+    !!! La huella del código para compartir es { $wanted }, el registro { $code } tiene { $found }.
+    !!! La clave o el código se escribió mal, o algo lo cambió por otro registro.
+    !!! Compruébalo con quien te lo envió antes de fiarte de lo que leas aquí.
+record-title = Registro { $title }
+typed-subkeys = Subclaves con tipo: { $types }
+card-send-failed = No se pudo enviar nuestra tarjeta al propietario del registro: { $error }
+no-metadata-block = El registro no tiene bloque de metadatos
+other-in-subkey-0 = El registro no tiene bloque de metadatos (la subclave 0 contiene otra cosa)
+metadata-read-failed = No se pudieron leer los metadatos del registro: { $error }
+protocol-read-failed = No se pudo leer la versión de protocolo del registro, se sigue como si no tuviera: { $error }
+read-only-record = Solo lectura: { $why }
+watch-active = Vigilancia de la DHT activa
+watch-not-active = Vigilancia de la DHT inactiva: { $error }
+watching-again = Vigilando { $code } de nuevo
+watch-again-failed = No se pudo volver a vigilar { $code }: { $error }
+alt-intro =
+    Pulsa ENTER para leer/releer la DHT
+    Escribe 'read <subkey>' para leer una subclave (las demás se traen detrás)
+    Escribe 'stats watch' y pulsa ENTER para ver cómo va la vigilancia
+    Escribe 'nick <public key> <name>' para ponerle nombre a un escritor
+    Escribe 'mail <text>' para dejarle un mensaje al propietario del registro, aunque no esté ('outbox' para ver si lo ha leído)
+    Escribe 'watch <record>' para seguir también los cambios de otro registro
+    Escribe 'feed [--record X] [--member NAME]' para ver todos los cambios en una sola línea de tiempo ('feed off' para parar)
+    Escribe 'help' para ver los comandos
+    Pulsa Ctrl+C para salir
+watch-died = { $tag } la vigilancia se ha caído, pulsa ENTER para releer la DHT
+record-changed = { $tag } el registro ha cambiado, pulsa ENTER para leerlo
+value-changed = { $tag } subclave { $subkey } (seq { $seq }, { $writer }): { $value }
+subkeys-changed = { $tag } han cambiado las subclaves { $subkeys }
+no-mailbox = Este registro no tiene buzón
+seal-failed = No se pudo sellar la carta: { $error }
+letter-left = Dejada en el hueco { $slot } del buzón ('outbox' muestra cuándo se lee)
+outbox-keep-failed = No se pudo guardar en la bandeja de salida, así que no se reenviará si se pierde: { $error }
+send-failed = No se pudo enviar: { $error }
+confirm-shared-write = { $code } no se creó en esta carpeta de datos, así que es de otra persona. ¿{ $what }? y para seguir, n para descartarlo
+shared-write-value = Escribir { $size } bytes en su subclave { $subkey }
+shared-write-lock = Cambiar el bloqueo de su subclave { $subkey }
+shared-write-publish = Publicar en su subclave { $subkey } cada { $secs }s
+shared-write-load = Cargar { $rows } fila(s) en él
+shared-write-member = Escribir en él como este nodo miembro
+not-written-granted = No se ha escrito: { $error } (concedido: { $grant })
+wrote-subkey = Escrita la subclave { $subkey }
+subkey-write-failed = No se pudo escribir la subclave { $subkey }: { $error }
+read-usage = Uso: read <subkey>
+subkeys-go-up-to = Las subclaves del registro llegan hasta la { $max }
+read-value = { $tag } subclave { $subkey } ({ $writer }): { $value }
+read-prefetched = { $tag } subclave { $subkey } ({ $writer }, ya traída): { $value }
+read-empty = { $tag } subclave { $subkey }: <sin datos>
+read-failed = { $tag } subclave { $subkey }: error ({ $error })
+already-watching = Ya se está vigilando { $code }
+open-failed = No se pudo abrir { $code }: { $error }
+watching-too = Vigilando también { $code }
+watch-failed = No se pudo vigilar { $code }: { $error }
+feed-off = Feed desactivado
+feed-on = Siguiendo el feed ('feed off' para parar)
+nick-set = { $key } se llama ahora { $name }
+not-a-public-key = No es una clave pública: { $error }
+nick-usage = Uso: nick <public key> <name>
+reading-dht = Leyendo la DHT...
+refresh-hint = Pulsa ENTER para actualizar, Ctrl+C para salir
+
+## Nodo miembro
+
+member-key = Clave de miembro: { $key } (dejada en { $file } para el nodo por defecto)
+member-no-record = Todavía no hay registro en el que escribir ({ $error }). Arranca ahora el nodo por defecto: el registro que cree tendrá subclaves para este miembro.
+member-no-room = el registro { $key } no tiene subclaves para la clave de este miembro, y el archivo de claves no concede ningún escritor; vuelve a arrancar el nodo por defecto para tener un registro con sitio para él
+member-own-key = la clave propia de este miembro
+member-granted-key = la clave de escritor que concede el archivo de claves
+member-writes-as = Escribiendo como { $who } en las subclaves { $subkeys } de { $key }
+member-help = El texto va a la subclave { $subkey }; <subclave>:<texto>, :sub <subclave>, :all <texto> y field <nombre> <texto> como en el nodo por defecto. Ctrl+D para salir.
+
+## En los dos nodos
+
+ctrl-c = Ctrl+C recibido, apagando...
+no-such-stats = No hay ninguna estadística llamada '{ $what }' aquí (mira `help`)
+bundle-written = Escrito { $path } (sin secretos), adjúntalo a la incidencia
+bundle-failed = No se pudo escribir el paquete: { $error }
+metrics-save-failed = No se pudieron guardar los tiempos de las llamadas para `stats trend`: { $error }
+not-written = No se ha escrito
+not-written-because = No se ha escrito: { $error }
+confirm-write = ¿Escribir "{ $text }" en la subclave { $subkey }? y para enviarlo, n para descartarlo
+
+## Estadísticas
+
+stats-no-watches = No hay ningún watch activo
+stats-watch-on = Watch sobre { $key }
+stats-watch-active = activo
+stats-watch-dead = MUERTO
+stats-watch-status = estado:             { $status }
+stats-watch-running = activo desde hace:  { $secs }s
+stats-watch-changes = cambios:            { $count }
+stats-watch-delay = retraso medio:      { $delay }
+stats-watch-missed = perdidos (huecos):  { $count }
+stats-watch-out-of-order = fuera de orden:     { $count }
+stats-watch-remaining = quedan por avisar:  { $count }
+stats-watch-subkey = subclave { $subkey }: { $changes } cambios, último seq { $last }, { $missed } perdidos
+stats-no-delay = n/d (los valores no traen la hora del escritor)
+stats-no-traffic = Todavía no se ha escrito ni leído nada de la DHT
+stats-col-feature = función
+stats-col-writes = escrituras
+stats-col-written = escrito
+stats-col-reads = lecturas
+stats-col-read = leído
+stats-col-total = total
+
+## Contactos
+
+no-contacts = Todavía no hay contactos
+contact-record = registro { $code }
+contact-reachable = localizable
+contact-saved = Guardado { $name }
+contact-save-failed = No se pudo guardar { $name }: { $error }
+contact-removed = Borrado { $name }
+no-such-contact = No hay ningún contacto llamado { $name }
+contact-remove-failed = No se pudo borrar { $name }: { $error }
+contact-remove-usage = Uso: contact remove <name>
+contact-add-usage = Uso: contact add <name> [--key KEY] [--record REC] [--route BLOB]
+contact-needs-value = { $flag } necesita un valor
+contact-unknown-option = Opción desconocida '{ $option }' (prueba --key, --record o --route)
+contact-unknown-command = Orden de contactos desconocida '{ $command }' (add, list o remove)
+
+## Conflictos
+
+conflict-lost = Otra persona cambió la subclave { $subkey } mientras escribías, así que lo tuyo no entró:
+conflict-base = base
+conflict-yours = tuyo
+conflict-theirs = suyo
+conflict-merged = combinado
+conflict-merged-with-conflicts = combinado, con conflictos
+conflict-choices = Escribe m para escribir la combinación, y para escribir lo tuyo encima de lo suyo, t para quedarte con lo suyo, o e <texto> para escribir otra cosa
+conflict-kept-theirs = Se queda lo suyo en la subclave { $subkey }, no se ha escrito nada
+conflict-merge-conflicted = La combinación tiene conflictos; elige y o t, o escribe e <texto> con tu propia versión
+
+## Relé
+
+relay-usage = Uso: relay [on|off|join <contact>|leave <contact>]
+relay-nothing-to-relay = Este nodo no vigila el registro, así que no tiene nada que reenviar; haz `relay join` al nodo que sí lo vigila
+relay-on = Modo relé activado: los contactos que nos hagan `relay join` reciben cada cambio que veamos
+relay-off = Modo relé desactivado; se ha dejado de enviar a { $count } suscriptor(es)
+relay-no-such-contact = No hay ningún contacto llamado { $name } (mira `contact list`)
+relay-no-route = { $name } no nos ha enviado una ruta por la que llegar a él
+relay-no-own-route = No tenemos ruta privada por la que nos lleguen los cambios
+relay-unreachable = No se pudo llegar a { $name }: { $error }
+relay-asked-join = Pedido a { $name } que reenvíe los cambios del registro; aparecen como [relay { $name }]
+relay-asked-leave = Pedido a { $name } que deje de reenviarnos
+relay-on-no-joiners = Modo relé activado; todavía no se ha suscrito nadie
+relay-on-sending-to = Modo relé activado, enviando a: { $names }
+relay-status-off = Modo relé desactivado (`relay on` para reenviar nuestros cambios)
+relay-joined = Suscrito a: { $names }
+relay-not-joined = No estamos suscritos a ningún relé (`relay join <contact>`)
+relayed-watch-died = { $code }: el watch ha muerto; no llegará nada más hasta que se vuelva a poner
+relayed-value = subclave { $subkey } (seq { $seq }, { $writer }): { $value }
+relayed-also-changed = también cambiaron: { $subkeys }
+relay-join-stranger = [relay] '{ $name }' pidió suscribirse, pero no es un contacto; se ignora
+relay-join-while-off = [relay] { $name } pidió suscribirse, pero el modo relé está desactivado (`relay on`)
+relay-join-bad-route = [relay] { $name } pidió suscribirse con una ruta que no podemos usar: { $error }
+relay-join-no-route = [relay] { $name } pidió suscribirse sin una ruta por la que enviarle
+relay-peer-joined = [relay] { $name } se ha suscrito; desde ahora recibe cada cambio que veamos
+relay-leave-stranger = [relay] '{ $name }' pidió darse de baja, pero no es un contacto; se ignora
+relay-peer-left = [relay] { $name } se ha dado de baja
+relay-leave-not-joined = [relay] { $name } pidió darse de baja, pero no estaba suscrito
+relay-undecodable = [relay] ha llegado un mensaje de relé que no se puede descodificar
+relay-dropped = [relay] se quita a { $name }, cuya ruta ha dejado de responder: { $error }
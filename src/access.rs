@@ -0,0 +1,160 @@
+use veilid_core::*;
+
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::envelope::Envelope;
+use crate::progress;
+use crate::record::{start_tool_node, FreshWriters};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`record access`: who can do what with a record, shown side by side.
+//
+//	A fresh record is made with the configured shape (owner_subkeys for
+//	the owner, member_subkeys for one member), then opened three times in
+//	turn with a different default writer:
+//
+//	  owner     the owner keypair from create_dht_record()
+//	  member    the member's keypair, whose member id is in the schema
+//	  none      no writer at all, as the alt node opens a record it joins
+//
+//	Each time every subkey is read and written with no per-call writer, so
+//	what happens is down to the credentials open_dht_record() was given.
+//	Reads work for everyone; a write only lands on the subkeys the schema
+//	gives that keypair, and without one nothing can be written.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub read: bool,
+    pub write: bool,
+}
+
+pub struct Column {
+    pub credential: &'static str,
+    pub cells: Vec<Cell>,
+    // the first refusal Veilid gave, to show why
+    pub refusal: Option<(ValueSubkey, String)>,
+}
+
+fn cell_text(cell: Cell) -> &'static str {
+    match (cell.read, cell.write) {
+        (true, true) => "read+write",
+        (true, false) => "read",
+        (false, true) => "write",
+        (false, false) => "-",
+    }
+}
+
+// One row per subkey, labelled with who the schema gives it to.
+pub fn render(owners: &[&str], columns: &[Column]) -> String {
+    let mut lines = Vec::new();
+    let mut header = format!("{:<8} {:<10}", "subkey", "schema");
+    for c in columns {
+        header.push_str(&format!(" {:<12}", c.credential));
+    }
+    lines.push(header.trim_end().to_string());
+    for (subkey, owner) in owners.iter().enumerate() {
+        let mut row = format!("{:<8} {:<10}", subkey, owner);
+        for c in columns {
+            let text = c.cells.get(subkey).map(|&cell| cell_text(cell)).unwrap_or("?");
+            row.push_str(&format!(" {text:<12}"));
+        }
+        lines.push(row.trim_end().to_string());
+    }
+    for c in columns {
+        if let Some((subkey, why)) = &c.refusal {
+            lines.push(format!("{} writing subkey {subkey}: {why}", c.credential));
+        }
+    }
+    lines.join("\n")
+}
+
+pub async fn run(options: &Options, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let (veilid, rc) = start_tool_node(options, config).await?;
+    let writers = FreshWriters::for_shape(&veilid, config.owner_subkeys, &[config.member_subkeys])?;
+    let desc = progress::spin(
+        "Creating a record to compare on",
+        rc.create_dht_record(CRYPTO_KIND_VLD0, writers.schema.clone(), Some(writers.owner.clone())),
+    )
+    .await?;
+    let key = desc.key();
+    let max_subkey = writers.schema.max_subkey();
+    println!("Record {key}: subkeys 0..={max_subkey}");
+
+    let owners: Vec<&str> = (0..=max_subkey)
+        .map(|subkey| if subkey < ValueSubkey::from(config.owner_subkeys) { "owner" } else { "member" })
+        .collect();
+    let credentials = [
+        ("owner", Some(writers.owner.clone())),
+        ("member", Some(writers.members[0].0.clone())),
+        ("none", None),
+    ];
+
+    let mut columns = Vec::new();
+    for (credential, writer) in credentials {
+        // re-opening swaps the default writer in place
+        let _ = rc.open_dht_record(key.clone(), writer).await?;
+        let mut column = Column {
+            credential,
+            cells: Vec::new(),
+            refusal: None,
+        };
+        let bar = progress::subkeys(u64::from(max_subkey) + 1, &format!("Trying as {credential}"));
+        for subkey in 0..=max_subkey {
+            let probe = Envelope::text(&format!("written as {credential}")).encode();
+            let write = match rc.set_dht_value(key.clone(), subkey, probe, None).await {
+                Ok(_) => true,
+                Err(e) => {
+                    column.refusal.get_or_insert((subkey, e.to_string()));
+                    false
+                }
+            };
+            let read = rc.get_dht_value(key.clone(), subkey, true).await.is_ok();
+            column.cells.push(Cell { read, write });
+            bar.inc(1);
+        }
+        bar.finish_and_clear();
+        columns.push(column);
+    }
+
+    println!();
+    println!("{}", render(&owners, &columns));
+    println!();
+    println!("The record is left behind; `record snapshot {key}` shows whose write each subkey kept.");
+
+    let _ = rc.close_dht_record(key).await;
+    veilid.shutdown().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_lines_up_credentials_per_subkey() {
+        let rw = Cell { read: true, write: true };
+        let r = Cell { read: true, write: false };
+        let columns = [
+            Column {
+                credential: "owner",
+                cells: vec![rw, r],
+                refusal: Some((1, "not a writer".to_string())),
+            },
+            Column {
+                credential: "none",
+                cells: vec![r],
+                refusal: None,
+            },
+        ];
+        let table = render(&["owner", "member"], &columns);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "subkey   schema     owner        none");
+        assert_eq!(lines[1], "0        owner      read+write   read");
+        // a missing cell shows as unknown rather than shifting the row
+        assert_eq!(lines[2], "1        member     read         ?");
+        assert_eq!(lines[3], "owner writing subkey 1: not a writer");
+    }
+}
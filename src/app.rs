@@ -0,0 +1,2101 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::io::{self, Write};
+use veilid_core::*;
+
+use crate::{
+    access, audit, backup, bench, chaos, chat, cli, commands, config, conflict, control, diag,
+    discovery, eventlog, events, exit, expect, fields, flood, i18n, janitor, keybundle, keyfile,
+    load, locks, logs, mailbox, member, metadata, monitor, nicknames, node, ordering, payloads,
+    prefetch, preflight, prefs, profile, progress, protocol, publish, receipts, record, recovery,
+    relay, repl, route, scenario, schema, shortcode, soak, store, templates, transcript, trend,
+    tutorial, warm, watch_status, webhook, winservice,
+};
+use crate::audit::AuditLog;
+use crate::commands::Prompt;
+use crate::config::{AppConfig, RecordSchema};
+use crate::contacts::Contacts;
+use crate::dht::Dht;
+use crate::envelope::Envelope;
+use crate::guard::{CreatedRecords, WriteGuard};
+use crate::metadata::RecordMetadata;
+use crate::queue::{Limits, OpQueue};
+use crate::quota::Quota;
+use crate::feed::{Feed, FeedFilter};
+use crate::heatmap::Heatmap;
+use crate::i18n::t;
+use crate::input::{Command, Destination, Input, Inputs};
+use crate::keyfile::Capability;
+use crate::nicknames::Nicknames;
+use crate::ready::Milestone;
+use crate::record_manager::RecordManager;
+use crate::repl::{Repl, ReplLine};
+use crate::shortcode::ShortcodeBook;
+use crate::soak::SoakRole;
+use crate::stats::{Bandwidth, Feature, Latency, WatchStats};
+use crate::transcript::say;
+use crate::watch::{ChangedValue, WatchSet};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	1: In the Default node, a DHT is created & can be edited at will.
+//	2: The default node will write the nessasary keys to a text file
+//	3: In a seperate console, run the application, but as Alternate
+//	4: This will read the text file, and allow the second node access to the DHT
+//	5: A few examples of DHT monotoring will be presented
+//
+//	The Two seperate nodes are run inside thier own functions:
+//	run_default_node() and run_alt_node()
+//      These functions can be found below the run function
+//	(main.rs just calls run(), and turns what goes wrong into an exit code)
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// How many of a change's other subkeys the alt prompt reads to show as they arrive.
+const LIVE_READS: usize = 8;
+
+// -------------------------------------------------------------------------
+// Run Function (Where the program starts, called from main.rs)
+// -------------------------------------------------------------------------
+
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+
+// Anything passed on the command line is handled here, otherwise we fall through to the menu.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::wants_help(&args) {
+        println!("{}", cli::usage());
+        return Ok(());
+    }
+    let options = match cli::parse(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{msg}");
+            std::process::exit(exit::Kind::Usage.code());
+        }
+    };
+    // before anything is printed in the language picked (see i18n.rs)
+    i18n::init(options.lang.as_deref());
+
+// Settings come from defaults, then config.json, then VEILID_EXAMPLE_* env vars, then flags.
+    let config = match AppConfig::load(&options) {
+        Ok(config) => config,
+        Err(msg) => {
+            eprintln!("{msg}");
+            std::process::exit(exit::Kind::Config.code());
+        }
+    };
+
+    match options.command {
+        cli::Command::Interactive => {}
+        cli::Command::AuditShow { role } => {
+            return audit::show(&config.data_dir()?, role.as_deref());
+        }
+        cli::Command::Soak { hours, role } => {
+            return soak::run(Some(hours), role, &options, &config).await;
+        }
+        cli::Command::Daemon { role } => {
+            if options.service {
+                // blocks this thread until Windows stops the service
+                return winservice::run();
+            }
+            return soak::run(None, role, &options, &config).await;
+        }
+        cli::Command::Control { request, role } => {
+            return control::send(request, role, &config).await;
+        }
+        cli::Command::ConfigValidate => {
+            if !config::print_validation(&config) {
+                std::process::exit(exit::Kind::Config.code());
+            }
+            return Ok(());
+        }
+        cli::Command::ConfigShow { effective } => {
+            return config::show(&config, effective);
+        }
+        cli::Command::RecordClone { ref source } => {
+            return record::clone(source, &options, &config).await;
+        }
+        cli::Command::RecordNew { ref template } => {
+            return templates::create(template, &options, &config).await;
+        }
+        cli::Command::RecordBackup { ref file, ref passphrase_file } => {
+            return backup::backup(file, passphrase_file.as_deref(), &options, &config).await;
+        }
+        cli::Command::RecordRestore { ref file, ref passphrase_file } => {
+            return backup::restore(file, passphrase_file.as_deref(), &options, &config).await;
+        }
+        cli::Command::RecordLoad { ref file, rate } => {
+            return load::run(file, rate, &options, &config).await;
+        }
+        cli::Command::RecordSnapshot { ref record } => {
+            return record::snapshot(record, &options, &config).await;
+        }
+        cli::Command::RecordDiff { ref a, ref b } => {
+            return record::diff(a, b, &config);
+        }
+        cli::Command::RecordAccess => {
+            return access::run(&options, &config).await;
+        }
+        cli::Command::RecordBench { rounds } => {
+            return bench::run(rounds, &options, &config).await;
+        }
+        cli::Command::Discover => {
+            return record::discover(&options, &config).await;
+        }
+        cli::Command::KeysPasswd => {
+            return store::passwd(&config).await;
+        }
+        cli::Command::KeysShow { ref namespace } => {
+            return store::show(namespace.as_deref(), &config).await;
+        }
+        cli::Command::KeysRotate { ref namespace } => {
+            return store::rotate(namespace.as_deref(), &config).await;
+        }
+        cli::Command::ScenarioRun { ref file } => {
+            return scenario::run(file, &options, &config).await;
+        }
+        cli::Command::Monitor { ref record, ref page } => {
+            return monitor::run(record, page.as_ref(), &options, &config).await;
+        }
+        cli::Command::SchemaGrow(ref args) => {
+            return schema::grow(args, &options, &config).await;
+        }
+        cli::Command::RecordEvents { ref file, ref record } => {
+            return eventlog::record(file, record.as_deref(), &options, &config).await;
+        }
+        cli::Command::ReplayEvents { ref file } => {
+            return eventlog::replay(file);
+        }
+    }
+
+// No point starting Veilid with settings we already know are broken.
+    let problems = config.validate();
+    if !problems.is_empty() {
+        eprintln!("{}", t!("config-problems"));
+        for p in &problems {
+            eprintln!("  - {p}");
+        }
+        std::process::exit(exit::Kind::Config.code());
+    }
+
+    if options.dry_run {
+        println!("{}\n", t!("dry-run-banner"));
+    }
+    if let Some(chaos) = &options.chaos {
+        if chaos.is_chaotic() {
+            println!("{}\n", t!("chaos-banner", chaos = format!("{chaos:?}")));
+        }
+        if let Some(latency) = chaos.injected {
+            println!(
+                "{}\n",
+                t!("latency-banner", min = format!("{:?}", latency.min), max = format!("{:?}", latency.max))
+            );
+        }
+    }
+
+// Everything typed at the prompts, and what they print back, can go in a transcript too.
+    if let Some(path) = &options.transcript {
+        transcript::start(path)?;
+    }
+
+// A newcomer's first run: the steps below, explained and checked one at a time (see tutorial.rs).
+    if options.tutorial {
+        return tutorial::run(&config).await;
+    }
+
+// This First Section is just A selection of what node to launch (--mode skips the question).
+    let role = match options.mode {
+        Some(role) => role,
+        None => loop {
+            println!("{}", t!("menu"));
+            io::stdout().flush().unwrap();
+
+            let mut input = String::new();
+            io::stdin().read_line(&mut input).unwrap();
+
+            match input.trim() {
+                "1" => break SoakRole::Default,
+                "2" => break SoakRole::Alt,
+                "3" => break SoakRole::Member,
+                _ => println!("{}\n", t!("menu-invalid")),
+            }
+        },
+    };
+
+// Each node takes its namespace, storage and key file from here (see node.rs).
+    let node_options = node::NodeOptions::new(&options, &config, role)?;
+    match role {
+        SoakRole::Default => {
+            println!("{}\n", t!("starting-default"));
+            run_default_node(&node_options).await?;
+        }
+        SoakRole::Alt => {
+            println!("{}\n", t!("starting-alt"));
+            run_alt_node(&node_options).await?;
+        }
+        SoakRole::Member => {
+            println!("{}\n", t!("starting-member"));
+            member::run(&node_options).await?;
+        }
+    }
+
+    Ok(())
+}
+
+
+
+// -------------------------------------------------------------------------
+// Update callback (this gets updated every time something updates/changes in the velid node)
+// -------------------------------------------------------------------------
+
+fn u_c(update: VeilidUpdate, watch_stats: Option<&WatchStats>, published_route: Option<&route::PublishedRoute>) {
+    match update {
+        VeilidUpdate::Log(_veilid_log) => {}
+        VeilidUpdate::AppMessage(msg) => {
+            let text = String::from_utf8_lossy(msg.message());
+            println!("AppMessage: {text}");
+        }
+        VeilidUpdate::AppCall(_veilid_app_call) => {}
+        // readiness is picked out in the callback itself, before the update is queued
+        VeilidUpdate::Attachment(_) => {}
+        VeilidUpdate::Network(_veilid_state_network) => {}
+        VeilidUpdate::Config(_veilid_state_config) => {println!("Config")}
+        VeilidUpdate::RouteChange(veilid_route_change) => {
+            println!("{veilid_route_change:?}");
+            // the route in our record has died; the node makes another (see route.rs)
+            if let Some(published) = published_route {
+                published.route_changed(&veilid_route_change);
+            }
+        }
+        VeilidUpdate::ValueChange(veilid_value_change) => {
+            // count it towards the watch statistics (`stats watch`)
+            if let Some(stats) = watch_stats {
+                // the envelope says when the writer sent it, so we can time the delivery
+                let written_ms = veilid_value_change
+                    .value
+                    .as_ref()
+                    .and_then(|v| Envelope::decode(v.data()).ok())
+                    .and_then(|env| env.written_ms);
+                stats.value_changed(&veilid_value_change, written_ms.map(u128::from));
+            }
+            }
+        VeilidUpdate::Shutdown => {println!("ShutDown")}
+    }
+
+}
+
+
+// A changed value as the alt prompt prints it as it arrives.
+fn changed_line(tag: &str, value: &ChangedValue, registry: &payloads::Registry, names: &Nicknames) -> String {
+    let shown = match &value.envelope {
+        Ok(env) => registry.show(value.subkey, env),
+        Err(_) => value.display(),
+    };
+    t!(
+        "value-changed",
+        tag = tag,
+        subkey = value.subkey,
+        seq = value.seq.to_string(),
+        writer = names.label(&value.writer),
+        value = shown
+    )
+}
+
+// A profile card, log, chat or relay message (see profile.rs, logs.rs, chat.rs, relay.rs), which the nodes handle themselves instead of printing it.
+fn peer_message(update: &VeilidUpdate) -> Option<Vec<u8>> {
+    match update {
+        VeilidUpdate::AppMessage(msg)
+            if profile::is_card_message(msg.message())
+                || logs::is_log_message(msg.message())
+                || chat::is_chat_message(msg.message())
+                || relay::is_relay_message(msg.message()) =>
+        {
+            Some(msg.message().to_vec())
+        }
+        _ => None,
+    }
+}
+
+
+// True on the update that brings the node back to fully attached (not on the ones after).
+fn came_online(update: &VeilidUpdate, was_online: &AtomicBool) -> bool {
+    match update {
+        VeilidUpdate::Attachment(att) => {
+            !was_online.swap(att.public_internet_ready, Ordering::Relaxed) && att.public_internet_ready
+        }
+        _ => false,
+    }
+}
+
+
+
+// -------------------------------------------------------------------------
+// Default Node Function (if the user selected Number 1 in run, or --mode default)
+// -------------------------------------------------------------------------
+
+async fn run_default_node(node_options: &node::NodeOptions<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    let (options, config) = (node_options.options, node_options.config);
+
+    let data_dir = node_options.data_dir.clone();
+    // checks the machine before Veilid starts; the marker goes when this function returns
+    let running = preflight::run(config, &node_options.storage_dir, "default", &node_options.namespace)?;
+
+
+// Profile cards and log messages other nodes send us are handled in the loop below, not printed.
+    let (peer_tx, peer_rx) = flume::unbounded::<Vec<u8>>();
+
+// Update Callback, this is our live feed of what the node is doing/incoming messages/etc.
+// It only sorts updates into bounded queues (see events.rs); a task prints them from there.
+    let events = Arc::new(events::EventQueues::new());
+    // the private route we keep in the record, and word when it dies (see route.rs)
+    let (published_route, route_died) = route::PublishedRoute::new();
+    {
+        let published_route = published_route.clone();
+        events::spawn_printer(events.clone(), move |update| u_c(update, None, Some(&published_route)));
+    }
+
+// Here we start the veilid node (we give this one a diffrent Namespace than the Alt. node),
+// attach to the network, and wait until we're attached enough (see node.rs and ready.rs).
+    let node = {
+        let events = events.clone();
+        node::VeilidNode::start_attached(config, &node_options.storage_dir, &node_options.namespace, move |update| {
+            if let Some(msg) = peer_message(&update) {
+                let _ = peer_tx.send(msg);
+                return;
+            }
+            events.push(update);
+        })
+        .await?
+    };
+    let veilid = node.api().clone();
+
+    if let Some(every) = options.chaos.as_ref().and_then(|c| c.reattach_every) {
+        chaos::spawn_reattach_cycles(veilid.clone(), every);
+    }
+
+
+// ------------- Node is Now Setup And attached, from here on is DHT stuff! -----------------------
+
+
+// Every DHT call goes through our Dht wrapper, which also writes it to the audit log.
+    let audit = Arc::new(AuditLog::open(&data_dir.join(audit::log_file_name("default")))?);
+    println!("{}", t!("auditing-to", path = audit.path().to_string_lossy()));
+    let routing = node.routing_context().clone();
+    let bandwidth = Arc::new(Bandwidth::new());
+    let latency = Arc::new(Latency::new());
+    // the prompt's calls go ahead of the janitor's and the mail poll's (see queue.rs)
+    let queue = Arc::new(OpQueue::new(Limits::default()));
+    // how full the record is getting, per writer (see quota.rs)
+    let quota = Arc::new(Quota::new());
+    // which subkeys are busy, for `stats heatmap` (see heatmap.rs)
+    let heatmap = Arc::new(Heatmap::new());
+    let rc = Dht::new(routing.get(), Some(audit), options.dry_run, options.chaos.clone())
+        .with_bandwidth(bandwidth.clone())
+        .with_latency(latency.clone())
+        .with_queue(queue.clone())
+        .with_quota(quota.clone())
+        .with_heatmap(heatmap.clone())
+        .with_try_again_retry(config.early_retry());
+    let mail_rc = rc.for_feature(Feature::Mail);
+
+// After a crash we pick the record we made back up, with the keys we made it with,
+// rather than making another one (see recovery.rs).
+    let (mut journal, resumed) = recovery::StateJournal::start(&data_dir, "default", &running);
+    let resumed = match resumed.as_ref().and_then(|state| state.record_key()) {
+        Some(key) if !rc.is_dry_run() => match recovery::load_writers(&veilid, &key).await {
+            Ok(Some(keys)) => keys.parse().map(|writers| (key, writers)),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some((key, _)) = &resumed {
+        println!("{}", t!("resumed-record", key = key.to_string()));
+    }
+
+// Create a keypair using VLD0 (only option in version 5.x, although VLD1 is in the works)
+    let owner_kp = match &resumed {
+        Some((_, writers)) => writers.member.clone(),
+        None => Crypto::generate_keypair(CRYPTO_KIND_VLD0)?,
+    };
+
+// We split the keypair into it's public and secret constituents. (the secret opens our mail)
+    let (owner_public, owner_secret) = owner_kp.clone().into_split();
+
+// we generate an ID to go with the key we just generated
+    let owner_id = veilid.generate_member_id(&owner_public)?;
+
+// veilid wants a bare ID for parts, so we convert the normal ID into a bare ID (no Idea what the diffrence is)
+    let bare_owner_id = owner_id.into_value();
+
+// A private route for other nodes to reach us on, and our profile card with it in.
+    let route = if rc.is_dry_run() {
+        None
+    } else {
+        match veilid.new_private_route().await {
+            Ok(route) => Some(route),
+            Err(e) => {
+                println!("{}", t!("no-private-route", error = e.to_string()));
+                None
+            }
+        }
+    };
+    let my_card = profile::ProfileCard::new(
+        &owner_kp,
+        &config.profile_name("default"),
+        &["write", "flood"],
+        route.as_ref().map(|r| r.blob.as_slice()),
+    )?;
+    let mut contacts = Contacts::open(&veilid, &data_dir).await?;
+    // how this prompt behaves, as it was left last time (see prefs.rs)
+    let mut prefs = prefs::PrefStore::open(&veilid).await?;
+    // call times by day, for `stats trend` (see trend.rs)
+    let mut metrics = trend::MetricsStore::open(&veilid, latency.clone()).await?;
+    // our audit log, for contacts who ask to follow it (see logs.rs)
+    let mut log_streams = logs::LogStreams::new(data_dir.join(audit::log_file_name("default")), &my_card, veilid.clone(), routing.get());
+    // `:msg` to whichever node last sent us its card (see chat.rs)
+    let mut chat = chat::Chat::new(veilid.clone(), routing.get());
+    // the record's changes, from a node in relay mode we've joined (see relay.rs)
+    let mut relay = relay::Relay::new(&my_card, veilid.clone(), routing.get(), false);
+
+// What kind of value each subkey takes, checked before anything is written (see payloads.rs).
+    let registry = payloads::Registry::from_config(config)?;
+
+// set up what that setup that ID will get set up with in the DHT we're creating.
+    let owner_opts = SetDHTValueOptions {
+        writer: Some(owner_kp.clone()),
+        allow_offline: None,
+    };
+    // advisory locks on the subkeys both nodes write (see locks.rs)
+    let locks = locks::Locks::new(config, &my_card, Some(owner_kp.clone()));
+
+// The mailbox's inbox subkeys get a member of their own, whose keypair we hand
+// out in the metadata block so anyone who joins can leave us mail (see mailbox.rs).
+    let drop_kp = match resumed.as_ref().and_then(|(_, writers)| writers.drop.clone()) {
+        Some(kp) => kp,
+        None => Crypto::generate_keypair(CRYPTO_KIND_VLD0)?,
+    };
+    let mut members = vec![DHTSchemaSMPLMember {
+        m_key: bare_owner_id.clone(),
+        m_cnt: config.member_subkeys,
+    }];
+    let mailbox_info = config.inbox_range().map(|(first, last)| mailbox::MailboxInfo {
+        drop_writer: drop_kp.to_string(),
+        recipient: owner_public.to_string(),
+        first_subkey: first,
+        last_subkey: last,
+        receipts_subkey: (u32::from(config.owner_subkeys) > receipts::RECEIPTS_SUBKEY).then_some(receipts::RECEIPTS_SUBKEY),
+    });
+    if mailbox_info.is_some() {
+        members.push(DHTSchemaSMPLMember {
+            m_key: veilid.generate_member_id(&drop_kp.key())?.into_value(),
+            m_cnt: config.inbox_subkeys,
+        });
+    }
+    let mut inbox = mailbox::Inbox::new();
+// A member node that has left its key in the data folder gets a member of its own,
+// after the mailbox's, and writes there with its own keypair (see member.rs).
+    let member_node = member::announced_key(&data_dir)?;
+    if let Some(member_key) = &member_node {
+        members.push(DHTSchemaSMPLMember {
+            m_key: veilid.generate_member_id(member_key)?.into_value(),
+            m_cnt: config.member_subkeys,
+        });
+    }
+
+// set up the schema (what users have access, how many keys, etc). With --schema dflt:<count>
+// it's a DFLT one instead: no members, every subkey the owner's, and the owner is our key.
+    let (schema, record_owner_choice) = match config.record_schema()? {
+        RecordSchema::Smpl => (DHTSchema::smpl(config.owner_subkeys, members)?, None),
+        RecordSchema::Dflt(count) => (DHTSchema::dflt(count)?, Some(owner_kp.clone())),
+    };
+
+// just a little check to make sure what we've done checks out so far.
+    schema.validate()?;
+
+
+// A resumed record keeps the schema it was made with, whatever the config says now
+// (or the grown one's, if `schema grow` has moved it on since).
+// The record manager keeps it open, with the record owner as its writer, until we shut down.
+    let records = RecordManager::new(rc.clone());
+    let reopened = match &resumed {
+        Some((key, writers)) => match progress::spin(
+            "Re-opening the DHT record",
+            records.open_at_startup(key.clone(), Some(writers.record_owner.clone())),
+        )
+        .await
+        {
+            Ok(handle) => Some(handle),
+            Err(e) => {
+                println!("{}", t!("reopen-failed", key = key.to_string(), error = e.to_string()));
+                None
+            }
+        },
+        None => None,
+    };
+
+// In dry-run mode we only work out what the record key would be, nothing is created.
+    let (record_key, record_owner, record_owner_kp, schema, record) = if rc.is_dry_run() {
+        let plan_owner = match &record_owner_choice {
+            Some(kp) => kp.clone(),
+            None => Crypto::generate_keypair(CRYPTO_KIND_VLD0)?,
+        };
+        let plan_owner_public = plan_owner.key();
+        (rc.plan_create_dht_record(schema.clone(), plan_owner).await?, plan_owner_public, None, schema, None)
+    } else if let Some(record) = reopened {
+        let record_desc = record.descriptor().clone();
+        (record_desc.key(), record_desc.owner(), record_desc.owner_keypair(), record_desc.schema(), Some(record))
+    } else {
+        let record_desc = progress::spin(
+            "Creating the DHT record",
+            rc.for_setup().create_dht_record(CRYPTO_KIND_VLD0, schema.clone(), record_owner_choice),
+        )
+        .await?;
+        // the keys go in the table store, so a crash doesn't leave the record unwritable
+        if let Some(record_owner) = record_desc.owner_keypair() {
+            let keys = recovery::WriterKeys::new(&record_owner, &owner_kp, mailbox_info.as_ref().map(|_| &drop_kp));
+            if let Err(e) = recovery::save_writers(&veilid, &record_desc.key(), &keys).await {
+                println!("{}", t!("recovery-keys-failed", error = e.to_string()));
+            }
+        }
+        let record = records.adopt(record_desc.clone(), record_desc.owner_keypair()).await;
+        (record_desc.key(), record_desc.owner(), record_desc.owner_keypair(), schema, Some(record))
+    };
+    if !rc.is_dry_run() {
+        journal.record_opened(&record_key, None);
+        node.ready().reach(Milestone::RecordOpened);
+    }
+
+// Subkey 0 says what the record is and who writes where, for whoever joins (see metadata.rs).
+// It's an owner subkey, so it's written as the record owner (the default writer).
+    if config.owner_subkeys > 0 {
+        let mut roster = vec![
+            metadata::RosterEntry {
+                name: "record-owner".to_string(),
+                key: record_owner.to_string(),
+                first_subkey: 0,
+                last_subkey: config.first_member_subkey() - 1,
+                fields: Default::default(),
+            },
+            metadata::RosterEntry {
+                name: "default-node".to_string(),
+                key: owner_public.to_string(),
+                first_subkey: config.first_member_subkey(),
+                last_subkey: config.total_subkeys() - 1,
+                fields: config.field_subkeys(),
+            },
+        ];
+        if let Some(info) = &mailbox_info {
+            roster.push(metadata::RosterEntry {
+                name: "inbox".to_string(),
+                key: drop_kp.key().to_string(),
+                first_subkey: info.first_subkey,
+                last_subkey: info.last_subkey,
+                fields: Default::default(),
+            });
+        }
+        // (a resumed record, or a DFLT one, may have no room for it)
+        if let Some(member_key) = &member_node {
+            if let Some(&(first, last)) = crate::schema::writable_subkeys(&schema, &record_owner, member_key).first() {
+                println!("{}", t!("member-node-added", key = member_key.to_string(), first = first, last = last));
+                roster.push(metadata::RosterEntry {
+                    name: "member-node".to_string(),
+                    key: member_key.to_string(),
+                    first_subkey: first,
+                    last_subkey: last,
+                    // its plain writes go to its first subkey
+                    fields: fields::layout(&config.shared_fields, first, last, Some(first)),
+                });
+            }
+        }
+        let mut meta = RecordMetadata::new(&config.record_title, &schema, roster);
+        meta.owner_card = Some(my_card.clone());
+        meta.mailbox = mailbox_info.clone();
+        meta.payload_types = config.payload_types.clone();
+        if let Err(e) = rc
+            .for_feature(Feature::Metadata)
+            .set_dht_value(record_key.clone(), metadata::METADATA_SUBKEY, meta.encode(), None)
+            .await
+        {
+            println!("{}", t!("metadata-write-failed", error = e.to_string()));
+        }
+    }
+
+// And which version of this program's protocol it's in, so a build that doesn't speak it can tell (see protocol.rs).
+    if let Some(protocol_subkey) = protocol::subkey(&schema) {
+        let ours = protocol::ProtocolValue::ours(!config.value_codecs.is_empty());
+        if let Err(e) = rc
+            .for_feature(Feature::Metadata)
+            .set_dht_value(record_key.clone(), protocol_subkey, ours.encode(), None)
+            .await
+        {
+            println!("{}", t!("protocol-write-failed", subkey = protocol_subkey, error = e.to_string()));
+        }
+    }
+
+// And a private route to reach us on, in a subkey of its own, made again whenever it dies (see route.rs).
+    let route_subkey = route::subkey(&schema).filter(|_| !rc.is_dry_run());
+    if let Some(subkey) = route_subkey {
+        match published_route.publish(&veilid, &rc.for_feature(Feature::Metadata), &record_key, subkey, &my_card.nickname).await {
+            Ok(_) => println!("{}", t!("route-published", subkey = subkey)),
+            Err(e) => println!("{}", t!("route-publish-failed", subkey = subkey, error = e.to_string())),
+        }
+    }
+
+// Let the other nodes know who is behind this key when they read what we wrote.
+    Nicknames::load(&data_dir)?.set(&owner_public, "default-node")?;
+
+    println!("OwnerPublic = {:?}", owner_public);
+    println!("owner_kp = {:?}", owner_kp);
+    println!("RecordKey = {:?}", record_key);
+    
+
+// --------------------------------------------------
+// Write keys to a file in the data folder
+// --------------------------------------------------
+
+    println!("{}", t!("keyfile-loaded"));
+
+    let key_file_path = &node_options.key_file;
+
+// A dry run doesn't create a real record, so don't clobber the key file from a real run.
+    if rc.is_dry_run() {
+        println!("{}", t!("dry-run-keyfile", path = key_file_path.to_string_lossy()));
+    } else {
+// The shortcode is a few words the alt node can be given instead of the whole key.
+        let code = ShortcodeBook::load(&data_dir)?.remember(&record_key)?;
+// Ours, so the alt prompt in this data folder writes to it without asking (see guard.rs).
+        CreatedRecords::load(&data_dir)?.add(&record_key)?;
+// share_grant decides which of our keys go in the file with it (see keyfile.rs).
+        let mut grant = keyfile::Grant::default();
+        let granted = config.share_grant()?;
+        if granted >= Capability::WriteOwnSubkeys {
+            grant.writer = Some(owner_kp.clone());
+            grant.writer_subkeys = Some((config.first_member_subkey(), config.total_subkeys() - 1));
+        }
+        if granted == Capability::Admin {
+            grant.owner = record_owner_kp.clone();
+        }
+        grant.claimed = grant.capabilities();
+        keybundle::save_file(key_file_path, &keyfile::KeyFile {
+            record_key: record_key.clone(),
+            shortcode: Some(code.clone()),
+            alt: config.join_preset(),
+            grant: grant.clone(),
+            expect: crate::schema::expectation(&schema, &record_owner, &grant),
+        }, Some(&schema))?;
+
+        println!("{}", t!("keyfile-written", path = key_file_path.to_string_lossy()));
+        println!("{}", t!("keyfile-grants", grant = grant.describe()));
+        if granted > Capability::Read {
+            println!("{}", t!("keyfile-holds-keys"));
+        }
+        // the fingerprint lets the joiner check it got this record and not a look-alike
+        let fingerprint = crate::schema::record_fingerprint(&schema, &record_owner);
+        let (code, key) = (
+            shortcode::share_string(&code, &fingerprint),
+            shortcode::share_string(&record_key.to_string(), &fingerprint),
+        );
+        println!("{}", t!("share-codes", code = code, key = key));
+    }
+
+// Opt-in: put the record in the public app index so `discover` can find it (see discovery.rs).
+    if let Some(label) = &config.announce_label {
+        if rc.is_dry_run() {
+            println!("{}", t!("dry-run-announce", label = label.as_str()));
+        } else {
+            let index_rc = rc.for_feature(Feature::Discovery);
+            match progress::spin("Announcing the record", discovery::announce(&index_rc, &record_key, label)).await {
+                Ok(slot) => {
+                    println!("{}", t!("announced", label = label.as_str(), slot = slot));
+                    discovery::spawn_reannounce(index_rc, record_key.clone(), label.clone());
+                }
+                Err(e) => println!("{}", t!("announce-failed", error = e.to_string())),
+            }
+        }
+    }
+
+
+// Up-arrow history, Ctrl+R search and line editing (see repl.rs)
+let repl = Repl::start(repl::history_file(&data_dir, "default"), "default> ", commands::names(Prompt::Default), false)?;
+repl.set_max_subkey(schema.max_subkey());
+repl.set_vi(prefs.get().edit_mode == prefs::EditMode::Vi);
+// how the DHT calls are doing, in front of the prompt
+let status = latency.clone();
+repl.set_status(move || status.summary());
+
+// Which subkey we're going to write to: one the schema gives our member key,
+// the default-subkey option or write_subkey if either is set (and is one of
+// them), otherwise the first past the owner subkeys (on a DFLT record our key
+// may write the metadata block too, so not simply the first it may write).
+// Text never goes to the owner subkeys: the metadata block, receipts,
+// protocol version and route live there.
+let text_subkey = |wanted: ValueSubkey| -> Result<ValueSubkey, String> {
+    if wanted < config.first_member_subkey() {
+        return Err(t!("subkey-reserved", subkey = wanted, first = config.first_member_subkey()));
+    }
+    crate::schema::pick_writer(&schema, &record_owner, &[&owner_kp], Some(wanted)).map(|(picked, _)| picked)
+};
+let preferred = prefs.get().default_subkey.and_then(|wanted| text_subkey(wanted).ok());
+let mut subkey: u32 = match preferred.map_or_else(|| text_subkey(config.planned_write_subkey()), Ok) {
+    Ok(subkey) => subkey,
+    Err(e) => return Err(format!("can't write to record {record_key}: {e}").into()),
+};
+
+// With a TTL set, what we write expires, and the janitor tombstones it once it has.
+if let Some(ttl) = config.value_ttl() {
+    println!("{}", t!("values-expire", secs = ttl.as_secs()));
+    if !rc.is_dry_run() {
+        janitor::spawn(rc.for_feature(Feature::Janitor), record_key.clone(), vec![subkey], Some(owner_kp.clone()));
+    }
+}
+
+// New mail is taken out of the inbox (and receipted) in the background; 'inbox' shows it.
+let mut mail_check = tokio::time::interval(mailbox::POLL_EVERY);
+// a background check that found nothing doesn't need the instructions again
+let mut quiet = false;
+let mut metrics_save = tokio::time::interval_at(tokio::time::Instant::now() + trend::SAVE_EVERY, trend::SAVE_EVERY);
+// `wait-for` polls in its own task and reports back here (see expect.rs)
+let waits = expect::Waits::default();
+
+// what we've written so far, numbered so readers can tell if they missed any (see ordering.rs);
+// the numbering carries on from the last run
+let mut sent_count: u64 = recovery::load_sent(&veilid).await?;
+// a write that lost to a newer value, for the m/y/t/e answer straight after (see conflict.rs)
+let mut pending_conflict: Option<conflict::Conflict> = None;
+// with `set-option confirm on`, the text waiting for a y before it's written
+let mut pending_write: Option<String> = None;
+// `publish` schedules, written with our member key or the record owner's (see publish.rs)
+let mut publisher = publish::Publisher::new(
+    rc.clone(),
+    record_key.clone(),
+    schema.clone(),
+    record_owner.clone(),
+    std::iter::once(owner_kp.clone()).chain(record_owner_kp.clone()).collect(),
+    registry.clone(),
+    &my_card.nickname,
+);
+
+// commands come through here from now on (see input.rs)
+let mut inputs = Inputs::new(repl);
+
+loop {
+    if !std::mem::take(&mut quiet) {
+        say!();
+        say!("{}", t!("default-intro"));
+        say!();
+    }
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            say!("\n{}", t!("ctrl-c"));
+            break;
+        }
+
+        Ok(msg) = peer_rx.recv_async() => {
+            if logs::is_log_message(&msg) {
+                say!("{}", log_streams.receive(&msg, &contacts).await);
+            } else if chat::is_chat_message(&msg) {
+                say!("{}", chat.receive(&msg));
+            } else if relay::is_relay_message(&msg) {
+                say!("{}", relay.receive(&msg, &contacts));
+            } else {
+                if let Some(card) = profile::CardMessage::decode(&msg) {
+                    chat.set_peer(card.card());
+                }
+                say!("{}", profile::receive(&msg, &my_card, &mut contacts, &veilid, &routing.get()).await);
+            }
+            continue;
+        }
+
+        _ = metrics_save.tick() => {
+            if let Err(e) = metrics.save().await {
+                say!("{}", t!("metrics-save-failed", error = e.to_string()));
+            }
+            quiet = true;
+            continue;
+        }
+
+        Ok(outcome) = waits.results().recv_async() => {
+            say!("{outcome}");
+            continue;
+        }
+
+        Ok(()) = route_died.recv_async(), if route_subkey.is_some() => {
+            let Some(subkey) = route_subkey else { continue };
+            match published_route.publish(&veilid, &rc.for_feature(Feature::Metadata), &record_key, subkey, &my_card.nickname).await {
+                Ok(_) => say!("{}", t!("route-renewed", subkey = subkey)),
+                Err(e) => say!("{}", t!("route-publish-failed", subkey = subkey, error = e.to_string())),
+            }
+            continue;
+        }
+
+        _ = mail_check.tick(), if mailbox_info.is_some() && !rc.is_dry_run() => {
+            let Some(info) = &mailbox_info else { continue };
+            let (received, problems) = inbox.fetch(&mail_rc, &veilid, &record_key, info, &owner_secret).await;
+            for p in &problems {
+                say!("{p}");
+            }
+            if received > 0 {
+                say!("{}", t!("new-letters", tag = prefs.get().tag("mail"), count = received));
+            }
+            quiet = received == 0 && problems.is_empty();
+            continue;
+        }
+
+        input = inputs.next() => {
+            let command = match input {
+                Input::Command(command) => command,
+                Input::Interrupted => {
+                    say!("{}", t!("ctrl-c"));
+                    break;
+                }
+                // EOF (unlikely in a terminal, but safe)
+                Input::Closed => break,
+            };
+
+            // only the line straight after a conflict (or a held write) answers it
+            let unanswered = pending_conflict.take();
+            let held = pending_write.take();
+
+            let text = match command {
+                Command::Other(text) => text,
+                Command::Help(topic) => {
+                    say!("{}", commands::help(Prompt::Default, &topic));
+                    continue;
+                }
+                Command::Stats(what) => {
+                    match what.as_str() {
+                        "events" => say!("{}", events.report()),
+                        "bandwidth" => say!("{}", bandwidth.report()),
+                        "queue" => say!("{}", queue.report()),
+                        "heatmap" => say!("{}", heatmap.report()),
+                        "trend" => say!("{}", metrics.report().await),
+                        _ => say!("{}", t!("no-such-stats", what = what.as_str())),
+                    }
+                    continue;
+                }
+                Command::Quota => {
+                    let names = Nicknames::load(&data_dir)?;
+                    say!("{}", quota.report(&record_key, |k| names.label(k)));
+                    continue;
+                }
+                Command::Field(args) => {
+                    // field <name> <text>: set our own copy of a shared field
+                    let (name, value) = args.split_once(' ').unwrap_or((args.as_str(), ""));
+                    let Some(field_subkey) = config.field_subkeys().get(name).copied() else {
+                        say!("{}", t!("not-a-field", name = name, fields = config.shared_fields.join(", ")));
+                        continue;
+                    };
+                    if let Err(e) = locks.may_write(&rc, &record_key, field_subkey).await {
+                        say!("{}", t!("not-set", error = e.to_string()));
+                        continue;
+                    }
+                    let value = match registry.encode(field_subkey, value.trim()) {
+                        Ok(value) => value,
+                        Err(e) => {
+                            say!("{}", t!("not-set", error = e.to_string()));
+                            continue;
+                        }
+                    };
+                    match rc
+                        .set_dht_value(record_key.clone(), field_subkey, value.encode(), Some(owner_opts.clone()))
+                        .await
+                    {
+                        Ok(_) => say!("{}", t!("field-set", name = name, subkey = field_subkey)),
+                        Err(e) => say!("{}", t!("field-set-failed", name = name, error = e.to_string())),
+                    }
+                    continue;
+                }
+                Command::Merge(name) => {
+                    match fields::merge_read(&rc, &record_key, &name).await {
+                        Ok(versions) => say!("{}", fields::render(&name, &versions)),
+                        Err(e) => say!("{e}"),
+                    }
+                    continue;
+                }
+                Command::DiagBundle => {
+                    let stats = format!(
+                        "latency: {}\n\n{}\n{}\n\n{}",
+                        latency.summary(),
+                        node.ready().describe(),
+                        bandwidth.report(),
+                        events.report()
+                    );
+                    match diag::bundle(&veilid, config, &data_dir, "default", &stats).await {
+                        Ok(path) => say!("{}", t!("bundle-written", path = path.to_string_lossy())),
+                        Err(e) => say!("{}", t!("bundle-failed", error = e.to_string())),
+                    }
+                    continue;
+                }
+                Command::Contact(args) => {
+                    say!("{}", contacts.command(&args, &ShortcodeBook::load(&data_dir)?).await);
+                    continue;
+                }
+                Command::Logs(args) => {
+                    say!("{}", log_streams.command(&args, &contacts, &my_card).await);
+                    continue;
+                }
+                Command::Relay(args) => {
+                    say!("{}", relay.command(&args, &contacts, &my_card).await);
+                    continue;
+                }
+                Command::Msg(text) => {
+                    say!("{}", chat.send(&text, &my_card).await);
+                    continue;
+                }
+                Command::Lock(args) => {
+                    say!("{}", locks.command(&rc, &record_key, &args, false).await);
+                    continue;
+                }
+                Command::Unlock(args) => {
+                    say!("{}", locks.command(&rc, &record_key, &args, true).await);
+                    continue;
+                }
+                Command::Publish(args) => {
+                    say!("{}", publisher.command(&args));
+                    continue;
+                }
+                Command::SetOption(args) => {
+                    say!("{}", prefs.command(&args).await);
+                    inputs.repl().set_vi(prefs.get().edit_mode == prefs::EditMode::Vi);
+                    let wanted = prefs.get().default_subkey.unwrap_or(config.planned_write_subkey());
+                    match text_subkey(wanted) {
+                        Ok(picked) if picked != subkey => {
+                            subkey = picked;
+                            say!("{}", t!("subkey-switched", subkey = subkey));
+                        }
+                        Ok(_) => {}
+                        Err(e) => say!("{}", t!("subkey-kept", subkey = subkey, error = e.to_string())),
+                    }
+                    continue;
+                }
+            };
+            let (text, confirmed) = match held {
+                Some(held) if text == "y" => (held, true),
+                Some(_) if text == "n" => {
+                    say!("{}", t!("not-written"));
+                    continue;
+                }
+                _ => (text, false),
+            };
+            let text = text.as_str();
+
+            if let (Some(conflict), Some(choice)) = (unanswered, conflict::Choice::parse(text)) {
+                let (outcome, again) = conflict.resolve(&rc, &record_key, choice).await;
+                say!("{outcome}");
+                pending_conflict = again;
+                continue;
+            }
+
+            if let Some(outcome) = expect::command(&rc, &record_key, text, &waits).await {
+                say!("{outcome}");
+                continue;
+            }
+
+            if text == "inbox" {
+                match &mailbox_info {
+                    Some(info) => say!("{}", inbox.check(&mail_rc, &veilid, &record_key, info, &owner_secret).await),
+                    None => say!("{}", t!("no-inbox-subkeys")),
+                }
+                continue;
+            }
+
+            // flood <subkey> <count>: see how fast one subkey can be written
+            if let Some(rest) = text.strip_prefix("flood ") {
+                let mut parts = rest.split_whitespace().map(str::parse::<u32>);
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(Ok(flood_subkey)), Some(Ok(count)), None) if count > 0 => {
+                        // owner subkeys are written as the record owner, the rest as our member key
+                        let writer = (flood_subkey >= config.first_member_subkey()).then(|| owner_kp.clone());
+                        say!("{}", t!("flooding", subkey = flood_subkey, count = count));
+                        say!("{}", flood::run(&rc.for_feature(Feature::Flood), &record_key, flood_subkey, count, writer).await);
+                    }
+                    _ => say!("{}", t!("flood-usage")),
+                }
+                continue;
+            }
+
+            // `:sub N` moves plain text to subkey N; `N:text` sends one line to
+            // subkey N and `:all text` to every subkey our member key may write,
+            // all of them past the owner subkeys (which it can reach on a DFLT record)
+            let (targets, line) = match Destination::parse(text) {
+                Err(e) => {
+                    say!("{}", t!("not-written-because", error = e));
+                    continue;
+                }
+                Ok(Destination::Switch(wanted)) => {
+                    match text_subkey(wanted) {
+                        Ok(picked) => {
+                            subkey = picked;
+                            say!("{}", t!("subkey-switched", subkey = subkey));
+                        }
+                        Err(e) => say!("{}", t!("subkey-kept", subkey = subkey, error = e)),
+                    }
+                    continue;
+                }
+                Ok(Destination::Current(line)) => (vec![subkey], line),
+                Ok(Destination::One(wanted, line)) => {
+                    match text_subkey(wanted) {
+                        Ok(picked) => (vec![picked], line),
+                        Err(e) => {
+                            say!("{}", t!("not-written-because", error = e));
+                            continue;
+                        }
+                    }
+                }
+                Ok(Destination::All(line)) => {
+                    let ranges = crate::schema::writable_subkeys(&schema, &record_owner, &owner_kp.key());
+                    let targets = ranges.iter().flat_map(|&(first, last)| first..=last);
+                    (targets.filter(|&target| target >= config.first_member_subkey()).collect(), line)
+                }
+            };
+
+            if prefs.get().confirm && !confirmed {
+                let ranges: Vec<_> = targets.iter().map(|&target| (target, target)).collect();
+                say!("{}", t!("confirm-write", text = line, subkey = crate::schema::describe_ranges(&ranges)));
+                pending_write = Some(text.to_string());
+                continue;
+            }
+            for target in targets {
+                if let Err(e) = locks.may_write(&rc, &record_key, target).await {
+                    say!("{}", t!("not-written-because", error = e.to_string()));
+                    continue;
+                }
+                let mut value = match registry.encode(target, line) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        say!("{}", t!("not-written-because", error = e.to_string()));
+                        continue;
+                    }
+                };
+                value.sender_seq = Some(sent_count + 1);
+                if let Some(ttl) = config.value_ttl() {
+                    value = value.expiring_after(ttl);
+                }
+
+                // A failed write isn't fatal, just report it and let the user try again.
+                match conflict::write(&rc, &record_key, target, value, Some(owner_opts.clone())).await {
+                    Err(e) => {
+                        say!("{}", t!("write-failed", subkey = target, error = e.to_string()));
+                        continue;
+                    }
+                    // someone else's got there first; the number is used either way
+                    Ok(Some(conflict)) => {
+                        sent_count += 1;
+                        let _ = recovery::save_sent(&veilid, sent_count).await;
+                        say!("{}", conflict.render());
+                        pending_conflict = Some(conflict);
+                        continue;
+                    }
+                    Ok(None) => {}
+                }
+
+                sent_count += 1;
+                if !rc.is_dry_run() {
+                    // at worst a reader takes the next run's first few for repeats
+                    let _ = recovery::save_sent(&veilid, sent_count).await;
+                    say!("{}", t!("wrote-numbered", count = sent_count, subkey = target, text = line));
+                }
+            }
+	    say!();
+
+        }
+    }
+}
+
+
+drop(record);
+records.close_all().await;
+if let Err(e) = metrics.save().await {
+    println!("{}", t!("metrics-save-failed", error = e.to_string()));
+}
+node.shutdown().await;
+journal.finish();
+running.finish();
+println!("{}", t!("shutdown-complete"));
+
+    Ok(())
+}
+
+
+
+
+
+
+
+// -------------------------------------------------------------------------
+// Alternate Node Function (if the user selected Number 2 in run, or --mode alt)
+// -------------------------------------------------------------------------
+
+async fn run_alt_node(node_options: &node::NodeOptions<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    let (options, config) = (node_options.options, node_options.config);
+
+    let data_dir = node_options.data_dir.clone();
+    let running = preflight::run(config, &node_options.storage_dir, "alt", &node_options.namespace)?;
+    // what the last run had going, if it crashed (see recovery.rs)
+    let (mut journal, resumed) = recovery::StateJournal::start(&data_dir, "alt", &running);
+
+// -------------------------------------------------------
+// Ask which record to join. A share code or full key can be typed in,
+// otherwise we load up the keys the main node stored in the txt file.
+// -------------------------------------------------------
+    let mut book = ShortcodeBook::load(&data_dir)?;
+
+// The same prompt is used for the record and then the commands further down (Tab completes both).
+    let mut repl = Repl::start(repl::history_file(&data_dir, "alt"), "alt> ", commands::names(Prompt::Alt), true)?;
+
+    let input = match resumed.as_ref().filter(|state| state.record_key().is_some()) {
+        Some(state) => {
+            println!("{}", t!("resumed-alt", state = state.describe()));
+            state.record.clone().unwrap_or_default()
+        }
+        // --key-file says which record, so there's nothing to ask
+        None if options.key_file.is_some() => String::new(),
+        None => {
+            println!("{}", t!("enter-record", file = node_options.key_file.to_string_lossy()));
+            match repl.next_line().await {
+                ReplLine::Line(line) => line,
+                ReplLine::Interrupted | ReplLine::Eof => {
+                    journal.finish();
+                    running.finish();
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+// The key file can also say how the default node expects us to be set up.
+    let mut config = config.clone();
+    let mut follow: Option<(ValueSubkey, ValueSubkey)> = resumed.as_ref().and_then(|state| state.follow);
+    // a share code or key can end in "#<fingerprint>", checked once the record is open
+    let (input, fingerprint) = shortcode::split_share(&input);
+    let fingerprint = fingerprint.map(str::to_string);
+    let mut namespace = node_options.namespace.clone();
+    let record_key = if input.is_empty() {
+        let keys = keybundle::load_file(&node_options.key_file)?;
+        follow = config.apply_join_preset(&keys.alt);
+        // --namespace wins over the key file's suggestion
+        if options.namespace.is_none() {
+            namespace = config.alt_namespace.clone();
+        }
+        if let Some(enc) = keys.alt.encryption.as_deref().filter(|e| *e != "none") {
+            println!("{}", t!("unreadable-encryption", encryption = enc));
+        }
+        keys.record_key
+    } else {
+        book.resolve(input).map_err(|e| exit::Kind::RecordNotFound.fail(e))?
+    };
+    let config = &config;
+    let mut registry = payloads::Registry::from_config(config)?;
+    let code = book.remember(&record_key)?;
+    // what the key file lets us do with this record, if it's the one it's for
+    let (grant, expected) = keybundle::load_file(&node_options.key_file)
+        .ok()
+        .filter(|keys| keys.record_key == record_key)
+        .map(|keys| (keys.grant, keys.expect))
+        .unwrap_or_default();
+    println!("{}", t!("joining", code = code.as_str()));
+    if let Some((first, last)) = follow {
+        println!("{}", t!("following", first = first, last = last));
+    }
+
+// -------------------------------------------------
+//    Now we have those key's loaded up, we can continue
+// -------------------------------------------------
+
+// The alt node is the one watching, so it keeps track of how the watch performs.
+    let watch_stats = Arc::new(WatchStats::new());
+    // and what each feature costs in DHT traffic, watches included
+    let bandwidth = Arc::new(Bandwidth::new());
+    // and which subkeys are busy, changes included
+    let heatmap = Arc::new(Heatmap::new());
+
+// Setting up the veilid node (using a diffrent namespace than the other node).
+// VeilidNode hands the records' changes to us through a WatchSet, see below.
+    let (peer_tx, peer_rx) = flume::unbounded::<Vec<u8>>();
+    // fires each time the node gets back online, to resend lost mail
+    let (online_tx, online_rx) = flume::unbounded::<()>();
+    // updates are queued (see events.rs) and printed by their own task
+    let events = Arc::new(events::EventQueues::new());
+    {
+        let watch_stats = watch_stats.clone();
+        events::spawn_printer(events.clone(), move |update| u_c(update, Some(&watch_stats), None));
+    }
+    let node = {
+        let events = events.clone();
+        let bandwidth = bandwidth.clone();
+        let heatmap = heatmap.clone();
+        let was_online = AtomicBool::new(false);
+        node::VeilidNode::start_attached(config, &node_options.storage_dir, &namespace, move |update| {
+            if let Some(msg) = peer_message(&update) {
+                let _ = peer_tx.send(msg);
+                return;
+            }
+            if came_online(&update, &was_online) {
+                let _ = online_tx.send(());
+            }
+            if let VeilidUpdate::ValueChange(change) = &update {
+                bandwidth.read(Feature::Watch, change.value.as_ref().map_or(0, |v| v.data_size()));
+                heatmap.changed(change);
+            }
+            events.push(update);
+        })
+        .await?
+    };
+    let veilid = node.api().clone();
+    println!("{}", t!("alt-ready"));
+
+    if let Some(every) = options.chaos.as_ref().and_then(|c| c.reattach_every) {
+        chaos::spawn_reattach_cycles(veilid.clone(), every);
+    }
+
+
+// ------------- Node is Now Setup And attached, from here on is DHT stuff! -----------------------    
+
+    // Create a keypair for this node using VLD0 (only option in version 5.x, although VLD1 is in the works)
+    let user_kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0)?; 
+
+    let audit = Arc::new(AuditLog::open(&data_dir.join(audit::log_file_name("alt")))?);
+    println!("{}", t!("auditing-to", path = audit.path().to_string_lossy()));
+    let latency = Arc::new(Latency::new());
+    let queue = Arc::new(OpQueue::new(Limits::default()));
+    let quota = Arc::new(Quota::new());
+    let rc = Dht::new(node.routing_context().get(), Some(audit), options.dry_run, options.chaos.clone())
+        .with_bandwidth(bandwidth.clone())
+        .with_latency(latency.clone())
+        .with_queue(queue.clone())
+        .with_quota(quota.clone())
+        .with_heatmap(heatmap.clone())
+        .with_try_again_retry(config.early_retry());
+    // how the DHT calls are doing, in front of the prompt
+    let status = latency.clone();
+    repl.set_status(move || status.summary());
+    let mail_rc = rc.for_feature(Feature::Mail);
+
+    // open up the dht record (following it if `schema grow` has moved it).
+    // The record manager keeps it open for as long as we hold the handle.
+    let records = RecordManager::new(rc.clone());
+    records.spawn_reaper();
+    // writing as the keys we were granted, the owner's first
+    let default_writer = grant.owner.clone().or(grant.writer.clone()).unwrap_or(user_kp.clone());
+    let record = records.open_at_startup(record_key.clone(), Some(default_writer)).await?;
+    let record_desc = record.descriptor().clone();
+    let forwarded = record_desc.key() != record_key;
+    let record_key = record_desc.key();
+
+    println!("{}", t!("opened-record", code = code.as_str(), key = format!("{:?}", record_desc.key())));
+    println!("{}", t!("granted", grant = grant.describe()));
+    // stop here if owner_keys.txt was made for some other record shape than the one we got,
+    // unless we were forwarded to a successor, which has a schema and keys of its own
+    if !forwarded {
+        crate::schema::check_expected(&expected, &record_desc.schema(), &record_desc.owner(), &grant)?;
+    }
+    if let Some(wanted) = &fingerprint {
+        let found = crate::schema::record_fingerprint(&record_desc.schema(), &record_desc.owner());
+        if !forwarded && *wanted != found {
+            println!();
+            println!(
+                "{}",
+                t!("fingerprint-warning", wanted = wanted.as_str(), code = code.as_str(), found = found.as_str())
+            );
+            println!();
+        }
+    }
+    journal.record_opened(&record_key, follow);
+    node.ready().reach(Milestone::RecordOpened);
+    // a record made somewhere else is someone's demo: writes to it ask first (see guard.rs)
+    let guard = WriteGuard::new(&data_dir, &record_key, options.yes)?;
+
+    // Who wrote what: the record owner gets a name automatically, others come from nicknames.json
+    let mut names = Nicknames::load(&data_dir)?;
+    if !names.knows(&record_desc.owner()) {
+        names.set(&record_desc.owner(), "record-owner")?;
+    }
+    let waiting = progress::spinner("Waiting for DHT to become routable...");
+
+    // preforming a DHT record inspection
+    let report = loop {
+        match rc
+            .inspect_dht_record(record_key.clone(), None, DHTReportScope::SyncGet)
+            .await
+        {
+            Ok(r) => break r,
+            Err(VeilidAPIError::TryAgain { .. }) => {
+                waiting.set_message("DHT not ready yet, retrying...");
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            }
+            Err(e) => {
+                waiting.abandon_with_message("DHT inspection failed");
+                eprintln!("inspect_dht_record failed: {e:?}");
+                return Err(e.into());
+            }
+        }
+    };
+    waiting.finish_with_message("DHT is routable");
+
+    // fetch what the network has newer than us now, rather than on the first ENTER (see warm.rs)
+    println!("{}", warm::warm(&rc, &record_key, &report, follow).await);
+
+    // our profile card, with a private route so whoever we send it to can answer
+    let route = match veilid.new_private_route().await {
+        Ok(route) => Some(route),
+        Err(e) => {
+            println!("{}", t!("no-private-route", error = e.to_string()));
+            None
+        }
+    };
+    let my_card = profile::ProfileCard::new(
+        &user_kp,
+        &config.profile_name("alt"),
+        &["watch", "feed"],
+        route.as_ref().map(|r| r.blob.as_slice()),
+    )?;
+    let mut contacts = Contacts::open(&veilid, &data_dir).await?;
+    // how this prompt behaves, as it was left last time (see prefs.rs)
+    let mut prefs = prefs::PrefStore::open(&veilid).await?;
+    // call times by day, for `stats trend` (see trend.rs)
+    let mut metrics = trend::MetricsStore::open(&veilid, latency.clone()).await?;
+    repl.set_vi(prefs.get().edit_mode == prefs::EditMode::Vi);
+    // our audit log, for contacts who ask to follow it (see logs.rs)
+    let mut log_streams = logs::LogStreams::new(
+        data_dir.join(audit::log_file_name("alt")),
+        &my_card,
+        veilid.clone(),
+        node.routing_context().get(),
+    );
+    // `:msg` to the default node, once we have its card from the metadata block (see chat.rs)
+    let mut chat = chat::Chat::new(veilid.clone(), node.routing_context().get());
+    // our watch's changes, passed on to whoever joins in relay mode (see relay.rs)
+    let mut relay = relay::Relay::new(&my_card, veilid.clone(), node.routing_context().get(), true);
+    // advisory locks, written as whichever granted key may write the lock subkey (see locks.rs)
+    let lock_writer = config.lock_subkey.and_then(|lock_subkey| {
+        let keys: Vec<&KeyPair> = grant.writer.iter().chain(grant.owner.iter()).collect();
+        crate::schema::pick_writer(&record_desc.schema(), &record_desc.owner(), &keys, Some(lock_subkey))
+            .ok()
+            .map(|(_, writer)| writer.clone())
+    });
+    let locks = locks::Locks::new(config, &my_card, lock_writer);
+
+    // what the default node says about the record, if it said anything,
+    // and where its mailbox is, if it has one
+    let mut mailbox_info = None;
+    match rc
+        .for_feature(Feature::Metadata)
+        .get_dht_value(record_key.clone(), metadata::METADATA_SUBKEY, true)
+        .await
+    {
+        Ok(Some(value)) => match RecordMetadata::decode(value.data()) {
+            Some(meta) => {
+                println!("{}", t!("record-title", title = meta.display()));
+                mailbox_info = meta.mailbox.clone();
+                registry.adopt(&meta.payload_types);
+                println!("{}", t!("typed-subkeys", types = registry.describe()));
+                // say hello to the record's owner; their card comes back over app_message
+                if let Some(card) = &meta.owner_card {
+                    chat.set_peer(card);
+                }
+                if let Some(blob) = meta.owner_card.as_ref().and_then(|c| c.route_blob()) {
+                    let hello = profile::CardMessage::Hello(my_card.clone());
+                    if let Err(e) = profile::send(&veilid, &node.routing_context().get(), blob, &hello).await {
+                        println!("{}", t!("card-send-failed", error = e.to_string()));
+                    }
+                }
+            }
+            None => println!("{}", t!("other-in-subkey-0")),
+        },
+        Ok(None) => println!("{}", t!("no-metadata-block")),
+        Err(e) => println!("{}", t!("metadata-read-failed", error = e.to_string())),
+    }
+
+    // whether the build that made the record speaks our protocol; if it's newer we
+    // only read, since what we'd write could be wrong for it (see protocol.rs)
+    let stated = match protocol::subkey(&record_desc.schema()) {
+        Some(protocol_subkey) => match rc
+            .for_feature(Feature::Metadata)
+            .get_dht_value(record_key.clone(), protocol_subkey, true)
+            .await
+        {
+            Ok(value) => value.and_then(|v| protocol::ProtocolValue::decode(v.data())),
+            Err(e) => {
+                println!("{}", t!("protocol-read-failed", error = e.to_string()));
+                None
+            }
+        },
+        None => None,
+    };
+    let read_only = match protocol::negotiate(stated.as_ref()) {
+        protocol::Verdict::Full => None,
+        protocol::Verdict::ReadOnly(why) => {
+            println!("{}", t!("read-only-record", why = why.as_str()));
+            Some(why)
+        }
+        protocol::Verdict::Refused(why) => return Err(why.into()),
+    };
+
+    // the default node's own private route, kept alive in the record; `:msg` goes over it (see route.rs)
+    let route_subkey = route::subkey(&record_desc.schema());
+    if let Some(subkey) = route_subkey {
+        if let Ok(Some(value)) = rc.for_feature(Feature::Metadata).get_dht_value(record_key.clone(), subkey, true).await {
+            if let Some(published) = route::RouteValue::decode(value.data()) {
+                chat.set_route(&published);
+                println!("{}", t!("peer-route", name = published.nickname.as_str(), subkey = subkey));
+            }
+        }
+    }
+
+    // mail we've sent, and anything from last time that needs sending again
+    let mut outbox = receipts::Outbox::open(&veilid).await?;
+    if let Some(info) = &mailbox_info {
+        for line in outbox.retransmit(&mail_rc, &record_key, info).await {
+            println!("{line}");
+        }
+    }
+    journal.pending_mail(outbox.pending());
+
+    // put a watch on the record. Its changes, and those of any record added
+    // later with 'watch <record>', all arrive through `changes`:
+    let changes = WatchSet::new();
+    // and go out to webhook_url as well, if there is one (see webhook.rs)
+    let webhook = webhook::Webhook::from_config(config)?;
+    // `publish` schedules, written with the keys we were granted (see publish.rs)
+    let mut publisher = publish::Publisher::new(
+        rc.clone(),
+        record_key.clone(),
+        record_desc.schema(),
+        record_desc.owner(),
+        grant.writer.iter().chain(grant.owner.iter()).cloned().collect(),
+        registry.clone(),
+        &my_card.nickname,
+    );
+    let subkeys = match follow {
+        Some((first, last)) => ValueSubkeyRangeSet::single_range(first, last),
+        None => ValueSubkeyRangeSet::full(),
+    };
+    match node.watch(&changes, record_key.clone(), subkeys).await {
+        Ok(()) => {
+            println!("{}", t!("watch-active"));
+            watch_stats.watch_started(&record_key);
+        }
+        Err(e) => println!("{}", t!("watch-not-active", error = e.to_string())),
+    }
+    // the other records being watched, kept open while we do
+    let mut also_watching = Vec::new();
+    for other in resumed.as_ref().map(|state| state.watch_keys()).unwrap_or_default() {
+        let other_code = book.remember(&other)?;
+        let resumed_watch = match records.open(other.clone(), None).await {
+            Ok(handle) => node.watch(&changes, handle.key(), ValueSubkeyRangeSet::full()).await.map(|()| handle),
+            Err(e) => Err(e),
+        };
+        match resumed_watch {
+            Ok(handle) => {
+                println!("{}", t!("watching-again", code = other_code.as_str()));
+                watch_stats.watch_started(&handle.key());
+                journal.watch_added(&handle.key());
+                also_watching.push(handle);
+            }
+            Err(e) => println!("{}", t!("watch-again-failed", code = other_code.as_str(), error = e.to_string())),
+        }
+    }
+    // every change, for 'feed'
+    let mut feed = Feed::new();
+    // each writer's numbered messages, to spot the ones the watches missed
+    let mut order = ordering::OrderTracker::new();
+    println!();
+
+println!("{}", t!("alt-intro"));
+println!();
+
+let mut inputs = Inputs::new(repl);
+// set by `watch status`, so a lone `r` next renews the watches
+let mut renew_offered = false;
+// a write that lost to a newer value, for the m/y/t/e answer straight after (see conflict.rs)
+let mut pending_conflict: Option<conflict::Conflict> = None;
+// with `set-option confirm on`, the `write` line waiting for a y
+let mut pending_write: Option<String> = None;
+// with `set-option refresh enter`, the changes heard since the record was last read
+let mut held_changes: u32 = 0;
+// `read <subkey>` fetches the rest of the record behind it (see prefetch.rs)
+let mut prefetch = prefetch::Prefetcher::new(&rc);
+let mut metrics_save = tokio::time::interval_at(tokio::time::Instant::now() + trend::SAVE_EVERY, trend::SAVE_EVERY);
+let waits = expect::Waits::default();
+
+loop {
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            say!("\n{}", t!("ctrl-c"));
+            break;
+        }
+
+        Ok(msg) = peer_rx.recv_async() => {
+            if logs::is_log_message(&msg) {
+                say!("{}", log_streams.receive(&msg, &contacts).await);
+            } else if chat::is_chat_message(&msg) {
+                say!("{}", chat.receive(&msg));
+            } else if relay::is_relay_message(&msg) {
+                say!("{}", relay.receive(&msg, &contacts));
+            } else {
+                if let Some(card) = profile::CardMessage::decode(&msg) {
+                    chat.set_peer(card.card());
+                }
+                say!("{}", profile::receive(&msg, &my_card, &mut contacts, &veilid, &node.routing_context().get()).await);
+            }
+        }
+
+        _ = metrics_save.tick() => {
+            if let Err(e) = metrics.save().await {
+                say!("{}", t!("metrics-save-failed", error = e.to_string()));
+            }
+        }
+
+        Ok(outcome) = waits.results().recv_async() => {
+            say!("{outcome}");
+        }
+
+        Ok(()) = online_rx.recv_async() => {
+            if let Some(info) = &mailbox_info {
+                for line in outbox.retransmit(&mail_rc, &record_key, info).await {
+                    say!("{line}");
+                }
+                journal.pending_mail(outbox.pending());
+            }
+        }
+
+        Some(change) = changes.next() => {
+            if let Some(hook) = &webhook {
+                hook.send(&change);
+            }
+            relay.forward(&change, &names);
+            // the default node made its route again
+            if let Some(value) = change.value.as_ref().filter(|v| change.record == record_key && Some(v.subkey) == route_subkey) {
+                if let Some(published) = value.envelope.as_ref().ok().and_then(route::RouteValue::from_envelope) {
+                    chat.set_route(&published);
+                    say!("{}", t!("peer-route-renewed", name = published.nickname.as_str()));
+                }
+            }
+            if let Some(value) = &change.value {
+                if let Some(seq) = value.envelope.as_ref().ok().and_then(|env| env.sender_seq) {
+                    let arrival = order.observe(&value.writer, seq);
+                    if let Some(note) = ordering::OrderTracker::note(&arrival, &names.label(&value.writer), seq) {
+                        say!("{note}");
+                    }
+                }
+            }
+            let shown = feed.record(&change, &names, audit::now_ms() as u64);
+            if feed.is_on() {
+                if let Some(line) = shown {
+                    say!("{line}");
+                }
+                continue;
+            }
+            let tag = prefs.get().tag(&format!("watch {}", change.shortcode()));
+            if change.watch_died {
+                say!("{}", t!("watch-died", tag = tag));
+            } else if prefs.get().refresh == prefs::Refresh::Enter {
+                held_changes += 1;
+                if held_changes == 1 {
+                    say!("{}", t!("record-changed", tag = tag));
+                }
+            } else {
+                if let Some(value) = &change.value {
+                    say!("{}", changed_line(&tag, value, &registry, &names));
+                }
+                // Veilid only sends the first changed subkey's value along, so read the others
+                let mut unread = ValueSubkeyRangeSet::new();
+                for (n, subkey) in change.unsent().into_iter().enumerate() {
+                    let read = if n < LIVE_READS {
+                        rc.get_dht_value(change.record.clone(), subkey, true).await.ok().flatten()
+                    } else {
+                        None
+                    };
+                    match read {
+                        Some(data) => say!("{}", changed_line(&tag, &ChangedValue::new(subkey, &data), &registry, &names)),
+                        None => {
+                            unread.insert(subkey);
+                        }
+                    }
+                }
+                if !unread.is_empty() {
+                    say!("{}", t!("subkeys-changed", tag = tag, subkeys = unread.to_string()));
+                }
+            }
+        }
+
+        input = inputs.next() => {
+            let command = match input {
+                Input::Command(command) => command,
+                Input::Interrupted => {
+                    say!("{}", t!("ctrl-c"));
+                    break;
+                }
+                // EOF (unlikely in terminal, but safe)
+                Input::Closed => break,
+            };
+
+            // `r` only renews the watches straight after `watch status`,
+            // m/y/t/e only answer a conflict straight after it, and y/n a held write
+            let renew = std::mem::take(&mut renew_offered);
+            let unanswered = pending_conflict.take();
+            let held = pending_write.take();
+            let answer = match &command {
+                Command::Other(line) if held.is_some() => line.trim(),
+                _ => "",
+            };
+            if answer == "n" {
+                say!("{}", t!("not-written"));
+                continue;
+            }
+            // y: back to the held line, this time without asking
+            let confirmed = answer == "y";
+            let command = match held.filter(|_| confirmed).and_then(|held| Command::parse(&held)) {
+                Some(held) => held,
+                None => command,
+            };
+
+            let line = match command {
+                Command::Other(line) => line,
+                Command::Help(topic) => {
+                    say!("{}", commands::help(Prompt::Alt, &topic));
+                    continue;
+                }
+                Command::Stats(what) => {
+                    match what.as_str() {
+                        "watch" => say!("{}", watch_stats.report()),
+                        "events" => say!("{}", events.report()),
+                        "bandwidth" => say!("{}", bandwidth.report()),
+                        "queue" => say!("{}", queue.report()),
+                        "heatmap" => say!("{}", heatmap.report()),
+                        "trend" => say!("{}", metrics.report().await),
+                        "order" => {
+                            names.reload()?;
+                            say!("{}", order.report(&names));
+                        }
+                        _ => say!("{}", t!("no-such-stats", what = what.as_str())),
+                    }
+                    continue;
+                }
+                Command::Quota => {
+                    say!("{}", quota.report(&record_key, |k| names.label(k)));
+                    continue;
+                }
+                Command::Field(args) => {
+                    // field <name> <text>: set the copy the roster gives the keys we were granted
+                    if let Some(why) = &read_only {
+                        say!("{}", t!("read-only-record", why = why.as_str()));
+                        continue;
+                    }
+                    let (name, value) = args.split_once(' ').unwrap_or((args.as_str(), ""));
+                    let keys: Vec<&KeyPair> = grant.writer.iter().chain(grant.owner.iter()).collect();
+                    let (field_subkey, writer) = match fields::own_subkey(&rc, &record_key, &keys, name).await {
+                        Ok((subkey, writer)) => (subkey, writer.clone()),
+                        Err(e) => {
+                            say!("{}", t!("not-set", error = e));
+                            continue;
+                        }
+                    };
+                    if let Err(e) = locks.may_write(&rc, &record_key, field_subkey).await {
+                        say!("{}", t!("not-set", error = e.to_string()));
+                        continue;
+                    }
+                    let value = match registry.encode(field_subkey, value.trim()) {
+                        Ok(value) => value,
+                        Err(e) => {
+                            say!("{}", t!("not-set", error = e.to_string()));
+                            continue;
+                        }
+                    };
+                    let what = t!("shared-write-value", size = value.encode().len(), subkey = field_subkey);
+                    if let Some(question) = guard.question(confirmed, &what) {
+                        say!("{question}");
+                        pending_write = Some(format!("field {args}"));
+                        continue;
+                    }
+                    let opts = SetDHTValueOptions {
+                        writer: Some(writer),
+                        allow_offline: None,
+                    };
+                    match rc.set_dht_value(record_key.clone(), field_subkey, value.encode(), Some(opts)).await {
+                        Ok(_) => say!("{}", t!("field-set", name = name, subkey = field_subkey)),
+                        Err(e) => say!("{}", t!("field-set-failed", name = name, error = e.to_string())),
+                    }
+                    continue;
+                }
+                Command::Merge(name) => {
+                    match fields::merge_read(&rc, &record_key, &name).await {
+                        Ok(versions) => say!("{}", fields::render(&name, &versions)),
+                        Err(e) => say!("{e}"),
+                    }
+                    continue;
+                }
+                Command::DiagBundle => {
+                    names.reload()?;
+                    let stats = format!(
+                        "latency: {}\n\n{}\n{}\n{}\n\n{}\n\n{}",
+                        latency.summary(),
+                        node.ready().describe(),
+                        watch_stats.report(),
+                        bandwidth.report(),
+                        events.report(),
+                        order.report(&names)
+                    );
+                    match diag::bundle(&veilid, config, &data_dir, "alt", &stats).await {
+                        Ok(path) => say!("{}", t!("bundle-written", path = path.to_string_lossy())),
+                        Err(e) => say!("{}", t!("bundle-failed", error = e.to_string())),
+                    }
+                    continue;
+                }
+                Command::Contact(args) => {
+                    say!("{}", contacts.command(&args, &book).await);
+                    continue;
+                }
+                Command::Logs(args) => {
+                    say!("{}", log_streams.command(&args, &contacts, &my_card).await);
+                    continue;
+                }
+                Command::Relay(args) => {
+                    say!("{}", relay.command(&args, &contacts, &my_card).await);
+                    continue;
+                }
+                Command::Msg(text) => {
+                    say!("{}", chat.send(&text, &my_card).await);
+                    continue;
+                }
+                Command::Lock(args) => {
+                    let subkey = args.split_whitespace().next().and_then(|s| s.parse::<ValueSubkey>().ok());
+                    let what = subkey.map(|subkey| t!("shared-write-lock", subkey = subkey));
+                    if let Some(question) = what.and_then(|what| guard.question(confirmed, &what)) {
+                        say!("{question}");
+                        pending_write = Some(format!("lock {args}"));
+                        continue;
+                    }
+                    say!("{}", locks.command(&rc, &record_key, &args, false).await);
+                    continue;
+                }
+                Command::Unlock(args) => {
+                    let subkey = args.split_whitespace().next().and_then(|s| s.parse::<ValueSubkey>().ok());
+                    let what = subkey.map(|subkey| t!("shared-write-lock", subkey = subkey));
+                    if let Some(question) = what.and_then(|what| guard.question(confirmed, &what)) {
+                        say!("{question}");
+                        pending_write = Some(format!("unlock {args}"));
+                        continue;
+                    }
+                    say!("{}", locks.command(&rc, &record_key, &args, true).await);
+                    continue;
+                }
+                Command::Publish(args) => {
+                    if let Some(why) = &read_only {
+                        say!("{}", t!("read-only-record", why = why.as_str()));
+                        continue;
+                    }
+                    // a new schedule asks once; its runs after that don't
+                    if let Ok(schedule) = publish::Schedule::parse(&args) {
+                        let secs = schedule.every.as_secs();
+                        let what = t!("shared-write-publish", subkey = schedule.subkey, secs = secs);
+                        if let Some(question) = guard.question(confirmed, &what) {
+                            say!("{question}");
+                            pending_write = Some(format!("publish {args}"));
+                            continue;
+                        }
+                    }
+                    say!("{}", publisher.command(&args));
+                    continue;
+                }
+                Command::SetOption(args) => {
+                    say!("{}", prefs.command(&args).await);
+                    inputs.repl().set_vi(prefs.get().edit_mode == prefs::EditMode::Vi);
+                    continue;
+                }
+            };
+            if let (Some(conflict), Some(choice)) = (unanswered, conflict::Choice::parse(&line)) {
+                let (outcome, again) = conflict.resolve(&rc, &record_key, choice).await;
+                say!("{outcome}");
+                pending_conflict = again;
+                continue;
+            }
+
+            if let Some(text) = line.trim().strip_prefix("mail ") {
+                // mail <text>: leave it in the record's mailbox for its owner to read later
+                if let Some(why) = &read_only {
+                    say!("{}", t!("read-only-record", why = why.as_str()));
+                    continue;
+                }
+                let Some(info) = &mailbox_info else {
+                    say!("{}", t!("no-mailbox"));
+                    continue;
+                };
+                let letter = mailbox::Letter {
+                    id: receipts::new_id(),
+                    from: user_kp.key().to_string(),
+                    nickname: config.profile_name("alt"),
+                    text: text.trim().to_string(),
+                    sent_ms: audit::now_ms() as u64,
+                };
+                let sealed = match info
+                    .recipient
+                    .parse::<PublicKey>()
+                    .map_err(|e| e.to_string())
+                    .and_then(|to| mailbox::seal(&veilid, &record_key, &to, &letter).map_err(|e| e.to_string()))
+                {
+                    Ok(sealed) => sealed,
+                    Err(e) => {
+                        say!("{}", t!("seal-failed", error = e));
+                        continue;
+                    }
+                };
+                match mailbox::post(&mail_rc, &record_key, info, &letter.id, sealed.clone()).await {
+                    Ok(slot) => {
+                        say!("{}", t!("letter-left", slot = slot));
+                        if let Err(e) = outbox.sent(&letter.id, &record_key, slot, &sealed, &letter.text).await {
+                            say!("{}", t!("outbox-keep-failed", error = e.to_string()));
+                        }
+                        journal.pending_mail(outbox.pending());
+                    }
+                    Err(e) => say!("{}", t!("send-failed", error = e.to_string())),
+                }
+                continue;
+            }
+
+            if line.trim() == "outbox" {
+                if let Some(info) = &mailbox_info {
+                    match outbox.refresh(&mail_rc, &record_key, info).await {
+                        Ok(moved) => moved.iter().for_each(|l| say!("{l}")),
+                        Err(e) => say!("{e}"),
+                    }
+                    journal.pending_mail(outbox.pending());
+                }
+                say!("{}", outbox.report(&record_key));
+                continue;
+            }
+
+            if let Some(rest) = line.trim().strip_prefix("write ") {
+                // write [subkey] <text>: only with the keys the key file granted, and only
+                // where the schema lets them write; no subkey means the first such one
+                if let Some(why) = &read_only {
+                    say!("{}", t!("read-only-record", why = why.as_str()));
+                    continue;
+                }
+                let (subkey, text) = match rest.trim().split_once(' ').unwrap_or((rest.trim(), "")) {
+                    (first, text) if first.parse::<ValueSubkey>().is_ok() => (first.parse().ok(), text),
+                    _ => (None, rest.trim()),
+                };
+                let keys: Vec<&KeyPair> = grant.writer.iter().chain(grant.owner.iter()).collect();
+                let (schema, owner) = (record_desc.schema(), record_desc.owner());
+                let picked = match subkey {
+                    Some(_) => crate::schema::pick_writer(&schema, &owner, &keys, subkey),
+                    // the default-subkey option, if the granted keys may write there,
+                    // then where the key file says the granted writer's subkeys start
+                    None => crate::schema::pick_writer(&schema, &owner, &keys, prefs.get().default_subkey)
+                        .or_else(|_| {
+                            let granted = grant.writer_subkeys.map(|(first, _)| first);
+                            crate::schema::pick_writer(&schema, &owner, &keys, granted.filter(|_| grant.owner.is_none()))
+                        })
+                        .or_else(|_| crate::schema::pick_writer(&schema, &owner, &keys, None)),
+                };
+                let (subkey, writer) = match picked {
+                    Ok((subkey, writer)) => (subkey, writer.clone()),
+                    Err(e) => {
+                        say!("{}", t!("not-written-granted", error = e.to_string(), grant = grant.describe()));
+                        continue;
+                    }
+                };
+                if let Err(e) = locks.may_write(&rc, &record_key, subkey).await {
+                    say!("{}", t!("not-written-because", error = e.to_string()));
+                    continue;
+                }
+                let value = match registry.encode(subkey, text.trim()) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        say!("{}", t!("not-written-because", error = e.to_string()));
+                        continue;
+                    }
+                };
+                let what = t!("shared-write-value", size = value.encode().len(), subkey = subkey);
+                if let Some(question) = guard.question(confirmed, &what) {
+                    say!("{question}");
+                    pending_write = Some(line.clone());
+                    continue;
+                }
+                if prefs.get().confirm && !confirmed {
+                    say!("{}", t!("confirm-write", text = text.trim(), subkey = subkey));
+                    pending_write = Some(line.clone());
+                    continue;
+                }
+                let opts = SetDHTValueOptions {
+                    writer: Some(writer),
+                    allow_offline: None,
+                };
+                match conflict::write(&rc, &record_key, subkey, value, Some(opts)).await {
+                    Ok(None) => say!("{}", t!("wrote-subkey", subkey = subkey)),
+                    Ok(Some(conflict)) => {
+                        say!("{}", conflict.render());
+                        pending_conflict = Some(conflict);
+                    }
+                    Err(e) => say!("{}", t!("subkey-write-failed", subkey = subkey, error = e.to_string())),
+                }
+                continue;
+            }
+
+            if let Some(outcome) = expect::command(&rc, &record_key, line.trim(), &waits).await {
+                say!("{outcome}");
+                continue;
+            }
+
+            if let Some(rest) = line.trim().strip_prefix("read ") {
+                // read <subkey>: just the one, from the network unless it was prefetched
+                let Ok(subkey) = rest.trim().parse::<ValueSubkey>() else {
+                    say!("{}", t!("read-usage"));
+                    continue;
+                };
+                let max_subkey = record_desc.schema().max_subkey();
+                if subkey > max_subkey {
+                    say!("{}", t!("subkeys-go-up-to", max = max_subkey));
+                    continue;
+                }
+                names.reload()?;
+                let read = prefs.get().tag("read");
+                match prefetch.read(&rc, &record_key, subkey, max_subkey).await {
+                    (Ok(Some(value)), local) => {
+                        let writer = nicknames::attribution(&value, &names);
+                        let shown = registry.display(subkey, value.data());
+                        if local {
+                            say!("{}", t!("read-prefetched", tag = read, subkey = subkey, writer = writer, value = shown));
+                        } else {
+                            say!("{}", t!("read-value", tag = read, subkey = subkey, writer = writer, value = shown));
+                        }
+                    }
+                    (Ok(None), _) => say!("{}", t!("read-empty", tag = read, subkey = subkey)),
+                    (Err(e), _) => say!("{}", t!("read-failed", tag = read, subkey = subkey, error = e.to_string())),
+                }
+                continue;
+            }
+
+            if line.trim() == "watch status" {
+                say!("{}", watch_status::status(&node, &watch_stats).await);
+                renew_offered = true;
+                continue;
+            }
+
+            if renew && line.trim() == "r" {
+                say!("{}", watch_status::renew(&node).await);
+                continue;
+            }
+
+            if let Some(rest) = line.trim().strip_prefix("watch ") {
+                // watch <shortcode or record key>
+                let other = match book.resolve(rest.trim()) {
+                    Ok(key) => key,
+                    Err(e) => {
+                        say!("{e}");
+                        continue;
+                    }
+                };
+                let other_code = book.remember(&other)?;
+                if node.watched_records().contains(&other) {
+                    say!("{}", t!("already-watching", code = other_code.as_str()));
+                    continue;
+                }
+                let handle = match progress::spin("Opening record", records.open(other.clone(), None)).await {
+                    Ok(handle) => handle,
+                    Err(e) => {
+                        say!("{}", t!("open-failed", code = other_code.as_str(), error = e.to_string()));
+                        continue;
+                    }
+                };
+                match node.watch(&changes, handle.key(), ValueSubkeyRangeSet::full()).await {
+                    Ok(()) => {
+                        say!("{}", t!("watching-too", code = other_code.as_str()));
+                        watch_stats.watch_started(&handle.key());
+                        journal.watch_added(&handle.key());
+                        also_watching.push(handle);
+                    }
+                    Err(e) => say!("{}", t!("watch-failed", code = other_code.as_str(), error = e.to_string())),
+                }
+                continue;
+            }
+
+            if line.trim() == "feed off" {
+                feed.stop();
+                say!("{}", t!("feed-off"));
+                continue;
+            }
+
+            if let Some(args) = line.trim().strip_prefix("feed").filter(|t| t.is_empty() || t.starts_with(' ')) {
+                match FeedFilter::parse(args, &book) {
+                    Ok(filter) => {
+                        names.reload()?;
+                        for earlier in feed.start(filter) {
+                            say!("{earlier}");
+                        }
+                        say!("{}", t!("feed-on"));
+                    }
+                    Err(e) => say!("{e}"),
+                }
+                continue;
+            }
+
+            if line.trim() == "watching" {
+                for key in node.watched_records() {
+                    say!("  {}  {key}", shortcode::shortcode(&key));
+                }
+                continue;
+            }
+
+            if let Some(rest) = line.trim().strip_prefix("nick ") {
+                // nick <public key> <name>
+                match rest.trim().split_once(' ') {
+                    Some((key, name)) => match key.parse::<PublicKey>() {
+                        Ok(key) => {
+                            names.set(&key, name.trim())?;
+                            say!("{}", t!("nick-set", key = key.to_string(), name = name.trim()));
+                        }
+                        Err(e) => say!("{}", t!("not-a-public-key", error = e.to_string())),
+                    },
+                    None => say!("{}", t!("nick-usage")),
+                }
+                continue;
+            }
+
+            say!("{}", t!("reading-dht"));
+            held_changes = 0;
+            names.reload()?;
+            let (first, last) = follow.unwrap_or((0, record_desc.schema().max_subkey()));
+            let last = last.min(record_desc.schema().max_subkey());
+            let bar = progress::subkeys(u64::from(last.saturating_sub(first)) + 1, "Reading");
+            let read = prefs.get().tag("read");
+            for subkey in first..=last {
+                let shown = match rc
+                    .get_dht_value(record_key.clone(), subkey, false)
+                    .await
+                {
+                    Ok(Some(value)) => {
+                        let text = registry.display(subkey, value.data());
+                        let writer = nicknames::attribution(&value, &names);
+                        t!("read-value", tag = read.as_str(), subkey = subkey, writer = writer, value = text)
+                    }
+                    Ok(None) => t!("read-empty", tag = read.as_str(), subkey = subkey),
+                    Err(e) => t!("read-failed", tag = read.as_str(), subkey = subkey, error = e.to_string()),
+                };
+                bar.suspend(|| say!("{shown}"));
+                bar.inc(1);
+            }
+            bar.finish_and_clear();
+
+            say!();
+            say!("{}", t!("refresh-hint"));
+            say!();
+        }
+    }
+}
+
+drop(record);
+drop(also_watching);
+records.close_all().await;
+if let Err(e) = metrics.save().await {
+    println!("{}", t!("metrics-save-failed", error = e.to_string()));
+}
+node.shutdown().await;
+journal.finish();
+running.finish();
+println!("{}", t!("shutdown-complete"));
+
+    Ok(())
+}
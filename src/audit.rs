@@ -0,0 +1,245 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Append-only audit log of every DHT operation the node performs.
+//
+//	Each line of the log file is one JSON entry. Every entry stores the hash
+//	of the entry before it, so if someone edits or deletes a line in the
+//	middle of the file, the chain breaks and `audit show` will point it out.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// The "previous hash" used by the very first entry in a log.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp_ms: u128,
+    pub op: String,
+    pub params: serde_json::Value,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    pub latency_ms: u128,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    // The hash covers every field except the hash itself.
+    fn compute_hash(&self) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.seq.to_le_bytes());
+        hasher.update(&self.timestamp_ms.to_le_bytes());
+        hasher.update(self.op.as_bytes());
+        hasher.update(self.params.to_string().as_bytes());
+        hasher.update(self.result.as_deref().unwrap_or("").as_bytes());
+        hasher.update(self.error.as_deref().unwrap_or("").as_bytes());
+        hasher.update(&self.latency_ms.to_le_bytes());
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.finalize().to_hex().to_string()
+    }
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    // (next sequence number, hash of the last entry written)
+    state: Mutex<(u64, String)>,
+}
+
+impl AuditLog {
+    // Open (or create) a log file, picking the chain up where it left off.
+    // A log whose chain is already broken isn't appended to, since new
+    // entries would hash over the tampered ones and hide where it broke.
+    pub fn open(path: &Path) -> io::Result<AuditLog> {
+        let mut next_seq = 0;
+        let mut last_hash = GENESIS_HASH.to_string();
+
+        if path.exists() {
+            let entries = read_entries(path)?;
+            if let Some(seq) = verify_chain(&entries) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "the audit log {} was modified (hash chain broken at entry #{seq}); move it aside to start a new one",
+                        path.to_string_lossy()
+                    ),
+                ));
+            }
+            if let Some(last) = entries.last() {
+                next_seq = last.seq + 1;
+                last_hash = last.hash.clone();
+            }
+        }
+
+        Ok(AuditLog {
+            path: path.to_owned(),
+            state: Mutex::new((next_seq, last_hash)),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(
+        &self,
+        op: &str,
+        params: serde_json::Value,
+        result: Option<String>,
+        error: Option<String>,
+        latency_ms: u128,
+    ) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        let mut entry = AuditEntry {
+            seq: state.0,
+            timestamp_ms: now_ms(),
+            op: op.to_string(),
+            params,
+            result,
+            error,
+            latency_ms,
+            prev_hash: state.1.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+
+        state.0 = entry.seq + 1;
+        state.1 = entry.hash;
+        Ok(())
+    }
+}
+
+pub fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+pub fn read_entries(path: &Path) -> io::Result<Vec<AuditEntry>> {
+    let file = File::open(path)?;
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: AuditEntry = serde_json::from_str(&line)?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// Walk the chain and return the sequence number of the first broken link (if any).
+pub fn verify_chain(entries: &[AuditEntry]) -> Option<u64> {
+    let mut prev_hash = GENESIS_HASH.to_string();
+    for entry in entries {
+        if entry.prev_hash != prev_hash || entry.compute_hash() != entry.hash {
+            return Some(entry.seq);
+        }
+        prev_hash = entry.hash.clone();
+    }
+    None
+}
+
+// -------------------------------------------------------------------------
+// `audit show` command: print every log in the data folder and check it.
+// -------------------------------------------------------------------------
+
+pub fn show(dir: &Path, role: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let wanted = match role {
+            Some(role) => name == log_file_name(role),
+            None => name.starts_with("audit_") && name.ends_with(".jsonl"),
+        };
+        if wanted {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    if paths.is_empty() {
+        println!("No audit logs found in {}", dir.to_string_lossy());
+        return Ok(());
+    }
+
+    for path in paths {
+        let entries = read_entries(&path)?;
+        println!("== {} ({} entries) ==", path.to_string_lossy(), entries.len());
+
+        for e in &entries {
+            let outcome = match (&e.result, &e.error) {
+                (_, Some(err)) => format!("ERROR {err}"),
+                (Some(res), None) => res.clone(),
+                (None, None) => "ok".to_string(),
+            };
+            println!(
+                "#{:<5} {} {:<8} {:>6}ms  {}  -> {}",
+                e.seq, e.timestamp_ms, e.op, e.latency_ms, e.params, outcome
+            );
+        }
+
+        match verify_chain(&entries) {
+            None => println!("Hash chain OK"),
+            Some(seq) => println!("WARNING: hash chain broken at entry #{seq} (log was modified)"),
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+pub fn log_file_name(role: &str) -> String {
+    format!("audit_{role}.jsonl")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_chain_is_checked_before_appending() {
+        let path = std::env::temp_dir().join(format!("audit-test-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let log = AuditLog::open(&path).unwrap();
+        for op in ["create", "set", "get"] {
+            log.append(op, serde_json::json!({ "subkey": 0 }), None, None, 5).unwrap();
+        }
+        let entries = read_entries(&path).unwrap();
+        assert_eq!(verify_chain(&entries), None);
+
+        // reopening carries on from the last entry
+        AuditLog::open(&path).unwrap().append("watch", serde_json::json!({}), None, None, 1).unwrap();
+        let entries = read_entries(&path).unwrap();
+        assert_eq!(entries.last().map(|e| e.seq), Some(3));
+        assert_eq!(verify_chain(&entries), None);
+
+        // an edited entry breaks the chain there, and the log isn't appended to
+        let edited = fs::read_to_string(&path).unwrap().replacen("\"set\"", "\"del\"", 1);
+        fs::write(&path, edited).unwrap();
+        assert_eq!(verify_chain(&read_entries(&path).unwrap()), Some(1));
+        assert!(AuditLog::open(&path).is_err());
+        let _ = fs::remove_file(&path);
+    }
+}
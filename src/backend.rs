@@ -0,0 +1,540 @@
+#[cfg(test)]
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+#[cfg(test)]
+use std::sync::Mutex;
+
+use veilid_core::*;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The DHT calls the example makes, as a trait.
+//
+//	RoutingContext is the real thing. MemoryDht is a test-only fake that keeps
+//	records in a HashMap, so code built on the Dht wrapper can be tested without
+//	starting Veilid or touching the network:
+//
+//	  let dht = Dht::with_backend(Arc::new(MemoryDht::new()), None, false, None);
+//
+//	The methods mirror RoutingContext's (same names, same arguments) so
+//	switching between the two needs no other changes.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub type DhtFuture<'a, T> = Pin<Box<dyn Future<Output = VeilidAPIResult<T>> + Send + 'a>>;
+
+pub trait DhtBackend: Send + Sync {
+    fn create_dht_record(
+        &self,
+        kind: CryptoKind,
+        schema: DHTSchema,
+        owner: Option<KeyPair>,
+    ) -> DhtFuture<'_, DHTRecordDescriptor>;
+
+    // The key a record with this schema and owner would get (nothing is created).
+    fn get_dht_record_key(&self, schema: DHTSchema, owner: PublicKey) -> DhtFuture<'_, RecordKey>;
+
+    fn open_dht_record(
+        &self,
+        record_key: RecordKey,
+        writer: Option<KeyPair>,
+    ) -> DhtFuture<'_, DHTRecordDescriptor>;
+
+    fn close_dht_record(&self, record_key: RecordKey) -> DhtFuture<'_, ()>;
+
+    fn set_dht_value(
+        &self,
+        record_key: RecordKey,
+        subkey: ValueSubkey,
+        data: Vec<u8>,
+        options: Option<SetDHTValueOptions>,
+    ) -> DhtFuture<'_, Option<ValueData>>;
+
+    fn get_dht_value(
+        &self,
+        record_key: RecordKey,
+        subkey: ValueSubkey,
+        force_refresh: bool,
+    ) -> DhtFuture<'_, Option<ValueData>>;
+
+    fn watch_dht_values(
+        &self,
+        record_key: RecordKey,
+        subkeys: Option<ValueSubkeyRangeSet>,
+        expiration: Option<Timestamp>,
+        count: Option<u32>,
+    ) -> DhtFuture<'_, bool>;
+
+    fn inspect_dht_record(
+        &self,
+        record_key: RecordKey,
+        subkeys: Option<ValueSubkeyRangeSet>,
+        scope: DHTReportScope,
+    ) -> DhtFuture<'_, DHTRecordReport>;
+}
+
+// -------------------------------------------------------------------------
+// The real one
+// -------------------------------------------------------------------------
+
+impl DhtBackend for RoutingContext {
+    fn create_dht_record(
+        &self,
+        kind: CryptoKind,
+        schema: DHTSchema,
+        owner: Option<KeyPair>,
+    ) -> DhtFuture<'_, DHTRecordDescriptor> {
+        Box::pin(RoutingContext::create_dht_record(self, kind, schema, owner))
+    }
+
+    fn get_dht_record_key(&self, schema: DHTSchema, owner: PublicKey) -> DhtFuture<'_, RecordKey> {
+        Box::pin(async move { self.api().get_dht_record_key(schema, owner, None).await })
+    }
+
+    fn open_dht_record(
+        &self,
+        record_key: RecordKey,
+        writer: Option<KeyPair>,
+    ) -> DhtFuture<'_, DHTRecordDescriptor> {
+        Box::pin(RoutingContext::open_dht_record(self, record_key, writer))
+    }
+
+    fn close_dht_record(&self, record_key: RecordKey) -> DhtFuture<'_, ()> {
+        Box::pin(RoutingContext::close_dht_record(self, record_key))
+    }
+
+    fn set_dht_value(
+        &self,
+        record_key: RecordKey,
+        subkey: ValueSubkey,
+        data: Vec<u8>,
+        options: Option<SetDHTValueOptions>,
+    ) -> DhtFuture<'_, Option<ValueData>> {
+        Box::pin(RoutingContext::set_dht_value(self, record_key, subkey, data, options))
+    }
+
+    fn get_dht_value(
+        &self,
+        record_key: RecordKey,
+        subkey: ValueSubkey,
+        force_refresh: bool,
+    ) -> DhtFuture<'_, Option<ValueData>> {
+        Box::pin(RoutingContext::get_dht_value(self, record_key, subkey, force_refresh))
+    }
+
+    fn watch_dht_values(
+        &self,
+        record_key: RecordKey,
+        subkeys: Option<ValueSubkeyRangeSet>,
+        expiration: Option<Timestamp>,
+        count: Option<u32>,
+    ) -> DhtFuture<'_, bool> {
+        Box::pin(RoutingContext::watch_dht_values(
+            self, record_key, subkeys, expiration, count,
+        ))
+    }
+
+    fn inspect_dht_record(
+        &self,
+        record_key: RecordKey,
+        subkeys: Option<ValueSubkeyRangeSet>,
+        scope: DHTReportScope,
+    ) -> DhtFuture<'_, DHTRecordReport> {
+        Box::pin(RoutingContext::inspect_dht_record(self, record_key, subkeys, scope))
+    }
+}
+
+// Veilid's member id for a VLD0 key is the key's own 32 bytes.
+pub fn member_id(key: &PublicKey) -> BareMemberId {
+    BareMemberId::new(key.ref_value())
+}
+
+// -------------------------------------------------------------------------
+// The fake one, compiled for tests only
+// -------------------------------------------------------------------------
+
+#[cfg(test)]
+struct MemoryRecord {
+    owner: KeyPair,
+    schema: DHTSchema,
+    // the writer given when the record was opened, used when a set doesn't name one
+    default_writer: Option<KeyPair>,
+    values: HashMap<ValueSubkey, ValueData>,
+    open: bool,
+    watched: bool,
+}
+
+// An in-memory DHT. Every record lives in one HashMap, so two Dht wrappers
+// sharing an Arc<MemoryDht> see each other's writes like two nodes would.
+// It checks writers the same way Veilid's SMPL/DFLT schemas do.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryDht {
+    records: Mutex<HashMap<RecordKey, MemoryRecord>>,
+}
+
+#[cfg(test)]
+fn not_found(record_key: &RecordKey) -> VeilidAPIError {
+    VeilidAPIError::invalid_argument("MemoryDht", "record_key", record_key.to_string())
+}
+
+#[cfg(test)]
+fn record_key_for(schema: &DHTSchema, owner: &PublicKey) -> RecordKey {
+    let hash = blake3::hash(format!("{owner}/{schema:?}").as_bytes());
+    RecordKey::new(
+        owner.kind(),
+        BareRecordKey::new(BareOpaqueRecordKey::new(hash.as_bytes()), None),
+    )
+}
+
+#[cfg(test)]
+fn descriptor(
+    record_key: &RecordKey,
+    owner: &KeyPair,
+    with_secret: bool,
+    schema: &DHTSchema,
+) -> VeilidAPIResult<DHTRecordDescriptor> {
+    // DHTRecordDescriptor::new is private to veilid-core, but it round-trips through serde.
+    serde_json::from_value(serde_json::json!({
+        "key": record_key,
+        "owner": owner.key(),
+        "owner_secret": with_secret.then(|| owner.secret()),
+        "schema": schema,
+    }))
+    .map_err(VeilidAPIError::internal)
+}
+
+// Who may write a subkey: Ok(()) or the same kind of error Veilid gives.
+#[cfg(test)]
+fn check_writer(schema: &DHTSchema, owner: &PublicKey, subkey: ValueSubkey, writer: &PublicKey) -> VeilidAPIResult<()> {
+    if subkey > schema.max_subkey() {
+        return Err(VeilidAPIError::invalid_argument("set_dht_value", "subkey", subkey));
+    }
+    let (o_cnt, members) = match schema {
+        DHTSchema::DFLT(dflt) => (dflt.o_cnt(), &[][..]),
+        DHTSchema::SMPL(smpl) => (smpl.o_cnt(), smpl.members()),
+    };
+    let mut rest = subkey;
+    let allowed = if rest < ValueSubkey::from(o_cnt) {
+        writer == owner
+    } else {
+        rest -= ValueSubkey::from(o_cnt);
+        let mut allowed = false;
+        for m in members {
+            if rest < ValueSubkey::from(m.m_cnt) {
+                allowed = m.m_key == member_id(writer);
+                break;
+            }
+            rest -= ValueSubkey::from(m.m_cnt);
+        }
+        allowed
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(VeilidAPIError::invalid_argument("set_dht_value", "writer", writer.to_string()))
+    }
+}
+
+#[cfg(test)]
+impl MemoryDht {
+    pub fn new() -> MemoryDht {
+        MemoryDht::default()
+    }
+
+    // Is anyone watching this record? (handy for asserting in tests)
+    pub fn is_watched(&self, record_key: &RecordKey) -> bool {
+        self.records
+            .lock()
+            .unwrap()
+            .get(record_key)
+            .is_some_and(|r| r.watched)
+    }
+
+    // Is the record open? (likewise)
+    pub fn is_open(&self, record_key: &RecordKey) -> bool {
+        self.records
+            .lock()
+            .unwrap()
+            .get(record_key)
+            .is_some_and(|r| r.open)
+    }
+
+    fn create(&self, schema: DHTSchema, owner: Option<KeyPair>) -> VeilidAPIResult<DHTRecordDescriptor> {
+        schema.validate()?;
+        let owner = match owner {
+            Some(kp) => kp,
+            None => Crypto::generate_keypair(CRYPTO_KIND_VLD0)?,
+        };
+        let record_key = record_key_for(&schema, &owner.key());
+        let mut records = self.records.lock().unwrap();
+        if records.contains_key(&record_key) {
+            return Err(VeilidAPIError::generic(format!("record {record_key} already exists")));
+        }
+        let desc = descriptor(&record_key, &owner, true, &schema)?;
+        records.insert(
+            record_key,
+            MemoryRecord {
+                owner: owner.clone(),
+                schema,
+                default_writer: Some(owner),
+                values: HashMap::new(),
+                open: true,
+                watched: false,
+            },
+        );
+        Ok(desc)
+    }
+
+    fn open(&self, record_key: &RecordKey, writer: Option<KeyPair>) -> VeilidAPIResult<DHTRecordDescriptor> {
+        let mut records = self.records.lock().unwrap();
+        let record = records.get_mut(record_key).ok_or_else(|| not_found(record_key))?;
+        record.open = true;
+        record.default_writer = writer;
+        // like Veilid, the owner secret only comes back to whoever opened as the owner
+        let is_owner = record
+            .default_writer
+            .as_ref()
+            .is_some_and(|w| w.key() == record.owner.key());
+        descriptor(record_key, &record.owner, is_owner, &record.schema)
+    }
+
+    fn with_open<T>(
+        &self,
+        record_key: &RecordKey,
+        f: impl FnOnce(&mut MemoryRecord) -> VeilidAPIResult<T>,
+    ) -> VeilidAPIResult<T> {
+        let mut records = self.records.lock().unwrap();
+        let record = records.get_mut(record_key).ok_or_else(|| not_found(record_key))?;
+        if !record.open {
+            return Err(VeilidAPIError::generic(format!("record {record_key} is not open")));
+        }
+        f(record)
+    }
+
+    fn set(
+        &self,
+        record_key: &RecordKey,
+        subkey: ValueSubkey,
+        data: Vec<u8>,
+        options: Option<SetDHTValueOptions>,
+    ) -> VeilidAPIResult<Option<ValueData>> {
+        self.with_open(record_key, |record| {
+            let writer = options
+                .and_then(|o| o.writer)
+                .or_else(|| record.default_writer.clone())
+                .ok_or_else(|| VeilidAPIError::generic("record was not opened with a writer"))?;
+            check_writer(&record.schema, &record.owner.key(), subkey, &writer.key())?;
+
+            let seq = match record.values.get(&subkey) {
+                // writing the same thing again doesn't bump the seq
+                Some(old) if old.data() == data.as_slice() && old.writer() == writer.key() => return Ok(None),
+                Some(old) => old.seq().next()?,
+                None => ValueSeqNum::ZERO,
+            };
+            let value = ValueData::new_with_seq(seq, data, writer.key())?;
+            record.values.insert(subkey, value);
+            Ok(None)
+        })
+    }
+}
+
+#[cfg(test)]
+impl DhtBackend for MemoryDht {
+    fn create_dht_record(
+        &self,
+        _kind: CryptoKind,
+        schema: DHTSchema,
+        owner: Option<KeyPair>,
+    ) -> DhtFuture<'_, DHTRecordDescriptor> {
+        let res = self.create(schema, owner);
+        Box::pin(async move { res })
+    }
+
+    fn get_dht_record_key(&self, schema: DHTSchema, owner: PublicKey) -> DhtFuture<'_, RecordKey> {
+        let res = schema.validate().map(|_| record_key_for(&schema, &owner));
+        Box::pin(async move { res })
+    }
+
+    fn open_dht_record(
+        &self,
+        record_key: RecordKey,
+        writer: Option<KeyPair>,
+    ) -> DhtFuture<'_, DHTRecordDescriptor> {
+        let res = self.open(&record_key, writer);
+        Box::pin(async move { res })
+    }
+
+    fn close_dht_record(&self, record_key: RecordKey) -> DhtFuture<'_, ()> {
+        let res = self.with_open(&record_key, |record| {
+            record.open = false;
+            record.watched = false;
+            Ok(())
+        });
+        Box::pin(async move { res })
+    }
+
+    fn set_dht_value(
+        &self,
+        record_key: RecordKey,
+        subkey: ValueSubkey,
+        data: Vec<u8>,
+        options: Option<SetDHTValueOptions>,
+    ) -> DhtFuture<'_, Option<ValueData>> {
+        let res = self.set(&record_key, subkey, data, options);
+        Box::pin(async move { res })
+    }
+
+    fn get_dht_value(
+        &self,
+        record_key: RecordKey,
+        subkey: ValueSubkey,
+        _force_refresh: bool,
+    ) -> DhtFuture<'_, Option<ValueData>> {
+        let res = self.with_open(&record_key, |record| {
+            if subkey > record.schema.max_subkey() {
+                return Err(VeilidAPIError::invalid_argument("get_dht_value", "subkey", subkey));
+            }
+            Ok(record.values.get(&subkey).cloned())
+        });
+        Box::pin(async move { res })
+    }
+
+    fn watch_dht_values(
+        &self,
+        record_key: RecordKey,
+        _subkeys: Option<ValueSubkeyRangeSet>,
+        _expiration: Option<Timestamp>,
+        count: Option<u32>,
+    ) -> DhtFuture<'_, bool> {
+        let res = self.with_open(&record_key, |record| {
+            // count 0 cancels, same as Veilid
+            record.watched = count != Some(0);
+            Ok(record.watched)
+        });
+        Box::pin(async move { res })
+    }
+
+    fn inspect_dht_record(
+        &self,
+        record_key: RecordKey,
+        _subkeys: Option<ValueSubkeyRangeSet>,
+        _scope: DHTReportScope,
+    ) -> DhtFuture<'_, DHTRecordReport> {
+        // DHTRecordReport can't be built outside veilid-core, so the fake's is always empty.
+        let res = self.with_open(&record_key, |_| Ok(DHTRecordReport::default()));
+        Box::pin(async move { res })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn smpl_with_member(member: &KeyPair) -> DHTSchema {
+        DHTSchema::smpl(
+            2,
+            vec![DHTSchemaSMPLMember {
+                m_key: member_id(&member.key()),
+                m_cnt: 2,
+            }],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn write_then_read() {
+        let dht = MemoryDht::new();
+        let desc = dht
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(1).unwrap(), None)
+            .await
+            .unwrap();
+        assert!(desc.owner_secret().is_some());
+
+        dht.set_dht_value(desc.key(), 0, b"hello".to_vec(), None).await.unwrap();
+        let value = dht.get_dht_value(desc.key(), 0, true).await.unwrap().unwrap();
+        assert_eq!(value.data(), b"hello");
+        assert_eq!(value.seq(), ValueSeqNum::ZERO);
+        assert_eq!(value.writer(), desc.owner());
+
+        dht.set_dht_value(desc.key(), 0, b"again".to_vec(), None).await.unwrap();
+        let value = dht.get_dht_value(desc.key(), 0, true).await.unwrap().unwrap();
+        assert_eq!(value.seq(), ValueSeqNum::from(1));
+    }
+
+    #[tokio::test]
+    async fn record_keys_match_the_plan() {
+        let dht = MemoryDht::new();
+        let owner = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let schema = DHTSchema::dflt(2).unwrap();
+        let planned = dht.get_dht_record_key(schema.clone(), owner.key()).await.unwrap();
+        let desc = dht
+            .create_dht_record(CRYPTO_KIND_VLD0, schema, Some(owner))
+            .await
+            .unwrap();
+        assert_eq!(desc.key(), planned);
+    }
+
+    #[tokio::test]
+    async fn members_only_write_their_own_subkeys() {
+        let dht = MemoryDht::new();
+        let member = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let stranger = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let desc = dht
+            .create_dht_record(CRYPTO_KIND_VLD0, smpl_with_member(&member), None)
+            .await
+            .unwrap();
+        let as_writer = |kp: &KeyPair| {
+            Some(SetDHTValueOptions {
+                writer: Some(kp.clone()),
+                allow_offline: None,
+            })
+        };
+
+        // owner: subkeys 0..2, member: 2..4
+        assert!(dht.set_dht_value(desc.key(), 1, b"o".to_vec(), None).await.is_ok());
+        assert!(dht.set_dht_value(desc.key(), 2, b"o".to_vec(), None).await.is_err());
+        assert!(dht.set_dht_value(desc.key(), 3, b"m".to_vec(), as_writer(&member)).await.is_ok());
+        assert!(dht.set_dht_value(desc.key(), 0, b"m".to_vec(), as_writer(&member)).await.is_err());
+        assert!(dht.set_dht_value(desc.key(), 2, b"s".to_vec(), as_writer(&stranger)).await.is_err());
+        assert!(dht.set_dht_value(desc.key(), 4, b"o".to_vec(), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn closed_records() {
+        let dht = MemoryDht::new();
+        let desc = dht
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(1).unwrap(), None)
+            .await
+            .unwrap();
+
+        dht.close_dht_record(desc.key()).await.unwrap();
+        assert!(dht.get_dht_value(desc.key(), 0, false).await.is_err());
+
+        // opened by someone else: no owner secret, and no default writer
+        let reopened = dht.open_dht_record(desc.key(), None).await.unwrap();
+        assert!(reopened.owner_secret().is_none());
+        assert!(dht.set_dht_value(desc.key(), 0, b"x".to_vec(), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn watches_end_on_cancel_or_close() {
+        let dht = MemoryDht::new();
+        let desc = dht
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(1).unwrap(), None)
+            .await
+            .unwrap();
+
+        assert!(dht.watch_dht_values(desc.key(), None, None, None).await.unwrap());
+        assert!(dht.is_watched(&desc.key()));
+        // a count of 0 cancels
+        assert!(!dht.watch_dht_values(desc.key(), None, None, Some(0)).await.unwrap());
+        assert!(!dht.is_watched(&desc.key()));
+
+        dht.watch_dht_values(desc.key(), None, None, None).await.unwrap();
+        dht.close_dht_record(desc.key()).await.unwrap();
+        assert!(!dht.is_watched(&desc.key()));
+        assert!(!dht.is_open(&desc.key()));
+    }
+}
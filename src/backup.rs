@@ -0,0 +1,424 @@
+use std::fs;
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::exit::Kind;
+use crate::keybundle;
+use crate::keyfile::{self, Capability, Grant, KeyFile};
+use crate::preflight;
+use crate::progress;
+use crate::record::start_node_in;
+use crate::recovery;
+use crate::shortcode::ShortcodeBook;
+use crate::snapshot::SubkeyState;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`record backup <file>` and `record restore <file>`: everything needed to
+//	get a record back on a new machine, sealed with a passphrase.
+//
+//	The backup holds the record key, its schema, every keypair we have for
+//	it (the default node's own, kept in its table store for crash recovery,
+//	and any the key file grants) and every subkey's current value.
+//
+//	It's sealed with VLD0's AEAD under a key derived from the passphrase
+//	(Argon2, with a random salt stored next to it), so the file on its own
+//	is safe to keep on a USB stick or in cloud storage. Lose the passphrase
+//	and the backup is gone too; there's no way around that by design. The
+//	passphrase is typed without echo, or for scripts read from
+//	VEILID_EXAMPLE_BACKUP_PASSPHRASE or the first line of --passphrase-file;
+//	never taken on the command line, where ps and shell history keep it.
+//
+//	Restore re-opens the record as its owner, or, if the DHT has forgotten
+//	it, makes it again: the same owner key and schema give the same record
+//	key. Subkeys the network has lost (or has older values for) are written
+//	back, and owner_keys.txt is rewritten granting the restored keys.
+//
+//	Backup reads the default node's table store, so it starts Veilid in the
+//	default namespace and won't run while the default node does.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const FORMAT: &str = "veilid-example-backup/1";
+const MIN_PASSPHRASE: usize = 8;
+pub const PASSPHRASE_ENV: &str = "VEILID_EXAMPLE_BACKUP_PASSPHRASE";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Backup {
+    pub record: String,
+    pub made_ms: u64,
+    pub schema: DHTSchema,
+    // the record owner's keypair, if we had it
+    #[serde(default)]
+    pub owner: Option<String>,
+    // member keypairs, the default node's first
+    #[serde(default)]
+    pub members: Vec<String>,
+    pub subkeys: Vec<SubkeyState>,
+}
+
+// What's actually on disk.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SealedBackup {
+    pub format: String,
+    pub salt: String,
+    pub nonce: String,
+    // the Backup as JSON, encrypted, base64
+    pub sealed: String,
+}
+
+impl Backup {
+    fn owner_keypair(&self) -> Option<KeyPair> {
+        self.owner.as_deref().and_then(|k| k.parse().ok())
+    }
+
+    fn member_keypairs(&self) -> Vec<KeyPair> {
+        self.members.iter().filter_map(|k| k.parse().ok()).collect()
+    }
+}
+
+// Member subkey ranges of `schema` (inclusive), with whose they are.
+fn member_ranges(schema: &DHTSchema) -> Vec<(BareMemberId, ValueSubkey, ValueSubkey)> {
+    let DHTSchema::SMPL(smpl) = schema else {
+        return Vec::new();
+    };
+    let mut first = ValueSubkey::from(smpl.o_cnt());
+    let mut ranges = Vec::new();
+    for m in smpl.members() {
+        let last = first + ValueSubkey::from(m.m_cnt);
+        if m.m_cnt > 0 {
+            ranges.push((m.m_key.clone(), first, last - 1));
+        }
+        first = last;
+    }
+    ranges
+}
+
+// The keypair we hold that may write `subkey`.
+fn writer_for(
+    veilid: &VeilidAPI,
+    schema: &DHTSchema,
+    owner: Option<&KeyPair>,
+    members: &[KeyPair],
+    subkey: ValueSubkey,
+) -> Option<KeyPair> {
+    let o_cnt = match schema {
+        DHTSchema::DFLT(dflt) => dflt.o_cnt(),
+        DHTSchema::SMPL(smpl) => smpl.o_cnt(),
+    };
+    if subkey < ValueSubkey::from(o_cnt) {
+        return owner.cloned();
+    }
+    let (id, _, _) = member_ranges(schema)
+        .into_iter()
+        .find(|(_, first, last)| (*first..=*last).contains(&subkey))?;
+    members
+        .iter()
+        .find(|kp| veilid.generate_member_id(&kp.key()).is_ok_and(|m| m.into_value() == id))
+        .cloned()
+}
+
+pub fn seal(veilid: &VeilidAPI, passphrase: &str, backup: &Backup) -> Result<SealedBackup, String> {
+    let crypto = veilid.crypto().map_err(|e| e.to_string())?;
+    let vcrypto = crypto.get(CRYPTO_KIND_VLD0).ok_or("VLD0 crypto isn't available")?;
+    let salt: Vec<u8> = (0..vcrypto.default_salt_length()).map(|_| rand::random::<u8>()).collect();
+    let key = vcrypto
+        .derive_shared_secret(passphrase.as_bytes(), &salt)
+        .map_err(|e| e.to_string())?;
+    let nonce = vcrypto.random_nonce();
+    let plain = serde_json::to_vec(backup).map_err(|e| e.to_string())?;
+    let sealed = vcrypto
+        .encrypt_aead(&plain, &nonce, &key, Some(FORMAT.as_bytes()))
+        .map_err(|e| e.to_string())?;
+    Ok(SealedBackup {
+        format: FORMAT.to_string(),
+        salt: BASE64.encode(salt),
+        nonce: nonce.to_string(),
+        sealed: BASE64.encode(sealed),
+    })
+}
+
+pub fn open(veilid: &VeilidAPI, passphrase: &str, file: &SealedBackup) -> Result<Backup, Box<dyn std::error::Error>> {
+    let crypto = veilid.crypto().map_err(|e| e.to_string())?;
+    let vcrypto = crypto.get(CRYPTO_KIND_VLD0).ok_or("VLD0 crypto isn't available")?;
+    let salt = BASE64.decode(&file.salt).map_err(|e| format!("bad salt: {e}"))?;
+    let nonce: Nonce = file.nonce.parse().map_err(|e| format!("bad nonce: {e}"))?;
+    let sealed = BASE64.decode(&file.sealed).map_err(|e| format!("bad contents: {e}"))?;
+    let key = vcrypto
+        .derive_shared_secret(passphrase.as_bytes(), &salt)
+        .map_err(|e| e.to_string())?;
+    let plain = vcrypto
+        .decrypt_aead(&sealed, &nonce, &key, Some(file.format.as_bytes()))
+        .map_err(|_| Kind::Credential.fail("wrong passphrase (or the file has been changed)"))?;
+    Ok(serde_json::from_slice(&plain).map_err(|e| format!("the backup opened but doesn't make sense: {e}"))?)
+}
+
+pub fn read_file(path: &Path) -> Result<SealedBackup, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("can't read {}: {e}", path.to_string_lossy()))?;
+    let file: SealedBackup =
+        serde_json::from_str(&text).map_err(|_| format!("{} isn't a record backup", path.to_string_lossy()))?;
+    if file.format != FORMAT {
+        return Err(format!("{} is a '{}' backup, this build reads {FORMAT}", path.to_string_lossy(), file.format));
+    }
+    Ok(file)
+}
+
+// From VEILID_EXAMPLE_BACKUP_PASSPHRASE, the first line of --passphrase-file,
+// or asked for without echo (twice, when making a backup).
+fn passphrase(file: Option<&Path>, confirm: bool) -> Result<String, String> {
+    let ask = |prompt: &str| rpassword::prompt_password(prompt).map_err(|e| e.to_string());
+    let pass = if let Ok(pass) = std::env::var(PASSPHRASE_ENV) {
+        pass
+    } else if let Some(file) = file {
+        let text = fs::read_to_string(file).map_err(|e| format!("couldn't read {}: {e}", file.to_string_lossy()))?;
+        text.lines().next().unwrap_or_default().to_string()
+    } else {
+        let pass = ask("Passphrase: ")?;
+        if confirm && ask("Again: ")? != pass {
+            return Err("the passphrases don't match".to_string());
+        }
+        pass
+    };
+    if confirm && pass.chars().count() < MIN_PASSPHRASE {
+        return Err(format!("use a passphrase of at least {MIN_PASSPHRASE} characters"));
+    }
+    Ok(pass)
+}
+
+// -------------------------------------------------------------------------
+// record backup <file> [--passphrase-file PATH]
+// -------------------------------------------------------------------------
+
+pub async fn backup(
+    file: &Path,
+    given: Option<&Path>,
+    options: &Options,
+    config: &AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    if let Some(pid) = preflight::running_pid(&data_dir, &config.default_namespace) {
+        return Err(format!("the default node (pid {pid}) is running; stop it first, the backup reads its table store").into());
+    }
+    let keys = keybundle::load(&data_dir)?;
+    let pass = passphrase(given, true)?;
+
+    let (veilid, rc) = start_node_in(options, config, &config.default_namespace).await?;
+
+    // the default node's own keys, and whatever the key file hands out
+    let mut owner = keys.grant.owner.clone();
+    let mut members: Vec<KeyPair> = Vec::new();
+    if let Some(writers) = recovery::load_writers(&veilid, &keys.record_key)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|w| w.parse())
+    {
+        owner = Some(writers.record_owner);
+        members.push(writers.member);
+        members.extend(writers.drop);
+    }
+    if let Some(writer) = keys.grant.writer.clone().filter(|w| !members.contains(w)) {
+        members.push(writer);
+    }
+
+    let desc = rc.open_dht_record(keys.record_key.clone(), owner.clone()).await?;
+    let schema = desc.schema();
+    let mut subkeys = Vec::new();
+    let bar = progress::subkeys(u64::from(schema.max_subkey()) + 1, "Reading");
+    for subkey in 0..=schema.max_subkey() {
+        match rc.get_dht_value(keys.record_key.clone(), subkey, true).await {
+            Ok(Some(v)) => subkeys.push(SubkeyState::new(subkey, &v)),
+            Ok(None) => {}
+            Err(e) => bar.suspend(|| println!("  subkey {subkey}: unreadable ({e}), left out")),
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+    let _ = rc.close_dht_record(keys.record_key.clone()).await;
+
+    let contents = Backup {
+        record: keys.record_key.to_string(),
+        made_ms: crate::audit::now_ms() as u64,
+        schema,
+        owner: owner.as_ref().map(|o| o.to_string()),
+        members: members.iter().map(|m| m.to_string()).collect(),
+        subkeys,
+    };
+    let sealed = seal(&veilid, &pass, &contents);
+    veilid.shutdown().await;
+    fs::write(file, serde_json::to_string_pretty(&sealed?)?)?;
+
+    println!(
+        "Backed up record {} ({} subkey value(s), {} keypair(s)) to {}",
+        keys.record_key,
+        contents.subkeys.len(),
+        usize::from(contents.owner.is_some()) + contents.members.len(),
+        file.to_string_lossy()
+    );
+    if contents.owner.is_none() {
+        println!("There was no owner key to back up, so a restore can't remake the record if the DHT forgets it.");
+    }
+    println!("Keep the passphrase somewhere else: without it the backup can't be opened.");
+    Ok(())
+}
+
+// -------------------------------------------------------------------------
+// record restore <file> [--passphrase-file PATH]
+// -------------------------------------------------------------------------
+
+pub async fn restore(
+    file: &Path,
+    given: Option<&Path>,
+    options: &Options,
+    config: &AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    let sealed = read_file(file)?;
+    let pass = passphrase(given, false)?;
+
+    let (veilid, rc) = start_node_in(options, config, &crate::node::tool_namespace(config)).await?;
+    let contents = match open(&veilid, &pass, &sealed) {
+        Ok(contents) => contents,
+        Err(e) => {
+            veilid.shutdown().await;
+            return Err(e);
+        }
+    };
+    let key: RecordKey = contents.record.parse()?;
+    let owner = contents.owner_keypair();
+    let members = contents.member_keypairs();
+    println!("Backup of {key} from {}s ago", (crate::audit::now_ms() as u64).saturating_sub(contents.made_ms) / 1000);
+
+    // the same owner and schema make the same record, if the DHT has let it go
+    let desc = match rc.open_dht_record(key.clone(), owner.clone()).await {
+        Ok(desc) => desc,
+        Err(e) => {
+            let Some(owner) = owner.clone() else {
+                veilid.shutdown().await;
+                return Err(format!("couldn't open the record ({e}) and the backup has no owner key to remake it with").into());
+            };
+            println!("Couldn't open the record ({e}), making it again");
+            let desc = rc.create_dht_record(CRYPTO_KIND_VLD0, contents.schema.clone(), Some(owner)).await?;
+            if desc.key() != key && !rc.is_dry_run() {
+                println!("The remade record has a different key ({}); its values go there", desc.key());
+            }
+            desc
+        }
+    };
+    let restored_key = desc.key();
+
+    let (mut written, mut current, mut no_key) = (0, 0, 0);
+    let bar = progress::subkeys(contents.subkeys.len() as u64, "Restoring");
+    for state in &contents.subkeys {
+        let on_network = rc.get_dht_value(restored_key.clone(), state.subkey, true).await.ok().flatten();
+        if on_network.is_some_and(|v| v.seq().to_option() >= state.seq) {
+            current += 1;
+        } else if let Some(writer) = writer_for(&veilid, &contents.schema, owner.as_ref(), &members, state.subkey) {
+            let data = BASE64.decode(&state.data)?;
+            let opts = SetDHTValueOptions {
+                writer: Some(writer),
+                allow_offline: None,
+            };
+            match rc.set_dht_value(restored_key.clone(), state.subkey, data, Some(opts)).await {
+                Ok(_) => written += 1,
+                Err(e) => bar.suspend(|| println!("  subkey {}: couldn't write it back ({e})", state.subkey)),
+            }
+        } else {
+            no_key += 1;
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+    let _ = rc.close_dht_record(restored_key.clone()).await;
+
+    // the key file, so the alt node (and the next backup) can use the keys again
+    let mut grant = Grant {
+        owner: owner.clone(),
+        ..Grant::default()
+    };
+    if let Some(first) = members.first() {
+        let range = member_ranges(&contents.schema).into_iter().find(|(id, _, _)| {
+            veilid.generate_member_id(&first.key()).is_ok_and(|m| m.into_value() == *id)
+        });
+        if let Some((_, from, to)) = range {
+            grant.writer = Some(first.clone());
+            grant.writer_subkeys = Some((from, to));
+        }
+    }
+    grant.claimed = grant.capabilities();
+    veilid.shutdown().await;
+
+    let code = if rc.is_dry_run() {
+        crate::shortcode::shortcode(&restored_key)
+    } else {
+        let code = ShortcodeBook::load(&data_dir)?.remember(&restored_key)?;
+        keyfile::save(&data_dir, &KeyFile {
+            record_key: restored_key.clone(),
+            shortcode: Some(code.clone()),
+            alt: config.join_preset(),
+            grant: grant.clone(),
+            expect: crate::schema::expectation(&desc.schema(), &desc.owner(), &grant),
+        })?;
+        code
+    };
+
+    println!("Restored record {code}: {written} subkey(s) written back, {current} already current");
+    if no_key > 0 {
+        println!("{no_key} subkey(s) couldn't be written back: the backup has no key for them");
+    }
+    println!("{} written; it grants {}", keyfile::FILE_NAME, grant.describe());
+    if grant.capabilities().contains(&Capability::Admin) {
+        println!("It holds the owner's key, keep it as safe as the backup.");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backup_contents_round_trip_and_member_ranges_line_up() {
+        let owner = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let member = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let ids = [BareMemberId::new(&[1; 32]), BareMemberId::new(&[2; 32])];
+        let schema = DHTSchema::smpl(
+            2,
+            vec![
+                DHTSchemaSMPLMember { m_key: ids[0].clone(), m_cnt: 3 },
+                DHTSchemaSMPLMember { m_key: ids[1].clone(), m_cnt: 4 },
+            ],
+        )
+        .unwrap();
+        assert_eq!(member_ranges(&schema), vec![(ids[0].clone(), 2, 4), (ids[1].clone(), 5, 8)]);
+        assert!(member_ranges(&DHTSchema::dflt(3).unwrap()).is_empty());
+
+        let backup = Backup {
+            record: "VLD0:x".to_string(),
+            made_ms: 1,
+            schema,
+            owner: Some(owner.to_string()),
+            members: vec![member.to_string(), "not a key".to_string()],
+            subkeys: Vec::new(),
+        };
+        let back: Backup = serde_json::from_slice(&serde_json::to_vec(&backup).unwrap()).unwrap();
+        assert_eq!(back, backup);
+        assert_eq!(back.owner_keypair(), Some(owner));
+        assert_eq!(back.member_keypairs(), vec![member]);
+
+        let dir = std::env::temp_dir().join(format!("backup-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("b.json");
+        fs::write(&path, r#"{"format": "something-else/9", "salt": "", "nonce": "", "sealed": ""}"#).unwrap();
+        assert!(read_file(&path).unwrap_err().contains("something-else/9"));
+        fs::write(&path, "owner_keys").unwrap();
+        assert!(read_file(&path).unwrap_err().contains("isn't a record backup"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,408 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinSet;
+use veilid_core::*;
+
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::dht::Dht;
+use crate::envelope::Envelope;
+use crate::node::VeilidNode;
+use crate::progress;
+use crate::record::FreshWriters;
+use crate::watch::{DecodedChange, WatchSet};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`record bench [--rounds N]`: the same number of subkeys laid out three
+//	ways, timed side by side, to help pick a schema shape for an app.
+//
+//	  dflt 16      one writer (the owner) for all 16 subkeys
+//	  smpl 4x4     four members with four subkeys each, no owner subkeys
+//	  smpl 16x1    sixteen members with one subkey each
+//
+//	Two nodes are started in this process (<default_namespace>-bench-a and
+//	-b). A makes a record of each shape and writes; B opens them with no
+//	writer, watches the whole record and reads, as a reader of the app
+//	would. All the shapes run at once, so they see the same network.
+//
+//	Each round writes every subkey (BENCH_WINDOW writes in flight, each
+//	signed by the subkey's writer), waits up to WATCH_WAIT for B to hear
+//	about each one, then has B read every subkey back with force_refresh.
+//	A watch is timed from when its write went out, so it can come in under
+//	the write itself. What isn't heard about in time counts as missed.
+//
+//	The records are left behind, like `record access` leaves its one.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub const DEFAULT_ROUNDS: u32 = 3;
+const BENCH_WINDOW: usize = 8;
+const WATCH_WAIT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    // one writer for this many subkeys
+    Dflt(u16),
+    // this many members with `each` subkeys apiece
+    Smpl { members: u16, each: u16 },
+}
+
+const SHAPES: [Shape; 3] = [
+    Shape::Dflt(16),
+    Shape::Smpl { members: 4, each: 4 },
+    Shape::Smpl { members: 16, each: 1 },
+];
+
+impl Shape {
+    pub fn name(self) -> String {
+        match self {
+            Shape::Dflt(count) => format!("dflt {count}"),
+            Shape::Smpl { members, each } => format!("smpl {members}x{each}"),
+        }
+    }
+
+    pub fn writers(self) -> u16 {
+        match self {
+            Shape::Dflt(_) => 1,
+            Shape::Smpl { members, .. } => members,
+        }
+    }
+
+    fn fresh_writers(self, veilid: &VeilidAPI) -> VeilidAPIResult<FreshWriters> {
+        match self {
+            Shape::Dflt(count) => FreshWriters::for_schema(veilid, &DHTSchema::dflt(count)?),
+            Shape::Smpl { members, each } => FreshWriters::for_shape(veilid, 0, &vec![each; usize::from(members)]),
+        }
+    }
+}
+
+// A call on one subkey, and how long it took if it worked.
+type Timed = (ValueSubkey, VeilidAPIResult<Duration>);
+
+// How long one kind of call took, each time it worked.
+#[derive(Default)]
+pub struct Latencies {
+    pub times: Vec<Duration>,
+    // failed outright, or (for watches) never heard about
+    pub missed: u32,
+}
+
+impl Latencies {
+    // The time `pct`% of the calls came in under (nearest rank).
+    pub fn percentile(&self, pct: usize) -> Option<Duration> {
+        let mut sorted = self.times.clone();
+        sorted.sort();
+        let rank = (sorted.len() * pct).div_ceil(100).max(1);
+        sorted.get(rank - 1).copied()
+    }
+
+    // "p50/p95" in milliseconds, and how many were missed if any.
+    fn cell(&self) -> String {
+        let ms = |pct| self.percentile(pct).map_or("-".to_string(), |d| d.as_millis().to_string());
+        let mut text = format!("{}/{}", ms(50), ms(95));
+        if self.missed > 0 {
+            text.push_str(&format!(" ({} missed)", self.missed));
+        }
+        text
+    }
+
+    // Note how a call went; a subkey whose call failed comes back.
+    fn tally(&mut self, done: Result<Timed, tokio::task::JoinError>) -> Option<ValueSubkey> {
+        match done {
+            Ok((_, Ok(took))) => {
+                self.times.push(took);
+                None
+            }
+            Ok((subkey, Err(_))) => {
+                self.missed += 1;
+                Some(subkey)
+            }
+            Err(_) => {
+                self.missed += 1;
+                None
+            }
+        }
+    }
+}
+
+pub struct ShapeReport {
+    pub shape: Shape,
+    pub record: Option<RecordKey>,
+    // why the record couldn't be made or opened, if it couldn't
+    pub error: Option<String>,
+    pub writes: Latencies,
+    pub reads: Latencies,
+    pub watches: Latencies,
+}
+
+impl ShapeReport {
+    fn new(shape: Shape) -> ShapeReport {
+        ShapeReport {
+            shape,
+            record: None,
+            error: None,
+            writes: Latencies::default(),
+            reads: Latencies::default(),
+            watches: Latencies::default(),
+        }
+    }
+}
+
+pub struct BenchReport {
+    pub rounds: u32,
+    pub shapes: Vec<ShapeReport>,
+}
+
+impl BenchReport {
+    // The shape with the lowest median for one kind of call.
+    fn fastest(&self, which: impl Fn(&ShapeReport) -> &Latencies) -> Option<String> {
+        self.shapes
+            .iter()
+            .filter_map(|s| which(s).percentile(50).map(|p50| (p50, s.shape.name())))
+            .min()
+            .map(|(_, name)| name)
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Schema shapes, {} round(s), p50/p95 in ms:", self.rounds)?;
+        writeln!(f, "{:<12} {:>7}  {:<18} {:<18} {:<18}", "shape", "writers", "write", "read", "watch")?;
+        for s in &self.shapes {
+            match &s.error {
+                Some(e) => writeln!(f, "{:<12} {:>7}  not run: {e}", s.shape.name(), s.shape.writers())?,
+                None => writeln!(
+                    f,
+                    "{:<12} {:>7}  {:<18} {:<18} {:<18}",
+                    s.shape.name(),
+                    s.shape.writers(),
+                    s.writes.cell(),
+                    s.reads.cell(),
+                    s.watches.cell()
+                )?,
+            }
+        }
+        for (what, fastest) in [
+            ("writes", self.fastest(|s| &s.writes)),
+            ("reads", self.fastest(|s| &s.reads)),
+            ("watches", self.fastest(|s| &s.watches)),
+        ] {
+            if let Some(name) = fastest {
+                writeln!(f, "fastest {what}: {name}")?;
+            }
+        }
+        write!(
+            f,
+            "One writer (dflt) means one key to share and one signer; members (smpl) let each\n\
+             writer keep its own key, and a missed or slow row says what that costs here."
+        )
+    }
+}
+
+// One shape's rounds, against a record A made and B is watching.
+async fn run_shape(
+    mut report: ShapeReport,
+    writers: FreshWriters,
+    rc_a: Dht,
+    rc_b: Dht,
+    changes: WatchSet,
+    rounds: u32,
+) -> ShapeReport {
+    let Some(key) = report.record.clone() else {
+        return report;
+    };
+    let max_subkey = writers.schema.max_subkey();
+
+    // stamp changes as they come in, so the time spent writing doesn't count against them
+    let (tx, arrivals) = flume::unbounded::<(Instant, DecodedChange)>();
+    let stamper = tokio::spawn(async move {
+        while let Some(change) = changes.next().await {
+            if tx.send((Instant::now(), change)).is_err() {
+                break;
+            }
+        }
+    });
+
+    for round in 1..=rounds {
+        // anything late from the last round isn't this round's
+        arrivals.drain();
+
+        let mut sent = HashMap::new();
+        let mut in_flight = JoinSet::new();
+        for subkey in 0..=max_subkey {
+            if in_flight.len() >= BENCH_WINDOW {
+                if let Some(done) = in_flight.join_next().await {
+                    if let Some(failed) = report.writes.tally(done) {
+                        sent.remove(&failed);
+                    }
+                }
+            }
+            let (rc, key) = (rc_a.clone(), key.clone());
+            let options = SetDHTValueOptions {
+                writer: Some(writers.writer_for(subkey).clone()),
+                allow_offline: None,
+            };
+            let data = Envelope::text(&format!("bench round {round}/{rounds}")).encode();
+            sent.insert(subkey, Instant::now());
+            in_flight.spawn(async move {
+                let start = Instant::now();
+                (subkey, rc.set_dht_value(key, subkey, data, Some(options)).await.map(|_| start.elapsed()))
+            });
+        }
+        while let Some(done) = in_flight.join_next().await {
+            if let Some(failed) = report.writes.tally(done) {
+                sent.remove(&failed);
+            }
+        }
+
+        let mut unheard: BTreeSet<ValueSubkey> = sent.keys().copied().collect();
+        let deadline = tokio::time::Instant::now() + WATCH_WAIT;
+        while !unheard.is_empty() {
+            let Ok(Ok((at, change))) = tokio::time::timeout_at(deadline, arrivals.recv_async()).await else {
+                break;
+            };
+            for subkey in change.subkeys.iter() {
+                if let Some(&went_out) = sent.get(&subkey).filter(|&&t| at >= t) {
+                    if unheard.remove(&subkey) {
+                        report.watches.times.push(at - went_out);
+                    }
+                }
+            }
+        }
+        report.watches.missed += unheard.len() as u32;
+
+        let mut in_flight = JoinSet::new();
+        for subkey in 0..=max_subkey {
+            if in_flight.len() >= BENCH_WINDOW {
+                if let Some(done) = in_flight.join_next().await {
+                    report.reads.tally(done);
+                }
+            }
+            let (rc, key) = (rc_b.clone(), key.clone());
+            in_flight.spawn(async move {
+                let start = Instant::now();
+                (subkey, rc.get_dht_value(key, subkey, true).await.map(|_| start.elapsed()))
+            });
+        }
+        while let Some(done) = in_flight.join_next().await {
+            report.reads.tally(done);
+        }
+    }
+
+    stamper.abort();
+    let _ = rc_b.close_dht_record(key.clone()).await;
+    let _ = rc_a.close_dht_record(key).await;
+    report
+}
+
+pub async fn run(rounds: u32, options: &Options, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    let ns_a = format!("{}-bench-a", config.default_namespace);
+    let ns_b = format!("{}-bench-b", config.default_namespace);
+    let node_a = progress::spin("Attaching node A (writes)", VeilidNode::start_attached(config, &data_dir, &ns_a, |_| {})).await?;
+    let node_b = progress::spin("Attaching node B (reads and watches)", VeilidNode::start_attached(config, &data_dir, &ns_b, |_| {})).await?;
+    let rc_a = Dht::new(node_a.routing_context().get(), None, false, options.chaos.clone());
+    let rc_b = Dht::new(node_b.routing_context().get(), None, false, options.chaos.clone());
+
+    // make every shape's record at once
+    let mut creating = JoinSet::new();
+    for (index, shape) in SHAPES.into_iter().enumerate() {
+        let writers = shape.fresh_writers(node_a.api())?;
+        let rc = rc_a.clone();
+        creating.spawn(async move {
+            let made = rc.create_dht_record(CRYPTO_KIND_VLD0, writers.schema.clone(), Some(writers.owner.clone())).await;
+            (index, shape, writers, made)
+        });
+    }
+    let bar = progress::spinner("Creating a record of each shape");
+    let mut made = Vec::new();
+    while let Some(done) = creating.join_next().await {
+        made.push(done?);
+    }
+    bar.finish_and_clear();
+    made.sort_by_key(|(index, ..)| *index);
+
+    let mut running = JoinSet::new();
+    for (index, shape, writers, created) in made {
+        let mut report = ShapeReport::new(shape);
+        let changes = WatchSet::new();
+        let opened = match created {
+            Ok(desc) => {
+                let key = desc.key();
+                println!("{:<12} {key}", shape.name());
+                // B joins the way a reader of the app would: no writer, watching everything
+                match rc_b.open_dht_record(key.clone(), None).await {
+                    Ok(_) => node_b.watch(&changes, key.clone(), ValueSubkeyRangeSet::full()).await.map(|()| key),
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        };
+        match opened {
+            Ok(key) => report.record = Some(key),
+            Err(e) => report.error = Some(e.to_string()),
+        }
+        let (rc_a, rc_b) = (rc_a.clone(), rc_b.clone());
+        running.spawn(async move { (index, run_shape(report, writers, rc_a, rc_b, changes, rounds).await) });
+    }
+    let bar = progress::spinner(&format!("Running {rounds} round(s) on every shape"));
+    let mut shapes = Vec::new();
+    while let Some(done) = running.join_next().await {
+        shapes.push(done?);
+    }
+    bar.finish_and_clear();
+    shapes.sort_by_key(|(index, _)| *index);
+
+    let report = BenchReport {
+        rounds,
+        shapes: shapes.into_iter().map(|(_, s)| s).collect(),
+    };
+    println!();
+    println!("{report}");
+
+    node_b.shutdown().await;
+    node_a.shutdown().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(list: &[u64]) -> Latencies {
+        Latencies {
+            times: list.iter().map(|&m| Duration::from_millis(m)).collect(),
+            missed: 0,
+        }
+    }
+
+    #[test]
+    fn shapes_are_compared_by_their_medians() {
+        let latencies = ms(&[40, 10, 30, 20, 100]);
+        assert_eq!(latencies.percentile(50), Some(Duration::from_millis(30)));
+        assert_eq!(latencies.percentile(95), Some(Duration::from_millis(100)));
+        assert_eq!(Latencies::default().percentile(50), None);
+
+        let mut slow = ShapeReport::new(Shape::Dflt(16));
+        slow.writes = ms(&[300, 500]);
+        slow.watches = Latencies { missed: 2, ..ms(&[900]) };
+        let mut quick = ShapeReport::new(Shape::Smpl { members: 4, each: 4 });
+        quick.writes = ms(&[100, 200]);
+        let mut broken = ShapeReport::new(Shape::Smpl { members: 16, each: 1 });
+        broken.error = Some("no peers".to_string());
+        let report = BenchReport {
+            rounds: 1,
+            shapes: vec![slow, quick, broken],
+        };
+
+        let text = report.to_string();
+        assert!(text.contains("dflt 16            1  300/500            -/-                900/900 (2 missed)"));
+        assert!(text.contains("smpl 16x1         16  not run: no peers"));
+        assert!(text.contains("fastest writes: smpl 4x4"));
+        assert!(text.contains("fastest watches: dflt 16"));
+        assert!(!text.contains("fastest reads"));
+    }
+}
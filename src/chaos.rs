@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use rand::Rng;
+use veilid_core::*;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Fault injection ("--chaos") for resilience testing.
+//
+//	The Dht wrapper asks us before every call whether to slow it down or fail
+//	it outright, and a background task keeps knocking the node off the
+//	network and back on again. This lets you see how the rest of the example
+//	copes with a bad network without needing one.
+//
+//	--inject-latency MIN[..MAX] is the gentle version for development: no
+//	failures and no reattaching, just every call's result held back a while
+//	(and quietly), so spinners and timeouts can be worked on over a LAN that
+//	answers in a few milliseconds. It can be added to --chaos as well.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+pub struct ChaosConfig {
+    // percentage (0-100) of DHT calls that fail with TryAgain
+    pub fail_pct: u32,
+    // percentage (0-100) of DHT calls that get an extra delay first
+    pub delay_pct: u32,
+    pub max_delay: Duration,
+    // how often to force a detach/attach cycle (None = never)
+    pub reattach_every: Option<Duration>,
+    // --inject-latency: added after every call, whatever the dice say
+    pub injected: Option<InjectedLatency>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InjectedLatency {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl InjectedLatency {
+    // "800ms" or "200ms..2s"
+    pub fn parse(text: &str) -> Result<InjectedLatency, String> {
+        let duration = |t: &str| {
+            crate::expect::parse_duration(t.trim())
+                .ok_or_else(|| format!("--inject-latency expects a time like 800ms or a range like 200ms..2s, got '{text}'"))
+        };
+        let (min, max) = match text.split_once("..") {
+            Some((min, max)) => (duration(min)?, duration(max)?),
+            None => (duration(text)?, duration(text)?),
+        };
+        if min > max {
+            return Err(format!("--inject-latency range runs backwards: {text}"));
+        }
+        Ok(InjectedLatency { min, max })
+    }
+
+    fn pick(&self) -> Duration {
+        if self.min == self.max {
+            return self.min;
+        }
+        rand::thread_rng().gen_range(self.min..=self.max)
+    }
+}
+
+impl ChaosConfig {
+    pub fn with_pct(pct: u32) -> ChaosConfig {
+        ChaosConfig {
+            fail_pct: pct,
+            delay_pct: pct,
+            max_delay: Duration::from_secs(3),
+            reattach_every: Some(Duration::from_secs(120)),
+            injected: None,
+        }
+    }
+
+    // Nothing but --inject-latency.
+    pub fn latency_only(latency: InjectedLatency) -> ChaosConfig {
+        ChaosConfig {
+            fail_pct: 0,
+            delay_pct: 0,
+            max_delay: Duration::ZERO,
+            reattach_every: None,
+            injected: Some(latency),
+        }
+    }
+
+    // Whether there's anything beyond injected latency going on, for the banner.
+    pub fn is_chaotic(&self) -> bool {
+        self.fail_pct > 0 || self.delay_pct > 0 || self.reattach_every.is_some()
+    }
+
+    // Called by the Dht wrapper before it makes a real call.
+    pub async fn before_call(&self, op: &str) -> VeilidAPIResult<()> {
+        // Roll the dice up front, the rng can't be held across an await.
+        let (delay, fail) = {
+            let mut rng = rand::thread_rng();
+            let delay = if rng.gen_range(0..100) < self.delay_pct {
+                let max_ms = self.max_delay.as_millis().max(1) as u64;
+                Some(Duration::from_millis(rng.gen_range(0..max_ms)))
+            } else {
+                None
+            };
+            (delay, rng.gen_range(0..100) < self.fail_pct)
+        };
+
+        if let Some(delay) = delay {
+            println!("[chaos] delaying {op} by {}ms", delay.as_millis());
+            tokio::time::sleep(delay).await;
+        }
+        if fail {
+            println!("[chaos] failing {op}");
+            return Err(VeilidAPIError::try_again(format!("chaos: injected failure in {op}")));
+        }
+        Ok(())
+    }
+
+    // Called by the Dht wrapper once the real call is done, before it hands
+    // the result back.
+    pub async fn after_call(&self) {
+        if let Some(latency) = &self.injected {
+            tokio::time::sleep(latency.pick()).await;
+        }
+    }
+}
+
+// Periodically detach from the network and attach again.
+pub fn spawn_reattach_cycles(veilid: VeilidAPI, every: Duration) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(every).await;
+            if veilid.is_shutdown() {
+                break;
+            }
+
+            println!("[chaos] forcing detach");
+            if let Err(e) = veilid.detach().await {
+                eprintln!("[chaos] detach failed: {e}");
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+
+            println!("[chaos] re-attaching");
+            if let Err(e) = veilid.attach().await {
+                eprintln!("[chaos] attach failed: {e}");
+            }
+        }
+    });
+}
@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::profile::ProfileCard;
+use crate::route::RouteValue;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`:msg <text>` at either prompt: a line of chat straight to the other
+//	node over app_message, nothing written to the DHT.
+//
+//	Each side needs the other's private route first, and gets it through
+//	the record:
+//
+//	  default node   puts its profile card, route and all, in the metadata
+//	                 block (subkey 0) when it makes the record, and a route
+//	                 it keeps alive in a subkey of its own (see route.rs)
+//	  alt node       reads them from there when it joins, and sends its own
+//	                 card back in a Hello (see profile.rs), since it may
+//	                 have joined with no subkey to write
+//
+//	A message carries the sender's card too, so whoever gets one can
+//	answer even if the cards crossed. The peer is whoever we last heard
+//	from, so a second alt node joining takes over the default node's end.
+//	Lines arrive as "[chat alice] ..." wherever the prompt is.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// So a chat message can be told apart from cards, logs and anything else.
+const CHAT_MAGIC: &[u8] = b"VXCHAT1\n";
+// well inside app_message's 32k, with room for the card
+const MAX_TEXT: usize = 4096;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatMessage {
+    pub from: ProfileCard,
+    pub text: String,
+}
+
+impl ChatMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = CHAT_MAGIC.to_vec();
+        out.extend(serde_json::to_vec(self).expect("chat message serializes"));
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Option<ChatMessage> {
+        serde_json::from_slice(data.strip_prefix(CHAT_MAGIC)?).ok()
+    }
+}
+
+// Quick check for the update callback, before any decoding.
+pub fn is_chat_message(data: &[u8]) -> bool {
+    data.starts_with(CHAT_MAGIC)
+}
+
+// Who `:msg` goes to, and what it needs to get there.
+pub struct Chat {
+    api: VeilidAPI,
+    rc: RoutingContext,
+    // the peer's nickname and route blob
+    peer: Option<(String, Vec<u8>)>,
+    // the same for the route the record's owner keeps in its record, used
+    // over any card's since it's renewed when it dies
+    published: Option<(String, Vec<u8>)>,
+}
+
+impl Chat {
+    pub fn new(api: VeilidAPI, rc: RoutingContext) -> Chat {
+        Chat {
+            api,
+            rc,
+            peer: None,
+            published: None,
+        }
+    }
+
+    // Talk to the node on `card` from now on, if it's signed and has a route.
+    pub fn set_peer(&mut self, card: &ProfileCard) {
+        if let (Ok(()), Some(blob)) = (card.verify(), card.route_blob()) {
+            self.peer = Some((card.nickname.clone(), blob));
+        }
+    }
+
+    // Talk over the route the record's owner published in it from now on.
+    pub fn set_route(&mut self, published: &RouteValue) {
+        if let Some(blob) = published.route_blob() {
+            self.published = Some((published.nickname.clone(), blob));
+        }
+    }
+
+    // `:msg <text>` at the prompt. Gives back what to print.
+    pub async fn send(&self, text: &str, mine: &ProfileCard) -> String {
+        let text = text.trim();
+        if text.is_empty() {
+            return "Usage: :msg <text>".to_string();
+        }
+        if text.len() > MAX_TEXT {
+            return format!("That's {} bytes; a message can be {MAX_TEXT} at most", text.len());
+        }
+        let Some((name, blob)) = self.published.as_ref().or(self.peer.as_ref()) else {
+            return "No one to talk to yet: the other node's route comes with its profile card when one of us joins the other's record".to_string();
+        };
+        if mine.route.is_none() {
+            return "We have no private route for an answer to come back on".to_string();
+        }
+        let msg = ChatMessage {
+            from: mine.clone(),
+            text: text.to_string(),
+        };
+        let sent = match self.api.import_remote_private_route(blob.clone()) {
+            Ok(route) => self.rc.app_message(Target::RouteId(route), msg.encode()).await,
+            Err(e) => Err(e),
+        };
+        match sent {
+            Ok(()) => format!("[chat -> {name}] {text}"),
+            Err(e) => format!("Couldn't reach {name}: {e}"),
+        }
+    }
+
+    // A chat message came in. Gives back what to print.
+    pub fn receive(&mut self, data: &[u8]) -> String {
+        let Some(msg) = ChatMessage::decode(data) else {
+            return "[chat] got a message that doesn't decode".to_string();
+        };
+        if let Err(e) = msg.from.verify() {
+            return format!("[chat] ignored a message from '{}': {e}", msg.from.nickname);
+        }
+        self.set_peer(&msg.from);
+        format!("[chat {}] {}", msg.from.nickname, msg.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip_with_their_card() {
+        let kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let msg = ChatMessage {
+            from: ProfileCard::new(&kp, "alice", &["watch"], Some(b"route")).unwrap(),
+            text: "hi there".to_string(),
+        };
+        let data = msg.encode();
+        assert!(is_chat_message(&data));
+        assert_eq!(ChatMessage::decode(&data), Some(msg));
+        assert_eq!(ChatMessage::decode(b"VXLOGS1\n{}"), None);
+    }
+}
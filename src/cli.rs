@@ -0,0 +1,675 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::chaos::{ChaosConfig, InjectedLatency};
+use crate::control::Request;
+use crate::schema::GrowArgs;
+use crate::soak::SoakRole;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Command line handling.
+//
+//	Running the program with no arguments gives the usual 1/2/3 menu, or
+//	starts the node --mode names straight away (for scripts and CI).
+//	Anything else on the command line is one of the extra commands below.
+//	Flags (anything starting with --) can go anywhere on the line, and flags
+//	that take a value accept both `--flag value` and `--flag=value`.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub enum Command {
+    // No arguments: show the node selection menu (or start the --mode one)
+    Interactive,
+    // audit show [default|alt]
+    AuditShow { role: Option<String> },
+    // soak --hours N [--role default|alt]
+    Soak { hours: f64, role: SoakRole },
+    // daemon [--role default|alt]
+    Daemon { role: SoakRole },
+    // attach|detach|status|get <n>|set <n> <text> [--role default|alt], sent to that role's daemon
+    Control { request: Request, role: SoakRole },
+    // config validate
+    ConfigValidate,
+    // config show [--effective]
+    ConfigShow { effective: bool },
+    // record clone <src-key>
+    RecordClone { source: String },
+    // record new --template chat|kvstore|statuspage|mailbox
+    RecordNew { template: String },
+    // record backup <file> [--passphrase] [--passphrase-file PATH]
+    RecordBackup { file: PathBuf, passphrase_file: Option<PathBuf> },
+    // record load <file> [--rate N]
+    RecordLoad { file: PathBuf, rate: u64 },
+    // record restore <file> [--passphrase] [--passphrase-file PATH]
+    RecordRestore { file: PathBuf, passphrase_file: Option<PathBuf> },
+    // record snapshot <record>
+    RecordSnapshot { record: String },
+    // record diff <a> <b>
+    RecordDiff { a: String, b: String },
+    // record access
+    RecordAccess,
+    // record bench [--rounds N]
+    RecordBench { rounds: u32 },
+    // discover
+    Discover,
+    // keys passwd
+    KeysPasswd,
+    // keys show [namespace]
+    KeysShow { namespace: Option<String> },
+    // keys rotate [namespace]
+    KeysRotate { namespace: Option<String> },
+    // scenario run <file.yaml>
+    ScenarioRun { file: PathBuf },
+    // monitor <record> [--render <template> --out <file>]
+    // (the template and the file it's rendered to, if there's a page to keep)
+    Monitor { record: String, page: Option<(PathBuf, PathBuf)> },
+    // schema grow <src> [--owner-subkeys N] [--member-subkeys N] [--owner KEYPAIR]
+    SchemaGrow(GrowArgs),
+    // record-events <file> [record]
+    RecordEvents { file: PathBuf, record: Option<String> },
+    // replay-events <file>
+    ReplayEvents { file: PathBuf },
+}
+
+pub struct Options {
+    pub command: Command,
+    // --dry-run: print the DHT writes we would make, but don't send them
+    pub dry_run: bool,
+    // --chaos[=PCT]: randomly delay/fail DHT calls and force detach/attach cycles
+    // (--inject-latency on its own gives one that only slows calls down)
+    pub chaos: Option<ChaosConfig>,
+    // --portable: keep all files next to the executable instead of the user data folder
+    pub portable: bool,
+    // --config PATH: use this config file instead of the default one
+    pub config_file: Option<PathBuf>,
+    // --data-dir PATH: keep all files in this folder
+    pub data_dir: Option<PathBuf>,
+    // --health-addr ADDR: serve /healthz, /livez and /readyz (soak and daemon only)
+    pub health_addr: Option<SocketAddr>,
+    // --journald: also log to the systemd journal with structured fields (soak and daemon only)
+    pub journald: bool,
+    // --service: run the daemon under the Windows service manager
+    pub service: bool,
+    // --ttl SECS: values the default node writes expire after this long
+    pub ttl_secs: Option<u64>,
+    // --tutorial: walk through attach, create, write, join and watch with two nodes
+    pub tutorial: bool,
+    // --early: start on the DHT once weakly attached, retrying calls that aren't possible yet
+    pub early: bool,
+    // --transcript FILE: record the prompts' commands and output (see transcript.rs)
+    pub transcript: Option<PathBuf>,
+    // --lang CODE: the language the node prompts talk in (see i18n.rs)
+    pub lang: Option<String>,
+    // --mode default|alt|member: start this node instead of showing the menu
+    pub mode: Option<SoakRole>,
+    // --namespace NAME: the Veilid namespace the node runs under, instead of the config's
+    pub namespace: Option<String>,
+    // --storage-dir PATH: keep the node's .veilid/ stores here instead of the data folder
+    pub storage_dir: Option<PathBuf>,
+    // --key-file PATH: the key file the default node writes and the alt node joins
+    pub key_file: Option<PathBuf>,
+    // --yes: write to records made elsewhere without asking first (see guard.rs)
+    pub yes: bool,
+    // --schema smpl|dflt:<count>: the kind of record the default node makes (record_schema)
+    pub schema: Option<String>,
+}
+
+pub fn parse(args: &[String]) -> Result<Options, String> {
+    let mut dry_run = false;
+    let mut portable = false;
+    let mut effective = false;
+    let mut config_file: Option<PathBuf> = None;
+    let mut data_dir: Option<PathBuf> = None;
+    let mut chaos: Option<ChaosConfig> = None;
+    let mut chaos_reattach: Option<u64> = None;
+    let mut inject_latency: Option<InjectedLatency> = None;
+    let mut hours: Option<f64> = None;
+    let mut role: Option<String> = None;
+    let mut owner_subkeys: Option<u16> = None;
+    let mut member_subkeys: Option<u16> = None;
+    let mut owner: Option<String> = None;
+    let mut template: Option<String> = None;
+    let mut passphrase_file: Option<PathBuf> = None;
+    let mut rate: Option<u64> = None;
+    let mut rounds: Option<u32> = None;
+    let mut render: Option<PathBuf> = None;
+    let mut out: Option<PathBuf> = None;
+    let mut health_addr: Option<SocketAddr> = None;
+    let mut journald = false;
+    let mut service = false;
+    let mut tutorial = false;
+    let mut early = false;
+    let mut yes = false;
+    let mut ttl_secs: Option<u64> = None;
+    let mut transcript: Option<PathBuf> = None;
+    let mut lang: Option<String> = None;
+    let mut mode: Option<SoakRole> = None;
+    let mut namespace: Option<String> = None;
+    let mut storage_dir: Option<PathBuf> = None;
+    let mut key_file: Option<PathBuf> = None;
+    let mut schema: Option<String> = None;
+    let mut words: Vec<&str> = Vec::new();
+
+    let mut iter = args.iter().map(|s| s.as_str());
+    while let Some(arg) = iter.next() {
+        if !arg.starts_with("--") {
+            words.push(arg);
+            continue;
+        }
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value)),
+            None => (arg, None),
+        };
+        // the value for flags that need one: either after '=' or the next argument
+        let mut value = || {
+            inline
+                .or_else(|| iter.next())
+                .ok_or_else(|| format!("{flag} needs a value"))
+        };
+
+        match flag {
+            "--dry-run" => dry_run = true,
+            "--portable" => portable = true,
+            "--effective" => effective = true,
+            "--journald" => journald = true,
+            "--service" => service = true,
+            "--tutorial" => tutorial = true,
+            "--early" => early = true,
+            "--yes" => yes = true,
+            "--config" => config_file = Some(value()?.into()),
+            "--data-dir" => data_dir = Some(value()?.into()),
+            // --chaos only takes its percentage in the --chaos=PCT form
+            "--chaos" => {
+                let pct = match inline {
+                    Some(v) => parse_number(flag, v)?,
+                    None => 10,
+                };
+                if pct > 100 {
+                    return Err(format!("--chaos percentage must be 0-100, got {pct}"));
+                }
+                chaos = Some(ChaosConfig::with_pct(pct as u32));
+            }
+            "--chaos-reattach" => chaos_reattach = Some(parse_number(flag, value()?)?),
+            "--inject-latency" => inject_latency = Some(InjectedLatency::parse(value()?)?),
+            "--hours" => {
+                let v = value()?;
+                hours = Some(
+                    v.parse()
+                        .ok()
+                        .filter(|h: &f64| *h > 0.0)
+                        .ok_or_else(|| format!("--hours expects a positive number, got '{v}'"))?,
+                );
+            }
+            "--ttl" => ttl_secs = Some(parse_number(flag, value()?)?),
+            "--role" => role = Some(value()?.to_string()),
+            "--owner-subkeys" => owner_subkeys = Some(parse_count(flag, value()?)?),
+            "--member-subkeys" => member_subkeys = Some(parse_count(flag, value()?)?),
+            "--owner" => owner = Some(value()?.to_string()),
+            "--template" => template = Some(value()?.to_string()),
+            "--rate" => {
+                let r = parse_number(flag, value()?)?;
+                if r == 0 {
+                    return Err("--rate must be at least 1 write a second".to_string());
+                }
+                rate = Some(r);
+            }
+            "--rounds" => {
+                let n = parse_number(flag, value()?)?;
+                if n == 0 {
+                    return Err("--rounds must be at least 1".to_string());
+                }
+                rounds = Some(u32::try_from(n).map_err(|_| format!("--rounds is too big: {n}"))?);
+            }
+            "--transcript" => transcript = Some(value()?.into()),
+            "--lang" => {
+                let v = value()?;
+                let known: Vec<&str> = crate::i18n::LANGS.iter().map(|(code, _)| *code).collect();
+                if !known.contains(&v) {
+                    return Err(format!("--lang must be one of {}, got '{v}'", known.join(", ")));
+                }
+                lang = Some(v.to_string());
+            }
+            // soak, daemon and the control commands take --role default|alt; there's no member daemon
+            "--mode" => {
+                mode = Some(match value()? {
+                    "default" => SoakRole::Default,
+                    "alt" => SoakRole::Alt,
+                    "member" => SoakRole::Member,
+                    other => return Err(format!("--mode must be default, alt or member, got '{other}'")),
+                })
+            }
+            "--namespace" => {
+                let v = value()?;
+                if v.is_empty() {
+                    return Err("--namespace can't be empty".to_string());
+                }
+                namespace = Some(v.to_string());
+            }
+            "--storage-dir" => storage_dir = Some(value()?.into()),
+            "--key-file" => key_file = Some(value()?.into()),
+            "--schema" => {
+                let v = value()?;
+                crate::config::RecordSchema::parse(v).map_err(|e| format!("--schema: {e}"))?;
+                schema = Some(v.to_string());
+            }
+            "--render" => render = Some(value()?.into()),
+            "--out" => out = Some(value()?.into()),
+            // the passphrase is asked for without echo unless it comes from a file or
+            // VEILID_EXAMPLE_BACKUP_PASSPHRASE; never on the command line, where ps and
+            // the shell history would keep it
+            "--passphrase" => {
+                if inline.is_some() {
+                    return Err(format!(
+                        "--passphrase=TEXT isn't taken, it would end up in ps and your shell history; use --passphrase-file PATH or {}",
+                        crate::backup::PASSPHRASE_ENV
+                    ));
+                }
+            }
+            "--passphrase-file" => passphrase_file = Some(value()?.into()),
+            "--health-addr" => {
+                let v = value()?;
+                health_addr = Some(
+                    v.parse()
+                        .map_err(|_| format!("--health-addr expects IP:PORT (like 0.0.0.0:8080), got '{v}'"))?,
+                );
+            }
+            _ => return Err(format!("Unknown option: {flag}\n\n{}", usage())),
+        }
+    }
+
+    let command = match words.as_slice() {
+        [] => Command::Interactive,
+        ["audit", "show"] => Command::AuditShow { role: None },
+        ["audit", "show", role @ ("default" | "alt")] => Command::AuditShow {
+            role: Some(role.to_string()),
+        },
+        ["soak"] => {
+            let hours = hours.ok_or("soak needs --hours N")?;
+            if dry_run {
+                return Err("soak can't be combined with --dry-run".to_string());
+            }
+            Command::Soak {
+                hours,
+                role: parse_role("--role", role.as_deref())?,
+            }
+        }
+        ["daemon"] => {
+            if dry_run {
+                return Err("daemon can't be combined with --dry-run".to_string());
+            }
+            Command::Daemon {
+                role: parse_role("--role", role.as_deref())?,
+            }
+        }
+        ["attach" | "detach" | "status" | "get" | "set", ..] => Command::Control {
+            request: Request::parse(&words.join(" "))?,
+            role: parse_role("--role", role.as_deref())?,
+        },
+        ["config", "validate"] => Command::ConfigValidate,
+        ["config", "show"] => Command::ConfigShow { effective },
+        ["record", "clone", source] => Command::RecordClone {
+            source: source.to_string(),
+        },
+        ["record", "new"] => Command::RecordNew {
+            template: template.ok_or("record new needs --template chat|kvstore|statuspage|mailbox")?,
+        },
+        ["record", "backup", file] => Command::RecordBackup {
+            file: file.into(),
+            passphrase_file,
+        },
+        ["record", "restore", file] => Command::RecordRestore {
+            file: file.into(),
+            passphrase_file,
+        },
+        ["record", "load", file] => Command::RecordLoad {
+            file: file.into(),
+            rate: rate.unwrap_or(crate::load::DEFAULT_RATE),
+        },
+        ["record", "snapshot", record] => Command::RecordSnapshot {
+            record: record.to_string(),
+        },
+        ["record", "diff", a, b] => Command::RecordDiff {
+            a: a.to_string(),
+            b: b.to_string(),
+        },
+        ["record", "access"] => Command::RecordAccess,
+        ["record", "bench"] => Command::RecordBench {
+            rounds: rounds.unwrap_or(crate::bench::DEFAULT_ROUNDS),
+        },
+        ["discover"] => Command::Discover,
+        ["keys", "passwd"] => Command::KeysPasswd,
+        ["keys", "show"] => Command::KeysShow { namespace: None },
+        ["keys", "show", ns] => Command::KeysShow {
+            namespace: Some(ns.to_string()),
+        },
+        ["keys", "rotate"] => Command::KeysRotate { namespace: None },
+        ["keys", "rotate", ns] => Command::KeysRotate {
+            namespace: Some(ns.to_string()),
+        },
+        ["scenario", "run", file] => Command::ScenarioRun { file: file.into() },
+        ["monitor", record] => Command::Monitor {
+            record: record.to_string(),
+            page: match (render.take(), out.take()) {
+                (Some(template), Some(file)) => Some((template, file)),
+                (None, None) => None,
+                (Some(_), None) => return Err("--render needs --out FILE to write the page to".to_string()),
+                (None, Some(_)) => return Err("--out needs --render TEMPLATE to fill it from".to_string()),
+            },
+        },
+        ["schema", "grow", source] => Command::SchemaGrow(GrowArgs {
+            source: source.to_string(),
+            owner_subkeys,
+            member_subkeys,
+            owner,
+        }),
+        ["record-events", file] => Command::RecordEvents {
+            file: file.into(),
+            record: None,
+        },
+        ["record-events", file, record] => Command::RecordEvents {
+            file: file.into(),
+            record: Some(record.to_string()),
+        },
+        ["replay-events", file] => Command::ReplayEvents { file: file.into() },
+        _ => return Err(format!("Unknown command: {}\n\n{}", words.join(" "), usage())),
+    };
+
+    let long_running = matches!(command, Command::Soak { .. } | Command::Daemon { .. });
+    if health_addr.is_some() && !long_running {
+        return Err("--health-addr only works with soak or daemon".to_string());
+    }
+    if journald && !long_running {
+        return Err("--journald only works with soak or daemon".to_string());
+    }
+    if service && !matches!(command, Command::Daemon { .. }) {
+        return Err("--service only works with daemon".to_string());
+    }
+    if rate.is_some() && !matches!(command, Command::RecordLoad { .. }) {
+        return Err("--rate only works with record load".to_string());
+    }
+    if rounds.is_some() && !matches!(command, Command::RecordBench { .. }) {
+        return Err("--rounds only works with record bench".to_string());
+    }
+    if transcript.is_some() && !matches!(command, Command::Interactive) {
+        return Err("--transcript only works with the interactive nodes".to_string());
+    }
+    let node_flags = mode.is_some() || namespace.is_some() || storage_dir.is_some() || key_file.is_some();
+    if node_flags && (!matches!(command, Command::Interactive) || tutorial) {
+        return Err("--mode, --namespace, --storage-dir and --key-file only work with the interactive nodes (not --tutorial)".to_string());
+    }
+    let asks = matches!(command, Command::RecordLoad { .. }) || (matches!(command, Command::Interactive) && !tutorial);
+    if yes && !asks {
+        return Err("--yes only works with the interactive nodes (not --tutorial) and record load".to_string());
+    }
+    if schema.is_some() && (!matches!(command, Command::Interactive) || tutorial) {
+        return Err("--schema only works with the interactive nodes (not --tutorial)".to_string());
+    }
+    if render.is_some() || out.is_some() {
+        return Err("--render and --out only work with monitor".to_string());
+    }
+    if dry_run && matches!(command, Command::RecordAccess) {
+        return Err("record access finds out by really writing, so it can't be a --dry-run".to_string());
+    }
+    if dry_run && matches!(command, Command::RecordBench { .. }) {
+        return Err("record bench times real DHT calls, so it can't be a --dry-run".to_string());
+    }
+    if dry_run && matches!(command, Command::ScenarioRun { .. }) {
+        return Err("scenario run checks what the network really holds, so it can't be a --dry-run".to_string());
+    }
+    if tutorial && (!matches!(command, Command::Interactive) || dry_run || chaos.is_some() || inject_latency.is_some()) {
+        return Err("--tutorial runs on its own (no command, --dry-run, --chaos or --inject-latency)".to_string());
+    }
+
+    if let Some(secs) = chaos_reattach {
+        match chaos.as_mut() {
+            // 0 turns the detach/attach cycles off
+            Some(c) => c.reattach_every = (secs > 0).then_some(Duration::from_secs(secs)),
+            None => return Err("--chaos-reattach needs --chaos as well".to_string()),
+        }
+    }
+    // injected latency rides along with the chaos hooks in the Dht wrapper
+    if let Some(latency) = inject_latency {
+        match chaos.as_mut() {
+            Some(c) => c.injected = Some(latency),
+            None => chaos = Some(ChaosConfig::latency_only(latency)),
+        }
+    }
+
+    Ok(Options {
+        command,
+        dry_run,
+        chaos,
+        portable,
+        config_file,
+        data_dir,
+        health_addr,
+        journald,
+        service,
+        ttl_secs,
+        tutorial,
+        early,
+        transcript,
+        lang,
+        mode,
+        namespace,
+        storage_dir,
+        key_file,
+        yes,
+        schema,
+    })
+}
+
+fn parse_role(flag: &str, role: Option<&str>) -> Result<SoakRole, String> {
+    match role {
+        None | Some("default") => Ok(SoakRole::Default),
+        Some("alt") => Ok(SoakRole::Alt),
+        Some(other) => Err(format!("{flag} must be default or alt, got '{other}'")),
+    }
+}
+
+// `--help` or `-h` anywhere: usage() is printed and nothing else is looked at.
+pub fn wants_help(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == "--help" || arg == "-h")
+}
+
+fn parse_number(flag: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{flag} expects a number, got '{value}'"))
+}
+
+fn parse_count(flag: &str, value: &str) -> Result<u16, String> {
+    u16::try_from(parse_number(flag, value)?).map_err(|_| format!("{flag} is too big: {value}"))
+}
+
+pub fn usage() -> String {
+    format!(
+        "Usage:
+  veilid_test_node [OPTIONS]                          start the interactive node menu
+  veilid_test_node --mode ROLE [OPTIONS]              start the default, alt or member node without the menu
+  veilid_test_node audit show [ROLE]                  print the DHT audit log (ROLE = default|alt)
+  veilid_test_node soak --hours N [--role ROLE]       long-running read/write/watch soak test
+  veilid_test_node daemon [--role ROLE] [--service]   run a role until stopped (no prompts, stops on SIGTERM)
+  veilid_test_node status|attach|detach [--role ROLE] ask the role's running daemon how it is, or to attach/detach
+  veilid_test_node get N | set N TEXT [--role ROLE]   read or write subkey N of the running daemon's record
+  veilid_test_node config validate                    check the configuration without starting Veilid
+  veilid_test_node config show [--effective]          print the config file (or the merged settings)
+  veilid_test_node record clone SRC                   copy a record into a new one with fresh owner/member keys
+  veilid_test_node record new --template T            make a record laid out for chat, kvstore, statuspage or mailbox
+  veilid_test_node record backup FILE [--passphrase]  seal the default node's record, keys and values into FILE
+  veilid_test_node record restore FILE [--passphrase] get a record back from a backup and rewrite owner_keys.txt
+  veilid_test_node record load FILE [--rate N] [--yes] seed the key file's record from CSV/JSON rows (subkey,value or name,value)
+  veilid_test_node record snapshot REC                save every subkey's value, seq and writer to snapshots/
+  veilid_test_node record diff A B                    show what changed between two snapshots (names or paths)
+  veilid_test_node record access                      open a new record as owner, member and nobody; compare reads/writes
+  veilid_test_node record bench [--rounds N]          time writes, reads and watches on DFLT and SMPL records of the same size
+  veilid_test_node discover                           list records announced in the public app index
+  veilid_test_node keys passwd                        set or change the protected store password (always_use_insecure_storage false)
+  veilid_test_node keys show [NAMESPACE]              where each device encryption key is kept, if it's sealed, what it protects
+  veilid_test_node keys rotate [NAMESPACE]            replace the device encryption key, re-encrypting the table store
+  veilid_test_node scenario run FILE.yaml             run a scripted demo (create, write, wait-for-change, assert-equals)
+  veilid_test_node monitor REC                        a live read-only view of a record for onlookers (never writes)
+  veilid_test_node monitor REC --render T --out FILE  ...also fill template T from it into FILE on every change
+  veilid_test_node schema grow SRC [--owner-subkeys N] [--member-subkeys N] [--owner KEYPAIR]
+                                                      move a record into a bigger one, leaving a forwarding pointer
+  veilid_test_node record-events FILE [REC]           record every Veilid update (sanitized) to FILE until Ctrl+C, watching REC
+  veilid_test_node replay-events FILE                 statistics from a recording: attachment, peers, bandwidth, changes
+
+Options:
+  --help, -h                print this and the exit codes
+  --config PATH             use this JSON config file (also VEILID_EXAMPLE_CONFIG)
+  --data-dir PATH           keep .veilid/, the key file and logs in PATH
+  --portable                keep .veilid/, the key file and logs next to the executable
+  --dry-run                 validate and print DHT writes (create/set) without sending them
+  --chaos[=PCT]             delay/fail PCT% of DHT calls (default 10) and force detach/attach cycles
+  --chaos-reattach SECS     seconds between forced detach/attach cycles (default 120, 0 = off)
+  --inject-latency T[..T]   hold back every DHT call's result for T (or a random time in the range), e.g. 200ms..2s
+  --health-addr IP:PORT     serve /healthz, /livez and /readyz over HTTP (soak and daemon)
+  --journald                also log to the systemd journal with structured fields (soak and daemon)
+  --service                 run the daemon as a Windows service (see src/winservice.rs for setup)
+  --rate N                  writes a second for record load (default 4)
+  --rounds N                rounds of writes, watches and reads for record bench (default 3)
+  --passphrase-file PATH    record backup/restore: the passphrase is the first line of PATH
+                            (or VEILID_EXAMPLE_BACKUP_PASSPHRASE); otherwise it's asked for, not echoed
+  --render T, --out FILE    monitor: render the record through template T into FILE (see src/page.rs)
+  --ttl SECS                values the default node writes expire after SECS (also value_ttl_secs in the config)
+  --tutorial                a guided first run: attach, create a record, write, join from a second node, watch
+  --early                   start on the DHT as soon as attached at all, retrying calls that get TryAgain (early_dht)
+  --transcript FILE         append the prompts' commands and output, with times, to FILE as Markdown
+  --lang CODE               the language the node prompts talk in: en or es (default: from LANG)
+  --mode default|alt|member start that node instead of asking which
+  --namespace NAME          run the node under Veilid namespace NAME (default: default/alt/member_namespace)
+  --storage-dir PATH        keep the node's .veilid/ stores in PATH instead of the data folder
+  --key-file PATH           the default node writes its record's keys to PATH; the alt node joins
+                            the record in PATH without asking (default: owner_keys.json in the data folder)
+  --yes                     alt/member node, record load: write to records this data folder didn't
+                            make without asking first
+  --schema smpl|dflt:N      default node: make an SMPL record (default) or a DFLT one of N subkeys, all
+                            written with its own key (also record_schema in the config)
+
+{}",
+        crate::exit::HELP
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn parsed(line: &str) -> Options {
+        parse(&args(line)).unwrap_or_else(|e| panic!("'{line}' didn't parse: {e}"))
+    }
+
+    fn refused(line: &str) -> String {
+        match parse(&args(line)) {
+            Ok(_) => panic!("'{line}' parsed"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn values_come_after_a_space_or_an_equals_sign() {
+        assert_eq!(parsed("--data-dir /tmp/a").data_dir, Some(PathBuf::from("/tmp/a")));
+        assert_eq!(parsed("--data-dir=/tmp/a").data_dir, Some(PathBuf::from("/tmp/a")));
+        assert_eq!(parsed("--ttl=30 --lang es").ttl_secs, Some(30));
+        assert_eq!(parsed("--mode=alt").mode, Some(SoakRole::Alt));
+        assert!(parsed("--dry-run --portable").dry_run);
+        assert_eq!(refused("--config"), "--config needs a value");
+        assert!(refused("--ttl=soon").contains("expects a number"));
+        assert!(refused("--lang xx").contains("--lang must be one of"));
+        assert!(refused("--frobnicate").starts_with("Unknown option: --frobnicate"));
+        assert!(refused("--passphrase=hunter2").contains("--passphrase-file"));
+    }
+
+    #[test]
+    fn chaos_takes_its_percentage_only_after_an_equals_sign() {
+        assert_eq!(parsed("--chaos").chaos.unwrap().fail_pct, 10);
+        assert_eq!(parsed("--chaos=25").chaos.unwrap().fail_pct, 25);
+        assert!(refused("--chaos=101").contains("0-100"));
+        // the 25 is read as a command word
+        assert!(refused("--chaos 25").starts_with("Unknown command: 25"));
+
+        assert_eq!(parsed("--chaos --chaos-reattach=0").chaos.unwrap().reattach_every, None);
+        assert!(refused("--chaos-reattach 30").contains("needs --chaos"));
+        let latency = parsed("--inject-latency 200ms..2s").chaos.unwrap();
+        assert!(!latency.is_chaotic());
+        assert!(latency.injected.is_some());
+    }
+
+    #[test]
+    fn command_words() {
+        assert!(matches!(parsed("").command, Command::Interactive));
+        assert!(matches!(parsed("audit show").command, Command::AuditShow { role: None }));
+        assert!(matches!(parsed("audit show alt").command, Command::AuditShow { role: Some(r) } if r == "alt"));
+        assert!(matches!(
+            parsed("soak --hours 1.5 --role alt").command,
+            Command::Soak { hours, role: SoakRole::Alt } if hours == 1.5
+        ));
+        assert!(matches!(parsed("daemon").command, Command::Daemon { role: SoakRole::Default }));
+        assert!(matches!(
+            parsed("set 3 hello world --role=alt").command,
+            Command::Control { request: Request::Set(3, text), role: SoakRole::Alt } if text == "hello world"
+        ));
+        assert!(matches!(
+            parsed("record load writes.txt").command,
+            Command::RecordLoad { rate, .. } if rate == crate::load::DEFAULT_RATE
+        ));
+        assert!(matches!(parsed("record load writes.txt --rate=5").command, Command::RecordLoad { rate: 5, .. }));
+        assert!(matches!(parsed("record diff a b").command, Command::RecordDiff { .. }));
+        assert!(matches!(parsed("keys show work").command, Command::KeysShow { namespace: Some(ns) } if ns == "work"));
+        assert!(matches!(
+            parsed("monitor KEY --render page.tmpl --out page.html").command,
+            Command::Monitor { page: Some(_), .. }
+        ));
+        assert!(matches!(parsed("record-events log.bin KEY").command, Command::RecordEvents { record: Some(_), .. }));
+
+        assert!(refused("soak").contains("--hours"));
+        assert!(refused("soak --hours 0").contains("positive number"));
+        assert!(refused("daemon --role member").contains("must be default or alt"));
+        assert!(refused("record new").contains("--template"));
+        assert!(refused("record diff a").starts_with("Unknown command: record diff a"));
+    }
+
+    #[test]
+    fn flags_that_only_go_with_some_commands() {
+        assert!(refused("--rate 5").contains("only works with record load"));
+        assert!(refused("record bench --rate 5").contains("only works with record load"));
+        assert!(refused("--rounds 3").contains("only works with record bench"));
+        assert!(refused("soak --hours 1 --dry-run").contains("--dry-run"));
+        assert!(refused("daemon --dry-run").contains("--dry-run"));
+        assert!(refused("record access --dry-run").contains("--dry-run"));
+        assert!(refused("--health-addr 127.0.0.1:8080").contains("soak or daemon"));
+        assert!(parsed("daemon --health-addr 127.0.0.1:8080 --journald").health_addr.is_some());
+        assert!(refused("soak --hours 1 --service").contains("only works with daemon"));
+        assert!(parsed("daemon --service").service);
+        assert!(refused("discover --transcript out.txt").contains("interactive nodes"));
+        assert!(refused("--tutorial --mode alt").contains("--mode"));
+        assert!(refused("--tutorial --chaos").contains("runs on its own"));
+        assert!(refused("--tutorial --schema dflt:2").contains("--schema"));
+
+        // --yes is for the places that would otherwise ask
+        assert!(parsed("--yes").yes);
+        assert!(parsed("record load writes.txt --yes").yes);
+        assert!(refused("soak --hours 1 --yes").contains("--yes only works"));
+        assert!(refused("--tutorial --yes").contains("--yes only works"));
+
+        assert!(refused("monitor KEY --render page.tmpl").contains("needs --out"));
+        assert!(refused("monitor KEY --out page.html").contains("needs --render"));
+        assert!(refused("discover --out page.html").contains("only work with monitor"));
+    }
+
+    #[test]
+    fn help_wins_anywhere_on_the_line() {
+        assert!(wants_help(&args("--help")));
+        assert!(wants_help(&args("record load writes.txt -h")));
+        assert!(wants_help(&args("--frobnicate --help")));
+        assert!(!wants_help(&args("record load help")));
+        assert!(!wants_help(&args("--helpful")));
+    }
+}
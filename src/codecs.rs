@@ -0,0 +1,180 @@
+use std::sync::{Arc, OnceLock, RwLock};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The codecs a value's body can be put through on its way into the DHT:
+//	compression, encryption, another serialization, anything that turns
+//	bytes into other bytes and back.
+//
+//	A writer picks a pipeline by name (value_codecs in the config, e.g.
+//	["deflate"]). The envelope runs the body through each codec in turn and
+//	lists their ids in its header (FLAG_PIPELINE, see envelope.rs), so a
+//	reader knows what to undo, and in which order, without being told.
+//	A value that went through a codec this build doesn't have comes out as
+//	unreadable, saying which one.
+//
+//	Adding a codec is implementing ValueCodec and registering it before
+//	anything is read or written:
+//
+//	  struct Brotli;
+//	  impl ValueCodec for Brotli {
+//	      fn id(&self) -> u8 { 200 }
+//	      fn name(&self) -> &str { "brotli" }
+//	      fn kind(&self) -> CodecKind { CodecKind::Compression }
+//	      fn encode(&self, body: &[u8]) -> Vec<u8> { ... }
+//	      fn decode(&self, body: &[u8]) -> Result<Vec<u8>, String> { ... }
+//	  }
+//	  codecs::register(Brotli)?;
+//
+//	The id is what goes in the value, so once values are out there with it
+//	a codec keeps its id for good. Ids below 128 are kept for the ones that
+//	come with the example; pick one from 128 up for your own.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// the biggest body a codec may hand back, so a small value can't inflate into gigabytes
+pub const MAX_DECODED_LEN: usize = 1024 * 1024;
+// ids from here up are for codecs registered by other programs
+pub const FIRST_USER_ID: u8 = 128;
+
+// Sets FLAG_COMPRESSED / FLAG_ENCRYPTED on what a codec writes, for readers
+// that can't undo it and want to say why.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodecKind {
+    Compression,
+    Encryption,
+    Other,
+}
+
+pub trait ValueCodec: Send + Sync {
+    // what values that went through it say, in their envelope header
+    fn id(&self) -> u8;
+    // what value_codecs and the messages call it
+    fn name(&self) -> &str;
+    fn kind(&self) -> CodecKind;
+    fn encode(&self, body: &[u8]) -> Vec<u8>;
+    // Err for bytes it couldn't have written
+    fn decode(&self, body: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+// DEFLATE (RFC 1951), for text and JSON that repeats itself.
+struct Deflate;
+
+impl ValueCodec for Deflate {
+    fn id(&self) -> u8 {
+        1
+    }
+
+    fn name(&self) -> &str {
+        "deflate"
+    }
+
+    fn kind(&self) -> CodecKind {
+        CodecKind::Compression
+    }
+
+    fn encode(&self, body: &[u8]) -> Vec<u8> {
+        miniz_oxide::deflate::compress_to_vec(body, 6)
+    }
+
+    fn decode(&self, body: &[u8]) -> Result<Vec<u8>, String> {
+        miniz_oxide::inflate::decompress_to_vec_with_limit(body, MAX_DECODED_LEN).map_err(|e| e.to_string())
+    }
+}
+
+static REGISTRY: OnceLock<RwLock<Vec<Arc<dyn ValueCodec>>>> = OnceLock::new();
+
+fn registry() -> &'static RwLock<Vec<Arc<dyn ValueCodec>>> {
+    REGISTRY.get_or_init(|| RwLock::new(vec![Arc::new(Deflate)]))
+}
+
+// Makes a codec available to pipelines and readers. Refused if its id or
+// name is taken already.
+pub fn register(codec: impl ValueCodec + 'static) -> Result<(), String> {
+    let mut codecs = registry().write().unwrap();
+    if let Some(taken) = codecs.iter().find(|c| c.id() == codec.id() || c.name() == codec.name()) {
+        return Err(format!(
+            "codec '{}' (id {}) clashes with '{}' (id {})",
+            codec.name(),
+            codec.id(),
+            taken.name(),
+            taken.id()
+        ));
+    }
+    codecs.push(Arc::new(codec));
+    Ok(())
+}
+
+pub fn get(id: u8) -> Option<Arc<dyn ValueCodec>> {
+    registry().read().unwrap().iter().find(|c| c.id() == id).cloned()
+}
+
+// The ids for a list of codec names, in the order given.
+pub fn pipeline(names: &[String]) -> Result<Vec<u8>, String> {
+    let codecs = registry().read().unwrap();
+    names
+        .iter()
+        .map(|name| {
+            codecs.iter().find(|c| c.name() == name).map(|c| c.id()).ok_or_else(|| {
+                let known: Vec<&str> = codecs.iter().map(|c| c.name()).collect();
+                format!("there's no codec called '{name}' (there's {})", known.join(", "))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::{Codec, Envelope, EnvelopeError, FLAG_COMPRESSED};
+
+    // A codec someone else might add: flips every bit.
+    struct Invert;
+
+    impl ValueCodec for Invert {
+        fn id(&self) -> u8 {
+            FIRST_USER_ID
+        }
+
+        fn name(&self) -> &str {
+            "invert"
+        }
+
+        fn kind(&self) -> CodecKind {
+            CodecKind::Other
+        }
+
+        fn encode(&self, body: &[u8]) -> Vec<u8> {
+            body.iter().map(|b| !b).collect()
+        }
+
+        fn decode(&self, body: &[u8]) -> Result<Vec<u8>, String> {
+            Ok(self.encode(body))
+        }
+    }
+
+    #[test]
+    fn values_come_back_through_a_pipeline_of_registered_codecs() {
+        assert!(pipeline(&["brotli".to_string()]).unwrap_err().contains("deflate"));
+        register(Invert).unwrap();
+        assert!(register(Invert).is_err());
+
+        let text = "the same words, the same words, the same words ".repeat(20);
+        let mut env = Envelope::new(Codec::Text, text.as_bytes().to_vec());
+        env.pipeline = pipeline(&["deflate".to_string(), "invert".to_string()]).unwrap();
+        let data = env.encode();
+        assert!(data.len() < text.len() / 2);
+
+        let read = Envelope::decode(&data).unwrap();
+        assert_eq!(read.body, text.as_bytes());
+        assert_eq!(read.pipeline, vec![1, FIRST_USER_ID]);
+        assert_ne!(read.flags & FLAG_COMPRESSED, 0);
+        assert_eq!(read.display(), text);
+
+        // a reader without the codec says which one it's missing
+        let mut unknown = data.clone();
+        let at = unknown.iter().position(|b| *b == FIRST_USER_ID).unwrap();
+        unknown[at] = 250;
+        assert_eq!(Envelope::decode(&unknown), Err(EnvelopeError::MissingCodec(250)));
+    }
+}
@@ -0,0 +1,345 @@
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Every command the node prompts understand, in one list.
+//
+//	`help` and `help <command>` are printed from here, and Tab completion
+//	offers the names, so a new command only needs an entry below (plus the
+//	code that runs it in app.rs). The "Veilid API" line points at what the
+//	command exercises, for anyone reading along in the Veilid docs.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Prompt {
+    Default,
+    Alt,
+}
+
+pub struct CommandInfo {
+    pub name: &'static str,
+    pub prompts: &'static [Prompt],
+    pub usage: &'static str,
+    pub summary: &'static str,
+    pub example: &'static str,
+    pub api: &'static [&'static str],
+}
+
+const BOTH: &[Prompt] = &[Prompt::Default, Prompt::Alt];
+
+pub const COMMANDS: &[CommandInfo] = &[
+    CommandInfo {
+        name: ":sub",
+        prompts: &[Prompt::Default],
+        usage: ":sub <subkey>",
+        summary: "send plain text to <subkey> from now on; <subkey>:<text> sends just one line there",
+        example: ":sub 3",
+        api: &["RoutingContext::set_dht_value"],
+    },
+    CommandInfo {
+        name: ":all",
+        prompts: &[Prompt::Default],
+        usage: ":all <text>",
+        summary: "write <text> to every subkey our member key may write",
+        example: ":all hello everyone",
+        api: &["RoutingContext::set_dht_value"],
+    },
+    CommandInfo {
+        name: "flood",
+        prompts: &[Prompt::Default],
+        usage: "flood <subkey> <count>",
+        summary: "write <count> values to one subkey as fast as possible, then report writes/sec and how far the seq moved",
+        example: "flood 2 50",
+        api: &["RoutingContext::set_dht_value", "RoutingContext::get_dht_value"],
+    },
+    CommandInfo {
+        name: "stats bandwidth",
+        prompts: BOTH,
+        usage: "stats bandwidth",
+        summary: "bytes this node has written to and read from the DHT, per feature (values, metadata, mail, watch, ...)",
+        example: "stats bandwidth",
+        api: &["RoutingContext::set_dht_value", "RoutingContext::get_dht_value", "VeilidUpdate::ValueChange"],
+    },
+    CommandInfo {
+        name: "stats queue",
+        prompts: BOTH,
+        usage: "stats queue",
+        summary: "DHT calls started per priority class (interactive, sync, background), how long they queued, and what's running now",
+        example: "stats queue",
+        api: &["RoutingContext::get_dht_value", "RoutingContext::set_dht_value"],
+    },
+    CommandInfo {
+        name: "stats heatmap",
+        prompts: BOTH,
+        usage: "stats heatmap",
+        summary: "reads, writes and watch changes per subkey over the last 30 minutes, as a grid shaded by how busy each minute was",
+        example: "stats heatmap",
+        api: &["RoutingContext::get_dht_value", "RoutingContext::set_dht_value", "VeilidUpdate::ValueChange"],
+    },
+    CommandInfo {
+        name: "stats trend",
+        prompts: BOTH,
+        usage: "stats trend",
+        summary: "today's DHT call times and failures against the last 30 days' (kept in the table store), to spot a network or setup that's got slower",
+        example: "stats trend",
+        api: &["TableStore::open", "TableDB::store_json", "RoutingContext::get_dht_value", "RoutingContext::set_dht_value"],
+    },
+    CommandInfo {
+        name: "quota",
+        prompts: BOTH,
+        usage: "quota",
+        summary: "bytes each writer has in the record, against what its subkeys and the record can hold (from what this node has read and written)",
+        example: "quota",
+        api: &["RoutingContext::get_dht_value", "RoutingContext::set_dht_value"],
+    },
+    CommandInfo {
+        name: "field",
+        prompts: BOTH,
+        usage: "field <name> <text>",
+        summary: "set this writer's own copy of a shared field (shared_fields in the config), in the subkey the record's roster gives it",
+        example: "field status back at 3pm",
+        api: &["RoutingContext::set_dht_value"],
+    },
+    CommandInfo {
+        name: "write",
+        prompts: &[Prompt::Alt],
+        usage: "write [subkey] <text>",
+        summary: "write a subkey with the keys owner_keys.txt granted (share_grant on the default node), where the schema lets them; without a subkey, the first one they can",
+        example: "write 3 on my way",
+        api: &["RoutingContext::set_dht_value"],
+    },
+    CommandInfo {
+        name: "merge",
+        prompts: BOTH,
+        usage: "merge <field>",
+        summary: "every writer's copy of a shared field side by side, newest first, with when each was written",
+        example: "merge status",
+        api: &["RoutingContext::get_dht_value"],
+    },
+    CommandInfo {
+        name: "lock",
+        prompts: BOTH,
+        usage: "lock [<subkey> [secs]]",
+        summary: "take an advisory lock on a subkey (lock_subkey in the config) so the other node won't write it until it runs out; no subkey lists the locks",
+        example: "lock 2 300",
+        api: &["RoutingContext::get_dht_value", "RoutingContext::set_dht_value"],
+    },
+    CommandInfo {
+        name: "unlock",
+        prompts: BOTH,
+        usage: "unlock <subkey>",
+        summary: "let go of a lock this node holds, before it runs out",
+        example: "unlock 2",
+        api: &["RoutingContext::get_dht_value", "RoutingContext::set_dht_value"],
+    },
+    CommandInfo {
+        name: "publish",
+        prompts: BOTH,
+        usage: "publish every <interval> subkey <n> from-command \"<cmd>\" | template \"<text>\"",
+        summary: "write a command's output or a filled-in template to a subkey on a timer; `publish` lists them, `publish stop <id>` stops one",
+        example: "publish every 5m subkey 3 from-command \"uptime\"",
+        api: &["RoutingContext::set_dht_value"],
+    },
+    CommandInfo {
+        name: "set-option",
+        prompts: BOTH,
+        usage: "set-option [refresh live|enter | default-subkey N|none | confirm on|off | color on|off | edit-mode emacs|vi]",
+        summary: "how this prompt behaves, kept in the table store for next time; on its own it shows them",
+        example: "set-option confirm on",
+        api: &["TableStore::open", "TableDB::store_json"],
+    },
+    CommandInfo {
+        name: "assert",
+        prompts: BOTH,
+        usage: "assert <subkey> <text>",
+        summary: "read the subkey fresh from the network and say PASS if it shows <text>, FAIL with what it shows if not",
+        example: "assert 2 hello",
+        api: &["RoutingContext::get_dht_value"],
+    },
+    CommandInfo {
+        name: "wait-for",
+        prompts: BOTH,
+        usage: "wait-for <subkey> --equals <text> [--timeout 30s]",
+        summary: "read the subkey every half second until it shows <text> (PASS, with how long it took) or the timeout runs out (FAIL)",
+        example: "wait-for 2 --equals hello --timeout 1m",
+        api: &["RoutingContext::get_dht_value"],
+    },
+    CommandInfo {
+        name: "diag bundle",
+        prompts: BOTH,
+        usage: "diag bundle",
+        summary: "write a zip for bug reports (version, config, Veilid's state, stats, recent audit entries and logs) into the data folder, secrets left out",
+        example: "diag bundle",
+        api: &["VeilidAPI::get_state", "veilid_version_string"],
+    },
+    CommandInfo {
+        name: "stats events",
+        prompts: BOTH,
+        usage: "stats events",
+        summary: "the queues between Veilid's update callback and the screen: how full each gets and how many updates were dropped",
+        example: "stats events",
+        api: &["VeilidUpdate"],
+    },
+    CommandInfo {
+        name: "stats watch",
+        prompts: &[Prompt::Alt],
+        usage: "stats watch",
+        summary: "how the watch on the record is doing: changes delivered, missed seqs, delivery delay",
+        example: "stats watch",
+        api: &["RoutingContext::watch_dht_values", "VeilidUpdate::ValueChange"],
+    },
+    CommandInfo {
+        name: "stats order",
+        prompts: &[Prompt::Alt],
+        usage: "stats order",
+        summary: "per writer: how many of its numbered messages arrived, which never did, and which came out of order",
+        example: "stats order",
+        api: &["VeilidUpdate::ValueChange"],
+    },
+    CommandInfo {
+        name: "watch",
+        prompts: &[Prompt::Alt],
+        usage: "watch <record>",
+        summary: "follow another record's changes as well (by shortcode or key); lines are tagged with the record's shortcode",
+        example: "watch harp-otter-coal-lime",
+        api: &["RoutingContext::open_dht_record", "RoutingContext::watch_dht_values"],
+    },
+    CommandInfo {
+        name: "read",
+        prompts: &[Prompt::Alt],
+        usage: "read <subkey>",
+        summary: "read one subkey from the network, and fetch the rest of the record in the background so reading them next is instant",
+        example: "read 3",
+        api: &["RoutingContext::get_dht_value"],
+    },
+    CommandInfo {
+        name: "watch status",
+        prompts: &[Prompt::Alt],
+        usage: "watch status",
+        summary: "ask Veilid about each watch again: still active, time left before watch_expiry_secs, changes and count left; 'r' next renews them",
+        example: "watch status",
+        api: &["RoutingContext::watch_dht_values"],
+    },
+    CommandInfo {
+        name: "feed",
+        prompts: &[Prompt::Alt],
+        usage: "feed [--record X] [--member NAME]",
+        summary: "every change from every watched record in one timeline, oldest first, optionally just one record or writer; 'feed off' stops it",
+        example: "feed --member alice",
+        api: &["VeilidUpdate::ValueChange", "ValueData::writer"],
+    },
+    CommandInfo {
+        name: "watching",
+        prompts: &[Prompt::Alt],
+        usage: "watching",
+        summary: "list the records being watched",
+        example: "watching",
+        api: &[],
+    },
+    CommandInfo {
+        name: "nick",
+        prompts: &[Prompt::Alt],
+        usage: "nick <public key> <name>",
+        summary: "give a writer's key a name, shown next to everything they write (saved in nicknames.json)",
+        example: "nick VLD0:AbCd...xyz alice",
+        api: &["ValueData::writer"],
+    },
+    CommandInfo {
+        name: "contact",
+        prompts: BOTH,
+        usage: "contact add|list|remove ...",
+        summary: "the peers we know (added by hand, or from profile cards swapped when nodes meet), kept in the table store",
+        example: "contact add alice --key VLD0:AbCd...xyz --record harp-otter-coal-lime",
+        api: &["TableStore::open", "TableDB::store_json", "RoutingContext::app_message"],
+    },
+    CommandInfo {
+        name: "logs",
+        prompts: BOTH,
+        usage: "logs follow|stop <contact>",
+        summary: "stream a contact's audit log to this prompt for ten minutes, to debug their node from here (they must have us as a contact too)",
+        example: "logs follow alt-node",
+        api: &["RoutingContext::app_message", "VeilidAPI::import_remote_private_route"],
+    },
+    CommandInfo {
+        name: "relay",
+        prompts: BOTH,
+        usage: "relay [on|off|join <contact>|leave <contact>]",
+        summary: "relay mode: the alt node passes each change its watch sees on to contacts who join, over app_message, so they need no watch of their own",
+        example: "relay join alt-node",
+        api: &["RoutingContext::app_message", "VeilidAPI::import_remote_private_route", "VeilidAPI::release_private_route"],
+    },
+    CommandInfo {
+        name: ":msg",
+        prompts: BOTH,
+        usage: ":msg <text>",
+        summary: "send a line of chat straight to the other node over its private route (from the profile cards swapped when the alt node joins)",
+        example: ":msg are you seeing my writes?",
+        api: &["RoutingContext::app_message", "VeilidAPI::import_remote_private_route", "VeilidUpdate::AppMessage"],
+    },
+    CommandInfo {
+        name: "inbox",
+        prompts: &[Prompt::Default],
+        usage: "inbox",
+        summary: "read the mail left in the record's inbox subkeys, free their slots and tell the senders it's been read",
+        example: "inbox",
+        api: &["RoutingContext::get_dht_value", "CryptoSystem::hpke_open"],
+    },
+    CommandInfo {
+        name: "mail",
+        prompts: &[Prompt::Alt],
+        usage: "mail <text>",
+        summary: "leave the record's owner a message in its inbox, sealed to their key; it waits there until they read it",
+        example: "mail back online at six",
+        api: &["CryptoSystem::hpke_seal", "RoutingContext::set_dht_value"],
+    },
+    CommandInfo {
+        name: "outbox",
+        prompts: &[Prompt::Alt],
+        usage: "outbox",
+        summary: "the mail you've sent this record and whether it's been delivered or read, from the owner's receipts subkey",
+        example: "outbox",
+        api: &["RoutingContext::get_dht_value", "TableDB::load_json"],
+    },
+    CommandInfo {
+        name: "help",
+        prompts: BOTH,
+        usage: "help [command]",
+        summary: "list the commands, or show one in detail",
+        example: "help flood",
+        api: &[],
+    },
+];
+
+pub fn for_prompt(prompt: Prompt) -> impl Iterator<Item = &'static CommandInfo> {
+    COMMANDS.iter().filter(move |c| c.prompts.contains(&prompt))
+}
+
+pub fn names(prompt: Prompt) -> Vec<&'static str> {
+    for_prompt(prompt).map(|c| c.name).collect()
+}
+
+// `help` (topic empty) or `help <topic>`
+pub fn help(prompt: Prompt, topic: &str) -> String {
+    let topic = topic.trim();
+    if topic.is_empty() {
+        let mut out = String::from("Commands:\n");
+        for c in for_prompt(prompt) {
+            out.push_str(&format!("  {:<26} {}\n", c.usage, c.summary));
+        }
+        out.push_str(match prompt {
+            Prompt::Default => "  (anything else)            write it to the DHT\n",
+            Prompt::Alt => "  (just ENTER)               read every subkey of the record\n",
+        });
+        out.push_str("Type 'help <command>' for an example.");
+        return out;
+    }
+    match for_prompt(prompt).find(|c| c.name == topic) {
+        Some(c) => {
+            let mut out = format!("{}\n  {}\n  example: {}", c.usage, c.summary, c.example);
+            if !c.api.is_empty() {
+                out.push_str(&format!("\n  Veilid API: {}", c.api.join(", ")));
+            }
+            out
+        }
+        None => format!("No command '{topic}' here. Type 'help' to see them all."),
+    }
+}
@@ -0,0 +1,793 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::cli::Options;
+use crate::keyfile::{Capability, JoinPreset};
+use crate::paths;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The example's settings, merged from (later ones win):
+//	  1: built-in defaults (the values the example always used)
+//	  2: a JSON config file  (config.json in the config folder, or --config PATH)
+//	  3: environment variables (VEILID_EXAMPLE_*)
+//	  4: command line flags
+//
+//	`config validate` checks the merged result and reports every problem it
+//	finds in one go, before we spend any time starting Veilid.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const ENV_PREFIX: &str = "VEILID_EXAMPLE_";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AppConfig {
+    pub program_name: String,
+    // each role gets its own namespace so they don't share Veilid storage
+    pub default_namespace: String,
+    pub alt_namespace: String,
+    pub member_namespace: String,
+    // overrides the platform data folder when set
+    pub data_dir: Option<PathBuf>,
+    // keep everything next to the executable (--portable)
+    pub portable: bool,
+    // the kind of record the default node makes: "smpl", or "dflt:<count>"
+    // for a DFLT record of <count> subkeys that are all its own (see RecordSchema)
+    pub record_schema: String,
+    // SMPL schema: subkeys for the owner, and for the one member
+    // (owner subkeys 0 to 3 hold the metadata, receipts, protocol version and private route)
+    pub owner_subkeys: u16,
+    pub member_subkeys: u16,
+    // the mailbox's inbox subkeys, after the member's (0 = no mailbox)
+    pub inbox_subkeys: u16,
+    // which subkey the default node writes to (must be one of the member's;
+    // None = the first one the record's schema lets it write)
+    pub write_subkey: Option<u32>,
+    // values the default node writes expire after this many seconds (None = never)
+    pub value_ttl_secs: Option<u64>,
+    // watches are placed for this many seconds, `watch status` renews them (None = until cancelled)
+    pub watch_expiry_secs: Option<u64>,
+    // POST each watched change to this http:// URL as JSON, signed with
+    // webhook_secret if set, tried webhook_retries more times if it fails (see webhook.rs)
+    pub webhook_url: Option<String>,
+    pub webhook_secret: Option<String>,
+    pub webhook_retries: u32,
+    // routing context used for every DHT call: private (safety) routes or direct,
+    // and prefer_unordered / prefer_ordered / ensure_ordered protocols
+    pub safe_routing: bool,
+    pub sequencing: String,
+    // when the node counts as attached: public_internet, local_network or peers
+    // (at least ready_min_peers), and how long to wait before carrying on
+    // anyway with a warning (None = for ever); see ready.rs
+    pub ready_when: String,
+    pub ready_min_peers: u64,
+    pub ready_timeout_secs: Option<u64>,
+    // stop with exit code 4 at the timeout instead of carrying on (for scripts; see exit.rs)
+    pub ready_timeout_exit: bool,
+    // early DHT mode (--early): go ahead once attached at all, however weakly,
+    // and retry calls that come back TryAgain for early_retry_secs
+    pub early_dht: bool,
+    pub early_retry_secs: u64,
+    // what the default node calls its record in the metadata block (subkey 0)
+    pub record_title: String,
+    // announce the default node's record in the public app index under this label (None = don't)
+    pub announce_label: Option<String>,
+    // the nickname on this node's profile card (None = the role, like "alt-node")
+    pub profile_name: Option<String>,
+    // fields every writer keeps its own copy of, at the end of its range (see fields.rs)
+    pub shared_fields: Vec<String>,
+    // a member subkey kept for advisory write locks, and how long `lock`
+    // holds one unless told (None = no locking); see locks.rs
+    pub lock_subkey: Option<u32>,
+    pub lock_secs: u64,
+    // what owner_keys.txt lets its holder do: read, write-own-subkeys or admin (see keyfile.rs)
+    pub share_grant: String,
+    // subkey -> the type of value it holds: presence, chat, manifest or metadata (see payloads.rs)
+    pub payload_types: BTreeMap<ValueSubkey, String>,
+    // the codecs what the nodes write goes through, in order, e.g. ["deflate"] (see codecs.rs)
+    pub value_codecs: Vec<String>,
+    // false: Veilid's secrets go in the OS keychain under a password (see store.rs)
+    pub always_use_insecure_storage: bool,
+    // a program that prints that password, so nobody has to type it (None = ask)
+    pub store_password_command: Option<String>,
+
+    // where each setting came from, for `config show --effective`
+    #[serde(skip)]
+    pub sources: Vec<String>,
+}
+
+impl Default for AppConfig {
+    fn default() -> AppConfig {
+        AppConfig {
+            program_name: "Example Veilid".to_string(),
+            default_namespace: "veilid-example-ver1".to_string(),
+            alt_namespace: "veilid-example-ver2".to_string(),
+            member_namespace: "veilid-example-ver3".to_string(),
+            data_dir: None,
+            portable: false,
+            record_schema: "smpl".to_string(),
+            owner_subkeys: 4,
+            member_subkeys: 2,
+            inbox_subkeys: 4,
+            write_subkey: None,
+            value_ttl_secs: None,
+            watch_expiry_secs: None,
+            webhook_url: None,
+            webhook_secret: None,
+            webhook_retries: 3,
+            safe_routing: true,
+            sequencing: "prefer_ordered".to_string(),
+            ready_when: "public_internet".to_string(),
+            ready_min_peers: 4,
+            ready_timeout_secs: None,
+            ready_timeout_exit: false,
+            early_dht: false,
+            early_retry_secs: 180,
+            record_title: "Veilid DHT example".to_string(),
+            announce_label: None,
+            profile_name: None,
+            shared_fields: Vec::new(),
+            lock_subkey: None,
+            lock_secs: 120,
+            share_grant: "read".to_string(),
+            payload_types: BTreeMap::from([(crate::metadata::METADATA_SUBKEY, "metadata".to_string())]),
+            value_codecs: Vec::new(),
+            always_use_insecure_storage: true,
+            store_password_command: None,
+            sources: Vec::new(),
+        }
+    }
+}
+
+// The kind of record the default node makes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordSchema {
+    // SMPL: owner subkeys, then the member's and the mailbox's
+    Smpl,
+    // DFLT with this many subkeys, every one of them the record owner's
+    // (the default node makes the record with its member key as the owner)
+    Dflt(u16),
+}
+
+impl RecordSchema {
+    // "smpl" or "dflt:<count>"
+    pub fn parse(text: &str) -> Result<RecordSchema, String> {
+        match text.split_once(':') {
+            None if text == "smpl" => Ok(RecordSchema::Smpl),
+            Some(("dflt", count)) => count
+                .parse()
+                .ok()
+                .filter(|&count: &u16| count > 0)
+                .map(RecordSchema::Dflt)
+                .ok_or_else(|| format!("dflt:<count> needs a subkey count of 1 to 65535, got '{count}'")),
+            _ => Err(format!("schema '{text}' should be smpl or dflt:<count>")),
+        }
+    }
+}
+
+impl AppConfig {
+    pub fn load(options: &Options) -> Result<AppConfig, String> {
+        let mut config = AppConfig::default();
+        config.sources.push("built-in defaults".to_string());
+
+        // ---- 2: config file ----
+        // an explicitly named file has to exist, the default one is optional
+        let (path, must_exist) = match config_file_path(options) {
+            Some(p) => (p, true),
+            None => (paths::config_dir(options.portable).join("config.json"), false),
+        };
+        if path.exists() {
+            let text = fs::read_to_string(&path)
+                .map_err(|e| format!("can't read config file {}: {e}", path.to_string_lossy()))?;
+            config = serde_json::from_str(&text)
+                .map_err(|e| format!("bad config file {}: {e}", path.to_string_lossy()))?;
+            config.sources = vec![
+                "built-in defaults".to_string(),
+                format!("file {}", path.to_string_lossy()),
+            ];
+        } else if must_exist {
+            return Err(format!("config file {} does not exist", path.to_string_lossy()));
+        }
+
+        // ---- 3: environment ----
+        config.apply_env()?;
+
+        // ---- 4: flags ----
+        if options.portable {
+            config.portable = true;
+            config.sources.push("flag --portable".to_string());
+        }
+        if let Some(dir) = &options.data_dir {
+            config.data_dir = Some(dir.clone());
+            config.sources.push("flag --data-dir".to_string());
+        }
+        if options.early {
+            config.early_dht = true;
+            config.sources.push("flag --early".to_string());
+        }
+        if let Some(secs) = options.ttl_secs {
+            config.value_ttl_secs = Some(secs);
+            config.sources.push("flag --ttl".to_string());
+        }
+        if let Some(schema) = &options.schema {
+            config.record_schema = schema.clone();
+            config.sources.push("flag --schema".to_string());
+        }
+
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<(), String> {
+        let var = |name: &str| std::env::var(format!("{ENV_PREFIX}{name}")).ok();
+        let mut applied = Vec::new();
+
+        if let Some(v) = var("DEFAULT_NAMESPACE") {
+            self.default_namespace = v;
+            applied.push("DEFAULT_NAMESPACE");
+        }
+        if let Some(v) = var("ALT_NAMESPACE") {
+            self.alt_namespace = v;
+            applied.push("ALT_NAMESPACE");
+        }
+        if let Some(v) = var("MEMBER_NAMESPACE") {
+            self.member_namespace = v;
+            applied.push("MEMBER_NAMESPACE");
+        }
+        if let Some(v) = var("DATA_DIR") {
+            self.data_dir = Some(v.into());
+            applied.push("DATA_DIR");
+        }
+        if let Some(v) = var("PORTABLE") {
+            self.portable = matches!(v.as_str(), "1" | "true" | "yes");
+            applied.push("PORTABLE");
+        }
+        if let Some(v) = var("RECORD_SCHEMA") {
+            self.record_schema = v;
+            applied.push("RECORD_SCHEMA");
+        }
+        if let Some(v) = var("OWNER_SUBKEYS") {
+            self.owner_subkeys = parse_env("OWNER_SUBKEYS", &v)?;
+            applied.push("OWNER_SUBKEYS");
+        }
+        if let Some(v) = var("MEMBER_SUBKEYS") {
+            self.member_subkeys = parse_env("MEMBER_SUBKEYS", &v)?;
+            applied.push("MEMBER_SUBKEYS");
+        }
+        if let Some(v) = var("INBOX_SUBKEYS") {
+            self.inbox_subkeys = parse_env("INBOX_SUBKEYS", &v)?;
+            applied.push("INBOX_SUBKEYS");
+        }
+        if let Some(v) = var("WRITE_SUBKEY") {
+            self.write_subkey = Some(parse_env("WRITE_SUBKEY", &v)?);
+            applied.push("WRITE_SUBKEY");
+        }
+        if let Some(v) = var("VALUE_TTL_SECS") {
+            self.value_ttl_secs = Some(parse_env("VALUE_TTL_SECS", &v)?);
+            applied.push("VALUE_TTL_SECS");
+        }
+        if let Some(v) = var("WATCH_EXPIRY_SECS") {
+            // empty for watches that don't expire
+            self.watch_expiry_secs = if v.is_empty() { None } else { Some(parse_env("WATCH_EXPIRY_SECS", &v)?) };
+            applied.push("WATCH_EXPIRY_SECS");
+        }
+        if let Some(v) = var("WEBHOOK_URL") {
+            self.webhook_url = Some(v).filter(|v| !v.is_empty());
+            applied.push("WEBHOOK_URL");
+        }
+        if let Some(v) = var("WEBHOOK_SECRET") {
+            self.webhook_secret = Some(v).filter(|v| !v.is_empty());
+            applied.push("WEBHOOK_SECRET");
+        }
+        if let Some(v) = var("WEBHOOK_RETRIES") {
+            self.webhook_retries = parse_env("WEBHOOK_RETRIES", &v)?;
+            applied.push("WEBHOOK_RETRIES");
+        }
+        if let Some(v) = var("SAFE_ROUTING") {
+            self.safe_routing = matches!(v.as_str(), "1" | "true" | "yes");
+            applied.push("SAFE_ROUTING");
+        }
+        if let Some(v) = var("SEQUENCING") {
+            self.sequencing = v;
+            applied.push("SEQUENCING");
+        }
+        if let Some(v) = var("READY_WHEN") {
+            self.ready_when = v;
+            applied.push("READY_WHEN");
+        }
+        if let Some(v) = var("READY_MIN_PEERS") {
+            self.ready_min_peers = parse_env("READY_MIN_PEERS", &v)?;
+            applied.push("READY_MIN_PEERS");
+        }
+        if let Some(v) = var("READY_TIMEOUT_SECS") {
+            // empty for no timeout
+            self.ready_timeout_secs = if v.is_empty() { None } else { Some(parse_env("READY_TIMEOUT_SECS", &v)?) };
+            applied.push("READY_TIMEOUT_SECS");
+        }
+        if let Some(v) = var("READY_TIMEOUT_EXIT") {
+            self.ready_timeout_exit = matches!(v.as_str(), "1" | "true" | "yes");
+            applied.push("READY_TIMEOUT_EXIT");
+        }
+        if let Some(v) = var("EARLY_DHT") {
+            self.early_dht = matches!(v.as_str(), "1" | "true" | "yes");
+            applied.push("EARLY_DHT");
+        }
+        if let Some(v) = var("EARLY_RETRY_SECS") {
+            self.early_retry_secs = parse_env("EARLY_RETRY_SECS", &v)?;
+            applied.push("EARLY_RETRY_SECS");
+        }
+        if let Some(v) = var("LOCK_SUBKEY") {
+            // empty for no locking
+            self.lock_subkey = if v.is_empty() { None } else { Some(parse_env("LOCK_SUBKEY", &v)?) };
+            applied.push("LOCK_SUBKEY");
+        }
+        if let Some(v) = var("LOCK_SECS") {
+            self.lock_secs = parse_env("LOCK_SECS", &v)?;
+            applied.push("LOCK_SECS");
+        }
+        if let Some(v) = var("RECORD_TITLE") {
+            self.record_title = v;
+            applied.push("RECORD_TITLE");
+        }
+        if let Some(v) = var("ANNOUNCE_LABEL") {
+            self.announce_label = Some(v).filter(|v| !v.is_empty());
+            applied.push("ANNOUNCE_LABEL");
+        }
+        if let Some(v) = var("PROFILE_NAME") {
+            self.profile_name = Some(v).filter(|v| !v.is_empty());
+            applied.push("PROFILE_NAME");
+        }
+        if let Some(v) = var("SHARED_FIELDS") {
+            // comma separated, empty for none
+            self.shared_fields = v.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()).collect();
+            applied.push("SHARED_FIELDS");
+        }
+        if let Some(v) = var("VALUE_CODECS") {
+            // comma separated, empty for none
+            self.value_codecs = v.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect();
+            applied.push("VALUE_CODECS");
+        }
+        if let Some(v) = var("SHARE_GRANT") {
+            self.share_grant = v;
+            applied.push("SHARE_GRANT");
+        }
+        if let Some(v) = var("PAYLOAD_TYPES") {
+            // subkey=type, comma separated, empty for none: "0=metadata,3=presence"
+            let mut types = BTreeMap::new();
+            for pair in v.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                let (subkey, name) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("{ENV_PREFIX}PAYLOAD_TYPES: expected subkey=type, got '{pair}'"))?;
+                types.insert(parse_env("PAYLOAD_TYPES", subkey.trim())?, name.trim().to_string());
+            }
+            self.payload_types = types;
+            applied.push("PAYLOAD_TYPES");
+        }
+        if let Some(v) = var("ALWAYS_USE_INSECURE_STORAGE") {
+            self.always_use_insecure_storage = matches!(v.as_str(), "1" | "true" | "yes");
+            applied.push("ALWAYS_USE_INSECURE_STORAGE");
+        }
+        if let Some(v) = var("STORE_PASSWORD_COMMAND") {
+            self.store_password_command = Some(v).filter(|v| !v.is_empty());
+            applied.push("STORE_PASSWORD_COMMAND");
+        }
+
+        for name in applied {
+            self.sources.push(format!("env {ENV_PREFIX}{name}"));
+        }
+        Ok(())
+    }
+
+    // The folder all our files go in (created if needed).
+    pub fn data_dir(&self) -> std::io::Result<PathBuf> {
+        match &self.data_dir {
+            Some(dir) => {
+                fs::create_dir_all(dir)?;
+                Ok(dir.clone())
+            }
+            None => paths::data_dir(self.portable),
+        }
+    }
+
+    // The first subkey that belongs to the member (owner subkeys come first).
+    // What this node calls itself on its profile card.
+    pub fn profile_name(&self, role: &str) -> String {
+        self.profile_name.clone().unwrap_or_else(|| format!("{role}-node"))
+    }
+
+    pub fn first_member_subkey(&self) -> u32 {
+        u32::from(self.owner_subkeys)
+    }
+
+    // The subkey the default node will write, as far as the config can say
+    // before there's a record: write_subkey, or the member's first.
+    pub fn planned_write_subkey(&self) -> u32 {
+        self.write_subkey.unwrap_or(self.first_member_subkey())
+    }
+
+    // Owner and member subkeys; a DFLT record's are all the owner's, and the
+    // ones past owner_subkeys stand in for the member's.
+    pub fn total_subkeys(&self) -> u32 {
+        match self.record_schema() {
+            Ok(RecordSchema::Dflt(count)) => u32::from(count),
+            _ => u32::from(self.owner_subkeys) + u32::from(self.member_subkeys),
+        }
+    }
+
+    // The mailbox's subkeys (inclusive), if it has any. A DFLT record has no
+    // member for the drop key, so no mailbox.
+    pub fn inbox_range(&self) -> Option<(u32, u32)> {
+        (self.inbox_subkeys > 0 && self.record_schema() == Ok(RecordSchema::Smpl))
+            .then(|| (self.total_subkeys(), self.total_subkeys() + u32::from(self.inbox_subkeys) - 1))
+    }
+
+    // Where the default node keeps each shared field: the member range's last
+    // subkey for the first field, the one before it for the next, and so on.
+    // validate() reports the ones that don't fit.
+    pub fn field_subkeys(&self) -> BTreeMap<String, ValueSubkey> {
+        match self.total_subkeys().checked_sub(1) {
+            Some(last) => crate::fields::layout(&self.shared_fields, self.first_member_subkey(), last, Some(self.planned_write_subkey())),
+            None => BTreeMap::new(),
+        }
+    }
+
+    pub fn value_ttl(&self) -> Option<Duration> {
+        self.value_ttl_secs.map(Duration::from_secs)
+    }
+
+    pub fn watch_expiry(&self) -> Option<Duration> {
+        self.watch_expiry_secs.map(Duration::from_secs)
+    }
+
+    pub fn ready_timeout(&self) -> Option<Duration> {
+        self.ready_timeout_secs.map(Duration::from_secs)
+    }
+
+    // How long the Dht wrapper keeps retrying TryAgain, in early DHT mode.
+    pub fn early_retry(&self) -> Option<Duration> {
+        self.early_dht.then(|| Duration::from_secs(self.early_retry_secs))
+    }
+
+    // What the default node suggests to whoever joins its record (see keyfile.rs).
+    pub fn join_preset(&self) -> JoinPreset {
+        JoinPreset {
+            namespace: Some(self.alt_namespace.clone()),
+            subkeys: Some((0, self.total_subkeys().saturating_sub(1))),
+            encryption: Some("none".to_string()),
+        }
+    }
+
+    // Take on an invite's suggestions. Returns the subkeys to follow, if it named them.
+    pub fn apply_join_preset(&mut self, preset: &JoinPreset) -> Option<(ValueSubkey, ValueSubkey)> {
+        if let Some(ns) = &preset.namespace {
+            self.alt_namespace = ns.clone();
+            self.sources.push("owner_keys.txt Alt.Namespace".to_string());
+        }
+        preset.subkeys
+    }
+
+    pub fn sequencing(&self) -> Result<Sequencing, String> {
+        match self.sequencing.as_str() {
+            "prefer_unordered" => Ok(Sequencing::PreferUnordered),
+            "prefer_ordered" => Ok(Sequencing::PreferOrdered),
+            "ensure_ordered" => Ok(Sequencing::EnsureOrdered),
+            other => Err(format!(
+                "sequencing '{other}' should be prefer_unordered, prefer_ordered or ensure_ordered"
+            )),
+        }
+    }
+
+    pub fn record_schema(&self) -> Result<RecordSchema, String> {
+        RecordSchema::parse(&self.record_schema)
+    }
+
+    pub fn share_grant(&self) -> Result<Capability, String> {
+        Capability::from_name(&self.share_grant).ok_or_else(|| {
+            format!("share_grant '{}' should be read, write-own-subkeys or admin", self.share_grant)
+        })
+    }
+
+    // Check everything we can without starting Veilid. Returns every problem found.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        // ---- namespaces ----
+        let namespaces = [
+            ("default_namespace", &self.default_namespace),
+            ("alt_namespace", &self.alt_namespace),
+            ("member_namespace", &self.member_namespace),
+        ];
+        for (name, ns) in namespaces {
+            if ns.is_empty() {
+                problems.push(format!("{name} is empty"));
+            } else if !ns
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                problems.push(format!(
+                    "{name} '{ns}' should only use letters, digits, '-' and '_' (it becomes a folder name)"
+                ));
+            }
+        }
+        for (i, (a, ns)) in namespaces.iter().enumerate() {
+            for (b, other) in &namespaces[i + 1..] {
+                if ns == other {
+                    problems.push(format!("{a} and {b} are both '{ns}': the two nodes would fight over the same storage"));
+                }
+            }
+        }
+
+        // ---- schema ----
+        match self.record_schema() {
+            Err(e) => problems.push(e),
+            Ok(RecordSchema::Dflt(count)) => {
+                if let Err(e) = DHTSchema::dflt(count).and_then(|schema| schema.validate()) {
+                    problems.push(format!("record_schema dflt:{count} is invalid: {e}"));
+                }
+                if u32::from(count) <= self.first_member_subkey() {
+                    problems.push(format!(
+                        "record_schema dflt:{count} leaves nothing past the {} owner subkeys (metadata, receipts, protocol version) for the default node to write",
+                        self.owner_subkeys
+                    ));
+                }
+            }
+            Ok(RecordSchema::Smpl) => {
+                // A throwaway member id is enough for Veilid to check the counts.
+                let mut members = vec![DHTSchemaSMPLMember {
+                    m_key: BareMemberId::new(&[0u8; 32]),
+                    m_cnt: self.member_subkeys,
+                }];
+                if self.inbox_subkeys > 0 {
+                    members.push(DHTSchemaSMPLMember {
+                        m_key: BareMemberId::new(&[1u8; 32]),
+                        m_cnt: self.inbox_subkeys,
+                    });
+                }
+                match DHTSchema::smpl(self.owner_subkeys, members) {
+                    Ok(schema) => {
+                        if let Err(e) = schema.validate() {
+                            problems.push(format!("schema (owner/member/inbox_subkeys) is invalid: {e}"));
+                        }
+                    }
+                    Err(e) => problems.push(format!("schema (owner/member/inbox_subkeys) is invalid: {e}")),
+                }
+                if self.member_subkeys == 0 {
+                    problems.push("member_subkeys is 0: the default node writes as the member, so it needs at least one".to_string());
+                }
+            }
+        }
+        if let Some(write_subkey) = self.write_subkey {
+            if write_subkey < self.first_member_subkey() || write_subkey >= self.total_subkeys() {
+                problems.push(format!(
+                    "write_subkey {write_subkey} is not a member subkey (member subkeys are {}..{})",
+                    self.first_member_subkey(),
+                    self.total_subkeys()
+                ));
+            }
+        }
+
+        for (i, name) in self.shared_fields.iter().enumerate() {
+            let subkey = self.total_subkeys().checked_sub(1 + i as u32);
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                problems.push(format!("shared_fields: '{name}' can't be empty or have spaces in it"));
+            } else if self.shared_fields[..i].contains(name) {
+                problems.push(format!("shared_fields: '{name}' is listed twice"));
+            } else if subkey.is_none_or(|s| s < self.first_member_subkey() || s == self.planned_write_subkey()) {
+                problems.push(format!(
+                    "shared_fields: no member subkey left for '{name}' (they're taken from the end of {}..{}, skipping write_subkey {}); raise member_subkeys or drop the field",
+                    self.first_member_subkey(),
+                    self.total_subkeys(),
+                    self.planned_write_subkey()
+                ));
+            }
+        }
+
+        if let Some(lock_subkey) = self.lock_subkey {
+            if lock_subkey < self.first_member_subkey() || lock_subkey >= self.total_subkeys() {
+                problems.push(format!(
+                    "lock_subkey {lock_subkey} is not a member subkey (member subkeys are {}..{}), so the nodes can't both write it",
+                    self.first_member_subkey(),
+                    self.total_subkeys()
+                ));
+            } else if lock_subkey == self.planned_write_subkey() || self.field_subkeys().values().any(|&s| s == lock_subkey) {
+                problems.push(format!("lock_subkey {lock_subkey} is already the write subkey or a shared field's"));
+            }
+        }
+        if self.lock_secs == 0 {
+            problems.push("lock_secs is 0: a lock would run out as soon as it was taken".to_string());
+        }
+
+        if let Err(e) = self.sequencing() {
+            problems.push(e);
+        }
+        if let Err(e) = crate::ready::ReadyWhen::from_config(self) {
+            problems.push(e);
+        }
+        if self.watch_expiry_secs.is_some_and(|secs| secs < 60) {
+            problems.push("watch_expiry_secs is under a minute: too short to renew by hand, and Veilid refuses watches that end within its RPC timeout".to_string());
+        }
+        if let Err(e) = crate::webhook::Webhook::from_config(self) {
+            problems.push(e);
+        }
+        if self.webhook_secret.is_some() && self.webhook_url.is_none() {
+            problems.push("webhook_secret is set, but there's no webhook_url to sign for".to_string());
+        }
+        if self.ready_timeout_secs == Some(0) {
+            problems.push("ready_timeout_secs is 0: the node wouldn't wait to attach at all (leave it out to wait for ever)".to_string());
+        }
+        if self.ready_timeout_exit && self.ready_timeout_secs.is_none() {
+            problems.push("ready_timeout_exit is set, but there's no ready_timeout_secs to stop at".to_string());
+        }
+        if let Err(e) = self.share_grant() {
+            problems.push(e);
+        }
+        if let Err(e) = crate::payloads::Registry::new(&self.payload_types) {
+            problems.push(e);
+        }
+        if let Err(e) = crate::codecs::pipeline(&self.value_codecs) {
+            problems.push(format!("value_codecs: {e}"));
+        }
+        for subkey in self.payload_types.keys().filter(|&&s| s >= self.total_subkeys()) {
+            problems.push(format!(
+                "payload_types: subkey {subkey} is past the record's last subkey ({})",
+                self.total_subkeys() - 1
+            ));
+        }
+        if self.value_ttl_secs == Some(0) {
+            problems.push("value_ttl_secs is 0: values would expire as soon as they're written".to_string());
+        }
+        if self.always_use_insecure_storage && self.store_password_command.is_some() {
+            problems.push(
+                "store_password_command is set, but there's no password to get while always_use_insecure_storage is true".to_string(),
+            );
+        }
+
+        // ---- folders ----
+        // (only looked at here; preflight makes them and checks they're writable)
+        if let Some(dir) = self.data_dir.as_ref().filter(|dir| dir.exists() && !dir.is_dir()) {
+            problems.push(format!("data_dir {} is a file, not a folder", dir.to_string_lossy()));
+        }
+
+        problems
+    }
+}
+
+fn config_file_path(options: &Options) -> Option<PathBuf> {
+    options
+        .config_file
+        .clone()
+        .or_else(|| std::env::var(format!("{ENV_PREFIX}CONFIG")).ok().map(PathBuf::from))
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{ENV_PREFIX}{name} has a bad value '{value}'"))
+}
+
+// -------------------------------------------------------------------------
+// `config validate` and `config show [--effective]`
+// -------------------------------------------------------------------------
+
+// Returns true if the config is good.
+pub fn print_validation(config: &AppConfig) -> bool {
+    let problems = config.validate();
+    if problems.is_empty() {
+        println!("Config OK");
+        return true;
+    }
+    println!("Found {} problem(s):", problems.len());
+    for p in &problems {
+        println!("  - {p}");
+    }
+    false
+}
+
+pub fn show(config: &AppConfig, effective: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if effective {
+        println!("{}", serde_json::to_string_pretty(config)?);
+        println!();
+        println!("Built from:");
+        for s in &config.sources {
+            println!("  {s}");
+        }
+        if let Ok(dir) = config.data_dir() {
+            println!("Data folder: {}", dir.to_string_lossy());
+        }
+    } else {
+        // just the file, if there is one
+        match config.sources.iter().find_map(|s| s.strip_prefix("file ")) {
+            Some(path) => println!("{}", fs::read_to_string(path)?),
+            None => println!("No config file in use (see `config show --effective` for the defaults)"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(change: impl FnOnce(&mut AppConfig)) -> Vec<String> {
+        let mut config = AppConfig::default();
+        change(&mut config);
+        config.validate()
+    }
+
+    #[test]
+    fn the_defaults_are_valid() {
+        assert_eq!(AppConfig::default().validate(), Vec::<String>::new());
+        assert_eq!(AppConfig::default().inbox_range(), Some((6, 9)));
+    }
+
+    #[test]
+    fn every_problem_is_reported_at_once() {
+        let found = problems(|c| {
+            c.alt_namespace = c.default_namespace.clone();
+            c.member_namespace = "has spaces".to_string();
+            c.member_subkeys = 0;
+            c.sequencing = "sideways".to_string();
+            c.lock_secs = 0;
+            c.webhook_secret = Some("s".to_string());
+        });
+        assert_eq!(found.len(), 6, "{found:#?}");
+        assert!(found[0].starts_with("member_namespace 'has spaces'"));
+        assert!(found[1].contains("fight over the same storage"));
+        assert!(found.iter().any(|p| p.starts_with("member_subkeys is 0")));
+        assert!(found.iter().any(|p| p.starts_with("sequencing 'sideways'")));
+
+        assert_eq!(problems(|c| c.write_subkey = Some(1)).len(), 1);
+        assert_eq!(problems(|c| c.shared_fields = vec!["a".into(), "a".into()]).len(), 1);
+        assert_eq!(problems(|c| c.ready_timeout_exit = true).len(), 1);
+    }
+
+    #[test]
+    fn dflt_records_need_room_past_the_owner_subkeys() {
+        assert_eq!(RecordSchema::parse("smpl"), Ok(RecordSchema::Smpl));
+        assert_eq!(RecordSchema::parse("dflt:8"), Ok(RecordSchema::Dflt(8)));
+        assert!(RecordSchema::parse("dflt:0").is_err());
+        assert!(RecordSchema::parse("dflt").is_err());
+
+        assert!(problems(|c| c.record_schema = "dflt:4".to_string())[0].contains("leaves nothing past the 4 owner subkeys"));
+        let config = AppConfig {
+            record_schema: "dflt:8".to_string(),
+            ..AppConfig::default()
+        };
+        assert!(config.validate().is_empty());
+        assert_eq!(config.total_subkeys(), 8);
+        assert_eq!(config.inbox_range(), None);
+    }
+
+    #[test]
+    fn flags_win_over_the_file_and_the_file_over_the_defaults() {
+        let dir = std::env::temp_dir().join(format!("config-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        fs::write(&path, r#"{ "owner_subkeys": 6, "value_ttl_secs": 10, "record_title": "from the file" }"#).unwrap();
+        let options = |extra: &[&str]| {
+            let mut args = vec!["--config".to_string(), path.to_string_lossy().into_owned()];
+            args.extend(extra.iter().map(|a| a.to_string()));
+            crate::cli::parse(&args).unwrap()
+        };
+
+        let config = AppConfig::load(&options(&["--ttl=30", "--early"])).unwrap();
+        assert_eq!(config.owner_subkeys, 6);
+        assert_eq!(config.record_title, "from the file");
+        assert_eq!(config.value_ttl_secs, Some(30));
+        assert!(config.early_dht);
+        // anything the file leaves out keeps its default
+        assert_eq!(config.member_subkeys, 2);
+        assert_eq!(config.sources.last().map(String::as_str), Some("flag --ttl"));
+        assert!(config.sources.iter().any(|s| s.starts_with("file ")));
+
+        fs::write(&path, "{ owner_subkeys: 6 }").unwrap();
+        assert!(AppConfig::load(&options(&[])).unwrap_err().starts_with("bad config file"));
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(AppConfig::load(&options(&[])).unwrap_err().ends_with("does not exist"));
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use veilid_core::*;
+
+use crate::dht::Dht;
+use crate::envelope::{Codec, Envelope};
+use crate::i18n::t;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	What happens when two nodes write the same text subkey at once.
+//
+//	Veilid keeps whichever value has the higher seq; a set that comes in
+//	behind a newer value doesn't go in, and gets the newer value back
+//	instead. The prompts used to say "Wrote" anyway and lose the edit.
+//
+//	Now a text write reads what this node had at the subkey first (the
+//	base), and if the set comes back with a newer value (theirs), it shows
+//	base, yours and theirs, with a line-by-line three-way merge of them,
+//	and asks straight after:
+//
+//	  m          write the merge (only if it came out without conflicts)
+//	  y          write yours over theirs
+//	  t          keep theirs, write nothing
+//	  e <text>   write this instead
+//
+//	Whatever goes out is checked the same way, so a third writer turning
+//	up meanwhile just means another round. Typed (JSON) subkeys aren't
+//	merged; the newer value wins there, as before.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq)]
+pub enum Merged {
+    Clean(String),
+    // with <<<<<<< / ||||||| / ======= / >>>>>>> markers around each clash
+    Conflicted(String),
+}
+
+// The pairs of lines a and b have in common, in order (longest common subsequence).
+fn common_lines(a: &[&str], b: &[&str]) -> Vec<(usize, usize)> {
+    // longest[i][j] = LCS length of a[i..] and b[j..]
+    let mut longest = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            longest[i][j] = if a[i] == b[j] {
+                longest[i + 1][j + 1] + 1
+            } else {
+                longest[i + 1][j].max(longest[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j, mut pairs) = (0, 0, Vec::new());
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if longest[i + 1][j] >= longest[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+// Line by line: where only one side changed a stretch of the base, that
+// side's lines win; where both changed it differently, it's a conflict.
+pub fn merge3(base: &str, yours: &str, theirs: &str) -> Merged {
+    if yours == theirs || theirs == base {
+        return Merged::Clean(yours.to_string());
+    }
+    if yours == base {
+        return Merged::Clean(theirs.to_string());
+    }
+    let (b, y, t): (Vec<&str>, Vec<&str>, Vec<&str>) = (base.lines().collect(), yours.lines().collect(), theirs.lines().collect());
+    let in_yours: HashMap<usize, usize> = common_lines(&b, &y).into_iter().collect();
+    let in_theirs: HashMap<usize, usize> = common_lines(&b, &t).into_iter().collect();
+    // base lines both sides kept, which the stretches in between are merged around
+    let kept = (0..b.len()).filter_map(|i| Some((i, *in_yours.get(&i)?, *in_theirs.get(&i)?)));
+
+    let (mut out, mut clean) = (Vec::new(), true);
+    let (mut bi, mut yi, mut ti) = (0, 0, 0);
+    for (bk, yk, tk) in kept.chain(std::iter::once((b.len(), y.len(), t.len()))) {
+        let (bs, ys, ts) = (&b[bi..bk], &y[yi..yk], &t[ti..tk]);
+        if ys == bs {
+            out.extend_from_slice(ts);
+        } else if ts == bs || ys == ts {
+            out.extend_from_slice(ys);
+        } else {
+            clean = false;
+            out.push("<<<<<<< yours");
+            out.extend_from_slice(ys);
+            out.push("||||||| base");
+            out.extend_from_slice(bs);
+            out.push("=======");
+            out.extend_from_slice(ts);
+            out.push(">>>>>>> theirs");
+        }
+        if bk < b.len() {
+            out.push(b[bk]);
+        }
+        (bi, yi, ti) = (bk + 1, yk + 1, tk + 1);
+    }
+    let text = out.join("\n");
+    if clean {
+        Merged::Clean(text)
+    } else {
+        Merged::Conflicted(text)
+    }
+}
+
+// The text in a value, if it's a live text envelope.
+fn text_of(data: &[u8]) -> Option<String> {
+    let env = Envelope::decode(data).ok()?;
+    if env.codec != Codec::Text || !env.is_live(crate::audit::now_ms() as u64) {
+        return None;
+    }
+    String::from_utf8(env.body).ok()
+}
+
+// A text write that a newer value beat to the subkey.
+pub struct Conflict {
+    subkey: ValueSubkey,
+    base: String,
+    // what we tried to write, kept for its envelope fields (ttl, sender_seq)
+    yours: Envelope,
+    theirs: String,
+    opts: Option<SetDHTValueOptions>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Choice {
+    Merge,
+    Yours,
+    Theirs,
+    Edit(String),
+}
+
+impl Choice {
+    pub fn parse(answer: &str) -> Option<Choice> {
+        match answer.trim() {
+            "m" => Some(Choice::Merge),
+            "y" => Some(Choice::Yours),
+            "t" => Some(Choice::Theirs),
+            other => other.strip_prefix("e ").map(|text| Choice::Edit(text.trim().to_string())),
+        }
+    }
+}
+
+// Writes `value` to `subkey`. Ok(Some) if it was text and a newer text
+// value got there first; any other value loses to a newer one quietly.
+pub async fn write(
+    rc: &Dht,
+    record: &RecordKey,
+    subkey: ValueSubkey,
+    value: Envelope,
+    opts: Option<SetDHTValueOptions>,
+) -> VeilidAPIResult<Option<Conflict>> {
+    let base = if value.codec == Codec::Text {
+        rc.get_dht_value(record.clone(), subkey, false).await?.and_then(|v| text_of(v.data()))
+    } else {
+        None
+    };
+    let Some(newer) = rc.set_dht_value(record.clone(), subkey, value.encode(), opts.clone()).await? else {
+        return Ok(None);
+    };
+    if value.codec != Codec::Text {
+        return Ok(None);
+    }
+    Ok(text_of(newer.data()).map(|theirs| Conflict {
+        subkey,
+        base: base.unwrap_or_default(),
+        yours: value,
+        theirs,
+        opts,
+    }))
+}
+
+fn block(label: &str, text: &str) -> String {
+    if text.contains('\n') {
+        let lines: Vec<String> = text.lines().map(|l| format!("    {l}")).collect();
+        format!("  {label}:\n{}", lines.join("\n"))
+    } else {
+        format!("  {label:<7} \"{text}\"")
+    }
+}
+
+impl Conflict {
+    fn yours_text(&self) -> String {
+        String::from_utf8_lossy(&self.yours.body).into_owned()
+    }
+
+    pub fn render(&self) -> String {
+        let merged = match merge3(&self.base, &self.yours_text(), &self.theirs) {
+            Merged::Clean(text) => block(&t!("conflict-merged"), &text),
+            Merged::Conflicted(text) => block(&t!("conflict-merged-with-conflicts"), &text),
+        };
+        [
+            t!("conflict-lost", subkey = self.subkey),
+            block(&t!("conflict-base"), &self.base),
+            block(&t!("conflict-yours"), &self.yours_text()),
+            block(&t!("conflict-theirs"), &self.theirs),
+            merged,
+            t!("conflict-choices"),
+        ]
+        .join("\n")
+    }
+
+    // Carries out the answer. Gives back what to print, and the conflict to
+    // ask about next if the write lost again (or `m` couldn't be done).
+    pub async fn resolve(self, rc: &Dht, record: &RecordKey, choice: Choice) -> (String, Option<Conflict>) {
+        let text = match choice {
+            Choice::Theirs => return (t!("conflict-kept-theirs", subkey = self.subkey), None),
+            Choice::Yours => self.yours_text(),
+            Choice::Edit(text) => text,
+            Choice::Merge => match merge3(&self.base, &self.yours_text(), &self.theirs) {
+                Merged::Clean(text) => text,
+                Merged::Conflicted(_) => {
+                    return (t!("conflict-merge-conflicted"), Some(self));
+                }
+            },
+        };
+        let mut value = self.yours.clone();
+        value.body = text.into_bytes();
+        value.written_ms = Some(crate::audit::now_ms() as u64);
+        match write(rc, record, self.subkey, value, self.opts.clone()).await {
+            Ok(None) => (t!("wrote-subkey", subkey = self.subkey), None),
+            Ok(Some(again)) => (again.render(), Some(again)),
+            Err(e) => (t!("subkey-write-failed", subkey = self.subkey, error = e.to_string()), None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_to_different_lines_merge_and_the_same_line_conflicts() {
+        let base = "title\nfirst\nsecond\nthird";
+        assert_eq!(
+            merge3(base, "title\nFIRST\nsecond\nthird", "title\nfirst\nsecond\nthird\nfourth"),
+            Merged::Clean("title\nFIRST\nsecond\nthird\nfourth".to_string())
+        );
+        assert_eq!(merge3("a", "a", "b"), Merged::Clean("b".to_string()));
+        assert_eq!(merge3("", "new", "new"), Merged::Clean("new".to_string()));
+        assert_eq!(
+            merge3(base, "title\nmine\nsecond\nthird", "title\nours\nsecond\nthird"),
+            Merged::Conflicted(
+                "title\n<<<<<<< yours\nmine\n||||||| base\nfirst\n=======\nours\n>>>>>>> theirs\nsecond\nthird".to_string()
+            )
+        );
+
+        assert_eq!(Choice::parse(" m "), Some(Choice::Merge));
+        assert_eq!(Choice::parse("e both of us"), Some(Choice::Edit("both of us".to_string())));
+        assert_eq!(Choice::parse("hello"), None);
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::i18n::t;
+use crate::profile::ProfileCard;
+use crate::shortcode::{self, ShortcodeBook};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Contacts: the peers this node knows, by name, kept in the node's Veilid
+//	table store (so each role has its own, encrypted along with the rest of
+//	its storage).
+//
+//	A contact can have a public key, a personal record, and the last private
+//	route we were given for them. Cards swapped over app_message (see
+//	profile.rs) fill these in; `contact add` does it by hand.
+//
+//	  contact add alice --key VLD0:... --record harp-otter-coal-lime
+//	  contact list
+//	  contact remove alice
+//
+//	contacts.json from older builds is left where it is and copied in once
+//	per role: the default and alt nodes share the data folder, and each has
+//	its own table store to fill. The table store notes it's been done.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const TABLE: &str = "contacts";
+const COL_CONTACTS: u32 = 0;
+// what's been brought in from older builds
+const COL_IMPORTED: u32 = 1;
+const LEGACY_FILE: &str = "contacts.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Contact {
+    pub name: String,
+    pub public_key: Option<String>,
+    // their personal record
+    pub record: Option<String>,
+    // the last private route blob they gave us (base64)
+    pub route: Option<String>,
+    // the last card they sent, if any
+    pub card: Option<ProfileCard>,
+    pub updated_ms: u64,
+}
+
+// A VLD0 public key of the right length: parsing alone takes any base64.
+fn public_key(text: &str) -> Result<PublicKey, String> {
+    let key = text.parse::<PublicKey>().map_err(|e| e.to_string())?;
+    let len = key.ref_value().len();
+    if key.kind() != CRYPTO_KIND_VLD0 {
+        return Err(format!("{} keys aren't supported, only VLD0", key.kind()));
+    }
+    if len != VLD0_PUBLIC_KEY_LENGTH {
+        return Err(format!("a public key is {VLD0_PUBLIC_KEY_LENGTH} bytes, that's {len}"));
+    }
+    Ok(key)
+}
+
+pub struct Contacts {
+    // None in tests: kept in memory only
+    db: Option<TableDB>,
+    by_name: BTreeMap<String, Contact>,
+}
+
+impl Contacts {
+    pub async fn open(api: &VeilidAPI, data_dir: &Path) -> VeilidAPIResult<Contacts> {
+        let db = api.table_store()?.open(TABLE, 2).await?;
+        let mut by_name = BTreeMap::new();
+        for key in db.get_keys(COL_CONTACTS).await? {
+            if let Some(contact) = db.load_json::<Contact>(COL_CONTACTS, &key).await? {
+                by_name.insert(contact.name.clone(), contact);
+            }
+        }
+        let mut contacts = Contacts { db: Some(db), by_name };
+        contacts.import_json(data_dir).await;
+        Ok(contacts)
+    }
+
+    #[cfg(test)]
+    fn in_memory() -> Contacts {
+        Contacts {
+            db: None,
+            by_name: BTreeMap::new(),
+        }
+    }
+
+    async fn import_json(&mut self, data_dir: &Path) {
+        let Some(db) = self.db.clone() else {
+            return;
+        };
+        if matches!(db.load(COL_IMPORTED, LEGACY_FILE.as_bytes()).await, Ok(Some(_))) {
+            return;
+        }
+        let Ok(text) = fs::read_to_string(data_dir.join(LEGACY_FILE)) else {
+            return;
+        };
+        let cards: BTreeMap<String, ProfileCard> = serde_json::from_str(&text).unwrap_or_default();
+        for card in cards.values() {
+            let _ = self.remember_card(card).await;
+        }
+        let _ = db.store(COL_IMPORTED, LEGACY_FILE.as_bytes(), &[1]).await;
+    }
+
+    async fn save(&mut self, contact: Contact) -> VeilidAPIResult<()> {
+        if let Some(db) = &self.db {
+            db.store_json(COL_CONTACTS, contact.name.as_bytes(), &contact).await?;
+        }
+        self.by_name.insert(contact.name.clone(), contact);
+        Ok(())
+    }
+
+    pub async fn remove(&mut self, name: &str) -> VeilidAPIResult<bool> {
+        if let Some(db) = &self.db {
+            db.delete(COL_CONTACTS, name.as_bytes()).await?;
+        }
+        Ok(self.by_name.remove(name).is_some())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Contact> {
+        self.by_name.get(name)
+    }
+
+    pub fn by_key(&self, public_key: &str) -> Option<&Contact> {
+        self.by_name
+            .values()
+            .find(|c| c.public_key.as_deref() == Some(public_key))
+    }
+
+    // Keep a (verified) card: it updates whoever has its key, or becomes a new
+    // contact named after its nickname. Returns true if we hadn't met this key before.
+    pub async fn remember_card(&mut self, card: &ProfileCard) -> VeilidAPIResult<bool> {
+        let existing = self.by_key(&card.public_key).cloned();
+        if let Some(old) = existing.as_ref().and_then(|c| c.card.as_ref()) {
+            if old.issued_ms >= card.issued_ms {
+                return Ok(false);
+            }
+        }
+        let new = existing.is_none();
+        let mut contact = existing.unwrap_or_else(|| Contact {
+            name: self.free_name(&card.nickname),
+            public_key: Some(card.public_key.clone()),
+            ..Contact::default()
+        });
+        if card.route.is_some() {
+            contact.route = card.route.clone();
+        }
+        contact.card = Some(card.clone());
+        contact.updated_ms = crate::audit::now_ms() as u64;
+        self.save(contact).await?;
+        Ok(new)
+    }
+
+    // `nickname`, or `nickname-2`, ... if that's taken.
+    fn free_name(&self, nickname: &str) -> String {
+        let base = if nickname.trim().is_empty() { "contact" } else { nickname.trim() };
+        let mut name = base.to_string();
+        let mut n = 2;
+        while self.by_name.contains_key(&name) {
+            name = format!("{base}-{n}");
+            n += 1;
+        }
+        name
+    }
+
+    // `contact ...` at the prompt (everything after "contact").
+    pub async fn command(&mut self, args: &str, book: &ShortcodeBook) -> String {
+        let mut words = args.split_whitespace();
+        match words.next() {
+            None | Some("list") => self.report(),
+            Some("remove") => match words.next() {
+                Some(name) => match self.remove(name).await {
+                    Ok(true) => t!("contact-removed", name = name),
+                    Ok(false) => t!("no-such-contact", name = name),
+                    Err(e) => t!("contact-remove-failed", name = name, error = e.to_string()),
+                },
+                None => t!("contact-remove-usage"),
+            },
+            Some("add") => {
+                let Some(name) = words.next() else {
+                    return t!("contact-add-usage");
+                };
+                let mut contact = self.get(name).cloned().unwrap_or(Contact {
+                    name: name.to_string(),
+                    ..Contact::default()
+                });
+                while let Some(flag) = words.next() {
+                    let Some(value) = words.next() else {
+                        return t!("contact-needs-value", flag = flag);
+                    };
+                    match flag {
+                        "--key" => match public_key(value) {
+                            Ok(key) => contact.public_key = Some(key.to_string()),
+                            Err(e) => return t!("not-a-public-key", error = e),
+                        },
+                        "--record" => match book.resolve(value) {
+                            Ok(key) => contact.record = Some(key.to_string()),
+                            Err(e) => return e,
+                        },
+                        "--route" => contact.route = Some(value.to_string()),
+                        other => return t!("contact-unknown-option", option = other),
+                    }
+                }
+                contact.updated_ms = crate::audit::now_ms() as u64;
+                match self.save(contact).await {
+                    Ok(()) => t!("contact-saved", name = name),
+                    Err(e) => t!("contact-save-failed", name = name, error = e.to_string()),
+                }
+            }
+            Some(other) => t!("contact-unknown-command", command = other),
+        }
+    }
+
+    // One line per contact, for `contact list`.
+    pub fn report(&self) -> String {
+        if self.by_name.is_empty() {
+            return t!("no-contacts");
+        }
+        self.by_name
+            .values()
+            .map(|c| {
+                let mut line = format!("  {:<16}", c.name);
+                if let Some(key) = &c.public_key {
+                    line.push_str(&format!(" {key}"));
+                }
+                if let Some(record) = c.record.as_ref().and_then(|r| r.parse::<RecordKey>().ok()) {
+                    line.push_str(&format!("  {}", t!("contact-record", code = shortcode::shortcode(&record))));
+                }
+                if let Some(card) = &c.card {
+                    line.push_str(&format!("  [{}]", card.capabilities.join(", ")));
+                }
+                if c.route.is_some() {
+                    line.push_str(&format!("  {}", t!("contact-reachable")));
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(kp: &KeyPair, name: &str) -> ProfileCard {
+        ProfileCard::new(kp, name, &["watch"], Some(b"route")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn cards_update_the_contact_with_their_key() {
+        let mut contacts = Contacts::in_memory();
+        let alice = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let other = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+
+        assert!(contacts.remember_card(&card(&alice, "alice")).await.unwrap());
+        // a different key with the same nickname doesn't take over alice's entry
+        assert!(contacts.remember_card(&card(&other, "alice")).await.unwrap());
+        assert_eq!(contacts.get("alice-2").unwrap().public_key, Some(other.key().to_string()));
+
+        // (remember_card doesn't check signatures, profile::receive does first)
+        let mut newer = card(&alice, "alice renamed");
+        newer.issued_ms += 1;
+        assert!(!contacts.remember_card(&newer).await.unwrap());
+        let updated = contacts.by_key(&alice.key().to_string()).unwrap();
+        assert_eq!(updated.name, "alice");
+        assert_eq!(updated.card.as_ref().unwrap().nickname, "alice renamed");
+
+        // an older card changes nothing
+        let mut older = card(&alice, "alice again");
+        older.issued_ms = 0;
+        contacts.remember_card(&older).await.unwrap();
+        assert_eq!(contacts.get("alice").unwrap().card.as_ref().unwrap().nickname, "alice renamed");
+    }
+
+    #[tokio::test]
+    async fn add_list_and_remove_by_hand() {
+        let mut contacts = Contacts::in_memory();
+        let book = ShortcodeBook::load(&std::env::temp_dir().join(format!("contacts-test-{}", std::process::id()))).unwrap();
+        let key = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap().key();
+
+        assert_eq!(contacts.command(&format!("add bob --key {key}"), &book).await, "Saved bob");
+        assert!(contacts.command("list", &book).await.contains(&key.to_string()));
+        assert!(contacts.command("add bob --key nope", &book).await.starts_with("Not a public key"));
+        assert!(contacts.command("add bob --colour red", &book).await.starts_with("Unknown option"));
+
+        assert_eq!(contacts.command("remove bob", &book).await, "Removed bob");
+        assert_eq!(contacts.command("remove bob", &book).await, "No contact called bob");
+        assert_eq!(contacts.report(), "No contacts yet");
+    }
+}
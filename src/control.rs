@@ -0,0 +1,314 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use veilid_core::*;
+
+use crate::config::AppConfig;
+use crate::dht::Dht;
+use crate::exit::Kind;
+use crate::input::Command;
+use crate::payloads::Registry;
+use crate::soak::SoakRole;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The daemon's control socket: `attach`, `detach`, `status`, `get` and
+//	`set` from the command line, answered by a daemon that's already
+//	running, so a script doing a lot of small things doesn't pay for
+//	attaching a new node each time.
+//
+//	  veilid_test_node daemon --role default &
+//	  veilid_test_node status --role default
+//	  veilid_test_node set 2 hello           # written to the daemon's record
+//	  veilid_test_node get 2                 # and read back from it
+//	  veilid_test_node detach                # the daemon stops its rounds
+//	  veilid_test_node attach                # and starts them again
+//
+//	A request line is read like a line at the prompts (see input.rs), so
+//	a prompt command that isn't one of these gets told so. `set` goes
+//	through the payload types and codecs like a write at the prompt does,
+//	and `get` shows the value the way `read` would; only the default
+//	role's daemon has a key to `set` with.
+//
+//	The daemon listens on 127.0.0.1, on whatever port it gets, and leaves
+//	the address and a random token in control-<role>.json in the data
+//	folder (readable only by us, where that can be said). A request is one
+//	line, "<token> <command>"; the answer is "ok" or "error: ..." on the
+//	first line and the details after it, then the daemon hangs up.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const USAGE: &str = "attach, detach, status, get <subkey> or set <subkey> <text>";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Request {
+    Attach,
+    Detach,
+    Status,
+    // get <subkey>
+    Get(ValueSubkey),
+    // set <subkey> <text>
+    Set(ValueSubkey, String),
+}
+
+impl Request {
+    // The line sent after the token.
+    pub fn line(&self) -> String {
+        match self {
+            Request::Attach => "attach".to_string(),
+            Request::Detach => "detach".to_string(),
+            Request::Status => "status".to_string(),
+            Request::Get(subkey) => format!("get {subkey}"),
+            Request::Set(subkey, text) => format!("set {subkey} {text}"),
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Request, String> {
+        let line = match Command::parse(line) {
+            Some(Command::Other(line)) => line,
+            Some(_) => return Err(format!("'{}' only works at the prompts; the daemon takes {USAGE}", line.trim())),
+            None => return Err(format!("no command (the daemon takes {USAGE})")),
+        };
+        let subkey = |text: &str| text.parse::<ValueSubkey>().map_err(|_| format!("'{text}' isn't a subkey number"));
+        let (word, rest) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+        match (word, rest.trim()) {
+            ("attach", "") => Ok(Request::Attach),
+            ("detach", "") => Ok(Request::Detach),
+            ("status", "") => Ok(Request::Status),
+            ("get", rest) if !rest.is_empty() => Ok(Request::Get(subkey(rest)?)),
+            ("set", rest) => match rest.split_once(' ') {
+                Some((n, text)) => Ok(Request::Set(subkey(n)?, text.trim().to_string())),
+                None => Err("set needs a subkey and the text to write".to_string()),
+            },
+            _ => Err(format!("no command '{line}' ({USAGE})")),
+        }
+    }
+}
+
+// What `get` and `set` work on: the daemon's record, once it has one.
+pub struct Target {
+    pub rc: Dht,
+    pub record_key: RecordKey,
+    // None for the alt role, which only reads
+    pub writer: Option<SetDHTValueOptions>,
+    pub registry: Registry,
+}
+
+// What the daemon leaves in the data folder for the commands to find it by.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct Endpoint {
+    addr: String,
+    token: String,
+    pid: u32,
+}
+
+fn endpoint_path(data_dir: &Path, role: SoakRole) -> PathBuf {
+    data_dir.join(format!("control-{}.json", role.name()))
+}
+
+// The request in a line, if it came with the right token.
+fn parse_request(line: &str, token: &str) -> Result<Request, String> {
+    let (given, command) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+    if given != token {
+        return Err("wrong token".to_string());
+    }
+    Request::parse(command)
+}
+
+// The running control socket. Dropping it takes control-<role>.json away,
+// so the commands don't go looking for a daemon that's gone.
+pub struct Control {
+    path: PathBuf,
+}
+
+impl Drop for Control {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Start answering requests for `veilid`. `detached` is set while a `detach`
+// is in force, for the daemon to leave the DHT alone until `attach`;
+// `target` is filled in once the daemon has its record.
+pub async fn serve(
+    data_dir: &Path,
+    role: SoakRole,
+    veilid: VeilidAPI,
+    detached: Arc<AtomicBool>,
+    target: Arc<OnceLock<Target>>,
+) -> io::Result<Control> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let endpoint = Endpoint {
+        addr: listener.local_addr()?.to_string(),
+        token: format!("{:032x}", rand::random::<u128>()),
+        pid: std::process::id(),
+    };
+    let path = endpoint_path(data_dir, role);
+    fs::write(&path, serde_json::to_string_pretty(&endpoint)?)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    let token = Arc::new(endpoint.token);
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let (veilid, detached, token, target) = (veilid.clone(), detached.clone(), token.clone(), target.clone());
+            tokio::spawn(async move {
+                let _ = answer(stream, &token, &veilid, &detached, &target).await;
+            });
+        }
+    });
+    Ok(Control { path })
+}
+
+async fn answer(
+    stream: TcpStream,
+    token: &str,
+    veilid: &VeilidAPI,
+    detached: &AtomicBool,
+    target: &OnceLock<Target>,
+) -> io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut line = String::new();
+    tokio::time::timeout(Duration::from_secs(5), BufReader::new(read).read_line(&mut line))
+        .await
+        .map_err(|_| io::ErrorKind::TimedOut)??;
+    let reply = match parse_request(&line, token) {
+        Ok(request) => run(request, veilid, detached, target).await,
+        Err(e) => Err(e),
+    };
+    let text = match reply {
+        Ok(details) => format!("ok\n{details}"),
+        Err(e) => format!("error: {e}\n"),
+    };
+    write.write_all(text.as_bytes()).await?;
+    write.shutdown().await
+}
+
+// The record, if it's open and the daemon isn't detached.
+fn record<'a>(target: &'a OnceLock<Target>, detached: &AtomicBool) -> Result<&'a Target, String> {
+    if detached.load(Ordering::Relaxed) {
+        return Err("the daemon is detached; `attach` it first".to_string());
+    }
+    target.get().ok_or_else(|| "the daemon hasn't opened its record yet, try again shortly".to_string())
+}
+
+async fn run(request: Request, veilid: &VeilidAPI, detached: &AtomicBool, target: &OnceLock<Target>) -> Result<String, String> {
+    match request {
+        Request::Attach => {
+            veilid.attach().await.map_err(|e| e.to_string())?;
+            detached.store(false, Ordering::Relaxed);
+            Ok("attaching; the daemon's rounds start again once it's attached\n".to_string())
+        }
+        Request::Detach => {
+            detached.store(true, Ordering::Relaxed);
+            veilid.detach().await.map_err(|e| e.to_string())?;
+            Ok("detaching; the daemon's rounds are paused until `attach`\n".to_string())
+        }
+        Request::Status => {
+            let state = veilid.get_state().await.map_err(|e| e.to_string())?;
+            let (att, net) = (&state.attachment, &state.network);
+            Ok(format!(
+                "attachment: {}{}\n\
+                 public internet ready: {}\n\
+                 peers: {} live, {} reliable\n\
+                 bandwidth: {} B/s down, {} B/s up\n\
+                 up for: {}s\n",
+                att.state,
+                if detached.load(Ordering::Relaxed) { " (detached on request)" } else { "" },
+                att.public_internet_ready,
+                att.live_peer_count.as_u64(),
+                att.reliable_peer_count.as_u64(),
+                net.bps_down.as_u64(),
+                net.bps_up.as_u64(),
+                att.uptime.as_u64() / 1_000_000,
+            ))
+        }
+        Request::Get(subkey) => {
+            let target = record(target, detached)?;
+            let value = target
+                .rc
+                .get_dht_value(target.record_key.clone(), subkey, true)
+                .await
+                .map_err(|e| e.to_string())?;
+            match value {
+                Some(value) => Ok(format!("{}\n", target.registry.display(subkey, value.data()))),
+                None => Ok(format!("subkey {subkey} is empty\n")),
+            }
+        }
+        Request::Set(subkey, text) => {
+            let target = record(target, detached)?;
+            let writer = target.writer.clone().ok_or("the alt daemon only reads; `set` goes to the default one")?;
+            let value = target.registry.encode(subkey, &text)?;
+            match target.rc.set_dht_value(target.record_key.clone(), subkey, value.encode(), Some(writer)).await {
+                Ok(None) => Ok(format!("wrote subkey {subkey}\n")),
+                Ok(Some(_)) => Err(format!("subkey {subkey} already has a newer value; `get {subkey}` to see it")),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+    }
+}
+
+// `attach`, `detach`, `status`, `get` or `set`: ask the role's daemon and print what it says.
+pub async fn send(request: Request, role: SoakRole, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let path = endpoint_path(&config.data_dir()?, role);
+    let text = fs::read_to_string(&path).map_err(|_| {
+        Kind::Network.fail(format!(
+            "no {} daemon is running here (start one with `daemon --role {}`)",
+            role.name(),
+            role.name()
+        ))
+    })?;
+    let endpoint: Endpoint =
+        serde_json::from_str(&text).map_err(|e| format!("{} is damaged: {e}", path.to_string_lossy()))?;
+    let mut stream = TcpStream::connect(&endpoint.addr).await.map_err(|e| {
+        Kind::Network.fail(format!(
+            "the {} daemon (pid {}) isn't answering on {}: {e}",
+            role.name(),
+            endpoint.pid,
+            endpoint.addr
+        ))
+    })?;
+    stream.write_all(format!("{} {}\n", endpoint.token, request.line()).as_bytes()).await?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).await?;
+    match reply.split_once('\n') {
+        Some(("ok", details)) => {
+            print!("{details}");
+            Ok(())
+        }
+        _ => Err(reply.trim().trim_start_matches("error: ").to_string().into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_need_the_token() {
+        assert_eq!(parse_request("abc status\n", "abc"), Ok(Request::Status));
+        assert_eq!(parse_request("abc detach", "abc"), Ok(Request::Detach));
+        assert_eq!(parse_request("abd status\n", "abc"), Err("wrong token".to_string()));
+        assert_eq!(parse_request("status\n", "abc"), Err("wrong token".to_string()));
+        assert!(parse_request("abc reboot\n", "abc").unwrap_err().contains(USAGE));
+        // read the way the prompts read a line
+        assert_eq!(parse_request("abc get 3\n", "abc"), Ok(Request::Get(3)));
+        assert_eq!(parse_request("abc set 2  hello world\n", "abc"), Ok(Request::Set(2, "hello world".to_string())));
+        assert!(parse_request("abc help\n", "abc").unwrap_err().contains("only works at the prompts"));
+        assert!(parse_request("abc get x\n", "abc").unwrap_err().contains("isn't a subkey"));
+        assert_eq!(Request::parse(&Request::Set(4, "a b".to_string()).line()), Ok(Request::Set(4, "a b".to_string())));
+    }
+}
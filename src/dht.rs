@@ -0,0 +1,788 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use veilid_core::*;
+
+use crate::audit::AuditLog;
+use crate::backend::DhtBackend;
+use crate::chaos::ChaosConfig;
+use crate::health::Health;
+use crate::heatmap::{Access, Heatmap};
+use crate::queue::{OpQueue, Priority};
+use crate::quota::Quota;
+use crate::stats::{Bandwidth, Feature, Latency};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	A thin wrapper around the Veilid RoutingContext (or any other DhtBackend,
+//	such as the in-memory one the tests use).
+//
+//	Every DHT call the example makes goes through here, so there is one place
+//	to time the call and write it to the audit log.
+//
+//	In dry-run mode the calls that change the network (create/set)
+//	are checked and printed, but never sent.
+//
+//	With --chaos, calls may be delayed or failed on purpose before they run,
+//	and with --inject-latency their results are held back for a while. The
+//	calls a node makes while starting up go through for_setup(), which
+//	leaves the failures out: a node that never reaches its prompt tests
+//	nothing.
+//
+//	Value bytes written and read are counted against the feature the handle
+//	was made for (for_feature), for `stats bandwidth`. The feature also
+//	decides where the call stands in the node's queue, if it has one
+//	(see queue.rs).
+//
+//	In early DHT mode (--early, see ready.rs) the node starts before it's
+//	fully attached, so opens, reads, writes and inspections that come back
+//	TryAgain are made again, backing off, until retry_for runs out.
+//
+//	The size and writer of every value read or written goes to the quota
+//	tracker, if there is one, which warns as a record fills up (quota.rs).
+//	Reads and writes that worked are counted per subkey for the heatmap
+//	(heatmap.rs).
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone)]
+pub struct Dht {
+    rc: Arc<dyn DhtBackend>,
+    audit: Option<Arc<AuditLog>>,
+    dry_run: bool,
+    chaos: Option<ChaosConfig>,
+    // schemas of the records we've created/opened, so dry-run can check subkeys
+    schemas: Arc<Mutex<HashMap<RecordKey, DHTSchema>>>,
+    // told about every call that worked, for --health-addr
+    health: Option<Arc<Health>>,
+    bandwidth: Option<Arc<Bandwidth>>,
+    // moving averages of how long each kind of call takes, for the prompt
+    latency: Option<Arc<Latency>>,
+    // shared by every handle on the node; calls wait here for a slot
+    queue: Option<Arc<OpQueue>>,
+    quota: Option<Arc<Quota>>,
+    heatmap: Option<Arc<Heatmap>>,
+    // early DHT mode: how long to keep retrying calls that come back TryAgain
+    retry_for: Option<Duration>,
+    // what the bytes through this handle count towards
+    feature: Feature,
+}
+
+impl Dht {
+    pub fn new(
+        rc: RoutingContext,
+        audit: Option<Arc<AuditLog>>,
+        dry_run: bool,
+        chaos: Option<ChaosConfig>,
+    ) -> Dht {
+        Dht::with_backend(Arc::new(rc), audit, dry_run, chaos)
+    }
+
+    pub fn with_backend(
+        rc: Arc<dyn DhtBackend>,
+        audit: Option<Arc<AuditLog>>,
+        dry_run: bool,
+        chaos: Option<ChaosConfig>,
+    ) -> Dht {
+        Dht {
+            rc,
+            audit,
+            dry_run,
+            chaos,
+            schemas: Arc::new(Mutex::new(HashMap::new())),
+            health: None,
+            bandwidth: None,
+            latency: None,
+            queue: None,
+            quota: None,
+            heatmap: None,
+            retry_for: None,
+            feature: Feature::Values,
+        }
+    }
+
+    pub fn with_health(mut self, health: Arc<Health>) -> Dht {
+        self.health = Some(health);
+        self
+    }
+
+    pub fn with_bandwidth(mut self, bandwidth: Arc<Bandwidth>) -> Dht {
+        self.bandwidth = Some(bandwidth);
+        self
+    }
+
+    pub fn with_latency(mut self, latency: Arc<Latency>) -> Dht {
+        self.latency = Some(latency);
+        self
+    }
+
+    pub fn with_queue(mut self, queue: Arc<OpQueue>) -> Dht {
+        self.queue = Some(queue);
+        self
+    }
+
+    pub fn with_quota(mut self, quota: Arc<Quota>) -> Dht {
+        self.quota = Some(quota);
+        self
+    }
+
+    pub fn with_heatmap(mut self, heatmap: Arc<Heatmap>) -> Dht {
+        self.heatmap = Some(heatmap);
+        self
+    }
+
+    pub fn with_try_again_retry(mut self, retry_for: Option<Duration>) -> Dht {
+        self.retry_for = retry_for;
+        self
+    }
+
+    // The same DHT, with what goes through it counted towards `feature`.
+    pub fn for_feature(&self, feature: Feature) -> Dht {
+        let mut dht = self.clone();
+        dht.feature = feature;
+        dht
+    }
+
+    // The same DHT without --chaos's delays and failures (--inject-latency
+    // still applies), for creating and opening the record at startup.
+    pub fn for_setup(&self) -> Dht {
+        let mut dht = self.clone();
+        dht.chaos = self.chaos.as_ref().and_then(|c| c.injected).map(ChaosConfig::latency_only);
+        dht
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    fn remember_schema(&self, desc: &DHTRecordDescriptor) {
+        self.schemas
+            .lock()
+            .unwrap()
+            .insert(desc.key(), desc.schema());
+    }
+
+    // Makes the call again while it comes back TryAgain, if retry_for is set.
+    // Each try is audited on its own.
+    async fn retrying<T, F, Fut>(&self, op: &str, call: F) -> VeilidAPIResult<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = VeilidAPIResult<T>>,
+    {
+        let Some(retry_for) = self.retry_for else {
+            return call().await;
+        };
+        let give_up = Instant::now() + retry_for;
+        let mut wait = RETRY_FIRST;
+        loop {
+            match call().await {
+                Err(VeilidAPIError::TryAgain { message }) if Instant::now() + wait < give_up => {
+                    println!("[early] {op}: {message}; trying again in {:.1}s", wait.as_secs_f32());
+                    tokio::time::sleep(wait).await;
+                    wait = (wait * 2).min(RETRY_MAX);
+                }
+                res => return res,
+            }
+        }
+    }
+
+    // Runs one DHT call, measures how long it took, and records it.
+    async fn audited<T, F>(
+        &self,
+        op: &str,
+        params: serde_json::Value,
+        describe: impl Fn(&T) -> String,
+        fut: F,
+    ) -> VeilidAPIResult<T>
+    where
+        F: Future<Output = VeilidAPIResult<T>>,
+    {
+        // time spent queued isn't the network's, so it's left out of the latency
+        let _slot = match &self.queue {
+            Some(queue) => Some(queue.acquire(Priority::of(self.feature)).await),
+            None => None,
+        };
+        let start = Instant::now();
+        let res = match &self.chaos {
+            Some(chaos) => {
+                let res = match chaos.before_call(op).await {
+                    Ok(()) => fut.await,
+                    Err(e) => Err(e),
+                };
+                chaos.after_call().await;
+                res
+            }
+            None => fut.await,
+        };
+        let latency_ms = start.elapsed().as_millis();
+
+        if let (Ok(_), Some(health)) = (&res, &self.health) {
+            health.dht_op_ok();
+        }
+        if let Some(latency) = &self.latency {
+            latency.record(op, latency_ms, res.is_ok());
+        }
+
+        if let Some(audit) = &self.audit {
+            let (result, error) = match &res {
+                Ok(v) => (Some(describe(v)), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            // A broken audit log should never take the node down with it.
+            if let Err(e) = audit.append(op, params, result, error, latency_ms) {
+                eprintln!("audit log write failed: {e}");
+            }
+        }
+
+        res
+    }
+
+    pub async fn create_dht_record(
+        &self,
+        kind: CryptoKind,
+        schema: DHTSchema,
+        owner: Option<KeyPair>,
+    ) -> VeilidAPIResult<DHTRecordDescriptor> {
+        if self.dry_run {
+            return Err(VeilidAPIError::generic(
+                "create_dht_record called in dry-run mode, use plan_create_dht_record",
+            ));
+        }
+        let params = json!({
+            "kind": kind.to_string(),
+            "schema": format!("{schema:?}"),
+            "owner": owner.as_ref().map(|kp| kp.key().to_string()),
+        });
+        let desc = self
+            .audited(
+                "create",
+                params,
+                |d: &DHTRecordDescriptor| d.key().to_string(),
+                self.rc.create_dht_record(kind, schema, owner),
+            )
+            .await?;
+        self.remember_schema(&desc);
+        if let Some(quota) = &self.quota {
+            quota.opened(&desc, Some(desc.owner()));
+        }
+        Ok(desc)
+    }
+
+    // Dry-run stand-in for create_dht_record: validates the schema and works out
+    // which record key the owner would get, without creating anything.
+    pub async fn plan_create_dht_record(
+        &self,
+        schema: DHTSchema,
+        owner: KeyPair,
+    ) -> VeilidAPIResult<RecordKey> {
+        let params = json!({
+            "kind": owner.kind().to_string(),
+            "schema": format!("{schema:?}"),
+            "owner": owner.key().to_string(),
+        });
+        let record_key = self
+            .audited(
+                "create",
+                params,
+                |k: &RecordKey| format!("dry-run, not sent (would be {k})"),
+                self.rc.get_dht_record_key(schema.clone(), owner.key()),
+            )
+            .await?;
+
+        println!(
+            "[dry-run] would call create_dht_record(kind: {}, schema: {:?}, owner: {})",
+            owner.kind(),
+            schema,
+            owner.key()
+        );
+        println!("[dry-run]   -> record key would be {record_key}");
+
+        self.schemas.lock().unwrap().insert(record_key.clone(), schema);
+        Ok(record_key)
+    }
+
+    // The key a record with this schema and owner gets, without creating it.
+    pub async fn get_dht_record_key(&self, schema: DHTSchema, owner: PublicKey) -> VeilidAPIResult<RecordKey> {
+        let params = json!({
+            "schema": format!("{schema:?}"),
+            "owner": owner.to_string(),
+        });
+        self.audited(
+            "record_key",
+            params,
+            |k: &RecordKey| k.to_string(),
+            self.rc.get_dht_record_key(schema, owner),
+        )
+        .await
+    }
+
+    pub async fn open_dht_record(
+        &self,
+        record_key: RecordKey,
+        writer: Option<KeyPair>,
+    ) -> VeilidAPIResult<DHTRecordDescriptor> {
+        self.retrying("open", || self.open_once(record_key.clone(), writer.clone())).await
+    }
+
+    async fn open_once(&self, record_key: RecordKey, writer: Option<KeyPair>) -> VeilidAPIResult<DHTRecordDescriptor> {
+        let params = json!({
+            "record": record_key.to_string(),
+            "writer": writer.as_ref().map(|kp| kp.key().to_string()),
+        });
+        let writer_key = writer.as_ref().map(|kp| kp.key());
+        let desc = self
+            .audited(
+                "open",
+                params,
+                |d: &DHTRecordDescriptor| d.key().to_string(),
+                self.rc.open_dht_record(record_key, writer),
+            )
+            .await?;
+        self.remember_schema(&desc);
+        if let Some(quota) = &self.quota {
+            quota.opened(&desc, writer_key);
+        }
+        Ok(desc)
+    }
+
+    // Opens a record, and if `schema grow` has moved it, follows the forwarding
+    // pointer(s) in subkey 0 to where it lives now. The returned descriptor is
+    // for the record actually opened, so use its key() from here on.
+    pub async fn open_following(
+        &self,
+        record_key: RecordKey,
+        writer: Option<KeyPair>,
+    ) -> VeilidAPIResult<DHTRecordDescriptor> {
+        let mut key = record_key;
+        for _ in 0..MAX_FORWARD_HOPS {
+            let desc = self.open_dht_record(key.clone(), writer.clone()).await?;
+            let moved_to = self
+                .get_dht_value(key.clone(), 0, true)
+                .await?
+                .and_then(|v| parse_forward_pointer(v.data()));
+            match moved_to {
+                Some(next) => {
+                    println!("Record {key} has moved to {next}, following it");
+                    let _ = self.close_dht_record(key).await;
+                    key = next;
+                }
+                None => return Ok(desc),
+            }
+        }
+        Err(VeilidAPIError::generic(format!(
+            "gave up after {MAX_FORWARD_HOPS} forwarding pointers (is there a loop?)"
+        )))
+    }
+
+    pub async fn close_dht_record(&self, record_key: RecordKey) -> VeilidAPIResult<()> {
+        let params = json!({ "record": record_key.to_string() });
+        self.audited(
+            "close",
+            params,
+            |_| "closed".to_string(),
+            self.rc.close_dht_record(record_key),
+        )
+        .await
+    }
+
+    pub async fn set_dht_value(
+        &self,
+        record_key: RecordKey,
+        subkey: ValueSubkey,
+        data: Vec<u8>,
+        options: Option<SetDHTValueOptions>,
+    ) -> VeilidAPIResult<Option<ValueData>> {
+        self.retrying("set", || self.set_once(record_key.clone(), subkey, data.clone(), options.clone()))
+            .await
+    }
+
+    async fn set_once(
+        &self,
+        record_key: RecordKey,
+        subkey: ValueSubkey,
+        data: Vec<u8>,
+        options: Option<SetDHTValueOptions>,
+    ) -> VeilidAPIResult<Option<ValueData>> {
+        let params = json!({
+            "record": record_key.to_string(),
+            "subkey": subkey,
+            "size": data.len(),
+            "writer": options
+                .as_ref()
+                .and_then(|o| o.writer.as_ref())
+                .map(|kp| kp.key().to_string()),
+        });
+        if self.dry_run {
+            let check = self.check_set(&record_key, subkey, &data);
+            if check.is_ok() {
+                let writer = options
+                    .as_ref()
+                    .and_then(|o| o.writer.as_ref())
+                    .map(|kp| kp.key().to_string())
+                    .unwrap_or_else(|| "<record default writer>".to_string());
+                println!(
+                    "[dry-run] would call set_dht_value(record: {record_key}, subkey: {subkey}, size: {} bytes, writer: {writer})",
+                    data.len()
+                );
+            }
+            return self
+                .audited("set", params, |_| "dry-run, not sent".to_string(), async {
+                    check.map(|_| None)
+                })
+                .await;
+        }
+        let size = data.len();
+        let writer = options.as_ref().and_then(|o| o.writer.as_ref()).map(|kp| kp.key());
+        let quota_key = record_key.clone();
+        let res = self
+            .audited(
+                "set",
+                params,
+                |newer: &Option<ValueData>| match newer {
+                    // set_dht_value hands back a value only when the network had a newer one
+                    Some(v) => format!("newer value exists (seq {})", v.seq()),
+                    None => "written".to_string(),
+                },
+                self.rc.set_dht_value(record_key, subkey, data, options),
+            )
+            .await;
+        if let (Ok(_), Some(bandwidth)) = (&res, &self.bandwidth) {
+            bandwidth.wrote(self.feature, size);
+        }
+        if let (Ok(_), Some(heatmap)) = (&res, &self.heatmap) {
+            heatmap.seen(&quota_key, subkey, Access::Write);
+        }
+        match (&res, &self.quota) {
+            (Ok(None), Some(quota)) => {
+                for warning in quota.wrote(&quota_key, subkey, writer.as_ref(), size) {
+                    println!("{warning}");
+                }
+            }
+            // ours lost, so the newer one is what the subkey holds
+            (Ok(Some(newer)), Some(quota)) => quota.saw(&quota_key, subkey, &newer.writer(), newer.data_size()),
+            _ => {}
+        }
+        res
+    }
+
+    // The same checks Veilid would make before accepting a write.
+    fn check_set(&self, record_key: &RecordKey, subkey: ValueSubkey, data: &[u8]) -> VeilidAPIResult<()> {
+        if data.len() > ValueData::MAX_LEN {
+            return Err(VeilidAPIError::invalid_argument(
+                "set_dht_value",
+                "data",
+                format!("{} bytes (max {})", data.len(), ValueData::MAX_LEN),
+            ));
+        }
+        if let Some(schema) = self.schemas.lock().unwrap().get(record_key) {
+            if subkey > schema.max_subkey() {
+                return Err(VeilidAPIError::invalid_argument(
+                    "set_dht_value",
+                    "subkey",
+                    format!("{subkey} (record has subkeys 0..={})", schema.max_subkey()),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn get_dht_value(
+        &self,
+        record_key: RecordKey,
+        subkey: ValueSubkey,
+        force_refresh: bool,
+    ) -> VeilidAPIResult<Option<ValueData>> {
+        self.retrying("get", || self.get_once(record_key.clone(), subkey, force_refresh)).await
+    }
+
+    async fn get_once(
+        &self,
+        record_key: RecordKey,
+        subkey: ValueSubkey,
+        force_refresh: bool,
+    ) -> VeilidAPIResult<Option<ValueData>> {
+        let params = json!({
+            "record": record_key.to_string(),
+            "subkey": subkey,
+            "force_refresh": force_refresh,
+        });
+        let quota_key = record_key.clone();
+        let res = self
+            .audited(
+                "get",
+                params,
+                |value: &Option<ValueData>| match value {
+                    Some(v) => format!("seq {} ({} bytes)", v.seq(), v.data_size()),
+                    None => "no data".to_string(),
+                },
+                self.rc.get_dht_value(record_key, subkey, force_refresh),
+            )
+            .await;
+        if let (Ok(value), Some(bandwidth)) = (&res, &self.bandwidth) {
+            bandwidth.read(self.feature, value.as_ref().map_or(0, |v| v.data_size()));
+        }
+        if let (Ok(Some(value)), Some(quota)) = (&res, &self.quota) {
+            quota.saw(&quota_key, subkey, &value.writer(), value.data_size());
+        }
+        if let (Ok(_), Some(heatmap)) = (&res, &self.heatmap) {
+            heatmap.seen(&quota_key, subkey, Access::Read);
+        }
+        res
+    }
+
+    pub async fn watch_dht_values(
+        &self,
+        record_key: RecordKey,
+        subkeys: Option<ValueSubkeyRangeSet>,
+        expiration: Option<Timestamp>,
+        count: Option<u32>,
+    ) -> VeilidAPIResult<bool> {
+        let params = json!({
+            "record": record_key.to_string(),
+            "subkeys": subkeys.as_ref().map(|s| s.to_string()),
+            "expiration": expiration.map(|t| t.as_u64()),
+            "count": count,
+        });
+        self.audited(
+            "watch",
+            params,
+            |active: &bool| format!("active={active}"),
+            self.rc
+                .watch_dht_values(record_key, subkeys, expiration, count),
+        )
+        .await
+    }
+
+    pub async fn inspect_dht_record(
+        &self,
+        record_key: RecordKey,
+        subkeys: Option<ValueSubkeyRangeSet>,
+        scope: DHTReportScope,
+    ) -> VeilidAPIResult<DHTRecordReport> {
+        self.retrying("inspect", || self.inspect_once(record_key.clone(), subkeys.clone(), scope))
+            .await
+    }
+
+    async fn inspect_once(
+        &self,
+        record_key: RecordKey,
+        subkeys: Option<ValueSubkeyRangeSet>,
+        scope: DHTReportScope,
+    ) -> VeilidAPIResult<DHTRecordReport> {
+        let params = json!({
+            "record": record_key.to_string(),
+            "subkeys": subkeys.as_ref().map(|s| s.to_string()),
+            "scope": format!("{scope:?}"),
+        });
+        self.audited(
+            "inspect",
+            params,
+            |r: &DHTRecordReport| format!("subkeys {}", r.subkeys()),
+            self.rc.inspect_dht_record(record_key, subkeys, scope),
+        )
+        .await
+    }
+}
+
+// -------------------------------------------------------------------------
+// Forwarding pointers
+//
+// When `schema grow` moves a record, the old record's subkey 0 is overwritten
+// with "veilid-example:moved-to <new record key>".
+// -------------------------------------------------------------------------
+
+const FORWARD_PREFIX: &str = "veilid-example:moved-to ";
+const MAX_FORWARD_HOPS: usize = 8;
+// early DHT mode's backoff between tries
+const RETRY_FIRST: Duration = Duration::from_millis(500);
+const RETRY_MAX: Duration = Duration::from_secs(8);
+
+pub fn forward_pointer(new_key: &RecordKey) -> Vec<u8> {
+    format!("{FORWARD_PREFIX}{new_key}").into_bytes()
+}
+
+pub fn parse_forward_pointer(data: &[u8]) -> Option<RecordKey> {
+    std::str::from_utf8(data)
+        .ok()?
+        .strip_prefix(FORWARD_PREFIX)?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryDht;
+
+    fn dht(backend: &Arc<MemoryDht>, dry_run: bool) -> Dht {
+        Dht::with_backend(backend.clone(), None, dry_run, None)
+    }
+
+    #[tokio::test]
+    async fn open_following_ends_up_on_the_newest_record() {
+        let backend = Arc::new(MemoryDht::new());
+        let rc = dht(&backend, false);
+        let schema = DHTSchema::dflt(1).unwrap();
+        let a = rc.create_dht_record(CRYPTO_KIND_VLD0, schema.clone(), None).await.unwrap();
+        let b = rc.create_dht_record(CRYPTO_KIND_VLD0, schema.clone(), None).await.unwrap();
+        let c = rc.create_dht_record(CRYPTO_KIND_VLD0, schema, None).await.unwrap();
+        rc.set_dht_value(a.key(), 0, forward_pointer(&b.key()), None).await.unwrap();
+        rc.set_dht_value(b.key(), 0, forward_pointer(&c.key()), None).await.unwrap();
+        rc.set_dht_value(c.key(), 0, b"here".to_vec(), None).await.unwrap();
+
+        let opened = rc.open_following(a.key(), None).await.unwrap();
+        assert_eq!(opened.key(), c.key());
+    }
+
+    #[tokio::test]
+    async fn open_following_gives_up_on_loops() {
+        let backend = Arc::new(MemoryDht::new());
+        let rc = dht(&backend, false);
+        let a = rc
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(1).unwrap(), None)
+            .await
+            .unwrap();
+        rc.set_dht_value(a.key(), 0, forward_pointer(&a.key()), None).await.unwrap();
+        assert!(rc.open_following(a.key(), None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn bytes_are_counted_per_feature() {
+        let backend = Arc::new(MemoryDht::new());
+        let bandwidth = Arc::new(Bandwidth::new());
+        let rc = dht(&backend, false).with_bandwidth(bandwidth.clone());
+        let key = rc
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(2).unwrap(), None)
+            .await
+            .unwrap()
+            .key();
+
+        rc.set_dht_value(key.clone(), 0, b"hello".to_vec(), None).await.unwrap();
+        let mail = rc.for_feature(Feature::Mail);
+        mail.set_dht_value(key.clone(), 1, vec![0; 100], None).await.unwrap();
+        mail.get_dht_value(key.clone(), 1, false).await.unwrap();
+        // a failed write moved nothing
+        assert!(mail.set_dht_value(key.clone(), 9, b"x".to_vec(), None).await.is_err());
+
+        let values = bandwidth.traffic(Feature::Values);
+        assert_eq!((values.writes, values.bytes_written, values.reads), (1, 5, 0));
+        let mail = bandwidth.traffic(Feature::Mail);
+        assert_eq!((mail.writes, mail.bytes_written, mail.reads, mail.bytes_read), (1, 100, 1, 100));
+        assert!(bandwidth.report().contains("mail"));
+    }
+
+    #[tokio::test]
+    async fn setup_calls_are_spared_injected_failures() {
+        let chaos = ChaosConfig {
+            fail_pct: 100,
+            delay_pct: 0,
+            ..ChaosConfig::with_pct(0)
+        };
+        let rc = Dht::with_backend(Arc::new(MemoryDht::new()), None, false, Some(chaos));
+        let schema = DHTSchema::dflt(1).unwrap();
+        assert!(rc.create_dht_record(CRYPTO_KIND_VLD0, schema.clone(), None).await.is_err());
+        let key = rc.for_setup().create_dht_record(CRYPTO_KIND_VLD0, schema, None).await.unwrap().key();
+        assert!(rc.for_setup().open_dht_record(key.clone(), None).await.is_ok());
+        // what the prompt does afterwards still gets them
+        assert!(rc.get_dht_value(key, 0, false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn injected_latency_holds_back_every_call() {
+        let backend = Arc::new(MemoryDht::new());
+        let latency = crate::chaos::InjectedLatency::parse("150ms").unwrap();
+        let rc = Dht::with_backend(backend, None, false, Some(ChaosConfig::latency_only(latency)));
+        let started = Instant::now();
+        let key = rc
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(1).unwrap(), None)
+            .await
+            .unwrap()
+            .key();
+        rc.set_dht_value(key.clone(), 0, b"slow".to_vec(), None).await.unwrap();
+        assert!(started.elapsed().as_millis() >= 300);
+        // the values themselves are untouched
+        let value = rc.get_dht_value(key, 0, false).await.unwrap().unwrap();
+        assert_eq!(value.data(), b"slow");
+
+        assert!(crate::chaos::InjectedLatency::parse("2s..200ms").is_err());
+        assert!(crate::chaos::InjectedLatency::parse("fast").is_err());
+        let range = crate::chaos::InjectedLatency::parse("200ms..2s").unwrap();
+        assert_eq!((range.min.as_millis(), range.max.as_millis()), (200, 2000));
+    }
+
+    #[tokio::test]
+    async fn early_mode_retries_try_again_until_it_runs_out() {
+        let backend = Arc::new(MemoryDht::new());
+        let key = dht(&backend, false)
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(1).unwrap(), None)
+            .await
+            .unwrap()
+            .key();
+        let always_fail = ChaosConfig {
+            fail_pct: 100,
+            delay_pct: 0,
+            max_delay: Duration::ZERO,
+            reattach_every: None,
+            injected: None,
+        };
+        let rc = Dht::with_backend(backend.clone(), None, false, Some(always_fail));
+        let started = Instant::now();
+        assert!(matches!(rc.get_dht_value(key.clone(), 0, false).await, Err(VeilidAPIError::TryAgain { .. })));
+        assert!(started.elapsed() < RETRY_FIRST);
+
+        // one retry fits in a second (after 500ms), the next wait wouldn't
+        let rc = rc.with_try_again_retry(Some(Duration::from_secs(1)));
+        let started = Instant::now();
+        assert!(matches!(rc.get_dht_value(key, 0, false).await, Err(VeilidAPIError::TryAgain { .. })));
+        assert!(started.elapsed() >= RETRY_FIRST && started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn latency_and_errors_are_averaged_per_call() {
+        let backend = Arc::new(MemoryDht::new());
+        let latency = Arc::new(Latency::new());
+        let rc = dht(&backend, false).with_latency(latency.clone());
+        let key = rc
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(1).unwrap(), None)
+            .await
+            .unwrap()
+            .key();
+        rc.set_dht_value(key.clone(), 0, b"a".to_vec(), None).await.unwrap();
+        rc.set_dht_value(key.clone(), 0, b"b".to_vec(), None).await.unwrap();
+        assert!(rc.set_dht_value(key, 5, b"c".to_vec(), None).await.is_err());
+
+        // busiest first, and one failure in three shows
+        let summary = latency.summary();
+        assert!(summary.starts_with("set 0ms 30% err"), "{summary}");
+        assert!(summary.ends_with("create 0ms"), "{summary}");
+    }
+
+    #[tokio::test]
+    async fn dry_run_writes_nothing_but_still_checks() {
+        let backend = Arc::new(MemoryDht::new());
+        let desc = dht(&backend, false)
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(1).unwrap(), None)
+            .await
+            .unwrap();
+
+        let rc = dht(&backend, true);
+        let _ = rc.open_dht_record(desc.key(), desc.owner_keypair()).await.unwrap();
+        rc.set_dht_value(desc.key(), 0, b"x".to_vec(), None).await.unwrap();
+        assert!(rc.get_dht_value(desc.key(), 0, false).await.unwrap().is_none());
+        // subkey 1 doesn't exist in a dflt(1) record
+        assert!(rc.set_dht_value(desc.key(), 1, b"x".to_vec(), None).await.is_err());
+    }
+
+    #[test]
+    fn forward_pointers_round_trip() {
+        let key: RecordKey = format!("VLD0:{}", BareOpaqueRecordKey::new(&[3; 32]).encode())
+            .parse()
+            .unwrap();
+        assert_eq!(parse_forward_pointer(&forward_pointer(&key)), Some(key));
+        assert_eq!(parse_forward_pointer(b"just some text"), None);
+    }
+}
@@ -0,0 +1,289 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use veilid_core::*;
+
+use crate::audit::{self, now_ms};
+use crate::config::AppConfig;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`diag bundle`: one zip to attach to an issue, holding what we'd ask for
+//	anyway:
+//
+//	  version.txt    this program, veilid-core, the OS, the role
+//	  config.json    our settings
+//	  state.json     Veilid's attachment, network and config state
+//	  stats.txt      what the `stats` commands print right now
+//	  audit.jsonl    the last AUDIT_KEEP entries of this role's audit log
+//	  logs/          the last LOG_KEEP lines of each log in the data folder
+//
+//	Everything that's JSON goes through redact() first: fields named like a
+//	secret (secret, password, token, private) are blanked, and so is any
+//	string that parses as a keypair, wherever it turns up. The logs get the
+//	same line by line (redact_text): JSON lines as JSON, and in plain ones
+//	any word that's a keypair and whatever follows "password=" or
+//	"token:" and the like. The home folder
+//	is replaced with ~ in every file, since it usually has the user's name
+//	in it. Public keys and record keys stay; they're what makes a report
+//	useful and they're public anyway.
+//
+//	The zip is written by hand (stored, not compressed) so this doesn't
+//	need a zip crate; any unzip tool reads it.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const AUDIT_KEEP: usize = 200;
+const LOG_KEEP: usize = 500;
+const REDACTED: &str = "<redacted>";
+const SECRET_FIELDS: [&str; 5] = ["secret", "password", "passphrase", "token", "private"];
+
+// Blank out anything that looks like a secret, in place.
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (name, field) in map.iter_mut() {
+                if is_secret_name(name) && !field.is_null() {
+                    *field = Value::String(REDACTED.to_string());
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        Value::String(s) if s.parse::<KeyPair>().is_ok() => *s = REDACTED.to_string(),
+        _ => {}
+    }
+}
+
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SECRET_FIELDS.iter().any(|s| name.contains(s))
+}
+
+// redact() for a log: each line that's JSON as JSON, the rest word by word.
+fn redact_text(text: &str) -> String {
+    text.lines().map(redact_line).collect::<Vec<_>>().join("\n")
+}
+
+fn redact_line(line: &str) -> String {
+    if let Ok(mut value) = serde_json::from_str::<Value>(line) {
+        if value.is_object() || value.is_array() {
+            redact(&mut value);
+            return value.to_string();
+        }
+    }
+    // "password: hunter2" hides the word after the name too
+    let mut hide_next = false;
+    line.split(' ')
+        .map(|word| {
+            let bare = word.trim_matches(|c: char| matches!(c, '"' | '\'' | ',' | ';' | '(' | ')' | '[' | ']'));
+            let hide = hide_next && !bare.is_empty();
+            hide_next = (hide_next && bare.is_empty()) || (word.ends_with(':') && is_secret_name(word));
+            if hide || bare.parse::<KeyPair>().is_ok() {
+                return REDACTED.to_string();
+            }
+            match word.split_once('=') {
+                Some((name, _)) if is_secret_name(name) => format!("{name}={REDACTED}"),
+                _ => word.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn without_home(text: &str) -> String {
+    match directories::BaseDirs::new() {
+        Some(dirs) => {
+            let home = dirs.home_dir().to_string_lossy().to_string();
+            if home.len() > 1 {
+                return text.replace(&home, "~");
+            }
+            text.to_string()
+        }
+        None => text.to_string(),
+    }
+}
+
+fn last_lines(text: &str, keep: usize) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    lines[lines.len().saturating_sub(keep)..].join("\n")
+}
+
+// -------------------------------------------------------------------------
+// A zip file, stored (no compression)
+// -------------------------------------------------------------------------
+
+#[derive(Default)]
+pub struct Bundle {
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl Bundle {
+    pub fn new() -> Bundle {
+        Bundle::default()
+    }
+
+    pub fn add(&mut self, name: &str, text: &str) {
+        self.files.push((name.to_string(), without_home(text).into_bytes()));
+    }
+
+    pub fn add_json(&mut self, name: &str, mut value: Value) {
+        redact(&mut value);
+        let text = serde_json::to_string_pretty(&value).unwrap_or_default();
+        self.add(name, &text);
+    }
+
+    pub fn to_zip(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for (name, data) in &self.files {
+            let offset = out.len() as u32;
+            let crc = crc32(data);
+            let size = data.len() as u32;
+            // local file header: version 2.0, no flags, stored, no date
+            out.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+            out.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+            out.extend_from_slice(&crc.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(data);
+
+            central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            central.extend_from_slice(&[20, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0]);
+            central.extend_from_slice(&crc.to_le_bytes());
+            central.extend_from_slice(&size.to_le_bytes());
+            central.extend_from_slice(&size.to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            // extra, comment, disk, internal attrs, external attrs
+            central.extend_from_slice(&[0; 12]);
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let central_at = out.len() as u32;
+        let count = self.files.len() as u16;
+        out.extend_from_slice(&central);
+        out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        out.extend_from_slice(&[0, 0, 0, 0]);
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        out.extend_from_slice(&central_at.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// -------------------------------------------------------------------------
+// `diag bundle`
+// -------------------------------------------------------------------------
+
+// Collect everything and write diag-<role>-<time>.zip into the data folder.
+// `stats` is what the node's `stats` commands print.
+pub async fn bundle(
+    api: &VeilidAPI,
+    config: &AppConfig,
+    data_dir: &Path,
+    role: &str,
+    stats: &str,
+) -> io::Result<PathBuf> {
+    let mut bundle = Bundle::new();
+    bundle.add(
+        "version.txt",
+        &format!(
+            "{} {}\nveilid-core {}\n{} {}\nrole {role}\nmade {}\n",
+            config.program_name,
+            env!("CARGO_PKG_VERSION"),
+            veilid_version_string(),
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            now_ms()
+        ),
+    );
+    bundle.add_json("config.json", serde_json::to_value(config)?);
+    match api.get_state().await {
+        Ok(state) => bundle.add_json("state.json", serde_json::to_value(&state)?),
+        Err(e) => bundle.add("state.json", &format!("{{\"error\": \"{e}\"}}")),
+    }
+    bundle.add("stats.txt", stats);
+
+    if let Ok(entries) = audit::read_entries(&data_dir.join(audit::log_file_name(role))) {
+        let mut lines = Vec::new();
+        for entry in &entries[entries.len().saturating_sub(AUDIT_KEEP)..] {
+            let mut value = serde_json::to_value(entry)?;
+            redact(&mut value);
+            lines.push(value.to_string());
+        }
+        bundle.add("audit.jsonl", &lines.join("\n"));
+    }
+
+    let mut logs: Vec<PathBuf> = fs::read_dir(data_dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|x| x == "log"))
+        .collect();
+    logs.sort();
+    for path in logs {
+        if let (Some(name), Ok(text)) = (path.file_name(), fs::read_to_string(&path)) {
+            bundle.add(&format!("logs/{}", name.to_string_lossy()), &redact_text(&last_lines(&text, LOG_KEEP)));
+        }
+    }
+
+    let path = data_dir.join(format!("diag-{role}-{}.zip", now_ms()));
+    fs::File::create(&path)?.write_all(&bundle.to_zip())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn secrets_are_redacted_and_the_zip_is_readable() {
+        let kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let mut value = json!({
+            "protected_store": { "device_encryption_key_password": "hunter2" },
+            "network": { "network_key_password": null, "peers": [kp.to_string()] },
+            "writer": kp.key().to_string(),
+        });
+        redact(&mut value);
+        assert_eq!(value["protected_store"]["device_encryption_key_password"], REDACTED);
+        assert!(value["network"]["network_key_password"].is_null());
+        assert_eq!(value["network"]["peers"][0], REDACTED);
+        assert_eq!(value["writer"], kp.key().to_string());
+
+        let log = format!("opened with {kp}, writer {}\nnetwork_key_password=hunter2 token: \"abc\" ok\n{{\"secret\":\"x\"}}", kp.key());
+        assert_eq!(
+            redact_text(&log),
+            format!("opened with {REDACTED} writer {}\nnetwork_key_password={REDACTED} token: {REDACTED} ok\n{{\"secret\":\"{REDACTED}\"}}", kp.key())
+        );
+
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        let mut bundle = Bundle::new();
+        bundle.add("a.txt", "hello");
+        let zip = bundle.to_zip();
+        assert_eq!(&zip[..4], b"PK\x03\x04");
+        // the end record says one file, and where the directory starts
+        let end = &zip[zip.len() - 22..];
+        assert_eq!(&end[..4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 1);
+        let central_at = u32::from_le_bytes([end[16], end[17], end[18], end[19]]) as usize;
+        assert_eq!(&zip[central_at..central_at + 4], b"PK\x01\x02");
+    }
+}
@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::dht::Dht;
+use crate::envelope::{Codec, Envelope};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The application index: one public record every copy of this program can
+//	find, where default nodes may announce their record with a label, and
+//	`discover` lists what's been announced. Handy for demos, no copying keys
+//	around.
+//
+//	The index's owner keypair is derived from INDEX_SEED, so everyone works
+//	out the same record key, and everyone can write to it. That's the point,
+//	but it also means anyone can overwrite or spam it: treat what's in there
+//	as hints, not trusted data. Announcing is opt-in (announce_label in the
+//	config).
+//
+//	Each record gets a slot (subkey) picked from its key, so announcements
+//	from different nodes mostly don't land on each other. They expire after
+//	ANNOUNCE_TTL; a node that's still up announces again (spawn_reannounce).
+//
+//	create_dht_record always makes up a fresh encryption key, which nobody
+//	else would know, so the index is created once and then used under its
+//	plain key (no encryption key), which is the one everyone can work out.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const INDEX_SEED: &[u8] = b"SMPL_Veilid_DHT_Example/app-index/v1";
+pub const INDEX_SLOTS: u16 = 64;
+pub const ANNOUNCE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Announcement {
+    pub record: String,
+    pub label: String,
+    // when it was announced (ms since the unix epoch)
+    #[serde(skip)]
+    pub announced_ms: u64,
+}
+
+// The same keypair for everyone.
+pub fn index_owner() -> KeyPair {
+    let seed = blake3::hash(INDEX_SEED);
+    let signing = SigningKey::from_bytes(seed.as_bytes());
+    KeyPair::new(
+        CRYPTO_KIND_VLD0,
+        BareKeyPair::new(
+            BarePublicKey::new(&signing.verifying_key().to_bytes()),
+            BareSecretKey::new(&signing.to_bytes()),
+        ),
+    )
+}
+
+fn index_schema() -> VeilidAPIResult<DHTSchema> {
+    DHTSchema::dflt(INDEX_SLOTS)
+}
+
+// Where a record's announcement goes.
+pub fn slot_for(record: &RecordKey) -> ValueSubkey {
+    let hash = blake3::hash(record.to_string().as_bytes());
+    let n = u32::from_le_bytes(hash.as_bytes()[..4].try_into().unwrap());
+    n % u32::from(INDEX_SLOTS)
+}
+
+// Open the index, creating it if nobody has yet (only when `create` is set).
+pub async fn open_index(rc: &Dht, create: bool) -> VeilidAPIResult<RecordKey> {
+    let owner = index_owner();
+    let key = rc.get_dht_record_key(index_schema()?, owner.key()).await?;
+    match rc.open_dht_record(key.clone(), Some(owner.clone())).await {
+        Ok(_) => Ok(key),
+        Err(e) if !create => Err(e),
+        Err(_) => {
+            let created = rc
+                .create_dht_record(CRYPTO_KIND_VLD0, index_schema()?, Some(owner.clone()))
+                .await?;
+            // reopen under the key everyone else uses
+            let _ = rc.close_dht_record(created.key()).await;
+            let _ = rc.open_dht_record(key.clone(), Some(owner)).await?;
+            Ok(key)
+        }
+    }
+}
+
+// Put `record` in the index under `label`. Gives back the slot it went in.
+pub async fn announce(rc: &Dht, record: &RecordKey, label: &str) -> VeilidAPIResult<ValueSubkey> {
+    let index = open_index(rc, true).await?;
+    let slot = slot_for(record);
+    let body = serde_json::to_vec(&Announcement {
+        record: record.to_string(),
+        label: label.to_string(),
+        announced_ms: 0,
+    })
+    .map_err(VeilidAPIError::internal)?;
+    let mut env = Envelope::new(Codec::Json, body);
+    env.written_ms = Some(crate::audit::now_ms() as u64);
+    let res = rc
+        .set_dht_value(index.clone(), slot, env.expiring_after(ANNOUNCE_TTL).encode(), None)
+        .await;
+    let _ = rc.close_dht_record(index).await;
+    res.map(|_| slot)
+}
+
+// Announce again every half ANNOUNCE_TTL, for as long as the node runs.
+pub fn spawn_reannounce(rc: Dht, record: RecordKey, label: String) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(ANNOUNCE_TTL / 2).await;
+            if let Err(e) = announce(&rc, &record, &label).await {
+                eprintln!("re-announcing the record failed: {e}");
+            }
+        }
+    })
+}
+
+// Everything announced that hasn't expired, newest first.
+pub async fn list(rc: &Dht) -> VeilidAPIResult<Vec<Announcement>> {
+    let index = open_index(rc, false).await?;
+    let now = crate::audit::now_ms() as u64;
+    let mut found = Vec::new();
+    for slot in 0..ValueSubkey::from(INDEX_SLOTS) {
+        let Ok(Some(value)) = rc.get_dht_value(index.clone(), slot, true).await else {
+            continue;
+        };
+        if let Some(a) = decode(value.data(), now) {
+            found.push(a);
+        }
+    }
+    let _ = rc.close_dht_record(index).await;
+    found.sort_by_key(|a| std::cmp::Reverse(a.announced_ms));
+    Ok(found)
+}
+
+// A VLD0 record key of the right length: parsing alone takes any base64.
+fn record_key(text: &str) -> Option<RecordKey> {
+    let key = text.trim().parse::<RecordKey>().ok()?;
+    let opaque = key.ref_value().ref_key();
+    (key.kind() == CRYPTO_KIND_VLD0 && opaque.len() == VLD0_HASH_DIGEST_LENGTH).then_some(key)
+}
+
+fn decode(data: &[u8], now_ms: u64) -> Option<Announcement> {
+    let env = Envelope::decode(data).ok()?;
+    if env.codec != Codec::Json || !env.is_live(now_ms) {
+        return None;
+    }
+    let mut a: Announcement = serde_json::from_slice(&env.body).ok()?;
+    // anyone can write here, so only list things that really are record keys
+    a.record = record_key(&a.record)?.to_string();
+    a.announced_ms = env.written_ms.unwrap_or(0);
+    Some(a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryDht;
+    use std::sync::Arc;
+
+    #[test]
+    fn everyone_gets_the_same_index_owner() {
+        assert_eq!(index_owner(), index_owner());
+    }
+
+    #[tokio::test]
+    async fn announced_records_are_listed() {
+        let rc = Dht::with_backend(Arc::new(MemoryDht::new()), None, false, None);
+        // nothing announced yet, so there's no index to read
+        assert!(list(&rc).await.is_err());
+
+        let record = rc
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(1).unwrap(), None)
+            .await
+            .unwrap()
+            .key();
+        let slot = announce(&rc, &record, "my demo").await.unwrap();
+        assert_eq!(slot, slot_for(&record));
+
+        let found = list(&rc).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].record, record.to_string());
+        assert_eq!(found[0].label, "my demo");
+        assert!(found[0].announced_ms > 0);
+
+        // announcing again reuses the index rather than making another
+        announce(&rc, &record, "renamed").await.unwrap();
+        assert_eq!(list(&rc).await.unwrap()[0].label, "renamed");
+    }
+
+    #[test]
+    fn junk_and_expired_entries_are_skipped() {
+        let junk = Envelope::new(Codec::Json, br#"{"record":"nope","label":"x"}"#.to_vec());
+        assert_eq!(decode(&junk.encode(), 0), None);
+        assert_eq!(decode(&Envelope::text("hello").encode(), 0), None);
+
+        let record = RecordKey::new(
+            CRYPTO_KIND_VLD0,
+            BareRecordKey::new(BareOpaqueRecordKey::new(&[7; 32]), None),
+        );
+        let body = serde_json::to_vec(&Announcement {
+            record: record.to_string(),
+            label: "old".to_string(),
+            announced_ms: 0,
+        })
+        .unwrap();
+        let mut old = Envelope::new(Codec::Json, body);
+        old.written_ms = Some(1_000);
+        let old = old.expiring_after(Duration::from_secs(1));
+        assert!(decode(&old.encode(), 1_500).is_some());
+        assert_eq!(decode(&old.encode(), 5_000), None);
+    }
+}
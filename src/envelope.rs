@@ -0,0 +1,467 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::codecs::{self, CodecKind};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The envelope every value we write to the DHT is wrapped in.
+//
+//	  bytes 0..4   magic "VXEN"
+//	  byte  4      format version (see ENVELOPE_VERSION)
+//	  byte  5      codec: how to read the body (raw bytes, UTF-8 text, JSON)
+//	  byte  6      flags: FLAG_COMPRESSED, FLAG_ENCRYPTED, FLAG_TIMESTAMP,
+//	               FLAG_EXPIRES, FLAG_TOMBSTONE, FLAG_SENDER_SEQ, FLAG_PIPELINE
+//	  8 bytes      only with FLAG_TIMESTAMP: when it was written (ms, little endian)
+//	  8 bytes      only with FLAG_EXPIRES: when it stops being valid (ms, little endian)
+//	  8 bytes      only with FLAG_SENDER_SEQ: the writer's own message counter
+//	               (little endian), so readers can spot gaps (see ordering.rs)
+//	  1 + N bytes  only with FLAG_PIPELINE: how many codecs the body went
+//	               through, then their ids in the order they ran (see codecs.rs)
+//	  the rest     the body
+//
+//	decode() undoes the pipeline, so readers only ever see the body as it
+//	was before the codecs ran; FLAG_COMPRESSED and FLAG_ENCRYPTED are set
+//	from the kinds of codec in it. Either flag without a pipeline means the
+//	body was packed some way this build can't undo, and it's shown as such.
+//
+//	Expiry is for things like presence and short-lived announcements: once
+//	the time has passed readers treat the value as gone, and the writer's
+//	janitor (see janitor.rs) replaces it with a tombstone, an empty value
+//	with FLAG_TOMBSTONE set, so the stale text doesn't hang around the DHT.
+//
+//	Values from before the envelope existed are plain UTF-8 text with no
+//	header. Anything that doesn't start with the magic is read as one of those,
+//	so records written by older builds still display fine.
+//
+//	A newer build may write a higher version. We refuse those with a clear
+//	message instead of showing garbage: bump ENVELOPE_VERSION only when the
+//	layout changes in a way old readers can't skip over.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub const MAGIC: [u8; 4] = *b"VXEN";
+pub const ENVELOPE_VERSION: u8 = 1;
+// version reported for headerless values written before the envelope existed
+pub const LEGACY_VERSION: u8 = 0;
+
+pub const FLAG_COMPRESSED: u8 = 0b0000_0001;
+pub const FLAG_ENCRYPTED: u8 = 0b0000_0010;
+pub const FLAG_TIMESTAMP: u8 = 0b0000_0100;
+pub const FLAG_EXPIRES: u8 = 0b0000_1000;
+pub const FLAG_TOMBSTONE: u8 = 0b0001_0000;
+pub const FLAG_SENDER_SEQ: u8 = 0b0010_0000;
+pub const FLAG_PIPELINE: u8 = 0b0100_0000;
+// the flags this build knows what to do with
+const KNOWN_FLAGS: u8 = FLAG_COMPRESSED
+    | FLAG_ENCRYPTED
+    | FLAG_TIMESTAMP
+    | FLAG_EXPIRES
+    | FLAG_TOMBSTONE
+    | FLAG_SENDER_SEQ
+    | FLAG_PIPELINE;
+// the flags encode() sets from the fields, not from `flags`
+const FIELD_FLAGS: u8 = FLAG_TIMESTAMP | FLAG_EXPIRES | FLAG_SENDER_SEQ | FLAG_PIPELINE;
+
+const HEADER_LEN: usize = 7;
+const TIMESTAMP_LEN: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    Raw = 0,
+    Text = 1,
+    Json = 2,
+}
+
+impl Codec {
+    fn from_byte(b: u8) -> Option<Codec> {
+        match b {
+            0 => Some(Codec::Raw),
+            1 => Some(Codec::Text),
+            2 => Some(Codec::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Envelope {
+    pub version: u8,
+    pub codec: Codec,
+    pub flags: u8,
+    // when the writer wrapped it, if they said (ms since the unix epoch)
+    pub written_ms: Option<u64>,
+    // when readers should stop believing it (ms since the unix epoch)
+    pub expires_ms: Option<u64>,
+    // the writer's count of what it has sent, 1 for the first
+    pub sender_seq: Option<u64>,
+    // the ids of the codecs the body goes through on the way out, in order
+    // (see codecs.rs); `body` is always what went in
+    pub pipeline: Vec<u8>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EnvelopeError {
+    // written by a newer build than this one
+    UnsupportedVersion(u8),
+    UnknownCodec(u8),
+    UnknownFlags(u8),
+    // went through a codec this build doesn't have
+    MissingCodec(u8),
+    // a codec couldn't undo what it's said to have done
+    CodecFailed(String, String),
+    Truncated,
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvelopeError::UnsupportedVersion(v) => write!(
+                f,
+                "value uses envelope version {v}, this build only reads up to {ENVELOPE_VERSION} (time to upgrade)"
+            ),
+            EnvelopeError::UnknownCodec(c) => write!(f, "value uses unknown codec {c}"),
+            EnvelopeError::UnknownFlags(fl) => write!(f, "value has unknown flags {fl:#010b}"),
+            EnvelopeError::MissingCodec(id) => write!(f, "value went through codec {id}, which this build doesn't have"),
+            EnvelopeError::CodecFailed(name, why) => write!(f, "value didn't come back through codec '{name}': {why}"),
+            EnvelopeError::Truncated => write!(f, "value is too short for its envelope header"),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+impl Envelope {
+    pub fn new(codec: Codec, body: Vec<u8>) -> Envelope {
+        Envelope {
+            version: ENVELOPE_VERSION,
+            codec,
+            flags: 0,
+            written_ms: None,
+            expires_ms: None,
+            sender_seq: None,
+            pipeline: Vec::new(),
+            body,
+        }
+    }
+
+    // UTF-8 text stamped with the time it was written, which is what the nodes send.
+    pub fn text(text: &str) -> Envelope {
+        let mut env = Envelope::new(Codec::Text, text.as_bytes().to_vec());
+        env.written_ms = Some(crate::audit::now_ms() as u64);
+        env
+    }
+
+    // What the janitor writes over an expired value.
+    pub fn tombstone() -> Envelope {
+        let mut env = Envelope::new(Codec::Raw, Vec::new());
+        env.flags = FLAG_TOMBSTONE;
+        env.written_ms = Some(crate::audit::now_ms() as u64);
+        env
+    }
+
+    // Expire `ttl` after it was written (or from now, if it has no timestamp).
+    pub fn expiring_after(mut self, ttl: Duration) -> Envelope {
+        let from = self.written_ms.unwrap_or(crate::audit::now_ms() as u64);
+        self.expires_ms = Some(from.saturating_add(ttl.as_millis() as u64));
+        self
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version == LEGACY_VERSION
+    }
+
+    pub fn is_tombstone(&self) -> bool {
+        self.flags & FLAG_TOMBSTONE != 0
+    }
+
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        self.expires_ms.is_some_and(|at| at <= now_ms)
+    }
+
+    // Readers skip tombstones and expired values as if the subkey were empty.
+    pub fn is_live(&self, now_ms: u64) -> bool {
+        !self.is_tombstone() && !self.is_expired(now_ms)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut flags = self.flags & !FIELD_FLAGS;
+        if self.written_ms.is_some() {
+            flags |= FLAG_TIMESTAMP;
+        }
+        if self.expires_ms.is_some() {
+            flags |= FLAG_EXPIRES;
+        }
+        if self.sender_seq.is_some() {
+            flags |= FLAG_SENDER_SEQ;
+        }
+        let mut body = self.body.clone();
+        if !self.pipeline.is_empty() {
+            flags |= FLAG_PIPELINE;
+            for &id in &self.pipeline {
+                // the ids come from codecs::pipeline(), which only gives out registered ones
+                let codec = codecs::get(id).unwrap_or_else(|| panic!("codec {id} isn't registered"));
+                flags |= match codec.kind() {
+                    CodecKind::Compression => FLAG_COMPRESSED,
+                    CodecKind::Encryption => FLAG_ENCRYPTED,
+                    CodecKind::Other => 0,
+                };
+                body = codec.encode(&body);
+            }
+        }
+        let mut out = Vec::with_capacity(HEADER_LEN + 3 * TIMESTAMP_LEN + 1 + self.pipeline.len() + body.len());
+        out.extend_from_slice(&MAGIC);
+        out.push(ENVELOPE_VERSION);
+        out.push(self.codec as u8);
+        out.push(flags);
+        for n in [self.written_ms, self.expires_ms, self.sender_seq].into_iter().flatten() {
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        if !self.pipeline.is_empty() {
+            out.push(self.pipeline.len() as u8);
+            out.extend_from_slice(&self.pipeline);
+        }
+        out.extend_from_slice(&body);
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Envelope, EnvelopeError> {
+        let Some(rest) = data.strip_prefix(&MAGIC) else {
+            return Ok(Envelope {
+                version: LEGACY_VERSION,
+                codec: Codec::Text,
+                flags: 0,
+                written_ms: None,
+                expires_ms: None,
+                sender_seq: None,
+                pipeline: Vec::new(),
+                body: data.to_vec(),
+            });
+        };
+        let [version, codec, flags, rest @ ..] = rest else {
+            return Err(EnvelopeError::Truncated);
+        };
+        if *version == LEGACY_VERSION || *version > ENVELOPE_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(*version));
+        }
+        let codec = Codec::from_byte(*codec).ok_or(EnvelopeError::UnknownCodec(*codec))?;
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(EnvelopeError::UnknownFlags(*flags));
+        }
+        let (written_ms, rest) = take_timestamp(rest, flags & FLAG_TIMESTAMP != 0)?;
+        let (expires_ms, rest) = take_timestamp(rest, flags & FLAG_EXPIRES != 0)?;
+        let (sender_seq, rest) = take_timestamp(rest, flags & FLAG_SENDER_SEQ != 0)?;
+        let (pipeline, body) = take_pipeline(rest, flags & FLAG_PIPELINE != 0)?;
+        let mut body = body.to_vec();
+        for &id in pipeline.iter().rev() {
+            let stage = codecs::get(id).ok_or(EnvelopeError::MissingCodec(id))?;
+            body = stage
+                .decode(&body)
+                .map_err(|why| EnvelopeError::CodecFailed(stage.name().to_string(), why))?;
+        }
+        Ok(Envelope {
+            version: *version,
+            codec,
+            flags: *flags,
+            written_ms,
+            expires_ms,
+            sender_seq,
+            pipeline: pipeline.to_vec(),
+            body,
+        })
+    }
+
+    // Something a person can read, whatever the codec.
+    pub fn display(&self) -> String {
+        if self.is_tombstone() {
+            return "<expired>".to_string();
+        }
+        if self.flags & (FLAG_COMPRESSED | FLAG_ENCRYPTED) != 0 && self.pipeline.is_empty() {
+            return format!("<{} bytes, compressed/encrypted>", self.body.len());
+        }
+        match self.codec {
+            Codec::Text | Codec::Json => String::from_utf8_lossy(&self.body).into_owned(),
+            Codec::Raw => format!("<{} raw bytes>", self.body.len()),
+        }
+    }
+}
+
+// The 8 byte timestamp (or counter) at the front of `rest`, if the flag says there is one.
+fn take_timestamp(rest: &[u8], present: bool) -> Result<(Option<u64>, &[u8]), EnvelopeError> {
+    if !present {
+        return Ok((None, rest));
+    }
+    if rest.len() < TIMESTAMP_LEN {
+        return Err(EnvelopeError::Truncated);
+    }
+    let (ts, rest) = rest.split_at(TIMESTAMP_LEN);
+    Ok((Some(u64::from_le_bytes(ts.try_into().unwrap())), rest))
+}
+
+// The codec ids at the front of `rest`, if the flag says there are any.
+fn take_pipeline(rest: &[u8], present: bool) -> Result<(&[u8], &[u8]), EnvelopeError> {
+    if !present {
+        return Ok((&rest[..0], rest));
+    }
+    let [count, rest @ ..] = rest else {
+        return Err(EnvelopeError::Truncated);
+    };
+    if rest.len() < *count as usize {
+        return Err(EnvelopeError::Truncated);
+    }
+    Ok(rest.split_at(*count as usize))
+}
+
+// Decode a DHT value straight to display text, errors included.
+// Expired values show as expired even before the janitor gets to them.
+pub fn display_value(data: &[u8]) -> String {
+    match Envelope::decode(data) {
+        Ok(env) if !env.is_live(crate::audit::now_ms() as u64) => "<expired>".to_string(),
+        Ok(env) => env.display(),
+        Err(e) => format!("<unreadable: {e}>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // The biggest value a subkey holds (veilid's ValueData::MAX_LEN).
+    const MAX_VALUE_LEN: usize = 32768;
+
+    fn codec() -> impl Strategy<Value = Codec> {
+        prop_oneof![Just(Codec::Raw), Just(Codec::Text), Just(Codec::Json)]
+    }
+
+    // Lengths around the edges that tend to break things, plus anything in between.
+    fn body() -> impl Strategy<Value = Vec<u8>> {
+        prop_oneof![
+            prop::collection::vec(any::<u8>(), 0..64),
+            prop::collection::vec(any::<u8>(), 0..MAX_VALUE_LEN - HEADER_LEN - TIMESTAMP_LEN),
+            (0usize..=4).prop_map(|n| vec![0xAA; MAX_VALUE_LEN - HEADER_LEN - TIMESTAMP_LEN - n]),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips(
+            codec in codec(),
+            flags in any::<u8>().prop_map(|f| f & (FLAG_COMPRESSED | FLAG_ENCRYPTED | FLAG_TOMBSTONE)),
+            written_ms in any::<Option<u64>>(),
+            expires_ms in any::<Option<u64>>(),
+            sender_seq in any::<Option<u64>>(),
+            body in body(),
+        ) {
+            let mut env = Envelope::new(codec, body);
+            env.flags = flags;
+            env.written_ms = written_ms;
+            env.expires_ms = expires_ms;
+            env.sender_seq = sender_seq;
+
+            let decoded = Envelope::decode(&env.encode()).unwrap();
+            prop_assert_eq!(decoded.codec, env.codec);
+            prop_assert_eq!(decoded.written_ms, env.written_ms);
+            prop_assert_eq!(decoded.expires_ms, env.expires_ms);
+            prop_assert_eq!(decoded.sender_seq, env.sender_seq);
+            prop_assert_eq!(decoded.flags & !FIELD_FLAGS, flags);
+            prop_assert_eq!(decoded.body, env.body);
+            prop_assert_eq!(decoded.version, ENVELOPE_VERSION);
+        }
+
+        #[test]
+        fn encoded_size_is_header_plus_body(
+            written_ms in any::<Option<u64>>(),
+            expires_ms in any::<Option<u64>>(),
+            sender_seq in any::<Option<u64>>(),
+            body in body(),
+        ) {
+            let mut env = Envelope::new(Codec::Raw, body);
+            env.written_ms = written_ms;
+            env.expires_ms = expires_ms;
+            env.sender_seq = sender_seq;
+            let extra = TIMESTAMP_LEN
+                * [written_ms, expires_ms, sender_seq].iter().filter(|n| n.is_some()).count();
+            prop_assert_eq!(env.encode().len(), HEADER_LEN + extra + env.body.len());
+        }
+
+        // Anything an old build wrote (no magic) comes back untouched as legacy text.
+        #[test]
+        fn legacy_values_pass_through(data in prop::collection::vec(any::<u8>(), 0..512)) {
+            prop_assume!(!data.starts_with(&MAGIC));
+            let env = Envelope::decode(&data).unwrap();
+            prop_assert!(env.is_legacy());
+            prop_assert_eq!(env.codec, Codec::Text);
+            prop_assert_eq!(env.body, data);
+        }
+
+        #[test]
+        fn legacy_text_displays_as_itself(text in "\\PC{0,200}") {
+            prop_assume!(!text.as_bytes().starts_with(&MAGIC));
+            prop_assert_eq!(display_value(text.as_bytes()), text);
+        }
+
+        // Random bytes after the magic never panic, they decode or give an error.
+        #[test]
+        fn garbage_after_magic_never_panics(rest in prop::collection::vec(any::<u8>(), 0..64)) {
+            let mut data = MAGIC.to_vec();
+            data.extend_from_slice(&rest);
+            let _ = Envelope::decode(&data);
+        }
+
+        #[test]
+        fn newer_versions_are_refused(version in (ENVELOPE_VERSION + 1)..=u8::MAX) {
+            let mut data = Envelope::new(Codec::Text, b"hi".to_vec()).encode();
+            data[4] = version;
+            prop_assert_eq!(Envelope::decode(&data), Err(EnvelopeError::UnsupportedVersion(version)));
+        }
+
+        #[test]
+        fn cut_off_timestamps_are_truncated(keep in 0..TIMESTAMP_LEN) {
+            let mut env = Envelope::new(Codec::Text, Vec::new());
+            env.written_ms = Some(1);
+            let data = env.encode();
+            prop_assert_eq!(
+                Envelope::decode(&data[..HEADER_LEN + keep]),
+                Err(EnvelopeError::Truncated)
+            );
+        }
+    }
+
+    #[test]
+    fn header_only_magic_is_truncated() {
+        assert_eq!(Envelope::decode(&MAGIC), Err(EnvelopeError::Truncated));
+    }
+
+    #[test]
+    fn expiry_and_tombstones() {
+        let env = Envelope::text("here").expiring_after(Duration::from_secs(60));
+        let written = env.written_ms.unwrap();
+        assert_eq!(env.expires_ms, Some(written + 60_000));
+        assert!(env.is_live(written + 59_999));
+        assert!(!env.is_live(written + 60_000));
+
+        // no expiry, never expires
+        assert!(Envelope::text("forever").is_live(u64::MAX));
+
+        let tomb = Envelope::decode(&Envelope::tombstone().encode()).unwrap();
+        assert!(tomb.is_tombstone());
+        assert!(!tomb.is_live(0));
+        assert_eq!(tomb.display(), "<expired>");
+    }
+
+    #[test]
+    fn expired_values_display_as_expired() {
+        let mut env = Envelope::text("gone");
+        env.expires_ms = Some(1);
+        assert_eq!(display_value(&env.encode()), "<expired>");
+    }
+
+    #[test]
+    fn unknown_flags_are_refused() {
+        let mut data = Envelope::new(Codec::Text, b"hi".to_vec()).encode();
+        data[6] = 0b1000_0000;
+        assert_eq!(Envelope::decode(&data), Err(EnvelopeError::UnknownFlags(0b1000_0000)));
+    }
+}
@@ -0,0 +1,547 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use veilid_core::*;
+
+use crate::audit::now_ms;
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::dht::Dht;
+use crate::exit::Kind;
+use crate::node::VeilidNode;
+use crate::progress;
+use crate::shortcode::ShortcodeBook;
+use crate::watch::WatchSet;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`record-events FILE [REC]` and `replay-events FILE`: keep everything a
+//	node hears from Veilid during a flaky session, and work out afterwards
+//	what happened.
+//
+//	The recorder runs a node of its own (<default_namespace>-recorder) that
+//	never writes, optionally watching one record, and appends every
+//	VeilidUpdate to FILE until Ctrl+C. Updates are cut down before they're
+//	kept: no log text, no message or value contents, no peer addresses and
+//	no config, only what they were, when, and how big. A record is kept as
+//	a 4 byte tag (the start of the blake3 of its key); the recorder prints
+//	the tag of the one it watches.
+//
+//	  bytes 0..4   magic "VXEV"
+//	  byte  4      format version (see VERSION)
+//	  8 bytes      when the recording started (ms, little endian)
+//	  then per update: a kind byte, 4 bytes of ms since the start and the
+//	  kind's fields (little endian), see Event::encode
+//
+//	The file is flushed after every update, so a recorder that's killed
+//	leaves a log that's good up to its last update. The replay reads it
+//	back and prints the statistics: time in each attachment state, drops,
+//	peers and bandwidth, value changes per record and the longest silence
+//	between them, log lines by level, dead routes.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const MAGIC: [u8; 4] = *b"VXEV";
+const VERSION: u8 = 1;
+
+// AttachmentState's values, in order
+const ATTACHMENT_STATES: [&str; 8] =
+    ["detached", "attaching", "weak", "fair", "good", "strong", "full", "detaching"];
+// VeilidLogLevel's values, from 1
+const LOG_LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+// A VeilidUpdate with everything private taken out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Log { level: u8 },
+    AppMessage { len: u32 },
+    AppCall { len: u32 },
+    Attachment { state: u8, public_ready: bool, local_ready: bool, peers: u32 },
+    Network { started: bool, bps_down: u64, bps_up: u64, peers: u32 },
+    Config,
+    RouteChange { dead: u32, dead_remote: u32 },
+    // None for a change that came without its value
+    ValueChange { record: u32, subkeys: u32, count: u32, value_len: Option<u32> },
+    Shutdown,
+}
+
+// What a record is called in the log.
+pub fn record_tag(key: &RecordKey) -> u32 {
+    let hash = blake3::hash(key.to_string().as_bytes());
+    u32::from_le_bytes(hash.as_bytes()[..4].try_into().unwrap())
+}
+
+fn clamp(n: usize) -> u32 {
+    u32::try_from(n).unwrap_or(u32::MAX)
+}
+
+impl Event {
+    pub fn from_update(update: &VeilidUpdate) -> Event {
+        match update {
+            VeilidUpdate::Log(log) => Event::Log { level: log.log_level as u8 },
+            VeilidUpdate::AppMessage(msg) => Event::AppMessage { len: clamp(msg.message().len()) },
+            VeilidUpdate::AppCall(call) => Event::AppCall { len: clamp(call.message().len()) },
+            VeilidUpdate::Attachment(att) => Event::Attachment {
+                state: att.state as u8,
+                public_ready: att.public_internet_ready,
+                local_ready: att.local_network_ready,
+                peers: u32::try_from(att.live_peer_count.as_u64()).unwrap_or(u32::MAX),
+            },
+            VeilidUpdate::Network(net) => Event::Network {
+                started: net.started,
+                bps_down: net.bps_down.as_u64(),
+                bps_up: net.bps_up.as_u64(),
+                peers: clamp(net.peers.len()),
+            },
+            VeilidUpdate::Config(_) => Event::Config,
+            VeilidUpdate::RouteChange(change) => Event::RouteChange {
+                dead: clamp(change.dead_routes.len()),
+                dead_remote: clamp(change.dead_remote_routes.len()),
+            },
+            VeilidUpdate::ValueChange(change) => Event::ValueChange {
+                record: record_tag(&change.key),
+                subkeys: u32::try_from(change.subkeys.len()).unwrap_or(u32::MAX),
+                count: change.count,
+                value_len: change.value.as_ref().map(|v| clamp(v.data_size())),
+            },
+            VeilidUpdate::Shutdown => Event::Shutdown,
+        }
+    }
+
+    fn encode(&self, at: u32, out: &mut Vec<u8>) {
+        let kind: u8 = match self {
+            Event::Log { .. } => 0,
+            Event::AppMessage { .. } => 1,
+            Event::AppCall { .. } => 2,
+            Event::Attachment { .. } => 3,
+            Event::Network { .. } => 4,
+            Event::Config => 5,
+            Event::RouteChange { .. } => 6,
+            Event::ValueChange { .. } => 7,
+            Event::Shutdown => 8,
+        };
+        out.push(kind);
+        out.extend_from_slice(&at.to_le_bytes());
+        match self {
+            Event::Log { level } => out.push(*level),
+            Event::AppMessage { len } | Event::AppCall { len } => out.extend_from_slice(&len.to_le_bytes()),
+            Event::Attachment { state, public_ready, local_ready, peers } => {
+                out.push(*state);
+                out.push(u8::from(*public_ready) | (u8::from(*local_ready) << 1));
+                out.extend_from_slice(&peers.to_le_bytes());
+            }
+            Event::Network { started, bps_down, bps_up, peers } => {
+                out.push(u8::from(*started));
+                out.extend_from_slice(&bps_down.to_le_bytes());
+                out.extend_from_slice(&bps_up.to_le_bytes());
+                out.extend_from_slice(&peers.to_le_bytes());
+            }
+            Event::RouteChange { dead, dead_remote } => {
+                out.extend_from_slice(&dead.to_le_bytes());
+                out.extend_from_slice(&dead_remote.to_le_bytes());
+            }
+            Event::ValueChange { record, subkeys, count, value_len } => {
+                for n in [*record, *subkeys, *count, value_len.unwrap_or(u32::MAX)] {
+                    out.extend_from_slice(&n.to_le_bytes());
+                }
+            }
+            Event::Config | Event::Shutdown => {}
+        }
+    }
+}
+
+// Reads little endian numbers off the front of a record, None once it runs out.
+struct Fields<'a>(&'a [u8]);
+
+impl Fields<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[b]| b)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    // One update: (ms since the start, the event).
+    fn event(&mut self) -> Option<Result<(u32, Event), String>> {
+        let kind = self.u8()?;
+        let at = self.u32()?;
+        let event = match kind {
+            0 => Event::Log { level: self.u8()? },
+            1 => Event::AppMessage { len: self.u32()? },
+            2 => Event::AppCall { len: self.u32()? },
+            3 => {
+                let state = self.u8()?;
+                let ready = self.u8()?;
+                Event::Attachment {
+                    state,
+                    public_ready: ready & 1 != 0,
+                    local_ready: ready & 2 != 0,
+                    peers: self.u32()?,
+                }
+            }
+            4 => Event::Network {
+                started: self.u8()? != 0,
+                bps_down: self.u64()?,
+                bps_up: self.u64()?,
+                peers: self.u32()?,
+            },
+            5 => Event::Config,
+            6 => Event::RouteChange {
+                dead: self.u32()?,
+                dead_remote: self.u32()?,
+            },
+            7 => Event::ValueChange {
+                record: self.u32()?,
+                subkeys: self.u32()?,
+                count: self.u32()?,
+                value_len: Some(self.u32()?).filter(|len| *len != u32::MAX),
+            },
+            8 => Event::Shutdown,
+            other => return Some(Err(format!("unknown update kind {other}"))),
+        };
+        Some(Ok((at, event)))
+    }
+}
+
+// A recording read back.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Recording {
+    pub started_ms: u64,
+    pub events: Vec<(u32, Event)>,
+    // the last update was cut off (the recorder was killed mid-write)
+    pub truncated: bool,
+}
+
+pub fn header(started_ms: u64) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    out.extend_from_slice(&started_ms.to_le_bytes());
+    out
+}
+
+pub fn parse(data: &[u8]) -> Result<Recording, String> {
+    let Some(rest) = data.strip_prefix(&MAGIC) else {
+        return Err("not an event recording (no VXEV at the start)".to_string());
+    };
+    let mut fields = Fields(rest);
+    match fields.u8() {
+        Some(VERSION) => {}
+        Some(v) => return Err(format!("recording format {v}, this build reads {VERSION} (time to upgrade)")),
+        None => return Err("the recording is cut off in its header".to_string()),
+    }
+    let started_ms = fields.u64().ok_or("the recording is cut off in its header")?;
+    let mut events = Vec::new();
+    let mut truncated = false;
+    while !fields.0.is_empty() {
+        match fields.event() {
+            Some(Ok(event)) => events.push(event),
+            Some(Err(e)) => return Err(format!("update {} of the recording: {e}", events.len() + 1)),
+            None => {
+                truncated = true;
+                break;
+            }
+        }
+    }
+    Ok(Recording {
+        started_ms,
+        events,
+        truncated,
+    })
+}
+
+// Writes updates to the file as they come in.
+struct Recorder {
+    out: Mutex<BufWriter<File>>,
+    started_ms: u64,
+}
+
+impl Recorder {
+    fn create(path: &Path) -> std::io::Result<Recorder> {
+        let started_ms = now_ms() as u64;
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&header(started_ms))?;
+        out.flush()?;
+        Ok(Recorder {
+            out: Mutex::new(out),
+            started_ms,
+        })
+    }
+
+    fn keep(&self, update: &VeilidUpdate) {
+        let at = u32::try_from((now_ms() as u64).saturating_sub(self.started_ms)).unwrap_or(u32::MAX);
+        let mut bytes = Vec::new();
+        Event::from_update(update).encode(at, &mut bytes);
+        let mut out = self.out.lock().unwrap();
+        // a full disk shouldn't take the node down; the replay sees where it stopped
+        let _ = out.write_all(&bytes).and_then(|()| out.flush());
+    }
+}
+
+pub async fn record(
+    file: &Path,
+    watch: Option<&str>,
+    options: &Options,
+    config: &AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    let mut book = ShortcodeBook::load(&data_dir)?;
+    let record_key = watch
+        .map(|record| book.resolve(record).map_err(|e| Kind::RecordNotFound.fail(e)))
+        .transpose()?;
+
+    // recording starts before Veilid does, so startup and attaching are in it too
+    let recorder = Arc::new(Recorder::create(file)?);
+    let namespace = format!("{}-recorder", config.default_namespace);
+    let node = {
+        let recorder = recorder.clone();
+        let start = VeilidNode::start_attached(config, &data_dir, &namespace, move |update| recorder.keep(&update));
+        progress::spin("Starting the recorder node", start).await?
+    };
+
+    let changes = WatchSet::new();
+    let rc = Dht::new(node.routing_context().get(), None, false, options.chaos.clone());
+    let mut opened = None;
+    if let Some(record_key) = record_key {
+        let record_key = rc.open_following(record_key, None).await?.key();
+        let code = book.remember(&record_key)?;
+        match node.watch(&changes, record_key.clone(), ValueSubkeyRangeSet::full()).await {
+            Ok(()) => println!("Watching {code} (tagged {:08x} in the recording)", record_tag(&record_key)),
+            Err(e) => println!("Couldn't watch {code}, recording the rest anyway: {e}"),
+        }
+        opened = Some(record_key);
+    }
+    println!("Recording every update to {} (Ctrl+C to stop)", file.to_string_lossy());
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            // the changes are recorded as they come in; this only keeps the queue empty
+            change = changes.next() => if change.is_none() {
+                break;
+            },
+        }
+    }
+
+    if let Some(record_key) = opened {
+        let _ = rc.close_dht_record(record_key).await;
+    }
+    node.shutdown().await;
+    let size = fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+    println!("Stopped, {size} bytes recorded; `replay-events {}` to see them", file.to_string_lossy());
+    Ok(())
+}
+
+pub fn replay(file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let recording = parse(&fs::read(file)?).map_err(|e| format!("{}: {e}", file.to_string_lossy()))?;
+    println!("{}", analyze(&recording));
+    Ok(())
+}
+
+fn secs(ms: u64) -> String {
+    format!("{:.1}s", ms as f64 / 1000.0)
+}
+
+// The statistics for a recording, as a report.
+pub fn analyze(recording: &Recording) -> String {
+    let end = recording.events.last().map_or(0, |(at, _)| u64::from(*at));
+    let mut kinds: BTreeMap<&str, u64> = BTreeMap::new();
+    let mut in_state: BTreeMap<u8, u64> = BTreeMap::new();
+    let mut state: Option<(u8, u64)> = None;
+    let mut drops = 0;
+    let mut first_ready: Option<u64> = None;
+    let mut peers: Option<(u32, u32)> = None;
+    let (mut down, mut up, mut samples) = (0u64, 0u64, 0u64);
+    let (mut max_down, mut max_up) = (0u64, 0u64);
+    let mut changes: BTreeMap<u32, u64> = BTreeMap::new();
+    let mut last_change: Option<u64> = None;
+    let mut longest_silence = 0;
+    let mut levels: BTreeMap<u8, u64> = BTreeMap::new();
+    let (mut dead, mut dead_remote) = (0u64, 0u64);
+    let (mut messages, mut message_bytes) = (0u64, 0u64);
+
+    for (at, event) in &recording.events {
+        let at = u64::from(*at);
+        let kind = match event {
+            Event::Log { level } => {
+                *levels.entry(*level).or_default() += 1;
+                "log"
+            }
+            Event::AppMessage { len } | Event::AppCall { len } => {
+                messages += 1;
+                message_bytes += u64::from(*len);
+                "app message/call"
+            }
+            Event::Attachment { state: now, public_ready, .. } => {
+                if let Some((was, since)) = state {
+                    *in_state.entry(was).or_default() += at - since;
+                    // attached at any strength (weak..full), then not
+                    if (2..=6).contains(&was) && !(2..=6).contains(now) {
+                        drops += 1;
+                    }
+                }
+                state = Some((*now, at));
+                if *public_ready && first_ready.is_none() {
+                    first_ready = Some(at);
+                }
+                "attachment"
+            }
+            Event::Network { bps_down, bps_up, peers: n, .. } => {
+                peers = Some(peers.map_or((*n, *n), |(lo, hi)| (lo.min(*n), hi.max(*n))));
+                down += bps_down;
+                up += bps_up;
+                samples += 1;
+                max_down = max_down.max(*bps_down);
+                max_up = max_up.max(*bps_up);
+                "network"
+            }
+            Event::Config => "config",
+            Event::RouteChange { dead: d, dead_remote: r } => {
+                dead += u64::from(*d);
+                dead_remote += u64::from(*r);
+                "route change"
+            }
+            Event::ValueChange { record, .. } => {
+                *changes.entry(*record).or_default() += 1;
+                if let Some(last) = last_change {
+                    longest_silence = longest_silence.max(at - last);
+                }
+                last_change = Some(at);
+                "value change"
+            }
+            Event::Shutdown => "shutdown",
+        };
+        *kinds.entry(kind).or_default() += 1;
+    }
+    if let Some((was, since)) = state {
+        *in_state.entry(was).or_default() += end - since;
+    }
+
+    let mut out = vec![format!(
+        "{} updates over {}{}",
+        recording.events.len(),
+        secs(end),
+        if recording.truncated { " (the last one was cut off)" } else { "" }
+    )];
+    out.push(format!(
+        "  by kind: {}",
+        kinds.iter().map(|(kind, n)| format!("{kind} {n}")).collect::<Vec<_>>().join(", ")
+    ));
+    let state_name = |s: u8| ATTACHMENT_STATES.get(s as usize).copied().unwrap_or("unknown");
+    out.push(format!(
+        "attachment: {}; dropped {drops} time(s); public internet ready after {}",
+        in_state.iter().map(|(s, ms)| format!("{} {}", state_name(*s), secs(*ms))).collect::<Vec<_>>().join(", "),
+        first_ready.map_or("never".to_string(), secs)
+    ));
+    match peers {
+        Some((lo, hi)) => out.push(format!(
+            "network: {lo}..={hi} peers; down {}/s average, {}/s at most; up {}/s average, {}/s at most",
+            down / samples,
+            max_down,
+            up / samples,
+            max_up
+        )),
+        None => out.push("network: no network updates".to_string()),
+    }
+    let total: u64 = changes.values().sum();
+    out.push(format!(
+        "value changes: {total}{}",
+        if total > 1 { format!(", longest silence between two {}", secs(longest_silence)) } else { String::new() }
+    ));
+    for (record, n) in &changes {
+        out.push(format!("  record {record:08x}: {n}"));
+    }
+    out.push(format!(
+        "logs: {}",
+        if levels.is_empty() {
+            "none".to_string()
+        } else {
+            levels
+                .iter()
+                .map(|(l, n)| format!("{} {n}", LOG_LEVELS.get(usize::from(*l).wrapping_sub(1)).unwrap_or(&"?")))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    ));
+    out.push(format!("routes: {dead} of ours and {dead_remote} remote ones died"));
+    out.push(format!("app messages/calls: {messages}, {message_bytes} bytes"));
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_recording_reads_back_and_adds_up() {
+        let attached = |state: u8, public_ready: bool| Event::Attachment {
+            state,
+            public_ready,
+            local_ready: false,
+            peers: 5,
+        };
+        let change = Event::ValueChange {
+            record: 0xabcd,
+            subkeys: 1,
+            count: 7,
+            value_len: Some(12),
+        };
+        let events = vec![
+            (0, attached(1, false)),
+            (1_000, attached(4, true)),
+            (1_500, Event::Network { started: true, bps_down: 300, bps_up: 100, peers: 4 }),
+            (2_000, change.clone()),
+            (2_500, Event::Log { level: 2 }),
+            (
+                6_000,
+                Event::ValueChange {
+                    record: 0xabcd,
+                    subkeys: 1,
+                    count: 7,
+                    value_len: None,
+                },
+            ),
+            (7_000, attached(1, false)),
+            (8_000, attached(4, true)),
+        ];
+        let mut data = header(1_700_000_000_000);
+        for (at, event) in &events {
+            event.encode(*at, &mut data);
+        }
+        let whole = parse(&data).unwrap();
+        assert_eq!(whole.events, events);
+        assert!(!whole.truncated);
+
+        // killed half way through the last one
+        let cut = parse(&data[..data.len() - 3]).unwrap();
+        assert_eq!(cut.events.len(), events.len() - 1);
+        assert!(cut.truncated);
+
+        let report = analyze(&whole);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "8 updates over 8.0s");
+        assert_eq!(
+            lines[2],
+            "attachment: attaching 2.0s, good 6.0s; dropped 1 time(s); public internet ready after 1.0s"
+        );
+        assert_eq!(lines[3], "network: 4..=4 peers; down 300/s average, 300/s at most; up 100/s average, 100/s at most");
+        assert_eq!(lines[4], "value changes: 2, longest silence between two 4.0s");
+        assert_eq!(lines[5], "  record 0000abcd: 2");
+        assert_eq!(lines[6], "logs: warn 1");
+
+        assert!(parse(b"nope").is_err());
+    }
+}
@@ -0,0 +1,248 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use flume::{Receiver, Sender, TrySendError};
+use tokio::sync::Notify;
+use veilid_core::*;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Bounded queues between Veilid's update callback and whatever prints the
+//	updates, one per kind of event.
+//
+//	The callback runs on Veilid's own threads and must never block, and a
+//	burst of log or network updates (a flaky connection, --chaos reattach
+//	cycles) shouldn't pile up in memory or bury the prompt. So each class
+//	gets a queue of its own size:
+//
+//	  - noisy ones (Log, Network, Attachment, Route) drop the oldest entry
+//	    when full: only the latest state is worth showing
+//	  - the rest (values, messages, anything else) keep what's queued and
+//	    drop the newcomer
+//
+//	Every drop is counted, and `stats events` shows the counts. Things that
+//	must not be lost (readiness, profile cards, watch dispatch) are picked
+//	out in the callback before an update reaches these queues.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Class {
+    Log,
+    Network,
+    Attachment,
+    Route,
+    Value,
+    Message,
+    Other,
+}
+
+// In the order the printer empties them.
+const CLASSES: [Class; 7] = [
+    Class::Value,
+    Class::Message,
+    Class::Attachment,
+    Class::Route,
+    Class::Other,
+    Class::Network,
+    Class::Log,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Policy {
+    DropOldest,
+    DropNewest,
+}
+
+impl Class {
+    pub fn of(update: &VeilidUpdate) -> Class {
+        match update {
+            VeilidUpdate::Log(_) => Class::Log,
+            VeilidUpdate::Network(_) => Class::Network,
+            VeilidUpdate::Attachment(_) => Class::Attachment,
+            VeilidUpdate::RouteChange(_) => Class::Route,
+            VeilidUpdate::ValueChange(_) => Class::Value,
+            VeilidUpdate::AppMessage(_) | VeilidUpdate::AppCall(_) => Class::Message,
+            _ => Class::Other,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Class::Log => "log",
+            Class::Network => "network",
+            Class::Attachment => "attachment",
+            Class::Route => "route",
+            Class::Value => "value",
+            Class::Message => "message",
+            Class::Other => "other",
+        }
+    }
+
+    fn capacity(self) -> usize {
+        match self {
+            Class::Log => 256,
+            Class::Network | Class::Attachment => 16,
+            Class::Route => 64,
+            Class::Value => 1024,
+            Class::Message => 256,
+            Class::Other => 64,
+        }
+    }
+
+    fn policy(self) -> Policy {
+        match self {
+            Class::Log | Class::Network | Class::Attachment | Class::Route => Policy::DropOldest,
+            Class::Value | Class::Message | Class::Other => Policy::DropNewest,
+        }
+    }
+}
+
+struct Lane<T> {
+    class: Class,
+    tx: Sender<T>,
+    // the callback holds a receiver too, to throw out the oldest entry
+    rx: Receiver<T>,
+    dropped: AtomicU64,
+    high_water: AtomicUsize,
+}
+
+pub struct EventQueues<T = VeilidUpdate> {
+    lanes: Vec<Lane<T>>,
+    ready: Notify,
+}
+
+impl<T> EventQueues<T> {
+    pub fn new() -> EventQueues<T> {
+        EventQueues::with_capacity(Class::capacity)
+    }
+
+    fn with_capacity(capacity: impl Fn(Class) -> usize) -> EventQueues<T> {
+        let lanes = CLASSES
+            .iter()
+            .map(|&class| {
+                let (tx, rx) = flume::bounded(capacity(class));
+                Lane {
+                    class,
+                    tx,
+                    rx,
+                    dropped: AtomicU64::new(0),
+                    high_water: AtomicUsize::new(0),
+                }
+            })
+            .collect();
+        EventQueues {
+            lanes,
+            ready: Notify::new(),
+        }
+    }
+
+    fn lane(&self, class: Class) -> &Lane<T> {
+        self.lanes.iter().find(|l| l.class == class).expect("every class has a lane")
+    }
+
+    // Queue `event` without ever blocking; drops one if the lane is full.
+    pub fn push_as(&self, class: Class, event: T) {
+        let lane = self.lane(class);
+        let mut event = event;
+        loop {
+            match lane.tx.try_send(event) {
+                Ok(()) => break,
+                Err(TrySendError::Full(back)) if class.policy() == Policy::DropOldest => {
+                    if lane.rx.try_recv().is_ok() {
+                        lane.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    event = back;
+                }
+                Err(_) => {
+                    lane.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+        lane.high_water.fetch_max(lane.rx.len(), Ordering::Relaxed);
+        self.ready.notify_one();
+    }
+
+    // The next queued event, busiest-to-matter classes first.
+    pub async fn next(&self) -> T {
+        loop {
+            for lane in &self.lanes {
+                if let Ok(event) = lane.rx.try_recv() {
+                    return event;
+                }
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    // What `stats events` prints.
+    pub fn report(&self) -> String {
+        let mut out = format!("  {:<11} {:>7} {:>9} {:>9} {:>9}  when full", "class", "queued", "peak", "capacity", "dropped");
+        for lane in &self.lanes {
+            out.push_str(&format!(
+                "\n  {:<11} {:>7} {:>9} {:>9} {:>9}  {}",
+                lane.class.name(),
+                lane.rx.len(),
+                lane.high_water.load(Ordering::Relaxed),
+                lane.class.capacity(),
+                lane.dropped.load(Ordering::Relaxed),
+                match lane.class.policy() {
+                    Policy::DropOldest => "drop oldest",
+                    Policy::DropNewest => "drop newest",
+                }
+            ));
+        }
+        out
+    }
+}
+
+impl EventQueues<VeilidUpdate> {
+    pub fn push(&self, update: VeilidUpdate) {
+        self.push_as(Class::of(&update), update);
+    }
+}
+
+impl<T> Default for EventQueues<T> {
+    fn default() -> Self {
+        EventQueues::new()
+    }
+}
+
+// Hand every queued update to `handle`, one at a time, for as long as the node runs.
+pub fn spawn_printer(
+    queues: Arc<EventQueues>,
+    handle: impl Fn(VeilidUpdate) + Send + 'static,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            // awaited first, so `handle` isn't held across the await
+            let update = queues.next().await;
+            handle(update);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn noisy_lanes_keep_the_newest_and_the_rest_keep_the_oldest() {
+        let queues = EventQueues::<u32>::with_capacity(|_| 2);
+        for n in 1..=5 {
+            queues.push_as(Class::Log, n);
+            queues.push_as(Class::Value, 10 + n);
+        }
+        assert_eq!(queues.lane(Class::Log).dropped.load(Ordering::Relaxed), 3);
+        assert_eq!(queues.lane(Class::Value).dropped.load(Ordering::Relaxed), 3);
+
+        // values come out first, then the log lane's latest two
+        let mut out = Vec::new();
+        for _ in 0..4 {
+            out.push(queues.next().await);
+        }
+        assert_eq!(out, vec![11, 12, 4, 5]);
+        assert!(queues.report().contains("drop oldest"));
+    }
+}
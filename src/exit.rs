@@ -0,0 +1,112 @@
+use std::error::Error;
+use std::fmt;
+
+use veilid_core::VeilidAPIError;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Exit codes, the same for every subcommand, so a script or CI job
+//	wrapping the program can tell what kind of failure it was:
+//
+//	  0  it worked
+//	  1  any other failure
+//	  2  the command line was wrong
+//	  3  the configuration is broken (config.json, env vars, validation)
+//	  4  the node didn't attach in ready_timeout_secs (with ready_timeout_exit)
+//	  5  the record wasn't found: no owner_keys.txt, an unknown shortcode,
+//	     or the network doesn't have it
+//	  6  a credential was refused: bad keys in owner_keys.txt, a wrong
+//	     backup passphrase or protected store password
+//	  7  the network let us down (timeouts, no route, nothing to try again on)
+//
+//	Code that knows which of these it's failing with returns a Failure
+//	(Kind::X.fail(..)); Veilid's own errors are sorted by their variant,
+//	and anything else is 1. `--help` lists the codes.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub const HELP: &str = "Exit codes:
+  0  success                 4  attach timeout (ready_timeout_exit)
+  1  other failure           5  record not found
+  2  usage error             6  credential invalid
+  3  configuration error     7  network failure";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Usage,
+    Config,
+    AttachTimeout,
+    RecordNotFound,
+    Credential,
+    Network,
+}
+
+impl Kind {
+    pub fn code(self) -> i32 {
+        match self {
+            Kind::Usage => 2,
+            Kind::Config => 3,
+            Kind::AttachTimeout => 4,
+            Kind::RecordNotFound => 5,
+            Kind::Credential => 6,
+            Kind::Network => 7,
+        }
+    }
+
+    pub fn fail(self, message: impl Into<String>) -> Box<dyn Error> {
+        Box::new(Failure {
+            kind: self,
+            message: message.into(),
+        })
+    }
+}
+
+// An error that knows which exit code it should end the program with.
+#[derive(Debug)]
+pub struct Failure {
+    kind: Kind,
+    message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Failure {}
+
+// The exit code for an error the program is stopping with.
+pub fn code(error: &(dyn Error + 'static)) -> i32 {
+    if let Some(failure) = error.downcast_ref::<Failure>() {
+        return failure.kind.code();
+    }
+    if error.is::<crate::keyfile::KeyFileError>() {
+        return Kind::Credential.code();
+    }
+    match error.downcast_ref::<VeilidAPIError>() {
+        Some(VeilidAPIError::KeyNotFound { .. }) => Kind::RecordNotFound.code(),
+        Some(
+            VeilidAPIError::Timeout
+            | VeilidAPIError::TryAgain { .. }
+            | VeilidAPIError::NoConnection { .. }
+            | VeilidAPIError::InvalidTarget { .. },
+        ) => Kind::Network.code(),
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_and_veilid_errors_get_their_own_codes() {
+        assert_eq!(code(Kind::RecordNotFound.fail("no owner_keys.txt").as_ref()), 5);
+        assert_eq!(Kind::Credential.fail("wrong passphrase").to_string(), "wrong passphrase");
+        let timeout: Box<dyn Error> = VeilidAPIError::Timeout.into();
+        assert_eq!(code(timeout.as_ref()), 7);
+        let other: Box<dyn Error> = "something else".into();
+        assert_eq!(code(other.as_ref()), 1);
+    }
+}
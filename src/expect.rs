@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use veilid_core::*;
+
+use crate::dht::Dht;
+use crate::envelope;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`assert` and `wait-for`: checks on what the network holds, for the
+//	prompts, scenario files and CI smoke tests.
+//
+//	  assert 2 hello                               one fresh read of subkey 2
+//	                                               must show "hello"
+//	  wait-for 2 --equals hello --timeout 30s      read it again every half
+//	                                               second until it does
+//
+//	Both compare the value as `read` would print it (the text of the
+//	envelope), and both ask the network rather than the local copy, so
+//	they see another node's write once it has spread. wait-for polls instead
+//	of waiting on a watch: a watch can be late, missed or never placed, and
+//	a CI run that depends on one fails for reasons that have nothing to do
+//	with whether the value arrived.
+//
+//	At the prompts wait-for runs as its own task and the PASS/FAIL line
+//	comes back through `Waits`, so the node keeps handling peers, watches
+//	and typed commands while it polls.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// How long wait-for gives up after when no --timeout is given.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_EVERY: Duration = Duration::from_millis(500);
+
+// "500ms", "30s" or "2m".
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let (number, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit())?);
+    let n: u64 = number.parse().ok()?;
+    match unit {
+        "ms" => Some(Duration::from_millis(n)),
+        "s" => Some(Duration::from_secs(n)),
+        "m" => Some(Duration::from_secs(n * 60)),
+        _ => None,
+    }
+}
+
+// The subkey as the network has it now, shown as text, or None if nobody
+// has written it.
+async fn fresh(rc: &Dht, key: &RecordKey, subkey: ValueSubkey) -> Result<Option<String>, String> {
+    Ok(rc
+        .get_dht_value(key.clone(), subkey, true)
+        .await
+        .map_err(|e| format!("couldn't read subkey {subkey}: {e}"))?
+        .map(|v| envelope::display_value(v.data())))
+}
+
+fn mismatch(subkey: ValueSubkey, found: &Option<String>) -> String {
+    match found {
+        Some(found) => format!("subkey {subkey} is \"{found}\""),
+        None => format!("subkey {subkey} has no value"),
+    }
+}
+
+pub async fn assert_value(rc: &Dht, key: &RecordKey, subkey: ValueSubkey, expected: &str) -> Result<(), String> {
+    let found = fresh(rc, key, subkey).await?;
+    if found.as_deref() == Some(expected) {
+        Ok(())
+    } else {
+        Err(mismatch(subkey, &found))
+    }
+}
+
+// Ok carries how long it took. Read errors along the way are retried like
+// a wrong value, since a node still finding its peers fails some reads.
+pub async fn wait_for(
+    rc: &Dht,
+    key: &RecordKey,
+    subkey: ValueSubkey,
+    expected: &str,
+    timeout: Duration,
+) -> Result<Duration, String> {
+    let started = tokio::time::Instant::now();
+    loop {
+        let last = match fresh(rc, key, subkey).await {
+            Ok(Some(found)) if found == expected => return Ok(started.elapsed()),
+            Ok(found) => mismatch(subkey, &found),
+            Err(e) => e,
+        };
+        if started.elapsed() + POLL_EVERY > timeout {
+            return Err(format!("gave up after {}s: {last}", timeout.as_secs_f32()));
+        }
+        tokio::time::sleep(POLL_EVERY).await;
+    }
+}
+
+// "<subkey> <text>"
+pub fn parse_assert(args: &str) -> Result<(ValueSubkey, String), String> {
+    let (subkey, text) = args.trim().split_once(' ').ok_or("usage: assert <subkey> <text>")?;
+    let subkey = subkey.parse().map_err(|_| format!("'{subkey}' isn't a subkey number"))?;
+    Ok((subkey, text.trim().to_string()))
+}
+
+// "<subkey> --equals <text> [--timeout DUR]"; the text may have spaces in it.
+pub fn parse_wait_for(args: &str) -> Result<(ValueSubkey, String, Duration), String> {
+    const USAGE: &str = "usage: wait-for <subkey> --equals <text> [--timeout 30s]";
+    let (subkey, rest) = args.trim().split_once(' ').ok_or(USAGE)?;
+    let subkey = subkey.parse().map_err(|_| format!("'{subkey}' isn't a subkey number"))?;
+    let rest = rest.trim().strip_prefix("--equals ").ok_or(USAGE)?;
+    let (text, timeout) = match rest.rsplit_once(" --timeout ") {
+        Some((text, timeout)) => (
+            text,
+            parse_duration(timeout.trim()).ok_or_else(|| format!("'{}' isn't a duration like 500ms, 30s or 2m", timeout.trim()))?,
+        ),
+        None => (rest, DEFAULT_TIMEOUT),
+    };
+    if text.trim().is_empty() {
+        return Err(USAGE.to_string());
+    }
+    Ok((subkey, text.trim().to_string(), timeout))
+}
+
+// Where the prompts' wait-for tasks report back; a node keeps one and
+// prints what arrives on `results()`.
+pub struct Waits {
+    tx: flume::Sender<String>,
+    rx: flume::Receiver<String>,
+}
+
+impl Default for Waits {
+    fn default() -> Waits {
+        let (tx, rx) = flume::unbounded();
+        Waits { tx, rx }
+    }
+}
+
+impl Waits {
+    pub fn results(&self) -> &flume::Receiver<String> {
+        &self.rx
+    }
+}
+
+// The two as prompt commands on our record; None if `line` is neither.
+// wait-for answers at once with what it's waiting on, and its outcome
+// arrives on `waits` later.
+pub async fn command(rc: &Dht, key: &RecordKey, line: &str, waits: &Waits) -> Option<String> {
+    if let Some(args) = line.strip_prefix("assert ") {
+        let outcome = match parse_assert(args) {
+            Ok((subkey, text)) => match assert_value(rc, key, subkey, &text).await {
+                Ok(()) => format!("PASS: subkey {subkey} is \"{text}\""),
+                Err(e) => format!("FAIL: {e}"),
+            },
+            Err(e) => e,
+        };
+        return Some(outcome);
+    }
+    if let Some(args) = line.strip_prefix("wait-for ") {
+        let (subkey, text, timeout) = match parse_wait_for(args) {
+            Ok(parsed) => parsed,
+            Err(e) => return Some(e),
+        };
+        let waiting = format!("Waiting up to {}s for subkey {subkey} to be \"{text}\"...", timeout.as_secs_f32());
+        let (rc, key, tx) = (rc.clone(), key.clone(), waits.tx.clone());
+        tokio::spawn(async move {
+            let outcome = match wait_for(&rc, &key, subkey, &text, timeout).await {
+                Ok(took) => format!("PASS: subkey {subkey} is \"{text}\" (after {:.1}s)", took.as_secs_f32()),
+                Err(e) => format!("FAIL: {e}"),
+            };
+            let _ = tx.send(outcome);
+        });
+        return Some(waiting);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assert_and_wait_for_arguments_parse() {
+        assert_eq!(parse_assert("2 hello world"), Ok((2, "hello world".to_string())));
+        assert!(parse_assert("2").is_err());
+        assert!(parse_assert("two hello").unwrap_err().contains("subkey number"));
+
+        assert_eq!(
+            parse_wait_for("2 --equals hello world --timeout 500ms"),
+            Ok((2, "hello world".to_string(), Duration::from_millis(500)))
+        );
+        assert_eq!(parse_wait_for("3 --equals ok"), Ok((3, "ok".to_string(), DEFAULT_TIMEOUT)));
+        // only the last --timeout counts, so the text can mention one
+        assert_eq!(
+            parse_wait_for("3 --equals use --timeout 5s --timeout 1m"),
+            Ok((3, "use --timeout 5s".to_string(), Duration::from_secs(60)))
+        );
+        assert!(parse_wait_for("3 hello").unwrap_err().starts_with("usage"));
+        assert!(parse_wait_for("3 --equals hi --timeout soon").unwrap_err().contains("duration"));
+
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("2h"), None);
+    }
+}
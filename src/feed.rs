@@ -0,0 +1,265 @@
+use std::collections::VecDeque;
+
+use veilid_core::*;
+
+use crate::nicknames::Nicknames;
+use crate::shortcode::{self, ShortcodeBook};
+use crate::watch::DecodedChange;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The feed: every change from every watched record, as one timeline.
+//
+//	The alt node hands each watch change to Feed::record. The last FEED_KEEP
+//	of them are kept, so `feed` can start by showing what already happened,
+//	oldest first by when the writer wrote it (changes don't always arrive in
+//	that order). After that, new changes are printed as feed lines as they
+//	come in, until `feed off`.
+//
+//	  feed                                  everything
+//	  feed --record harp-otter-coal-lime    one record (shortcode or key)
+//	  feed --member alice                   one writer (nickname or key)
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const FEED_KEEP: usize = 200;
+
+#[derive(Clone, Debug)]
+pub struct FeedEntry {
+    // when the writer wrote it, or when it got here if the value doesn't say
+    pub at_ms: u64,
+    pub record: RecordKey,
+    pub subkey: Option<ValueSubkey>,
+    // full key, and what to call it
+    pub writer: Option<(String, String)>,
+    pub text: String,
+}
+
+impl FeedEntry {
+    pub fn line(&self) -> String {
+        let subkey = match self.subkey {
+            Some(subkey) => format!(" #{subkey}"),
+            None => String::new(),
+        };
+        let who = match &self.writer {
+            Some((_, label)) => format!("{label}: "),
+            None => String::new(),
+        };
+        format!(
+            "[feed {} {}{subkey}] {who}{}",
+            clock(self.at_ms),
+            shortcode::shortcode(&self.record),
+            self.text
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FeedFilter {
+    pub record: Option<RecordKey>,
+    pub member: Option<String>,
+}
+
+impl FeedFilter {
+    // The arguments after `feed`: [--record X] [--member NAME], either as
+    // `--flag value` or `--flag=value`.
+    pub fn parse(args: &str, book: &ShortcodeBook) -> Result<FeedFilter, String> {
+        let mut filter = FeedFilter::default();
+        let mut words = args.split_whitespace();
+        while let Some(word) = words.next() {
+            let (flag, inline) = match word.split_once('=') {
+                Some((flag, value)) => (flag, Some(value)),
+                None => (word, None),
+            };
+            let mut value = || {
+                inline
+                    .or_else(|| words.next())
+                    .ok_or_else(|| format!("{flag} needs a value"))
+            };
+            match flag {
+                "--record" => filter.record = Some(book.resolve(value()?)?),
+                "--member" => filter.member = Some(value()?.to_string()),
+                other => return Err(format!("Unknown feed option '{other}' (try --record or --member)")),
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn matches(&self, entry: &FeedEntry) -> bool {
+        if self.record.as_ref().is_some_and(|r| *r != entry.record) {
+            return false;
+        }
+        match (&self.member, &entry.writer) {
+            (None, _) => true,
+            (Some(member), Some((key, label))) => label.eq_ignore_ascii_case(member) || key.starts_with(member.as_str()),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Feed {
+    recent: VecDeque<FeedEntry>,
+    // Some while the feed is being shown
+    filter: Option<FeedFilter>,
+}
+
+impl Feed {
+    pub fn new() -> Feed {
+        Feed::default()
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    // Keep a change. Gives back the line to print if the feed is on and the
+    // change gets through its filter.
+    pub fn record(&mut self, change: &DecodedChange, names: &Nicknames, now_ms: u64) -> Option<String> {
+        let entry = entry_for(change, names, now_ms);
+        if self.recent.len() == FEED_KEEP {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(entry.clone());
+        self.filter
+            .as_ref()
+            .filter(|f| f.matches(&entry))
+            .map(|_| entry.line())
+    }
+
+    // Turn the feed on. Gives back what's already been seen that matches, oldest first.
+    pub fn start(&mut self, filter: FeedFilter) -> Vec<String> {
+        let mut seen: Vec<&FeedEntry> = self.recent.iter().filter(|e| filter.matches(e)).collect();
+        // stable, so entries written in the same millisecond keep their arrival order
+        seen.sort_by_key(|e| e.at_ms);
+        let lines = seen.into_iter().map(FeedEntry::line).collect();
+        self.filter = Some(filter);
+        lines
+    }
+
+    pub fn stop(&mut self) {
+        self.filter = None;
+    }
+}
+
+fn entry_for(change: &DecodedChange, names: &Nicknames, now_ms: u64) -> FeedEntry {
+    let mut entry = FeedEntry {
+        at_ms: now_ms,
+        record: change.record.clone(),
+        subkey: None,
+        writer: None,
+        text: if change.watch_died {
+            "<the watch died>".to_string()
+        } else {
+            format!("<subkeys {} changed>", change.subkeys)
+        },
+    };
+    if let Some(value) = &change.value {
+        if let Ok(env) = &value.envelope {
+            entry.at_ms = env.written_ms.unwrap_or(now_ms);
+        }
+        entry.subkey = Some(value.subkey);
+        entry.writer = Some((value.writer.to_string(), names.label(&value.writer)));
+        entry.text = value.display();
+    }
+    entry
+}
+
+// "14:03:27" (UTC)
+fn clock(ms: u64) -> String {
+    let secs = ms / 1000 % 86_400;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::Envelope;
+    use crate::watch::ChangedValue;
+
+    fn key(byte: u8) -> RecordKey {
+        RecordKey::new(
+            CRYPTO_KIND_VLD0,
+            BareRecordKey::new(BareOpaqueRecordKey::new(&[byte; 32]), None),
+        )
+    }
+
+    fn change(record: &RecordKey, writer: &PublicKey, text: &str, written_ms: u64) -> DecodedChange {
+        let mut envelope = Envelope::text(text);
+        envelope.written_ms = Some(written_ms);
+        DecodedChange {
+            record: record.clone(),
+            subkeys: ValueSubkeyRangeSet::single(1),
+            value: Some(ChangedValue {
+                subkey: 1,
+                seq: ValueSeqNum::from(1),
+                writer: writer.clone(),
+                envelope: Ok(envelope),
+            }),
+            watch_died: false,
+        }
+    }
+
+    fn no_names() -> Nicknames {
+        // nothing there, so nobody has a nickname yet
+        Nicknames::load(&std::env::temp_dir().join(format!("feed-test-{}", std::process::id()))).unwrap()
+    }
+
+    #[test]
+    fn backlog_comes_out_in_writing_order() {
+        let names = no_names();
+        let alice = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap().key();
+        let mut feed = Feed::new();
+        assert_eq!(feed.record(&change(&key(1), &alice, "second", 2_000), &names, 9_000), None);
+        feed.record(&change(&key(2), &alice, "first", 1_000), &names, 9_000);
+
+        let lines = feed.start(FeedFilter::default());
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("first"), "{}", lines[0]);
+        assert!(lines[1].ends_with("second"), "{}", lines[1]);
+        assert!(feed.is_on());
+
+        let live = feed.record(&change(&key(1), &alice, "third", 3_000), &names, 9_000).unwrap();
+        assert!(live.starts_with("[feed 00:00:03 "), "{live}");
+        feed.stop();
+        assert_eq!(feed.record(&change(&key(1), &alice, "fourth", 4_000), &names, 9_000), None);
+    }
+
+    #[test]
+    fn filters_by_record_and_member() {
+        let names = no_names();
+        let alice = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap().key();
+        let bob = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap().key();
+        let mut feed = Feed::new();
+        feed.record(&change(&key(1), &alice, "a1", 1), &names, 0);
+        feed.record(&change(&key(2), &alice, "a2", 2), &names, 0);
+        feed.record(&change(&key(1), &bob, "b1", 3), &names, 0);
+
+        let by_record = FeedFilter {
+            record: Some(key(1)),
+            member: None,
+        };
+        assert_eq!(feed.start(by_record).len(), 2);
+
+        let by_member = FeedFilter {
+            record: Some(key(1)),
+            member: Some(alice.to_string()),
+        };
+        let lines = feed.start(by_member);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with("a1"));
+    }
+
+    #[test]
+    fn parses_options() {
+        let book = ShortcodeBook::load(&std::env::temp_dir().join(format!("feed-book-{}", std::process::id()))).unwrap();
+        let code = shortcode::shortcode(&key(3));
+        let filter = FeedFilter::parse(&format!("--record {} --member=alice", key(3)), &book).unwrap();
+        assert_eq!(filter.record, Some(key(3)));
+        assert_eq!(filter.member.as_deref(), Some("alice"));
+        assert!(FeedFilter::parse("--member", &book).is_err());
+        assert!(FeedFilter::parse("--colour red", &book).is_err());
+        // a shortcode we've never seen can't be looked up
+        assert!(FeedFilter::parse(&format!("--record {code}"), &book).is_err());
+    }
+}
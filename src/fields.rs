@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+
+use veilid_core::*;
+
+use crate::dht::Dht;
+use crate::envelope::Envelope;
+use crate::metadata::{self, RecordMetadata};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Shared fields: one logical value (say "status") that every writer keeps
+//	its own copy of, in a subkey of its own range, instead of all of them
+//	taking turns on one shared subkey and the last write winning.
+//
+//	Where each writer keeps each field is in the metadata block's roster
+//	(RosterEntry::fields), so readers don't have to know the layout. Every
+//	writer in the roster gets shared_fields at the end of its range, last
+//	subkey first, skipping the subkey its plain writes go to (layout();
+//	the default node's are AppConfig::field_subkeys).
+//
+//	`field <name> <text>` sets the copy of whichever writer the prompt
+//	writes as; the alt and member prompts look theirs up in the roster.
+//	`merge <field>` reads every writer's copy and shows them side by side,
+//	newest first, with when each was written.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// Where a writer with the subkeys first..=last keeps each field. Ones that
+// don't fit are left out (validate() reports them for the default node).
+pub fn layout(names: &[String], first: ValueSubkey, last: ValueSubkey, skip: Option<ValueSubkey>) -> BTreeMap<String, ValueSubkey> {
+    names
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| {
+            let subkey = last.checked_sub(i as u32)?;
+            (subkey >= first && Some(subkey) != skip).then(|| (name.clone(), subkey))
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FieldVersion {
+    // the roster name
+    pub writer: String,
+    pub subkey: ValueSubkey,
+    pub written_ms: Option<u64>,
+    // None if the writer hasn't set it (or it expired)
+    pub text: Option<String>,
+}
+
+async fn read_metadata(rc: &Dht, record: &RecordKey) -> Result<RecordMetadata, String> {
+    rc.get_dht_value(record.clone(), metadata::METADATA_SUBKEY, true)
+        .await
+        .map_err(|e| format!("couldn't read the metadata block: {e}"))?
+        .and_then(|v| RecordMetadata::decode(v.data()))
+        .ok_or_else(|| "the record has no metadata block, so nobody has said where their fields are".to_string())
+}
+
+// Where the first of `writers` that keeps `field` keeps it, as the roster says.
+pub async fn own_subkey<'a>(
+    rc: &Dht,
+    record: &RecordKey,
+    writers: &[&'a KeyPair],
+    field: &str,
+) -> Result<(ValueSubkey, &'a KeyPair), String> {
+    let meta = read_metadata(rc, record).await?;
+    writers
+        .iter()
+        .find_map(|writer| {
+            let key = writer.key().to_string();
+            let entry = meta.members.iter().find(|entry| entry.key == key)?;
+            entry.fields.get(field).map(|&subkey| (subkey, *writer))
+        })
+        .ok_or_else(|| format!("the roster has no '{field}' field for the keys we write with"))
+}
+
+// Every writer's copy of `field`, newest first, unwritten ones last.
+pub async fn merge_read(rc: &Dht, record: &RecordKey, field: &str) -> Result<Vec<FieldVersion>, String> {
+    let meta = read_metadata(rc, record).await?;
+
+    let mut versions = Vec::new();
+    for entry in &meta.members {
+        let Some(&subkey) = entry.fields.get(field) else {
+            continue;
+        };
+        let value = rc
+            .get_dht_value(record.clone(), subkey, true)
+            .await
+            .map_err(|e| format!("couldn't read {}'s {field} (subkey {subkey}): {e}", entry.name))?;
+        let env = value
+            .and_then(|v| Envelope::decode(v.data()).ok())
+            .filter(|env| env.is_live(crate::audit::now_ms() as u64));
+        versions.push(FieldVersion {
+            writer: entry.name.clone(),
+            subkey,
+            written_ms: env.as_ref().and_then(|env| env.written_ms),
+            text: env.map(|env| env.display()),
+        });
+    }
+    if versions.is_empty() {
+        return Err(format!("no writer keeps a '{field}' field on this record"));
+    }
+    sort(&mut versions);
+    Ok(versions)
+}
+
+fn sort(versions: &mut [FieldVersion]) {
+    versions.sort_by(|a, b| {
+        (b.text.is_some(), b.written_ms).cmp(&(a.text.is_some(), a.written_ms))
+    });
+}
+
+pub fn render(field: &str, versions: &[FieldVersion]) -> String {
+    let now = crate::audit::now_ms() as u64;
+    let mut out = format!("{field}, as each writer has it:");
+    for v in versions {
+        let when = match v.written_ms {
+            Some(ms) => ago(now.saturating_sub(ms)),
+            None => "-".to_string(),
+        };
+        let text = match &v.text {
+            Some(text) => format!("\"{text}\""),
+            None => "(not set)".to_string(),
+        };
+        out.push_str(&format!("\n  {:<16} {:>10}  {text}  (subkey {})", v.writer, when, v.subkey));
+    }
+    out
+}
+
+fn ago(ms: u64) -> String {
+    match ms / 1000 {
+        s @ 0..=59 => format!("{s}s ago"),
+        s @ 60..=3599 => format!("{}m ago", s / 60),
+        s @ 3600..=86_399 => format!("{}h ago", s / 3600),
+        s => format!("{}d ago", s / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryDht;
+    use crate::metadata::RosterEntry;
+    use std::sync::Arc;
+
+    fn entry(name: &str, first: ValueSubkey, status: Option<ValueSubkey>) -> RosterEntry {
+        RosterEntry {
+            name: name.to_string(),
+            key: format!("VLD0:{name}"),
+            first_subkey: first,
+            last_subkey: first + 1,
+            fields: status.map(|s| BTreeMap::from([("status".to_string(), s)])).unwrap_or_default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn every_writers_copy_newest_first() {
+        let rc = Dht::with_backend(Arc::new(MemoryDht::new()), None, false, None);
+        let schema = DHTSchema::dflt(6).unwrap();
+        let record = rc.create_dht_record(CRYPTO_KIND_VLD0, schema.clone(), None).await.unwrap().key();
+        let roster = vec![
+            entry("alice", 0, Some(1)),
+            entry("bob", 2, Some(3)),
+            entry("carol", 4, Some(5)),
+            entry("inbox", 4, None),
+        ];
+        let meta = RecordMetadata::new("t", &schema, roster);
+        rc.set_dht_value(record.clone(), 0, meta.encode(), None).await.unwrap();
+
+        let mut older = Envelope::text("busy");
+        older.written_ms = Some(1_000);
+        rc.set_dht_value(record.clone(), 1, older.encode(), None).await.unwrap();
+        rc.set_dht_value(record.clone(), 3, Envelope::text("free").encode(), None).await.unwrap();
+
+        let versions = merge_read(&rc, &record, "status").await.unwrap();
+        let order: Vec<(&str, Option<&str>)> = versions.iter().map(|v| (v.writer.as_str(), v.text.as_deref())).collect();
+        assert_eq!(order, vec![("bob", Some("free")), ("alice", Some("busy")), ("carol", None)]);
+        assert!(render("status", &versions).contains("(not set)"));
+
+        assert!(merge_read(&rc, &record, "mood").await.is_err());
+    }
+
+    #[test]
+    fn fields_fill_a_range_from_the_end() {
+        let names: Vec<String> = ["status", "mood", "away"].iter().map(|s| s.to_string()).collect();
+        let laid = layout(&names, 4, 6, Some(5));
+        assert_eq!(laid, BTreeMap::from([("status".to_string(), 6), ("away".to_string(), 4)]));
+        // no room at all
+        assert!(layout(&names, 4, 4, Some(4)).is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinSet;
+use veilid_core::*;
+
+use crate::dht::Dht;
+use crate::envelope::Envelope;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`flood <subkey> <count>` (default node prompt): how fast can one subkey
+//	really be updated?
+//
+//	Writes "flood 1/N" .. "flood N/N" to the subkey, keeping up to
+//	FLOOD_WINDOW writes in flight instead of waiting for each one to be
+//	confirmed before starting the next. Afterwards the subkey is read back to
+//	see how far its seq moved: ideally by exactly N, with "flood N/N" on top.
+//	Writes that overlap can land out of order or overwrite each other, which
+//	is what the seq check is there to show.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const FLOOD_WINDOW: usize = 8;
+
+pub struct FloodReport {
+    pub subkey: ValueSubkey,
+    pub count: u32,
+    pub accepted: u32,
+    // the network already had something newer than what we sent
+    pub superseded: u32,
+    pub failed: u32,
+    pub elapsed: Duration,
+    pub seq_before: Option<u32>,
+    pub seq_after: Option<u32>,
+    // the body that ended up on top
+    pub last_value: Option<String>,
+}
+
+pub async fn run(
+    rc: &Dht,
+    record_key: &RecordKey,
+    subkey: ValueSubkey,
+    count: u32,
+    writer: Option<KeyPair>,
+) -> FloodReport {
+    let seq_of = |v: Option<ValueData>| v.and_then(|v| v.seq().to_option());
+    let seq_before = seq_of(rc.get_dht_value(record_key.clone(), subkey, true).await.ok().flatten());
+
+    let mut report = FloodReport {
+        subkey,
+        count,
+        accepted: 0,
+        superseded: 0,
+        failed: 0,
+        elapsed: Duration::ZERO,
+        seq_before,
+        seq_after: None,
+        last_value: None,
+    };
+
+    let start = Instant::now();
+    let mut in_flight = JoinSet::new();
+    for i in 1..=count {
+        // wait for a slot before starting the next write
+        if in_flight.len() >= FLOOD_WINDOW {
+            if let Some(done) = in_flight.join_next().await {
+                report.tally(done);
+            }
+        }
+        let rc = rc.clone();
+        let record_key = record_key.clone();
+        let options = SetDHTValueOptions {
+            writer: writer.clone(),
+            allow_offline: None,
+        };
+        let data = Envelope::text(&format!("flood {i}/{count}")).encode();
+        in_flight.spawn(async move { rc.set_dht_value(record_key, subkey, data, Some(options)).await });
+    }
+    while let Some(done) = in_flight.join_next().await {
+        report.tally(done);
+    }
+    report.elapsed = start.elapsed();
+
+    if let Ok(Some(value)) = rc.get_dht_value(record_key.clone(), subkey, true).await {
+        report.seq_after = value.seq().to_option();
+        report.last_value = Some(crate::envelope::display_value(value.data()));
+    }
+    report
+}
+
+impl FloodReport {
+    fn tally(
+        &mut self,
+        done: Result<VeilidAPIResult<Option<ValueData>>, tokio::task::JoinError>,
+    ) {
+        match done {
+            Ok(Ok(None)) => self.accepted += 1,
+            Ok(Ok(Some(_))) => self.superseded += 1,
+            Ok(Err(_)) | Err(_) => self.failed += 1,
+        }
+    }
+
+    pub fn writes_per_sec(&self) -> f64 {
+        f64::from(self.accepted) / self.elapsed.as_secs_f64().max(0.001)
+    }
+
+    // How far the seq moved, if we could see it both times (a fresh subkey starts at 0).
+    pub fn seq_advance(&self) -> Option<u32> {
+        let after = self.seq_after?;
+        Some(match self.seq_before {
+            Some(before) => after.saturating_sub(before),
+            None => after + 1,
+        })
+    }
+}
+
+impl fmt::Display for FloodReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Flood of subkey {} ({} writes, {} in flight):", self.subkey, self.count, FLOOD_WINDOW)?;
+        writeln!(
+            f,
+            "  accepted {}, superseded {}, failed {} in {:.1}s ({:.1} writes/sec)",
+            self.accepted,
+            self.superseded,
+            self.failed,
+            self.elapsed.as_secs_f64(),
+            self.writes_per_sec()
+        )?;
+        let show = |seq: Option<u32>| seq.map_or("none".to_string(), |s| s.to_string());
+        match self.seq_advance() {
+            Some(advance) => {
+                let verdict = if advance == self.count { "continuous" } else { "gaps or merged writes" };
+                writeln!(
+                    f,
+                    "  seq {} -> {}: advanced by {advance} of {} ({verdict})",
+                    show(self.seq_before),
+                    show(self.seq_after),
+                    self.count
+                )?;
+            }
+            None => writeln!(f, "  seq could not be read back")?,
+        }
+        write!(
+            f,
+            "  on top now: {}",
+            self.last_value.as_deref().unwrap_or("<nothing>")
+        )
+    }
+}
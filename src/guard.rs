@@ -0,0 +1,131 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use veilid_core::*;
+
+use crate::i18n::t;
+use crate::shortcode;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Guarded writes to shared records.
+//
+//	A record joined by a share code is usually somebody else's demo, and a
+//	`write` at the alt prompt lands in it for everyone watching. So before
+//	writing to a record this data folder didn't make, the prompt says which
+//	record and what's about to happen to it (which subkey, how many bytes)
+//	and waits for a y (n drops it). --yes skips the question, for scripts.
+//
+//	Every path that writes to a record asks through a WriteGuard: text and
+//	`field` at the alt prompt, `lock`/`unlock`, `publish` schedules (once,
+//	when one is set up), `record load` (once, before the first row) and
+//	the member node (once, before its prompt).
+//
+//	The records the default node made here are listed in
+//	created_records.json in the data folder; everything else counts as
+//	someone else's.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const FILE_NAME: &str = "created_records.json";
+
+pub struct CreatedRecords {
+    path: PathBuf,
+    keys: BTreeSet<String>,
+}
+
+impl CreatedRecords {
+    pub fn load(data_dir: &Path) -> io::Result<CreatedRecords> {
+        let path = data_dir.join(FILE_NAME);
+        let keys = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            BTreeSet::new()
+        };
+        Ok(CreatedRecords { path, keys })
+    }
+
+    // Note a record we made (saved straight away).
+    pub fn add(&mut self, record_key: &RecordKey) -> io::Result<()> {
+        if self.keys.insert(record_key.to_string()) {
+            fs::write(&self.path, serde_json::to_string_pretty(&self.keys)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, record_key: &RecordKey) -> bool {
+        self.keys.contains(&record_key.to_string())
+    }
+}
+
+// Whether writes to one record need a y first.
+pub struct WriteGuard {
+    // the record's share code, while it's someone else's and --yes wasn't given
+    shared: Option<String>,
+}
+
+impl WriteGuard {
+    pub fn new(data_dir: &Path, record_key: &RecordKey, yes: bool) -> io::Result<WriteGuard> {
+        let shared = !yes && !CreatedRecords::load(data_dir)?.contains(record_key);
+        Ok(WriteGuard {
+            shared: shared.then(|| shortcode::shortcode(record_key)),
+        })
+    }
+
+    // The question to put before `what` (one of the shared-write-* messages)
+    // is done, or None if it can go ahead: the record is ours, or the y
+    // has been given.
+    pub fn question(&self, confirmed: bool, what: &str) -> Option<String> {
+        let code = self.shared.as_ref().filter(|_| !confirmed)?;
+        Some(t!("confirm-shared-write", code = code.as_str(), what = what))
+    }
+
+    // For the tools without a prompt to hold the write at: ask on the terminal.
+    pub fn confirm(&self, what: &str) -> io::Result<bool> {
+        let Some(question) = self.question(false, what) else {
+            return Ok(true);
+        };
+        print!("{question} ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        Ok(answer.trim() == "y")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_records_made_here_count_as_ours() {
+        let dir = std::env::temp_dir().join(format!("guard-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let key = |byte: u8| {
+            RecordKey::new(
+                CRYPTO_KIND_VLD0,
+                BareRecordKey::new(BareOpaqueRecordKey::new(&[byte; 32]), None),
+            )
+        };
+
+        let mut created = CreatedRecords::load(&dir).unwrap();
+        assert!(!created.contains(&key(1)));
+        created.add(&key(1)).unwrap();
+        created.add(&key(1)).unwrap();
+
+        // another console sharing the data folder sees it too
+        let again = CreatedRecords::load(&dir).unwrap();
+        assert!(again.contains(&key(1)));
+        assert!(!again.contains(&key(2)));
+
+        // only someone else's record asks, and only until it's answered
+        assert_eq!(WriteGuard::new(&dir, &key(1), false).unwrap().question(false, "write"), None);
+        let theirs = WriteGuard::new(&dir, &key(2), false).unwrap();
+        assert!(theirs.question(false, "write").is_some_and(|q| q.contains(&shortcode::shortcode(&key(2)))));
+        assert_eq!(theirs.question(true, "write"), None);
+        assert_eq!(WriteGuard::new(&dir, &key(2), true).unwrap().question(false, "write"), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	--health-addr ADDR: a tiny HTTP endpoint for Docker/Kubernetes checks.
+//
+//	  GET /livez    200 while the main loop is still turning over
+//	                (restart the container if this fails)
+//	  GET /readyz   200 once attached, with the record open and a DHT call
+//	                succeeding recently (stop sending work if this fails)
+//	  GET /healthz  the details as JSON, 200 if ready, 503 if not
+//
+//	Only the soak and daemon modes run long enough to be worth checking.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// No successful DHT call for this long and we stop claiming to be ready.
+const STALE_DHT_OP: Duration = Duration::from_secs(5 * 60);
+// The main loop wakes at least every 30s; this long without a beat means it's stuck.
+const STALE_BEAT: Duration = Duration::from_secs(2 * 60);
+
+const NEVER: u64 = u64::MAX;
+
+pub struct Health {
+    started: Instant,
+    attached: AtomicBool,
+    record_open: AtomicBool,
+    // ms since `started`, or NEVER
+    last_dht_ok_ms: AtomicU64,
+    last_beat_ms: AtomicU64,
+}
+
+impl Default for Health {
+    fn default() -> Health {
+        Health::new()
+    }
+}
+
+impl Health {
+    pub fn new() -> Health {
+        Health {
+            started: Instant::now(),
+            attached: AtomicBool::new(false),
+            record_open: AtomicBool::new(false),
+            last_dht_ok_ms: AtomicU64::new(NEVER),
+            last_beat_ms: AtomicU64::new(0),
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    pub fn set_attached(&self, ready: bool) {
+        self.attached.store(ready, Ordering::Relaxed);
+    }
+
+    pub fn set_record_open(&self, open: bool) {
+        self.record_open.store(open, Ordering::Relaxed);
+    }
+
+    // Called by the Dht wrapper after every call that worked.
+    pub fn dht_op_ok(&self) {
+        self.last_dht_ok_ms.store(self.now_ms(), Ordering::Relaxed);
+    }
+
+    // Called by the main loop each time around.
+    pub fn beat(&self) {
+        self.last_beat_ms.store(self.now_ms(), Ordering::Relaxed);
+    }
+
+    fn age(&self, at_ms: u64) -> Option<Duration> {
+        (at_ms != NEVER).then(|| Duration::from_millis(self.now_ms().saturating_sub(at_ms)))
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.age(self.last_beat_ms.load(Ordering::Relaxed))
+            .is_some_and(|age| age < STALE_BEAT)
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.attached.load(Ordering::Relaxed)
+            && self.record_open.load(Ordering::Relaxed)
+            && self
+                .age(self.last_dht_ok_ms.load(Ordering::Relaxed))
+                .is_some_and(|age| age < STALE_DHT_OP)
+    }
+
+    pub fn report(&self) -> serde_json::Value {
+        json!({
+            "live": self.is_live(),
+            "ready": self.is_ready(),
+            "attached": self.attached.load(Ordering::Relaxed),
+            "record_open": self.record_open.load(Ordering::Relaxed),
+            "last_dht_op_age_secs": self
+                .age(self.last_dht_ok_ms.load(Ordering::Relaxed))
+                .map(|a| a.as_secs()),
+            "uptime_secs": self.started.elapsed().as_secs(),
+        })
+    }
+}
+
+// Bind the endpoint (so a bad address is reported straight away) and serve it in the background.
+pub async fn serve(addr: SocketAddr, health: Arc<Health>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    println!("Health endpoint listening on http://{}/healthz", listener.local_addr()?);
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let health = health.clone();
+            tokio::spawn(async move {
+                let _ = answer(stream, &health).await;
+            });
+        }
+    });
+    Ok(())
+}
+
+async fn answer(mut stream: TcpStream, health: &Health) -> std::io::Result<()> {
+    // The request line is all we need, so one read is plenty.
+    let mut buf = [0u8; 1024];
+    let n = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+        .await
+        .map_err(|_| std::io::ErrorKind::TimedOut)??;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("GET "))
+        .and_then(|rest| rest.split_whitespace().next());
+
+    let (ok, body) = match path {
+        Some("/livez") => (health.is_live(), json!({ "live": health.is_live() })),
+        Some("/readyz") => (health.is_ready(), json!({ "ready": health.is_ready() })),
+        Some("/healthz") => (health.is_ready(), health.report()),
+        _ => {
+            return respond(&mut stream, "404 Not Found", "{\"error\":\"not found\"}").await;
+        }
+    };
+    let status = if ok { "200 OK" } else { "503 Service Unavailable" };
+    respond(&mut stream, status, &body.to_string()).await
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
@@ -0,0 +1,177 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+
+use veilid_core::*;
+
+use crate::audit::now_ms;
+use crate::shortcode::shortcode;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Which subkeys are busy: `stats heatmap` at either prompt.
+//
+//	The Dht wrapper counts every read and write it makes against the
+//	record and subkey, and the alt node counts every change its watches
+//	deliver. Each subkey's counts go in one-minute buckets, and the heatmap
+//	is a row per subkey and a column per minute, darker the busier:
+//
+//	  record brisk-otter-lamp, a column a minute, newest on the right
+//	  subkey |                              | reads writes changes
+//	       0 |.                             |     1      0       0
+//	       3 |        ..::-=+**#%%@@@@@@@@@@|     4      0     212
+//	  hottest: subkey 3, 211 in the last 30 minutes
+//
+//	A watch that never stops firing, or a member flooding the record, is
+//	the row that stays dark. Only this node's own traffic is in it, and
+//	the columns are scaled to the busiest cell of that record.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const BUCKET_MS: u64 = 60_000;
+// how many minutes the heatmap goes back
+const COLUMNS: u64 = 30;
+// from quiet to the busiest cell
+const SHADES: &[char] = &['.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Change,
+}
+
+#[derive(Default)]
+struct SubkeyHeat {
+    reads: u64,
+    writes: u64,
+    changes: u64,
+    // (minute, how many that minute), oldest first, no further back than COLUMNS
+    recent: VecDeque<(u64, u64)>,
+}
+
+impl SubkeyHeat {
+    fn in_window(&self, now_bucket: u64) -> impl Iterator<Item = &(u64, u64)> {
+        self.recent.iter().filter(move |(bucket, _)| bucket + COLUMNS > now_bucket)
+    }
+}
+
+#[derive(Default)]
+pub struct Heatmap {
+    records: Mutex<HashMap<RecordKey, BTreeMap<ValueSubkey, SubkeyHeat>>>,
+}
+
+impl Heatmap {
+    pub fn new() -> Heatmap {
+        Heatmap::default()
+    }
+
+    pub fn seen(&self, record: &RecordKey, subkey: ValueSubkey, access: Access) {
+        self.seen_at(record, subkey, access, now_ms() as u64);
+    }
+
+    // Call this from the update callback for every VeilidUpdate::ValueChange.
+    pub fn changed(&self, change: &VeilidValueChange) {
+        for subkey in change.subkeys.iter() {
+            self.seen(&change.key, subkey, Access::Change);
+        }
+    }
+
+    fn seen_at(&self, record: &RecordKey, subkey: ValueSubkey, access: Access, now: u64) {
+        let mut records = self.records.lock().unwrap();
+        let heat = records.entry(record.clone()).or_default().entry(subkey).or_default();
+        match access {
+            Access::Read => heat.reads += 1,
+            Access::Write => heat.writes += 1,
+            Access::Change => heat.changes += 1,
+        }
+        let bucket = now / BUCKET_MS;
+        match heat.recent.back_mut() {
+            Some((last, count)) if *last == bucket => *count += 1,
+            _ => heat.recent.push_back((bucket, 1)),
+        }
+        while heat.recent.front().is_some_and(|(oldest, _)| oldest + COLUMNS <= bucket) {
+            heat.recent.pop_front();
+        }
+    }
+
+    pub fn report(&self) -> String {
+        self.render(now_ms() as u64)
+    }
+
+    fn render(&self, now: u64) -> String {
+        let records = self.records.lock().unwrap();
+        if records.is_empty() {
+            return "No DHT reads, writes or changes yet".to_string();
+        }
+        let now_bucket = now / BUCKET_MS;
+        let mut out = Vec::new();
+        let mut ordered: Vec<_> = records.iter().collect();
+        ordered.sort_by_key(|(key, _)| shortcode(key));
+        for (key, subkeys) in ordered {
+            let busiest = subkeys
+                .values()
+                .flat_map(|heat| heat.in_window(now_bucket).map(|(_, count)| *count))
+                .max()
+                .unwrap_or(0);
+            out.push(format!("record {}, a column a minute, newest on the right", shortcode(key)));
+            out.push(format!("subkey |{}| reads writes changes", " ".repeat(COLUMNS as usize)));
+            for (subkey, heat) in subkeys {
+                let mut row = vec![' '; COLUMNS as usize];
+                for (bucket, count) in heat.in_window(now_bucket) {
+                    let column = (COLUMNS - 1 - (now_bucket - bucket)) as usize;
+                    let shade = (count * SHADES.len() as u64).div_ceil(busiest).max(1) as usize;
+                    row[column] = SHADES[shade - 1];
+                }
+                out.push(format!(
+                    "{subkey:>6} |{}| {:>5} {:>6} {:>7}",
+                    row.iter().collect::<String>(),
+                    heat.reads,
+                    heat.writes,
+                    heat.changes
+                ));
+            }
+            let hottest = subkeys
+                .iter()
+                .map(|(subkey, heat)| (subkey, heat.in_window(now_bucket).map(|(_, count)| count).sum::<u64>()))
+                .max_by_key(|(_, total)| *total);
+            match hottest {
+                Some((subkey, total)) if total > 0 => {
+                    out.push(format!("hottest: subkey {subkey}, {total} in the last {COLUMNS} minutes"))
+                }
+                _ => out.push(format!("nothing in the last {COLUMNS} minutes")),
+            }
+            out.push(String::new());
+        }
+        out.pop();
+        out.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_busiest_subkey_is_darkest_in_its_minute() {
+        let key = RecordKey::new(
+            CRYPTO_KIND_VLD0,
+            BareRecordKey::new(BareOpaqueRecordKey::new(&[7; 32]), None),
+        );
+        let heatmap = Heatmap::new();
+        let start = 100 * BUCKET_MS;
+        heatmap.seen_at(&key, 0, Access::Read, start);
+        for _ in 0..8 {
+            heatmap.seen_at(&key, 3, Access::Change, start + BUCKET_MS);
+        }
+        heatmap.seen_at(&key, 3, Access::Write, start + BUCKET_MS);
+
+        let report = heatmap.render(start + BUCKET_MS);
+        let rows: Vec<&str> = report.lines().collect();
+        assert_eq!(rows[2], format!("     0 |{}. |     1      0       0", " ".repeat(28)));
+        assert_eq!(rows[3], format!("     3 |{}@|     0      1       8", " ".repeat(29)));
+        assert_eq!(rows[4], "hottest: subkey 3, 9 in the last 30 minutes");
+
+        // half an hour on, it has all scrolled off
+        assert!(heatmap.render(start + 31 * BUCKET_MS).ends_with("nothing in the last 30 minutes"));
+    }
+}
@@ -0,0 +1,169 @@
+use std::sync::OnceLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	What the interactive nodes print, in the language picked with --lang
+//	(or the terminal's LANG, or English), for workshops where English
+//	isn't everyone's first language.
+//
+//	The messages are Fluent files under locales/, one per language, built
+//	into the binary. Code asks for one by its id, with any values it needs:
+//
+//	  say!("{}", t!("wrote-subkey", subkey = subkey));
+//
+//	A message a translation doesn't have yet comes out in English, so a
+//	new message only has to be added to en.ftl to work. To add a language,
+//	copy en.ftl to locales/<code>.ftl, translate the text after each `=`
+//	(leaving the ids and $variables alone) and add it to LANGS.
+//
+//	The prompts' own messages are in here, and the ones the member node,
+//	`stats watch` and `stats bandwidth`, contacts, conflicts and the relay
+//	put together. The other reports the prompts print (events, queue,
+//	heatmap, trend, quota, locks, publish, logs, chat, mail, set-option)
+//	and the one-shot subcommands are still English.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const ENGLISH: &str = include_str!("../locales/en.ftl");
+
+// Each --lang there is, with its messages.
+pub const LANGS: &[(&str, &str)] = &[("en", ENGLISH), ("es", include_str!("../locales/es.ftl"))];
+
+type Bundle = FluentBundle<FluentResource>;
+
+struct Messages {
+    // None for English
+    chosen: Option<Bundle>,
+    english: Bundle,
+}
+
+static MESSAGES: OnceLock<Messages> = OnceLock::new();
+
+// t!("id") or t!("id", name = value, ...): the message, as a String
+macro_rules! t {
+    ($id:literal) => {
+        $crate::i18n::message($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = ::fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::message($id, Some(&args))
+    }};
+}
+
+fn bundle(lang: &str, source: &str) -> Bundle {
+    let id: LanguageIdentifier = lang.parse().expect("LANGS only has valid language codes");
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // the Unicode isolation marks Fluent puts around values show up as junk in some terminals
+    bundle.set_use_isolating(false);
+    // the tests make sure the files parse; anything that doesn't falls back to English
+    let resource = FluentResource::try_new(source.to_string()).unwrap_or_else(|(resource, _)| resource);
+    bundle.add_resource_overriding(resource);
+    bundle
+}
+
+// "es_ES.UTF-8" -> "es"
+fn language(locale: &str) -> &str {
+    locale.split(['_', '-', '.', '@']).next().unwrap_or_default()
+}
+
+// Picks the language, once, before anything is printed. `lang` is --lang,
+// which cli.rs has checked is one of LANGS.
+pub fn init(lang: Option<&str>) {
+    let from_env = || {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+    };
+    let wanted = lang.map(str::to_string).or_else(from_env).unwrap_or_default();
+    let chosen = LANGS
+        .iter()
+        .find(|(code, _)| *code != "en" && *code == language(&wanted))
+        .map(|(code, source)| bundle(code, source));
+    let _ = MESSAGES.set(Messages {
+        chosen,
+        english: bundle("en", ENGLISH),
+    });
+}
+
+pub fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    let messages = MESSAGES.get_or_init(|| Messages {
+        chosen: None,
+        english: bundle("en", ENGLISH),
+    });
+    messages
+        .chosen
+        .iter()
+        .chain([&messages.english])
+        .find_map(|bundle| {
+            let pattern = bundle.get_message(id)?.value()?;
+            Some(bundle.format_pattern(pattern, args, &mut Vec::new()).into_owned())
+        })
+        // a typo'd id shows as itself rather than as nothing
+        .unwrap_or_else(|| id.to_string())
+}
+
+pub(crate) use t;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the ids of the messages in an .ftl file
+    fn ids(source: &str) -> Vec<&str> {
+        source
+            .lines()
+            .filter_map(|line| line.split_once(" ="))
+            .map(|(id, _)| id)
+            .filter(|id| id.starts_with(|c: char| c.is_ascii_lowercase()) && !id.contains(' '))
+            .collect()
+    }
+
+    #[test]
+    fn every_language_has_every_message_and_the_code_asks_for_known_ones() {
+        let english = ids(ENGLISH);
+        for (code, source) in LANGS {
+            assert!(FluentResource::try_new(source.to_string()).is_ok(), "{code}.ftl doesn't parse");
+            assert_eq!(ids(source), english, "{code}.ftl doesn't have the same messages as en.ftl");
+        }
+        let sources = [
+            ("app.rs", include_str!("app.rs")),
+            ("member.rs", include_str!("member.rs")),
+            ("stats.rs", include_str!("stats.rs")),
+            ("contacts.rs", include_str!("contacts.rs")),
+            ("conflict.rs", include_str!("conflict.rs")),
+            ("relay.rs", include_str!("relay.rs")),
+            ("guard.rs", include_str!("guard.rs")),
+            ("load.rs", include_str!("load.rs")),
+        ];
+        for (file, source) in sources {
+            // only t!( on its own, not the end of format!( or print!(
+            let calls = source.match_indices("t!(\"").filter(|&(at, _)| {
+                !source[..at].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+            });
+            for (at, call) in calls {
+                let id = source[at + call.len()..].split('"').next().unwrap();
+                assert!(english.contains(&id), "{file} asks for '{id}', which en.ftl doesn't have");
+            }
+        }
+    }
+
+    #[test]
+    fn values_and_plurals_are_filled_in() {
+        let es = bundle("es", LANGS[1].1);
+        let mut args = FluentArgs::new();
+        args.set("tag", "[mail]");
+        args.set("count", 1);
+        let pattern = es.get_message("new-letters").unwrap().value().unwrap();
+        assert_eq!(
+            es.format_pattern(pattern, Some(&args), &mut Vec::new()),
+            "[mail] 1 carta nueva, escribe 'inbox' para leerlas"
+        );
+        assert_eq!(language("es_ES.UTF-8"), "es");
+        assert_eq!(t!("wrote-subkey", subkey = 3), "Wrote subkey 3");
+    }
+}
@@ -0,0 +1,224 @@
+use veilid_core::ValueSubkey;
+
+use crate::repl::{Repl, ReplLine};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Where the node loops get their commands from.
+//
+//	Both prompts used to read a line from the Repl, sort out Ctrl+C and
+//	EOF, trim it, skip it if blank and then try it against each command's
+//	prefix in turn, the same way twice. Now they ask Inputs for the next
+//	Input, which is already a Command, and only the commands one prompt
+//	has to itself are left to each loop (as Command::Other).
+//
+//	The terminal is the only frontend so far. Anything else that wants to
+//	drive a node (a control socket, an HTTP bridge, a GUI) should come in
+//	here next to the Repl and hand over lines the same way, so the loops
+//	don't need to know where a command came from.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub enum Input {
+    Command(Command),
+    // Ctrl+C at the prompt
+    Interrupted,
+    // Ctrl+D, or stdin closed
+    Closed,
+}
+
+// The commands both prompts understand; see commands.rs for what they do.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    // `help`, or `help <topic>` (the topic keeps its leading space)
+    Help(String),
+    // `contact ...`; `contacts` is `contact list`
+    Contact(String),
+    // `logs follow|stop <contact>`
+    Logs(String),
+    // `relay [on|off|join|leave ...]`, see relay.rs
+    Relay(String),
+    // `stats <what>`
+    Stats(String),
+    Quota,
+    // `merge <field>`
+    Merge(String),
+    // `field <name> <text>`, see fields.rs
+    Field(String),
+    DiagBundle,
+    // `lock [<subkey> [secs]]` and `unlock <subkey>`
+    Lock(String),
+    Unlock(String),
+    // `publish ...`, see publish.rs
+    Publish(String),
+    // `set-option [<name> <value>]`, see prefs.rs
+    SetOption(String),
+    // `:msg <text>`, see chat.rs
+    Msg(String),
+    // anything else, trimmed, for the prompt's own commands (or, at the
+    // default prompt, text to write)
+    Other(String),
+}
+
+// The words after `command`, if the line is that command (so `helpful` isn't `help`).
+fn words_after<'a>(line: &'a str, command: &str) -> Option<&'a str> {
+    line.strip_prefix(command).filter(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+impl Command {
+    // None for a blank line.
+    pub fn parse(line: &str) -> Option<Command> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        let command = if let Some(topic) = words_after(line, "help") {
+            Command::Help(topic.to_string())
+        } else if line == "contacts" {
+            Command::Contact("list".to_string())
+        } else if let Some(args) = words_after(line, "contact") {
+            Command::Contact(args.to_string())
+        } else if let Some(args) = line.strip_prefix("logs ") {
+            Command::Logs(args.to_string())
+        } else if let Some(args) = words_after(line, "relay") {
+            Command::Relay(args.trim().to_string())
+        } else if let Some(what) = line.strip_prefix("stats ") {
+            Command::Stats(what.trim().to_string())
+        } else if line == "quota" {
+            Command::Quota
+        } else if let Some(name) = line.strip_prefix("merge ") {
+            Command::Merge(name.trim().to_string())
+        } else if let Some(args) = line.strip_prefix("field ") {
+            Command::Field(args.trim().to_string())
+        } else if let Some(args) = words_after(line, "lock") {
+            Command::Lock(args.trim().to_string())
+        } else if let Some(args) = words_after(line, "unlock") {
+            Command::Unlock(args.trim().to_string())
+        } else if let Some(args) = words_after(line, "publish") {
+            Command::Publish(args.trim().to_string())
+        } else if let Some(args) = words_after(line, "set-option") {
+            Command::SetOption(args.trim().to_string())
+        } else if let Some(text) = words_after(line, ":msg") {
+            Command::Msg(text.trim().to_string())
+        } else if line == "diag bundle" {
+            Command::DiagBundle
+        } else {
+            Command::Other(line.to_string())
+        };
+        Some(command)
+    }
+}
+
+// Where a line of text at the default prompt goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Destination<'a> {
+    // `:sub N`: plain text goes to subkey N from now on
+    Switch(ValueSubkey),
+    // `N:text`: this line goes to subkey N
+    One(ValueSubkey, &'a str),
+    // `:all text`: to every subkey we can write
+    All(&'a str),
+    // anything else, to the subkey plain text goes to
+    Current(&'a str),
+}
+
+impl Destination<'_> {
+    pub fn parse(line: &str) -> Result<Destination<'_>, String> {
+        if let Some(rest) = words_after(line, ":sub") {
+            return rest
+                .trim()
+                .parse()
+                .map(Destination::Switch)
+                .map_err(|_| "Usage: :sub <subkey>".to_string());
+        }
+        if let Some(rest) = words_after(line, ":all") {
+            return match rest.trim() {
+                "" => Err("Usage: :all <text>".to_string()),
+                text => Ok(Destination::All(text)),
+            };
+        }
+        match line.split_once(':') {
+            Some((n, text)) if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => {
+                let subkey = n.parse().map_err(|_| format!("there's no subkey {n}"))?;
+                match text.trim() {
+                    "" => Err(format!("Usage: {n}:<text>")),
+                    text => Ok(Destination::One(subkey, text)),
+                }
+            }
+            _ => Ok(Destination::Current(line)),
+        }
+    }
+}
+
+pub struct Inputs {
+    repl: Repl,
+}
+
+impl Inputs {
+    pub fn new(repl: Repl) -> Inputs {
+        Inputs { repl }
+    }
+
+    pub fn repl(&self) -> &Repl {
+        &self.repl
+    }
+
+    // The next command from any frontend. Safe in a tokio::select!, like
+    // Repl::next_line (a half-typed line isn't lost).
+    pub async fn next(&mut self) -> Input {
+        loop {
+            match self.repl.next_line().await {
+                ReplLine::Line(line) => {
+                    if let Some(command) = Command::parse(&line) {
+                        crate::transcript::command(self.repl.prompt(), line.trim());
+                        return Input::Command(command);
+                    }
+                }
+                ReplLine::Interrupted => return Input::Interrupted,
+                ReplLine::Eof => return Input::Closed,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_commands_parse_the_same_for_both_prompts() {
+        assert_eq!(Command::parse("   "), None);
+        assert_eq!(Command::parse(" help "), Some(Command::Help(String::new())));
+        assert_eq!(Command::parse("help watch"), Some(Command::Help(" watch".to_string())));
+        assert_eq!(Command::parse("helpful text"), Some(Command::Other("helpful text".to_string())));
+        assert_eq!(Command::parse("contacts"), Some(Command::Contact("list".to_string())));
+        assert_eq!(Command::parse("contact add bob"), Some(Command::Contact(" add bob".to_string())));
+        assert_eq!(Command::parse("logs follow alt-node"), Some(Command::Logs("follow alt-node".to_string())));
+        assert_eq!(Command::parse("relay join  alt-node"), Some(Command::Relay("join  alt-node".to_string())));
+        assert_eq!(Command::parse("relay"), Some(Command::Relay(String::new())));
+        assert_eq!(Command::parse("stats  queue"), Some(Command::Stats("queue".to_string())));
+        assert_eq!(Command::parse("quota"), Some(Command::Quota));
+        assert_eq!(Command::parse("merge status"), Some(Command::Merge("status".to_string())));
+        assert_eq!(Command::parse("field status  back at 3"), Some(Command::Field("status  back at 3".to_string())));
+        assert_eq!(Command::parse("diag bundle"), Some(Command::DiagBundle));
+        assert_eq!(Command::parse("lock 2 300"), Some(Command::Lock("2 300".to_string())));
+        assert_eq!(Command::parse("unlock  2"), Some(Command::Unlock("2".to_string())));
+        assert_eq!(Command::parse("publish"), Some(Command::Publish(String::new())));
+        assert_eq!(Command::parse("set-option confirm on"), Some(Command::SetOption("confirm on".to_string())));
+        assert_eq!(Command::parse(":msg  hi there"), Some(Command::Msg("hi there".to_string())));
+        assert_eq!(Command::parse(":msgs"), Some(Command::Other(":msgs".to_string())));
+        assert_eq!(Command::parse("watch harp-otter"), Some(Command::Other("watch harp-otter".to_string())));
+    }
+
+    #[test]
+    fn text_can_name_its_subkeys() {
+        assert_eq!(Destination::parse(":sub 3"), Ok(Destination::Switch(3)));
+        assert!(Destination::parse(":sub three").is_err());
+        assert_eq!(Destination::parse("0:hello there"), Ok(Destination::One(0, "hello there")));
+        assert!(Destination::parse("4:").is_err());
+        assert_eq!(Destination::parse(":all  hi all"), Ok(Destination::All("hi all")));
+        assert!(Destination::parse(":all").is_err());
+        assert_eq!(Destination::parse("note: 3:00"), Ok(Destination::Current("note: 3:00")));
+        assert_eq!(Destination::parse(":subway"), Ok(Destination::Current(":subway")));
+    }
+}
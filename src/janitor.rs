@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use veilid_core::*;
+
+use crate::audit::now_ms;
+use crate::dht::Dht;
+use crate::envelope::Envelope;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The janitor: tidies up expired values on the subkeys this node writes.
+//
+//	Readers already ignore a value once its expiry has passed, but the old
+//	bytes stay on the DHT until someone overwrites them. Only a writer can do
+//	that, so a node that writes expiring values runs this in the background:
+//	every JANITOR_INTERVAL it looks at its own subkeys and replaces anything
+//	expired with a tombstone.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const JANITOR_INTERVAL: Duration = Duration::from_secs(30);
+
+// Watch `subkeys` of `record_key`, writing tombstones as `writer` (None = the record owner).
+pub fn spawn(
+    rc: Dht,
+    record_key: RecordKey,
+    subkeys: Vec<ValueSubkey>,
+    writer: Option<KeyPair>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(JANITOR_INTERVAL).await;
+            for &subkey in &subkeys {
+                sweep(&rc, &record_key, subkey, writer.clone()).await;
+            }
+        }
+    })
+}
+
+async fn sweep(rc: &Dht, record_key: &RecordKey, subkey: ValueSubkey, writer: Option<KeyPair>) {
+    // our own last write is in local storage, no need to ask the network
+    let Ok(Some(value)) = rc.get_dht_value(record_key.clone(), subkey, false).await else {
+        return;
+    };
+    let Ok(env) = Envelope::decode(value.data()) else {
+        return;
+    };
+    if env.is_tombstone() || !env.is_expired(now_ms() as u64) {
+        return;
+    }
+
+    let options = SetDHTValueOptions {
+        writer,
+        allow_offline: None,
+    };
+    match rc
+        .set_dht_value(record_key.clone(), subkey, Envelope::tombstone().encode(), Some(options))
+        .await
+    {
+        Ok(_) => println!("[janitor] subkey {subkey} expired, tombstone written"),
+        Err(e) => println!("[janitor] subkey {subkey} expired, tombstone failed: {e}"),
+    }
+}
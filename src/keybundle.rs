@@ -0,0 +1,312 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::audit::now_ms;
+use crate::exit::Kind;
+use crate::keyfile::{self, Capability, Expected, Grant, JoinPreset, KeyFile, KeyFileError, Role};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	owner_keys.json: the key file as JSON, with a format version, when it
+//	was made and the record's schema. The default node writes this one now;
+//	owner_keys.txt (keyfile.rs) is still read, so files handed out by older
+//	builds keep working.
+//
+//	  {
+//	    "version": 1,
+//	    "created_ms": 1718000000000,
+//	    "record_key": "VLD0:<base64 key>",
+//	    "shortcode": "word-word-word-word",
+//	    "schema": { "kind": "SMPL", "o_cnt": 2, "members": [...] },
+//	    "grant": { "claimed": ["read", "write-own-subkeys"], "writer": "VLD0:<keypair>",
+//	               "writer_subkeys": [2, 3], "owner": null },
+//	    "join": { "namespace": "veilid-example-ver2", "subkeys": [0, 3], "encryption": "none" },
+//	    "expect": { "role": "member", "writer_subkeys": [2, 3],
+//	                "fingerprint": "<64 hex digits>" }
+//	  }
+//
+//	The fields mean what the txt lines of the same name do. Everything but
+//	version and record_key may be left out.
+//
+//	Which format a file is in is decided by what's in it ('{' first means
+//	JSON), not its name, and which one gets written by the name: a path
+//	ending in .txt gets the old format, for whoever still reads it.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub const FILE_NAME: &str = "owner_keys.json";
+// bumped when a field changes meaning; older builds refuse newer files
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct KeyBundle {
+    pub version: u32,
+    #[serde(default)]
+    pub created_ms: u64,
+    pub record_key: RecordKey,
+    #[serde(default)]
+    pub shortcode: Option<String>,
+    // the record's schema, as it was created
+    #[serde(default)]
+    pub schema: Option<DHTSchema>,
+    #[serde(default)]
+    pub grant: GrantSection,
+    #[serde(default)]
+    pub join: JoinSection,
+    #[serde(default)]
+    pub expect: ExpectSection,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct GrantSection {
+    #[serde(default)]
+    pub claimed: Vec<String>,
+    #[serde(default)]
+    pub writer: Option<KeyPair>,
+    #[serde(default)]
+    pub writer_subkeys: Option<(ValueSubkey, ValueSubkey)>,
+    // the record owner's keypair, for admin
+    #[serde(default)]
+    pub owner: Option<KeyPair>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct JoinSection {
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub subkeys: Option<(ValueSubkey, ValueSubkey)>,
+    #[serde(default)]
+    pub encryption: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ExpectSection {
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub writer_subkeys: Option<(ValueSubkey, ValueSubkey)>,
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+// Only the version, to refuse a newer file before anything else in it can fail.
+#[derive(Deserialize)]
+struct Versioned {
+    version: Option<u32>,
+}
+
+fn check_range(name: &str, range: Option<(ValueSubkey, ValueSubkey)>) -> Result<(), String> {
+    match range {
+        Some((first, last)) if first > last => Err(format!("{name}: {first} comes after {last}")),
+        _ => Ok(()),
+    }
+}
+
+impl KeyBundle {
+    pub fn new(keys: &KeyFile, schema: Option<&DHTSchema>) -> KeyBundle {
+        KeyBundle {
+            version: VERSION,
+            created_ms: now_ms() as u64,
+            record_key: keys.record_key.clone(),
+            shortcode: keys.shortcode.clone(),
+            schema: schema.cloned(),
+            grant: GrantSection {
+                claimed: keys.grant.claimed.iter().map(|c| c.name().to_string()).collect(),
+                writer: keys.grant.writer.clone(),
+                writer_subkeys: keys.grant.writer_subkeys,
+                owner: keys.grant.owner.clone(),
+            },
+            join: JoinSection {
+                namespace: keys.alt.namespace.clone(),
+                subkeys: keys.alt.subkeys,
+                encryption: keys.alt.encryption.clone(),
+            },
+            expect: ExpectSection {
+                role: keys.expect.role.map(|r| r.name().to_string()),
+                writer_subkeys: keys.expect.writer_subkeys,
+                fingerprint: keys.expect.fingerprint.clone(),
+            },
+        }
+    }
+
+    // Checked the way keyfile::parse checks the txt lines.
+    pub fn keys(&self) -> Result<KeyFile, String> {
+        if self.record_key.kind() != CRYPTO_KIND_VLD0 {
+            return Err(format!(
+                "record_key uses crypto kind '{}', this example only knows {CRYPTO_KIND_VLD0}",
+                self.record_key.kind()
+            ));
+        }
+        // an empty or cut-off key decodes without complaint
+        let len = self.record_key.ref_value().ref_key().len();
+        if len != 32 {
+            return Err(format!("record_key is {len} bytes, expected 32"));
+        }
+        let mut claimed = Vec::new();
+        for name in &self.grant.claimed {
+            let cap = Capability::from_name(name)
+                .ok_or_else(|| format!("grant.claimed: '{name}' isn't read, write-own-subkeys or admin"))?;
+            if !claimed.contains(&cap) {
+                claimed.push(cap);
+            }
+        }
+        let role = self
+            .expect
+            .role
+            .as_deref()
+            .map(|name| Role::from_name(name).ok_or_else(|| format!("expect.role: '{name}' isn't reader, member or owner")))
+            .transpose()?;
+        check_range("grant.writer_subkeys", self.grant.writer_subkeys)?;
+        check_range("join.subkeys", self.join.subkeys)?;
+        check_range("expect.writer_subkeys", self.expect.writer_subkeys)?;
+        if let Some(ns) = &self.join.namespace {
+            if ns.is_empty() || !ns.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(format!("join.namespace: '{ns}' has characters that aren't allowed"));
+            }
+        }
+        Ok(KeyFile {
+            record_key: self.record_key.clone(),
+            shortcode: self.shortcode.clone(),
+            alt: JoinPreset {
+                namespace: self.join.namespace.clone(),
+                subkeys: self.join.subkeys,
+                encryption: self.join.encryption.clone(),
+            },
+            grant: Grant {
+                claimed,
+                writer: self.grant.writer.clone(),
+                writer_subkeys: self.grant.writer_subkeys,
+                owner: self.grant.owner.clone(),
+            },
+            expect: Expected {
+                role,
+                writer_subkeys: self.expect.writer_subkeys,
+                fingerprint: self.expect.fingerprint.clone(),
+            },
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn parse(text: &str) -> Result<KeyBundle, String> {
+        let versioned: Versioned = serde_json::from_str(text).map_err(|e| format!("not a key bundle ({e})"))?;
+        match versioned.version {
+            None => return Err("no \"version\", so not a key bundle".to_string()),
+            Some(v) if v > VERSION => {
+                return Err(format!("key bundle version {v}, this build reads up to {VERSION} (time to upgrade)"))
+            }
+            Some(_) => {}
+        }
+        serde_json::from_str(text).map_err(|e| format!("bad key bundle: {e}"))
+    }
+}
+
+// Where the data folder's key file is: whichever of owner_keys.json and
+// owner_keys.txt was written last, owner_keys.json if neither is there yet.
+pub fn default_path(data_dir: &Path) -> PathBuf {
+    let json = data_dir.join(FILE_NAME);
+    let txt = data_dir.join(keyfile::FILE_NAME);
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(&json), modified(&txt)) {
+        (Some(j), Some(t)) if t > j => txt,
+        (None, Some(_)) => txt,
+        _ => json,
+    }
+}
+
+pub fn load(data_dir: &Path) -> Result<KeyFile, Box<dyn std::error::Error>> {
+    load_file(&default_path(data_dir))
+}
+
+// A key file in either format.
+pub fn load_file(path: &Path) -> Result<KeyFile, Box<dyn std::error::Error>> {
+    if !path.exists() {
+        return Err(Kind::RecordNotFound.fail(KeyFileError::Missing(path.to_path_buf()).to_string()));
+    }
+    let text = fs::read_to_string(path)?;
+    if !text.trim_start().starts_with('{') {
+        return Ok(keyfile::parse(&text)?);
+    }
+    KeyBundle::parse(&text)
+        .and_then(|bundle| bundle.keys())
+        .map_err(|e| Kind::Credential.fail(format!("{}: {e}", path.to_string_lossy())))
+}
+
+// A bundle, or the old txt format for a path ending in .txt.
+pub fn save_file(path: &Path, keys: &KeyFile, schema: Option<&DHTSchema>) -> std::io::Result<()> {
+    if path.extension().is_some_and(|ext| ext == "txt") {
+        return keyfile::save_file(path, keys);
+    }
+    KeyBundle::new(keys, schema).save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundles_round_trip_and_old_txt_files_still_load() {
+        let dir = std::env::temp_dir().join(format!("keybundle-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dflt = DHTSchema::dflt(2).unwrap();
+        let keys = KeyFile {
+            record_key: RecordKey::new(
+                CRYPTO_KIND_VLD0,
+                BareRecordKey::new(BareOpaqueRecordKey::new(&[7; 32]), None),
+            ),
+            shortcode: Some("apple-banana-cherry-delta".to_string()),
+            alt: JoinPreset {
+                namespace: Some("veilid-example-ver2".to_string()),
+                subkeys: Some((0, 3)),
+                encryption: None,
+            },
+            grant: Grant {
+                claimed: vec![Capability::Read, Capability::WriteOwnSubkeys],
+                writer: Some(Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap()),
+                writer_subkeys: Some((2, 3)),
+                owner: None,
+            },
+            expect: Expected {
+                role: Some(Role::Member),
+                ..Expected::default()
+            },
+        };
+
+        assert_eq!(default_path(&dir), dir.join(FILE_NAME));
+        save_file(&dir.join(FILE_NAME), &keys, Some(&dflt)).unwrap();
+        let bundle = KeyBundle::parse(&fs::read_to_string(dir.join(FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(bundle.version, VERSION);
+        assert_eq!(bundle.schema, Some(dflt.clone()));
+        assert_eq!(load(&dir).unwrap(), keys);
+
+        // an owner_keys.txt from an older build, written after the bundle
+        let old = dir.join(keyfile::FILE_NAME);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        save_file(&old, &keys, Some(&dflt)).unwrap();
+        assert!(fs::read_to_string(&old).unwrap().starts_with("RecordKey = "));
+        assert_eq!(default_path(&dir), old);
+        assert_eq!(load(&dir).unwrap(), keys);
+
+        let newer = serde_json::to_string(&KeyBundle { version: VERSION + 1, ..bundle.clone() }).unwrap();
+        assert!(KeyBundle::parse(&newer).unwrap_err().contains("time to upgrade"));
+        let bad_role = KeyBundle {
+            expect: ExpectSection {
+                role: Some("admin".to_string()),
+                ..ExpectSection::default()
+            },
+            ..bundle
+        };
+        assert!(bad_role.keys().unwrap_err().starts_with("expect.role"));
+        fs::write(dir.join(FILE_NAME), "{\"version\": 1}").unwrap();
+        let err = load_file(&dir.join(FILE_NAME)).unwrap_err().to_string();
+        assert!(err.contains("owner_keys.json") && err.contains("record_key"), "{err}");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,765 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use veilid_core::*;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	owner_keys.txt: how the default node hands its record to the alt node.
+//	Builds from here on write owner_keys.json instead (see keybundle.rs),
+//	but still read this, and write it for a --key-file ending in .txt.
+//
+//	  RecordKey = VLD0:<base64 key>
+//	  ShortCode = word-word-word-word
+//	  Alt.Namespace = veilid-example-ver2
+//	  Alt.Subkeys = 0-3
+//	  Alt.Encryption = none
+//	  Grant = read, write-own-subkeys
+//	  Grant.Writer = VLD0:<keypair>
+//	  Grant.WriterSubkeys = 2-3
+//	  Record.Role = member
+//	  Record.WriterSubkeys = 2-3
+//	  Record.Fingerprint = <64 hex digits>
+//
+//	The Alt.* lines are the default node's suggestions for the node joining
+//	the record: which Veilid namespace to run under, which subkeys to read
+//	and watch, and how (if at all) the payloads are encrypted. The alt node
+//	uses them instead of its own config when they're there.
+//
+//	The Grant lines say what the holder may do, and carry the keys to do it
+//	with: read needs nothing but the record key, write-own-subkeys comes with
+//	a member keypair and the subkeys it writes (Grant.Writer and
+//	Grant.WriterSubkeys), admin with the record owner's keypair (Grant.Owner).
+//	The `Grant =` line is only what the file claims; what the holder can
+//	actually do is worked out from which keys are there (Grant::capabilities),
+//	so a file that claims admin without the owner key is read-only. A file
+//	with keys in it is a secret: hand it over like one.
+//
+//	The Record.* lines say what the holder should find once it opens the
+//	record: the role its keys give it there (reader, member or owner), the
+//	subkeys it's meant to write, and a fingerprint of the schema and owner
+//	together (see schema::record_fingerprint), the same one share codes
+//	carry. The alt node checks them against the descriptor
+//	it gets back and stops before writing anything if they don't match.
+//
+//	One `Name = value` per line. Blank lines and lines starting with '#' are
+//	skipped, and names we don't know are ignored so newer builds can add
+//	fields. RecordKey is the only required one.
+//
+//	This file is the only part of the example that reads something a person
+//	might have edited by hand, so parse() never panics and says which line
+//	is wrong. It's kept free of other crate modules so the fuzz target in
+//	fuzz/ can build it on its own.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub const FILE_NAME: &str = "owner_keys.txt";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyFile {
+    pub record_key: RecordKey,
+    pub shortcode: Option<String>,
+    pub alt: JoinPreset,
+    pub grant: Grant,
+    pub expect: Expected,
+}
+
+// Settings suggested for the joining node. Anything missing is left to its config.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct JoinPreset {
+    pub namespace: Option<String>,
+    // first..=last subkey to read and watch
+    pub subkeys: Option<(ValueSubkey, ValueSubkey)>,
+    // payload encryption the writer uses, "none" if it doesn't
+    pub encryption: Option<String>,
+}
+
+// What the holder should find in the record it opens. Anything missing isn't checked.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Expected {
+    pub role: Option<Role>,
+    // first..=last subkey the holder is meant to write
+    pub writer_subkeys: Option<(ValueSubkey, ValueSubkey)>,
+    // schema::record_fingerprint of its owner and schema
+    pub fingerprint: Option<String>,
+}
+
+// Who the holder is in the record's schema, going by the keys it was given.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Reader,
+    Member,
+    Owner,
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Reader => "reader",
+            Role::Member => "member",
+            Role::Owner => "owner",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Role> {
+        [Role::Reader, Role::Member, Role::Owner].into_iter().find(|r| r.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Capability {
+    Read,
+    WriteOwnSubkeys,
+    Admin,
+}
+
+impl Capability {
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Read => "read",
+            Capability::WriteOwnSubkeys => "write-own-subkeys",
+            Capability::Admin => "admin",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Capability> {
+        [Capability::Read, Capability::WriteOwnSubkeys, Capability::Admin]
+            .into_iter()
+            .find(|c| c.name() == name)
+    }
+}
+
+// What the file hands its holder, beyond reading.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Grant {
+    // the `Grant =` line, as written
+    pub claimed: Vec<Capability>,
+    // a member keypair, and the subkeys it writes
+    pub writer: Option<KeyPair>,
+    pub writer_subkeys: Option<(ValueSubkey, ValueSubkey)>,
+    // the record owner's keypair
+    pub owner: Option<KeyPair>,
+}
+
+impl Grant {
+    // What the holder can do, going by the keys, not the claim.
+    pub fn capabilities(&self) -> Vec<Capability> {
+        let mut caps = vec![Capability::Read];
+        if self.writer.is_some() && self.writer_subkeys.is_some() {
+            caps.push(Capability::WriteOwnSubkeys);
+        }
+        if self.owner.is_some() {
+            caps.push(Capability::Admin);
+        }
+        caps
+    }
+
+    // For showing after joining, e.g. "read, write-own-subkeys (subkeys 2..=3)".
+    pub fn describe(&self) -> String {
+        let caps = self.capabilities();
+        let mut out = caps
+            .iter()
+            .map(|c| match (c, self.writer_subkeys) {
+                (Capability::WriteOwnSubkeys, Some((first, last))) => format!("{} (subkeys {first}..={last})", c.name()),
+                _ => c.name().to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let missing: Vec<&str> = self.claimed.iter().filter(|c| !caps.contains(c)).map(|c| c.name()).collect();
+        if !missing.is_empty() {
+            out.push_str(&format!(
+                " (the file also claims {}, but doesn't carry the keys for it)",
+                missing.join(", ")
+            ));
+        }
+        out
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyFileError {
+    // load_file's path isn't there
+    Missing(PathBuf),
+    Empty,
+    MissingRecordKey,
+    // a line that isn't `Name = value`
+    BadLine { line: usize, text: String },
+    BadRecordKey { line: usize, reason: String },
+    WrongCryptoKind { line: usize, kind: String },
+    Duplicate { line: usize, name: &'static str },
+    BadValue { line: usize, name: &'static str, reason: String },
+}
+
+impl fmt::Display for KeyFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyFileError::Missing(path) => write!(f, "{} does not exist", path.to_string_lossy()),
+            KeyFileError::Empty => write!(f, "{FILE_NAME} is empty"),
+            KeyFileError::MissingRecordKey => write!(f, "{FILE_NAME} has no 'RecordKey = ...' line"),
+            KeyFileError::BadLine { line, text } => {
+                write!(f, "{FILE_NAME} line {line}: expected 'Name = value', got '{text}'")
+            }
+            KeyFileError::BadRecordKey { line, reason } => {
+                write!(f, "{FILE_NAME} line {line}: bad RecordKey ({reason})")
+            }
+            KeyFileError::WrongCryptoKind { line, kind } => write!(
+                f,
+                "{FILE_NAME} line {line}: RecordKey uses crypto kind '{kind}', this example only knows {CRYPTO_KIND_VLD0}"
+            ),
+            KeyFileError::Duplicate { line, name } => {
+                write!(f, "{FILE_NAME} line {line}: {name} given more than once")
+            }
+            KeyFileError::BadValue { line, name, reason } => {
+                write!(f, "{FILE_NAME} line {line}: bad {name} ({reason})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeyFileError {}
+
+pub fn parse(text: &str) -> Result<KeyFile, KeyFileError> {
+    if text.trim().is_empty() {
+        return Err(KeyFileError::Empty);
+    }
+
+    let mut record_key: Option<RecordKey> = None;
+    let mut shortcode: Option<String> = None;
+    let mut alt = JoinPreset::default();
+    let mut grant = Grant::default();
+    let mut claimed = false;
+    let mut expect = Expected::default();
+
+    for (i, raw) in text.lines().enumerate() {
+        let line = i + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let Some((name, value)) = trimmed.split_once('=') else {
+            return Err(KeyFileError::BadLine {
+                line,
+                text: trimmed.to_string(),
+            });
+        };
+        let value = value.trim();
+
+        match name.trim() {
+            "RecordKey" => {
+                if record_key.is_some() {
+                    return Err(KeyFileError::Duplicate { line, name: "RecordKey" });
+                }
+                record_key = Some(parse_record_key(line, value)?);
+            }
+            "ShortCode" => {
+                if shortcode.is_some() {
+                    return Err(KeyFileError::Duplicate { line, name: "ShortCode" });
+                }
+                shortcode = Some(value.to_string());
+            }
+            "Alt.Namespace" => {
+                let name = "Alt.Namespace";
+                if alt.namespace.is_some() {
+                    return Err(KeyFileError::Duplicate { line, name });
+                }
+                check_word(line, name, value, |c| c.is_ascii_alphanumeric() || c == '-' || c == '_')?;
+                alt.namespace = Some(value.to_string());
+            }
+            "Alt.Subkeys" => {
+                if alt.subkeys.is_some() {
+                    return Err(KeyFileError::Duplicate { line, name: "Alt.Subkeys" });
+                }
+                alt.subkeys = Some(parse_subkeys(line, "Alt.Subkeys", value)?);
+            }
+            "Alt.Encryption" => {
+                let name = "Alt.Encryption";
+                if alt.encryption.is_some() {
+                    return Err(KeyFileError::Duplicate { line, name });
+                }
+                check_word(line, name, value, |c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')?;
+                alt.encryption = Some(value.to_string());
+            }
+            "Grant" => {
+                if claimed {
+                    return Err(KeyFileError::Duplicate { line, name: "Grant" });
+                }
+                claimed = true;
+                for word in value.split(',').map(str::trim).filter(|w| !w.is_empty()) {
+                    let cap = Capability::from_name(word).ok_or_else(|| KeyFileError::BadValue {
+                        line,
+                        name: "Grant",
+                        reason: format!("'{word}' isn't read, write-own-subkeys or admin"),
+                    })?;
+                    if !grant.claimed.contains(&cap) {
+                        grant.claimed.push(cap);
+                    }
+                }
+            }
+            "Grant.Writer" => {
+                if grant.writer.is_some() {
+                    return Err(KeyFileError::Duplicate { line, name: "Grant.Writer" });
+                }
+                grant.writer = Some(parse_keypair(line, "Grant.Writer", value)?);
+            }
+            "Grant.WriterSubkeys" => {
+                if grant.writer_subkeys.is_some() {
+                    return Err(KeyFileError::Duplicate { line, name: "Grant.WriterSubkeys" });
+                }
+                grant.writer_subkeys = Some(parse_subkeys(line, "Grant.WriterSubkeys", value)?);
+            }
+            "Grant.Owner" => {
+                if grant.owner.is_some() {
+                    return Err(KeyFileError::Duplicate { line, name: "Grant.Owner" });
+                }
+                grant.owner = Some(parse_keypair(line, "Grant.Owner", value)?);
+            }
+            "Record.Role" => {
+                if expect.role.is_some() {
+                    return Err(KeyFileError::Duplicate { line, name: "Record.Role" });
+                }
+                expect.role = Some(Role::from_name(value).ok_or_else(|| KeyFileError::BadValue {
+                    line,
+                    name: "Record.Role",
+                    reason: format!("'{value}' isn't reader, member or owner"),
+                })?);
+            }
+            "Record.WriterSubkeys" => {
+                if expect.writer_subkeys.is_some() {
+                    return Err(KeyFileError::Duplicate { line, name: "Record.WriterSubkeys" });
+                }
+                expect.writer_subkeys = Some(parse_subkeys(line, "Record.WriterSubkeys", value)?);
+            }
+            "Record.Fingerprint" => {
+                let name = "Record.Fingerprint";
+                if expect.fingerprint.is_some() {
+                    return Err(KeyFileError::Duplicate { line, name });
+                }
+                check_word(line, name, value, |c| c.is_ascii_hexdigit())?;
+                expect.fingerprint = Some(value.to_string());
+            }
+            "" => {
+                return Err(KeyFileError::BadLine {
+                    line,
+                    text: trimmed.to_string(),
+                })
+            }
+            _ => {}
+        }
+    }
+
+    Ok(KeyFile {
+        record_key: record_key.ok_or(KeyFileError::MissingRecordKey)?,
+        shortcode,
+        alt,
+        grant,
+        expect,
+    })
+}
+
+// A non-empty value made only of the characters `allowed` accepts.
+fn check_word(line: usize, name: &'static str, value: &str, allowed: impl Fn(char) -> bool) -> Result<(), KeyFileError> {
+    if value.is_empty() || !value.chars().all(allowed) {
+        return Err(KeyFileError::BadValue {
+            line,
+            name,
+            reason: format!("'{value}' has characters that aren't allowed"),
+        });
+    }
+    Ok(())
+}
+
+fn parse_keypair(line: usize, name: &'static str, value: &str) -> Result<KeyPair, KeyFileError> {
+    value.parse().map_err(|e: VeilidAPIError| KeyFileError::BadValue {
+        line,
+        name,
+        reason: e.to_string(),
+    })
+}
+
+// "3" or "0-3"
+fn parse_subkeys(line: usize, name: &'static str, value: &str) -> Result<(ValueSubkey, ValueSubkey), KeyFileError> {
+    let bad = |reason: String| KeyFileError::BadValue { line, name, reason };
+    let number = |s: &str| {
+        s.trim()
+            .parse::<ValueSubkey>()
+            .map_err(|_| bad(format!("'{value}' should be N or FIRST-LAST")))
+    };
+    let (first, last) = match value.split_once('-') {
+        Some((first, last)) => (number(first)?, number(last)?),
+        None => {
+            let n = number(value)?;
+            (n, n)
+        }
+    };
+    if first > last {
+        return Err(bad(format!("{first} comes after {last}")));
+    }
+    Ok((first, last))
+}
+
+fn parse_record_key(line: usize, value: &str) -> Result<RecordKey, KeyFileError> {
+    // Veilid guesses a kind for keys without a "KIND:" prefix; we'd rather be told.
+    let Some((kind, _)) = value.split_once(':') else {
+        return Err(KeyFileError::BadRecordKey {
+            line,
+            reason: "missing the 'VLD0:' prefix".to_string(),
+        });
+    };
+    if kind != CRYPTO_KIND_VLD0.to_string() {
+        return Err(KeyFileError::WrongCryptoKind {
+            line,
+            kind: kind.to_string(),
+        });
+    }
+    let record_key: RecordKey = value.parse().map_err(|e: VeilidAPIError| KeyFileError::BadRecordKey {
+        line,
+        reason: e.to_string(),
+    })?;
+    // An empty or cut-off key decodes without complaint, so check the length too.
+    let len = record_key.ref_value().ref_key().len();
+    if len != 32 {
+        return Err(KeyFileError::BadRecordKey {
+            line,
+            reason: format!("key is {len} bytes, expected 32"),
+        });
+    }
+    Ok(record_key)
+}
+
+pub fn render(keys: &KeyFile) -> String {
+    let mut out = format!("RecordKey = {}\n", keys.record_key);
+    if let Some(code) = &keys.shortcode {
+        out.push_str(&format!("ShortCode = {code}\n"));
+    }
+    if let Some(ns) = &keys.alt.namespace {
+        out.push_str(&format!("Alt.Namespace = {ns}\n"));
+    }
+    if let Some((first, last)) = keys.alt.subkeys {
+        out.push_str(&format!("Alt.Subkeys = {first}-{last}\n"));
+    }
+    if let Some(enc) = &keys.alt.encryption {
+        out.push_str(&format!("Alt.Encryption = {enc}\n"));
+    }
+    let grant = &keys.grant;
+    if !grant.claimed.is_empty() {
+        let names: Vec<&str> = grant.claimed.iter().map(|c| c.name()).collect();
+        out.push_str(&format!("Grant = {}\n", names.join(", ")));
+    }
+    if let Some(writer) = &grant.writer {
+        out.push_str(&format!("Grant.Writer = {writer}\n"));
+    }
+    if let Some((first, last)) = grant.writer_subkeys {
+        out.push_str(&format!("Grant.WriterSubkeys = {first}-{last}\n"));
+    }
+    if let Some(owner) = &grant.owner {
+        out.push_str(&format!("Grant.Owner = {owner}\n"));
+    }
+    let expect = &keys.expect;
+    if let Some(role) = expect.role {
+        out.push_str(&format!("Record.Role = {}\n", role.name()));
+    }
+    if let Some((first, last)) = expect.writer_subkeys {
+        out.push_str(&format!("Record.WriterSubkeys = {first}-{last}\n"));
+    }
+    if let Some(fingerprint) = &expect.fingerprint {
+        out.push_str(&format!("Record.Fingerprint = {fingerprint}\n"));
+    }
+    out
+}
+
+pub fn save(data_dir: &Path, keys: &KeyFile) -> std::io::Result<()> {
+    save_file(&data_dir.join(FILE_NAME), keys)
+}
+
+// Load or save a key file somewhere other than the data folder (--key-file).
+pub fn load_file(path: &Path) -> Result<KeyFile, Box<dyn std::error::Error>> {
+    if !path.exists() {
+        return Err(KeyFileError::Missing(path.to_path_buf()).into());
+    }
+    Ok(parse(&fs::read_to_string(path)?)?)
+}
+
+pub fn save_file(path: &Path, keys: &KeyFile) -> std::io::Result<()> {
+    fs::write(path, render(keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> RecordKey {
+        RecordKey::new(
+            CRYPTO_KIND_VLD0,
+            BareRecordKey::new(BareOpaqueRecordKey::new(&[byte; 32]), None),
+        )
+    }
+
+    fn keyfile(byte: u8) -> KeyFile {
+        KeyFile {
+            record_key: key(byte),
+            shortcode: Some("apple-banana-cherry-delta".to_string()),
+            alt: JoinPreset {
+                namespace: Some("veilid-example-ver2".to_string()),
+                subkeys: Some((0, 3)),
+                encryption: Some("none".to_string()),
+            },
+            grant: Grant::default(),
+            expect: Expected::default(),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_render() {
+        let keys = keyfile(7);
+        assert_eq!(parse(&render(&keys)), Ok(keys));
+    }
+
+    #[test]
+    fn shortcode_is_optional() {
+        let keys = KeyFile {
+            record_key: key(1),
+            shortcode: None,
+            alt: JoinPreset::default(),
+            grant: Grant::default(),
+            expect: Expected::default(),
+        };
+        assert_eq!(parse(&render(&keys)), Ok(keys));
+    }
+
+    #[test]
+    fn tolerates_whitespace_comments_and_crlf() {
+        let text = format!(
+            "# written by the default node\r\n\r\n   RecordKey   =   {}   \r\n\tShortCode=x\r\n",
+            key(2)
+        );
+        let keys = parse(&text).unwrap();
+        assert_eq!(keys.record_key, key(2));
+        assert_eq!(keys.shortcode.as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn ignores_unknown_fields() {
+        let text = format!("Namespace = veilid-example-ver1\nRecordKey = {}\n", key(3));
+        assert_eq!(parse(&text).unwrap().record_key, key(3));
+    }
+
+    #[test]
+    fn capabilities_come_from_the_keys_not_the_claim() {
+        let writer = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let mut keys = keyfile(4);
+        keys.grant = Grant {
+            claimed: vec![Capability::Read, Capability::WriteOwnSubkeys, Capability::Admin],
+            writer: Some(writer),
+            writer_subkeys: Some((2, 3)),
+            owner: None,
+        };
+        let back = parse(&render(&keys)).unwrap();
+        assert_eq!(back, keys);
+        assert_eq!(back.grant.capabilities(), vec![Capability::Read, Capability::WriteOwnSubkeys]);
+        assert_eq!(
+            back.grant.describe(),
+            "read, write-own-subkeys (subkeys 2..=3) (the file also claims admin, but doesn't carry the keys for it)"
+        );
+
+        // a writer without its subkeys writes nothing
+        keys.grant.writer_subkeys = None;
+        assert_eq!(keys.grant.capabilities(), vec![Capability::Read]);
+
+        for bad in ["Grant = read, fly", "Grant.Writer = VLD0:nope", "Grant.WriterSubkeys = 3-2"] {
+            let text = format!("RecordKey = {}\n{bad}\n", key(4));
+            assert!(matches!(parse(&text), Err(KeyFileError::BadValue { line: 2, .. })), "{bad}");
+        }
+    }
+
+    #[test]
+    fn join_presets() {
+        let text = format!("RecordKey = {}\nAlt.Subkeys = 2\nAlt.Namespace = my_ns-2\n", key(3));
+        let keys = parse(&text).unwrap();
+        assert_eq!(keys.alt.subkeys, Some((2, 2)));
+        assert_eq!(keys.alt.namespace.as_deref(), Some("my_ns-2"));
+        assert_eq!(keys.alt.encryption, None);
+
+        for bad in [
+            "Alt.Subkeys = 3-1",
+            "Alt.Subkeys = -1",
+            "Alt.Subkeys = 1-",
+            "Alt.Subkeys = all",
+            "Alt.Namespace = has space",
+            "Alt.Namespace = ../up",
+            "Alt.Encryption = AES!",
+            "Alt.Encryption =",
+        ] {
+            let text = format!("RecordKey = {}\n{bad}\n", key(3));
+            assert!(
+                matches!(parse(&text), Err(KeyFileError::BadValue { line: 2, .. })),
+                "{bad:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn record_expectations() {
+        let mut keys = keyfile(5);
+        keys.expect = Expected {
+            role: Some(Role::Member),
+            writer_subkeys: Some((2, 3)),
+            fingerprint: Some("5be01c7a94d3e2f18a6c0b7d29e4f1a3c8b5d6e7f0a1b2c3d4e5f60718293a4b".to_string()),
+        };
+        assert_eq!(parse(&render(&keys)), Ok(keys));
+
+        for bad in [
+            "Record.Role = admin",
+            "Record.WriterSubkeys = 3-2",
+            "Record.Fingerprint = 5be0#1c7a",
+        ] {
+            let text = format!("RecordKey = {}\n{bad}\n", key(5));
+            assert!(matches!(parse(&text), Err(KeyFileError::BadValue { line: 2, .. })), "{bad}");
+        }
+    }
+
+    #[test]
+    fn empty_and_blank_files() {
+        assert_eq!(parse(""), Err(KeyFileError::Empty));
+        assert_eq!(parse(" \n\t\r\n"), Err(KeyFileError::Empty));
+        assert_eq!(parse("# only a comment\n"), Err(KeyFileError::MissingRecordKey));
+    }
+
+    #[test]
+    fn missing_record_key() {
+        assert_eq!(
+            parse("ShortCode = apple-banana-cherry-delta\n"),
+            Err(KeyFileError::MissingRecordKey)
+        );
+    }
+
+    #[test]
+    fn truncated_lines() {
+        assert_eq!(
+            parse("RecordKey\n"),
+            Err(KeyFileError::BadLine {
+                line: 1,
+                text: "RecordKey".to_string()
+            })
+        );
+        assert!(matches!(
+            parse("RecordKey =\n"),
+            Err(KeyFileError::BadRecordKey { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse("RecordKey = VLD0:\n"),
+            Err(KeyFileError::BadRecordKey { line: 1, .. })
+        ));
+        assert_eq!(
+            parse("= VLD0:abc\n"),
+            Err(KeyFileError::BadLine {
+                line: 1,
+                text: "= VLD0:abc".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn truncated_key() {
+        let full = key(4).to_string();
+        for cut in [6, 10, full.len() / 2, full.len() - 1] {
+            let text = format!("RecordKey = {}\n", &full[..cut]);
+            assert!(
+                matches!(parse(&text), Err(KeyFileError::BadRecordKey { line: 1, .. })),
+                "cut at {cut} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn swapped_fields() {
+        let text = format!("RecordKey = apple-banana-cherry-delta\nShortCode = {}\n", key(5));
+        assert!(matches!(
+            parse(&text),
+            Err(KeyFileError::BadRecordKey { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn wrong_crypto_kinds() {
+        let bare = key(6).ref_value().encode();
+        for kind in ["VLD1", "NONE", "vld0", "XXXX"] {
+            let text = format!("RecordKey = {kind}:{bare}\n");
+            assert_eq!(
+                parse(&text),
+                Err(KeyFileError::WrongCryptoKind {
+                    line: 1,
+                    kind: kind.to_string()
+                })
+            );
+        }
+        // no kind at all
+        assert!(matches!(
+            parse(&format!("RecordKey = {bare}\n")),
+            Err(KeyFileError::BadRecordKey { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn duplicates_are_refused() {
+        let text = format!("RecordKey = {}\nRecordKey = {}\n", key(1), key(2));
+        assert_eq!(
+            parse(&text),
+            Err(KeyFileError::Duplicate {
+                line: 2,
+                name: "RecordKey"
+            })
+        );
+        let text = format!("RecordKey = {}\nShortCode = a\nShortCode = b\n", key(1));
+        assert_eq!(
+            parse(&text),
+            Err(KeyFileError::Duplicate {
+                line: 3,
+                name: "ShortCode"
+            })
+        );
+    }
+
+    #[test]
+    fn unicode_garbage() {
+        for text in [
+            "RecordKey = VLD0:🔑🔑🔑🔑🔑🔑🔑🔑🔑🔑🔑\n",
+            "RecordKey = 🔑🔑:abc\n",
+            "RécordKey = VLD0:abc\n",
+            "\u{feff}RecordKey\u{200b} = x\n",
+            "RecordKey = VLD0:\u{0}\u{0}\u{0}\n",
+            "ＲｅｃｏｒｄＫｅｙ ＝ ｘ\n",
+        ] {
+            assert!(parse(text).is_err(), "{text:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let text = format!("# header\n\nRecordKey = {}\nthis is not a field\n", key(1));
+        assert_eq!(
+            parse(&text),
+            Err(KeyFileError::BadLine {
+                line: 4,
+                text: "this is not a field".to_string()
+            })
+        );
+        assert!(KeyFileError::BadLine {
+            line: 4,
+            text: String::new()
+        }
+        .to_string()
+        .contains("line 4"));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("keyfile-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let keys = keyfile(9);
+        save(&dir, &keys).unwrap();
+        assert_eq!(load_file(&dir.join(FILE_NAME)).unwrap(), keys);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(load_file(&dir.join(FILE_NAME)).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The example as a library. main.rs only calls app::run(); everything
+//	else is in here, so another program can reuse the parts that aren't
+//	particular to this example, above all starting a node, attaching and
+//	waiting until it's ready to use (node::VeilidNode):
+//
+//	  use veilid_test_node::config::AppConfig;
+//	  use veilid_test_node::node::VeilidNode;
+//
+//	  let config = AppConfig::default();
+//	  let node = VeilidNode::start_attached(&config, &dir, "my-app", |_| {}).await?;
+//
+//	Only the pub modules are meant for that. The rest are the example's
+//	own and change whenever it does.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+mod access;
+pub mod app;
+mod audit;
+mod backend;
+mod backup;
+mod bench;
+mod chaos;
+mod chat;
+mod commands;
+pub mod cli;
+pub mod codecs;
+pub mod config;
+mod conflict;
+mod contacts;
+mod control;
+mod dht;
+mod diag;
+mod discovery;
+mod envelope;
+mod eventlog;
+mod events;
+pub mod exit;
+mod expect;
+mod feed;
+mod fields;
+mod flood;
+mod guard;
+mod health;
+mod heatmap;
+mod i18n;
+mod input;
+mod janitor;
+mod keybundle;
+mod keyfile;
+mod load;
+mod locks;
+mod logs;
+mod mailbox;
+mod member;
+mod metadata;
+mod monitor;
+mod nicknames;
+pub mod node;
+mod ordering;
+mod page;
+mod paths;
+mod payloads;
+mod preflight;
+mod prefetch;
+mod prefs;
+mod profile;
+mod progress;
+mod protocol;
+mod publish;
+mod queue;
+mod quota;
+pub mod ready;
+mod receipts;
+mod record;
+mod recovery;
+mod relay;
+mod record_manager;
+mod repl;
+mod route;
+mod scenario;
+mod schema;
+mod shortcode;
+mod snapshot;
+mod soak;
+mod stats;
+mod store;
+mod systemd;
+mod templates;
+mod transcript;
+mod trend;
+mod tutorial;
+mod warm;
+pub mod watch;
+mod watch_status;
+mod webhook;
+mod winservice;
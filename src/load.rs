@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use veilid_core::*;
+
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::guard::WriteGuard;
+use crate::i18n::t;
+use crate::keybundle;
+use crate::metadata::{self, RecordMetadata};
+use crate::payloads::Registry;
+use crate::progress;
+use crate::record::start_tool_node;
+use crate::record_manager::RecordManager;
+use crate::schema::pick_writer;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`record load <file> [--rate N]`: seed the record in owner_keys.txt with
+//	a file of values, so a demo starts with something in it.
+//
+//	A .json file is either an object or a list of rows:
+//
+//	  {"2": "hello", "status": "busy"}
+//	  [{"subkey": 2, "value": "hello"}, {"name": "status", "value": "busy"}]
+//
+//	anything else is read as CSV, one `subkey,value` or `name,value` row a
+//	line (a header row, blank lines and # comments are skipped; a value in
+//	double quotes can have commas in it, with "" for a quote). A name is
+//	looked up in the metadata block's roster, which says where each writer
+//	keeps each of its named fields (see fields.rs); the first one the key
+//	file lets us write is used.
+//
+//	Values go through the payload types like any write at the prompt, so a
+//	typed subkey only takes a row that parses as its type (a JSON value
+//	that isn't a string is written as its JSON text). Writes go out at
+//	--rate per second (4 unless told), since a burst of sets from one node
+//	is the quickest way to get TryAgain back; what went in, what was
+//	skipped and why is reported at the end. A record this data folder
+//	didn't make asks once before the first row, unless --yes (see guard.rs).
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub const DEFAULT_RATE: u64 = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Subkey(ValueSubkey),
+    Name(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Row {
+    // where in the file, for the report: "line 3" or "row 2"
+    pub at: String,
+    pub target: Target,
+    pub value: String,
+}
+
+fn target(field: &str) -> Target {
+    match field.parse() {
+        Ok(subkey) => Target::Subkey(subkey),
+        Err(_) => Target::Name(field.to_string()),
+    }
+}
+
+fn json_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+pub fn parse_json(text: &str) -> Result<Vec<Row>, String> {
+    let doc: serde_json::Value = serde_json::from_str(text).map_err(|e| format!("not valid JSON: {e}"))?;
+    match doc {
+        serde_json::Value::Object(map) => Ok(map
+            .iter()
+            .map(|(key, value)| Row {
+                at: format!("\"{key}\""),
+                target: target(key),
+                value: json_text(value),
+            })
+            .collect()),
+        serde_json::Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let at = format!("row {}", i + 1);
+                let value = item.get("value").ok_or(format!("{at} has no \"value\""))?;
+                let target = match (item.get("subkey").and_then(|s| s.as_u64()), item.get("name").and_then(|n| n.as_str())) {
+                    (Some(subkey), None) => Target::Subkey(u32::try_from(subkey).map_err(|_| format!("{at}: subkey {subkey} is too big"))?),
+                    (None, Some(name)) => Target::Name(name.to_string()),
+                    _ => return Err(format!("{at} needs a \"subkey\" number or a \"name\", not both")),
+                };
+                Ok(Row {
+                    at,
+                    target,
+                    value: json_text(value),
+                })
+            })
+            .collect(),
+        _ => Err("the JSON should be an object or a list of rows".to_string()),
+    }
+}
+
+// A CSV value, without its quotes if it had them.
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => value.to_string(),
+    }
+}
+
+pub fn parse_csv(text: &str) -> Result<Vec<Row>, String> {
+    let mut rows = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let at = format!("line {}", i + 1);
+        let (first, value) = trimmed.split_once(',').ok_or(format!("{at} has no comma: {trimmed}"))?;
+        let first = first.trim();
+        if rows.is_empty() && matches!(first, "subkey" | "name") {
+            continue;
+        }
+        rows.push(Row {
+            at,
+            target: target(first),
+            value: unquote(value),
+        });
+    }
+    Ok(rows)
+}
+
+pub fn read_rows(file: &Path) -> Result<Vec<Row>, String> {
+    let text = std::fs::read_to_string(file).map_err(|e| format!("couldn't read {}: {e}", file.to_string_lossy()))?;
+    let is_json = file.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let rows = if is_json { parse_json(&text)? } else { parse_csv(&text)? };
+    if rows.is_empty() {
+        return Err(format!("{} has no rows in it", file.to_string_lossy()));
+    }
+    Ok(rows)
+}
+
+#[derive(Default)]
+struct Summary {
+    written: usize,
+    bytes: usize,
+    // (where in the file, why)
+    skipped: Vec<(String, String)>,
+    failed: Vec<(String, String)>,
+}
+
+// -------------------------------------------------------------------------
+// record load <file> [--rate N]
+// -------------------------------------------------------------------------
+
+pub async fn run(file: &Path, rate: u64, options: &Options, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let rows = read_rows(file)?;
+    let data_dir = config.data_dir()?;
+    let keys = keybundle::load(&data_dir)?;
+    let granted: Vec<&KeyPair> = keys.grant.owner.iter().chain(keys.grant.writer.iter()).collect();
+    if granted.is_empty() {
+        return Err("the key file only grants reading, so there's nothing to load with".into());
+    }
+
+    let (veilid, rc) = start_tool_node(options, config).await?;
+    let records = RecordManager::new(rc.clone());
+    let record = records.open(keys.record_key.clone(), granted.first().map(|kp| (*kp).clone())).await?;
+    let desc = record.descriptor().clone();
+    let record_key = desc.key();
+
+    // names, and the payload types the config leaves out, come from the metadata block
+    let mut registry = Registry::from_config(config)?;
+    let mut names: BTreeMap<String, Vec<ValueSubkey>> = BTreeMap::new();
+    if let Some(meta) = rc
+        .get_dht_value(record_key.clone(), metadata::METADATA_SUBKEY, true)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|v| RecordMetadata::decode(v.data()))
+    {
+        registry.adopt(&meta.payload_types);
+        for entry in &meta.members {
+            for (name, &subkey) in &entry.fields {
+                names.entry(name.clone()).or_default().push(subkey);
+            }
+        }
+    }
+
+    let guard = WriteGuard::new(&data_dir, &record_key, options.yes)?;
+    if !guard.confirm(&t!("shared-write-load", rows = rows.len()))? {
+        println!("{}", t!("not-written"));
+        drop(record);
+        records.close_all().await;
+        veilid.shutdown().await;
+        return Ok(());
+    }
+
+    println!("Loading {} row(s) into {record_key} at {rate}/s", rows.len());
+    let mut summary = Summary::default();
+    let mut pace = tokio::time::interval(Duration::from_secs_f64(1.0 / rate as f64));
+    pace.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let started = tokio::time::Instant::now();
+    let bar = progress::subkeys(rows.len() as u64, "Loading");
+    for row in &rows {
+        bar.inc(1);
+        let picked = match &row.target {
+            Target::Subkey(subkey) => pick_writer(&desc.schema(), &desc.owner(), &granted, Some(*subkey)),
+            Target::Name(name) => match names.get(name) {
+                None => Err(format!("no writer keeps a field called '{name}' (see the metadata roster)")),
+                Some(subkeys) => subkeys
+                    .iter()
+                    .find_map(|&subkey| pick_writer(&desc.schema(), &desc.owner(), &granted, Some(subkey)).ok())
+                    .ok_or(format!("'{name}' is kept in subkey(s) {subkeys:?}, none of which the key file lets us write")),
+            },
+        };
+        let (subkey, writer) = match picked {
+            Ok(picked) => picked,
+            Err(e) => {
+                summary.skipped.push((row.at.clone(), e));
+                continue;
+            }
+        };
+        let value = match registry.encode(subkey, &row.value) {
+            Ok(value) => value.encode(),
+            Err(e) => {
+                summary.skipped.push((row.at.clone(), e));
+                continue;
+            }
+        };
+        pace.tick().await;
+        let opts = SetDHTValueOptions {
+            writer: Some(writer.clone()),
+            allow_offline: None,
+        };
+        let size = value.len();
+        match rc.set_dht_value(record_key.clone(), subkey, value, Some(opts)).await {
+            Ok(_) => {
+                summary.written += 1;
+                summary.bytes += size;
+            }
+            Err(e) => summary.failed.push((row.at.clone(), format!("subkey {subkey}: {e}"))),
+        }
+    }
+    bar.finish();
+    drop(record);
+    records.close_all().await;
+    veilid.shutdown().await;
+
+    println!(
+        "Wrote {} of {} row(s) ({}) in {:.1}s",
+        summary.written,
+        rows.len(),
+        crate::stats::bytes(summary.bytes as u64),
+        started.elapsed().as_secs_f64()
+    );
+    for (what, list) in [("Skipped", &summary.skipped), ("Failed", &summary.failed)] {
+        if !list.is_empty() {
+            println!("{what} {}:", list.len());
+            for (at, why) in list {
+                println!("  {at}: {why}");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_and_json_rows_name_a_subkey_or_a_field() {
+        let csv = "subkey,value\n2,hello\n\n# a comment\nstatus, \"busy, very\"\n3,\"say \"\"hi\"\"\"\n";
+        let rows = parse_csv(csv).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!((rows[0].at.as_str(), &rows[0].target, rows[0].value.as_str()), ("line 2", &Target::Subkey(2), "hello"));
+        assert_eq!((&rows[1].target, rows[1].value.as_str()), (&Target::Name("status".to_string()), "busy, very"));
+        assert_eq!(rows[2].value, "say \"hi\"");
+        assert!(parse_csv("2 hello").unwrap_err().contains("line 1"));
+
+        let rows = parse_json(r#"[{"subkey": 4, "value": {"status": "away"}}, {"name": "status", "value": "free"}]"#).unwrap();
+        assert_eq!((&rows[0].target, rows[0].value.as_str()), (&Target::Subkey(4), r#"{"status":"away"}"#));
+        assert_eq!(rows[1].target, Target::Name("status".to_string()));
+        let rows = parse_json(r#"{"2": "hello"}"#).unwrap();
+        assert_eq!((&rows[0].target, rows[0].value.as_str()), (&Target::Subkey(2), "hello"));
+        assert!(parse_json(r#"[{"subkey": 1, "name": "x", "value": "y"}]"#).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::config::AppConfig;
+use crate::dht::Dht;
+use crate::envelope::{Codec, Envelope};
+use crate::profile::ProfileCard;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Advisory write locks, so the default and alt nodes don't write over each
+//	other's half-finished edit of a subkey they both write (with the member
+//	key that owner_keys.txt hands out).
+//
+//	lock_subkey in the config reserves one member subkey for a lock table:
+//	subkey -> who holds it and until when. Nothing in Veilid enforces it;
+//	both prompts read the table before they write, and refuse a subkey
+//	someone else holds. A lock runs out on its own after lock_secs (or what
+//	`lock` was given), so a node that goes away doesn't hold it for ever.
+//
+//	  lock                  show the locks in the table
+//	  lock 3 [secs]         take (or extend) the lock on subkey 3
+//	  unlock 3              let it go
+//
+//	Holders are told apart by their profile card key, not the writer key,
+//	since both nodes write as the same member. If two nodes take a lock at
+//	the same moment, Veilid keeps one table and the other set comes back
+//	with the newer value; that node is told it lost.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Lock {
+    // the holder's profile card key, and its nickname for showing
+    pub holder: String,
+    pub name: String,
+    pub expires_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LockTable {
+    pub locks: BTreeMap<ValueSubkey, Lock>,
+}
+
+impl LockTable {
+    pub fn decode(data: &[u8]) -> Option<LockTable> {
+        let env = Envelope::decode(data).ok()?;
+        if env.codec != Codec::Json {
+            return None;
+        }
+        serde_json::from_slice(&env.body).ok()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut env = Envelope::new(Codec::Json, serde_json::to_vec(self).expect("lock table serializes"));
+        env.written_ms = Some(crate::audit::now_ms() as u64);
+        env.encode()
+    }
+
+    // The lock on `subkey`, if it hasn't run out.
+    pub fn live(&self, subkey: ValueSubkey, now_ms: u64) -> Option<&Lock> {
+        self.locks.get(&subkey).filter(|lock| lock.expires_ms > now_ms)
+    }
+
+    fn prune(&mut self, now_ms: u64) {
+        self.locks.retain(|_, lock| lock.expires_ms > now_ms);
+    }
+}
+
+fn secs_left(lock: &Lock, now_ms: u64) -> u64 {
+    lock.expires_ms.saturating_sub(now_ms).div_ceil(1000)
+}
+
+pub struct Locks {
+    // None when lock_subkey isn't set: no table, and every write goes ahead
+    subkey: Option<ValueSubkey>,
+    holder: String,
+    name: String,
+    default_secs: u64,
+    // who writes the table, None to use the record's default writer
+    writer: Option<KeyPair>,
+}
+
+impl Locks {
+    pub fn new(config: &AppConfig, mine: &ProfileCard, writer: Option<KeyPair>) -> Locks {
+        Locks {
+            subkey: config.lock_subkey,
+            holder: mine.public_key.clone(),
+            name: mine.nickname.clone(),
+            default_secs: config.lock_secs,
+            writer,
+        }
+    }
+
+    async fn table(&self, rc: &Dht, record: &RecordKey, lock_subkey: ValueSubkey) -> Result<LockTable, String> {
+        let value = rc
+            .get_dht_value(record.clone(), lock_subkey, true)
+            .await
+            .map_err(|e| format!("couldn't read the lock table (subkey {lock_subkey}): {e}"))?;
+        match value {
+            None => Ok(LockTable::default()),
+            Some(value) => LockTable::decode(value.data())
+                .ok_or(format!("subkey {lock_subkey} doesn't hold a lock table; is lock_subkey right?")),
+        }
+    }
+
+    // Writes the table; Err if someone else's went in first.
+    async fn save(&self, rc: &Dht, record: &RecordKey, lock_subkey: ValueSubkey, table: &LockTable) -> Result<(), String> {
+        let opts = self.writer.clone().map(|writer| SetDHTValueOptions {
+            writer: Some(writer),
+            allow_offline: None,
+        });
+        match rc.set_dht_value(record.clone(), lock_subkey, table.encode(), opts).await {
+            Ok(None) => Ok(()),
+            Ok(Some(_)) => Err("someone else changed the locks at the same moment, try again".to_string()),
+            Err(e) => Err(format!("couldn't write the lock table (subkey {lock_subkey}): {e}")),
+        }
+    }
+
+    // Before a write to `subkey`: Err saying who holds it, if it isn't us.
+    pub async fn may_write(&self, rc: &Dht, record: &RecordKey, subkey: ValueSubkey) -> Result<(), String> {
+        let Some(lock_subkey) = self.subkey else {
+            return Ok(());
+        };
+        let now = crate::audit::now_ms() as u64;
+        match self.table(rc, record, lock_subkey).await?.live(subkey, now) {
+            Some(lock) if lock.holder != self.holder => Err(format!(
+                "subkey {subkey} is locked by {} for another {}s",
+                lock.name,
+                secs_left(lock, now)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // `lock [<subkey> [secs]]` and `unlock <subkey>` at the prompt.
+    pub async fn command(&self, rc: &Dht, record: &RecordKey, args: &str, unlock: bool) -> String {
+        let Some(lock_subkey) = self.subkey else {
+            return "Locking is off; set lock_subkey in the config to reserve a subkey for the lock table".to_string();
+        };
+        let mut parts = args.split_whitespace();
+        let (subkey, secs) = match (parts.next().map(str::parse::<ValueSubkey>), parts.next().map(str::parse::<u64>), parts.next()) {
+            (None, None, None) if !unlock => return self.show(rc, record, lock_subkey).await,
+            (Some(Ok(subkey)), None, None) => (subkey, self.default_secs),
+            (Some(Ok(subkey)), Some(Ok(secs)), None) if !unlock && secs > 0 => (subkey, secs),
+            _ if unlock => return "Usage: unlock <subkey>".to_string(),
+            _ => return "Usage: lock [<subkey> [secs]]".to_string(),
+        };
+        if subkey == lock_subkey {
+            return format!("Subkey {subkey} holds the lock table itself");
+        }
+        let mut table = match self.table(rc, record, lock_subkey).await {
+            Ok(table) => table,
+            Err(e) => return e,
+        };
+        let now = crate::audit::now_ms() as u64;
+        match table.live(subkey, now) {
+            Some(lock) if lock.holder != self.holder => {
+                return format!("Subkey {subkey} is locked by {} for another {}s", lock.name, secs_left(lock, now));
+            }
+            None if unlock => return format!("Subkey {subkey} isn't locked"),
+            _ => {}
+        }
+        table.prune(now);
+        if unlock {
+            table.locks.remove(&subkey);
+        } else {
+            table.locks.insert(
+                subkey,
+                Lock {
+                    holder: self.holder.clone(),
+                    name: self.name.clone(),
+                    expires_ms: now + secs * 1000,
+                },
+            );
+        }
+        match (self.save(rc, record, lock_subkey, &table).await, unlock) {
+            (Ok(()), false) => format!("Locked subkey {subkey} for {secs}s; 'unlock {subkey}' when you're done"),
+            (Ok(()), true) => format!("Unlocked subkey {subkey}"),
+            (Err(e), _) => format!("Not changed: {e}"),
+        }
+    }
+
+    async fn show(&self, rc: &Dht, record: &RecordKey, lock_subkey: ValueSubkey) -> String {
+        let table = match self.table(rc, record, lock_subkey).await {
+            Ok(table) => table,
+            Err(e) => return e,
+        };
+        let now = crate::audit::now_ms() as u64;
+        let mut lines = vec![format!("Locks (table in subkey {lock_subkey}):")];
+        for (subkey, lock) in &table.locks {
+            if table.live(*subkey, now).is_some() {
+                let who = if lock.holder == self.holder { "us" } else { lock.name.as_str() };
+                lines.push(format!("  subkey {subkey:<4} {who:<16} {}s left", secs_left(lock, now)));
+            }
+        }
+        if lines.len() == 1 {
+            lines.push("  none held".to_string());
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryDht;
+    use std::sync::Arc;
+
+    fn locks(name: &str) -> Locks {
+        Locks {
+            subkey: Some(3),
+            holder: format!("VLD0:{name}"),
+            name: name.to_string(),
+            default_secs: 60,
+            writer: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn a_lock_keeps_others_off_until_unlocked_or_expired() {
+        let rc = Dht::with_backend(Arc::new(MemoryDht::new()), None, false, None);
+        let record = rc.create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(4).unwrap(), None).await.unwrap().key();
+        let (alice, bob) = (locks("alice"), locks("bob"));
+
+        assert!(alice.command(&rc, &record, "2", false).await.starts_with("Locked subkey 2 for 60s"));
+        assert!(alice.may_write(&rc, &record, 2).await.is_ok());
+        assert!(bob.may_write(&rc, &record, 2).await.unwrap_err().contains("locked by alice"));
+        assert!(bob.command(&rc, &record, "2 30", false).await.contains("locked by alice"));
+        assert!(bob.command(&rc, &record, "2", true).await.contains("locked by alice"));
+        assert!(bob.may_write(&rc, &record, 1).await.is_ok());
+        assert_eq!(bob.command(&rc, &record, "3", false).await, "Subkey 3 holds the lock table itself");
+
+        assert_eq!(alice.command(&rc, &record, "2", true).await, "Unlocked subkey 2");
+        assert!(bob.command(&rc, &record, "2", false).await.starts_with("Locked"));
+        assert!(alice.command(&rc, &record, "", false).await.contains("bob"));
+
+        // a lock that has run out is nobody's
+        let mut table = LockTable::default();
+        table.locks.insert(
+            1,
+            Lock {
+                holder: "VLD0:bob".to_string(),
+                name: "bob".to_string(),
+                expires_ms: 1,
+            },
+        );
+        rc.set_dht_value(record.clone(), 3, table.encode(), None).await.unwrap();
+        assert!(alice.may_write(&rc, &record, 1).await.is_ok());
+        assert_eq!(alice.command(&rc, &record, "1", true).await, "Subkey 1 isn't locked");
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use veilid_core::*;
+
+use crate::audit::{self, AuditEntry};
+use crate::contacts::Contacts;
+use crate::nicknames::short_key;
+use crate::profile::ProfileCard;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`logs follow <contact>`: another node's audit log, live, at this prompt.
+//
+//	For debugging the alt node from the default node's console during a
+//	demo (or the other way round). The follower sends a Follow with its
+//	profile card over app_message to the route the contact gave us; the
+//	other node checks the card, and if its key is one of its own contacts,
+//	sends back the last BACKLOG entries of its audit log and then each new
+//	one as it's written, to the route on the card, for FOLLOW_FOR. Lines
+//	arrive as "[logs alice] ..." wherever the prompt is.
+//
+//	  logs follow alice      start (or, after FOLLOW_FOR, start again)
+//	  logs stop alice        ask alice to stop sending
+//
+//	Nodes only stream to keys they've met (profile.rs swaps cards when the
+//	alt node joins), since the audit log shows every record and subkey the
+//	node touches.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// So a log message can be told apart from cards and anything else.
+const LOGS_MAGIC: &[u8] = b"VXLOGS1\n";
+// entries sent straight away, before the new ones
+const BACKLOG: usize = 20;
+const FOLLOW_FOR: Duration = Duration::from_secs(10 * 60);
+const POLL_EVERY: Duration = Duration::from_secs(1);
+// per message, to stay well inside app_message's size limit
+const LINES_PER_MESSAGE: usize = 20;
+const LINE_WIDTH: usize = 300;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum LogMessage {
+    // "send me your log", with who's asking and where to send it
+    Follow(ProfileCard),
+    Stop(ProfileCard),
+    // `from` is the sender's public key
+    Lines { from: String, lines: Vec<String> },
+}
+
+impl LogMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = LOGS_MAGIC.to_vec();
+        out.extend(serde_json::to_vec(self).expect("log message serializes"));
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Option<LogMessage> {
+        serde_json::from_slice(data.strip_prefix(LOGS_MAGIC)?).ok()
+    }
+}
+
+// Quick check for the update callback, before any decoding.
+pub fn is_log_message(data: &[u8]) -> bool {
+    data.starts_with(LOGS_MAGIC)
+}
+
+// One audit entry as a line, the way `audit show` prints it.
+pub fn format_entry(e: &AuditEntry) -> String {
+    let outcome = match (&e.result, &e.error) {
+        (_, Some(err)) => format!("ERROR {err}"),
+        (Some(res), None) => res.clone(),
+        (None, None) => "ok".to_string(),
+    };
+    let line = format!("#{} {} {}ms {} -> {outcome}", e.seq, e.op, e.latency_ms, e.params);
+    if line.chars().count() > LINE_WIDTH {
+        format!("{}…", line.chars().take(LINE_WIDTH - 1).collect::<String>())
+    } else {
+        line
+    }
+}
+
+// The streams this node is sending, and what it needs to answer requests.
+pub struct LogStreams {
+    audit_path: PathBuf,
+    // our public key, for the Lines we send
+    public_key: String,
+    api: VeilidAPI,
+    rc: RoutingContext,
+    // by the follower's public key
+    sending: HashMap<String, JoinHandle<()>>,
+}
+
+impl LogStreams {
+    pub fn new(audit_path: PathBuf, mine: &ProfileCard, api: VeilidAPI, rc: RoutingContext) -> LogStreams {
+        LogStreams {
+            audit_path,
+            public_key: mine.public_key.clone(),
+            api,
+            rc,
+            sending: HashMap::new(),
+        }
+    }
+
+    async fn send(&self, blob: Vec<u8>, msg: &LogMessage) -> VeilidAPIResult<()> {
+        let route = self.api.import_remote_private_route(blob)?;
+        self.rc.app_message(Target::RouteId(route), msg.encode()).await
+    }
+
+    // `logs follow|stop <contact>` at the prompt.
+    pub async fn command(&mut self, args: &str, contacts: &Contacts, mine: &ProfileCard) -> String {
+        const USAGE: &str = "Usage: logs follow|stop <contact>";
+        let (verb, name) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+        let name = name.trim();
+        if name.is_empty() {
+            return USAGE.to_string();
+        }
+        let msg = match verb {
+            "follow" => LogMessage::Follow(mine.clone()),
+            "stop" => LogMessage::Stop(mine.clone()),
+            _ => return USAGE.to_string(),
+        };
+        let Some(contact) = contacts.get(name).or_else(|| contacts.by_key(name)) else {
+            return format!("No contact called {name} (see `contact list`)");
+        };
+        let Some(blob) = contact.card.as_ref().and_then(|c| c.route_blob()) else {
+            return format!("{name} hasn't sent us a route to reach them on");
+        };
+        if mine.route.is_none() {
+            return "We have no private route for the lines to come back on".to_string();
+        }
+        match (self.send(blob, &msg).await, verb) {
+            (Ok(()), "follow") => format!(
+                "Asked {name} for their log; lines show up as [logs {name}] for the next {} minutes",
+                FOLLOW_FOR.as_secs() / 60
+            ),
+            (Ok(()), _) => format!("Asked {name} to stop sending their log"),
+            (Err(e), _) => format!("Couldn't reach {name}: {e}"),
+        }
+    }
+
+    // A log message came in. Gives back what to print.
+    pub async fn receive(&mut self, data: &[u8], contacts: &Contacts) -> String {
+        match LogMessage::decode(data) {
+            Some(LogMessage::Lines { from, lines }) => {
+                let name = contacts.by_key(&from).map_or_else(|| short_key(&from), |c| c.name.clone());
+                lines.iter().map(|l| format!("[logs {name}] {l}")).collect::<Vec<_>>().join("\n")
+            }
+            Some(LogMessage::Follow(card)) => {
+                let Some(contact) = self.checked(&card, contacts) else {
+                    return format!("[logs] '{}' asked for our log, but isn't a contact; ignored", card.nickname);
+                };
+                let Some(blob) = card.route_blob() else {
+                    return format!("[logs] {contact} asked for our log without a route to send it on");
+                };
+                let task = tokio::spawn(stream(
+                    self.api.clone(),
+                    self.rc.clone(),
+                    blob,
+                    self.audit_path.clone(),
+                    self.public_key.clone(),
+                ));
+                if let Some(old) = self.sending.insert(card.public_key.clone(), task) {
+                    old.abort();
+                }
+                format!("[logs] sending our log to {contact} for {} minutes", FOLLOW_FOR.as_secs() / 60)
+            }
+            Some(LogMessage::Stop(card)) => {
+                let Some(contact) = self.checked(&card, contacts) else {
+                    return format!("[logs] '{}' asked us to stop, but isn't a contact; ignored", card.nickname);
+                };
+                match self.sending.remove(&card.public_key) {
+                    Some(task) => {
+                        task.abort();
+                        format!("[logs] stopped sending our log to {contact}")
+                    }
+                    None => format!("[logs] {contact} asked us to stop, but we weren't sending"),
+                }
+            }
+            None => "[logs] got a log message that doesn't decode".to_string(),
+        }
+    }
+
+    // The contact's name, if the card is signed and its key is someone we know.
+    fn checked(&self, card: &ProfileCard, contacts: &Contacts) -> Option<String> {
+        card.verify().ok()?;
+        contacts.by_key(&card.public_key).map(|c| c.name.clone())
+    }
+}
+
+impl Drop for LogStreams {
+    fn drop(&mut self) {
+        for task in self.sending.values() {
+            task.abort();
+        }
+    }
+}
+
+// Send the tail of the audit log, then whatever gets added, until FOLLOW_FOR
+// is up or the route stops working.
+async fn stream(api: VeilidAPI, rc: RoutingContext, blob: Vec<u8>, path: PathBuf, from: String) {
+    let Ok(route) = api.import_remote_private_route(blob) else {
+        return;
+    };
+    let until = tokio::time::Instant::now() + FOLLOW_FOR;
+    let mut next_seq = audit::read_entries(&path)
+        .ok()
+        .and_then(|entries| entries.last().map(|e| (e.seq + 1).saturating_sub(BACKLOG as u64)))
+        .unwrap_or(0);
+    while tokio::time::Instant::now() < until {
+        let mut fresh = Vec::new();
+        for e in audit::read_entries(&path).unwrap_or_default() {
+            if e.seq >= next_seq {
+                next_seq = e.seq + 1;
+                fresh.push(format_entry(&e));
+            }
+        }
+        for lines in fresh.chunks(LINES_PER_MESSAGE) {
+            let msg = LogMessage::Lines {
+                from: from.clone(),
+                lines: lines.to_vec(),
+            };
+            if rc.app_message(Target::RouteId(route.clone()), msg.encode()).await.is_err() {
+                return;
+            }
+        }
+        tokio::time::sleep(POLL_EVERY).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip_and_lines_stay_short() {
+        let kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let card = ProfileCard::new(&kp, "alice", &["watch"], Some(b"route")).unwrap();
+        for msg in [
+            LogMessage::Follow(card.clone()),
+            LogMessage::Stop(card),
+            LogMessage::Lines {
+                from: kp.key().to_string(),
+                lines: vec!["#1 get 12ms {} -> ok".to_string()],
+            },
+        ] {
+            let data = msg.encode();
+            assert!(is_log_message(&data));
+            assert_eq!(LogMessage::decode(&data), Some(msg));
+        }
+        assert_eq!(LogMessage::decode(b"VXCARD1\n{}"), None);
+
+        let entry = AuditEntry {
+            seq: 7,
+            timestamp_ms: 0,
+            op: "set".to_string(),
+            params: serde_json::json!({ "text": "x".repeat(500) }),
+            result: None,
+            error: Some("timed out".to_string()),
+            latency_ms: 40,
+            prev_hash: String::new(),
+            hash: String::new(),
+        };
+        let line = format_entry(&entry);
+        assert!(line.starts_with("#7 set 40ms {\"text\":\"xxx"), "{line}");
+        assert_eq!(line.chars().count(), LINE_WIDTH);
+    }
+}
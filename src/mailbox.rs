@@ -0,0 +1,365 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::dht::Dht;
+use crate::envelope::{Codec, Envelope, FLAG_ENCRYPTED};
+use crate::receipts::{Receipts, Status};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	A store-and-forward mailbox: a range of inbox subkeys on the default
+//	node's record that anyone who joins can leave a letter in, whether or
+//	not the default node is listening at the time. The DHT keeps the letter
+//	until the owner comes round to drain it.
+//
+//	The inbox subkeys belong to a "drop" member whose keypair is published
+//	in the record's metadata block (see metadata.rs), so every reader of the
+//	record can write there. Letters are sealed with HPKE to the recipient's
+//	key, so they can't read each other's mail, only overwrite it.
+//
+//	A sender starts at a slot picked from the letter's id and takes the
+//	first one that's empty or tombstoned. The owner drains the inbox every
+//	POLL_EVERY, and on `inbox`: every slot whose seq is newer than the last
+//	one it acknowledged is opened, then acknowledged by writing a tombstone
+//	over it, which frees the slot for the next sender. The letters wait in
+//	memory until `inbox` shows them; receipts.rs tells the sender how far
+//	each one got.
+//
+//	The "from" on a letter is whatever the sender put there: the drop key is
+//	shared, so the DHT can't say who really wrote it.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// How often the owner looks for new mail without being asked.
+pub const POLL_EVERY: Duration = Duration::from_secs(30);
+
+// Where the mailbox is, as the metadata block tells it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MailboxInfo {
+    // the drop member's keypair, which writes the inbox subkeys
+    pub drop_writer: String,
+    // whose key letters are sealed to
+    pub recipient: String,
+    // inclusive
+    pub first_subkey: ValueSubkey,
+    pub last_subkey: ValueSubkey,
+    // the owner subkey its delivery receipts go in (older blocks don't say)
+    #[serde(default)]
+    pub receipts_subkey: Option<ValueSubkey>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Letter {
+    #[serde(default)]
+    pub id: String,
+    pub from: String,
+    pub nickname: String,
+    pub text: String,
+    pub sent_ms: u64,
+}
+
+// A letter still in the inbox, as it came out of the DHT.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pending {
+    pub subkey: ValueSubkey,
+    pub seq: u32,
+    pub sealed: Vec<u8>,
+}
+
+impl MailboxInfo {
+    pub fn writer(&self) -> Result<KeyPair, String> {
+        self.drop_writer
+            .parse()
+            .map_err(|e| format!("the mailbox's drop key is malformed: {e}"))
+    }
+
+    fn write_as(&self) -> Result<SetDHTValueOptions, String> {
+        Ok(SetDHTValueOptions {
+            writer: Some(self.writer()?),
+            allow_offline: None,
+        })
+    }
+
+    fn slots(&self) -> impl Iterator<Item = ValueSubkey> {
+        self.first_subkey..=self.last_subkey
+    }
+}
+
+// The slot a letter tries first, so letters mostly start apart.
+fn first_choice(info: &MailboxInfo, id: &str) -> ValueSubkey {
+    let count = info.last_subkey - info.first_subkey + 1;
+    let hash = blake3::hash(id.as_bytes());
+    info.first_subkey + u32::from_le_bytes(hash.as_bytes()[..4].try_into().unwrap()) % count
+}
+
+// The letter, sealed to `recipient` and wrapped in an envelope. The record
+// key goes in as associated data, so it only opens in this record's inbox.
+pub fn seal(api: &VeilidAPI, record: &RecordKey, recipient: &PublicKey, letter: &Letter) -> VeilidAPIResult<Vec<u8>> {
+    let crypto = api.crypto()?;
+    let vcrypto = crypto
+        .get(CRYPTO_KIND_VLD0)
+        .ok_or_else(|| VeilidAPIError::generic("VLD0 crypto isn't available"))?;
+    let plaintext = serde_json::to_vec(letter).map_err(VeilidAPIError::internal)?;
+    let sealed = vcrypto.hpke_seal(
+        &vcrypto.encapsulation_key_from_signing_key(recipient)?,
+        record.to_string().as_bytes(),
+        &plaintext,
+    )?;
+    let mut env = Envelope::new(Codec::Json, sealed);
+    env.flags = FLAG_ENCRYPTED;
+    env.written_ms = Some(letter.sent_ms);
+    Ok(env.encode())
+}
+
+pub fn open(api: &VeilidAPI, record: &RecordKey, secret: &SecretKey, sealed: &[u8]) -> VeilidAPIResult<Letter> {
+    let crypto = api.crypto()?;
+    let vcrypto = crypto
+        .get(CRYPTO_KIND_VLD0)
+        .ok_or_else(|| VeilidAPIError::generic("VLD0 crypto isn't available"))?;
+    let plaintext = vcrypto.hpke_open(
+        &vcrypto.decapsulation_key_from_signing_secret(secret)?,
+        record.to_string().as_bytes(),
+        sealed,
+    )?;
+    serde_json::from_slice(&plaintext).map_err(|e| VeilidAPIError::parse_error("not a letter", e.to_string()))
+}
+
+// Put `value` (the sealed letter `id`) in the first free slot. Gives back the slot.
+pub async fn post(
+    rc: &Dht,
+    record: &RecordKey,
+    info: &MailboxInfo,
+    id: &str,
+    value: Vec<u8>,
+) -> Result<ValueSubkey, String> {
+    let start = first_choice(info, id);
+    let mut order: Vec<ValueSubkey> = info.slots().filter(|s| *s >= start).collect();
+    order.extend(info.slots().filter(|s| *s < start));
+    for slot in order {
+        let taken = match rc.get_dht_value(record.clone(), slot, true).await {
+            Ok(None) => false,
+            Ok(Some(v)) => !Envelope::decode(v.data()).is_ok_and(|env| env.is_tombstone()),
+            Err(e) => return Err(format!("couldn't read inbox slot {slot}: {e}")),
+        };
+        if taken {
+            continue;
+        }
+        return match rc.set_dht_value(record.clone(), slot, value, Some(info.write_as()?)).await {
+            Ok(None) => Ok(slot),
+            // someone got there first
+            Ok(Some(_)) => Err(format!("inbox slot {slot} was taken while we wrote, try again")),
+            Err(e) => Err(format!("couldn't write inbox slot {slot}: {e}")),
+        };
+    }
+    Err("the inbox is full, try again once its owner has read some".to_string())
+}
+
+// The owner's side: which seq it has acknowledged in each slot, the letters
+// it hasn't shown yet, and the receipts it has given.
+#[derive(Default)]
+pub struct Inbox {
+    acked: BTreeMap<ValueSubkey, u32>,
+    unread: Vec<Letter>,
+    receipts: Receipts,
+}
+
+impl Inbox {
+    pub fn new() -> Inbox {
+        Inbox::default()
+    }
+
+    // Letters that arrived since the last ack, oldest slot first.
+    pub async fn drain(&mut self, rc: &Dht, record: &RecordKey, info: &MailboxInfo) -> VeilidAPIResult<Vec<Pending>> {
+        let mut found = Vec::new();
+        for slot in info.slots() {
+            let Some(value) = rc.get_dht_value(record.clone(), slot, true).await? else {
+                continue;
+            };
+            let seq = u32::from(value.seq());
+            if self.acked.get(&slot).is_some_and(|acked| seq <= *acked) {
+                continue;
+            }
+            match Envelope::decode(value.data()) {
+                // our own ack, or nothing there any more
+                Ok(env) if env.is_tombstone() => {
+                    self.acked.insert(slot, seq);
+                }
+                Ok(env) if env.flags & FLAG_ENCRYPTED != 0 => found.push(Pending {
+                    subkey: slot,
+                    seq,
+                    sealed: env.body,
+                }),
+                // not a letter: treat it as read, the next sender can have the slot
+                _ => {
+                    self.acked.insert(slot, seq);
+                    let _ = rc
+                        .set_dht_value(record.clone(), slot, Envelope::tombstone().encode(), info.write_as().ok())
+                        .await;
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    // Mark `letter` read and free its slot. If the tombstone doesn't make it,
+    // the letter still won't be shown again.
+    pub async fn ack(&mut self, rc: &Dht, record: &RecordKey, info: &MailboxInfo, letter: &Pending) -> Result<(), String> {
+        self.acked.insert(letter.subkey, letter.seq);
+        match rc
+            .set_dht_value(record.clone(), letter.subkey, Envelope::tombstone().encode(), Some(info.write_as()?))
+            .await
+        {
+            Ok(None) => {
+                self.acked.insert(letter.subkey, letter.seq + 1);
+                Ok(())
+            }
+            // a new letter landed on top before we acked: leave it for the next drain
+            Ok(Some(_)) => Ok(()),
+            Err(e) => Err(format!("couldn't free inbox slot {}: {e}", letter.subkey)),
+        }
+    }
+
+    // Take everything new out of the inbox and mark it delivered. Gives back
+    // how many letters came in, and anything that went wrong on the way.
+    pub async fn fetch(
+        &mut self,
+        rc: &Dht,
+        api: &VeilidAPI,
+        record: &RecordKey,
+        info: &MailboxInfo,
+        secret: &SecretKey,
+    ) -> (usize, Vec<String>) {
+        let pending = match self.drain(rc, record, info).await {
+            Ok(pending) => pending,
+            Err(e) => return (0, vec![format!("Couldn't read the inbox: {e}")]),
+        };
+        let mut problems = Vec::new();
+        let mut received = 0;
+        for letter in &pending {
+            match open(api, record, secret, &letter.sealed) {
+                Ok(l) => {
+                    self.receipts.mark(&l.id, Status::Delivered, crate::audit::now_ms() as u64);
+                    self.unread.push(l);
+                    received += 1;
+                }
+                Err(e) => problems.push(format!("[mail] slot {}: couldn't open it ({e})", letter.subkey)),
+            }
+            if let Err(e) = self.ack(rc, record, info, letter).await {
+                problems.push(format!("[mail] {e}"));
+            }
+        }
+        if received > 0 {
+            problems.extend(self.publish_receipts(rc, record, info).await);
+        }
+        (received, problems)
+    }
+
+    async fn publish_receipts(&self, rc: &Dht, record: &RecordKey, info: &MailboxInfo) -> Option<String> {
+        let subkey = info.receipts_subkey?;
+        let e = self.receipts.publish(rc, record, subkey).await.err()?;
+        Some(format!("[mail] couldn't write the receipts to subkey {subkey}: {e}"))
+    }
+
+    // `inbox` at the prompt: fetch, then show every unread letter and mark it read.
+    pub async fn check(
+        &mut self,
+        rc: &Dht,
+        api: &VeilidAPI,
+        record: &RecordKey,
+        info: &MailboxInfo,
+        secret: &SecretKey,
+    ) -> String {
+        let (_, mut lines) = self.fetch(rc, api, record, info, secret).await;
+        if self.unread.is_empty() {
+            lines.push("No new mail".to_string());
+            return lines.join("\n");
+        }
+        let now = crate::audit::now_ms() as u64;
+        for l in std::mem::take(&mut self.unread) {
+            lines.push(format!("[mail] from {} ({}): {}", l.nickname, l.from, l.text));
+            self.receipts.mark(&l.id, Status::Read, now);
+        }
+        lines.extend(self.publish_receipts(rc, record, info).await);
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{member_id, MemoryDht};
+    use std::sync::Arc;
+
+    async fn mailbox(rc: &Dht) -> (RecordKey, MailboxInfo) {
+        let drop = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let schema = DHTSchema::smpl(
+            1,
+            vec![DHTSchemaSMPLMember {
+                m_key: member_id(&drop.key()),
+                m_cnt: 2,
+            }],
+        )
+        .unwrap();
+        let desc = rc.create_dht_record(CRYPTO_KIND_VLD0, schema, None).await.unwrap();
+        let info = MailboxInfo {
+            drop_writer: drop.to_string(),
+            recipient: "VLD0:whoever".to_string(),
+            first_subkey: 1,
+            last_subkey: 2,
+            receipts_subkey: None,
+        };
+        (desc.key(), info)
+    }
+
+    fn sealed(text: &str) -> Vec<u8> {
+        let mut env = Envelope::new(Codec::Json, text.as_bytes().to_vec());
+        env.flags = FLAG_ENCRYPTED;
+        env.encode()
+    }
+
+    #[tokio::test]
+    async fn letters_are_drained_once_and_free_their_slot() {
+        let rc = Dht::with_backend(Arc::new(MemoryDht::new()), None, false, None);
+        let (record, info) = mailbox(&rc).await;
+        let mut inbox = Inbox::new();
+
+        let a = post(&rc, &record, &info, "alice", sealed("one")).await.unwrap();
+        let b = post(&rc, &record, &info, "alice", sealed("two")).await.unwrap();
+        assert_ne!(a, b);
+        assert!(post(&rc, &record, &info, "bob", sealed("three")).await.unwrap_err().contains("full"));
+
+        let pending = inbox.drain(&rc, &record, &info).await.unwrap();
+        assert_eq!(pending.len(), 2);
+        for letter in &pending {
+            inbox.ack(&rc, &record, &info, letter).await.unwrap();
+        }
+        assert!(inbox.drain(&rc, &record, &info).await.unwrap().is_empty());
+
+        // acked slots are free again, and the new letter is new to the owner
+        let c = post(&rc, &record, &info, "bob", sealed("three")).await.unwrap();
+        let pending = inbox.drain(&rc, &record, &info).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].subkey, c);
+        assert_eq!(pending[0].sealed, b"three");
+    }
+
+    #[tokio::test]
+    async fn a_letter_is_not_shown_twice_even_if_the_ack_is_lost() {
+        let rc = Dht::with_backend(Arc::new(MemoryDht::new()), None, false, None);
+        let (record, mut info) = mailbox(&rc).await;
+        let mut inbox = Inbox::new();
+        post(&rc, &record, &info, "alice", sealed("hello")).await.unwrap();
+
+        let pending = inbox.drain(&rc, &record, &info).await.unwrap();
+        // the tombstone can't be written with the wrong key
+        let real_writer = info.drop_writer.clone();
+        info.drop_writer = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap().to_string();
+        assert!(inbox.ack(&rc, &record, &info, &pending[0]).await.is_err());
+        info.drop_writer = real_writer;
+
+        assert!(inbox.drain(&rc, &record, &info).await.unwrap().is_empty());
+    }
+}
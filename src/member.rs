@@ -0,0 +1,308 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::audit::{self, AuditLog};
+use crate::conflict;
+use crate::dht::Dht;
+use crate::exit::Kind;
+use crate::guard::WriteGuard;
+use crate::i18n::t;
+use crate::input::{Command, Destination, Input, Inputs};
+use crate::keybundle;
+use crate::locks::Locks;
+use crate::node::{self, VeilidNode};
+use crate::nicknames::Nicknames;
+use crate::payloads::Registry;
+use crate::profile::ProfileCard;
+use crate::recovery;
+use crate::record_manager::RecordManager;
+use crate::repl::{self, Repl};
+use crate::transcript::say;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The member node (menu option 3, --mode member): a third node that
+//	writes to the default node's record with a keypair of its own, so the
+//	record has two SMPL members writing side by side.
+//
+//	An SMPL record's members are fixed when it's made, so the member node
+//	can't join one that exists. It leaves its public key in
+//	member_node.json in the data folder instead, and the next record the
+//	default node makes has a member for it (member_subkeys subkeys, after
+//	the mailbox's):
+//
+//	  1: veilid_test_node --mode member     makes its key, finds no room
+//	  2: veilid_test_node --mode default    makes a record with room for it
+//	  3: veilid_test_node --mode member     writes to its own subkeys
+//
+//	Its keypair stays in its own table store; only the public half goes in
+//	the file. On a record made before it had one, it writes with the member
+//	key the key file grants instead (share_grant write-own-subkeys or
+//	admin), if there is one.
+//
+//	At its prompt a line is written to its first subkey, and `:sub N`,
+//	`N:text` and `:all text` work as they do at the default prompt: the
+//	same payload types, locks, numbering and m/y/t/e answer to a conflict
+//	(see conflict.rs). Every
+//	line is a write, so on a record this data folder didn't make it asks
+//	once before the prompt starts, unless --yes (see guard.rs).
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const FILE_NAME: &str = "member_node.json";
+const TABLE: &str = "member";
+const COL_KEYPAIR: u32 = 0;
+const KEY: &[u8] = b"keypair";
+
+#[derive(Serialize, Deserialize)]
+struct Announcement {
+    public: String,
+}
+
+// The key a member node has left for the default node, if one has.
+pub fn announced_key(data_dir: &Path) -> Result<Option<PublicKey>, String> {
+    let path = data_dir.join(FILE_NAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let damaged = |e: String| format!("{} is damaged: {e}", path.to_string_lossy());
+    let text = fs::read_to_string(&path).map_err(|e| damaged(e.to_string()))?;
+    let announcement: Announcement = serde_json::from_str(&text).map_err(|e| damaged(e.to_string()))?;
+    announcement.public.parse::<PublicKey>().map(Some).map_err(|e| damaged(e.to_string()))
+}
+
+fn announce(data_dir: &Path, key: &PublicKey) -> io::Result<()> {
+    let announcement = Announcement { public: key.to_string() };
+    fs::write(data_dir.join(FILE_NAME), serde_json::to_string_pretty(&announcement)?)
+}
+
+// Ours from last time, or a new one (kept for next time).
+async fn own_keypair(api: &VeilidAPI) -> VeilidAPIResult<KeyPair> {
+    let db = api.table_store()?.open(TABLE, 1).await?;
+    if let Some(kp) = db.load_json::<String>(COL_KEYPAIR, KEY).await?.and_then(|s| s.parse().ok()) {
+        return Ok(kp);
+    }
+    let kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0)?;
+    db.store_json(COL_KEYPAIR, KEY, &kp.to_string()).await?;
+    Ok(kp)
+}
+
+pub async fn run(node_options: &node::NodeOptions<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    let (options, config) = (node_options.options, node_options.config);
+    let data_dir = &node_options.data_dir;
+    let running = crate::preflight::run(config, &node_options.storage_dir, "member", &node_options.namespace)?;
+
+    let node = VeilidNode::start_attached(config, &node_options.storage_dir, &node_options.namespace, |_| {}).await?;
+    let member_kp = own_keypair(node.api()).await?;
+    announce(data_dir, &member_kp.key())?;
+    say!("{}", t!("member-key", key = member_kp.key().to_string(), file = FILE_NAME));
+
+    let keys = match keybundle::load_file(&node_options.key_file) {
+        Ok(keys) => keys,
+        Err(e) => {
+            say!("{}", t!("member-no-record", error = e.to_string()));
+            node.shutdown().await;
+            running.finish();
+            return Ok(());
+        }
+    };
+
+    let audit = Arc::new(AuditLog::open(&data_dir.join(audit::log_file_name("member")))?);
+    let rc = Dht::new(node.routing_context().get(), Some(audit), options.dry_run, options.chaos.clone());
+    let records = RecordManager::new(rc.clone());
+    let record = records.open_at_startup(keys.record_key.clone(), Some(member_kp.clone())).await?;
+    let desc = record.descriptor().clone();
+    let record_key = desc.key();
+    let (schema, owner) = (desc.schema(), desc.owner());
+
+    // our own key if the record has room for it, otherwise the one the key file grants
+    let candidates: Vec<&KeyPair> = std::iter::once(&member_kp).chain(keys.grant.writer.iter()).collect();
+    let (mut subkey, writer) = match crate::schema::pick_writer(&schema, &owner, &candidates, None) {
+        Ok((subkey, writer)) => (subkey, writer.clone()),
+        Err(_) => {
+            drop(record);
+            records.close_all().await;
+            node.shutdown().await;
+            return Err(Kind::Credential.fail(t!("member-no-room", key = record_key.to_string())));
+        }
+    };
+    let ranges = crate::schema::writable_subkeys(&schema, &owner, &writer.key());
+    let who = if writer.key() == member_kp.key() { t!("member-own-key") } else { t!("member-granted-key") };
+    say!(
+        "{}",
+        t!(
+            "member-writes-as",
+            who = who,
+            subkeys = crate::schema::describe_ranges(&ranges),
+            key = record_key.to_string()
+        )
+    );
+    Nicknames::load(data_dir)?.set(&member_kp.key(), "member-node")?;
+    let opts = SetDHTValueOptions {
+        writer: Some(writer.clone()),
+        allow_offline: None,
+    };
+    // values are encoded as the default node's are, payload types and codecs included
+    let registry = Registry::from_config(config)?;
+    // a subkey someone has locked is left alone, as at the other prompts (see locks.rs)
+    let card = ProfileCard::new(&writer, &config.profile_name("member"), &["write"], None)?;
+    let locks = Locks::new(config, &card, None);
+    // numbered like the default node's lines, carrying on from the last run (see ordering.rs)
+    let mut sent_count: u64 = recovery::load_sent(node.api()).await?;
+    // a write that lost to a newer value, for the m/y/t/e answer straight after
+    let mut pending_conflict: Option<conflict::Conflict> = None;
+
+    if !WriteGuard::new(data_dir, &record_key, options.yes)?.confirm(&t!("shared-write-member"))? {
+        say!("{}", t!("not-written"));
+        drop(record);
+        records.close_all().await;
+        node.shutdown().await;
+        running.finish();
+        return Ok(());
+    }
+
+    let repl = Repl::start(repl::history_file(data_dir, "member"), "member> ", vec![":sub", ":all", "field"], false)?;
+    repl.set_max_subkey(schema.max_subkey());
+    let mut inputs = Inputs::new(repl);
+    say!("{}", t!("member-help", subkey = subkey));
+    loop {
+        let input = inputs.next().await;
+        let unanswered = pending_conflict.take();
+        let text = match input {
+            Input::Command(Command::Other(text)) => text,
+            // field <name> <text>: our copy, where the roster says this writer keeps it
+            Input::Command(Command::Field(args)) => {
+                let (name, value) = args.split_once(' ').unwrap_or((args.as_str(), ""));
+                let field_subkey = match crate::fields::own_subkey(&rc, &record_key, &[&writer], name).await {
+                    Ok((field_subkey, _)) => field_subkey,
+                    Err(e) => {
+                        say!("{}", t!("not-set", error = e));
+                        continue;
+                    }
+                };
+                if let Err(e) = locks.may_write(&rc, &record_key, field_subkey).await {
+                    say!("{}", t!("not-set", error = e));
+                    continue;
+                }
+                let value = match registry.encode(field_subkey, value.trim()) {
+                    Ok(value) => value,
+                    Err(e) => {
+                        say!("{}", t!("not-set", error = e));
+                        continue;
+                    }
+                };
+                match rc.set_dht_value(record_key.clone(), field_subkey, value.encode(), Some(opts.clone())).await {
+                    Ok(_) => say!("{}", t!("field-set", name = name, subkey = field_subkey)),
+                    Err(e) => say!("{}", t!("field-set-failed", name = name, error = e.to_string())),
+                }
+                continue;
+            }
+            Input::Command(_) => {
+                say!("{}", t!("member-help", subkey = subkey));
+                continue;
+            }
+            Input::Interrupted | Input::Closed => break,
+        };
+        if let (Some(conflict), Some(choice)) = (unanswered, conflict::Choice::parse(&text)) {
+            let (outcome, again) = conflict.resolve(&rc, &record_key, choice).await;
+            say!("{outcome}");
+            pending_conflict = again;
+            continue;
+        }
+        // the same as at the default prompt, with the subkeys this writer has
+        let (targets, line) = match Destination::parse(&text) {
+            Err(e) => {
+                say!("{}", t!("not-written-because", error = e));
+                continue;
+            }
+            Ok(Destination::Switch(wanted)) => {
+                match crate::schema::pick_writer(&schema, &owner, &[&writer], Some(wanted)) {
+                    Ok((picked, _)) => {
+                        subkey = picked;
+                        say!("{}", t!("subkey-switched", subkey = subkey));
+                    }
+                    Err(e) => say!("{}", t!("subkey-kept", subkey = subkey, error = e)),
+                }
+                continue;
+            }
+            Ok(Destination::Current(line)) => (vec![subkey], line),
+            Ok(Destination::One(wanted, line)) => match crate::schema::pick_writer(&schema, &owner, &[&writer], Some(wanted)) {
+                Ok((picked, _)) => (vec![picked], line),
+                Err(e) => {
+                    say!("{}", t!("not-written-because", error = e));
+                    continue;
+                }
+            },
+            Ok(Destination::All(line)) => (ranges.iter().flat_map(|&(first, last)| first..=last).collect(), line),
+        };
+        for target in targets {
+            if let Err(e) = locks.may_write(&rc, &record_key, target).await {
+                say!("{}", t!("not-written-because", error = e));
+                continue;
+            }
+            let mut value = match registry.encode(target, line) {
+                Ok(value) => value,
+                Err(e) => {
+                    say!("{}", t!("not-written-because", error = e));
+                    continue;
+                }
+            };
+            value.sender_seq = Some(sent_count + 1);
+            if let Some(ttl) = config.value_ttl() {
+                value = value.expiring_after(ttl);
+            }
+            match conflict::write(&rc, &record_key, target, value, Some(opts.clone())).await {
+                Err(e) => {
+                    say!("{}", t!("write-failed", subkey = target, error = e.to_string()));
+                    continue;
+                }
+                // someone else's got there first; the number is used either way
+                Ok(Some(conflict)) => {
+                    sent_count += 1;
+                    let _ = recovery::save_sent(node.api(), sent_count).await;
+                    say!("{}", conflict.render());
+                    pending_conflict = Some(conflict);
+                    continue;
+                }
+                Ok(None) => {}
+            }
+            sent_count += 1;
+            if !rc.is_dry_run() {
+                let _ = recovery::save_sent(node.api(), sent_count).await;
+                say!("{}", t!("wrote-numbered", count = sent_count, subkey = target, text = line));
+            }
+        }
+    }
+
+    drop(record);
+    records.close_all().await;
+    node.shutdown().await;
+    running.finish();
+    println!("{}", t!("shutdown-complete"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_announced_key_reads_back() {
+        let dir = std::env::temp_dir().join(format!("member-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(announced_key(&dir), Ok(None));
+
+        let kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        announce(&dir, &kp.key()).unwrap();
+        assert_eq!(announced_key(&dir), Ok(Some(kp.key())));
+
+        fs::write(dir.join(FILE_NAME), "{\"public\": \"not a key\"}").unwrap();
+        assert!(announced_key(&dir).unwrap_err().contains("damaged"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::envelope::{Codec, Envelope};
+use crate::mailbox::MailboxInfo;
+use crate::profile::ProfileCard;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The record's metadata block, kept in owner subkey 0.
+//
+//	The default node writes it when it creates the record: a title, when it
+//	was made, the schema, who may write which subkeys, and the version of
+//	this program. It's JSON in a Json envelope, so any reader can make sense
+//	of it; the alt node shows it when it joins.
+//
+//	It also carries the default node's profile card, whose private route is
+//	how a joining node first says hello (see profile.rs).
+//
+//	And, if the record has inbox subkeys, where they are and the key that
+//	writes them, so joiners can leave mail (see mailbox.rs).
+//
+//	Each roster entry also says which of its subkeys hold its copy of each
+//	shared field (see fields.rs), and the block says what type of value
+//	each typed subkey holds (see payloads.rs).
+//
+//	Records made with owner_subkeys = 0 have nowhere to put it.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub const METADATA_SUBKEY: ValueSubkey = 0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordMetadata {
+    pub title: String,
+    pub created_ms: u64,
+    pub schema: String,
+    pub members: Vec<RosterEntry>,
+    pub app_version: String,
+    // older blocks don't have one
+    #[serde(default)]
+    pub owner_card: Option<ProfileCard>,
+    #[serde(default)]
+    pub mailbox: Option<MailboxInfo>,
+    // subkey -> the payload type it holds (see payloads.rs)
+    #[serde(default)]
+    pub payload_types: BTreeMap<ValueSubkey, String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RosterEntry {
+    pub name: String,
+    pub key: String,
+    // inclusive
+    pub first_subkey: ValueSubkey,
+    pub last_subkey: ValueSubkey,
+    // shared field name -> the subkey this writer keeps it in
+    #[serde(default)]
+    pub fields: BTreeMap<String, ValueSubkey>,
+}
+
+impl RecordMetadata {
+    pub fn new(title: &str, schema: &DHTSchema, members: Vec<RosterEntry>) -> RecordMetadata {
+        RecordMetadata {
+            title: title.to_string(),
+            created_ms: crate::audit::now_ms() as u64,
+            schema: format!("{schema:?}"),
+            members,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            owner_card: None,
+            mailbox: None,
+            payload_types: BTreeMap::new(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut env = Envelope::new(Codec::Json, serde_json::to_vec(self).expect("metadata serializes"));
+        env.written_ms = Some(self.created_ms);
+        env.encode()
+    }
+
+    // None if the value isn't a metadata block (old records, or something else in subkey 0).
+    pub fn decode(data: &[u8]) -> Option<RecordMetadata> {
+        let env = Envelope::decode(data).ok()?;
+        if env.codec != Codec::Json {
+            return None;
+        }
+        serde_json::from_slice(&env.body).ok()
+    }
+
+    // Several lines, for showing on join.
+    pub fn display(&self) -> String {
+        let mut out = format!(
+            "\"{}\" (made by version {}, {}s ago)\n  schema: {}",
+            self.title,
+            self.app_version,
+            (crate::audit::now_ms() as u64).saturating_sub(self.created_ms) / 1000,
+            self.schema
+        );
+        for m in &self.members {
+            out.push_str(&format!(
+                "\n  {} writes subkeys {}..={} ({})",
+                m.name, m.first_subkey, m.last_subkey, m.key
+            ));
+            for (field, subkey) in &m.fields {
+                out.push_str(&format!("\n    its {field} is in subkey {subkey}"));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_ignores_other_values() {
+        let schema = DHTSchema::dflt(2).unwrap();
+        let meta = RecordMetadata::new(
+            "test",
+            &schema,
+            vec![RosterEntry {
+                name: "alice".to_string(),
+                key: "VLD0:alice".to_string(),
+                first_subkey: 0,
+                last_subkey: 1,
+                fields: BTreeMap::new(),
+            }],
+        );
+        let decoded = RecordMetadata::decode(&meta.encode()).unwrap();
+        assert_eq!(decoded, meta);
+        assert!(decoded.display().contains("alice writes subkeys 0..=1"));
+
+        assert_eq!(RecordMetadata::decode(&Envelope::text("hi").encode()), None);
+        assert_eq!(RecordMetadata::decode(b"not an envelope"), None);
+    }
+}
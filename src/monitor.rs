@@ -0,0 +1,259 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use veilid_core::*;
+
+use crate::audit::{log_file_name, AuditLog};
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::dht::Dht;
+use crate::envelope;
+use crate::exit::Kind;
+use crate::nicknames::Nicknames;
+use crate::node::VeilidNode;
+use crate::page::{Page, View};
+use crate::progress;
+use crate::shortcode::{self, ShortcodeBook};
+use crate::watch::WatchSet;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`monitor <record>`: a live, read-only view of a record, for the people
+//	watching a demo rather than running it.
+//
+//	The record is opened with no writer, inspected, read in full and
+//	watched; the screen is redrawn with every subkey's seq, writer and
+//	value each time a change comes in, and everything is read again every
+//	REFRESH_EVERY in case a change was missed or the watch died.
+//
+//	The loop only ever sees a ReadOnly, which has no set or delete and
+//	doesn't hand out the Dht or routing context inside it. There's no way
+//	to write from here short of changing this file, so it's safe to leave
+//	running on a projector or give to someone to poke at.
+//
+//	It runs as a node of its own (<default_namespace>-monitor), so it can
+//	sit next to the default and alt nodes.
+//
+//	With --render and --out, each redraw also goes through a template into
+//	a file (see page.rs).
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const REFRESH_EVERY: Duration = Duration::from_secs(30);
+// values longer than this are cut short to keep one subkey to a line
+const VALUE_WIDTH: usize = 48;
+
+// The DHT calls the monitor may make: open without a writer, read, inspect, watch.
+struct ReadOnly {
+    node: VeilidNode,
+    rc: Dht,
+    changes: WatchSet,
+}
+
+impl ReadOnly {
+    async fn open(&self, record_key: RecordKey) -> VeilidAPIResult<DHTRecordDescriptor> {
+        self.rc.open_following(record_key, None).await
+    }
+
+    async fn read(&self, record_key: &RecordKey, subkey: ValueSubkey) -> VeilidAPIResult<Option<ValueData>> {
+        self.rc.get_dht_value(record_key.clone(), subkey, true).await
+    }
+
+    async fn inspect(&self, record_key: &RecordKey) -> VeilidAPIResult<DHTRecordReport> {
+        self.rc.inspect_dht_record(record_key.clone(), None, DHTReportScope::SyncGet).await
+    }
+
+    async fn watch(&self, record_key: &RecordKey) -> VeilidAPIResult<()> {
+        self.node.watch(&self.changes, record_key.clone(), ValueSubkeyRangeSet::full()).await
+    }
+
+    async fn close(self, record_key: RecordKey) {
+        let _ = self.rc.close_dht_record(record_key).await;
+        self.node.api().clone().shutdown().await;
+    }
+}
+
+// What the screen shows for one subkey.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Row {
+    pub seq: Option<ValueSeqNum>,
+    pub writer: Option<String>,
+    pub value: Option<String>,
+    // changes heard since the monitor started
+    pub changes: u32,
+    // the last read failed
+    pub error: Option<String>,
+}
+
+fn cut(text: &str) -> String {
+    if text.chars().count() <= VALUE_WIDTH {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(VALUE_WIDTH - 1).collect::<String>())
+    }
+}
+
+pub fn render(code: &str, watch: &str, rows: &[Row]) -> String {
+    let mut lines = vec![
+        format!("Monitoring {code} (read-only, Ctrl+C to stop)"),
+        format!("watch: {watch}"),
+        String::new(),
+        format!("{:<7} {:<6} {:<16} {:<8} value", "subkey", "seq", "writer", "changes"),
+    ];
+    for (subkey, row) in rows.iter().enumerate() {
+        let seq = row.seq.map_or("-".to_string(), |s| s.to_string());
+        let value = match (&row.error, &row.value) {
+            (Some(e), _) => format!("<read failed: {e}>"),
+            (None, Some(v)) => cut(v),
+            (None, None) => "<no data>".to_string(),
+        };
+        lines.push(format!(
+            "{subkey:<7} {seq:<6} {:<16} {:<8} {value}",
+            row.writer.as_deref().unwrap_or("-"),
+            row.changes
+        ));
+    }
+    lines.join("\n")
+}
+
+async fn refresh_row(ro: &ReadOnly, record_key: &RecordKey, subkey: ValueSubkey, names: &Nicknames, row: &mut Row) {
+    match ro.read(record_key, subkey).await {
+        Ok(Some(v)) => {
+            row.seq = Some(v.seq());
+            row.writer = Some(names.label(&v.writer()));
+            row.value = Some(envelope::display_value(v.data()));
+            row.error = None;
+        }
+        Ok(None) => row.error = None,
+        Err(e) => row.error = Some(e.to_string()),
+    }
+}
+
+fn draw(code: &str, watch: &str, rows: &[Row]) {
+    // clear the screen and start from the top
+    println!("\x1b[2J\x1b[H{}", render(code, watch, rows));
+    let _ = io::stdout().flush();
+}
+
+pub async fn run(
+    record: &str,
+    page: Option<&(PathBuf, PathBuf)>,
+    options: &Options,
+    config: &AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // before starting a node, so a bad template is found straight away
+    let page = page.map(|(template, out)| Page::load(template, out)).transpose()?;
+    let data_dir = config.data_dir()?;
+    let mut book = ShortcodeBook::load(&data_dir)?;
+    let record_key = book.resolve(record).map_err(|e| Kind::RecordNotFound.fail(e))?;
+    let mut names = Nicknames::load(&data_dir)?;
+
+    let namespace = format!("{}-monitor", config.default_namespace);
+    let node = progress::spin("Starting the monitor node", VeilidNode::start_attached(config, &data_dir, &namespace, |_| {})).await?;
+    let audit = Arc::new(AuditLog::open(&data_dir.join(log_file_name("monitor")))?);
+    let rc = Dht::new(node.routing_context().get(), Some(audit), false, options.chaos.clone());
+    let ro = ReadOnly {
+        node,
+        rc,
+        changes: WatchSet::new(),
+    };
+
+    let desc = ro.open(record_key).await?;
+    let record_key = desc.key();
+    let code = book.remember(&record_key)?;
+    let report = progress::spin("Inspecting the record", ro.inspect(&record_key)).await?;
+    println!("{} subkey(s), {} of them offline", report.subkeys().len(), report.offline_subkeys().len());
+
+    let mut rows = vec![Row::default(); desc.schema().max_subkey() as usize + 1];
+    for (subkey, row) in (0..).zip(rows.iter_mut()) {
+        refresh_row(&ro, &record_key, subkey, &names, row).await;
+    }
+    let mut watch = match ro.watch(&record_key).await {
+        Ok(()) => "placed".to_string(),
+        Err(e) => format!("not placed ({e}), reading every {}s instead", REFRESH_EVERY.as_secs()),
+    };
+
+    let mut refresh = tokio::time::interval(REFRESH_EVERY);
+    refresh.tick().await;
+    let record_text = record_key.to_string();
+    loop {
+        draw(&code, &watch, &rows);
+        if let Some(page) = &page {
+            let view = View {
+                record: &record_text,
+                code: &code,
+                watch: &watch,
+                now_ms: crate::audit::now_ms() as u64,
+                rows: &rows,
+            };
+            if let Err(e) = page.write(&view) {
+                println!("{e}");
+            }
+        }
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            change = ro.changes.next() => match change {
+                Some(change) if change.watch_died => {
+                    watch = format!("lost, placing it again in under {}s", REFRESH_EVERY.as_secs());
+                }
+                Some(change) => {
+                    for subkey in change.subkeys.iter() {
+                        if let Some(row) = rows.get_mut(subkey as usize) {
+                            row.changes += 1;
+                            refresh_row(&ro, &record_key, subkey, &names, row).await;
+                        }
+                    }
+                    watch = format!("placed, last change at subkey(s) {}", change.subkeys);
+                }
+                None => break,
+            },
+            _ = refresh.tick() => {
+                // nicknames set at the other consoles since the last time
+                let _ = names.reload();
+                for (subkey, row) in (0..).zip(rows.iter_mut()) {
+                    refresh_row(&ro, &record_key, subkey, &names, row).await;
+                }
+                if !watch.starts_with("placed") {
+                    if let Ok(()) = ro.watch(&record_key).await {
+                        watch = "placed again".to_string();
+                    }
+                }
+            }
+        }
+    }
+
+    println!("\nStopped monitoring {}", shortcode::shortcode(&record_key));
+    ro.close(record_key).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_show_seq_writer_and_a_cut_down_value() {
+        let rows = [
+            Row {
+                seq: Some(ValueSeqNum::from(3)),
+                writer: Some("alice".to_string()),
+                value: Some("x".repeat(60)),
+                changes: 2,
+                error: None,
+            },
+            Row::default(),
+            Row {
+                error: Some("timed out".to_string()),
+                ..Row::default()
+            },
+        ];
+        let screen = render("harp-otter-coal-lime", "placed", &rows);
+        let lines: Vec<&str> = screen.lines().collect();
+        assert_eq!(lines[0], "Monitoring harp-otter-coal-lime (read-only, Ctrl+C to stop)");
+        assert_eq!(lines[4], format!("0       3      alice            2        {}…", "x".repeat(VALUE_WIDTH - 1)));
+        assert_eq!(lines[5], "1       -      -                0        <no data>");
+        assert_eq!(lines[6], "2       -      -                0        <read failed: timed out>");
+    }
+}
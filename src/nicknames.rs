@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use veilid_core::*;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Nicknames for writer keys.
+//
+//	Every DHT value remembers the public key that wrote it. On their own those
+//	keys are unreadable, so nodes sharing a data folder keep a little book
+//	(nicknames.json) of "this key is the default node", which the read paths
+//	use to show who wrote each subkey.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub struct Nicknames {
+    path: PathBuf,
+    names: BTreeMap<String, String>,
+}
+
+impl Nicknames {
+    pub fn load(data_dir: &Path) -> io::Result<Nicknames> {
+        let path = data_dir.join("nicknames.json");
+        let names = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Nicknames { path, names })
+    }
+
+    pub fn set(&mut self, key: &PublicKey, nickname: &str) -> io::Result<()> {
+        self.names.insert(key.to_string(), nickname.to_string());
+        fs::write(&self.path, serde_json::to_string_pretty(&self.names)?)
+    }
+
+    pub fn knows(&self, key: &PublicKey) -> bool {
+        self.names.contains_key(&key.to_string())
+    }
+
+    // Re-read the file, picking up names other nodes added since we started.
+    pub fn reload(&mut self) -> io::Result<()> {
+        *self = Nicknames::load(self.path.parent().unwrap_or(Path::new(".")))?;
+        Ok(())
+    }
+
+    // (public key, nickname) for everyone in the book
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.names.iter().map(|(k, n)| (k.as_str(), n.as_str()))
+    }
+
+    // "alice" if we know the key, otherwise a shortened key like "VLD0:AbCdEfGh…"
+    pub fn label(&self, key: &PublicKey) -> String {
+        let key = key.to_string();
+        match self.names.get(&key) {
+            Some(name) => name.clone(),
+            None => short_key(&key),
+        }
+    }
+}
+
+pub fn short_key(key: &str) -> String {
+    match key.char_indices().nth(13) {
+        Some((i, _)) => format!("{}…", &key[..i]),
+        None => key.to_string(),
+    }
+}
+
+// "seq 4, written by alice" for a value we just read
+pub fn attribution(value: &ValueData, names: &Nicknames) -> String {
+    format!("seq {}, written by {}", value.seq(), names.label(&value.writer()))
+}
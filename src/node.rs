@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio_stream::Stream;
+use veilid_core::*;
+
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::keybundle;
+use crate::ready::{Milestone, ReadyGate, ReadySignal};
+use crate::soak::SoakRole;
+use crate::watch::{DecodedChange, WatchManager, WatchSet};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Starting a Veilid node.
+//
+//	start_attached() is the whole of it: build the VeilidConfig, start
+//	Veilid with an update callback, attach, and wait until the node is
+//	attached enough to use. The one-shot commands (record clone, ...) use it
+//	as it is; the default and alt nodes go through VeilidNode below, which
+//	is also what another program using this crate as a library would start:
+//
+//	  let node = VeilidNode::start_attached(&config, &dir, "my-app", |_| {}).await?;
+//	  let rc = node.routing_context().get();
+//	  ...
+//	  node.shutdown().await;
+//
+//	VeilidNode wraps a started node together with its watch manager, for
+//	code that wants ValueChanges (from one record or many) as a stream
+//	instead of a callback, and its milestones (see ready.rs). It remembers
+//	how it placed each watch, so `watch status` can ask Veilid about the
+//	same watch again and renew it before watch_expiry_secs runs out.
+//
+//	Every DHT call goes out through one RoutingContextHandle per node, made
+//	once from the config (safe_routing, sequencing), so all of them behave
+//	the same way and there's one place to change how.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// The base configuration of a veilid node. Each role gets its own namespace,
+// so two nodes can run side by side out of the same data folder.
+// Asks for the protected store password the first time it's needed (see store.rs).
+pub fn node_config(config: &AppConfig, data_dir: &Path, namespace: &str) -> Result<VeilidConfig, String> {
+    let password = crate::store::password(config)?;
+    Ok(node_config_with(config, data_dir, namespace, &password))
+}
+
+// The same, with the password already known.
+pub fn node_config_with(config: &AppConfig, data_dir: &Path, namespace: &str, password: &str) -> VeilidConfig {
+    VeilidConfig {
+        program_name: config.program_name.clone(),
+        namespace: namespace.into(),
+
+        protected_store: VeilidConfigProtectedStore {
+            // IMPORTANT: don't leave this true in production
+            // It's the default so the example starts without a password, and it is insecure
+            always_use_insecure_storage: config.always_use_insecure_storage,
+            // no keychain (a headless box, a container): the file, sealed with the password
+            allow_insecure_fallback: true,
+            device_encryption_key_password: password.to_string(),
+            directory: data_dir
+                .join(".veilid/protected_store")
+                .to_string_lossy()
+                .to_string(),
+            ..Default::default()
+        },
+        table_store: VeilidConfigTableStore {
+            directory: data_dir
+                .join(".veilid/table_store")
+                .to_string_lossy()
+                .to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+// What an interactive node runs with: the command line and settings, and
+// where this node keeps things, worked out once from --namespace,
+// --storage-dir and --key-file (or the config, if they weren't given).
+pub struct NodeOptions<'a> {
+    pub options: &'a Options,
+    pub config: &'a AppConfig,
+    pub namespace: String,
+    // logs, history, contacts and the rest of our own files
+    pub data_dir: PathBuf,
+    // where Veilid's .veilid/ stores go
+    pub storage_dir: PathBuf,
+    pub key_file: PathBuf,
+}
+
+impl<'a> NodeOptions<'a> {
+    pub fn new(options: &'a Options, config: &'a AppConfig, role: SoakRole) -> std::io::Result<NodeOptions<'a>> {
+        let data_dir = config.data_dir()?;
+        let storage_dir = match &options.storage_dir {
+            Some(dir) => {
+                fs::create_dir_all(dir)?;
+                dir.clone()
+            }
+            None => data_dir.clone(),
+        };
+        let namespace = options.namespace.clone().unwrap_or_else(|| match role {
+            SoakRole::Default => config.default_namespace.clone(),
+            SoakRole::Alt => config.alt_namespace.clone(),
+            SoakRole::Member => config.member_namespace.clone(),
+        });
+        Ok(NodeOptions {
+            options,
+            config,
+            namespace,
+            key_file: options.key_file.clone().unwrap_or_else(|| keybundle::default_path(&data_dir)),
+            data_dir,
+            storage_dir,
+        })
+    }
+}
+
+// The namespace the one-shot commands run under, so they never share storage
+// with a default or alt node that might be running at the same time.
+pub fn tool_namespace(config: &AppConfig) -> String {
+    format!("{}-tools", config.default_namespace)
+}
+
+// Start Veilid, attach, and wait until the node is attached enough (ready_when, see ready.rs).
+// `on_update` sees every update too (pass `|_| {}` if you don't care).
+pub async fn start_attached(
+    config: &AppConfig,
+    data_dir: &Path,
+    namespace: &str,
+    on_update: impl Fn(VeilidUpdate) + Send + Sync + 'static,
+) -> Result<VeilidAPI, Box<dyn std::error::Error>> {
+    start_signalled(config, data_dir, namespace, ReadySignal::new(), on_update).await
+}
+
+// start_attached(), marking Milestone::Attached on `signal` on the way.
+async fn start_signalled(
+    config: &AppConfig,
+    data_dir: &Path,
+    namespace: &str,
+    signal: ReadySignal,
+    on_update: impl Fn(VeilidUpdate) + Send + Sync + 'static,
+) -> Result<VeilidAPI, Box<dyn std::error::Error>> {
+    let ready = ReadyGate::new(config, signal)?;
+
+    let update_callback = {
+        let ready = ready.clone();
+        Arc::new(move |update: VeilidUpdate| {
+            ready.update(&update);
+            on_update(update);
+        })
+    };
+
+    let veilid = veilid_core::api_startup(update_callback, node_config(config, data_dir, namespace)?)
+        .await
+        .map_err(crate::store::startup_failed)?;
+    veilid.attach().await?;
+
+    ready.wait().await?;
+
+    Ok(veilid)
+}
+
+// The routing context everything on a node shares. Cheap to clone.
+#[derive(Clone)]
+pub struct RoutingContextHandle {
+    rc: RoutingContext,
+}
+
+impl RoutingContextHandle {
+    pub fn new(api: &VeilidAPI, config: &AppConfig) -> Result<RoutingContextHandle, Box<dyn std::error::Error>> {
+        let sequencing = config.sequencing()?;
+        let rc = if config.safe_routing {
+            api.routing_context()?.with_default_safety()?.with_sequencing(sequencing)
+        } else {
+            api.routing_context()?.with_safety(SafetySelection::Unsafe(sequencing))?
+        };
+        Ok(RoutingContextHandle { rc })
+    }
+
+    pub fn get(&self) -> RoutingContext {
+        self.rc.clone()
+    }
+}
+
+// A started, attached node plus the watch manager its update callback feeds.
+pub struct VeilidNode {
+    api: VeilidAPI,
+    rc: RoutingContextHandle,
+    watches: Arc<WatchManager>,
+    ready: ReadySignal,
+    // how long each watch is placed for (None = until cancelled)
+    watch_expiry: Option<Duration>,
+    placed: Mutex<HashMap<RecordKey, PlacedWatch>>,
+}
+
+// A watch as we last asked Veilid for it.
+#[derive(Clone)]
+struct PlacedWatch {
+    subkeys: Option<ValueSubkeyRangeSet>,
+    expiration: Option<Timestamp>,
+}
+
+// What asking Veilid about a placed watch again found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchCheck {
+    // None for the whole record
+    pub subkeys: Option<ValueSubkeyRangeSet>,
+    // None if it never expires; zero once it has
+    pub expires_in: Option<Duration>,
+    // Veilid still holds the watch (it reconciles it with the network in the background)
+    pub active: bool,
+}
+
+fn time_left(expiration: Timestamp) -> Duration {
+    Duration::from_micros(expiration.as_u64().saturating_sub(Timestamp::now().as_u64()))
+}
+
+impl VeilidNode {
+    // start_attached(), with every ValueChange also handed to the watch manager.
+    pub async fn start_attached(
+        config: &AppConfig,
+        data_dir: &Path,
+        namespace: &str,
+        on_update: impl Fn(VeilidUpdate) + Send + Sync + 'static,
+    ) -> Result<VeilidNode, Box<dyn std::error::Error>> {
+        let watches = Arc::new(WatchManager::new());
+        let ready = ReadySignal::new();
+        let api = {
+            let (watches, ready) = (watches.clone(), ready.clone());
+            start_signalled(config, data_dir, namespace, ready.clone(), move |update| {
+                if let VeilidUpdate::ValueChange(change) = &update {
+                    // a change with no subkeys (or count 0) is a watch ending, not delivering
+                    if !change.subkeys.is_empty() && change.count > 0 {
+                        ready.reach(Milestone::FirstWatch);
+                    }
+                    watches.dispatch(change);
+                }
+                on_update(update);
+            })
+            .await?
+        };
+        let rc = RoutingContextHandle::new(&api, config)?;
+        Ok(VeilidNode {
+            api,
+            rc,
+            watches,
+            ready,
+            watch_expiry: config.watch_expiry(),
+            placed: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn api(&self) -> &VeilidAPI {
+        &self.api
+    }
+
+    pub fn routing_context(&self) -> &RoutingContextHandle {
+        &self.rc
+    }
+
+    // Attached, record opened, first watch delivered: for waiting on one of
+    // them, or saying when they happened. The node marks the first and last;
+    // whoever opens the record marks that.
+    pub fn ready(&self) -> &ReadySignal {
+        &self.ready
+    }
+
+    // Detach and stop Veilid. Close the node's records first.
+    pub async fn shutdown(self) {
+        self.api.shutdown().await;
+    }
+
+    // Follow changes to some subkeys of one record (an empty set means all of
+    // them) as a stream of their own: watch() with a WatchSet of one. The
+    // record must already be open on this node; fails if the network won't
+    // take the watch.
+    pub async fn subscribe(
+        &self,
+        record: RecordKey,
+        subkeys: ValueSubkeyRangeSet,
+    ) -> VeilidAPIResult<impl Stream<Item = DecodedChange> + Unpin> {
+        let set = WatchSet::new();
+        self.watch(&set, record, subkeys).await?;
+        Ok(set.into_stream())
+    }
+
+    // Follow changes to some subkeys of a record (an empty set means all of
+    // them), through `set` alongside whatever else it follows. The record must
+    // already be open on this node. Places (or widens) the record's watch, and
+    // fails if the network won't take it.
+    pub async fn watch(
+        &self,
+        set: &WatchSet,
+        record: RecordKey,
+        subkeys: ValueSubkeyRangeSet,
+    ) -> VeilidAPIResult<()> {
+        let watched = set.add(&self.watches, record.clone(), subkeys);
+        self.place_watch(record, watched).await
+    }
+
+    // Records with someone still following them.
+    pub fn watched_records(&self) -> Vec<RecordKey> {
+        self.watches.records()
+    }
+
+    async fn place_watch(&self, record: RecordKey, watched: Option<ValueSubkeyRangeSet>) -> VeilidAPIResult<()> {
+        let expiration = self
+            .watch_expiry
+            .map(|expiry| Timestamp::new(Timestamp::now().as_u64() + expiry.as_micros() as u64));
+        let active = self
+            .rc
+            .get()
+            .watch_dht_values(record.clone(), watched.clone(), expiration, None)
+            .await?;
+        if !active {
+            return Err(VeilidAPIError::generic("the network did not accept the watch"));
+        }
+        self.placed.lock().unwrap().insert(
+            record,
+            PlacedWatch {
+                subkeys: watched,
+                expiration,
+            },
+        );
+        Ok(())
+    }
+
+    // Asks Veilid about a record's watch again, with the parameters it was
+    // placed with, so nothing about it changes (the expiry isn't pushed back).
+    pub async fn check_watch(&self, record: &RecordKey) -> VeilidAPIResult<WatchCheck> {
+        let Some(placed) = self.placed.lock().unwrap().get(record).cloned() else {
+            return Err(VeilidAPIError::generic("no watch was placed on this record"));
+        };
+        let expires_in = placed.expiration.map(time_left);
+        let active = if expires_in == Some(Duration::ZERO) {
+            // Veilid won't take an expiration in the past, and the watch is gone anyway
+            false
+        } else {
+            self.rc
+                .get()
+                .watch_dht_values(record.clone(), placed.subkeys.clone(), placed.expiration, None)
+                .await?
+        };
+        Ok(WatchCheck {
+            subkeys: placed.subkeys,
+            expires_in,
+            active,
+        })
+    }
+
+    // Places a record's watch again, from now, with the same subkeys.
+    pub async fn renew_watch(&self, record: &RecordKey) -> VeilidAPIResult<()> {
+        let subkeys = self.placed.lock().unwrap().get(record).and_then(|p| p.subkeys.clone());
+        self.place_watch(record.clone(), subkeys).await
+    }
+}
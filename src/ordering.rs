@@ -0,0 +1,173 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeInclusive;
+
+use veilid_core::*;
+
+use crate::nicknames::Nicknames;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Per-sender ordering: the default node numbers what it writes (the
+//	envelope's sender_seq, 1, 2, 3, ...) and the alt node checks the numbers
+//	as changes arrive through its watches.
+//
+//	stats.rs already spots skipped DHT seqs, but those are per subkey. The
+//	sender's own counter follows one writer across every subkey and record
+//	it writes to, so "#4 then #7" means two of its messages never showed up
+//	here, wherever they went, and "#5" turning up after "#7" means it came
+//	late.
+//
+//	There's no log behind a subkey: a later write replaces the earlier one,
+//	so a missed message can't be fetched again, only reported. The first
+//	number seen from a writer is taken as the start; whatever it wrote
+//	before we joined doesn't count as missing.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// How many missing numbers to remember per writer, for spotting late arrivals.
+const MISSING_KEEP: usize = 1000;
+
+#[derive(Debug, PartialEq)]
+pub enum Arrival {
+    // the first we've heard from this writer
+    First,
+    InOrder,
+    // these never arrived (yet)
+    Gap(RangeInclusive<u64>),
+    // one we had down as missing
+    Late,
+    // seen it already
+    Repeat,
+}
+
+#[derive(Default)]
+struct SenderOrder {
+    next: u64,
+    missing: BTreeSet<u64>,
+    received: u64,
+    gaps: u64,
+    missed: u64,
+    late: u64,
+}
+
+#[derive(Default)]
+pub struct OrderTracker {
+    by_writer: BTreeMap<PublicKey, SenderOrder>,
+}
+
+impl OrderTracker {
+    pub fn new() -> OrderTracker {
+        OrderTracker::default()
+    }
+
+    pub fn observe(&mut self, writer: &PublicKey, seq: u64) -> Arrival {
+        let Some(order) = self.by_writer.get_mut(writer) else {
+            self.by_writer.insert(
+                writer.clone(),
+                SenderOrder {
+                    next: seq + 1,
+                    received: 1,
+                    ..SenderOrder::default()
+                },
+            );
+            return Arrival::First;
+        };
+        if seq < order.next {
+            if order.missing.remove(&seq) {
+                order.received += 1;
+                order.late += 1;
+                return Arrival::Late;
+            }
+            return Arrival::Repeat;
+        }
+        order.received += 1;
+        let arrival = if seq == order.next {
+            Arrival::InOrder
+        } else {
+            let missed = order.next..=seq - 1;
+            order.gaps += 1;
+            order.missed += seq - order.next;
+            order.missing.extend(missed.clone().take(MISSING_KEEP));
+            while order.missing.len() > MISSING_KEEP {
+                order.missing.pop_first();
+            }
+            Arrival::Gap(missed)
+        };
+        order.next = seq + 1;
+        arrival
+    }
+
+    // What to tell the user about an arrival, if anything.
+    pub fn note(arrival: &Arrival, writer: &str, seq: u64) -> Option<String> {
+        match arrival {
+            Arrival::Gap(missed) if missed.start() == missed.end() => {
+                Some(format!("[order] {writer}'s #{} never arrived (got #{seq} next)", missed.start()))
+            }
+            Arrival::Gap(missed) => Some(format!(
+                "[order] {writer}'s #{}..#{} never arrived (got #{seq} next)",
+                missed.start(),
+                missed.end()
+            )),
+            Arrival::Late => Some(format!("[order] {writer}'s #{seq} arrived late, out of order")),
+            _ => None,
+        }
+    }
+
+    // `stats order`: one line per writer.
+    pub fn report(&self, names: &Nicknames) -> String {
+        if self.by_writer.is_empty() {
+            return "No numbered messages received yet".to_string();
+        }
+        self.by_writer
+            .iter()
+            .map(|(writer, o)| {
+                format!(
+                    "  {:<20} up to #{}, {} received, {} missed in {} gap(s) ({} still missing), {} late",
+                    names.label(writer),
+                    o.next - 1,
+                    o.received,
+                    o.missed,
+                    o.gaps,
+                    o.missing.len(),
+                    o.late
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaps_late_arrivals_and_repeats() {
+        let mut tracker = OrderTracker::new();
+        let alice = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap().key();
+        let bob = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap().key();
+
+        // joining late: whatever came before #3 isn't missing
+        assert_eq!(tracker.observe(&alice, 3), Arrival::First);
+        assert_eq!(tracker.observe(&alice, 4), Arrival::InOrder);
+        assert_eq!(tracker.observe(&alice, 7), Arrival::Gap(5..=6));
+        assert_eq!(tracker.observe(&alice, 6), Arrival::Late);
+        assert_eq!(tracker.observe(&alice, 6), Arrival::Repeat);
+        assert_eq!(tracker.observe(&alice, 4), Arrival::Repeat);
+        assert_eq!(tracker.observe(&alice, 8), Arrival::InOrder);
+
+        // each writer has its own count
+        assert_eq!(tracker.observe(&bob, 1), Arrival::First);
+        assert_eq!(tracker.observe(&bob, 2), Arrival::InOrder);
+
+        let order = &tracker.by_writer[&alice];
+        assert_eq!((order.received, order.gaps, order.missed, order.late), (5, 1, 2, 1));
+        assert_eq!(order.missing, BTreeSet::from([5]));
+
+        assert_eq!(
+            OrderTracker::note(&Arrival::Gap(5..=5), "alice", 6).unwrap(),
+            "[order] alice's #5 never arrived (got #6 next)"
+        );
+        assert_eq!(OrderTracker::note(&Arrival::InOrder, "alice", 6), None);
+    }
+}
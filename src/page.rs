@@ -0,0 +1,202 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::monitor::Row;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`monitor <record> --render <template> --out <file>`: the monitor's view
+//	of the record, written through a template of your own every time the
+//	screen is redrawn, for a static "network status" page or a text file
+//	something else picks up.
+//
+//	The template language is a small piece of Handlebars, enough for a
+//	table of subkeys; nothing else needs a template engine, so there isn't
+//	one in the build:
+//
+//	  {{record}} {{code}}         the record key, and its share code
+//	  {{watch}} {{updated}}       the watch's state, and the time (UTC)
+//	  {{subkey.3}}                subkey 3's value ("" if it has none)
+//	  {{#each subkeys}} ... {{/each}}
+//	                              once per subkey, with {{subkey}},
+//	                              {{seq}}, {{writer}}, {{value}},
+//	                              {{changes}} and {{error}} inside
+//
+//	When the output ends in .html or .htm every value is HTML-escaped.
+//	The template is checked once at start-up, so a typo stops the monitor
+//	there rather than leaving a stale page. The file is written next to
+//	itself and renamed into place, so a web server never serves half a page.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const EACH_OPEN: &str = "#each subkeys";
+const EACH_CLOSE: &str = "{{/each}}";
+
+// What a template can refer to.
+pub struct View<'a> {
+    pub record: &'a str,
+    pub code: &'a str,
+    pub watch: &'a str,
+    pub now_ms: u64,
+    pub rows: &'a [Row],
+}
+
+fn escape(text: &str, html: bool) -> String {
+    if !html {
+        return text.to_string();
+    }
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn clock(now_ms: u64) -> String {
+    let secs = now_ms / 1000 % 86_400;
+    format!("{:02}:{:02}:{:02} UTC", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+fn row_value(row: &Row, name: &str) -> Option<String> {
+    Some(match name {
+        "seq" => row.seq.map(|s| s.to_string()).unwrap_or_default(),
+        "writer" => row.writer.clone().unwrap_or_default(),
+        "value" => row.value.clone().unwrap_or_default(),
+        "changes" => row.changes.to_string(),
+        "error" => row.error.clone().unwrap_or_default(),
+        _ => return None,
+    })
+}
+
+// A {{name}}, inside an {{#each}} if `row` is set.
+fn lookup(name: &str, view: &View, row: Option<(usize, &Row)>) -> Result<String, String> {
+    if let Some((subkey, row)) = row {
+        if name == "subkey" {
+            return Ok(subkey.to_string());
+        }
+        if let Some(value) = row_value(row, name) {
+            return Ok(value);
+        }
+    }
+    match name {
+        "record" => Ok(view.record.to_string()),
+        "code" => Ok(view.code.to_string()),
+        "watch" => Ok(view.watch.to_string()),
+        "updated" => Ok(clock(view.now_ms)),
+        _ => {
+            let subkey = name
+                .strip_prefix("subkey.")
+                .and_then(|n| n.parse::<usize>().ok())
+                .ok_or(format!("the template has {{{{{name}}}}}, which isn't something it can show"))?;
+            Ok(view.rows.get(subkey).and_then(|r| r.value.clone()).unwrap_or_default())
+        }
+    }
+}
+
+fn expand(template: &str, view: &View, row: Option<(usize, &Row)>, html: bool) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or("the template has a {{ with no }}")?;
+        let tag = after[..end].trim();
+        rest = &after[end + 2..];
+        if tag == EACH_OPEN {
+            if row.is_some() {
+                return Err("{{#each subkeys}} can't be inside another".to_string());
+            }
+            let close = rest.find(EACH_CLOSE).ok_or("the template's {{#each subkeys}} has no {{/each}}")?;
+            let body = &rest[..close];
+            for (subkey, r) in view.rows.iter().enumerate() {
+                out.push_str(&expand(body, view, Some((subkey, r)), html)?);
+            }
+            rest = &rest[close + EACH_CLOSE.len()..];
+        } else if tag.starts_with('#') || tag.starts_with('/') {
+            return Err(format!("the template has {{{{{tag}}}}}; only {{{{#each subkeys}}}} blocks are understood"));
+        } else {
+            out.push_str(&escape(&lookup(tag, view, row)?, html));
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+pub struct Page {
+    template: String,
+    out: PathBuf,
+    html: bool,
+}
+
+impl Page {
+    // Reads the template and makes sure it renders.
+    pub fn load(template: &Path, out: &Path) -> Result<Page, String> {
+        let text = fs::read_to_string(template).map_err(|e| format!("couldn't read the template {}: {e}", template.to_string_lossy()))?;
+        let html = out
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"));
+        let page = Page {
+            template: text,
+            out: out.to_path_buf(),
+            html,
+        };
+        let empty = View {
+            record: "",
+            code: "",
+            watch: "",
+            now_ms: 0,
+            rows: &[Row::default()],
+        };
+        page.render(&empty)?;
+        Ok(page)
+    }
+
+    pub fn render(&self, view: &View) -> Result<String, String> {
+        expand(&self.template, view, None, self.html)
+    }
+
+    pub fn write(&self, view: &View) -> Result<(), String> {
+        let text = self.render(view)?;
+        let mut temp = self.out.clone().into_os_string();
+        temp.push(".tmp");
+        fs::write(&temp, text)
+            .and_then(|()| fs::rename(&temp, &self.out))
+            .map_err(|e| format!("couldn't write {}: {e}", self.out.to_string_lossy()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subkeys_are_looped_over_and_escaped_for_html() {
+        let rows = [
+            Row {
+                writer: Some("alice".to_string()),
+                value: Some("<b>up</b>".to_string()),
+                changes: 2,
+                ..Row::default()
+            },
+            Row::default(),
+        ];
+        let view = View {
+            record: "VLD0:abc",
+            code: "harp-otter",
+            watch: "placed",
+            now_ms: 3_723_000,
+            rows: &rows,
+        };
+        let template = "{{code}} at {{updated}}\n{{#each subkeys}}{{subkey}}={{ value }} by {{writer}};{{/each}}\nfirst: {{subkey.0}}";
+        assert_eq!(
+            expand(template, &view, None, true).unwrap(),
+            "harp-otter at 01:02:03 UTC\n0=&lt;b&gt;up&lt;/b&gt; by alice;1= by ;\nfirst: &lt;b&gt;up&lt;/b&gt;"
+        );
+        assert_eq!(expand("{{subkey.0}}", &view, None, false).unwrap(), "<b>up</b>");
+        assert!(expand("{{subkey}}", &view, None, false).is_err());
+        assert!(expand("{{#if value}}x{{/if}}", &view, None, false).unwrap_err().contains("#each"));
+        assert!(expand("{{#each subkeys}}x", &view, None, false).is_err());
+        assert!(expand("{{code", &view, None, false).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Where the example keeps its files (.veilid stores, owner_keys.txt, logs).
+//
+//	By default that's the normal per-user data folder for the platform:
+//	  Linux:   ~/.local/share/veilid-example
+//	  Windows: %APPDATA%\veilid-example\data
+//	  macOS:   ~/Library/Application Support/veilid-example
+//
+//	With --portable everything lives next to the executable instead (the old
+//	behavior), which is handy when running straight out of target/ or a USB
+//	stick, but breaks if the program is installed somewhere read-only.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// Grab the location from the executable file (depending on the platform,
+// this can be diffrent from where it was launched from)
+pub fn exe_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|x| x.parent().map(|p| p.to_owned()))
+        .unwrap_or_else(|| ".".into())
+}
+
+// Where config.json is looked for (not created, the file is optional).
+pub fn config_dir(portable: bool) -> PathBuf {
+    if portable {
+        return exe_dir();
+    }
+    ProjectDirs::from("", "", "veilid-example")
+        .map(|dirs| dirs.config_dir().to_owned())
+        .unwrap_or_else(exe_dir)
+}
+
+// The folder all our files go in, created if it isn't there yet.
+pub fn data_dir(portable: bool) -> io::Result<PathBuf> {
+    let dir = if portable {
+        exe_dir()
+    } else {
+        ProjectDirs::from("", "", "veilid-example")
+            .map(|dirs| dirs.data_dir().to_owned())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "couldn't find a home/data folder for this user (try --portable)",
+                )
+            })?
+    };
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::config::AppConfig;
+use crate::envelope::{self, Codec, Envelope};
+use crate::metadata::RecordMetadata;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	What kind of value each subkey is meant to hold, so that:
+//
+//	  - a read of a typed subkey shows the struct, field by field, instead
+//	    of a line of JSON (or says it isn't one)
+//	  - a write to a typed subkey has to parse as that type first; what
+//	    goes out is the type serialized again, in a Json envelope, so
+//	    every reader gets the same field names whatever was typed
+//
+//	The map comes from payload_types in the config (subkey -> type name),
+//	e.g. {"0": "metadata", "3": "presence"}, and the record's metadata
+//	block can add to it for subkeys the config leaves out. Subkeys in
+//	neither take any text, as before.
+//
+//	  presence   {"status": "away", "until_ms": 1760000000000}
+//	  chat       {"from": "alice", "text": "hi", "reply_to": 12}
+//	  manifest   {"name": "app", "version": "1.2.0", "files": [{"path": "a.bin", "size": 10}]}
+//	  metadata   the record's metadata block (see metadata.rs)
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayloadType {
+    Presence,
+    ChatMessage,
+    Manifest,
+    Metadata,
+}
+
+const TYPES: [PayloadType; 4] = [
+    PayloadType::Presence,
+    PayloadType::ChatMessage,
+    PayloadType::Manifest,
+    PayloadType::Metadata,
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Presence {
+    pub status: String,
+    // when the status stops being true, if the writer knows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ChatMessage {
+    pub from: String,
+    pub text: String,
+    // the sender_seq of the message this answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestFile {
+    pub path: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+// A value that parsed as its subkey's type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Payload {
+    Presence(Presence),
+    ChatMessage(ChatMessage),
+    Manifest(Manifest),
+    Metadata(Box<RecordMetadata>),
+}
+
+impl PayloadType {
+    pub fn name(self) -> &'static str {
+        match self {
+            PayloadType::Presence => "presence",
+            PayloadType::ChatMessage => "chat",
+            PayloadType::Manifest => "manifest",
+            PayloadType::Metadata => "metadata",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PayloadType> {
+        TYPES.into_iter().find(|t| t.name() == name)
+    }
+
+    pub fn parse(self, json: &[u8]) -> Result<Payload, String> {
+        fn typed<T: DeserializeOwned>(json: &[u8]) -> Result<T, String> {
+            serde_json::from_slice(json).map_err(|e| e.to_string())
+        }
+        Ok(match self {
+            PayloadType::Presence => Payload::Presence(typed(json)?),
+            PayloadType::ChatMessage => Payload::ChatMessage(typed(json)?),
+            PayloadType::Manifest => Payload::Manifest(typed(json)?),
+            PayloadType::Metadata => Payload::Metadata(Box::new(typed(json)?)),
+        })
+    }
+}
+
+impl Payload {
+    pub fn to_json(&self) -> Vec<u8> {
+        let json = match self {
+            Payload::Presence(p) => serde_json::to_vec(p),
+            Payload::ChatMessage(m) => serde_json::to_vec(m),
+            Payload::Manifest(m) => serde_json::to_vec(m),
+            Payload::Metadata(m) => serde_json::to_vec(m),
+        };
+        json.expect("payloads serialize")
+    }
+
+    // The struct, one field per line.
+    pub fn pretty(&self) -> String {
+        match self {
+            Payload::Presence(p) => format!("{p:#?}"),
+            Payload::ChatMessage(m) => format!("{m:#?}"),
+            Payload::Manifest(m) => format!("{m:#?}"),
+            // the whole block has the owner's card in it, which nobody wants to read
+            Payload::Metadata(m) => format!("RecordMetadata {}", m.display()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    types: BTreeMap<ValueSubkey, PayloadType>,
+    // the codec ids encode() puts every value through (see codecs.rs)
+    pipeline: Vec<u8>,
+}
+
+impl Registry {
+    pub fn new(names: &BTreeMap<ValueSubkey, String>) -> Result<Registry, String> {
+        let mut types = BTreeMap::new();
+        for (&subkey, name) in names {
+            let t = PayloadType::from_name(name).ok_or_else(|| {
+                let known: Vec<&str> = TYPES.iter().map(|t| t.name()).collect();
+                format!("payload_types: subkey {subkey} has unknown type '{name}' (known: {})", known.join(", "))
+            })?;
+            types.insert(subkey, t);
+        }
+        Ok(Registry {
+            types,
+            pipeline: Vec::new(),
+        })
+    }
+
+    // Put what encode() makes through these codecs (from codecs::pipeline).
+    pub fn with_pipeline(mut self, pipeline: Vec<u8>) -> Registry {
+        self.pipeline = pipeline;
+        self
+    }
+
+    // The config's payload_types, with its value_codecs as the pipeline:
+    // what every node and tool that writes values should encode with.
+    pub fn from_config(config: &AppConfig) -> Result<Registry, String> {
+        Ok(Registry::new(&config.payload_types)?.with_pipeline(crate::codecs::pipeline(&config.value_codecs)?))
+    }
+
+    // Types the record's metadata block gives, for the subkeys our config
+    // says nothing about. Names this build doesn't know are left out.
+    pub fn adopt(&mut self, names: &BTreeMap<ValueSubkey, String>) {
+        for (&subkey, name) in names {
+            if let Some(t) = PayloadType::from_name(name) {
+                self.types.entry(subkey).or_insert(t);
+            }
+        }
+    }
+
+    pub fn expected(&self, subkey: ValueSubkey) -> Option<PayloadType> {
+        self.types.get(&subkey).copied()
+    }
+
+    // What to write for `text`: checked against the subkey's type if it has one.
+    pub fn encode(&self, subkey: ValueSubkey, text: &str) -> Result<Envelope, String> {
+        let mut env = match self.expected(subkey) {
+            None => Envelope::text(text),
+            Some(t) => {
+                let payload = t
+                    .parse(text.as_bytes())
+                    .map_err(|e| format!("subkey {subkey} holds {} values, and that isn't one: {e}", t.name()))?;
+                let mut env = Envelope::new(Codec::Json, payload.to_json());
+                env.written_ms = Some(crate::audit::now_ms() as u64);
+                env
+            }
+        };
+        env.pipeline = self.pipeline.clone();
+        Ok(env)
+    }
+
+    // A decoded value, shown as its subkey's type where it is one.
+    pub fn show(&self, subkey: ValueSubkey, env: &Envelope) -> String {
+        if !env.is_live(crate::audit::now_ms() as u64) {
+            return "<expired>".to_string();
+        }
+        let Some(t) = self.expected(subkey) else {
+            return env.display();
+        };
+        match t.parse(&env.body) {
+            Ok(payload) => payload.pretty(),
+            Err(e) => format!("<not a {} value ({e})> {}", t.name(), env.display()),
+        }
+    }
+
+    // The same, straight from the DHT's bytes.
+    pub fn display(&self, subkey: ValueSubkey, data: &[u8]) -> String {
+        match Envelope::decode(data) {
+            Ok(env) => self.show(subkey, &env),
+            Err(_) => envelope::display_value(data),
+        }
+    }
+
+    // For `config show` and the like, e.g. "0 metadata, 3 presence".
+    pub fn describe(&self) -> String {
+        if self.types.is_empty() {
+            return "no typed subkeys".to_string();
+        }
+        self.types
+            .iter()
+            .map(|(subkey, t)| format!("{subkey} {}", t.name()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_subkeys_check_writes_and_pretty_print_reads() {
+        let names = BTreeMap::from([(0, "metadata".to_string()), (3, "presence".to_string())]);
+        let registry = Registry::new(&names).unwrap();
+        assert_eq!(registry.describe(), "0 metadata, 3 presence");
+
+        // untyped subkeys take anything
+        assert_eq!(registry.encode(2, "hello").unwrap().display(), "hello");
+
+        // typed ones only their type, written back out the same way whatever the spacing
+        assert!(registry.encode(3, "hello").is_err());
+        assert!(registry.encode(3, r#"{"status": "away", "mood": "meh"}"#).is_err());
+        let env = registry.encode(3, r#"{ "status" : "away" }"#).unwrap();
+        assert_eq!(env.codec, Codec::Json);
+        assert_eq!(env.body, br#"{"status":"away"}"#);
+        let shown = registry.display(3, &env.encode());
+        assert!(shown.starts_with("Presence {") && shown.contains("status: \"away\""), "{shown}");
+
+        // something else in a typed subkey is shown as it is, with why
+        let shown = registry.display(3, &Envelope::text("hi").encode());
+        assert!(shown.starts_with("<not a presence value") && shown.ends_with("hi"), "{shown}");
+
+        let bad = BTreeMap::from([(1, "weather".to_string())]);
+        assert!(Registry::new(&bad).unwrap_err().contains("weather"));
+    }
+}
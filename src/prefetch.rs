@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinHandle;
+use veilid_core::*;
+
+use crate::dht::Dht;
+use crate::stats::Feature;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`read <subkey>` at the alt prompt: one subkey, fetched from the network,
+//	and the rest of the record fetched behind it.
+//
+//	Someone who reads one subkey usually reads the next one too, and each
+//	read that goes out to the network takes a round trip or more. So once
+//	a subkey has been read, the others are fetched one at a time in the
+//	background, which lands them in Veilid's local record store; a `read`
+//	of one of those within FRESH_FOR takes the local copy and comes back
+//	straight away (it says "prefetched" when it did).
+//
+//	The prefetch goes through the op queue as a background call, so a read
+//	typed meanwhile goes ahead of it, and there's only ever one running;
+//	reading a different record starts that one's instead.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// How long a fetched value is taken as current without asking the network again.
+const FRESH_FOR: Duration = Duration::from_secs(60);
+
+type Fetched = Arc<Mutex<HashMap<(RecordKey, ValueSubkey), Instant>>>;
+
+pub struct Prefetcher {
+    rc: Dht,
+    // when each subkey last came from the network, by a read or the prefetch
+    fetched: Fetched,
+    task: Option<(RecordKey, JoinHandle<()>)>,
+}
+
+fn is_fresh(fetched: &Fetched, record: &RecordKey, subkey: ValueSubkey) -> bool {
+    fetched
+        .lock()
+        .unwrap()
+        .get(&(record.clone(), subkey))
+        .is_some_and(|at| at.elapsed() < FRESH_FOR)
+}
+
+impl Prefetcher {
+    pub fn new(rc: &Dht) -> Prefetcher {
+        Prefetcher {
+            rc: rc.for_feature(Feature::Prefetch),
+            fetched: Arc::new(Mutex::new(HashMap::new())),
+            task: None,
+        }
+    }
+
+    // Reads `subkey` with `rc` (locally if it was fetched lately, otherwise from
+    // the network), then starts fetching the rest of the record. True if the
+    // value was the local copy.
+    pub async fn read(
+        &mut self,
+        rc: &Dht,
+        record: &RecordKey,
+        subkey: ValueSubkey,
+        max_subkey: ValueSubkey,
+    ) -> (VeilidAPIResult<Option<ValueData>>, bool) {
+        let local = is_fresh(&self.fetched, record, subkey);
+        let value = rc.get_dht_value(record.clone(), subkey, !local).await;
+        if value.is_ok() && !local {
+            self.fetched.lock().unwrap().insert((record.clone(), subkey), Instant::now());
+        }
+        self.start(record, max_subkey);
+        (value, local)
+    }
+
+    fn start(&mut self, record: &RecordKey, max_subkey: ValueSubkey) {
+        if let Some((running, task)) = &self.task {
+            if running == record && !task.is_finished() {
+                return;
+            }
+            task.abort();
+        }
+        let (rc, fetched, key) = (self.rc.clone(), self.fetched.clone(), record.clone());
+        let task = tokio::spawn(async move {
+            for subkey in 0..=max_subkey {
+                if is_fresh(&fetched, &key, subkey) {
+                    continue;
+                }
+                if rc.get_dht_value(key.clone(), subkey, true).await.is_ok() {
+                    fetched.lock().unwrap().insert((key.clone(), subkey), Instant::now());
+                }
+            }
+        });
+        self.task = Some((record.clone(), task));
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        if let Some((_, task)) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryDht;
+
+    #[tokio::test]
+    async fn a_read_fetches_the_rest_so_the_next_read_is_local() {
+        let rc = Dht::with_backend(Arc::new(MemoryDht::new()), None, false, None);
+        let key = rc
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(3).unwrap(), None)
+            .await
+            .unwrap()
+            .key();
+        for subkey in 0..3 {
+            rc.set_dht_value(key.clone(), subkey, format!("value {subkey}").into_bytes(), None).await.unwrap();
+        }
+
+        let mut prefetch = Prefetcher::new(&rc);
+        let (value, local) = prefetch.read(&rc, &key, 1, 2).await;
+        assert_eq!(value.unwrap().unwrap().data(), b"value 1");
+        assert!(!local);
+        let (_, task) = prefetch.task.take().unwrap();
+        task.await.unwrap();
+
+        let (value, local) = prefetch.read(&rc, &key, 2, 2).await;
+        assert_eq!(value.unwrap().unwrap().data(), b"value 2");
+        assert!(local);
+        assert!(is_fresh(&prefetch.fetched, &key, 0));
+    }
+}
@@ -0,0 +1,298 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::audit::now_ms;
+use crate::config::AppConfig;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Checks run before a node calls api_startup, printed under a short
+//	banner. A lot of "Veilid is broken" turns out to be the machine:
+//
+//	  clock       Veilid timestamps and signs everything, and peers turn
+//	              away a node whose clock is far off
+//	  folders     the data folder and Veilid's stores must be writable
+//	  disk        the stores grow with every record; a full disk corrupts them
+//	  last run    a node that was killed (or crashed) leaves its marker
+//	              behind; it may have left the stores half written, or still
+//	              be running
+//
+//	Every problem comes with what to do about it. Only the ones Veilid
+//	can't start with (nowhere to write) stop us.
+//
+//	The marker is a small file, .running-<namespace>, made here and removed
+//	when the node shuts down cleanly. Finding one left over is also what
+//	tells recovery.rs to replay its journal.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// Nothing this program writes can be older than this (2024-01-01).
+const EARLIEST_SANE_MS: u128 = 1_704_067_200_000;
+// ... or this far ahead (2100-01-01).
+const LATEST_SANE_MS: u128 = 4_102_444_800_000;
+const DISK_LOW_BYTES: u64 = 200 * 1024 * 1024;
+const DISK_FULL_BYTES: u64 = 20 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Ok,
+    Warn,
+    // Veilid won't start like this
+    Fatal,
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub level: Level,
+    pub check: &'static str,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl Finding {
+    fn ok(check: &'static str, detail: impl Into<String>) -> Finding {
+        Finding {
+            level: Level::Ok,
+            check,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(level: Level, check: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Finding {
+        Finding {
+            level,
+            check,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+// Left behind unless the node shuts down cleanly (finish): an error out of
+// the node, a panic or a kill all count as unclean next time.
+pub struct RunMarker {
+    path: PathBuf,
+    // the last run in this namespace left its marker behind (see recovery.rs)
+    unclean: bool,
+}
+
+impl RunMarker {
+    pub fn after_unclean_exit(&self) -> bool {
+        self.unclean
+    }
+
+    pub fn finish(self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+enum LastRun {
+    Clean,
+    Unclean,
+    StillRunning(u32),
+}
+
+fn marker_path(data_dir: &Path, namespace: &str) -> PathBuf {
+    data_dir.join(format!(".running-{namespace}"))
+}
+
+// Run every check, print the banner and the findings, and leave our marker.
+// Errors if there's something Veilid can't start with.
+pub fn run(config: &AppConfig, data_dir: &Path, role: &str, namespace: &str) -> Result<RunMarker, String> {
+    println!(
+        "{} {} ({role} node, namespace {namespace})",
+        config.program_name,
+        env!("CARGO_PKG_VERSION")
+    );
+    println!("Data folder: {}", data_dir.to_string_lossy());
+
+    let last_run = last_run(data_dir, namespace);
+    let findings = vec![
+        check_clock(now_ms()),
+        check_folders(data_dir),
+        check_disk(data_dir),
+        check_last_run(&last_run, namespace),
+    ];
+    for f in &findings {
+        let mark = match f.level {
+            Level::Ok => "ok",
+            Level::Warn => "!!",
+            Level::Fatal => "XX",
+        };
+        println!("  [{mark}] {:<9} {}", f.check, f.detail);
+        if let Some(fix) = &f.fix {
+            println!("            fix: {fix}");
+        }
+    }
+    println!();
+
+    let fatal: Vec<&str> = findings.iter().filter(|f| f.level == Level::Fatal).map(|f| f.check).collect();
+    if !fatal.is_empty() {
+        return Err(format!("can't start Veilid, see the {} check(s) above", fatal.join(", ")));
+    }
+
+    let path = marker_path(data_dir, namespace);
+    fs::write(&path, format!("pid {}\nstarted_ms {}\n", std::process::id(), now_ms()))
+        .map_err(|e| format!("can't write {}: {e}", path.to_string_lossy()))?;
+    Ok(RunMarker {
+        path,
+        unclean: matches!(last_run, LastRun::Unclean),
+    })
+}
+
+fn check_clock(now: u128) -> Finding {
+    if (EARLIEST_SANE_MS..LATEST_SANE_MS).contains(&now) {
+        return Finding::ok("clock", "looks right");
+    }
+    let year = 1970 + now / 1000 / 31_556_952;
+    Finding::problem(
+        Level::Warn,
+        "clock",
+        format!("the system clock says it's {year}"),
+        "set the date and time (turn on network time sync); peers ignore nodes whose clock is far off",
+    )
+}
+
+// Make the folder if needed, then prove we can write into it.
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(".write_test");
+    fs::write(&probe, b"ok")?;
+    fs::remove_file(&probe)
+}
+
+fn check_folders(data_dir: &Path) -> Finding {
+    for sub in ["", ".veilid/protected_store", ".veilid/table_store"] {
+        let path = data_dir.join(sub);
+        if let Err(e) = check_writable(&path) {
+            return Finding::problem(
+                Level::Fatal,
+                "folders",
+                format!("{} is not writable: {e}", path.to_string_lossy()),
+                "fix the folder's permissions, or pick another with --data-dir",
+            );
+        }
+    }
+    Finding::ok("folders", "data folder and stores are writable")
+}
+
+fn check_disk(data_dir: &Path) -> Finding {
+    let Some(free) = free_space(data_dir) else {
+        return Finding::ok("disk", "couldn't tell how much space is free, skipped");
+    };
+    let detail = format!("{} MiB free", free / (1024 * 1024));
+    match free {
+        f if f < DISK_FULL_BYTES => Finding::problem(
+            Level::Fatal,
+            "disk",
+            detail,
+            "free some space; Veilid's stores get corrupted when a write fails halfway",
+        ),
+        f if f < DISK_LOW_BYTES => Finding::problem(
+            Level::Warn,
+            "disk",
+            detail,
+            "free some space soon, the stores grow with every record",
+        ),
+        _ => Finding::ok("disk", detail),
+    }
+}
+
+// The pid of a node still running in `namespace`, for tools that need it stopped.
+pub fn running_pid(data_dir: &Path, namespace: &str) -> Option<u32> {
+    match last_run(data_dir, namespace) {
+        LastRun::StillRunning(pid) => Some(pid),
+        LastRun::Clean | LastRun::Unclean => None,
+    }
+}
+
+fn last_run(data_dir: &Path, namespace: &str) -> LastRun {
+    let Ok(text) = fs::read_to_string(marker_path(data_dir, namespace)) else {
+        return LastRun::Clean;
+    };
+    let pid = text
+        .lines()
+        .find_map(|l| l.strip_prefix("pid "))
+        .and_then(|p| p.trim().parse::<u32>().ok());
+    match pid {
+        Some(pid) if is_running(pid) => LastRun::StillRunning(pid),
+        _ => LastRun::Unclean,
+    }
+}
+
+fn check_last_run(last_run: &LastRun, namespace: &str) -> Finding {
+    match last_run {
+        LastRun::Clean => Finding::ok("last run", "shut down cleanly"),
+        LastRun::StillRunning(pid) => Finding::problem(
+            Level::Warn,
+            "last run",
+            format!("another node (pid {pid}) may still be using namespace {namespace}"),
+            "stop it first, or give this one its own namespace; two nodes can't share the stores",
+        ),
+        LastRun::Unclean => Finding::problem(
+            Level::Warn,
+            "last run",
+            "the last node in this namespace didn't shut down cleanly",
+            "nothing to do if it starts fine; if Veilid reports a table store error, run `config validate` and remove the namespace's .veilid folder",
+        ),
+    }
+}
+
+// Free bytes on the disk holding `dir`, where we know how to ask.
+#[cfg(unix)]
+fn free_space(dir: &Path) -> Option<u64> {
+    // POSIX df, so no extra crates: the 4th column of the 2nd line, in KiB
+    let out = std::process::Command::new("df").arg("-Pk").arg(dir).output().ok()?;
+    let text = String::from_utf8(out.stdout).ok()?;
+    let kib: u64 = text.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(not(unix))]
+fn free_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn is_running(pid: u32) -> bool {
+    pid != std::process::id() && Path::new(&format!("/proc/{pid}")).exists()
+}
+
+// Nowhere cheap to ask, so assume it isn't.
+#[cfg(not(target_os = "linux"))]
+fn is_running(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_and_leftover_markers() {
+        assert_eq!(check_clock(EARLIEST_SANE_MS + 1).level, Level::Ok);
+        let stopped = check_clock(0);
+        assert_eq!(stopped.level, Level::Warn);
+        assert!(stopped.detail.contains("1970"));
+
+        let dir = std::env::temp_dir().join(format!("preflight-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert!(matches!(last_run(&dir, "ns"), LastRun::Clean));
+
+        // a marker nobody cleaned up, from a pid that can't be running
+        fs::write(marker_path(&dir, "ns"), "pid 4294967295\nstarted_ms 1\n").unwrap();
+        assert!(matches!(last_run(&dir, "ns"), LastRun::Unclean));
+        assert_eq!(check_last_run(&LastRun::Unclean, "ns").level, Level::Warn);
+
+        // ours stays until the node finishes cleanly
+        let marker = RunMarker {
+            path: marker_path(&dir, "ns"),
+            unclean: false,
+        };
+        assert!(matches!(last_run(&dir, "ns"), LastRun::Unclean));
+        marker.finish();
+        assert!(matches!(last_run(&dir, "ns"), LastRun::Clean));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
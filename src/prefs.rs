@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	How a prompt behaves, set from the prompt and kept in that role's table
+//	store, so the default and alt nodes each keep their own and the next
+//	run starts the way this one was left:
+//
+//	  set-option                       show them all
+//	  set-option refresh live|enter    live: each watched change is printed
+//	                                   as it comes; enter: they're held, and
+//	                                   ENTER reads the record (alt prompt)
+//	  set-option default-subkey N|none where plain text (default prompt) or
+//	                                   `write <text>` (alt) goes; none falls
+//	                                   back to write_subkey in the config
+//	  set-option confirm on|off        ask before each write, y to send it
+//	  set-option color on|off          colour the [watch], [read] and [mail]
+//	                                   tags (off to start with if NO_COLOR is set)
+//	  set-option edit-mode emacs|vi    the line editor's keys
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const TABLE: &str = "prefs";
+const COL_PREFS: u32 = 0;
+const KEY: &[u8] = b"prefs";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Refresh {
+    Live,
+    Enter,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EditMode {
+    Emacs,
+    Vi,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Prefs {
+    pub refresh: Refresh,
+    pub default_subkey: Option<ValueSubkey>,
+    pub confirm: bool,
+    pub color: bool,
+    pub edit_mode: EditMode,
+}
+
+impl Default for Prefs {
+    fn default() -> Prefs {
+        Prefs {
+            refresh: Refresh::Live,
+            default_subkey: None,
+            confirm: false,
+            color: std::env::var_os("NO_COLOR").is_none(),
+            edit_mode: EditMode::Emacs,
+        }
+    }
+}
+
+fn on_off(value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        other => Err(format!("'{other}' should be on or off")),
+    }
+}
+
+impl Prefs {
+    // Changes one option; what it's set to now.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<String, String> {
+        match name {
+            "refresh" => {
+                self.refresh = match value {
+                    "live" => Refresh::Live,
+                    "enter" => Refresh::Enter,
+                    other => return Err(format!("'{other}' should be live or enter")),
+                }
+            }
+            "default-subkey" => {
+                self.default_subkey = match value {
+                    "none" => None,
+                    n => Some(n.parse().map_err(|_| format!("'{n}' should be a subkey number or none"))?),
+                }
+            }
+            "confirm" => self.confirm = on_off(value)?,
+            "color" => self.color = on_off(value)?,
+            "edit-mode" => {
+                self.edit_mode = match value {
+                    "emacs" => EditMode::Emacs,
+                    "vi" => EditMode::Vi,
+                    other => return Err(format!("'{other}' should be emacs or vi")),
+                }
+            }
+            other => return Err(format!("No option called '{other}' (see `help set-option`)")),
+        }
+        Ok(format!("{name} is now {value}"))
+    }
+
+    pub fn report(&self) -> String {
+        let on = |b: bool| if b { "on" } else { "off" };
+        [
+            format!("  refresh         {}", if self.refresh == Refresh::Live { "live" } else { "enter" }),
+            format!(
+                "  default-subkey  {}",
+                self.default_subkey.map_or("none (write_subkey in the config)".to_string(), |s| s.to_string())
+            ),
+            format!("  confirm         {}", on(self.confirm)),
+            format!("  color           {}", on(self.color)),
+            format!("  edit-mode       {}", if self.edit_mode == EditMode::Vi { "vi" } else { "emacs" }),
+        ]
+        .join("\n")
+    }
+
+    // A [tag] for the start of a line, in cyan if colour is on.
+    pub fn tag(&self, tag: &str) -> String {
+        if self.color {
+            format!("\x1b[36m[{tag}]\x1b[0m")
+        } else {
+            format!("[{tag}]")
+        }
+    }
+}
+
+pub struct PrefStore {
+    // None in tests: kept in memory only
+    db: Option<TableDB>,
+    prefs: Prefs,
+}
+
+impl PrefStore {
+    pub async fn open(api: &VeilidAPI) -> VeilidAPIResult<PrefStore> {
+        let db = api.table_store()?.open(TABLE, 1).await?;
+        let prefs = db.load_json::<Prefs>(COL_PREFS, KEY).await?.unwrap_or_default();
+        Ok(PrefStore { db: Some(db), prefs })
+    }
+
+    #[cfg(test)]
+    fn in_memory() -> PrefStore {
+        PrefStore {
+            db: None,
+            prefs: Prefs::default(),
+        }
+    }
+
+    pub fn get(&self) -> &Prefs {
+        &self.prefs
+    }
+
+    pub async fn command(&mut self, args: &str) -> String {
+        let mut words = args.split_whitespace();
+        let (name, value) = match (words.next(), words.next(), words.next()) {
+            (None, _, _) => return format!("Options (set-option <name> <value> to change one):\n{}", self.prefs.report()),
+            (Some(name), Some(value), None) => (name, value),
+            _ => return "Usage: set-option <name> <value>".to_string(),
+        };
+        let mut changed = self.prefs.clone();
+        let outcome = match changed.set(name, value) {
+            Ok(outcome) => outcome,
+            Err(e) => return e,
+        };
+        if let Some(db) = &self.db {
+            if let Err(e) = db.store_json(COL_PREFS, KEY, &changed).await {
+                return format!("Couldn't save the option: {e}");
+            }
+        }
+        self.prefs = changed;
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn options_are_checked_before_they_change() {
+        let mut store = PrefStore::in_memory();
+        assert_eq!(store.command("refresh enter").await, "refresh is now enter");
+        assert_eq!(store.get().refresh, Refresh::Enter);
+        assert_eq!(store.command("default-subkey 3").await, "default-subkey is now 3");
+        assert_eq!(store.get().default_subkey, Some(3));
+        store.command("default-subkey none").await;
+        assert_eq!(store.get().default_subkey, None);
+        assert!(store.command("confirm maybe").await.contains("on or off"));
+        assert!(!store.get().confirm);
+        assert!(store.command("colour on").await.starts_with("No option"));
+        assert!(store.command("edit-mode").await.starts_with("Usage"));
+        store.command("color off").await;
+        assert_eq!(store.get().tag("read"), "[read]");
+        assert!(store.command("").await.contains("refresh         enter"));
+
+        // options saved by an older build keep the defaults for the rest
+        let old: Prefs = serde_json::from_str(r#"{"confirm": true}"#).unwrap();
+        assert!(old.confirm);
+        assert_eq!(old.edit_mode, EditMode::Emacs);
+    }
+}
@@ -0,0 +1,212 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::contacts::Contacts;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Profile cards: who a node is (a nickname, its public key, what it can
+//	do) and how to reach it (a private route), signed with that key.
+//
+//	Nodes swap cards over app_message the first time they meet. For now
+//	that's when the alt node joins a record: the default node's route is in
+//	the record's metadata block (subkey 0), so the alt node sends a Hello
+//	with its card there, and the default node answers with a Reply carrying
+//	its own. Both sides keep the other's card in their contacts (contacts.rs).
+//
+//	Cards are VLD0 (ed25519) signed, so a card can't claim someone else's
+//	key. Anything can still send one; the signature only proves the sender
+//	holds the key on the card.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// So a card message can be told apart from any other app_message.
+const CARD_MAGIC: &[u8] = b"VXCARD1\n";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProfileCard {
+    pub nickname: String,
+    pub public_key: String,
+    pub capabilities: Vec<String>,
+    // an imported-able private route blob (base64), if the node wants replies
+    pub route: Option<String>,
+    pub issued_ms: u64,
+    // base64 ed25519 signature over signed_bytes()
+    pub signature: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum CardMessage {
+    // "here's mine, send yours back"
+    Hello(ProfileCard),
+    Reply(ProfileCard),
+}
+
+impl ProfileCard {
+    pub fn new(
+        keypair: &KeyPair,
+        nickname: &str,
+        capabilities: &[&str],
+        route: Option<&[u8]>,
+    ) -> Result<ProfileCard, String> {
+        let mut card = ProfileCard {
+            nickname: nickname.to_string(),
+            public_key: keypair.key().to_string(),
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            route: route.map(|r| BASE64.encode(r)),
+            issued_ms: crate::audit::now_ms() as u64,
+            signature: String::new(),
+        };
+        let signing = signing_key(keypair)?;
+        card.signature = BASE64.encode(signing.sign(&card.signed_bytes()).to_bytes());
+        Ok(card)
+    }
+
+    // Everything but the signature, in a fixed order.
+    fn signed_bytes(&self) -> Vec<u8> {
+        format!(
+            "{}\n{}\n{}\n{}\n{}",
+            self.nickname,
+            self.public_key,
+            self.capabilities.join(","),
+            self.route.as_deref().unwrap_or(""),
+            self.issued_ms
+        )
+        .into_bytes()
+    }
+
+    // Ok if the card was signed by the key it names.
+    pub fn verify(&self) -> Result<(), String> {
+        let key: PublicKey = self
+            .public_key
+            .parse()
+            .map_err(|e| format!("card has a bad public key: {e}"))?;
+        if key.kind() != CRYPTO_KIND_VLD0 {
+            return Err(format!("card key is {}, only VLD0 cards can be checked", key.kind()));
+        }
+        let bytes: [u8; 32] = key
+            .value()
+            .to_vec()
+            .try_into()
+            .map_err(|_| "card public key is the wrong length".to_string())?;
+        let verifying = VerifyingKey::from_bytes(&bytes).map_err(|e| format!("card public key is invalid: {e}"))?;
+        let sig: [u8; 64] = BASE64
+            .decode(&self.signature)
+            .ok()
+            .and_then(|s| s.try_into().ok())
+            .ok_or("card signature is malformed")?;
+        verifying
+            .verify(&self.signed_bytes(), &Signature::from_bytes(&sig))
+            .map_err(|_| "card signature doesn't match its key".to_string())
+    }
+
+    pub fn route_blob(&self) -> Option<Vec<u8>> {
+        self.route.as_ref().and_then(|r| BASE64.decode(r).ok())
+    }
+}
+
+fn signing_key(keypair: &KeyPair) -> Result<SigningKey, String> {
+    if keypair.kind() != CRYPTO_KIND_VLD0 {
+        return Err(format!("can only sign cards with VLD0 keys, not {}", keypair.kind()));
+    }
+    let secret: [u8; 32] = keypair
+        .ref_bare_secret()
+        .to_vec()
+        .try_into()
+        .map_err(|_| "secret key is the wrong length".to_string())?;
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+impl CardMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = CARD_MAGIC.to_vec();
+        out.extend(serde_json::to_vec(self).expect("card serializes"));
+        out
+    }
+
+    // None if it isn't a card message at all.
+    pub fn decode(data: &[u8]) -> Option<CardMessage> {
+        serde_json::from_slice(data.strip_prefix(CARD_MAGIC)?).ok()
+    }
+
+    pub fn card(&self) -> &ProfileCard {
+        match self {
+            CardMessage::Hello(card) | CardMessage::Reply(card) => card,
+        }
+    }
+}
+
+// Quick check for the update callback, before any decoding.
+pub fn is_card_message(data: &[u8]) -> bool {
+    data.starts_with(CARD_MAGIC)
+}
+
+// Send `msg` to the private route in `blob`.
+pub async fn send(api: &VeilidAPI, rc: &RoutingContext, blob: Vec<u8>, msg: &CardMessage) -> VeilidAPIResult<()> {
+    let route = api.import_remote_private_route(blob)?;
+    rc.app_message(Target::RouteId(route), msg.encode()).await
+}
+
+// A card message came in: check it, keep it, and answer a Hello with `mine`.
+// Gives back what to tell the user.
+pub async fn receive(
+    data: &[u8],
+    mine: &ProfileCard,
+    contacts: &mut Contacts,
+    api: &VeilidAPI,
+    rc: &RoutingContext,
+) -> String {
+    let Some(msg) = CardMessage::decode(data) else {
+        return "[contact] got a card message that doesn't decode".to_string();
+    };
+    let card = msg.card();
+    if let Err(e) = card.verify() {
+        return format!("[contact] ignored a card for '{}': {e}", card.nickname);
+    }
+    let mut out = match contacts.remember_card(card).await {
+        Ok(true) => format!("[contact] met {} ({})", card.nickname, card.public_key),
+        Ok(false) => format!("[contact] {} sent their card again", card.nickname),
+        Err(e) => format!("[contact] couldn't save {}'s card: {e}", card.nickname),
+    };
+    if let (CardMessage::Hello(_), Some(blob)) = (&msg, card.route_blob()) {
+        if let Err(e) = send(api, rc, blob, &CardMessage::Reply(mine.clone())).await {
+            out.push_str(&format!(", but couldn't send ours back: {e}"));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_cards_verify_and_tampered_ones_dont() {
+        let kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let card = ProfileCard::new(&kp, "alice", &["watch"], Some(b"route")).unwrap();
+        assert_eq!(card.verify(), Ok(()));
+        assert_eq!(card.route_blob().as_deref(), Some(&b"route"[..]));
+
+        let mut renamed = card.clone();
+        renamed.nickname = "mallory".to_string();
+        assert!(renamed.verify().is_err());
+
+        let other = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let mut stolen = card.clone();
+        stolen.public_key = other.key().to_string();
+        assert!(stolen.verify().is_err());
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let msg = CardMessage::Hello(ProfileCard::new(&kp, "bob", &[], None).unwrap());
+        let data = msg.encode();
+        assert!(is_card_message(&data));
+        assert_eq!(CardMessage::decode(&data), Some(msg));
+        assert_eq!(CardMessage::decode(b"hello there"), None);
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Progress feedback for the slow parts (attaching, creating records,
+//	reading or copying every subkey), which otherwise look like a hang.
+//
+//	Spinners and bars draw on stderr and show the time spent so far. When
+//	stderr isn't a terminal (logs, pipes, services) indicatif hides them.
+//	Print inside ProgressBar::suspend while a bar is up so the two don't
+//	draw over each other. (Not ProgressBar::println, which prints nothing
+//	at all once the bar is hidden.)
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const TICK: Duration = Duration::from_millis(100);
+
+// the last frame is what's left on screen once it's done
+const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏", "✔"];
+const FAILED_FRAMES: &[&str] = &["✘", "✘"];
+
+pub fn spinner(msg: &str) -> ProgressBar {
+    let bar = ProgressBar::new_spinner().with_message(msg.to_string());
+    bar.set_style(
+        ProgressStyle::with_template("{spinner:.green} {msg} [{elapsed}]")
+            .unwrap()
+            .tick_strings(SPINNER_FRAMES),
+    );
+    bar.enable_steady_tick(TICK);
+    bar
+}
+
+// One step per subkey.
+pub fn subkeys(len: u64, msg: &str) -> ProgressBar {
+    let bar = ProgressBar::new(len).with_message(msg.to_string());
+    bar.set_style(
+        ProgressStyle::with_template("{msg} [{bar:30.cyan/blue}] {pos}/{len} subkeys [{elapsed}]")
+            .unwrap()
+            .progress_chars("=> "),
+    );
+    bar.enable_steady_tick(TICK);
+    bar
+}
+
+// Run `fut` with a spinner, leaving "<msg> done" and the time taken behind,
+// or a cross and the error if it failed.
+pub async fn spin<T, E: Display>(msg: &str, fut: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+    let bar = spinner(msg);
+    let out = fut.await;
+    match &out {
+        Ok(_) => bar.finish_with_message(format!("{msg} done")),
+        Err(e) => {
+            bar.set_style(
+                ProgressStyle::with_template("{spinner:.red} {msg} [{elapsed}]")
+                    .unwrap()
+                    .tick_strings(FAILED_FRAMES),
+            );
+            bar.abandon_with_message(format!("{msg} failed: {e}"));
+        }
+    }
+    out
+}
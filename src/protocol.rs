@@ -0,0 +1,149 @@
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::envelope::{Codec, Envelope};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Which version of the example's protocol a record is written in, so two
+//	builds sharing a record find out they don't agree before one of them
+//	mis-reads the other's values.
+//
+//	The protocol is everything about a record this program decides: the
+//	envelope, the metadata block, where the mailbox and receipts are, and
+//	so on. PROTOCOL goes up when any of that changes in a way an older
+//	build would get wrong.
+//
+//	The default node puts the version it writes in owner subkey 2 when it
+//	makes the record, with the oldest version that can still read the
+//	record properly. A joining node compares that with what it supports:
+//
+//	  the record's version is one it speaks          everything as normal
+//	  newer, but it can still be read by this build  read only: this build
+//	                                                 would write it wrong
+//	  newer, and this build can't read it, or older  refused, with what to
+//	  than anything this build still reads           upgrade or go back to
+//
+//	Records with fewer than three owner subkeys, and those made before
+//	there was a protocol subkey, don't say; they're taken as version 1.
+//	It's kept to its own small subkey, apart from the metadata block, so
+//	that a build of any age can always read it.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// The owner subkey the protocol version goes in, on records with at least three.
+pub const PROTOCOL_SUBKEY: ValueSubkey = 2;
+// What this build writes. 2 added the codec pipeline (FLAG_PIPELINE, see
+// codecs.rs), which a build speaking 1 can't take off again.
+pub const PROTOCOL: u32 = 2;
+// The first version with the codec pipeline.
+const PIPELINE: u32 = 2;
+// What this build reads and writes in full.
+pub const SUPPORTED: RangeInclusive<u32> = 1..=PROTOCOL;
+// What a record that doesn't say is taken to be.
+const UNSTATED: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolValue {
+    pub version: u32,
+    // the oldest version that can read the record without getting it wrong
+    pub min_reader: u32,
+    // the build that wrote it, for the message when they don't agree
+    pub app_version: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Full,
+    // why this build mustn't write to the record
+    ReadOnly(String),
+    // why this build can't use the record at all
+    Refused(String),
+}
+
+impl ProtocolValue {
+    // What this build says about the records it makes. Values that go
+    // through a codec pipeline can only be read from PIPELINE on; without
+    // one, anything this build still reads can read them too.
+    pub fn ours(pipelined: bool) -> ProtocolValue {
+        ProtocolValue {
+            version: PROTOCOL,
+            min_reader: if pipelined { PIPELINE } else { *SUPPORTED.start() },
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        Envelope::new(Codec::Json, serde_json::to_vec(self).expect("protocol value serializes")).encode()
+    }
+
+    // None if the subkey holds something else.
+    pub fn decode(data: &[u8]) -> Option<ProtocolValue> {
+        let env = Envelope::decode(data).ok()?;
+        if env.codec != Codec::Json {
+            return None;
+        }
+        serde_json::from_slice(&env.body).ok()
+    }
+}
+
+// Where the record keeps its protocol version, if it has room for one.
+pub fn subkey(schema: &DHTSchema) -> Option<ValueSubkey> {
+    let owner_subkeys = match schema {
+        DHTSchema::DFLT(dflt) => dflt.o_cnt(),
+        DHTSchema::SMPL(smpl) => smpl.o_cnt(),
+    };
+    (ValueSubkey::from(owner_subkeys) > PROTOCOL_SUBKEY).then_some(PROTOCOL_SUBKEY)
+}
+
+// Whether this build can use a record that says `stated` (None if it doesn't say).
+pub fn negotiate(stated: Option<&ProtocolValue>) -> Verdict {
+    let (version, min_reader, by) = match stated {
+        Some(p) => (p.version, p.min_reader, p.app_version.as_str()),
+        None => (UNSTATED, UNSTATED, "an older build"),
+    };
+    let ours = format!("this build ({}) speaks {}..={}", env!("CARGO_PKG_VERSION"), SUPPORTED.start(), SUPPORTED.end());
+    if SUPPORTED.contains(&version) {
+        Verdict::Full
+    } else if version < *SUPPORTED.start() {
+        Verdict::Refused(format!(
+            "the record is in protocol {version} (made by {by}), which {ours} no longer reads; use an older build"
+        ))
+    } else if min_reader <= PROTOCOL {
+        Verdict::ReadOnly(format!(
+            "the record is in protocol {version} (made by {by}) and {ours}, so it can read the record but not write to it; upgrade to write"
+        ))
+    } else {
+        Verdict::Refused(format!(
+            "the record is in protocol {version} (made by {by}) and needs at least {min_reader} to read, but {ours}; upgrade to join it"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stated(version: u32, min_reader: u32) -> ProtocolValue {
+        ProtocolValue {
+            version,
+            min_reader,
+            app_version: "9.9.9".to_string(),
+        }
+    }
+
+    #[test]
+    fn newer_records_are_read_only_or_refused() {
+        let ours = ProtocolValue::ours(false);
+        assert_eq!(ProtocolValue::decode(&ours.encode()), Some(ours.clone()));
+        assert_eq!(negotiate(None), Verdict::Full);
+        assert_eq!(negotiate(Some(&ours)), Verdict::Full);
+        // a build that only speaks 1 can't take a pipelined record's values apart
+        assert_eq!(ProtocolValue::ours(true).min_reader, PIPELINE);
+        assert!(matches!(negotiate(Some(&stated(PROTOCOL + 1, PROTOCOL))), Verdict::ReadOnly(why) if why.contains("9.9.9")));
+        assert!(matches!(negotiate(Some(&stated(PROTOCOL + 2, PROTOCOL + 1))), Verdict::Refused(why) if why.contains("upgrade")));
+        assert!(matches!(negotiate(Some(&stated(0, 0))), Verdict::Refused(why) if why.contains("older build")));
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use veilid_core::*;
+
+use crate::dht::Dht;
+use crate::expect::parse_duration;
+use crate::payloads::Registry;
+use crate::schema::pick_writer;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`publish ...`: write something to a subkey on a timer, so a node can
+//	keep a status subkey up to date on its own.
+//
+//	  publish every 5m subkey 3 from-command "uptime"
+//	                      run the command (sh -c, or cmd /C on Windows) and
+//	                      write what it prints
+//	  publish every 30s subkey 3 template "{node} up, run {count} at {time}"
+//	                      write the text, with {node} (our nickname), {count}
+//	                      (how many times this one has run), {time} (UTC,
+//	                      hh:mm:ss) and {subkey} filled in
+//	  publish             list what's running, with when each last wrote
+//	  publish stop 2      stop one (or `publish stop all`)
+//
+//	The first write happens straight away. Each one goes through the
+//	payload types like a write at the prompt, with whichever of our keys
+//	may write the subkey, and a failure (the command exiting non-zero, a
+//	value the subkey's type won't take, a set that fails) is printed and
+//	tried again next time round. Nothing is kept across restarts.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// anything more often than this is `flood`'s job
+const MIN_EVERY: Duration = Duration::from_secs(10);
+// a command that takes longer than this (or the interval, if shorter) is given up on
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+const USAGE: &str = "Usage: publish every <5m|30s> subkey <n> from-command \"<cmd>\" | template \"<text>\"; publish; publish stop <id>|all";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    Command(String),
+    Template(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    pub every: Duration,
+    pub subkey: ValueSubkey,
+    pub source: Source,
+}
+
+fn unquote(text: &str) -> &str {
+    let text = text.trim();
+    text.strip_prefix('"').and_then(|t| t.strip_suffix('"')).unwrap_or(text)
+}
+
+impl Schedule {
+    // `every <dur> subkey <n> from-command "<cmd>"` or `... template "<text>"`.
+    pub fn parse(args: &str) -> Result<Schedule, String> {
+        let mut words = args.trim().splitn(5, ' ');
+        let (Some("every"), Some(every), Some("subkey"), Some(subkey), Some(rest)) =
+            (words.next(), words.next(), words.next(), words.next(), words.next())
+        else {
+            return Err(USAGE.to_string());
+        };
+        let every = parse_duration(every).ok_or(format!("'{every}' isn't an interval like 30s or 5m"))?;
+        if every < MIN_EVERY {
+            return Err(format!("every {}s is too often; {}s at the least", every.as_secs(), MIN_EVERY.as_secs()));
+        }
+        let subkey = subkey.parse().map_err(|_| format!("'{subkey}' isn't a subkey number"))?;
+        let (kind, text) = rest.trim().split_once(' ').ok_or(USAGE)?;
+        let text = unquote(text);
+        if text.is_empty() {
+            return Err(USAGE.to_string());
+        }
+        let source = match kind {
+            "from-command" => Source::Command(text.to_string()),
+            "template" => Source::Template(text.to_string()),
+            _ => return Err(USAGE.to_string()),
+        };
+        Ok(Schedule { every, subkey, source })
+    }
+
+    fn describe(&self) -> String {
+        let source = match &self.source {
+            Source::Command(cmd) => format!("from-command \"{cmd}\""),
+            Source::Template(text) => format!("template \"{text}\""),
+        };
+        format!("every {}s subkey {} {source}", self.every.as_secs(), self.subkey)
+    }
+}
+
+// hh:mm:ss UTC
+fn clock(now_ms: u64) -> String {
+    let secs = now_ms / 1000 % 86_400;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+pub fn fill(template: &str, node: &str, count: u64, subkey: ValueSubkey, now_ms: u64) -> String {
+    template
+        .replace("{node}", node)
+        .replace("{count}", &count.to_string())
+        .replace("{subkey}", &subkey.to_string())
+        .replace("{time}", &clock(now_ms))
+}
+
+async fn run_command(command: &str, timeout: Duration) -> Result<String, String> {
+    let shell = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let out = tokio::time::timeout(timeout, tokio::process::Command::new(shell.0).args([shell.1, command]).kill_on_drop(true).output())
+        .await
+        .map_err(|_| format!("`{command}` took longer than {}s", timeout.as_secs()))?
+        .map_err(|e| format!("`{command}` couldn't run: {e}"))?;
+    if !out.status.success() {
+        return Err(format!("`{command}` failed ({})", out.status));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim_end().to_string())
+}
+
+// What a schedule needs to write, shared with its task.
+#[derive(Clone)]
+struct Target {
+    rc: Dht,
+    record: RecordKey,
+    registry: Registry,
+    node: String,
+}
+
+#[derive(Default)]
+struct Progress {
+    runs: u64,
+    last_ms: Option<u64>,
+    last_error: Option<String>,
+}
+
+struct Job {
+    schedule: Schedule,
+    progress: Arc<Mutex<Progress>>,
+    task: JoinHandle<()>,
+}
+
+// One run: make the text and write it. Gives back what was written.
+async fn publish_once(target: &Target, schedule: &Schedule, writer: &KeyPair, count: u64) -> Result<String, String> {
+    let text = match &schedule.source {
+        Source::Command(cmd) => run_command(cmd, COMMAND_TIMEOUT.min(schedule.every)).await?,
+        Source::Template(text) => fill(text, &target.node, count, schedule.subkey, crate::audit::now_ms() as u64),
+    };
+    let value = target.registry.encode(schedule.subkey, &text)?;
+    let opts = SetDHTValueOptions {
+        writer: Some(writer.clone()),
+        allow_offline: None,
+    };
+    target
+        .rc
+        .set_dht_value(target.record.clone(), schedule.subkey, value.encode(), Some(opts))
+        .await
+        .map_err(|e| format!("couldn't write subkey {}: {e}", schedule.subkey))?;
+    Ok(text)
+}
+
+pub struct Publisher {
+    target: Target,
+    schema: DHTSchema,
+    owner: PublicKey,
+    // every key we hold for the record; pick_writer finds the one for a subkey
+    keys: Vec<KeyPair>,
+    next_id: u32,
+    jobs: BTreeMap<u32, Job>,
+}
+
+impl Publisher {
+    pub fn new(
+        rc: Dht,
+        record: RecordKey,
+        schema: DHTSchema,
+        owner: PublicKey,
+        keys: Vec<KeyPair>,
+        registry: Registry,
+        node: &str,
+    ) -> Publisher {
+        Publisher {
+            target: Target {
+                rc,
+                record,
+                registry,
+                node: node.to_string(),
+            },
+            schema,
+            owner,
+            keys,
+            next_id: 1,
+            jobs: BTreeMap::new(),
+        }
+    }
+
+    // `publish ...` at the prompt.
+    pub fn command(&mut self, args: &str) -> String {
+        let args = args.trim();
+        if args.is_empty() || args == "list" {
+            return self.list();
+        }
+        if let Some(which) = args.strip_prefix("stop ") {
+            return self.stop(which.trim());
+        }
+        let schedule = match Schedule::parse(args) {
+            Ok(schedule) => schedule,
+            Err(e) => return e,
+        };
+        let keys: Vec<&KeyPair> = self.keys.iter().collect();
+        let writer = match pick_writer(&self.schema, &self.owner, &keys, Some(schedule.subkey)) {
+            Ok((_, writer)) => writer.clone(),
+            Err(e) => return format!("Not scheduled: {e}"),
+        };
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        let task = tokio::spawn(run(self.target.clone(), schedule.clone(), writer, id, progress.clone()));
+        let described = schedule.describe();
+        self.jobs.insert(id, Job { schedule, progress, task });
+        format!("Publishing #{id}: {described} (starting now; 'publish stop {id}' to stop)")
+    }
+
+    fn list(&self) -> String {
+        if self.jobs.is_empty() {
+            return "Nothing is being published (see `help publish`)".to_string();
+        }
+        let now = crate::audit::now_ms() as u64;
+        let mut lines = Vec::new();
+        for (id, job) in &self.jobs {
+            let progress = job.progress.lock().unwrap();
+            let last = match progress.last_ms {
+                Some(ms) => format!("last wrote {}s ago", now.saturating_sub(ms) / 1000),
+                None => "hasn't written yet".to_string(),
+            };
+            let error = progress.last_error.as_ref().map(|e| format!("; last failure: {e}")).unwrap_or_default();
+            lines.push(format!("  #{id} {}: {} run(s), {last}{error}", job.schedule.describe(), progress.runs));
+        }
+        lines.join("\n")
+    }
+
+    fn stop(&mut self, which: &str) -> String {
+        if which == "all" {
+            let n = self.jobs.len();
+            for job in std::mem::take(&mut self.jobs).into_values() {
+                job.task.abort();
+            }
+            return format!("Stopped {n} publisher(s)");
+        }
+        let job = which.trim_start_matches('#').parse().ok().and_then(|id| self.jobs.remove(&id));
+        match job {
+            Some(job) => {
+                job.task.abort();
+                format!("Stopped publishing {}", job.schedule.describe())
+            }
+            None => format!("No publisher #{which} (type `publish` to list them)"),
+        }
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        for job in self.jobs.values() {
+            job.task.abort();
+        }
+    }
+}
+
+async fn run(target: Target, schedule: Schedule, writer: KeyPair, id: u32, progress: Arc<Mutex<Progress>>) {
+    let mut ticks = tokio::time::interval(schedule.every);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut count = 0;
+    loop {
+        ticks.tick().await;
+        count += 1;
+        let result = publish_once(&target, &schedule, &writer, count).await;
+        let mut progress = progress.lock().unwrap();
+        progress.runs = count;
+        match result {
+            Ok(_) => {
+                progress.last_ms = Some(crate::audit::now_ms() as u64);
+                progress.last_error = None;
+            }
+            Err(e) => {
+                // once per new failure, not every time round
+                if progress.last_error.as_ref() != Some(&e) {
+                    println!("[publish #{id}] {e}");
+                }
+                progress.last_error = Some(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryDht;
+    use crate::envelope::Envelope;
+
+    #[tokio::test]
+    async fn schedules_parse_and_a_template_is_filled_and_written() {
+        assert_eq!(
+            Schedule::parse("every 5m subkey 3 from-command \"uptime -p\""),
+            Ok(Schedule {
+                every: Duration::from_secs(300),
+                subkey: 3,
+                source: Source::Command("uptime -p".to_string()),
+            })
+        );
+        assert!(Schedule::parse("every 1s subkey 3 template hi").unwrap_err().contains("too often"));
+        assert!(Schedule::parse("every 5m subkey x template hi").is_err());
+        assert!(Schedule::parse("every 5m subkey 3 shout hi").is_err());
+        assert_eq!(fill("{node} #{count} at {time}", "alt-node", 2, 3, 3_723_000), "alt-node #2 at 01:02:03");
+
+        let rc = Dht::with_backend(std::sync::Arc::new(MemoryDht::new()), None, false, None);
+        let writer = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let desc = rc.create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(4).unwrap(), Some(writer.clone())).await.unwrap();
+        let mut publisher = Publisher::new(
+            rc.clone(),
+            desc.key(),
+            desc.schema(),
+            desc.owner(),
+            vec![writer.clone()],
+            Registry::default(),
+            "alt-node",
+        );
+        let schedule = Schedule::parse("every 1m subkey 2 template \"{node} run {count}\"").unwrap();
+        let text = publish_once(&publisher.target, &schedule, &writer, 7).await.unwrap();
+        assert_eq!(text, "alt-node run 7");
+        let stored = rc.get_dht_value(desc.key(), 2, false).await.unwrap().unwrap();
+        assert_eq!(Envelope::decode(stored.data()).unwrap().display(), "alt-node run 7");
+
+        assert!(publisher.command("every 1m subkey 9 template x").starts_with("Not scheduled"));
+        assert!(publisher.command("every 1m subkey 1 template x").starts_with("Publishing #1"));
+        assert!(publisher.command("list").contains("#1 every 60s subkey 1"));
+        assert!(publisher.command("stop 1").starts_with("Stopped"));
+        assert!(publisher.command("stop 1").starts_with("No publisher"));
+    }
+}
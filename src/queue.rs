@@ -0,0 +1,246 @@
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::stats::Feature;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	One queue for all of a node's DHT calls, so the ones the user is waiting
+//	on go first.
+//
+//	Every call through the Dht wrapper takes a slot here before it runs,
+//	with a priority that comes from the feature its handle is for:
+//
+//	  interactive   values, metadata, flood     what someone just typed
+//	  sync          mail, watch                 keeping up with other nodes
+//	  background    janitor, discovery,         tidying up, re-announcing and
+//	                prefetch                    fetching what might be read next
+//
+//	A call only starts while fewer than `total` are running, fewer than its
+//	own class's limit are, and nothing of a higher class is waiting. So the
+//	janitor gets one call at a time, never more, and a get typed at the
+//	prompt never sits behind a sweep of expired values or a mail poll; at
+//	worst it waits for calls already on the wire to finish.
+//
+//	`stats queue` at either prompt shows how long each class has waited.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Interactive,
+    Sync,
+    Background,
+}
+
+const PRIORITIES: [Priority; 3] = [Priority::Interactive, Priority::Sync, Priority::Background];
+
+impl Priority {
+    pub fn name(self) -> &'static str {
+        match self {
+            Priority::Interactive => "interactive",
+            Priority::Sync => "sync",
+            Priority::Background => "background",
+        }
+    }
+
+    pub fn of(feature: Feature) -> Priority {
+        match feature {
+            Feature::Values | Feature::Metadata | Feature::Flood => Priority::Interactive,
+            Feature::Mail | Feature::Watch => Priority::Sync,
+            Feature::Janitor | Feature::Discovery | Feature::Prefetch => Priority::Background,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    // calls running at once, all classes together
+    pub total: usize,
+    // and of each class, by Priority
+    pub per_class: [usize; 3],
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            total: 6,
+            per_class: [6, 3, 1],
+        }
+    }
+}
+
+#[derive(Default)]
+struct Class {
+    running: usize,
+    waiting: usize,
+    // calls started so far, and how long they queued for
+    started: u64,
+    waited: Duration,
+    longest_wait: Duration,
+}
+
+pub struct OpQueue {
+    limits: Limits,
+    classes: Mutex<[Class; 3]>,
+    // told whenever a slot frees up or a waiter leaves
+    changed: Notify,
+}
+
+// A slot in the queue; the next call can have it once this is dropped.
+pub struct Permit<'a> {
+    queue: &'a OpQueue,
+    priority: Priority,
+}
+
+// Counts a caller as waiting until it gets a slot or gives up.
+struct Waiting<'a> {
+    queue: &'a OpQueue,
+    priority: Priority,
+    counted: bool,
+}
+
+impl OpQueue {
+    pub fn new(limits: Limits) -> OpQueue {
+        OpQueue {
+            limits,
+            classes: Mutex::new(Default::default()),
+            changed: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, [Class; 3]> {
+        self.classes.lock().unwrap()
+    }
+
+    fn may_start(&self, classes: &[Class; 3], priority: Priority) -> bool {
+        let i = priority.index();
+        classes.iter().map(|c| c.running).sum::<usize>() < self.limits.total
+            && classes[i].running < self.limits.per_class[i]
+            && classes[..i].iter().all(|c| c.waiting == 0)
+    }
+
+    pub async fn acquire(&self, priority: Priority) -> Permit<'_> {
+        let queued = Instant::now();
+        self.lock()[priority.index()].waiting += 1;
+        let mut waiting = Waiting {
+            queue: self,
+            priority,
+            counted: true,
+        };
+        loop {
+            // registered before looking, so a slot freed in between still wakes us
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            {
+                let mut classes = self.lock();
+                if self.may_start(&classes, priority) {
+                    let class = &mut classes[priority.index()];
+                    let waited = queued.elapsed();
+                    class.waiting -= 1;
+                    class.running += 1;
+                    class.started += 1;
+                    class.waited += waited;
+                    class.longest_wait = class.longest_wait.max(waited);
+                    waiting.counted = false;
+                    drop(classes);
+                    // one fewer waiting may let a lower class in
+                    self.changed.notify_waiters();
+                    return Permit { queue: self, priority };
+                }
+            }
+            changed.await;
+        }
+    }
+
+    // For `stats queue`.
+    pub fn report(&self) -> String {
+        let classes = self.lock();
+        let mut lines = vec![format!("DHT call queue (at most {} at once):", self.limits.total)];
+        for p in PRIORITIES {
+            let c = &classes[p.index()];
+            let average = if c.started == 0 {
+                0.0
+            } else {
+                c.waited.as_secs_f32() / c.started as f32
+            };
+            lines.push(format!(
+                "  {:<12} {} calls, waited {:.2}s on average and {:.2}s at most; {} running, {} waiting (limit {})",
+                p.name(),
+                c.started,
+                average,
+                c.longest_wait.as_secs_f32(),
+                c.running,
+                c.waiting,
+                self.limits.per_class[p.index()]
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.queue.lock()[self.priority.index()].running -= 1;
+        self.queue.changed.notify_waiters();
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        // the call was dropped before it got a slot
+        if self.counted {
+            self.queue.lock()[self.priority.index()].waiting -= 1;
+            self.queue.changed.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn higher_classes_go_first_and_each_keeps_to_its_limit() {
+        let queue = OpQueue::new(Limits {
+            total: 1,
+            per_class: [1, 1, 1],
+        });
+        let first = queue.acquire(Priority::Background).await;
+        let order = Mutex::new(Vec::new());
+        // the background call queues first, but the interactive one gets the slot
+        let background = async {
+            let _slot = queue.acquire(Priority::Background).await;
+            order.lock().unwrap().push(Priority::Background);
+        };
+        let interactive = async {
+            let _slot = queue.acquire(Priority::Interactive).await;
+            order.lock().unwrap().push(Priority::Interactive);
+        };
+        let release = async move {
+            tokio::task::yield_now().await;
+            drop(first);
+        };
+        tokio::join!(background, interactive, release);
+        assert_eq!(*order.lock().unwrap(), vec![Priority::Interactive, Priority::Background]);
+
+        // background gets one slot of the two, and a caller that gives up stops counting as waiting
+        let queue = OpQueue::new(Limits {
+            total: 2,
+            per_class: [2, 2, 1],
+        });
+        let _sweep = queue.acquire(Priority::Background).await;
+        let second = tokio::time::timeout(Duration::from_millis(50), queue.acquire(Priority::Background)).await;
+        assert!(second.is_err());
+        let _typed = queue.acquire(Priority::Interactive).await;
+        assert!(queue.report().contains("1 running, 0 waiting (limit 1)"), "{}", queue.report());
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+
+use veilid_core::*;
+
+use crate::schema::writable_subkeys;
+use crate::stats::bytes;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	How full each record is, and whose bytes are in it.
+//
+//	Veilid caps a value at ValueData::MAX_LEN and everything stored for a
+//	record at MAX_RECORD_DATA, but nothing tells a writer how close it is
+//	until a set fails. So the Dht wrapper tells us the size and writer of
+//	every value it reads or writes, and we keep the latest per subkey. A
+//	writer's allotment is its schema subkeys times MAX_LEN, and never more
+//	than the record can hold.
+//
+//	This is only what this node has seen: subkeys it never read count as
+//	empty, and someone else's write shows up once we read it back.
+//
+//	A write that takes a writer or the record past WARN_AT of its limit
+//	prints a warning, once until it drops back under; a single value only
+//	warns past VALUE_WARN_AT, since the next one at that size may not go
+//	in at all. `quota` at either prompt shows the totals per writer.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// Veilid's limit on the data stored for one record, all subkeys together
+// (storage_manager's MAX_RECORD_DATA_SIZE, which isn't exported).
+pub const MAX_RECORD_DATA: usize = 1_048_576;
+// share of a limit that gets a warning
+const WARN_AT: f64 = 0.8;
+// the same for one value, which a writer fills on purpose more often than a whole share
+const VALUE_WARN_AT: f64 = 0.95;
+
+struct RecordUsage {
+    schema: DHTSchema,
+    owner: PublicKey,
+    // who a set without a writer of its own writes as
+    default_writer: Option<PublicKey>,
+    // the latest value seen at each subkey: writer and size
+    subkeys: BTreeMap<ValueSubkey, (PublicKey, usize)>,
+    // limits already warned about, so a flood doesn't repeat them
+    warned: HashSet<String>,
+}
+
+// One writer's share of a record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriterUsage {
+    pub writer: PublicKey,
+    pub role: &'static str,
+    pub subkeys: usize,
+    pub bytes: usize,
+    pub largest: usize,
+    // what the schema lets it store, capped at what the record can hold
+    pub allotted: usize,
+}
+
+fn share(used: usize, limit: usize) -> f64 {
+    if limit == 0 {
+        1.0
+    } else {
+        used as f64 / limit as f64
+    }
+}
+
+fn percent(used: usize, limit: usize) -> String {
+    format!("{:.0}%", share(used, limit) * 100.0)
+}
+
+impl RecordUsage {
+    fn allotted(&self, writer: &PublicKey) -> usize {
+        let subkeys: usize = writable_subkeys(&self.schema, &self.owner, writer)
+            .iter()
+            .map(|(first, last)| (last - first + 1) as usize)
+            .sum();
+        (subkeys * ValueData::MAX_LEN).min(MAX_RECORD_DATA)
+    }
+
+    fn role(&self, writer: &PublicKey) -> &'static str {
+        if *writer == self.owner {
+            "owner"
+        } else if writable_subkeys(&self.schema, &self.owner, writer).is_empty() {
+            "not in schema"
+        } else {
+            "member"
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.subkeys.values().map(|(_, size)| size).sum()
+    }
+
+    fn writers(&self) -> Vec<WriterUsage> {
+        let mut by_writer: BTreeMap<String, WriterUsage> = BTreeMap::new();
+        for (writer, size) in self.subkeys.values() {
+            let usage = by_writer.entry(writer.to_string()).or_insert_with(|| WriterUsage {
+                writer: writer.clone(),
+                role: self.role(writer),
+                subkeys: 0,
+                bytes: 0,
+                largest: 0,
+                allotted: self.allotted(writer),
+            });
+            usage.subkeys += 1;
+            usage.bytes += size;
+            usage.largest = usage.largest.max(*size);
+        }
+        let mut writers: Vec<WriterUsage> = by_writer.into_values().collect();
+        writers.sort_by_key(|w| std::cmp::Reverse(w.bytes));
+        writers
+    }
+
+    // Warns once when `used` goes past `warn_at` of `limit`, and forgets it when it drops back.
+    fn check(&mut self, what: String, used: usize, limit: usize, warn_at: f64, warnings: &mut Vec<String>) {
+        if share(used, limit) >= warn_at {
+            if self.warned.insert(what.clone()) {
+                warnings.push(format!(
+                    "Warning: {what} is at {} of {} ({})",
+                    bytes(used as u64),
+                    bytes(limit as u64),
+                    percent(used, limit)
+                ));
+            }
+        } else {
+            self.warned.remove(&what);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Quota {
+    records: Mutex<HashMap<RecordKey, RecordUsage>>,
+}
+
+impl Quota {
+    pub fn new() -> Quota {
+        Quota::default()
+    }
+
+    // A record was created or opened; `writer` is the one it was opened with.
+    pub fn opened(&self, desc: &DHTRecordDescriptor, writer: Option<PublicKey>) {
+        let mut records = self.records.lock().unwrap();
+        let usage = records.entry(desc.key()).or_insert_with(|| RecordUsage {
+            schema: desc.schema(),
+            owner: desc.owner(),
+            default_writer: None,
+            subkeys: BTreeMap::new(),
+            warned: HashSet::new(),
+        });
+        usage.default_writer = writer;
+    }
+
+    // A value was read back.
+    pub fn saw(&self, record_key: &RecordKey, subkey: ValueSubkey, writer: &PublicKey, size: usize) {
+        if let Some(usage) = self.records.lock().unwrap().get_mut(record_key) {
+            usage.subkeys.insert(subkey, (writer.clone(), size));
+        }
+    }
+
+    // A value was written, with `writer` or else the record's default one.
+    // Gives back any warnings to print.
+    pub fn wrote(&self, record_key: &RecordKey, subkey: ValueSubkey, writer: Option<&PublicKey>, size: usize) -> Vec<String> {
+        let mut records = self.records.lock().unwrap();
+        let Some(usage) = records.get_mut(record_key) else {
+            return Vec::new();
+        };
+        let Some(writer) = writer.cloned().or_else(|| usage.default_writer.clone()) else {
+            return Vec::new();
+        };
+        usage.subkeys.insert(subkey, (writer.clone(), size));
+
+        let mut warnings = Vec::new();
+        usage.check(format!("the value at subkey {subkey}"), size, ValueData::MAX_LEN, VALUE_WARN_AT, &mut warnings);
+        let mine = usage
+            .subkeys
+            .values()
+            .filter(|(w, _)| *w == writer)
+            .map(|(_, size)| size)
+            .sum();
+        let allotted = usage.allotted(&writer);
+        usage.check(format!("{} {}'s share", usage.role(&writer), writer), mine, allotted, WARN_AT, &mut warnings);
+        let total = usage.total();
+        usage.check("the record".to_string(), total, MAX_RECORD_DATA, WARN_AT, &mut warnings);
+        warnings
+    }
+
+    // What `quota` prints; `label` names a writer (nicknames, usually).
+    pub fn report(&self, record_key: &RecordKey, label: impl Fn(&PublicKey) -> String) -> String {
+        let records = self.records.lock().unwrap();
+        let Some(usage) = records.get(record_key) else {
+            return "This node hasn't opened that record".to_string();
+        };
+        let total = usage.total();
+        let mut lines = vec![format!(
+            "{} of the {} a record can hold ({}), over the {} subkey(s) this node has seen:",
+            bytes(total as u64),
+            bytes(MAX_RECORD_DATA as u64),
+            percent(total, MAX_RECORD_DATA),
+            usage.subkeys.len()
+        )];
+        let writers = usage.writers();
+        if writers.is_empty() {
+            lines.push("  nothing read or written yet".to_string());
+        }
+        for w in writers {
+            lines.push(format!(
+                "  {:<16} {:<13} {:>3} subkey(s) {:>10} of {:>10} ({:>4})  largest value {}",
+                label(&w.writer),
+                w.role,
+                w.subkeys,
+                bytes(w.bytes as u64),
+                bytes(w.allotted as u64),
+                percent(w.bytes, w.allotted),
+                bytes(w.largest as u64)
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{DhtBackend, MemoryDht};
+
+    fn usage_of(quota: &Quota, record_key: &RecordKey) -> Vec<WriterUsage> {
+        quota.records.lock().unwrap()[record_key].writers()
+    }
+
+    #[tokio::test]
+    async fn usage_is_counted_per_writer_and_warned_about_once() {
+        let dht = MemoryDht::new();
+        let owner = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let member = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let schema = DHTSchema::smpl(
+            1,
+            vec![DHTSchemaSMPLMember {
+                m_key: crate::backend::member_id(&member.key()),
+                m_cnt: 2,
+            }],
+        )
+        .unwrap();
+        let desc = dht.create_dht_record(CRYPTO_KIND_VLD0, schema, Some(owner.clone())).await.unwrap();
+        let key = desc.key();
+
+        let quota = Quota::new();
+        quota.opened(&desc, Some(owner.key()));
+        assert!(quota.wrote(&key, 0, None, 100).is_empty());
+        // the member's two subkeys hold 64 KiB; 30000 + 24000 bytes is past 80% of that,
+        // while 30000 on its own is under 95% of a value
+        assert!(quota.wrote(&key, 1, Some(&member.key()), 30_000).is_empty());
+        let warnings = quota.wrote(&key, 2, Some(&member.key()), 24_000);
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(warnings[0].contains("member") && warnings[0].contains("82%"), "{warnings:?}");
+        // still over, so no second warning
+        assert!(quota.wrote(&key, 2, Some(&member.key()), 25_000).is_empty());
+
+        let writers = usage_of(&quota, &key);
+        assert_eq!(writers[0].role, "member");
+        assert_eq!((writers[0].subkeys, writers[0].bytes, writers[0].largest), (2, 55_000, 30_000));
+        assert_eq!(writers[0].allotted, 2 * ValueData::MAX_LEN);
+        assert_eq!((writers[1].role, writers[1].bytes), ("owner", 100));
+
+        // a value read back replaces what we knew of that subkey
+        quota.saw(&key, 1, &owner.key(), 10);
+        assert_eq!(usage_of(&quota, &key)[0].bytes, 25_000);
+        assert!(quota.report(&key, |k| k.to_string()).contains("3 subkey(s) this node has seen"));
+    }
+}
@@ -0,0 +1,256 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+use veilid_core::*;
+
+use crate::config::AppConfig;
+use crate::exit::Kind;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	When a node counts as attached enough to start on the DHT.
+//
+//	It used to be public_internet_ready and nothing else, which never comes
+//	on a LAN with no way out, so the node sat at the spinner forever. The
+//	config picks the condition instead (ready_when):
+//
+//	  public_internet   Veilid says the public internet routing domain is
+//	                    ready (the default, and what the example always did)
+//	  local_network     the local network routing domain is ready
+//	  peers             at least ready_min_peers live peers
+//
+//	and ready_timeout_secs, if set, stops waiting after that long with a
+//	warning and carries on anyway; the first DHT calls may fail, but the
+//	node is usable once the network catches up. With ready_timeout_exit it
+//	gives up instead, and the program exits with code 4 (see exit.rs).
+//
+//	Early DHT mode (--early, or early_dht) goes further: any attached
+//	state will do, even AttachedWeak, and the Dht wrapper retries the
+//	calls that come back TryAgain meanwhile (see dht.rs). On a slow
+//	network that's a prompt in seconds rather than minutes of spinner.
+//
+//	Attached is the first of a node's milestones (ReadySignal). The nodes
+//	also mark when their record is open and when the first watched change
+//	arrives, for anything that wants to wait for one of those instead, and
+//	`diag bundle` says how long each took.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadyWhen {
+    PublicInternet,
+    LocalNetwork,
+    Peers(u64),
+}
+
+impl ReadyWhen {
+    pub fn from_config(config: &AppConfig) -> Result<ReadyWhen, String> {
+        match config.ready_when.as_str() {
+            "public_internet" => Ok(ReadyWhen::PublicInternet),
+            "local_network" => Ok(ReadyWhen::LocalNetwork),
+            "peers" if config.ready_min_peers == 0 => {
+                Err("ready_when 'peers' needs ready_min_peers of at least 1".to_string())
+            }
+            "peers" => Ok(ReadyWhen::Peers(config.ready_min_peers)),
+            other => Err(format!("ready_when '{other}' should be public_internet, local_network or peers")),
+        }
+    }
+
+    pub fn is_met(self, att: &VeilidStateAttachment) -> bool {
+        match self {
+            ReadyWhen::PublicInternet => att.public_internet_ready,
+            ReadyWhen::LocalNetwork => att.local_network_ready,
+            ReadyWhen::Peers(min) => att.live_peer_count.as_u64() >= min,
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            ReadyWhen::PublicInternet => "full attachment".to_string(),
+            ReadyWhen::LocalNetwork => "the local network".to_string(),
+            ReadyWhen::Peers(min) => format!("{min} live peer(s)"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Milestone {
+    // ready_when is met (or, in early DHT mode, attached at all)
+    Attached,
+    RecordOpened,
+    // the first change a watch delivered
+    FirstWatch,
+}
+
+impl Milestone {
+    const ALL: [Milestone; 3] = [Milestone::Attached, Milestone::RecordOpened, Milestone::FirstWatch];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Milestone::Attached => "attached",
+            Milestone::RecordOpened => "record opened",
+            Milestone::FirstWatch => "first watch delivered",
+        }
+    }
+}
+
+// The milestones a node has reached, and how long after start-up. Clones
+// share them, so the update callback can mark one and the rest of the node
+// wait for it.
+#[derive(Clone)]
+pub struct ReadySignal {
+    started: Instant,
+    reached: Arc<watch::Sender<[Option<Duration>; 3]>>,
+}
+
+impl Default for ReadySignal {
+    fn default() -> ReadySignal {
+        ReadySignal::new()
+    }
+}
+
+impl ReadySignal {
+    pub fn new() -> ReadySignal {
+        ReadySignal {
+            started: Instant::now(),
+            reached: Arc::new(watch::Sender::new([None; 3])),
+        }
+    }
+
+    // Only the first time counts.
+    pub fn reach(&self, milestone: Milestone) {
+        let after = self.started.elapsed();
+        self.reached.send_if_modified(|reached| {
+            let slot = &mut reached[milestone as usize];
+            if slot.is_some() {
+                return false;
+            }
+            *slot = Some(after);
+            true
+        });
+    }
+
+    pub fn reached(&self, milestone: Milestone) -> Option<Duration> {
+        self.reached.borrow()[milestone as usize]
+    }
+
+    // Returns straight away if it's been reached already.
+    pub async fn wait(&self, milestone: Milestone) {
+        let mut rx = self.reached.subscribe();
+        // we hold the sender, so this only ends one way
+        let _ = rx.wait_for(|reached| reached[milestone as usize].is_some()).await;
+    }
+
+    // One line per milestone, for `diag bundle`.
+    pub fn describe(&self) -> String {
+        Milestone::ALL
+            .iter()
+            .map(|&m| match self.reached(m) {
+                Some(after) => format!("{:<22} after {:.1}s\n", m.name(), after.as_secs_f64()),
+                None => format!("{:<22} not yet\n", m.name()),
+            })
+            .collect()
+    }
+}
+
+// The gate the update callbacks feed and the start-up code waits on.
+#[derive(Clone)]
+pub struct ReadyGate {
+    when: ReadyWhen,
+    timeout: Option<Duration>,
+    // give up at the timeout rather than carry on
+    exit: bool,
+    early: bool,
+    signal: ReadySignal,
+}
+
+impl ReadyGate {
+    // Marks Milestone::Attached on `signal` when the time comes.
+    pub fn new(config: &AppConfig, signal: ReadySignal) -> Result<ReadyGate, String> {
+        Ok(ReadyGate {
+            when: ReadyWhen::from_config(config)?,
+            timeout: config.ready_timeout(),
+            exit: config.ready_timeout_exit,
+            early: config.early_dht,
+            signal,
+        })
+    }
+
+    // For the update callback: opens the gate on the first update that meets the condition.
+    pub fn update(&self, update: &VeilidUpdate) {
+        if let VeilidUpdate::Attachment(att) = update {
+            if self.when.is_met(att) || (self.early && att.state.is_attached()) {
+                self.signal.reach(Milestone::Attached);
+            }
+        }
+    }
+
+    // Waits for the gate, with a spinner, or until ready_timeout_secs is up.
+    pub async fn wait(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let (goal, done) = if self.early {
+            ("an attached state (early DHT mode)".to_string(), "Veilid attached; DHT calls will retry until it's ready")
+        } else {
+            (self.when.describe(), "Veilid fully attached")
+        };
+        let waiting = crate::progress::spinner(&format!("Waiting for Veilid to reach {goal}..."));
+        let Some(timeout) = self.timeout else {
+            self.signal.wait(Milestone::Attached).await;
+            waiting.finish_with_message(done);
+            return Ok(());
+        };
+        match tokio::time::timeout(timeout, self.signal.wait(Milestone::Attached)).await {
+            Ok(()) => waiting.finish_with_message(done),
+            Err(_) if self.exit => {
+                waiting.abandon_with_message(format!("No {goal} after {}s", timeout.as_secs()));
+                return Err(Kind::AttachTimeout.fail(format!("the node didn't reach {goal} in {}s", timeout.as_secs())));
+            }
+            Err(_) => waiting.finish_with_message(format!(
+                "Warning: no {goal} after {}s, carrying on anyway; DHT calls may fail until the network catches up",
+                timeout.as_secs()
+            )),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ready_when_comes_from_the_config() {
+        let mut config = AppConfig::default();
+        assert_eq!(ReadyWhen::from_config(&config), Ok(ReadyWhen::PublicInternet));
+        config.ready_when = "peers".to_string();
+        config.ready_min_peers = 3;
+        assert_eq!(ReadyWhen::from_config(&config), Ok(ReadyWhen::Peers(3)));
+        config.ready_min_peers = 0;
+        assert!(ReadyWhen::from_config(&config).is_err());
+        config.ready_when = "lan".to_string();
+        assert!(ReadyWhen::from_config(&config).unwrap_err().contains("local_network"));
+    }
+
+    #[tokio::test]
+    async fn milestones_are_waited_for_one_at_a_time() {
+        let signal = ReadySignal::new();
+        let waiter = tokio::spawn({
+            let signal = signal.clone();
+            async move { signal.wait(Milestone::FirstWatch).await }
+        });
+        signal.reach(Milestone::Attached);
+        signal.reach(Milestone::RecordOpened);
+        assert!(!waiter.is_finished());
+        assert_eq!(signal.reached(Milestone::FirstWatch), None);
+
+        let first = signal.reached(Milestone::Attached).unwrap();
+        signal.reach(Milestone::Attached);
+        assert_eq!(signal.reached(Milestone::Attached), Some(first));
+
+        signal.reach(Milestone::FirstWatch);
+        waiter.await.unwrap();
+        // already reached: no waiting
+        signal.wait(Milestone::RecordOpened).await;
+        assert!(signal.describe().lines().all(|line| line.contains("after")));
+    }
+}
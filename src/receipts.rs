@@ -0,0 +1,354 @@
+use std::collections::BTreeMap;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::dht::Dht;
+use crate::envelope::{Codec, Envelope};
+use crate::mailbox::{self, MailboxInfo};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Delivery receipts for mail (see mailbox.rs).
+//
+//	Every letter carries an id. When the mailbox's owner takes a letter out
+//	of the inbox it marks the id Delivered, and Read once it has been shown;
+//	the list of recent receipts lives in one of the owner's subkeys (the
+//	mailbox info in the metadata block says which). Receipts only name ids,
+//	never what the letters said.
+//
+//	The sender keeps what it sent in its outbox (in the table store, so it
+//	outlives a restart), and reads the receipts to see how each letter is
+//	doing. A letter with no receipt whose slot no longer holds it was lost
+//	(overwritten by another sender, most likely), so it's sent again when
+//	the node comes back online or starts up.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// The owner subkey receipts go in, on records with at least two (0 holds the metadata).
+pub const RECEIPTS_SUBKEY: ValueSubkey = 1;
+// How many receipts the owner keeps there, newest last.
+pub const RECEIPTS_KEEP: usize = 100;
+
+const TABLE: &str = "outbox";
+const COL_SENT: u32 = 0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Sent,
+    Delivered,
+    Read,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Receipt {
+    pub id: String,
+    pub status: Status,
+    pub at_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Receipts {
+    pub entries: Vec<Receipt>,
+}
+
+// A fresh letter id.
+pub fn new_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+impl Receipts {
+    // Move `id` on to `status`. A receipt never goes backwards; returns false if nothing changed.
+    pub fn mark(&mut self, id: &str, status: Status, now_ms: u64) -> bool {
+        // letters from before ids existed can't be receipted
+        if id.is_empty() || self.status(id).is_some_and(|s| s >= status) {
+            return false;
+        }
+        self.entries.retain(|r| r.id != id);
+        self.entries.push(Receipt {
+            id: id.to_string(),
+            status,
+            at_ms: now_ms,
+        });
+        if self.entries.len() > RECEIPTS_KEEP {
+            self.entries.drain(..self.entries.len() - RECEIPTS_KEEP);
+        }
+        true
+    }
+
+    pub fn status(&self, id: &str) -> Option<Status> {
+        self.entries.iter().find(|r| r.id == id).map(|r| r.status)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut env = Envelope::new(Codec::Json, serde_json::to_vec(self).expect("receipts serialize"));
+        env.written_ms = Some(crate::audit::now_ms() as u64);
+        env.encode()
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Receipts> {
+        let env = Envelope::decode(data).ok()?;
+        if env.codec != Codec::Json {
+            return None;
+        }
+        serde_json::from_slice(&env.body).ok()
+    }
+
+    // What the owner has written so far (nothing yet is an empty list).
+    pub async fn fetch(rc: &Dht, record: &RecordKey, subkey: ValueSubkey) -> VeilidAPIResult<Receipts> {
+        Ok(rc
+            .get_dht_value(record.clone(), subkey, true)
+            .await?
+            .and_then(|v| Receipts::decode(v.data()))
+            .unwrap_or_default())
+    }
+
+    // The owner's side: put the list in its subkey.
+    pub async fn publish(&self, rc: &Dht, record: &RecordKey, subkey: ValueSubkey) -> VeilidAPIResult<()> {
+        rc.set_dht_value(record.clone(), subkey, self.encode(), None).await.map(|_| ())
+    }
+}
+
+// A letter we sent, as the outbox remembers it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SentLetter {
+    pub id: String,
+    pub record: String,
+    pub slot: ValueSubkey,
+    // the sealed value we wrote (base64), so it can go again as it was
+    pub sealed: String,
+    // the start of the text, for listing
+    pub preview: String,
+    pub sent_ms: u64,
+    pub status: Status,
+}
+
+pub struct Outbox {
+    // None in tests: kept in memory only
+    db: Option<TableDB>,
+    by_id: BTreeMap<String, SentLetter>,
+}
+
+impl Outbox {
+    pub async fn open(api: &VeilidAPI) -> VeilidAPIResult<Outbox> {
+        let db = api.table_store()?.open(TABLE, 1).await?;
+        let mut by_id = BTreeMap::new();
+        for key in db.get_keys(COL_SENT).await? {
+            if let Some(sent) = db.load_json::<SentLetter>(COL_SENT, &key).await? {
+                by_id.insert(sent.id.clone(), sent);
+            }
+        }
+        Ok(Outbox { db: Some(db), by_id })
+    }
+
+    #[cfg(test)]
+    fn in_memory() -> Outbox {
+        Outbox {
+            db: None,
+            by_id: BTreeMap::new(),
+        }
+    }
+
+    async fn save(&mut self, sent: SentLetter) -> VeilidAPIResult<()> {
+        if let Some(db) = &self.db {
+            db.store_json(COL_SENT, sent.id.as_bytes(), &sent).await?;
+        }
+        self.by_id.insert(sent.id.clone(), sent);
+        Ok(())
+    }
+
+    // Keep a letter that has just gone into `slot`.
+    pub async fn sent(
+        &mut self,
+        id: &str,
+        record: &RecordKey,
+        slot: ValueSubkey,
+        sealed: &[u8],
+        text: &str,
+    ) -> VeilidAPIResult<()> {
+        self.save(SentLetter {
+            id: id.to_string(),
+            record: record.to_string(),
+            slot,
+            sealed: BASE64.encode(sealed),
+            preview: text.chars().take(40).collect(),
+            sent_ms: crate::audit::now_ms() as u64,
+            status: Status::Sent,
+        })
+        .await
+    }
+
+    fn for_record(&self, record: &RecordKey) -> Vec<SentLetter> {
+        let record = record.to_string();
+        self.by_id.values().filter(|s| s.record == record).cloned().collect()
+    }
+
+    // Catch up with the owner's receipts. One line per letter whose status moved.
+    pub async fn refresh(&mut self, rc: &Dht, record: &RecordKey, info: &MailboxInfo) -> Result<Vec<String>, String> {
+        let Some(subkey) = info.receipts_subkey else {
+            return Ok(Vec::new());
+        };
+        let receipts = Receipts::fetch(rc, record, subkey)
+            .await
+            .map_err(|e| format!("couldn't read the receipts: {e}"))?;
+        let mut lines = Vec::new();
+        for mut sent in self.for_record(record) {
+            match receipts.status(&sent.id) {
+                Some(status) if status > sent.status => {
+                    sent.status = status;
+                    lines.push(format!("[mail] \"{}\" was {}", sent.preview, status.verb()));
+                    self.save(sent).await.map_err(|e| e.to_string())?;
+                }
+                _ => {}
+            }
+        }
+        Ok(lines)
+    }
+
+    // Send again whatever has no receipt and is no longer in its slot.
+    pub async fn retransmit(&mut self, rc: &Dht, record: &RecordKey, info: &MailboxInfo) -> Vec<String> {
+        let mut lines = match self.refresh(rc, record, info).await {
+            Ok(lines) => lines,
+            Err(e) => return vec![format!("[mail] {e}")],
+        };
+        for mut sent in self.for_record(record) {
+            if sent.status != Status::Sent {
+                continue;
+            }
+            let Ok(sealed) = BASE64.decode(&sent.sealed) else {
+                continue;
+            };
+            let waiting = match rc.get_dht_value(record.clone(), sent.slot, true).await {
+                Ok(Some(v)) => v.data() == sealed.as_slice(),
+                Ok(None) => false,
+                // can't tell, try again next time
+                Err(_) => continue,
+            };
+            if waiting {
+                continue;
+            }
+            match mailbox::post(rc, record, info, &sent.id, sealed).await {
+                Ok(slot) => {
+                    lines.push(format!("[mail] resent \"{}\" (inbox slot {slot})", sent.preview));
+                    sent.slot = slot;
+                    if let Err(e) = self.save(sent).await {
+                        lines.push(format!("[mail] couldn't update the outbox: {e}"));
+                    }
+                }
+                Err(e) => lines.push(format!("[mail] couldn't resend \"{}\": {e}", sent.preview)),
+            }
+        }
+        lines
+    }
+
+    // Letters still waiting on a receipt, to any record.
+    pub fn pending(&self) -> usize {
+        self.by_id.values().filter(|s| s.status == Status::Sent).count()
+    }
+
+    // `outbox`: one line per letter sent to `record`.
+    pub fn report(&self, record: &RecordKey) -> String {
+        let mut sent = self.for_record(record);
+        if sent.is_empty() {
+            return "Nothing sent to this record".to_string();
+        }
+        sent.sort_by_key(|s| s.sent_ms);
+        sent.iter()
+            .map(|s| format!("  {:<10} \"{}\"", s.status.verb(), s.preview))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Status {
+    fn verb(self) -> &'static str {
+        match self {
+            Status::Sent => "sent",
+            Status::Delivered => "delivered",
+            Status::Read => "read",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{member_id, MemoryDht};
+    use std::sync::Arc;
+
+    #[test]
+    fn receipts_only_move_forward() {
+        let mut receipts = Receipts::default();
+        assert!(receipts.mark("a", Status::Delivered, 1));
+        assert!(receipts.mark("a", Status::Read, 2));
+        assert!(!receipts.mark("a", Status::Delivered, 3));
+        assert_eq!(receipts.status("a"), Some(Status::Read));
+        assert_eq!(receipts.status("b"), None);
+        assert_eq!(Receipts::decode(&receipts.encode()), Some(receipts.clone()));
+
+        for n in 0..RECEIPTS_KEEP {
+            receipts.mark(&n.to_string(), Status::Delivered, 4);
+        }
+        assert_eq!(receipts.entries.len(), RECEIPTS_KEEP);
+        assert_eq!(receipts.status("a"), None);
+    }
+
+    #[tokio::test]
+    async fn lost_letters_are_sent_again_and_receipted_ones_arent() {
+        let rc = Dht::with_backend(Arc::new(MemoryDht::new()), None, false, None);
+        let drop = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let schema = DHTSchema::smpl(
+            2,
+            vec![DHTSchemaSMPLMember {
+                m_key: member_id(&drop.key()),
+                m_cnt: 2,
+            }],
+        )
+        .unwrap();
+        let record = rc.create_dht_record(CRYPTO_KIND_VLD0, schema, None).await.unwrap().key();
+        let info = MailboxInfo {
+            drop_writer: drop.to_string(),
+            recipient: "VLD0:whoever".to_string(),
+            first_subkey: 2,
+            last_subkey: 3,
+            receipts_subkey: Some(1),
+        };
+        let as_drop = || {
+            Some(SetDHTValueOptions {
+                writer: Some(drop.clone()),
+                allow_offline: None,
+            })
+        };
+        let mut outbox = Outbox::in_memory();
+
+        for (id, text) in [("kept", "one"), ("lost", "two")] {
+            let value = Envelope::text(text).encode();
+            let slot = mailbox::post(&rc, &record, &info, id, value.clone()).await.unwrap();
+            outbox.sent(id, &record, slot, &value, text).await.unwrap();
+        }
+        // the owner took "kept" and said so; "lost" got overwritten by someone else
+        let mut receipts = Receipts::default();
+        receipts.mark("kept", Status::Delivered, 1);
+        receipts.publish(&rc, &record, 1).await.unwrap();
+        let lost_slot = outbox.by_id["lost"].slot;
+        let kept_slot = outbox.by_id["kept"].slot;
+        rc.set_dht_value(record.clone(), kept_slot, Envelope::tombstone().encode(), as_drop())
+            .await
+            .unwrap();
+        rc.set_dht_value(record.clone(), lost_slot, Envelope::text("not yours").encode(), as_drop())
+            .await
+            .unwrap();
+
+        let lines = outbox.retransmit(&rc, &record, &info).await;
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].contains("\"one\" was delivered"));
+        assert!(lines[1].contains("resent \"two\""));
+        assert_eq!(outbox.by_id["lost"].slot, kept_slot);
+        assert!(outbox.report(&record).contains("delivered"));
+
+        // still in its slot, so nothing more to do
+        assert!(outbox.retransmit(&rc, &record, &info).await.is_empty());
+    }
+}
@@ -0,0 +1,326 @@
+use std::sync::Arc;
+
+use veilid_core::*;
+
+use crate::audit::{log_file_name, AuditLog};
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::dht::Dht;
+use crate::discovery;
+use crate::exit::Kind;
+use crate::node;
+use crate::progress;
+use crate::record_manager::RecordManager;
+use crate::shortcode::ShortcodeBook;
+use crate::snapshot::{self, Snapshot, SubkeyState};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`record ...` commands: one-shot operations on whole records.
+//
+//	record clone <src>   make a private copy of a record: same schema shape,
+//	                     brand new owner and member keys, all readable
+//	                     values copied across.
+//	record new --template <t>
+//	                     a fresh record laid out for chat, kvstore,
+//	                     statuspage or mailbox (see templates.rs).
+//	record load <file>   write a CSV or JSON file of values into the
+//	                     record in owner_keys.txt (see load.rs).
+//	record snapshot <rec> save every subkey's value, seq and writer
+//	                     (see snapshot.rs).
+//	record diff <a> <b>  compare two snapshots, no node needed.
+//	record access        which subkeys the owner, a member and nobody can
+//	                     read and write (see access.rs).
+//	record bench         DFLT and SMPL records of the same size, writes,
+//	                     reads and watches timed side by side (see bench.rs).
+//	discover             list the records announced in the app index
+//	                     (see discovery.rs).
+//	record backup/restore
+//	                     a passphrase-sealed copy of a record's keys and
+//	                     values, and getting it back (see backup.rs).
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// Start a tool node and give back a Dht wrapper that audits to audit_tools.jsonl
+pub async fn start_tool_node(
+    options: &Options,
+    config: &AppConfig,
+) -> Result<(VeilidAPI, Dht), Box<dyn std::error::Error>> {
+    start_node_in(options, config, &node::tool_namespace(config)).await
+}
+
+// The same, in another namespace (to get at that node's table store).
+pub async fn start_node_in(
+    options: &Options,
+    config: &AppConfig,
+    namespace: &str,
+) -> Result<(VeilidAPI, Dht), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    let veilid = node::start_attached(config, &data_dir, namespace, |_| {}).await?;
+    let audit = Arc::new(AuditLog::open(&data_dir.join(log_file_name("tools")))?);
+    let routing = node::RoutingContextHandle::new(&veilid, config)?;
+    let rc = Dht::new(routing.get(), Some(audit), options.dry_run, options.chaos.clone());
+    Ok((veilid, rc))
+}
+
+// A fresh keypair for every writer the schema has, plus the schema rebuilt around them.
+// SMPL member ids are tied to keys we don't hold, so a clone gets its own members.
+pub struct FreshWriters {
+    pub owner: KeyPair,
+    // (keypair, first subkey, subkey count) for each SMPL member
+    pub members: Vec<(KeyPair, ValueSubkey, u16)>,
+    pub schema: DHTSchema,
+}
+
+impl FreshWriters {
+    pub fn for_schema(veilid: &VeilidAPI, schema: &DHTSchema) -> VeilidAPIResult<FreshWriters> {
+        match schema {
+            DHTSchema::DFLT(dflt) => Ok(FreshWriters {
+                owner: Crypto::generate_keypair(CRYPTO_KIND_VLD0)?,
+                members: Vec::new(),
+                schema: DHTSchema::dflt(dflt.o_cnt())?,
+            }),
+            DHTSchema::SMPL(smpl) => {
+                let counts: Vec<u16> = smpl.members().iter().map(|m| m.m_cnt).collect();
+                FreshWriters::for_shape(veilid, smpl.o_cnt(), &counts)
+            }
+        }
+    }
+
+    // An SMPL schema with `o_cnt` owner subkeys and a member for each count, all keys new.
+    pub fn for_shape(veilid: &VeilidAPI, o_cnt: u16, member_counts: &[u16]) -> VeilidAPIResult<FreshWriters> {
+        let owner = Crypto::generate_keypair(CRYPTO_KIND_VLD0)?;
+        let mut members = Vec::new();
+        let mut smpl_members = Vec::new();
+        let mut next_subkey = ValueSubkey::from(o_cnt);
+        for &m_cnt in member_counts {
+            let kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0)?;
+            let member_id = veilid.generate_member_id(&kp.key())?;
+            smpl_members.push(DHTSchemaSMPLMember {
+                m_key: member_id.into_value(),
+                m_cnt,
+            });
+            members.push((kp, next_subkey, m_cnt));
+            next_subkey += ValueSubkey::from(m_cnt);
+        }
+        Ok(FreshWriters {
+            owner,
+            members,
+            schema: DHTSchema::smpl(o_cnt, smpl_members)?,
+        })
+    }
+
+    // The keypair allowed to write a given subkey.
+    pub fn writer_for(&self, subkey: ValueSubkey) -> &KeyPair {
+        self.members
+            .iter()
+            .find(|(_, first, cnt)| subkey >= *first && subkey < *first + ValueSubkey::from(*cnt))
+            .map(|(kp, _, _)| kp)
+            .unwrap_or(&self.owner)
+    }
+
+    pub fn print_credentials(&self, record_key: &RecordKey, code: &str) {
+        println!("RecordKey = {record_key}");
+        let fingerprint = crate::schema::record_fingerprint(&self.schema, &self.owner.key());
+        println!("ShortCode = {}", crate::shortcode::share_string(code, &fingerprint));
+        println!("Owner     = {}", self.owner);
+        for (i, (kp, first, cnt)) in self.members.iter().enumerate() {
+            let last = *first + ValueSubkey::from(*cnt).saturating_sub(1);
+            println!("Member {i}  = {kp}   (subkeys {first}..={last})");
+        }
+        println!("Keep these safe: anyone holding a keypair can write its subkeys.");
+    }
+}
+
+// -------------------------------------------------------------------------
+// record clone <src>
+// -------------------------------------------------------------------------
+
+pub async fn clone(
+    source: &str,
+    options: &Options,
+    config: &AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    let mut book = ShortcodeBook::load(&data_dir)?;
+    let src_key = book.resolve(source).map_err(|e| Kind::RecordNotFound.fail(e))?;
+
+    let (veilid, rc) = start_tool_node(options, config).await?;
+    let records = RecordManager::new(rc.clone());
+
+    let src = records.open(src_key, None).await?;
+    let src_key = src.key();
+    let schema = src.descriptor().schema();
+    println!("Source {} has schema {:?}", book.remember(&src_key)?, schema);
+
+    // Read everything we can first, so a half-readable source doesn't leave a half-made clone.
+    let mut values = Vec::new();
+    let bar = progress::subkeys(u64::from(schema.max_subkey()) + 1, "Reading");
+    for subkey in 0..=schema.max_subkey() {
+        match rc.get_dht_value(src_key.clone(), subkey, true).await {
+            Ok(Some(v)) => values.push((subkey, v.data().to_vec())),
+            Ok(None) => bar.suspend(|| println!("  subkey {subkey}: empty, skipped")),
+            Err(e) => bar.suspend(|| println!("  subkey {subkey}: unreadable ({e}), skipped")),
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+
+    let writers = FreshWriters::for_schema(&veilid, &schema)?;
+    let new_key = if rc.is_dry_run() {
+        rc.plan_create_dht_record(writers.schema.clone(), writers.owner.clone())
+            .await?
+    } else {
+        let desc = progress::spin(
+            "Creating the clone",
+            rc.create_dht_record(CRYPTO_KIND_VLD0, writers.schema.clone(), Some(writers.owner.clone())),
+        )
+        .await?;
+        records.adopt(desc, Some(writers.owner.clone())).await.key()
+    };
+
+    let mut copied = 0;
+    let bar = progress::subkeys(values.len() as u64, "Copying");
+    for (subkey, data) in values {
+        let opts = SetDHTValueOptions {
+            writer: Some(writers.writer_for(subkey).clone()),
+            allow_offline: None,
+        };
+        match rc.set_dht_value(new_key.clone(), subkey, data, Some(opts)).await {
+            Ok(_) => copied += 1,
+            Err(e) => bar.suspend(|| println!("  subkey {subkey}: copy failed ({e})")),
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+
+    drop(src);
+    records.close_all().await;
+
+    println!();
+    println!("Cloned {copied} subkey value(s) into a new record:");
+    let code = if rc.is_dry_run() {
+        crate::shortcode::shortcode(&new_key)
+    } else {
+        book.remember(&new_key)?
+    };
+    writers.print_credentials(&new_key, &code);
+
+    veilid.shutdown().await;
+    Ok(())
+}
+
+// -------------------------------------------------------------------------
+// record snapshot <record>
+// -------------------------------------------------------------------------
+
+pub async fn snapshot(
+    record: &str,
+    options: &Options,
+    config: &AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    let mut book = ShortcodeBook::load(&data_dir)?;
+    let key = book.resolve(record).map_err(|e| Kind::RecordNotFound.fail(e))?;
+
+    let (veilid, rc) = start_tool_node(options, config).await?;
+    let records = RecordManager::new(rc.clone());
+    let record = records.open(key, None).await?;
+    let key = record.key();
+    let max_subkey = record.descriptor().schema().max_subkey();
+    println!("Snapshotting {}", book.remember(&key)?);
+
+    let mut snap = Snapshot {
+        record: key.to_string(),
+        taken_ms: crate::audit::now_ms() as u64,
+        max_subkey,
+        subkeys: Vec::new(),
+        unreadable: Vec::new(),
+    };
+    let bar = progress::subkeys(u64::from(max_subkey) + 1, "Reading");
+    for subkey in 0..=max_subkey {
+        match rc.get_dht_value(key.clone(), subkey, true).await {
+            Ok(Some(v)) => snap.subkeys.push(SubkeyState::new(subkey, &v)),
+            Ok(None) => {}
+            Err(e) => {
+                bar.suspend(|| println!("  subkey {subkey}: unreadable ({e})"));
+                snap.unreadable.push(subkey);
+            }
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+    drop(record);
+    records.close_all().await;
+    veilid.shutdown().await;
+
+    let path = snap.save(&data_dir)?;
+    println!(
+        "Saved {} ({} of {} subkeys have values) to {}",
+        snap.name(),
+        snap.subkeys.len(),
+        u64::from(max_subkey) + 1,
+        path.to_string_lossy()
+    );
+    Ok(())
+}
+
+// -------------------------------------------------------------------------
+// record diff <a> <b>
+// -------------------------------------------------------------------------
+
+pub fn diff(a: &str, b: &str, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    let a = Snapshot::load(&data_dir, a)?;
+    let b = Snapshot::load(&data_dir, b)?;
+    println!("{} -> {}", a.name(), b.name());
+    if a.record != b.record {
+        println!("(different records, comparing subkey by subkey)");
+    }
+    let changes = snapshot::diff(&a, &b);
+    if changes.is_empty() {
+        println!("No differences");
+    }
+    for change in &changes {
+        println!("{change}");
+    }
+    Ok(())
+}
+
+// -------------------------------------------------------------------------
+// discover
+// -------------------------------------------------------------------------
+
+pub async fn discover(options: &Options, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    let mut book = ShortcodeBook::load(&data_dir)?;
+    let (veilid, rc) = start_tool_node(options, config).await?;
+
+    let found = progress::spin("Reading the app index", discovery::list(&rc)).await;
+    veilid.shutdown().await;
+    let found = match found {
+        Ok(found) => found,
+        Err(e) => {
+            println!("No app index yet (nobody has announced a record): {e}");
+            return Ok(());
+        }
+    };
+    if found.is_empty() {
+        println!("Nothing announced right now");
+        return Ok(());
+    }
+
+    // Anyone can write to the index, so these are suggestions, not vouched for.
+    println!("Announced records (join one by giving the alt node its shortcode):");
+    let now = crate::audit::now_ms() as u64;
+    for a in found {
+        let key: RecordKey = a.record.parse()?;
+        let code = book.remember(&key)?;
+        println!(
+            "  {code:<32} {:<24} {}m ago",
+            a.label,
+            now.saturating_sub(a.announced_ms) / 60_000
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use veilid_core::*;
+
+use crate::dht::Dht;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	One place that owns every open record.
+//
+//	Anything that needs a record asks the RecordManager for it instead of
+//	calling open_dht_record itself. The first request opens it (following any
+//	forwarding pointers), later ones share the same open record, and each
+//	caller holds a RecordHandle for as long as it needs it.
+//
+//	When the last handle is dropped the record isn't closed straight away:
+//	it lingers for IDLE_LINGER in case someone wants it again, then the
+//	reaper (spawn_reaper) closes it.
+//
+//	Records are opened with the writer from the first open. Asking for the
+//	same record with a different writer while it's still open is an error,
+//	not a quiet downgrade: close it (drop every handle) first. Pass the
+//	writer in SetDHTValueOptions when writing, as the rest of the example
+//	does.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const IDLE_LINGER: Duration = Duration::from_secs(60);
+
+struct OpenRecord {
+    desc: DHTRecordDescriptor,
+    writer: Option<KeyPair>,
+    refs: usize,
+    // when the last handle went away
+    idle_since: Option<Instant>,
+}
+
+struct Inner {
+    rc: Dht,
+    // keyed by the key callers asked for, which may have forwarded elsewhere
+    open: Mutex<HashMap<RecordKey, OpenRecord>>,
+    // only one open at a time, so two callers can't both open the same record
+    opening: tokio::sync::Mutex<()>,
+    linger: Duration,
+}
+
+#[derive(Clone)]
+pub struct RecordManager {
+    inner: Arc<Inner>,
+}
+
+// A claim on an open record. The record stays open while any handle for it exists.
+pub struct RecordHandle {
+    inner: Arc<Inner>,
+    requested: RecordKey,
+    desc: DHTRecordDescriptor,
+}
+
+impl RecordManager {
+    pub fn new(rc: Dht) -> RecordManager {
+        RecordManager::with_linger(rc, IDLE_LINGER)
+    }
+
+    pub fn with_linger(rc: Dht, linger: Duration) -> RecordManager {
+        RecordManager {
+            inner: Arc::new(Inner {
+                rc,
+                open: Mutex::new(HashMap::new()),
+                opening: tokio::sync::Mutex::new(()),
+                linger,
+            }),
+        }
+    }
+
+    pub async fn open(&self, record_key: RecordKey, writer: Option<KeyPair>) -> VeilidAPIResult<RecordHandle> {
+        self.open_on(&self.inner.rc, record_key, writer).await
+    }
+
+    // The node's own record as it starts up, without --chaos's failures (see Dht::for_setup).
+    pub async fn open_at_startup(&self, record_key: RecordKey, writer: Option<KeyPair>) -> VeilidAPIResult<RecordHandle> {
+        self.open_on(&self.inner.rc.for_setup(), record_key, writer).await
+    }
+
+    async fn open_on(&self, rc: &Dht, record_key: RecordKey, writer: Option<KeyPair>) -> VeilidAPIResult<RecordHandle> {
+        let _opening = self.inner.opening.lock().await;
+        if let Some(handle) = self.claim(&record_key, writer.as_ref())? {
+            return Ok(handle);
+        }
+        let desc = rc.open_following(record_key.clone(), writer.clone()).await?;
+        Ok(self.insert(record_key, desc, writer))
+    }
+
+    // A record this node has just created (create_dht_record leaves it open),
+    // so it's closed along with the rest.
+    pub async fn adopt(&self, desc: DHTRecordDescriptor, writer: Option<KeyPair>) -> RecordHandle {
+        let _opening = self.inner.opening.lock().await;
+        self.insert(desc.key(), desc, writer)
+    }
+
+    fn insert(&self, record_key: RecordKey, desc: DHTRecordDescriptor, writer: Option<KeyPair>) -> RecordHandle {
+        self.inner.open.lock().unwrap().insert(
+            record_key.clone(),
+            OpenRecord {
+                desc,
+                writer,
+                refs: 0,
+                idle_since: None,
+            },
+        );
+        self.claim(&record_key, None).ok().flatten().expect("just inserted")
+    }
+
+    // A handle on the record if it's already open. Asking for a writer it
+    // wasn't opened with is refused.
+    fn claim(&self, record_key: &RecordKey, writer: Option<&KeyPair>) -> VeilidAPIResult<Option<RecordHandle>> {
+        let mut open = self.inner.open.lock().unwrap();
+        let Some(entry) = open.get_mut(record_key) else {
+            return Ok(None);
+        };
+        if let Some(writer) = writer {
+            if entry.writer.as_ref() != Some(writer) {
+                return Err(VeilidAPIError::generic(format!(
+                    "{record_key} is already open with {}, not writer {}; close it before opening it with another writer",
+                    entry.writer.as_ref().map_or("no writer".to_string(), |w| format!("writer {}", w.key())),
+                    writer.key()
+                )));
+            }
+        }
+        entry.refs += 1;
+        entry.idle_since = None;
+        Ok(Some(RecordHandle {
+            inner: self.inner.clone(),
+            requested: record_key.clone(),
+            desc: entry.desc.clone(),
+        }))
+    }
+
+    // How many handles are out for a record (0 if it's idle or not open at all).
+    #[cfg(test)]
+    pub fn refs(&self, record_key: &RecordKey) -> usize {
+        self.inner
+            .open
+            .lock()
+            .unwrap()
+            .get(record_key)
+            .map_or(0, |e| e.refs)
+    }
+
+    #[cfg(test)]
+    pub fn is_open(&self, record_key: &RecordKey) -> bool {
+        self.inner.open.lock().unwrap().contains_key(record_key)
+    }
+
+    // Close every record that has had no handles for the linger time.
+    pub async fn close_idle(&self) {
+        let _opening = self.inner.opening.lock().await;
+        let expired: Vec<(RecordKey, RecordKey)> = {
+            let mut open = self.inner.open.lock().unwrap();
+            let keys: Vec<RecordKey> = open
+                .iter()
+                .filter(|(_, e)| e.idle_since.is_some_and(|t| t.elapsed() >= self.inner.linger))
+                .map(|(k, _)| k.clone())
+                .collect();
+            keys.into_iter()
+                .filter_map(|k| open.remove(&k).map(|e| (k, e.desc.key())))
+                .collect()
+        };
+        for (_, key) in expired {
+            let _ = self.inner.rc.close_dht_record(key).await;
+        }
+    }
+
+    // Close everything, handles or not (on the way out).
+    pub async fn close_all(&self) {
+        let _opening = self.inner.opening.lock().await;
+        let all: Vec<RecordKey> = self
+            .inner
+            .open
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, e)| e.desc.key())
+            .collect();
+        for key in all {
+            let _ = self.inner.rc.close_dht_record(key).await;
+        }
+    }
+
+    // Run close_idle in the background every so often.
+    pub fn spawn_reaper(&self) -> tokio::task::JoinHandle<()> {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(manager.inner.linger.max(Duration::from_secs(1))).await;
+                manager.close_idle().await;
+            }
+        })
+    }
+}
+
+impl RecordHandle {
+    // The record actually opened (after following any forwarding pointers).
+    pub fn descriptor(&self) -> &DHTRecordDescriptor {
+        &self.desc
+    }
+
+    pub fn key(&self) -> RecordKey {
+        self.desc.key()
+    }
+}
+
+impl Clone for RecordHandle {
+    fn clone(&self) -> RecordHandle {
+        if let Some(entry) = self.inner.open.lock().unwrap().get_mut(&self.requested) {
+            entry.refs += 1;
+        }
+        RecordHandle {
+            inner: self.inner.clone(),
+            requested: self.requested.clone(),
+            desc: self.desc.clone(),
+        }
+    }
+}
+
+impl Drop for RecordHandle {
+    fn drop(&mut self) {
+        if let Some(entry) = self.inner.open.lock().unwrap().get_mut(&self.requested) {
+            entry.refs = entry.refs.saturating_sub(1);
+            if entry.refs == 0 {
+                entry.idle_since = Some(Instant::now());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryDht;
+
+    async fn setup(linger: Duration) -> (RecordManager, Arc<MemoryDht>, RecordKey) {
+        let mem = Arc::new(MemoryDht::new());
+        let rc = Dht::with_backend(mem.clone(), None, false, None);
+        let desc = rc
+            .create_dht_record(CRYPTO_KIND_VLD0, DHTSchema::dflt(1).unwrap(), None)
+            .await
+            .unwrap();
+        rc.close_dht_record(desc.key()).await.unwrap();
+        (RecordManager::with_linger(rc, linger), mem, desc.key())
+    }
+
+    #[tokio::test]
+    async fn opens_are_shared() {
+        let (records, _, key) = setup(Duration::ZERO).await;
+        let a = records.open(key.clone(), None).await.unwrap();
+        let b = records.open(key.clone(), None).await.unwrap();
+        let c = b.clone();
+        assert_eq!(a.key(), key);
+        assert_eq!(records.refs(&key), 3);
+        drop((a, b));
+        assert_eq!(records.refs(&key), 1);
+        drop(c);
+        assert_eq!(records.refs(&key), 0);
+        assert!(records.is_open(&key));
+    }
+
+    #[tokio::test]
+    async fn idle_records_close_after_the_linger() {
+        let (records, mem, key) = setup(Duration::ZERO).await;
+        let handle = records.open(key.clone(), None).await.unwrap();
+
+        // still in use, so it stays open
+        records.close_idle().await;
+        assert!(records.is_open(&key));
+
+        drop(handle);
+        records.close_idle().await;
+        assert!(!records.is_open(&key));
+        assert!(!mem.is_open(&key));
+    }
+
+    #[tokio::test]
+    async fn lingering_records_are_reused() {
+        let (records, _, key) = setup(Duration::from_secs(3600)).await;
+        drop(records.open(key.clone(), None).await.unwrap());
+        records.close_idle().await;
+        assert!(records.is_open(&key));
+
+        let again = records.open(key.clone(), None).await.unwrap();
+        assert_eq!(records.refs(&key), 1);
+        drop(again);
+    }
+
+    #[tokio::test]
+    async fn a_second_writer_is_refused_while_open() {
+        let (records, _, key) = setup(Duration::from_secs(3600)).await;
+        let first = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let second = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let handle = records.open(key.clone(), Some(first.clone())).await.unwrap();
+
+        assert!(records.open(key.clone(), Some(second)).await.is_err());
+        // the same writer, or just reading, shares it
+        drop(records.open(key.clone(), Some(first)).await.unwrap());
+        drop(records.open(key.clone(), None).await.unwrap());
+        assert_eq!(records.refs(&key), 1);
+        drop(handle);
+    }
+}
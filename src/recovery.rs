@@ -0,0 +1,238 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::preflight::RunMarker;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	A small journal of what a node has going, so that after a crash (or a
+//	kill -9, or a power cut) the next run picks up where it left off instead
+//	of asking again:
+//
+//	  - the default node re-opens the record it made, writing with the same
+//	    keys, rather than making a new one
+//	  - the alt node rejoins its record without the prompt and puts back
+//	    every watch it had ('watch <record>' ones included)
+//	  - both say how much mail was still waiting on a receipt (the outbox
+//	    itself lives in the table store and resends on its own)
+//
+//	journal-<role>.json is rewritten whole on every change, through a temp
+//	file and a rename, so a crash mid-write leaves the old one. It's only
+//	read back when preflight.rs found the last run's marker still there.
+//	Only a clean shutdown removes it (finish), along with the marker; a node
+//	that errors out or panics leaves both for the next run to resume from.
+//
+//	The keys the default node writes with don't go in the file: they're kept
+//	in Veilid's table store ("journal"), which is encrypted, under the
+//	record's key. So is how far its sender_seq numbering has got (see
+//	ordering.rs), which carries on across clean restarts too, or readers
+//	would take the new run's #1, #2, ... for repeats.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const TABLE: &str = "journal";
+const COLUMNS: u32 = 2;
+const COL_WRITERS: u32 = 0;
+const COL_SENT: u32 = 1;
+const SENT_KEY: &[u8] = b"sent_count";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct JournalState {
+    // the record this node has open (made, for the default node; joined, for the alt)
+    #[serde(default)]
+    pub record: Option<String>,
+    // the subkeys the alt node follows on it
+    #[serde(default)]
+    pub follow: Option<(ValueSubkey, ValueSubkey)>,
+    // other records being watched
+    #[serde(default)]
+    pub watches: Vec<String>,
+    // letters still waiting on a receipt
+    #[serde(default)]
+    pub pending_mail: usize,
+}
+
+impl JournalState {
+    pub fn record_key(&self) -> Option<RecordKey> {
+        self.record.as_deref().and_then(|k| k.parse().ok())
+    }
+
+    pub fn watch_keys(&self) -> Vec<RecordKey> {
+        self.watches.iter().filter_map(|k| k.parse().ok()).collect()
+    }
+
+    // What we're about to pick up again, for the user.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(record) = &self.record {
+            parts.push(format!("record {record}"));
+        }
+        if !self.watches.is_empty() {
+            parts.push(format!("{} more watch(es)", self.watches.len()));
+        }
+        if self.pending_mail > 0 {
+            parts.push(format!("{} letter(s) waiting on a receipt", self.pending_mail));
+        }
+        if parts.is_empty() {
+            return "nothing to resume".to_string();
+        }
+        parts.join(", ")
+    }
+}
+
+// The default node's keys for the record it made.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WriterKeys {
+    pub record_owner: String,
+    pub member: String,
+    #[serde(default)]
+    pub drop: Option<String>,
+}
+
+pub struct Writers {
+    pub record_owner: KeyPair,
+    pub member: KeyPair,
+    pub drop: Option<KeyPair>,
+}
+
+impl WriterKeys {
+    pub fn new(record_owner: &KeyPair, member: &KeyPair, drop: Option<&KeyPair>) -> WriterKeys {
+        WriterKeys {
+            record_owner: record_owner.to_string(),
+            member: member.to_string(),
+            drop: drop.map(|d| d.to_string()),
+        }
+    }
+
+    pub fn parse(&self) -> Option<Writers> {
+        Some(Writers {
+            record_owner: self.record_owner.parse().ok()?,
+            member: self.member.parse().ok()?,
+            drop: match &self.drop {
+                Some(d) => Some(d.parse().ok()?),
+                None => None,
+            },
+        })
+    }
+}
+
+pub struct StateJournal {
+    path: PathBuf,
+    state: JournalState,
+}
+
+fn journal_path(data_dir: &Path, role: &str) -> PathBuf {
+    data_dir.join(format!("journal-{role}.json"))
+}
+
+impl StateJournal {
+    // Start a fresh journal for this run. What the last run left is returned
+    // if it ended without cleaning up after itself.
+    pub fn start(data_dir: &Path, role: &str, marker: &RunMarker) -> (StateJournal, Option<JournalState>) {
+        let path = journal_path(data_dir, role);
+        let previous = if marker.after_unclean_exit() {
+            fs::read(&path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<JournalState>(&bytes).ok())
+                .filter(|state| *state != JournalState::default())
+        } else {
+            None
+        };
+        let journal = StateJournal {
+            path,
+            state: previous.clone().unwrap_or_default(),
+        };
+        (journal, previous)
+    }
+
+    fn save(&self) {
+        let tmp = self.path.with_extension("json.tmp");
+        let Ok(bytes) = serde_json::to_vec_pretty(&self.state) else {
+            return;
+        };
+        // not worth stopping the node over; the worst case is a run that doesn't resume
+        if fs::write(&tmp, bytes).and_then(|()| fs::rename(&tmp, &self.path)).is_err() {
+            println!("Couldn't update {}", self.path.to_string_lossy());
+        }
+    }
+
+    pub fn record_opened(&mut self, record: &RecordKey, follow: Option<(ValueSubkey, ValueSubkey)>) {
+        self.state.record = Some(record.to_string());
+        self.state.follow = follow;
+        self.save();
+    }
+
+    pub fn watch_added(&mut self, record: &RecordKey) {
+        let record = record.to_string();
+        if !self.state.watches.contains(&record) {
+            self.state.watches.push(record);
+            self.save();
+        }
+    }
+
+    pub fn pending_mail(&mut self, pending: usize) {
+        if self.state.pending_mail != pending {
+            self.state.pending_mail = pending;
+            self.save();
+        }
+    }
+
+    // A clean exit leaves nothing to resume.
+    pub fn finish(self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub async fn save_writers(api: &VeilidAPI, record: &RecordKey, writers: &WriterKeys) -> VeilidAPIResult<()> {
+    let db = api.table_store()?.open(TABLE, COLUMNS).await?;
+    db.store_json(COL_WRITERS, record.to_string().as_bytes(), writers).await
+}
+
+pub async fn load_writers(api: &VeilidAPI, record: &RecordKey) -> VeilidAPIResult<Option<WriterKeys>> {
+    let db = api.table_store()?.open(TABLE, COLUMNS).await?;
+    db.load_json::<WriterKeys>(COL_WRITERS, record.to_string().as_bytes()).await
+}
+
+// The last sender_seq this node's writer used (0 if it hasn't written yet).
+pub async fn load_sent(api: &VeilidAPI) -> VeilidAPIResult<u64> {
+    let db = api.table_store()?.open(TABLE, COLUMNS).await?;
+    Ok(db.load_json::<u64>(COL_SENT, SENT_KEY).await?.unwrap_or(0))
+}
+
+pub async fn save_sent(api: &VeilidAPI, sent: u64) -> VeilidAPIResult<()> {
+    let db = api.table_store()?.open(TABLE, COLUMNS).await?;
+    db.store_json(COL_SENT, SENT_KEY, &sent).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_round_trips_and_tells_what_it_resumes() {
+        let key: RecordKey = RecordKey::new(
+            CRYPTO_KIND_VLD0,
+            BareRecordKey::new(BareOpaqueRecordKey::new(&[7; 32]), None),
+        );
+        let state = JournalState {
+            record: Some(key.to_string()),
+            follow: Some((1, 3)),
+            watches: vec![key.to_string(), "not a key".to_string()],
+            pending_mail: 2,
+        };
+        let back: JournalState = serde_json::from_slice(&serde_json::to_vec(&state).unwrap()).unwrap();
+        assert_eq!(back, state);
+        assert_eq!(back.record_key(), Some(key.clone()));
+        assert_eq!(back.watch_keys(), vec![key]);
+        assert!(back.describe().contains("2 letter(s)"));
+        assert_eq!(JournalState::default().describe(), "nothing to resume");
+
+        let owner = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let member = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let writer = WriterKeys::new(&owner, &member, None).parse().unwrap();
+        assert_eq!((writer.record_owner, writer.member, writer.drop), (owner, member, None));
+    }
+}
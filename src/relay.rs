@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use veilid_core::*;
+
+use crate::contacts::Contacts;
+use crate::i18n::t;
+use crate::nicknames::{short_key, Nicknames};
+use crate::profile::ProfileCard;
+use crate::watch::DecodedChange;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Relay mode: one node watches the record and passes each change on to
+//	whoever joined it, over app_message, so they don't need DHT watches of
+//	their own.
+//
+//	Every watch is something the network has to keep and renew; with ten
+//	nodes following a record that's ten watches on the same subkeys. With a
+//	well-connected node relaying, there's one, and the rest just listen on
+//	their private routes:
+//
+//	  alt node       relay on              take joins, pass changes on
+//	  default node   relay join alt-node   ask the alt node for its changes
+//	                 relay leave alt-node  and to stop sending them
+//	  either         relay                 who we relay to, or which relays we joined
+//
+//	A Join carries the joiner's profile card; the relay checks it, and if
+//	its key is one of the relay's contacts (profile.rs swaps cards when the
+//	alt node joins), sends each change it decodes to the route on the card,
+//	already read and named, as "[relay alt-node] ..." lines. A joiner whose
+//	route stops answering is dropped after MAX_FAILURES sends in a row; it
+//	can join again once it has a new route.
+//
+//	Only the alt node watches the record, so it's the one that can relay;
+//	the default node writes to it, and its own writes never come back to
+//	it as changes anyway.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// So a relay message can be told apart from cards, logs and chat.
+const RELAY_MAGIC: &[u8] = b"VXRLAY1\n";
+// failed sends in a row before a joiner is dropped
+const MAX_FAILURES: u32 = 3;
+// of a value, to stay well inside app_message's size limit
+const VALUE_WIDTH: usize = 2000;
+
+// A watched change, as the relay saw it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RelayedChange {
+    pub shortcode: String,
+    pub subkey: Option<ValueSubkey>,
+    pub seq: String,
+    // as the relay knows the writer
+    pub writer: String,
+    pub value: String,
+    // changed subkeys whose values didn't come along; read them to see
+    pub unsent: Vec<ValueSubkey>,
+    pub watch_died: bool,
+}
+
+impl RelayedChange {
+    pub fn new(change: &DecodedChange, names: &Nicknames) -> RelayedChange {
+        let mut value = change.value.as_ref().map(|v| v.display()).unwrap_or_default();
+        if value.chars().count() > VALUE_WIDTH {
+            value = format!("{}…", value.chars().take(VALUE_WIDTH - 1).collect::<String>());
+        }
+        RelayedChange {
+            shortcode: change.shortcode(),
+            subkey: change.value.as_ref().map(|v| v.subkey),
+            seq: change.value.as_ref().map(|v| v.seq.to_string()).unwrap_or_default(),
+            writer: change.value.as_ref().map(|v| names.label(&v.writer)).unwrap_or_default(),
+            value,
+            unsent: change.unsent(),
+            watch_died: change.watch_died,
+        }
+    }
+
+    // What a joiner prints, after "[relay <name>] ".
+    fn describe(&self) -> String {
+        if self.watch_died {
+            return t!("relayed-watch-died", code = self.shortcode.as_str());
+        }
+        let mut parts = Vec::new();
+        if let Some(subkey) = self.subkey {
+            parts.push(t!(
+                "relayed-value",
+                subkey = subkey,
+                seq = self.seq.as_str(),
+                writer = self.writer.as_str(),
+                value = self.value.as_str()
+            ));
+        }
+        if !self.unsent.is_empty() {
+            let unsent = self.unsent.iter().map(ValueSubkey::to_string).collect::<Vec<_>>().join(", ");
+            parts.push(t!("relayed-also-changed", subkeys = unsent));
+        }
+        format!("{} {}", self.shortcode, parts.join("; "))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RelayMessage {
+    // "send me the changes", with who's asking and where to send them
+    Join(ProfileCard),
+    Leave(ProfileCard),
+    // `from` is the relay's public key
+    Change { from: String, change: RelayedChange },
+}
+
+impl RelayMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = RELAY_MAGIC.to_vec();
+        out.extend(serde_json::to_vec(self).expect("relay message serializes"));
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Option<RelayMessage> {
+        serde_json::from_slice(data.strip_prefix(RELAY_MAGIC)?).ok()
+    }
+}
+
+// Quick check for the update callback, before any decoding.
+pub fn is_relay_message(data: &[u8]) -> bool {
+    data.starts_with(RELAY_MAGIC)
+}
+
+struct Joiner {
+    name: String,
+    route: RouteId,
+    failures: u32,
+}
+
+// by the joiner's public key
+type Joiners = Arc<Mutex<HashMap<String, Joiner>>>;
+
+// This node's side of relaying: the joiners when it relays, the relays it
+// joined when it doesn't.
+pub struct Relay {
+    api: VeilidAPI,
+    rc: RoutingContext,
+    // whether this node watches anything to relay
+    watching: bool,
+    on: bool,
+    joiners: Joiners,
+    // the forwarding task's queue, so changes go out in the order they came
+    queue: flume::Sender<RelayedChange>,
+    forwarder: JoinHandle<()>,
+    // contact names of the relays we joined
+    joined: Vec<String>,
+}
+
+impl Relay {
+    pub fn new(mine: &ProfileCard, api: VeilidAPI, rc: RoutingContext, watching: bool) -> Relay {
+        let joiners = Joiners::default();
+        let (queue, changes) = flume::unbounded();
+        let forwarder = tokio::spawn(forward(
+            api.clone(),
+            rc.clone(),
+            mine.public_key.clone(),
+            joiners.clone(),
+            changes,
+        ));
+        Relay {
+            api,
+            rc,
+            watching,
+            on: false,
+            joiners,
+            queue,
+            forwarder,
+            joined: Vec::new(),
+        }
+    }
+
+    async fn send(&self, blob: Vec<u8>, msg: &RelayMessage) -> VeilidAPIResult<()> {
+        let route = self.api.import_remote_private_route(blob)?;
+        self.rc.app_message(Target::RouteId(route), msg.encode()).await
+    }
+
+    // `relay [on|off|join <contact>|leave <contact>]` at the prompt.
+    pub async fn command(&mut self, args: &str, contacts: &Contacts, mine: &ProfileCard) -> String {
+        let (verb, name) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+        let name = name.trim();
+        match verb {
+            "" => self.status(),
+            "on" if !self.watching => t!("relay-nothing-to-relay"),
+            "on" => {
+                self.on = true;
+                t!("relay-on")
+            }
+            "off" => {
+                self.on = false;
+                let dropped = std::mem::take(&mut *self.joiners.lock().unwrap());
+                t!("relay-off", count = dropped.len())
+            }
+            "join" | "leave" if !name.is_empty() => {
+                let Some(contact) = contacts.get(name).or_else(|| contacts.by_key(name)) else {
+                    return t!("relay-no-such-contact", name = name);
+                };
+                let Some(blob) = contact.card.as_ref().and_then(|c| c.route_blob()) else {
+                    return t!("relay-no-route", name = name);
+                };
+                if mine.route.is_none() {
+                    return t!("relay-no-own-route");
+                }
+                let (msg, joining) = match verb {
+                    "join" => (RelayMessage::Join(mine.clone()), true),
+                    _ => (RelayMessage::Leave(mine.clone()), false),
+                };
+                if let Err(e) = self.send(blob, &msg).await {
+                    return t!("relay-unreachable", name = name, error = e.to_string());
+                }
+                let contact_name = contact.name.clone();
+                self.joined.retain(|n| *n != contact_name);
+                if joining {
+                    self.joined.push(contact_name);
+                    t!("relay-asked-join", name = name)
+                } else {
+                    t!("relay-asked-leave", name = name)
+                }
+            }
+            _ => t!("relay-usage"),
+        }
+    }
+
+    fn status(&self) -> String {
+        let mut lines = Vec::new();
+        if self.on {
+            let joiners = self.joiners.lock().unwrap();
+            let mut names: Vec<&str> = joiners.values().map(|j| j.name.as_str()).collect();
+            names.sort_unstable();
+            lines.push(if names.is_empty() {
+                t!("relay-on-no-joiners")
+            } else {
+                t!("relay-on-sending-to", names = names.join(", "))
+            });
+        } else if self.watching {
+            lines.push(t!("relay-status-off"));
+        }
+        if !self.joined.is_empty() {
+            lines.push(t!("relay-joined", names = self.joined.join(", ")));
+        } else if !self.watching {
+            lines.push(t!("relay-not-joined"));
+        }
+        lines.join("\n")
+    }
+
+    // A watched change: pass it on to the joiners, in the background.
+    pub fn forward(&self, change: &DecodedChange, names: &Nicknames) {
+        if self.on && !self.joiners.lock().unwrap().is_empty() {
+            let _ = self.queue.send(RelayedChange::new(change, names));
+        }
+    }
+
+    // A relay message came in. Gives back what to print.
+    pub fn receive(&mut self, data: &[u8], contacts: &Contacts) -> String {
+        match RelayMessage::decode(data) {
+            Some(RelayMessage::Change { from, change }) => {
+                let name = contacts.by_key(&from).map_or_else(|| short_key(&from), |c| c.name.clone());
+                format!("[relay {name}] {}", change.describe())
+            }
+            Some(RelayMessage::Join(card)) => {
+                let Some(contact) = checked(&card, contacts) else {
+                    return t!("relay-join-stranger", name = card.nickname.as_str());
+                };
+                if !self.on {
+                    return t!("relay-join-while-off", name = contact);
+                }
+                let route = match card.route_blob().map(|blob| self.api.import_remote_private_route(blob)) {
+                    Some(Ok(route)) => route,
+                    Some(Err(e)) => return t!("relay-join-bad-route", name = contact, error = e.to_string()),
+                    None => return t!("relay-join-no-route", name = contact),
+                };
+                let joiner = Joiner {
+                    name: contact.clone(),
+                    route,
+                    failures: 0,
+                };
+                self.joiners.lock().unwrap().insert(card.public_key.clone(), joiner);
+                t!("relay-peer-joined", name = contact)
+            }
+            Some(RelayMessage::Leave(card)) => {
+                let Some(contact) = checked(&card, contacts) else {
+                    return t!("relay-leave-stranger", name = card.nickname.as_str());
+                };
+                match self.joiners.lock().unwrap().remove(&card.public_key) {
+                    Some(_) => t!("relay-peer-left", name = contact),
+                    None => t!("relay-leave-not-joined", name = contact),
+                }
+            }
+            None => t!("relay-undecodable"),
+        }
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        self.forwarder.abort();
+    }
+}
+
+// The contact's name, if the card is signed and its key is someone we know.
+fn checked(card: &ProfileCard, contacts: &Contacts) -> Option<String> {
+    card.verify().ok()?;
+    contacts.by_key(&card.public_key).map(|c| c.name.clone())
+}
+
+// Send each change to every joiner, one change at a time, and drop the
+// joiners whose routes keep failing.
+async fn forward(api: VeilidAPI, rc: RoutingContext, from: String, joiners: Joiners, changes: flume::Receiver<RelayedChange>) {
+    while let Ok(change) = changes.recv_async().await {
+        let data = RelayMessage::Change { from: from.clone(), change }.encode();
+        let routes: Vec<(String, RouteId)> = joiners.lock().unwrap().iter().map(|(key, j)| (key.clone(), j.route.clone())).collect();
+        for (key, route) in routes {
+            let sent = rc.app_message(Target::RouteId(route.clone()), data.clone()).await;
+            let mut joiners = joiners.lock().unwrap();
+            let Some(joiner) = joiners.get_mut(&key) else { continue };
+            match sent {
+                Ok(()) => joiner.failures = 0,
+                Err(e) => {
+                    joiner.failures += 1;
+                    if joiner.failures >= MAX_FAILURES {
+                        println!("{}", t!("relay-dropped", name = joiner.name.as_str(), error = e.to_string()));
+                        joiners.remove(&key);
+                        let _ = api.release_private_route(route);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::Envelope;
+    use crate::watch::ChangedValue;
+
+    fn change(text: &str) -> DecodedChange {
+        let record = RecordKey::new(CRYPTO_KIND_VLD0, BareRecordKey::new(BareOpaqueRecordKey::new(&[7; 32]), None));
+        let mut subkeys = ValueSubkeyRangeSet::single(1);
+        subkeys.insert(4);
+        DecodedChange {
+            record,
+            subkeys,
+            value: Some(ChangedValue {
+                subkey: 1,
+                seq: ValueSeqNum::from(3),
+                writer: Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap().key(),
+                envelope: Ok(Envelope::text(text)),
+            }),
+            watch_died: false,
+        }
+    }
+
+    #[test]
+    fn changes_go_out_read_and_named() {
+        let names = Nicknames::load(&std::env::temp_dir().join(format!("relay-test-{}", std::process::id()))).unwrap();
+        let relayed = RelayedChange::new(&change("hello"), &names);
+        assert_eq!(relayed.subkey, Some(1));
+        assert_eq!(relayed.unsent, vec![4]);
+        assert!(relayed.describe().ends_with(": hello; also changed: 4"), "{}", relayed.describe());
+        assert_eq!(RelayedChange::new(&change(&"x".repeat(5000)), &names).value.chars().count(), VALUE_WIDTH);
+
+        let kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let card = ProfileCard::new(&kp, "alice", &["watch"], Some(b"route")).unwrap();
+        for msg in [
+            RelayMessage::Join(card.clone()),
+            RelayMessage::Leave(card),
+            RelayMessage::Change {
+                from: kp.key().to_string(),
+                change: relayed,
+            },
+        ] {
+            let data = msg.encode();
+            assert!(is_relay_message(&data));
+            assert_eq!(RelayMessage::decode(&data), Some(msg));
+        }
+        assert_eq!(RelayMessage::decode(b"VXLOGS1\n{}"), None);
+    }
+}
@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::config::Configurer;
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::{Context, EditMode, Editor, Helper, Highlighter, Hinter, Validator};
+use veilid_core::ValueSubkey;
+
+use crate::nicknames::{short_key, Nicknames};
+use crate::shortcode::ShortcodeBook;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Line editing for the interactive nodes: arrow keys, up-arrow history,
+//	Ctrl+R to search it, and a history file per role in the data folder, so
+//	it carries over between runs (and --data-dir/--config setups keep their
+//	own).
+//
+//	rustyline blocks while it waits for a key, so it lives on its own thread.
+//	The node loop asks for a line with next_line(), which is safe to use in
+//	a tokio::select!: if another branch wins, the line being typed isn't
+//	lost, the next call picks it up.
+//
+//	Tab completes the prompt's commands, subkey numbers once the record is
+//	open, writer keys from nicknames.json (type part of the nickname), and,
+//	where a record is asked for (including 'watch' and 'feed --record'),
+//	shortcodes and keys from shortcodes.json.
+//
+//	The prompt can carry a status in front of it (set_status), worked out
+//	afresh each time it's shown; the nodes put the DHT latency summary there.
+//	Emacs keys to start with; set_vi switches them (see prefs.rs).
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub enum ReplLine {
+    Line(String),
+    // Ctrl+C at the prompt
+    Interrupted,
+    // Ctrl+D, or stdin closed
+    Eof,
+}
+
+pub struct Repl {
+    want_tx: flume::Sender<()>,
+    line_rx: flume::Receiver<ReplLine>,
+    // a line has been asked for and hasn't arrived yet
+    pending: bool,
+    max_subkey: Arc<AtomicU32>,
+    status: Arc<Mutex<Option<StatusFn>>>,
+    vi: Arc<AtomicBool>,
+    prompt: String,
+}
+
+type StatusFn = Box<dyn Fn() -> String + Send>;
+
+pub fn history_file(data_dir: &Path, role: &str) -> PathBuf {
+    data_dir.join(format!("history_{role}.txt"))
+}
+
+// u32::MAX until a record is open
+const NO_RECORD: u32 = u32::MAX;
+
+// What Tab offers at the prompt.
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct ReplCompleter {
+    commands: Vec<&'static str>,
+    // also offer record shortcodes/keys as the first word
+    records: bool,
+    data_dir: PathBuf,
+    max_subkey: Arc<AtomicU32>,
+}
+
+impl ReplCompleter {
+    fn offer_records(&self, word: &str, offer: &mut impl FnMut(String, &str)) {
+        // the book is small and other nodes add to it, so read it fresh
+        if let Ok(book) = ShortcodeBook::load(&self.data_dir) {
+            for (code, key) in book.entries() {
+                if code.starts_with(word) {
+                    offer(format!("{code}  ({})", short_key(key)), code);
+                } else if key.starts_with(word) {
+                    offer(format!("{key}  ({code})"), key);
+                }
+            }
+        }
+    }
+}
+
+impl Completer for ReplCompleter {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(' ').map_or(0, |i| i + 1);
+        let word = &before[start..];
+        let earlier: Vec<&str> = before[..start].split_whitespace().collect();
+
+        let mut out = Vec::new();
+        let mut offer = |display: String, replacement: &str| {
+            out.push(Pair {
+                display,
+                replacement: replacement.to_string(),
+            })
+        };
+        match earlier.as_slice() {
+            [] => {
+                for cmd in self.commands.iter().filter(|c| c.starts_with(word)) {
+                    offer(cmd.to_string(), cmd);
+                }
+                if self.records {
+                    self.offer_records(word, &mut offer);
+                }
+            }
+            ["contact"] => {
+                for sub in ["add", "list", "remove"].into_iter().filter(|s| s.starts_with(word)) {
+                    offer(sub.to_string(), sub);
+                }
+            }
+            ["contact", "add", _, .., "--record"] | ["watch"] | ["feed", .., "--record"] => self.offer_records(word, &mut offer),
+            ["feed", .., "--member"] => {
+                if let Ok(names) = Nicknames::load(&self.data_dir) {
+                    for (_, name) in names.entries().filter(|(_, n)| n.starts_with(word)) {
+                        offer(name.to_string(), name);
+                    }
+                }
+            }
+            ["feed", ..] => {
+                for flag in ["--record", "--member"].into_iter().filter(|f| f.starts_with(word)) {
+                    offer(flag.to_string(), flag);
+                }
+            }
+            ["flood"] => {
+                let max = self.max_subkey.load(Ordering::Relaxed);
+                if max != NO_RECORD {
+                    for subkey in (0..=max).map(|s| s.to_string()).filter(|s| s.starts_with(word)) {
+                        offer(subkey.clone(), &subkey);
+                    }
+                }
+            }
+            ["nick"] => {
+                if let Ok(names) = Nicknames::load(&self.data_dir) {
+                    for (key, name) in names.entries() {
+                        if key.starts_with(word) || name.starts_with(word) {
+                            offer(format!("{name}  ({})", short_key(key)), key);
+                        }
+                    }
+                }
+            }
+            ["help"] => {
+                for cmd in self.commands.iter().filter(|c| c.starts_with(word)) {
+                    offer(cmd.to_string(), cmd);
+                }
+            }
+            _ => {}
+        }
+        Ok((start, out))
+    }
+}
+
+impl Repl {
+    // `commands` are what Tab offers as the first word (see commands.rs). With `records`,
+    // record shortcodes and keys are offered there too.
+    pub fn start(
+        history: PathBuf,
+        prompt: &str,
+        commands: Vec<&'static str>,
+        records: bool,
+    ) -> Result<Repl, Box<dyn std::error::Error>> {
+        let max_subkey = Arc::new(AtomicU32::new(NO_RECORD));
+        let mut editor: Editor<ReplCompleter, FileHistory> = Editor::new()?;
+        editor.set_helper(Some(ReplCompleter {
+            commands,
+            records,
+            data_dir: history.parent().unwrap_or(Path::new(".")).to_path_buf(),
+            max_subkey: max_subkey.clone(),
+        }));
+        // there's no history the first time round
+        let _ = editor.load_history(&history);
+
+        let (want_tx, want_rx) = flume::unbounded::<()>();
+        let (line_tx, line_rx) = flume::unbounded();
+        let prompt_text = prompt.to_string();
+        let prompt = prompt_text.clone();
+        let status: Arc<Mutex<Option<StatusFn>>> = Arc::new(Mutex::new(None));
+        let shown_status = status.clone();
+        let vi = Arc::new(AtomicBool::new(false));
+        let use_vi = vi.clone();
+        std::thread::spawn(move || {
+            while want_rx.recv().is_ok() {
+                editor.set_edit_mode(if use_vi.load(Ordering::Relaxed) { EditMode::Vi } else { EditMode::Emacs });
+                let shown = match shown_status.lock().unwrap().as_ref().map(|status| status()) {
+                    Some(status) if !status.is_empty() => format!("[{status}] {prompt}"),
+                    _ => prompt.clone(),
+                };
+                let line = match editor.readline(&shown) {
+                    Ok(line) => {
+                        if !line.trim().is_empty() {
+                            let _ = editor.add_history_entry(line.as_str());
+                            let _ = editor.save_history(&history);
+                        }
+                        ReplLine::Line(line)
+                    }
+                    Err(ReadlineError::Interrupted) => ReplLine::Interrupted,
+                    Err(_) => ReplLine::Eof,
+                };
+                if line_tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Repl {
+            want_tx,
+            line_rx,
+            pending: false,
+            max_subkey,
+            status,
+            vi,
+            prompt: prompt_text,
+        })
+    }
+
+    // Once a record is open, Tab can offer its subkeys.
+    pub fn set_max_subkey(&self, max_subkey: ValueSubkey) {
+        self.max_subkey.store(max_subkey, Ordering::Relaxed);
+    }
+
+    // Shown in front of the prompt from the next line on; empty shows nothing.
+    pub fn set_status(&self, status: impl Fn() -> String + Send + 'static) {
+        *self.status.lock().unwrap() = Some(Box::new(status));
+    }
+
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    // vi keys instead of emacs ones, from the next line on.
+    pub fn set_vi(&self, vi: bool) {
+        self.vi.store(vi, Ordering::Relaxed);
+    }
+
+    pub async fn next_line(&mut self) -> ReplLine {
+        if !self.pending {
+            if self.want_tx.send(()).is_err() {
+                return ReplLine::Eof;
+            }
+            self.pending = true;
+        }
+        let line = self.line_rx.recv_async().await.unwrap_or(ReplLine::Eof);
+        self.pending = false;
+        line
+    }
+}
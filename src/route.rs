@@ -0,0 +1,156 @@
+use std::sync::{Arc, Mutex};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::dht::Dht;
+use crate::envelope::{Codec, Envelope};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The default node's private route, kept in owner subkey 3 of its record,
+//	so whoever joins the record can reach the node over app_message.
+//
+//	A private route lets a node take messages without saying where it is:
+//	new_custom_private_route() picks a few hops through other nodes (here
+//	Reliable and PreferOrdered, as a chat line should arrive whole) and
+//	gives back the route's id and a blob describing it. The default node
+//	writes the blob to the subkey once the record is open; the alt node
+//	reads it when it joins, imports it with import_remote_private_route()
+//	and sends its `:msg` lines over it (see chat.rs). The alt node's watch
+//	covers the subkey, so a new blob takes over as soon as it's written.
+//
+//	Routes die when a hop goes away, and Veilid says so with a RouteChange
+//	update listing the dead route's id. When ours is among them, the
+//	default node makes another and writes it over the old one.
+//
+//	Records with fewer than four owner subkeys have no room for it; then
+//	the route on the owner's profile card in the metadata block, which
+//	isn't renewed, is all a joining node gets.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// The owner subkey the route goes in, on records with at least four.
+pub const ROUTE_SUBKEY: ValueSubkey = 3;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RouteValue {
+    // whose route it is, for the [chat ...] tag
+    pub nickname: String,
+    // the blob new_custom_private_route() gave back, base64
+    pub blob: String,
+    pub made_ms: u64,
+}
+
+impl RouteValue {
+    pub fn encode(&self) -> Vec<u8> {
+        Envelope::new(Codec::Json, serde_json::to_vec(self).expect("route value serializes")).encode()
+    }
+
+    // None if the subkey holds something else.
+    pub fn decode(data: &[u8]) -> Option<RouteValue> {
+        RouteValue::from_envelope(&Envelope::decode(data).ok()?)
+    }
+
+    pub fn from_envelope(env: &Envelope) -> Option<RouteValue> {
+        if env.codec != Codec::Json {
+            return None;
+        }
+        serde_json::from_slice(&env.body).ok()
+    }
+
+    pub fn route_blob(&self) -> Option<Vec<u8>> {
+        BASE64.decode(&self.blob).ok()
+    }
+}
+
+// Where the record keeps the route, if it has room for one.
+pub fn subkey(schema: &DHTSchema) -> Option<ValueSubkey> {
+    let owner_subkeys = match schema {
+        DHTSchema::DFLT(dflt) => dflt.o_cnt(),
+        DHTSchema::SMPL(smpl) => smpl.o_cnt(),
+    };
+    (ValueSubkey::from(owner_subkeys) > ROUTE_SUBKEY).then_some(ROUTE_SUBKEY)
+}
+
+// The route we last published, and word to the node when it dies.
+pub struct PublishedRoute {
+    current: Mutex<Option<RouteId>>,
+    died: flume::Sender<()>,
+}
+
+impl PublishedRoute {
+    pub fn new() -> (Arc<PublishedRoute>, flume::Receiver<()>) {
+        let (died, died_rx) = flume::unbounded();
+        let published = PublishedRoute {
+            current: Mutex::new(None),
+            died,
+        };
+        (Arc::new(published), died_rx)
+    }
+
+    // From the update callback: a RouteChange that lists our route means it
+    // needs making again.
+    pub fn route_changed(&self, change: &VeilidRouteChange) {
+        let current = self.current.lock().unwrap();
+        if current.as_ref().is_some_and(|id| change.dead_routes.contains(id)) {
+            let _ = self.died.send(());
+        }
+    }
+
+    // Make a new route, write its blob to `subkey` of the record (as the
+    // record owner, its default writer) and let the old one go.
+    pub async fn publish(
+        &self,
+        api: &VeilidAPI,
+        rc: &Dht,
+        record_key: &RecordKey,
+        subkey: ValueSubkey,
+        nickname: &str,
+    ) -> VeilidAPIResult<RouteId> {
+        let spec = PrivateSpec {
+            crypto_kinds: vec![CRYPTO_KIND_VLD0],
+            stability: Stability::Reliable,
+            sequencing: Sequencing::PreferOrdered,
+            ..Default::default()
+        };
+        let route = api.new_custom_private_route(spec).await?;
+        let value = RouteValue {
+            nickname: nickname.to_string(),
+            blob: BASE64.encode(&route.blob),
+            made_ms: crate::audit::now_ms() as u64,
+        };
+        if let Err(e) = rc.set_dht_value(record_key.clone(), subkey, value.encode(), None).await {
+            let _ = api.release_private_route(route.route_id);
+            return Err(e);
+        }
+        let old = self.current.lock().unwrap().replace(route.route_id.clone());
+        if let Some(old) = old {
+            // it's usually dead already, which Veilid may have cleared up itself
+            let _ = api.release_private_route(old);
+        }
+        Ok(route.route_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_route_needs_a_fourth_owner_subkey() {
+        let value = RouteValue {
+            nickname: "default-node".to_string(),
+            blob: BASE64.encode(b"route"),
+            made_ms: 1,
+        };
+        assert_eq!(RouteValue::decode(&value.encode()), Some(value.clone()));
+        assert_eq!(value.route_blob().as_deref(), Some(&b"route"[..]));
+        assert_eq!(RouteValue::decode(&Envelope::text("route").encode()), None);
+
+        assert_eq!(subkey(&DHTSchema::dflt(4).unwrap()), Some(ROUTE_SUBKEY));
+        assert_eq!(subkey(&DHTSchema::dflt(3).unwrap()), None);
+    }
+}
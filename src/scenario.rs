@@ -0,0 +1,460 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use veilid_core::*;
+
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::dht::Dht;
+use crate::envelope::Envelope;
+use crate::expect::{self, parse_duration};
+use crate::node::VeilidNode;
+use crate::shortcode::ShortcodeBook;
+use crate::watch::WatchSet;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`scenario run <file.yaml>`: a demo or regression check written down once
+//	and run the same way every time.
+//
+//	  name: write, then watch from a second node
+//	  steps:
+//	    - create                  # a record shaped like the config's
+//	    - write 2 hello
+//	    - b: open                 # node b joins the record from the step above
+//	    - b: assert-equals 2 hello
+//	    - write 2 again
+//	    - b: wait-for-change 2 30s
+//	    - b: assert-equals 2 again
+//	    - sleep 1s
+//
+//	Every step runs on a node, `a` unless it says otherwise, each started
+//	the first time it's named, in a namespace of its own
+//	(<default_namespace>-scenario-<node>). The actions:
+//
+//	  create [OWNER MEMBER]      an SMPL record with that many owner and member
+//	                             subkeys (default: owner_subkeys and member_subkeys)
+//	  open [RECORD]              open the created record (or another, by key or
+//	                             share code) and watch all of it
+//	  write SUBKEY TEXT          as whoever may write that subkey of the created record
+//	  wait-for-change SUBKEY DUR a change reached this node's watch since it opened
+//	                             the record (or since the last wait on that subkey)
+//	  wait-for SUBKEY --equals TEXT [--timeout DUR]
+//	                             fresh reads until the subkey shows TEXT (see expect.rs)
+//	  assert-equals SUBKEY TEXT  a fresh read of the subkey shows TEXT (or `assert`)
+//	  sleep DUR                  DUR is like 500ms, 30s or 2m
+//
+//	Only that much YAML is understood: `name:`, `steps:`, and one `- ` item
+//	per step, optionally `- node: action`. The run stops at the first step
+//	that fails, since the rest usually depend on it, and the command exits
+//	non-zero so CI can use it.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Create { owner_subkeys: Option<u16>, member_subkeys: Option<u16> },
+    Open { record: Option<String> },
+    Write { subkey: ValueSubkey, text: String },
+    WaitForChange { subkey: ValueSubkey, timeout: Duration },
+    WaitFor { subkey: ValueSubkey, text: String, timeout: Duration },
+    AssertEquals { subkey: ValueSubkey, text: String },
+    Sleep(Duration),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    // where it is in the file
+    pub line: usize,
+    pub node: String,
+    pub action: Action,
+    // the step as written, for the report
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Scenario {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+fn parse_action(words: &str) -> Result<Action, String> {
+    let (verb, rest) = words.split_once(' ').unwrap_or((words, ""));
+    let rest = rest.trim();
+    let subkey_and = |what: &str| -> Result<(ValueSubkey, String), String> {
+        let (subkey, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+        let subkey = subkey.parse().map_err(|_| format!("{verb} needs a subkey number first, got '{subkey}'"))?;
+        if tail.trim().is_empty() {
+            return Err(format!("{verb} needs {what} after the subkey"));
+        }
+        Ok((subkey, tail.trim().to_string()))
+    };
+    let duration = |text: &str| parse_duration(text).ok_or_else(|| format!("'{text}' isn't a duration like 500ms, 30s or 2m"));
+    Ok(match verb {
+        "create" => {
+            let counts: Vec<&str> = rest.split_whitespace().collect();
+            let count = |s: &str| s.parse::<u16>().map_err(|_| format!("create takes two subkey counts, got '{rest}'"));
+            match counts.as_slice() {
+                [] => Action::Create { owner_subkeys: None, member_subkeys: None },
+                [o, m] => Action::Create {
+                    owner_subkeys: Some(count(o)?),
+                    member_subkeys: Some(count(m)?),
+                },
+                _ => return Err(format!("create takes no counts or two (OWNER MEMBER), got '{rest}'")),
+            }
+        }
+        "open" => Action::Open {
+            record: Some(rest.to_string()).filter(|r| !r.is_empty()),
+        },
+        "write" => {
+            let (subkey, text) = subkey_and("the text")?;
+            Action::Write { subkey, text }
+        }
+        "wait-for-change" => {
+            let (subkey, timeout) = subkey_and("a timeout")?;
+            Action::WaitForChange { subkey, timeout: duration(&timeout)? }
+        }
+        "wait-for" => {
+            let (subkey, text, timeout) = expect::parse_wait_for(rest)?;
+            Action::WaitFor { subkey, text, timeout }
+        }
+        "assert-equals" | "assert" => {
+            let (subkey, text) = subkey_and("the expected text")?;
+            Action::AssertEquals { subkey, text }
+        }
+        "sleep" => Action::Sleep(duration(rest)?),
+        other => return Err(format!("unknown action '{other}'")),
+    })
+}
+
+// Strip a trailing `# comment` and YAML quotes around the whole item.
+fn clean(item: &str) -> &str {
+    let item = match item.find(" #") {
+        Some(at) => &item[..at],
+        None => item,
+    };
+    let item = item.trim();
+    item.strip_prefix('"')
+        .and_then(|i| i.strip_suffix('"'))
+        .or_else(|| item.strip_prefix('\'').and_then(|i| i.strip_suffix('\'')))
+        .unwrap_or(item)
+}
+
+pub fn parse(text: &str) -> Result<Scenario, String> {
+    let mut name = None;
+    let mut in_steps = false;
+    let mut steps = Vec::new();
+    for (i, raw) in text.lines().enumerate() {
+        let line = i + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(value) = trimmed.strip_prefix("name:") {
+            name = Some(clean(value).to_string());
+            in_steps = false;
+        } else if trimmed == "steps:" {
+            in_steps = true;
+        } else if let Some(item) = trimmed.strip_prefix("- ").filter(|_| in_steps) {
+            let item = clean(item);
+            let (node, words) = match item.split_once(':') {
+                Some((node, words)) if !node.is_empty() && node.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') => {
+                    (node.to_string(), clean(words))
+                }
+                _ => ("a".to_string(), item),
+            };
+            let action = parse_action(words).map_err(|e| format!("line {line}: {e}"))?;
+            steps.push(Step {
+                line,
+                node,
+                action,
+                text: item.to_string(),
+            });
+        } else {
+            return Err(format!("line {line}: expected 'name:', 'steps:' or a '- step', got '{trimmed}'"));
+        }
+    }
+    if steps.is_empty() {
+        return Err("the scenario has no steps".to_string());
+    }
+    Ok(Scenario {
+        name: name.unwrap_or_else(|| "unnamed scenario".to_string()),
+        steps,
+    })
+}
+
+// -------------------------------------------------------------------------
+// Running one
+// -------------------------------------------------------------------------
+
+struct Actor {
+    node: VeilidNode,
+    rc: Dht,
+    changes: WatchSet,
+    // changes heard but not yet waited for, by subkey
+    heard: BTreeMap<ValueSubkey, u32>,
+}
+
+// The record `create` made, and the keys that write it.
+struct Created {
+    key: RecordKey,
+    owner_subkeys: ValueSubkey,
+    owner: Option<KeyPair>,
+    member: KeyPair,
+}
+
+struct Run<'a> {
+    options: &'a Options,
+    config: &'a AppConfig,
+    actors: BTreeMap<String, Actor>,
+    created: Option<Created>,
+    // the record each node has open
+    opened: BTreeMap<String, RecordKey>,
+}
+
+impl Run<'_> {
+    async fn actor(&mut self, name: &str) -> Result<&mut Actor, String> {
+        if !self.actors.contains_key(name) {
+            let data_dir = self.config.data_dir().map_err(|e| e.to_string())?;
+            let namespace = format!("{}-scenario-{name}", self.config.default_namespace);
+            let node = VeilidNode::start_attached(self.config, &data_dir, &namespace, |_| {})
+                .await
+                .map_err(|e| format!("node {name} didn't start: {e}"))?;
+            // --chaos applies, so a scenario can be run against a flaky network too
+            let rc = Dht::new(node.routing_context().get(), None, false, self.options.chaos.clone());
+            self.actors.insert(
+                name.to_string(),
+                Actor {
+                    node,
+                    rc,
+                    changes: WatchSet::new(),
+                    heard: BTreeMap::new(),
+                },
+            );
+        }
+        Ok(self.actors.get_mut(name).expect("just added"))
+    }
+
+    fn opened(&self, node: &str) -> Result<RecordKey, String> {
+        self.opened
+            .get(node)
+            .cloned()
+            .ok_or_else(|| format!("node {node} has no record open (use create or open first)"))
+    }
+
+    // One step. Ok carries a short note for the report.
+    async fn step(&mut self, step: &Step) -> Result<String, String> {
+        let node = step.node.as_str();
+        match &step.action {
+            Action::Create { owner_subkeys, member_subkeys } => {
+                let owner_subkeys = owner_subkeys.unwrap_or(self.config.owner_subkeys);
+                let member_subkeys = member_subkeys.unwrap_or(self.config.member_subkeys);
+                let actor = self.actor(node).await?;
+                let member = Crypto::generate_keypair(CRYPTO_KIND_VLD0).map_err(|e| e.to_string())?;
+                let member_id = actor.node.api().generate_member_id(&member.key()).map_err(|e| e.to_string())?;
+                let schema = DHTSchema::smpl(
+                    owner_subkeys,
+                    vec![DHTSchemaSMPLMember {
+                        m_key: member_id.into_value(),
+                        m_cnt: member_subkeys,
+                    }],
+                )
+                .map_err(|e| e.to_string())?;
+                let desc = actor
+                    .rc
+                    .create_dht_record(CRYPTO_KIND_VLD0, schema, None)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let key = desc.key();
+                watch_all(actor, &key).await?;
+                self.created = Some(Created {
+                    key: key.clone(),
+                    owner_subkeys: ValueSubkey::from(owner_subkeys),
+                    owner: desc.owner_keypair(),
+                    member,
+                });
+                self.opened.insert(node.to_string(), key.clone());
+                Ok(format!("record {key}"))
+            }
+            Action::Open { record } => {
+                let key = match record {
+                    Some(text) => {
+                        let data_dir = self.config.data_dir().map_err(|e| e.to_string())?;
+                        let book = ShortcodeBook::load(&data_dir).map_err(|e| e.to_string())?;
+                        book.resolve(text)?
+                    }
+                    None => self
+                        .created
+                        .as_ref()
+                        .map(|c| c.key.clone())
+                        .ok_or("nothing has been created to open; give a record key")?,
+                };
+                let actor = self.actor(node).await?;
+                let _ = actor.rc.open_dht_record(key.clone(), None).await.map_err(|e| e.to_string())?;
+                watch_all(actor, &key).await?;
+                self.opened.insert(node.to_string(), key.clone());
+                Ok(format!("watching {key}"))
+            }
+            Action::Write { subkey, text } => {
+                let key = self.opened(node)?;
+                let created = self.created.as_ref().filter(|c| c.key == key).ok_or("only the created record can be written")?;
+                let writer = if *subkey < created.owner_subkeys {
+                    created.owner.clone().ok_or("no owner key for that subkey")?
+                } else {
+                    created.member.clone()
+                };
+                let opts = SetDHTValueOptions {
+                    writer: Some(writer),
+                    allow_offline: None,
+                };
+                let actor = self.actor(node).await?;
+                actor
+                    .rc
+                    .set_dht_value(key, *subkey, Envelope::text(text).encode(), Some(opts))
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok(String::new())
+            }
+            Action::WaitForChange { subkey, timeout } => {
+                let key = self.opened(node)?;
+                let actor = self.actor(node).await?;
+                let deadline = tokio::time::Instant::now() + *timeout;
+                loop {
+                    if let Some(count) = actor.heard.remove(subkey) {
+                        return Ok(format!("{count} change(s)"));
+                    }
+                    match tokio::time::timeout_at(deadline, actor.changes.next()).await {
+                        Ok(Some(change)) if change.record == key => {
+                            for s in change.subkeys.iter() {
+                                *actor.heard.entry(s).or_default() += 1;
+                            }
+                        }
+                        Ok(Some(_)) => {}
+                        Ok(None) => return Err("the watch stream closed".to_string()),
+                        Err(_) => return Err(format!("no change to subkey {subkey} in {}s", timeout.as_secs_f32())),
+                    }
+                }
+            }
+            Action::WaitFor { subkey, text, timeout } => {
+                let key = self.opened(node)?;
+                let actor = self.actor(node).await?;
+                let took = expect::wait_for(&actor.rc, &key, *subkey, text, *timeout).await?;
+                Ok(format!("there after {:.1}s", took.as_secs_f32()))
+            }
+            Action::AssertEquals { subkey, text } => {
+                let key = self.opened(node)?;
+                let actor = self.actor(node).await?;
+                expect::assert_value(&actor.rc, &key, *subkey, text).await?;
+                Ok(String::new())
+            }
+            Action::Sleep(how_long) => {
+                tokio::time::sleep(*how_long).await;
+                Ok(String::new())
+            }
+        }
+    }
+}
+
+async fn watch_all(actor: &Actor, key: &RecordKey) -> Result<(), String> {
+    actor
+        .node
+        .watch(&actor.changes, key.clone(), ValueSubkeyRangeSet::full())
+        .await
+        .map_err(|e| format!("couldn't watch {key}: {e}"))
+}
+
+pub async fn run(file: &Path, options: &Options, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(file).map_err(|e| format!("can't read {}: {e}", file.to_string_lossy()))?;
+    let scenario = parse(&text).map_err(|e| format!("{}: {e}", file.to_string_lossy()))?;
+    println!("Scenario: {} ({} steps)", scenario.name, scenario.steps.len());
+
+    let mut run = Run {
+        options,
+        config,
+        actors: BTreeMap::new(),
+        created: None,
+        opened: BTreeMap::new(),
+    };
+    let mut failure = None;
+    for (i, step) in scenario.steps.iter().enumerate() {
+        let started = Instant::now();
+        let result = run.step(step).await;
+        let took = format!("{:.1}s", started.elapsed().as_secs_f32());
+        match result {
+            Ok(note) if note.is_empty() => println!("  [pass] {:>2} {}: {}  ({took})", i + 1, step.node, step.text),
+            Ok(note) => println!("  [pass] {:>2} {}: {}  ({took}, {note})", i + 1, step.node, step.text),
+            Err(e) => {
+                println!("  [FAIL] {:>2} {}: {}  ({took})", i + 1, step.node, step.text);
+                println!("         line {}: {e}", step.line);
+                failure = Some(i);
+                break;
+            }
+        }
+    }
+
+    for (_, actor) in std::mem::take(&mut run.actors) {
+        actor.node.api().clone().shutdown().await;
+    }
+    match failure {
+        None => {
+            println!("PASS: all {} steps", scenario.steps.len());
+            Ok(())
+        }
+        Some(i) => Err(format!(
+            "FAIL at step {} of {} ({} skipped)",
+            i + 1,
+            scenario.steps.len(),
+            scenario.steps.len() - i - 1
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenario_files_parse_into_steps_per_node() {
+        let text = "# a demo\n\
+                    name: \"write then watch\"\n\
+                    steps:\n  \
+                      - create 1 2\n  \
+                      - write 2 hello world   # the first value\n  \
+                      - b: open\n  \
+                      - 'b: wait-for-change 2 30s'\n  \
+                      - b: assert-equals 2 hello world\n  \
+                      - b: wait-for 2 --equals hello world --timeout 10s\n  \
+                      - sleep 500ms\n";
+        let scenario = parse(text).unwrap();
+        assert_eq!(scenario.name, "write then watch");
+        let actions: Vec<(&str, &Action)> = scenario.steps.iter().map(|s| (s.node.as_str(), &s.action)).collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("a", &Action::Create { owner_subkeys: Some(1), member_subkeys: Some(2) }),
+                ("a", &Action::Write { subkey: 2, text: "hello world".to_string() }),
+                ("b", &Action::Open { record: None }),
+                ("b", &Action::WaitForChange { subkey: 2, timeout: Duration::from_secs(30) }),
+                ("b", &Action::AssertEquals { subkey: 2, text: "hello world".to_string() }),
+                (
+                    "b",
+                    &Action::WaitFor {
+                        subkey: 2,
+                        text: "hello world".to_string(),
+                        timeout: Duration::from_secs(10)
+                    }
+                ),
+                ("a", &Action::Sleep(Duration::from_millis(500))),
+            ]
+        );
+        assert_eq!(scenario.steps[1].line, 5);
+
+        assert!(parse("steps:\n  - fly 2\n").unwrap_err().starts_with("line 2: unknown action"));
+        assert!(parse("steps:\n  - wait-for-change 2 soon\n").unwrap_err().contains("duration"));
+        assert!(parse("steps:\n  - write two x\n").unwrap_err().contains("subkey number"));
+        assert!(parse("name: empty\n").unwrap_err().contains("no steps"));
+    }
+}
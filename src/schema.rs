@@ -0,0 +1,546 @@
+use veilid_core::*;
+
+use crate::backend::member_id;
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::dht::forward_pointer;
+use crate::exit::Kind;
+use crate::keyfile::{Expected, Grant, Role};
+use crate::metadata::{RecordMetadata, METADATA_SUBKEY};
+use crate::progress;
+use crate::record::start_node_in;
+use crate::record_manager::RecordManager;
+use crate::recovery::{self, WriterKeys};
+use crate::shortcode::ShortcodeBook;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`schema ...` commands.
+//
+//	A record's schema can't change once it's created, so "growing" one means
+//	making a successor record with more subkeys and moving in:
+//	  1: read every subkey of the old record
+//	  2: create the successor with the same owner and the same members, so
+//	     every writer's key still writes its (now bigger) range
+//	  3: copy the values across, member subkeys shifted to their new place,
+//	     and the metadata block's roster, fields, mailbox and payload types
+//	     redone for the new layout
+//	  4: overwrite the old record's subkey 0 with a forwarding pointer
+//
+//	It runs in the default node's namespace, whose table store has the keys
+//	the node made the record with (see recovery.rs); they're saved again
+//	under the successor's key, so the node carries on writing to it after a
+//	restart. A member subkey written by a key that isn't there (another
+//	node's) is left behind for its writer to write again.
+//
+//	Anything that opens records with Dht::open_following, directly or
+//	through the RecordManager (the default node resuming its record, the
+//	alt and member nodes, soak, record clone), then ends up on the
+//	successor without being told.
+//
+//	Also here: which subkeys a keypair may write, worked out from the
+//	schema, so a write without a subkey goes to the writer's first one;
+//	and what owner_keys.txt says the holder will find in the record (its
+//	Record.* lines), checked against what it really finds. The record
+//	fingerprint (owner and schema) also rides along on share codes.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub struct GrowArgs {
+    pub source: String,
+    pub owner_subkeys: Option<u16>,
+    pub member_subkeys: Option<u16>,
+    // the old record's owner keypair, needed to write the forwarding pointer
+    pub owner: Option<String>,
+}
+
+// (owner subkey count, subkey count of each member)
+fn shape(schema: &DHTSchema) -> (u16, Vec<u16>) {
+    match schema {
+        DHTSchema::DFLT(dflt) => (dflt.o_cnt(), Vec::new()),
+        DHTSchema::SMPL(smpl) => (smpl.o_cnt(), smpl.members().iter().map(|m| m.m_cnt).collect()),
+    }
+}
+
+// Where an old subkey ends up in the successor: owner subkeys keep their
+// number, member subkeys keep their offset within that member's range.
+fn new_subkey(old_schema: &DHTSchema, grown: &DHTSchema, subkey: ValueSubkey) -> ValueSubkey {
+    let (o_cnt, members) = shape(old_schema);
+    let (grown_o_cnt, grown_members) = shape(grown);
+    let mut rest = subkey;
+    if rest < ValueSubkey::from(o_cnt) {
+        return rest;
+    }
+    rest -= ValueSubkey::from(o_cnt);
+    let mut first = ValueSubkey::from(grown_o_cnt);
+    for (m_cnt, grown_cnt) in members.iter().zip(&grown_members) {
+        if rest < ValueSubkey::from(*m_cnt) {
+            return first + rest;
+        }
+        rest -= ValueSubkey::from(*m_cnt);
+        first += ValueSubkey::from(*grown_cnt);
+    }
+    // can't happen, subkey came from 0..=max_subkey()
+    subkey
+}
+
+// The last subkey of the owner's or member's range that `subkey` is in.
+fn range_end(schema: &DHTSchema, subkey: ValueSubkey) -> ValueSubkey {
+    let (o_cnt, members) = shape(schema);
+    let mut end = ValueSubkey::from(o_cnt);
+    for m_cnt in members {
+        if subkey < end {
+            break;
+        }
+        end += ValueSubkey::from(m_cnt);
+    }
+    end.saturating_sub(1).max(subkey)
+}
+
+// An inclusive range moved to the successor. One that ran to the end of a
+// writer's range still does, so it takes in the new subkeys.
+fn new_range(old_schema: &DHTSchema, grown: &DHTSchema, first: ValueSubkey, last: ValueSubkey) -> (ValueSubkey, ValueSubkey) {
+    let moved = new_subkey(old_schema, grown, last);
+    let last = if last == range_end(old_schema, last) { range_end(grown, moved) } else { moved };
+    (new_subkey(old_schema, grown, first), last)
+}
+
+// The metadata block as it should read in the successor: every subkey it
+// names is where that value went.
+fn grown_metadata(meta: &RecordMetadata, old_schema: &DHTSchema, grown: &DHTSchema) -> RecordMetadata {
+    let mut meta = meta.clone();
+    meta.schema = format!("{grown:?}");
+    for entry in &mut meta.members {
+        (entry.first_subkey, entry.last_subkey) = new_range(old_schema, grown, entry.first_subkey, entry.last_subkey);
+        for subkey in entry.fields.values_mut() {
+            *subkey = new_subkey(old_schema, grown, *subkey);
+        }
+    }
+    if let Some(info) = &mut meta.mailbox {
+        (info.first_subkey, info.last_subkey) = new_range(old_schema, grown, info.first_subkey, info.last_subkey);
+    }
+    meta.payload_types = std::mem::take(&mut meta.payload_types)
+        .into_iter()
+        .map(|(subkey, name)| (new_subkey(old_schema, grown, subkey), name))
+        .collect();
+    meta
+}
+
+// The subkeys `writer` may write (inclusive ranges, lowest first) in a
+// record with this schema and owner.
+pub fn writable_subkeys(schema: &DHTSchema, owner: &PublicKey, writer: &PublicKey) -> Vec<(ValueSubkey, ValueSubkey)> {
+    let (o_cnt, member_counts) = shape(schema);
+    let mut ranges = Vec::new();
+    if o_cnt > 0 && writer == owner {
+        ranges.push((0, ValueSubkey::from(o_cnt) - 1));
+    }
+    if let DHTSchema::SMPL(smpl) = schema {
+        let id = member_id(writer);
+        let mut first = ValueSubkey::from(o_cnt);
+        for (m, m_cnt) in smpl.members().iter().zip(member_counts) {
+            if m_cnt > 0 && m.m_key == id {
+                ranges.push((first, first + ValueSubkey::from(m_cnt) - 1));
+            }
+            first += ValueSubkey::from(m_cnt);
+        }
+    }
+    ranges
+}
+
+pub fn describe_ranges(ranges: &[(ValueSubkey, ValueSubkey)]) -> String {
+    ranges
+        .iter()
+        .map(|&(first, last)| if first == last { first.to_string() } else { format!("{first}..={last}") })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Which subkey a write goes to and which of `keys` signs it: `subkey` if
+// one of them may write it, or with None the first subkey any of them may
+// (the earlier keys first).
+pub fn pick_writer<'a>(
+    schema: &DHTSchema,
+    owner: &PublicKey,
+    keys: &[&'a KeyPair],
+    subkey: Option<ValueSubkey>,
+) -> Result<(ValueSubkey, &'a KeyPair), String> {
+    let writable: Vec<(&KeyPair, Vec<(ValueSubkey, ValueSubkey)>)> =
+        keys.iter().map(|&kp| (kp, writable_subkeys(schema, owner, &kp.key()))).collect();
+    let all: Vec<(ValueSubkey, ValueSubkey)> = writable.iter().flat_map(|(_, r)| r.iter().copied()).collect();
+    if all.is_empty() {
+        return Err(if keys.is_empty() {
+            "we hold no keys for this record, so we can only read it".to_string()
+        } else {
+            "the record's schema gives none of our keys a subkey to write".to_string()
+        });
+    }
+    match subkey {
+        None => Ok(writable
+            .iter()
+            .find_map(|(kp, ranges)| ranges.first().map(|&(first, _)| (first, *kp)))
+            .expect("some key has a range")),
+        Some(subkey) => writable
+            .iter()
+            .find(|(_, ranges)| ranges.iter().any(|&(first, last)| (first..=last).contains(&subkey)))
+            .map(|(kp, _)| (subkey, *kp))
+            .ok_or_else(|| format!("subkey {subkey} isn't ours to write; we can write {}", describe_ranges(&all))),
+    }
+}
+
+// A name for a record's owner and schema together (member ids included),
+// which is what its key is made from: a record opened by a mistyped key or
+// a wrong address book entry has another one. The whole hash, so it can't
+// be matched by trying owners until one fits.
+pub fn record_fingerprint(schema: &DHTSchema, owner: &PublicKey) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(owner.to_string().as_bytes());
+    hasher.update(&schema.compile());
+    hasher.finalize().to_hex().to_string()
+}
+
+// What a grant's keys make of a record with this schema and owner: the
+// key file's Record.* lines when it's written, and what they're checked
+// against when it's used.
+pub fn expectation(schema: &DHTSchema, owner: &PublicKey, grant: &Grant) -> Expected {
+    let can_write = |kp: &Option<KeyPair>| kp.as_ref().is_some_and(|kp| !writable_subkeys(schema, owner, &kp.key()).is_empty());
+    let role = if can_write(&grant.owner) {
+        Role::Owner
+    } else if can_write(&grant.writer) {
+        Role::Member
+    } else {
+        Role::Reader
+    };
+    // the range a `write` without a subkey lands in
+    let keys: Vec<&KeyPair> = grant.writer.iter().chain(grant.owner.iter()).collect();
+    let writer_subkeys = pick_writer(schema, owner, &keys, None).ok().and_then(|(first, kp)| {
+        writable_subkeys(schema, owner, &kp.key())
+            .into_iter()
+            .find(|&(from, _)| from == first)
+    });
+    Expected {
+        role: Some(role),
+        writer_subkeys,
+        fingerprint: Some(record_fingerprint(schema, owner)),
+    }
+}
+
+// Ok if the record is what the key file said it would be, or every
+// difference, one per line.
+pub fn check_expected(expected: &Expected, schema: &DHTSchema, owner: &PublicKey, grant: &Grant) -> Result<(), String> {
+    let found = expectation(schema, owner, grant);
+    let subkeys = |s: Option<(ValueSubkey, ValueSubkey)>| s.map_or("none".to_string(), |r| describe_ranges(&[r]));
+    let mut diff = Vec::new();
+    if let (Some(want), Some(got)) = (&expected.fingerprint, &found.fingerprint) {
+        if want != got {
+            diff.push(format!("  fingerprint     file has {want}, the record has {got} (another owner or schema)"));
+        }
+    }
+    if let (Some(want), Some(got)) = (expected.role, found.role) {
+        if want != got {
+            diff.push(format!("  role            file says {}, our keys make us {}", want.name(), got.name()));
+        }
+    }
+    if expected.writer_subkeys.is_some() && expected.writer_subkeys != found.writer_subkeys {
+        diff.push(format!(
+            "  writer subkeys  file says {}, we can write {}",
+            subkeys(expected.writer_subkeys),
+            subkeys(found.writer_subkeys)
+        ));
+    }
+    if diff.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("the record isn't what {} says it is:\n{}", crate::keyfile::FILE_NAME, diff.join("\n")))
+    }
+}
+
+// -------------------------------------------------------------------------
+// schema grow <src> [--owner-subkeys N] [--member-subkeys N] [--owner KEYPAIR]
+// -------------------------------------------------------------------------
+
+pub async fn grow(
+    args: &GrowArgs,
+    options: &Options,
+    config: &AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let owner_arg: Option<KeyPair> = match &args.owner {
+        Some(text) => Some(text.parse().map_err(|e| format!("--owner is not a keypair: {e}"))?),
+        None => None,
+    };
+
+    let data_dir = config.data_dir()?;
+    let mut book = ShortcodeBook::load(&data_dir)?;
+    let src_key = book.resolve(&args.source).map_err(|e| Kind::RecordNotFound.fail(e))?;
+
+    // the default node's namespace, for the keys it made the record with
+    let (veilid, rc) = start_node_in(options, config, &config.default_namespace).await?;
+
+    // If it has already been grown once, grow the latest one.
+    let records = RecordManager::new(rc.clone());
+    let old_record = records.open(src_key.clone(), owner_arg.clone()).await?;
+    let old = old_record.descriptor().clone();
+    let old_key = old.key();
+    let old_schema = old.schema();
+    let saved = match recovery::load_writers(&veilid, &old_key).await? {
+        Some(keys) => Some(keys),
+        None => recovery::load_writers(&veilid, &src_key).await?,
+    }
+    .and_then(|keys| keys.parse());
+
+    // Only the owner can write subkey 0, and without the pointer nobody finds the successor.
+    let old_owner = owner_arg
+        .or_else(|| saved.as_ref().map(|keys| keys.record_owner.clone()))
+        .or_else(|| old.owner_keypair())
+        .ok_or("need the old record's owner keypair to leave a forwarding pointer (pass --owner KEYPAIR)")?;
+    if old_owner.key() != old.owner() {
+        return Err(format!("{} is not the owner of {old_key}", old_owner.key()).into());
+    }
+
+    // ---------- work out the bigger schema ----------
+    let (old_o_cnt, old_members) = shape(&old_schema);
+    if old_o_cnt == 0 {
+        return Err("subkey 0 belongs to a member, so there's nowhere for the owner to leave a forwarding pointer".into());
+    }
+    let o_cnt = args.owner_subkeys.unwrap_or(old_o_cnt);
+    let m_cnts: Vec<u16> = old_members
+        .iter()
+        .map(|m| args.member_subkeys.unwrap_or(*m))
+        .collect();
+    if o_cnt < old_o_cnt || m_cnts.iter().zip(&old_members).any(|(new, old)| new < old) {
+        return Err("schema grow can only add subkeys, not remove them".into());
+    }
+    if o_cnt == old_o_cnt && m_cnts == old_members {
+        return Err("nothing to grow: pass --owner-subkeys and/or --member-subkeys".into());
+    }
+    let grown = match &old_schema {
+        DHTSchema::DFLT(_) => {
+            if args.member_subkeys.is_some() {
+                return Err("this record has a DFLT schema, it has no member subkeys".into());
+            }
+            DHTSchema::dflt(o_cnt)?
+        }
+        DHTSchema::SMPL(smpl) => DHTSchema::smpl(
+            o_cnt,
+            smpl.members()
+                .iter()
+                .zip(&m_cnts)
+                .map(|(m, m_cnt)| DHTSchemaSMPLMember {
+                    m_key: m.m_key.clone(),
+                    m_cnt: *m_cnt,
+                })
+                .collect(),
+        )?,
+    };
+    grown.validate()?;
+    println!("Growing {old_key}: {old_schema:?} -> {grown:?}");
+
+    // ---------- read everything before changing anything ----------
+    let mut values = Vec::new();
+    let bar = progress::subkeys(u64::from(old_schema.max_subkey()) + 1, "Reading");
+    for subkey in 0..=old_schema.max_subkey() {
+        match rc.get_dht_value(old_key.clone(), subkey, true).await? {
+            Some(v) => values.push((subkey, v.data().to_vec())),
+            None => bar.suspend(|| println!("  subkey {subkey}: empty, nothing to move")),
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+    // the roster and the rest would still point at the old layout
+    for (subkey, data) in &mut values {
+        if *subkey == METADATA_SUBKEY {
+            if let Some(meta) = RecordMetadata::decode(data) {
+                *data = grown_metadata(&meta, &old_schema, &grown).encode();
+            }
+        }
+    }
+
+    // ---------- successor ----------
+    // the same owner and members, so nobody's key stops working
+    let new_key = if rc.is_dry_run() {
+        rc.plan_create_dht_record(grown.clone(), old_owner.clone()).await?
+    } else {
+        let desc = progress::spin(
+            "Creating the grown record",
+            rc.create_dht_record(CRYPTO_KIND_VLD0, grown.clone(), Some(old_owner.clone())),
+        )
+        .await?;
+        records.adopt(desc, Some(old_owner.clone())).await.key()
+    };
+    let mut held = vec![old_owner.clone()];
+    if let Some(keys) = &saved {
+        held.push(keys.member.clone());
+        held.extend(keys.drop.clone());
+    }
+    // and kept, so the default node can write to the successor after a restart
+    if !rc.is_dry_run() {
+        let member = saved.as_ref().map_or(&old_owner, |keys| &keys.member);
+        let drop = saved.as_ref().and_then(|keys| keys.drop.as_ref());
+        recovery::save_writers(&veilid, &new_key, &WriterKeys::new(&old_owner, member, drop)).await?;
+    }
+
+    // A value that doesn't make it across is fatal: the pointer would hide the only copy.
+    let bar = progress::subkeys(values.len() as u64, "Moving");
+    for (subkey, data) in values {
+        let to = new_subkey(&old_schema, &grown, subkey);
+        let writer = held
+            .iter()
+            .find(|kp| writable_subkeys(&grown, &old_owner.key(), &kp.key()).iter().any(|&(from, last)| (from..=last).contains(&to)));
+        let Some(writer) = writer else {
+            bar.suspend(|| println!("  subkey {subkey}: written by a key we don't hold; its writer can write it again as subkey {to}"));
+            bar.inc(1);
+            continue;
+        };
+        let opts = SetDHTValueOptions {
+            writer: Some(writer.clone()),
+            allow_offline: None,
+        };
+        rc.set_dht_value(new_key.clone(), to, data, Some(opts))
+            .await
+            .map_err(|e| format!("moving subkey {subkey} -> {to} failed, old record left as it was: {e}"))?;
+        bar.suspend(|| println!("  subkey {subkey} -> {to}"));
+        bar.inc(1);
+    }
+    bar.finish();
+
+    // ---------- forwarding pointer ----------
+    let opts = SetDHTValueOptions {
+        writer: Some(old_owner.clone()),
+        allow_offline: None,
+    };
+    rc.set_dht_value(old_key.clone(), 0, forward_pointer(&new_key), Some(opts))
+        .await?;
+    drop(old_record);
+    records.close_all().await;
+
+    println!();
+    println!("{old_key} now forwards to the grown record:");
+    let code = if rc.is_dry_run() {
+        crate::shortcode::shortcode(&new_key)
+    } else {
+        book.remember(&new_key)?
+    };
+    println!("RecordKey = {new_key}");
+    let fingerprint = record_fingerprint(&grown, &old_owner.key());
+    println!("ShortCode = {}", crate::shortcode::share_string(&code, &fingerprint));
+    println!("The owner and member keys are the ones the old record had; every writer keeps its own.");
+
+    veilid.shutdown().await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::RosterEntry;
+
+    #[test]
+    fn writers_get_the_subkeys_their_schema_gives_them() {
+        let owner = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let first = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let second = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let stranger = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let schema = DHTSchema::smpl(
+            2,
+            vec![
+                DHTSchemaSMPLMember { m_key: member_id(&first.key()), m_cnt: 2 },
+                DHTSchemaSMPLMember { m_key: member_id(&second.key()), m_cnt: 3 },
+            ],
+        )
+        .unwrap();
+        let owner_key = owner.key();
+
+        assert_eq!(writable_subkeys(&schema, &owner_key, &owner.key()), vec![(0, 1)]);
+        assert_eq!(writable_subkeys(&schema, &owner_key, &second.key()), vec![(4, 6)]);
+        assert!(writable_subkeys(&schema, &owner_key, &stranger.key()).is_empty());
+
+        // the second member's first subkey, not a hardcoded 2
+        assert_eq!(pick_writer(&schema, &owner_key, &[&second, &owner], None), Ok((4, &second)));
+        assert_eq!(pick_writer(&schema, &owner_key, &[&second, &owner], Some(1)), Ok((1, &owner)));
+        assert_eq!(
+            pick_writer(&schema, &owner_key, &[&second], Some(2)).unwrap_err(),
+            "subkey 2 isn't ours to write; we can write 4..=6"
+        );
+        assert!(pick_writer(&schema, &owner_key, &[&stranger], None).unwrap_err().contains("none of our keys"));
+        assert!(pick_writer(&schema, &owner_key, &[], None).unwrap_err().contains("only read"));
+    }
+
+    #[test]
+    fn grown_records_keep_their_members_and_shift_their_subkeys() {
+        let owner = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let member = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let shaped = |o_cnt, m_cnt| {
+            DHTSchema::smpl(o_cnt, vec![DHTSchemaSMPLMember { m_key: member_id(&member.key()), m_cnt }]).unwrap()
+        };
+        let (old, grown) = (shaped(2, 2), shaped(4, 3));
+        assert_eq!(new_subkey(&old, &grown, 1), 1);
+        assert_eq!(new_subkey(&old, &grown, 2), 4);
+        assert_eq!(new_subkey(&old, &grown, 3), 5);
+        // the member's key still writes its range, now a bigger one further on
+        assert_eq!(writable_subkeys(&grown, &owner.key(), &member.key()), vec![(4, 6)]);
+    }
+
+    #[test]
+    fn the_metadata_block_follows_the_grown_layout() {
+        let first = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let second = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let shaped = |o_cnt, m_cnt| {
+            let member = |kp: &KeyPair| DHTSchemaSMPLMember { m_key: member_id(&kp.key()), m_cnt };
+            DHTSchema::smpl(o_cnt, vec![member(&first), member(&second)]).unwrap()
+        };
+        let (old, grown) = (shaped(2, 2), shaped(3, 4));
+        let entry = |name: &str, first_subkey, last_subkey| RosterEntry {
+            name: name.to_string(),
+            key: String::new(),
+            first_subkey,
+            last_subkey,
+            fields: Default::default(),
+        };
+        let mut meta = RecordMetadata::new(
+            "grown",
+            &old,
+            vec![entry("record-owner", 0, 1), entry("first", 2, 3), entry("second", 4, 5)],
+        );
+        meta.members[2].fields.insert("status".to_string(), 5);
+        meta.payload_types.insert(4, "text".to_string());
+
+        let moved = grown_metadata(&meta, &old, &grown);
+        assert_eq!(moved.schema, format!("{grown:?}"));
+        let ranges: Vec<_> = moved.members.iter().map(|m| (m.first_subkey, m.last_subkey)).collect();
+        assert_eq!(ranges, vec![(0, 2), (3, 6), (7, 10)]);
+        assert_eq!(moved.members[2].fields["status"], 8);
+        assert_eq!(moved.payload_types.keys().collect::<Vec<_>>(), vec![&7]);
+    }
+
+    #[test]
+    fn key_file_expectations_are_checked_against_the_record() {
+        let owner = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let member = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let shaped = |m_cnt| {
+            DHTSchema::smpl(2, vec![DHTSchemaSMPLMember { m_key: member_id(&member.key()), m_cnt }]).unwrap()
+        };
+        let grant = Grant {
+            writer: Some(member.clone()),
+            writer_subkeys: Some((2, 3)),
+            ..Grant::default()
+        };
+        let expected = expectation(&shaped(2), &owner.key(), &grant);
+        assert_eq!(expected.role, Some(Role::Member));
+        assert_eq!(expected.writer_subkeys, Some((2, 3)));
+        assert_eq!(expected.fingerprint.as_ref().map(String::len), Some(64));
+        assert_eq!(check_expected(&expected, &shaped(2), &owner.key(), &grant), Ok(()));
+
+        // a record of another shape, or the same shape with another owner
+        let diff = check_expected(&expected, &shaped(4), &owner.key(), &grant).unwrap_err();
+        assert!(diff.contains("  fingerprint     file has "), "{diff}");
+        assert!(diff.contains("  writer subkeys  file says 2..=3, we can write 2..=5"), "{diff}");
+        let stranger = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap();
+        let diff = check_expected(&expected, &shaped(2), &stranger.key(), &grant).unwrap_err();
+        assert!(diff.contains("  fingerprint     file has "), "{diff}");
+        assert!(!diff.contains("role "), "{diff}");
+
+        // the member key has no place in the record
+        let diff = check_expected(&expected, &DHTSchema::dflt(2).unwrap(), &owner.key(), &grant).unwrap_err();
+        assert!(diff.contains("  role            file says member, our keys make us reader"), "{diff}");
+        assert!(diff.contains("we can write none"), "{diff}");
+    }
+}
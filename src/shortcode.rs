@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use veilid_core::*;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Human-friendly shortcodes for record keys.
+//
+//	A record key is a long base64 string that is painful to copy between
+//	consoles. A shortcode is four words (e.g. "harp-otter-coal-lime") taken
+//	from a hash of the key. Four words can't hold a whole key, so going back
+//	from a code to the key uses a small address book (shortcodes.json in the
+//	data folder) that every node fills in as it sees records. Two consoles
+//	sharing a data folder can therefore hand a record over by code alone.
+//
+//	What gets handed over can carry a fingerprint of the record's owner and
+//	schema after a '#' ("harp-otter-coal-lime#<64 hex digits>", or the full
+//	key with one), so the joining node can tell it opened the record it was
+//	meant to and not one a typo or a stale address book led it to.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const CODE_WORDS: usize = 4;
+
+const WORDS: [&str; 256] = [
+    "able", "acid", "aged", "also", "arch", "army", "atom", "aunt", "away", "axis", "baby",
+    "back", "bald", "band", "bank", "barn", "base", "bath", "bead", "beam", "bean", "bear",
+    "bell", "belt", "bend", "best", "bike", "bird", "bite", "blue", "boat", "body", "bold",
+    "bolt", "bone", "book", "boot", "bowl", "brave", "bread", "brick", "bride", "brook",
+    "brush", "cabin", "cable", "cake", "calm", "camel", "camp", "canal", "candy", "cape",
+    "card", "cargo", "carp", "cart", "cave", "cedar", "chain", "chalk", "charm", "chess",
+    "chief", "chin", "city", "clam", "clay", "cliff", "clock", "cloud", "clown", "coal",
+    "coast", "coat", "cobra", "coin", "comet", "coral", "corn", "couch", "crab", "crane",
+    "crow", "crown", "cube", "curl", "dance", "dart", "dawn", "deer", "delta", "desk", "dial",
+    "dice", "dish", "dock", "dome", "door", "dove", "dragon", "drum", "duck", "dune", "eagle",
+    "earth", "echo", "elbow", "elder", "elm", "ember", "engine", "fable", "face", "falcon",
+    "farm", "feast", "fern", "field", "fig", "film", "fire", "fish", "flag", "flame", "flute",
+    "foam", "fog", "forest", "fork", "fox", "frog", "frost", "fruit", "gate", "gecko", "gem",
+    "ghost", "giant", "gift", "glass", "globe", "glove", "goat", "gold", "grape", "grass",
+    "gull", "hammer", "harbor", "harp", "hawk", "hazel", "heart", "hill", "honey", "hook",
+    "horn", "horse", "house", "ice", "idea", "igloo", "inch", "iris", "iron", "island", "ivory",
+    "jacket", "jade", "jam", "jar", "jazz", "jelly", "jewel", "judge", "juice", "jungle",
+    "kayak", "kettle", "key", "king", "kite", "kiwi", "knee", "knife", "lake", "lamp", "lark",
+    "lava", "leaf", "lemon", "lens", "lily", "lime", "lion", "llama", "lobster", "lotus",
+    "lunar", "lynx", "magnet", "mango", "maple", "marble", "market", "mask", "meadow", "melon",
+    "mint", "mirror", "monk", "moon", "moose", "moss", "moth", "mouse", "mule", "nail", "nest",
+    "night", "noble", "north", "nut", "oak", "oasis", "ocean", "olive", "onion", "opal",
+    "orbit", "otter", "owl", "paint", "palm", "panda", "paper", "parrot", "peach", "pearl",
+    "pepper", "piano", "pilot", "pine", "planet", "plum", "pond", "poppy", "prism", "quail",
+    "quartz", "queen", "quill", "rabbit", "radar", "rain",
+];
+
+// The shortcode for a record key. Always the same for the same key.
+pub fn shortcode(record_key: &RecordKey) -> String {
+    let hash = blake3::hash(record_key.to_string().as_bytes());
+    hash.as_bytes()[..CODE_WORDS]
+        .iter()
+        .map(|b| WORDS[*b as usize])
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// "code#fingerprint": what the default node prints for others to join with.
+pub fn share_string(code_or_key: &str, fingerprint: &str) -> String {
+    format!("{code_or_key}#{fingerprint}")
+}
+
+// Split what was typed into the code or key, and the fingerprint if it had one.
+pub fn split_share(input: &str) -> (&str, Option<&str>) {
+    match input.trim().rsplit_once('#') {
+        Some((code, fingerprint)) if !fingerprint.trim().is_empty() => (code.trim(), Some(fingerprint.trim())),
+        Some((code, _)) => (code.trim(), None),
+        None => (input.trim(), None),
+    }
+}
+
+// Accept "Harp Otter coal-lime" etc. and tidy it into "harp-otter-coal-lime".
+// Returns None if it isn't made of our words.
+pub fn normalize(input: &str) -> Option<String> {
+    let words: Vec<String> = input
+        .split(['-', ' ', '_'])
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_ascii_lowercase())
+        .collect();
+    if words.len() != CODE_WORDS || !words.iter().all(|w| WORDS.contains(&w.as_str())) {
+        return None;
+    }
+    Some(words.join("-"))
+}
+
+// Every record this data folder has seen, by shortcode.
+pub struct ShortcodeBook {
+    path: PathBuf,
+    entries: BTreeMap<String, String>,
+}
+
+impl ShortcodeBook {
+    pub fn load(data_dir: &Path) -> io::Result<ShortcodeBook> {
+        let path = data_dir.join("shortcodes.json");
+        let entries = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(ShortcodeBook { path, entries })
+    }
+
+    // Add a record to the book (saved straight away) and hand back its code.
+    pub fn remember(&mut self, record_key: &RecordKey) -> io::Result<String> {
+        let code = shortcode(record_key);
+        if self.entries.get(&code) != Some(&record_key.to_string()) {
+            self.entries.insert(code.clone(), record_key.to_string());
+            fs::write(&self.path, serde_json::to_string_pretty(&self.entries)?)?;
+        }
+        Ok(code)
+    }
+
+    // (shortcode, record key) for every record in the book
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(c, k)| (c.as_str(), k.as_str()))
+    }
+
+    // Turn whatever the user typed (a full record key or a shortcode) into a record key.
+    // A fingerprint on the end is dropped; only the alt node's join checks it.
+    pub fn resolve(&self, input: &str) -> Result<RecordKey, String> {
+        let (input, _) = split_share(input);
+        if let Ok(record_key) = input.parse::<RecordKey>() {
+            return Ok(record_key);
+        }
+        let code = normalize(input)
+            .ok_or_else(|| format!("'{input}' is neither a record key nor a shortcode"))?;
+        let key = self
+            .entries
+            .get(&code)
+            .ok_or_else(|| format!("shortcode '{code}' is not in {}", self.path.to_string_lossy()))?;
+        key.parse()
+            .map_err(|e| format!("shortcode '{code}' points at a bad key: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> RecordKey {
+        RecordKey::new(
+            CRYPTO_KIND_VLD0,
+            BareRecordKey::new(BareOpaqueRecordKey::new(&[byte; 32]), None),
+        )
+    }
+
+    #[test]
+    fn codes_are_tidied_or_refused() {
+        assert_eq!(normalize("Harp Otter coal-lime").as_deref(), Some("harp-otter-coal-lime"));
+        assert_eq!(normalize(" harp__otter--coal lime ").as_deref(), Some("harp-otter-coal-lime"));
+        assert_eq!(normalize("harp-otter-coal"), None);
+        assert_eq!(normalize("harp-otter-coal-lime-moss"), None);
+        assert_eq!(normalize("harp-otter-coal-zebra"), None);
+
+        let code = shortcode(&key(1));
+        assert_eq!(code, shortcode(&key(1)));
+        assert_ne!(code, shortcode(&key(2)));
+        assert_eq!(normalize(&code.to_uppercase().replace('-', " ")), Some(code));
+    }
+
+    #[test]
+    fn fingerprints_split_off_the_end() {
+        assert_eq!(split_share(" harp-otter-coal-lime#abc1 "), ("harp-otter-coal-lime", Some("abc1")));
+        assert_eq!(split_share("harp-otter-coal-lime#"), ("harp-otter-coal-lime", None));
+        assert_eq!(split_share("harp-otter-coal-lime"), ("harp-otter-coal-lime", None));
+        let shared = share_string("harp-otter-coal-lime", "abc1");
+        assert_eq!(split_share(&shared), ("harp-otter-coal-lime", Some("abc1")));
+    }
+
+    #[test]
+    fn codes_resolve_through_the_book() {
+        let dir = std::env::temp_dir().join(format!("shortcode-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut book = ShortcodeBook::load(&dir).unwrap();
+        let code = book.remember(&key(3)).unwrap();
+        assert_eq!(code, shortcode(&key(3)));
+
+        // a second node sharing the data folder finds it by code, fingerprint or not
+        let other = ShortcodeBook::load(&dir).unwrap();
+        assert_eq!(other.entries().count(), 1);
+        assert_eq!(other.resolve(&code), Ok(key(3)));
+        assert_eq!(other.resolve(&share_string(&code.replace('-', " "), "abc1")), Ok(key(3)));
+        // a full key doesn't need the book at all
+        assert_eq!(other.resolve(&key(4).to_string()), Ok(key(4)));
+
+        assert!(other.resolve(&shortcode(&key(5))).unwrap_err().contains("is not in"));
+        assert!(other.resolve("not a code").unwrap_err().contains("neither a record key nor a shortcode"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
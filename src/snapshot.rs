@@ -0,0 +1,210 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::envelope;
+use crate::shortcode;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Record snapshots: every subkey's value, seq and writer at one moment,
+//	saved as JSON under snapshots/ in the data folder.
+//
+//	Two snapshots of the same record show what changed in between (has a
+//	write reached this node yet?), and a snapshot of a source and its clone
+//	(or a grown record) shows whether everything made it across.
+//
+//	Snapshots are named <shortcode>-<unix ms>, e.g.
+//	harp-otter-coal-lime-1718000000000.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const SNAPSHOT_DIR: &str = "snapshots";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SubkeyState {
+    pub subkey: ValueSubkey,
+    pub seq: Option<u32>,
+    pub writer: String,
+    // the raw value, base64
+    pub data: String,
+}
+
+impl SubkeyState {
+    pub fn new(subkey: ValueSubkey, value: &ValueData) -> SubkeyState {
+        SubkeyState {
+            subkey,
+            seq: value.seq().to_option(),
+            writer: value.writer().to_string(),
+            data: BASE64.encode(value.data()),
+        }
+    }
+
+    // The value as a read would print it.
+    pub fn display(&self) -> String {
+        match BASE64.decode(&self.data) {
+            Ok(data) => envelope::display_value(&data),
+            Err(_) => "<bad snapshot data>".to_string(),
+        }
+    }
+
+    fn seq_text(&self) -> String {
+        self.seq.map_or("?".to_string(), |s| s.to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snapshot {
+    pub record: String,
+    pub taken_ms: u64,
+    pub max_subkey: ValueSubkey,
+    // subkeys with a value; the rest were empty
+    pub subkeys: Vec<SubkeyState>,
+    // subkeys we couldn't read at all, so we don't know
+    pub unreadable: Vec<ValueSubkey>,
+}
+
+impl Snapshot {
+    pub fn name(&self) -> String {
+        match self.record.parse::<RecordKey>() {
+            Ok(key) => format!("{}-{}", shortcode::shortcode(&key), self.taken_ms),
+            Err(_) => format!("snapshot-{}", self.taken_ms),
+        }
+    }
+
+    // Write it to snapshots/<name>.json and give back the path.
+    pub fn save(&self, data_dir: &Path) -> io::Result<PathBuf> {
+        let dir = data_dir.join(SNAPSHOT_DIR);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.json", self.name()));
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    // By name (looked for under snapshots/) or by path.
+    pub fn load(data_dir: &Path, name: &str) -> Result<Snapshot, String> {
+        let named = data_dir.join(SNAPSHOT_DIR).join(format!("{name}.json"));
+        let path = if named.exists() { named } else { PathBuf::from(name) };
+        let text = fs::read_to_string(&path).map_err(|e| format!("can't read snapshot '{name}': {e}"))?;
+        serde_json::from_str(&text).map_err(|e| format!("{} is not a snapshot: {e}", path.to_string_lossy()))
+    }
+
+    fn get(&self, subkey: ValueSubkey) -> Option<&SubkeyState> {
+        self.subkeys.iter().find(|s| s.subkey == subkey)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SubkeyDiff {
+    Added(SubkeyState),
+    Removed(SubkeyState),
+    Changed { before: SubkeyState, after: SubkeyState },
+    // unreadable in one snapshot or the other, so can't say
+    Unknown(ValueSubkey),
+}
+
+// What changed from `a` to `b`, by subkey. Unchanged subkeys are left out.
+pub fn diff(a: &Snapshot, b: &Snapshot) -> Vec<SubkeyDiff> {
+    let mut out = Vec::new();
+    for subkey in 0..=a.max_subkey.max(b.max_subkey) {
+        if a.unreadable.contains(&subkey) || b.unreadable.contains(&subkey) {
+            out.push(SubkeyDiff::Unknown(subkey));
+            continue;
+        }
+        match (a.get(subkey), b.get(subkey)) {
+            (None, Some(after)) => out.push(SubkeyDiff::Added(after.clone())),
+            (Some(before), None) => out.push(SubkeyDiff::Removed(before.clone())),
+            (Some(before), Some(after)) if before != after => out.push(SubkeyDiff::Changed {
+                before: before.clone(),
+                after: after.clone(),
+            }),
+            _ => {}
+        }
+    }
+    out
+}
+
+impl std::fmt::Display for SubkeyDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubkeyDiff::Added(s) => write!(f, "+ subkey {} (seq {}): {}", s.subkey, s.seq_text(), s.display()),
+            SubkeyDiff::Removed(s) => write!(f, "- subkey {} (seq {}): {}", s.subkey, s.seq_text(), s.display()),
+            SubkeyDiff::Changed { before, after } => {
+                write!(f, "~ subkey {} seq {} -> {}", after.subkey, before.seq_text(), after.seq_text())?;
+                if before.writer != after.writer {
+                    write!(f, ", writer {} -> {}", before.writer, after.writer)?;
+                }
+                if before.data != after.data {
+                    write!(f, "\n    was: {}\n    now: {}", before.display(), after.display())?;
+                }
+                Ok(())
+            }
+            SubkeyDiff::Unknown(subkey) => write!(f, "? subkey {subkey}: unreadable in one of the snapshots"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(subkey: ValueSubkey, seq: u32, text: &str) -> SubkeyState {
+        SubkeyState {
+            subkey,
+            seq: Some(seq),
+            writer: "VLD0:writer".to_string(),
+            data: BASE64.encode(envelope::Envelope::text(text).encode()),
+        }
+    }
+
+    fn snapshot(subkeys: Vec<SubkeyState>, unreadable: Vec<ValueSubkey>) -> Snapshot {
+        Snapshot {
+            record: "not a key".to_string(),
+            taken_ms: 1,
+            max_subkey: 4,
+            subkeys,
+            unreadable,
+        }
+    }
+
+    #[test]
+    fn diff_shows_only_what_changed() {
+        let a = snapshot(vec![state(0, 1, "same"), state(1, 1, "old"), state(2, 3, "gone")], vec![]);
+        let b = snapshot(vec![state(0, 1, "same"), state(1, 2, "new"), state(3, 0, "fresh")], vec![4]);
+
+        let changes = diff(&a, &b);
+        assert_eq!(
+            changes,
+            vec![
+                SubkeyDiff::Changed {
+                    before: state(1, 1, "old"),
+                    after: state(1, 2, "new"),
+                },
+                SubkeyDiff::Removed(state(2, 3, "gone")),
+                SubkeyDiff::Added(state(3, 0, "fresh")),
+                SubkeyDiff::Unknown(4),
+            ]
+        );
+        assert_eq!(changes[0].to_string(), "~ subkey 1 seq 1 -> 2\n    was: old\n    now: new");
+        assert!(diff(&a, &a).is_empty());
+    }
+
+    #[test]
+    fn saves_and_loads_by_name() {
+        let dir = std::env::temp_dir().join(format!("snapshot-test-{}", std::process::id()));
+        let snap = snapshot(vec![state(0, 7, "hi")], vec![]);
+        let path = snap.save(&dir).unwrap();
+
+        let by_name = Snapshot::load(&dir, &snap.name()).unwrap();
+        let by_path = Snapshot::load(&dir, &path.to_string_lossy()).unwrap();
+        assert_eq!(by_name.subkeys, snap.subkeys);
+        assert_eq!(by_path.subkeys[0].display(), "hi");
+        assert!(Snapshot::load(&dir, "nope").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
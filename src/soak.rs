@@ -0,0 +1,482 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+use veilid_core::*;
+
+use crate::audit::{log_file_name, now_ms, AuditLog};
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::control;
+use crate::dht::Dht;
+use crate::envelope::Envelope;
+use crate::health::{self, Health};
+use crate::exit::Kind;
+use crate::keyfile::{self, KeyFileError};
+use crate::payloads::Registry;
+use crate::ready::ReadyWhen;
+use crate::systemd::{self, Journal};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Soak test mode: `soak --hours N [--role default|alt]`
+//	Daemon mode:    `daemon [--role default|alt]`
+//
+//	Runs one role for a long time at a gentle pace (the daemon is the same
+//	thing with no end time, made for running in a container or as a service:
+//	no prompts, and it stops cleanly on SIGTERM as well as Ctrl+C). The default role creates a
+//	record and keeps writing to it (reading each write back), the alt role
+//	joins that record, watches it and keeps reading it. Run both in two
+//	consoles; they find each other through KEY_FILE, not owner_keys.txt,
+//	so a soak never clobbers the interactive nodes' key file. Errors and attachment state changes are logged as they happen,
+//	and a summary is printed every hour, so slow leaks, dying watches and a
+//	flapping network connection show up over a long run.
+//
+//	A daemon can be told to detach and attach again, asked how it's doing,
+//	and read from or written to, from another console (`detach`, `attach`,
+//	`status`, `get`, `set`, see control.rs). Its rounds wait while it's
+//	detached.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// How often each role does its read/write round.
+const OP_INTERVAL: Duration = Duration::from_secs(30);
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+// in the data folder; the interactive alt node can join it with --key-file
+const KEY_FILE: &str = "soak_owner_keys.txt";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SoakRole {
+    Default,
+    Alt,
+    // only an interactive node (--mode member, see member.rs), never soaked
+    Member,
+}
+
+impl SoakRole {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SoakRole::Default => "default",
+            SoakRole::Alt => "alt",
+            SoakRole::Member => "member",
+        }
+    }
+}
+
+// What the update callback tells the soak loop about.
+enum SoakEvent {
+    // the state, and whether it meets ready_when (see ready.rs)
+    Attachment(AttachmentState, bool),
+    ValueChange { subkeys: ValueSubkeyRangeSet, count: u32 },
+}
+
+#[derive(Default)]
+struct Counters {
+    writes_ok: u64,
+    writes_failed: u64,
+    reads_ok: u64,
+    reads_failed: u64,
+    mismatched_reads: u64,
+    value_changes: u64,
+    watch_deaths: u64,
+    watch_renewals: u64,
+    state_changes: u64,
+}
+
+struct SoakLog {
+    file: File,
+    // --journald
+    journal: Option<Journal>,
+    // sent with every journal entry
+    fields: Vec<(&'static str, String)>,
+}
+
+impl SoakLog {
+    // Every line goes to the console and to <mode>_<role>.log (and the journal with --journald)
+    fn line(&mut self, msg: &str) {
+        let line = format!("[{}] {msg}", now_ms());
+        println!("{line}");
+        let _ = writeln!(self.file, "{line}");
+        if let Some(journal) = &self.journal {
+            journal.send(systemd::PRIORITY_INFO, msg, &self.fields);
+        }
+    }
+}
+
+// `hours` is None for the daemon, which runs until it's told to stop.
+pub async fn run(
+    hours: Option<f64>,
+    role: SoakRole,
+    options: &Options,
+    config: &AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    let mode = if hours.is_some() { "soak" } else { "daemon" };
+
+    let mut log = SoakLog {
+        file: OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(data_dir.join(format!("{mode}_{}.log", role.name())))?,
+        journal: if options.journald {
+            Some(Journal::open(&config.program_name.replace(' ', "-").to_lowercase())?)
+        } else {
+            None
+        },
+        fields: vec![
+            ("VEILID_MODE", mode.to_string()),
+            ("VEILID_ROLE", role.name().to_string()),
+        ],
+    };
+    match hours {
+        Some(hours) => log.line(&format!("soak starting: role={} hours={hours}", role.name())),
+        None => log.line(&format!("daemon starting: role={}", role.name())),
+    }
+
+    let health = Arc::new(Health::new());
+    if let Some(addr) = options.health_addr {
+        health::serve(addr, health.clone()).await?;
+    }
+
+    // For an alt soak, the record has to exist already.
+    let joined_key = match role {
+        SoakRole::Alt | SoakRole::Member => Some(
+            keyfile::load_file(&data_dir.join(KEY_FILE))
+                .map_err(|e| match e.downcast_ref::<KeyFileError>() {
+                    Some(KeyFileError::Missing(_)) => Kind::RecordNotFound.fail(e.to_string()),
+                    _ => e,
+                })?
+                .record_key,
+        ),
+        SoakRole::Default => None,
+    };
+
+    let ready_when = ReadyWhen::from_config(config)?;
+    let (event_tx, event_rx) = flume::unbounded::<SoakEvent>();
+    let update_callback = Arc::new(move |update: VeilidUpdate| match update {
+        VeilidUpdate::Attachment(att) => {
+            let _ = event_tx.send(SoakEvent::Attachment(att.state, ready_when.is_met(&att)));
+        }
+        VeilidUpdate::ValueChange(change) => {
+            let _ = event_tx.send(SoakEvent::ValueChange {
+                subkeys: change.subkeys.clone(),
+                count: change.count,
+            });
+        }
+        _ => {}
+    });
+
+    let namespace = format!("veilid-example-{mode}-{}", role.name());
+    let _running = crate::preflight::run(config, &data_dir, &format!("{mode} {}", role.name()), &namespace)?;
+    let veilid_config = crate::node::node_config(config, &data_dir, &namespace)?;
+    let veilid = veilid_core::api_startup(update_callback, veilid_config)
+        .await
+        .map_err(crate::store::startup_failed)?;
+    veilid.attach().await?;
+    // the daemon takes attach/detach/status/get/set from the command line (see control.rs)
+    let detached = Arc::new(AtomicBool::new(false));
+    let target = Arc::new(OnceLock::new());
+    let _control = match hours {
+        None => Some(control::serve(&data_dir, role, veilid.clone(), detached.clone(), target.clone()).await?),
+        Some(_) => None,
+    };
+
+    let mut counters = Counters::default();
+    let mut last_state: Option<AttachmentState> = None;
+
+    // Wait for the first fully-ready attachment, logging the states on the way.
+    log.line(&format!("waiting for {}...", ready_when.describe()));
+    // only polled when ready_timeout_secs is set
+    let give_up = tokio::time::sleep(config.ready_timeout().unwrap_or(OP_INTERVAL));
+    tokio::pin!(give_up);
+    loop {
+        tokio::select! {
+            event = event_rx.recv_async() => {
+                if let SoakEvent::Attachment(state, ready) = event? {
+                    note_state(&mut log, &mut counters, &mut last_state, state);
+                    health.set_attached(ready);
+                    if ready {
+                        log.line("attached");
+                        break;
+                    }
+                }
+            }
+            // attaching can take a while, that's not the same as being stuck
+            _ = tokio::time::sleep(OP_INTERVAL) => health.beat(),
+            _ = &mut give_up, if config.ready_timeout().is_some() => {
+                if config.ready_timeout_exit {
+                    log.line(&format!("no {} after ready_timeout_secs, giving up", ready_when.describe()));
+                    return Err(Kind::AttachTimeout.fail(format!("the node didn't reach {}", ready_when.describe())));
+                }
+                log.line(&format!("warning: no {} after ready_timeout_secs, carrying on anyway", ready_when.describe()));
+                break;
+            }
+        }
+    }
+
+    if let Some(every) = options.chaos.as_ref().and_then(|c| c.reattach_every) {
+        crate::chaos::spawn_reattach_cycles(veilid.clone(), every);
+    }
+
+    let audit = Arc::new(AuditLog::open(&data_dir.join(log_file_name(&format!("{mode}_{}", role.name()))))?);
+    let routing = crate::node::RoutingContextHandle::new(&veilid, config)?;
+    let rc = Dht::new(routing.get(), Some(audit), options.dry_run, options.chaos.clone())
+        .with_health(health.clone());
+
+    // ---------- set up the record for this role ----------
+    let mut writer: Option<SetDHTValueOptions> = None;
+    let record_key = match joined_key {
+        None => {
+            let owner_kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0)?;
+            let (owner_public, _) = owner_kp.clone().into_split();
+            let member_id = veilid.generate_member_id(&owner_public)?;
+            let schema = DHTSchema::smpl(
+                config.owner_subkeys,
+                vec![DHTSchemaSMPLMember {
+                    m_key: member_id.into_value(),
+                    m_cnt: config.member_subkeys,
+                }],
+            )?;
+            let desc = rc.create_dht_record(CRYPTO_KIND_VLD0, schema, None).await?;
+            let record_key = desc.key();
+
+            // leave the key where an alt soak can find it
+            // (the namespace is left out, soak nodes pick their own)
+            keyfile::save_file(&data_dir.join(KEY_FILE), &keyfile::KeyFile {
+                record_key: record_key.clone(),
+                shortcode: None,
+                alt: keyfile::JoinPreset {
+                    namespace: None,
+                    ..config.join_preset()
+                },
+                grant: Default::default(),
+                expect: crate::schema::expectation(&desc.schema(), &desc.owner(), &Default::default()),
+            })?;
+
+            writer = Some(SetDHTValueOptions {
+                writer: Some(owner_kp),
+                allow_offline: None,
+            });
+            record_key
+        }
+        Some(record_key) => {
+            let user_kp = Crypto::generate_keypair(CRYPTO_KIND_VLD0)?;
+            let record_key = rc.open_following(record_key, Some(user_kp)).await?.key();
+            let active = rc
+                .watch_dht_values(record_key.clone(), None, None, None)
+                .await?;
+            log.line(&format!("watch active: {active}"));
+            record_key
+        }
+    };
+    health.set_record_open(true);
+    let _ = target.set(control::Target {
+        rc: rc.clone(),
+        record_key: record_key.clone(),
+        writer: writer.clone(),
+        registry: Registry::from_config(config)?,
+    });
+    log.fields.push(("VEILID_RECORD", record_key.to_string()));
+    log.line(&format!("soaking record {record_key}"));
+    systemd::notify(&format!("READY=1\nSTATUS=attached, record {record_key}"));
+    let watchdog = systemd::watchdog_enabled();
+
+    // ---------- the soak loop ----------
+    let started = Instant::now();
+    let deadline = hours.map(|h| started + Duration::from_secs_f64(h * 3600.0));
+    let mut op_tick = tokio::time::interval(OP_INTERVAL);
+    let mut summary_tick = tokio::time::interval(SUMMARY_INTERVAL);
+    summary_tick.tick().await; // the first tick fires straight away
+    let mut round: u64 = 0;
+    let mut watch_dead = false;
+    // made once: a fresh future per pass would miss a Ctrl+C that lands
+    // while another arm is being handled
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        health.beat();
+        if watchdog {
+            systemd::notify("WATCHDOG=1");
+        }
+        tokio::select! {
+            signal = &mut shutdown => {
+                log.line(&format!("{signal} received, stopping {mode}"));
+                break;
+            }
+            _ = sleep_until(deadline) => {
+                log.line("soak duration reached");
+                break;
+            }
+            _ = summary_tick.tick() => {
+                let summary = summary(&counters, started);
+                systemd::notify(&format!("STATUS={summary}"));
+                log.line(&summary);
+            }
+            event = event_rx.recv_async() => {
+                match event? {
+                    SoakEvent::Attachment(state, ready) => {
+                        note_state(&mut log, &mut counters, &mut last_state, state);
+                        health.set_attached(ready);
+                    }
+                    SoakEvent::ValueChange { subkeys, count } => {
+                        if subkeys.is_empty() || count == 0 {
+                            counters.watch_deaths += 1;
+                            watch_dead = true;
+                            log.line("watch died");
+                        } else {
+                            counters.value_changes += 1;
+                        }
+                    }
+                }
+            }
+            _ = op_tick.tick() => {
+                if detached.load(Ordering::Relaxed) {
+                    continue;
+                }
+                round += 1;
+                match &writer {
+                    // default role: write, then read it back
+                    Some(opts) => {
+                        let data = Envelope::text(&format!("soak round {round} at {}", now_ms())).encode();
+                        match rc.set_dht_value(record_key.clone(), config.planned_write_subkey(), data.clone(), Some(opts.clone())).await {
+                            Ok(_) => counters.writes_ok += 1,
+                            Err(e) => {
+                                counters.writes_failed += 1;
+                                log.line(&format!("write failed: {e}"));
+                                continue;
+                            }
+                        }
+                        match rc.get_dht_value(record_key.clone(), config.planned_write_subkey(), true).await {
+                            Ok(Some(v)) if v.data() == data.as_slice() => counters.reads_ok += 1,
+                            Ok(_) => {
+                                counters.reads_ok += 1;
+                                counters.mismatched_reads += 1;
+                                log.line("read-back didn't match what was just written");
+                            }
+                            Err(e) => {
+                                counters.reads_failed += 1;
+                                log.line(&format!("read failed: {e}"));
+                            }
+                        }
+                    }
+                    // alt role: read every subkey and keep the watch alive
+                    None => {
+                        for subkey in 0..config.total_subkeys() {
+                            match rc.get_dht_value(record_key.clone(), subkey, true).await {
+                                Ok(_) => counters.reads_ok += 1,
+                                Err(e) => {
+                                    counters.reads_failed += 1;
+                                    log.line(&format!("read of subkey {subkey} failed: {e}"));
+                                }
+                            }
+                        }
+                        if watch_dead {
+                            match rc.watch_dht_values(record_key.clone(), None, None, None).await {
+                                Ok(true) => {
+                                    counters.watch_renewals += 1;
+                                    watch_dead = false;
+                                    log.line("watch renewed");
+                                }
+                                Ok(false) => log.line("watch renewal refused"),
+                                Err(e) => log.line(&format!("watch renewal failed: {e}")),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    systemd::notify("STOPPING=1");
+    log.line(&summary(&counters, started));
+    health.set_record_open(false);
+    veilid.shutdown().await;
+    log.line(&format!("{mode} finished"));
+    Ok(())
+}
+
+static STOP_REQUESTED: Notify = Notify::const_new();
+
+// Ask a running soak/daemon to stop, the same as Ctrl+C (the Windows service uses this).
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn request_stop() {
+    STOP_REQUESTED.notify_one();
+}
+
+// Ctrl+C, SIGTERM (what `docker stop` and systemd send) where there is one,
+// or request_stop().
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut term) = signal(SignalKind::terminate()) {
+            return tokio::select! {
+                _ = tokio::signal::ctrl_c() => "Ctrl+C",
+                _ = term.recv() => "SIGTERM",
+                _ = STOP_REQUESTED.notified() => "stop request",
+            };
+        }
+    }
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => "Ctrl+C",
+        _ = STOP_REQUESTED.notified() => "stop request",
+    }
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(tokio::time::Instant::from_std(deadline)).await,
+        None => std::future::pending().await,
+    }
+}
+
+fn note_state(
+    log: &mut SoakLog,
+    counters: &mut Counters,
+    last_state: &mut Option<AttachmentState>,
+    state: AttachmentState,
+) {
+    if *last_state != Some(state) {
+        if last_state.is_some() {
+            counters.state_changes += 1;
+        }
+        log.line(&format!("attachment state: {state}"));
+        *last_state = Some(state);
+    }
+}
+
+fn summary(c: &Counters, started: Instant) -> String {
+    let mut out = format!(
+        "SUMMARY after {:.1}h: writes {} ok / {} failed, reads {} ok / {} failed ({} mismatched), \
+         value changes {}, watch deaths {}, watch renewals {}, attachment changes {}",
+        started.elapsed().as_secs_f64() / 3600.0,
+        c.writes_ok,
+        c.writes_failed,
+        c.reads_ok,
+        c.reads_failed,
+        c.mismatched_reads,
+        c.value_changes,
+        c.watch_deaths,
+        c.watch_renewals,
+        c.state_changes,
+    );
+    if let Some(rss) = resident_memory_kb() {
+        out.push_str(&format!(", memory {rss} kB"));
+    }
+    out
+}
+
+// Resident memory of this process, so a slow leak shows up in the summaries.
+// Only available where /proc exists (Linux).
+fn resident_memory_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))
+        .and_then(|v| v.trim().trim_end_matches("kB").trim().parse().ok())
+}
@@ -0,0 +1,395 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use veilid_core::*;
+
+use crate::audit::now_ms;
+use crate::i18n::t;
+use crate::trend::OpTotals;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Watch delivery statistics.
+//
+//	Every ValueChange the node receives is counted against the watch it
+//	belongs to. We remember the last sequence number seen per subkey, so if
+//	seq jumps from 3 to 6 we know two updates never reached us.
+//
+//	Delivery delay needs the writer's timestamp, which only values that carry
+//	one can give us; plain text values just count towards the totals.
+//
+//	Bandwidth: bytes written to and read from the DHT, split by the feature
+//	that moved them (see Dht::for_feature), so `stats bandwidth` shows what
+//	each one costs. Only value bytes are counted, not Veilid's own overhead.
+//
+//	Latency: an exponential moving average per kind of call (get, set, ...),
+//	two in fact: a quick one that follows the last few calls and a slow one
+//	that stands for "normal". The prompt shows the quick one with an arrow
+//	when it has drifted well away from the slow one, ▲ slower than usual and
+//	▼ faster, so a struggling network shows up as you type. Failures get
+//	the same treatment as an error rate.
+//	The calls are totalled as well, for trend.rs to keep day by day.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Default)]
+struct SubkeyStats {
+    last_seq: Option<u32>,
+    delivered: u64,
+    missed: u64,
+    out_of_order: u64,
+}
+
+struct RecordWatch {
+    started: Instant,
+    delivered: u64,
+    // sum of (local receipt - writer timestamp) over the changes that had a timestamp
+    delay_total_ms: u128,
+    delay_samples: u64,
+    remaining_count: Option<u32>,
+    died: bool,
+    subkeys: BTreeMap<ValueSubkey, SubkeyStats>,
+}
+
+impl RecordWatch {
+    fn new() -> RecordWatch {
+        RecordWatch {
+            started: Instant::now(),
+            delivered: 0,
+            delay_total_ms: 0,
+            delay_samples: 0,
+            remaining_count: None,
+            died: false,
+            subkeys: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct WatchStats {
+    watches: Mutex<HashMap<RecordKey, RecordWatch>>,
+}
+
+impl WatchStats {
+    pub fn new() -> WatchStats {
+        WatchStats::default()
+    }
+
+    // Call this once watch_dht_values has succeeded for a record.
+    pub fn watch_started(&self, record_key: &RecordKey) {
+        self.watches
+            .lock()
+            .unwrap()
+            .insert(record_key.clone(), RecordWatch::new());
+    }
+
+    // Call this from the update callback for every VeilidUpdate::ValueChange.
+    // `writer_ts_ms` is the writer's clock (unix ms) if the value carried one.
+    pub fn value_changed(&self, change: &VeilidValueChange, writer_ts_ms: Option<u128>) {
+        let mut watches = self.watches.lock().unwrap();
+        let watch = watches
+            .entry(change.key.clone())
+            .or_insert_with(RecordWatch::new);
+
+        // An empty subkey range or a zero count means the watch has died.
+        if change.subkeys.is_empty() || change.count == 0 {
+            watch.died = true;
+            watch.remaining_count = Some(0);
+            return;
+        }
+
+        watch.delivered += 1;
+        if change.count != u32::MAX {
+            watch.remaining_count = Some(change.count);
+        }
+
+        if let Some(ts) = writer_ts_ms {
+            watch.delay_total_ms += now_ms().saturating_sub(ts);
+            watch.delay_samples += 1;
+        }
+
+        // Only the first subkey in the range comes with a value (and so a seq).
+        let Some(subkey) = change.subkeys.nth_subkey(0) else {
+            return;
+        };
+        let entry = watch.subkeys.entry(subkey).or_default();
+        entry.delivered += 1;
+
+        let Some(seq) = change.value.as_ref().and_then(|v| v.seq().to_option()) else {
+            return;
+        };
+        match entry.last_seq {
+            Some(last) if seq > last + 1 => {
+                entry.missed += u64::from(seq - last - 1);
+                entry.last_seq = Some(seq);
+            }
+            Some(last) if seq <= last => entry.out_of_order += 1,
+            _ => entry.last_seq = Some(seq),
+        }
+    }
+
+    // Changes delivered so far and the count the last one said was left, for `watch status`.
+    pub fn counts(&self, record_key: &RecordKey) -> Option<(u64, Option<u32>)> {
+        self.watches
+            .lock()
+            .unwrap()
+            .get(record_key)
+            .map(|w| (w.delivered, w.remaining_count))
+    }
+
+    // What `stats watch` prints.
+    pub fn report(&self) -> String {
+        let watches = self.watches.lock().unwrap();
+        if watches.is_empty() {
+            return t!("stats-no-watches");
+        }
+
+        let mut out = String::new();
+        for (record_key, w) in watches.iter() {
+            let missed: u64 = w.subkeys.values().map(|s| s.missed).sum();
+            let out_of_order: u64 = w.subkeys.values().map(|s| s.out_of_order).sum();
+            let avg_delay = if w.delay_samples > 0 {
+                format!("{}ms", w.delay_total_ms / u128::from(w.delay_samples))
+            } else {
+                t!("stats-no-delay")
+            };
+
+            let status = if w.died { t!("stats-watch-dead") } else { t!("stats-watch-active") };
+            out.push_str(&format!("{}\n", t!("stats-watch-on", key = record_key.to_string())));
+            out.push_str(&format!("  {}\n", t!("stats-watch-status", status = status)));
+            out.push_str(&format!("  {}\n", t!("stats-watch-running", secs = w.started.elapsed().as_secs())));
+            out.push_str(&format!("  {}\n", t!("stats-watch-changes", count = w.delivered)));
+            out.push_str(&format!("  {}\n", t!("stats-watch-delay", delay = avg_delay)));
+            out.push_str(&format!("  {}\n", t!("stats-watch-missed", count = missed)));
+            out.push_str(&format!("  {}\n", t!("stats-watch-out-of-order", count = out_of_order)));
+            if let Some(count) = w.remaining_count {
+                out.push_str(&format!("  {}\n", t!("stats-watch-remaining", count = count)));
+            }
+            for (subkey, s) in &w.subkeys {
+                let last = s
+                    .last_seq
+                    .map(|q| q.to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                out.push_str(&format!(
+                    "    {}\n",
+                    t!("stats-watch-subkey", subkey = subkey, changes = s.delivered, last = last, missed = s.missed)
+                ));
+            }
+        }
+        out
+    }
+}
+
+// -------------------------------------------------------------------------
+// Bandwidth per feature
+// -------------------------------------------------------------------------
+
+// What a DHT call was done for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Feature {
+    // what the user types and reads back
+    Values,
+    Metadata,
+    Mail,
+    Discovery,
+    Flood,
+    Janitor,
+    // the rest of a record, fetched behind a `read` (see prefetch.rs)
+    Prefetch,
+    // changes delivered by watches
+    Watch,
+}
+
+impl Feature {
+    fn name(self) -> &'static str {
+        match self {
+            Feature::Values => "values",
+            Feature::Metadata => "metadata",
+            Feature::Mail => "mail",
+            Feature::Discovery => "discovery",
+            Feature::Flood => "flood",
+            Feature::Janitor => "janitor",
+            Feature::Prefetch => "prefetch",
+            Feature::Watch => "watch",
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub struct Traffic {
+    pub writes: u64,
+    pub bytes_written: u64,
+    pub reads: u64,
+    pub bytes_read: u64,
+}
+
+#[derive(Default)]
+pub struct Bandwidth {
+    by_feature: Mutex<BTreeMap<Feature, Traffic>>,
+}
+
+impl Bandwidth {
+    pub fn new() -> Bandwidth {
+        Bandwidth::default()
+    }
+
+    pub fn wrote(&self, feature: Feature, bytes: usize) {
+        let mut map = self.by_feature.lock().unwrap();
+        let t = map.entry(feature).or_default();
+        t.writes += 1;
+        t.bytes_written += bytes as u64;
+    }
+
+    pub fn read(&self, feature: Feature, bytes: usize) {
+        let mut map = self.by_feature.lock().unwrap();
+        let t = map.entry(feature).or_default();
+        t.reads += 1;
+        t.bytes_read += bytes as u64;
+    }
+
+    #[cfg(test)]
+    pub fn traffic(&self, feature: Feature) -> Traffic {
+        self.by_feature.lock().unwrap().get(&feature).copied().unwrap_or_default()
+    }
+
+    // What `stats bandwidth` prints.
+    pub fn report(&self) -> String {
+        let map = self.by_feature.lock().unwrap();
+        if map.is_empty() {
+            return t!("stats-no-traffic");
+        }
+        let mut out = format!(
+            "  {:<10} {:>8} {:>12} {:>8} {:>12}\n",
+            t!("stats-col-feature"),
+            t!("stats-col-writes"),
+            t!("stats-col-written"),
+            t!("stats-col-reads"),
+            t!("stats-col-read")
+        );
+        let mut total = Traffic::default();
+        for (feature, t) in map.iter() {
+            out.push_str(&format!(
+                "  {:<10} {:>8} {:>12} {:>8} {:>12}\n",
+                feature.name(),
+                t.writes,
+                bytes(t.bytes_written),
+                t.reads,
+                bytes(t.bytes_read)
+            ));
+            total.writes += t.writes;
+            total.bytes_written += t.bytes_written;
+            total.reads += t.reads;
+            total.bytes_read += t.bytes_read;
+        }
+        out.push_str(&format!(
+            "  {:<10} {:>8} {:>12} {:>8} {:>12}",
+            t!("stats-col-total"),
+            total.writes,
+            bytes(total.bytes_written),
+            total.reads,
+            bytes(total.bytes_read)
+        ));
+        out
+    }
+}
+
+pub fn bytes(n: u64) -> String {
+    match n {
+        0..=9_999 => format!("{n} B"),
+        10_000..=9_999_999 => format!("{:.1} KiB", n as f64 / 1024.0),
+        _ => format!("{:.1} MiB", n as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+// -------------------------------------------------------------------------
+// Latency and error rate per call
+// -------------------------------------------------------------------------
+
+// How much of the latest call goes into each average.
+const FAST_WEIGHT: f64 = 0.3;
+const SLOW_WEIGHT: f64 = 0.05;
+// how far apart the two have to be for an arrow
+const DRIFT: f64 = 0.25;
+// error rates below this aren't shown
+const ERRORS_SHOWN: f64 = 0.05;
+// calls shown in the prompt, busiest first
+const PROMPT_OPS: usize = 3;
+
+#[derive(Default)]
+struct OpLatency {
+    calls: u64,
+    fast_ms: f64,
+    slow_ms: f64,
+    errors: f64,
+}
+
+fn ema(avg: f64, sample: f64, weight: f64) -> f64 {
+    avg + weight * (sample - avg)
+}
+
+#[derive(Default)]
+pub struct Latency {
+    by_op: Mutex<BTreeMap<String, OpLatency>>,
+    // totals since trend.rs last saved them
+    unsaved: Mutex<BTreeMap<String, OpTotals>>,
+}
+
+impl Latency {
+    pub fn new() -> Latency {
+        Latency::default()
+    }
+
+    pub fn record(&self, op: &str, latency_ms: u128, ok: bool) {
+        let mut map = self.by_op.lock().unwrap();
+        let o = map.entry(op.to_string()).or_default();
+        let ms = latency_ms as f64;
+        if o.calls == 0 {
+            o.fast_ms = ms;
+            o.slow_ms = ms;
+        } else {
+            o.fast_ms = ema(o.fast_ms, ms, FAST_WEIGHT);
+            o.slow_ms = ema(o.slow_ms, ms, SLOW_WEIGHT);
+        }
+        o.errors = ema(o.errors, if ok { 0.0 } else { 1.0 }, FAST_WEIGHT);
+        o.calls += 1;
+        drop(map);
+        self.unsaved.lock().unwrap().entry(op.to_string()).or_default().add(latency_ms, ok);
+    }
+
+    // The calls made since the last time this was asked, for trend.rs to keep.
+    pub fn take_unsaved(&self) -> BTreeMap<String, OpTotals> {
+        std::mem::take(&mut *self.unsaved.lock().unwrap())
+    }
+
+    // For the prompt: "get 220ms ▲, set 1.4s ▼". Empty until a call is made.
+    pub fn summary(&self) -> String {
+        let map = self.by_op.lock().unwrap();
+        let mut ops: Vec<(&String, &OpLatency)> = map.iter().collect();
+        ops.sort_by_key(|(_, o)| std::cmp::Reverse(o.calls));
+        ops.iter()
+            .take(PROMPT_OPS)
+            .map(|(op, o)| {
+                let mut part = format!("{op} {}", duration(o.fast_ms));
+                if o.fast_ms > o.slow_ms * (1.0 + DRIFT) {
+                    part.push_str(" ▲");
+                } else if o.fast_ms < o.slow_ms * (1.0 - DRIFT) {
+                    part.push_str(" ▼");
+                }
+                if o.errors >= ERRORS_SHOWN {
+                    part.push_str(&format!(" {:.0}% err", o.errors * 100.0));
+                }
+                part
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn duration(ms: f64) -> String {
+    if ms < 1000.0 {
+        format!("{ms:.0}ms")
+    } else {
+        format!("{:.1}s", ms / 1000.0)
+    }
+}
@@ -0,0 +1,531 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use veilid_core::*;
+
+use crate::config::AppConfig;
+use crate::exit::Kind;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Veilid's protected store: where a node keeps its secrets on this machine,
+//	above all the device encryption key the table store is sealed with.
+//
+//	By default the example runs with always_use_insecure_storage, as it
+//	always has: a plain file in .veilid/protected_store and no password.
+//	Set it to false and Veilid uses the OS keychain (or that file, where
+//	there isn't one), with the device encryption key sealed under a
+//	password that every node needs before it can start. It comes from:
+//
+//	  1 VEILID_EXAMPLE_STORE_PASSWORD
+//	  2 store_password_command in the config: a program that prints it,
+//	    like a password manager's CLI or an askpass helper
+//	  3 the terminal, asked once per run however many nodes start
+//
+//	`keys passwd` sets or changes it. Every namespace this data folder has
+//	run is started once, not attached, with the old password and the new
+//	one, which makes Veilid seal its key again; nothing else is rewritten.
+//
+//	`keys show` says where each namespace's device encryption key is kept,
+//	whether it's sealed, and what the table store holds under it.
+//
+//	`keys rotate` replaces the key itself, which Veilid has no call for, so
+//	it's done the long way round with the public table store API:
+//
+//	  1 start the namespace and read every table into memory
+//	  2 set the table files aside, in .veilid/rotate-<ns>-<ms>; the old key
+//	    is only ever held in memory, never written out next to them
+//	  3 start on the empty store to forget the key, then again so Veilid
+//	    makes a new one, and write the tables back under that
+//	  4 start once more and check every table reads back, node id included
+//
+//	The set-aside copy is deleted once step 4 passes, and put back (old
+//	key and all) if anything before then fails. Veilid's own components
+//	run during step 3 as well, so its caches, the routing table above all,
+//	may come back with fewer entries; it rebuilds them once attached.
+//
+//	Switch on a fresh data folder (or before the first run): a node that
+//	already made its key in the insecure file won't find it in the keychain,
+//	makes a new one, and can't read the tables sealed with the old.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+pub const PASSWORD_ENV: &str = "VEILID_EXAMPLE_STORE_PASSWORD";
+const MIN_PASSWORD: usize = 8;
+// the table every namespace's table store makes first, <namespace>___veilid_all_tables
+const ALL_TABLES_SUFFIX: &str = "___veilid_all_tables";
+// the protected store entry Veilid keeps the device encryption key under
+const DEVICE_KEY: &str = "device_encryption_key";
+// Veilid's own table the node id lives in
+const CONFIG_TABLE: &str = "__veilid_config";
+
+// The password once we have it, so the second node of a run doesn't ask again.
+static PASSWORD: Mutex<Option<String>> = Mutex::new(None);
+
+// Read from the terminal without echo.
+fn ask(prompt: &str) -> Result<String, String> {
+    rpassword::prompt_password(prompt).map_err(|e| e.to_string())
+}
+
+fn from_command(command: &str) -> Result<String, String> {
+    let shell = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let out = Command::new(shell.0)
+        .args([shell.1, command])
+        .output()
+        .map_err(|e| format!("store_password_command couldn't run: {e}"))?;
+    if !out.status.success() {
+        return Err(format!("store_password_command failed ({})", out.status));
+    }
+    let text = String::from_utf8(out.stdout).map_err(|_| "store_password_command printed something that isn't text".to_string())?;
+    // password managers end with a newline; the password itself is the first line
+    Ok(text.lines().next().unwrap_or_default().to_string())
+}
+
+// What's set up to give the password without asking, if anything.
+fn given(config: &AppConfig) -> Result<Option<String>, String> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(Some(password));
+    }
+    config.store_password_command.as_deref().map(from_command).transpose()
+}
+
+// The password for node_config: empty while the store is the insecure file.
+pub fn password(config: &AppConfig) -> Result<String, String> {
+    if config.always_use_insecure_storage {
+        return Ok(String::new());
+    }
+    let mut cached = PASSWORD.lock().unwrap();
+    if let Some(password) = cached.as_ref() {
+        return Ok(password.clone());
+    }
+    let password = match given(config)? {
+        Some(password) => password,
+        None => ask("Protected store password: ")?,
+    };
+    *cached = Some(password.clone());
+    Ok(password)
+}
+
+// api_startup's error, with what to do about it when it's the password.
+pub fn explain(e: VeilidAPIError) -> String {
+    let text = e.to_string();
+    if text.contains("device encryption key") {
+        format!("{text}\n(wrong protected store password? `keys passwd` changes it, and {PASSWORD_ENV} overrides what's asked for)")
+    } else {
+        text
+    }
+}
+
+// explain(), for a node that couldn't start: a wrong password exits as a refused credential.
+pub fn startup_failed(e: VeilidAPIError) -> Box<dyn std::error::Error> {
+    let wrong_password = e.to_string().contains("device encryption key");
+    let text = explain(e);
+    if wrong_password {
+        Kind::Credential.fail(text)
+    } else {
+        text.into()
+    }
+}
+
+// Namespaces whose table store has been set up in this data folder.
+pub fn started_namespaces(data_dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(data_dir.join(".veilid/table_store")) else {
+        return Vec::new();
+    };
+    let mut found: Vec<String> = entries
+        .filter_map(|e| e.ok()?.file_name().into_string().ok())
+        .filter_map(|name| name.strip_suffix(ALL_TABLES_SUFFIX).map(str::to_string))
+        .filter(|ns| !ns.is_empty())
+        .collect();
+    found.sort();
+    found.dedup();
+    found
+}
+
+// -------------------------------------------------------------------------
+// keys passwd
+// -------------------------------------------------------------------------
+
+pub async fn passwd(config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    if config.always_use_insecure_storage {
+        return Err("the protected store has no password while always_use_insecure_storage is true; \
+                    set it to false in the config first (see store.rs)"
+            .into());
+    }
+    let data_dir = config.data_dir()?;
+    let namespaces = started_namespaces(&data_dir);
+    if namespaces.is_empty() {
+        println!("No node has run in this data folder yet. The password will be asked for on the first run.");
+        return Ok(());
+    }
+    for ns in &namespaces {
+        if let Some(pid) = crate::preflight::running_pid(&data_dir, ns) {
+            return Err(format!("a node is running in '{ns}' (pid {pid}); stop it first").into());
+        }
+    }
+
+    let old = match given(config)? {
+        Some(password) => password,
+        None => ask("Current password (just ENTER if there isn't one yet): ")?,
+    };
+    let new = ask("New password: ")?;
+    if ask("Again: ")? != new {
+        return Err("the passwords don't match".into());
+    }
+    if new.chars().count() < MIN_PASSWORD {
+        return Err(format!("use a password of at least {MIN_PASSWORD} characters").into());
+    }
+
+    println!("Sealing the device encryption key again in {} namespace(s):", namespaces.len());
+    for (i, ns) in namespaces.iter().enumerate() {
+        let mut veilid_config = crate::node::node_config_with(config, &data_dir, ns, &old);
+        veilid_config.protected_store.new_device_encryption_key_password = Some(new.clone());
+        let veilid = match api_startup(Arc::new(|_| {}), veilid_config).await {
+            Ok(veilid) => veilid,
+            Err(e) => {
+                let done = &namespaces[..i];
+                return Err(format!(
+                    "{ns}: {}\nAlready on the new password: {}",
+                    explain(e),
+                    if done.is_empty() { "none".to_string() } else { done.join(", ") }
+                )
+                .into());
+            }
+        };
+        veilid.shutdown().await;
+        println!("  {ns}: done");
+    }
+    println!();
+    println!("Use the new password from now on; update {PASSWORD_ENV} or store_password_command's source if you use one.");
+    Ok(())
+}
+
+// -------------------------------------------------------------------------
+// keys show / keys rotate
+// -------------------------------------------------------------------------
+
+// One table, read out in full.
+struct Table {
+    name: String,
+    columns: u32,
+    // (column, key, value)
+    entries: Vec<(u32, Vec<u8>, Vec<u8>)>,
+}
+
+// Started but never attached: nothing goes out on the network.
+async fn start_offline(config: &AppConfig, data_dir: &Path, namespace: &str) -> Result<VeilidAPI, String> {
+    let veilid_config = crate::node::node_config(config, data_dir, namespace)?;
+    api_startup(Arc::new(|_| {}), veilid_config)
+        .await
+        .map_err(|e| format!("{namespace}: {}", explain(e)))
+}
+
+fn device_key(veilid: &VeilidAPI) -> Result<Option<Vec<u8>>, String> {
+    let store = veilid.protected_store().map_err(|e| e.to_string())?;
+    store.load_user_secret(DEVICE_KEY).map_err(|e| e.to_string())
+}
+
+// What the stored key is, without showing it: its crypto kind, whether a
+// password seals it, and a fingerprint that changes when it's replaced or
+// sealed again.
+pub fn describe_key(stored: &[u8]) -> String {
+    // the kind's four letters, then the key: 32 bytes bare, more when sealed
+    if stored.len() < 4 {
+        return "not a device encryption key".to_string();
+    }
+    let (kind, key) = stored.split_at(4);
+    let sealed = if key.len() > 32 { "sealed with the password" } else { "not sealed (no password)" };
+    let fingerprint = &blake3::hash(stored).to_hex()[..16];
+    format!("{}, {sealed}, fingerprint {fingerprint}", String::from_utf8_lossy(kind))
+}
+
+fn where_kept(config: &AppConfig, data_dir: &Path, namespace: &str) -> String {
+    let file = data_dir.join(".veilid/protected_store").join(format!("insecure_keyring_{namespace}"));
+    if config.always_use_insecure_storage || file.exists() {
+        format!("file {}", file.to_string_lossy())
+    } else {
+        format!("OS keychain, service veilid_protected_store_{namespace}")
+    }
+}
+
+async fn read_tables(veilid: &VeilidAPI) -> Result<Vec<Table>, String> {
+    let store = veilid.table_store().map_err(|e| e.to_string())?;
+    let mut names: Vec<String> = store.list_all().into_iter().map(|(name, _)| name).collect();
+    names.sort();
+    let mut tables = Vec::new();
+    for name in names {
+        let Some(info) = store.info(&name).await.map_err(|e| format!("table {name}: {e}"))? else {
+            continue;
+        };
+        let db = store.open(&name, info.column_count).await.map_err(|e| format!("table {name}: {e}"))?;
+        let mut entries = Vec::new();
+        for col in 0..info.column_count {
+            for key in db.get_keys(col).await.map_err(|e| format!("table {name}: {e}"))? {
+                if let Some(value) = db.load(col, &key).await.map_err(|e| format!("table {name}: {e}"))? {
+                    entries.push((col, key, value));
+                }
+            }
+        }
+        tables.push(Table {
+            name,
+            columns: info.column_count,
+            entries,
+        });
+    }
+    Ok(tables)
+}
+
+async fn write_tables(veilid: &VeilidAPI, tables: &[Table]) -> Result<(), String> {
+    let store = veilid.table_store().map_err(|e| e.to_string())?;
+    for table in tables {
+        let db = store.open(&table.name, table.columns).await.map_err(|e| format!("table {}: {e}", table.name))?;
+        let tx = db.transact();
+        for (col, key, value) in &table.entries {
+            tx.store(*col, key, value).await.map_err(|e| format!("table {}: {e}", table.name))?;
+        }
+        tx.commit().await.map_err(|e| format!("table {}: {e}", table.name))?;
+    }
+    Ok(())
+}
+
+// This namespace's table store files (each table is <namespace>_<table>).
+pub fn table_files(data_dir: &Path, namespace: &str) -> Vec<std::path::PathBuf> {
+    let prefix = format!("{namespace}_");
+    let Ok(entries) = fs::read_dir(data_dir.join(".veilid/table_store")) else {
+        return Vec::new();
+    };
+    let mut files: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+        .map(|e| e.path())
+        .collect();
+    files.sort();
+    files
+}
+
+fn targets(data_dir: &Path, only: Option<&str>) -> Result<Vec<String>, String> {
+    let started = started_namespaces(data_dir);
+    match only {
+        Some(ns) if started.iter().any(|s| s == ns) => Ok(vec![ns.to_string()]),
+        Some(ns) => Err(format!("no node has run in namespace '{ns}' here (there's {})", started.join(", "))),
+        None => Ok(started),
+    }
+}
+
+pub async fn show(only: Option<&str>, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    let namespaces = targets(&data_dir, only)?;
+    if namespaces.is_empty() {
+        println!("No node has run in this data folder yet, so there are no keys to show.");
+        return Ok(());
+    }
+    for ns in &namespaces {
+        println!("{ns}");
+        println!("  kept in:    {}", where_kept(config, &data_dir, ns));
+        if let Some(pid) = crate::preflight::running_pid(&data_dir, ns) {
+            println!("  (running as pid {pid}; stop it to see its key and tables)");
+            continue;
+        }
+        let veilid = start_offline(config, &data_dir, ns).await?;
+        let key = device_key(&veilid);
+        let tables = read_tables(&veilid).await;
+        veilid.shutdown().await;
+        match key? {
+            Some(stored) => println!("  device key: {}", describe_key(&stored)),
+            None => println!("  device key: none (the table store isn't encrypted)"),
+        }
+        let tables = tables?;
+        let entries: usize = tables.iter().map(|t| t.entries.len()).sum();
+        println!("  tables:     {} holding {entries} entries", tables.len());
+        for t in &tables {
+            println!("    {:<32} {:>6} entries", t.name, t.entries.len());
+        }
+    }
+    Ok(())
+}
+
+// Put the set-aside tables and key back the way they were.
+async fn roll_back(config: &AppConfig, data_dir: &Path, ns: &str, aside: &Path, old_key: &[u8]) -> Result<(), String> {
+    let io = |e: std::io::Error| e.to_string();
+    remove_table_files(data_dir, ns)?;
+    // the old key goes back through a node running on empty tables, which are thrown away after
+    let veilid = start_offline(config, data_dir, ns).await?;
+    let saved = veilid
+        .protected_store()
+        .map_err(|e| e.to_string())
+        .and_then(|store| store.save_user_secret(DEVICE_KEY, old_key).map_err(|e| e.to_string()));
+    veilid.shutdown().await;
+    saved?;
+    remove_table_files(data_dir, ns)?;
+    for entry in fs::read_dir(aside).map_err(io)? {
+        let entry = entry.map_err(io)?;
+        fs::rename(entry.path(), data_dir.join(".veilid/table_store").join(entry.file_name())).map_err(io)?;
+    }
+    fs::remove_dir_all(aside).map_err(io)
+}
+
+fn remove_table_files(data_dir: &Path, ns: &str) -> Result<(), String> {
+    for file in table_files(data_dir, ns) {
+        fs::remove_file(file).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// Step 2: every table file into `aside`. All or nothing.
+fn set_aside(data_dir: &Path, ns: &str, aside: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(aside)?;
+    let mut moved = Vec::new();
+    for file in table_files(data_dir, ns) {
+        let to = aside.join(file.file_name().expect("a file"));
+        if let Err(e) = fs::rename(&file, &to) {
+            for (from, to) in moved {
+                let _ = fs::rename(to, from);
+            }
+            let _ = fs::remove_dir_all(aside);
+            return Err(e);
+        }
+        moved.push((file, to));
+    }
+    Ok(())
+}
+
+// Steps 3 and 4 for one namespace; Ok is the new key.
+async fn rewrite(config: &AppConfig, data_dir: &Path, ns: &str, before: &[Table]) -> Result<Vec<u8>, String> {
+    // forgetting the key on a store with no tables in it loses nothing
+    let veilid = start_offline(config, data_dir, ns).await?;
+    let forgotten = veilid
+        .protected_store()
+        .map_err(|e| e.to_string())
+        .and_then(|store| store.remove_user_secret(DEVICE_KEY).map_err(|e| e.to_string()));
+    veilid.shutdown().await;
+    forgotten?;
+    remove_table_files(data_dir, ns)?;
+
+    let veilid = start_offline(config, data_dir, ns).await?;
+    let written = write_tables(&veilid, before).await;
+    let new_key = device_key(&veilid);
+    veilid.shutdown().await;
+    written?;
+    let new_key = new_key?.ok_or("Veilid didn't make a new device key")?;
+
+    let veilid = start_offline(config, data_dir, ns).await?;
+    let after = read_tables(&veilid).await;
+    veilid.shutdown().await;
+    let after = after?;
+    for table in before {
+        let Some(now) = after.iter().find(|t| t.name == table.name) else {
+            return Err(format!("table {} is missing after the rewrite", table.name));
+        };
+        if table.name == CONFIG_TABLE && now.entries != table.entries {
+            return Err("the node id didn't survive the rewrite".to_string());
+        }
+        if now.entries.len() != table.entries.len() {
+            println!("    {}: {} entries, now {} (Veilid rewrote it while running)", table.name, table.entries.len(), now.entries.len());
+        }
+    }
+    Ok(new_key)
+}
+
+pub async fn rotate(only: Option<&str>, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    let namespaces = targets(&data_dir, only)?;
+    if namespaces.is_empty() {
+        println!("No node has run in this data folder yet, so there are no keys to rotate.");
+        return Ok(());
+    }
+    for ns in &namespaces {
+        if let Some(pid) = crate::preflight::running_pid(&data_dir, ns) {
+            return Err(format!("a node is running in '{ns}' (pid {pid}); stop it first").into());
+        }
+        // the files are picked out by prefix, so one namespace mustn't be the start of another's
+        if let Some(other) = started_namespaces(&data_dir).iter().find(|o| o.starts_with(&format!("{ns}_"))) {
+            return Err(format!("'{other}' shares its file names' prefix with '{ns}'; rotate can't tell their tables apart").into());
+        }
+    }
+
+    for ns in &namespaces {
+        println!("{ns}:");
+        // ---- 1 ----
+        let veilid = start_offline(config, &data_dir, ns).await?;
+        let read = async {
+            let tables = read_tables(&veilid).await?;
+            let old_key = device_key(&veilid)?.ok_or("there's no device key to rotate")?;
+            Ok::<_, String>((tables, old_key))
+        }
+        .await;
+        veilid.shutdown().await;
+        let (before, old_key) = read?;
+        let entries: usize = before.iter().map(|t| t.entries.len()).sum();
+        println!("  read {} tables, {entries} entries; old key {}", before.len(), describe_key(&old_key));
+
+        // ---- 2 ----
+        let aside = data_dir.join(".veilid").join(format!("rotate-{ns}-{}", crate::audit::now_ms()));
+        if let Err(e) = set_aside(&data_dir, ns, &aside) {
+            return Err(format!("couldn't set {ns}'s tables aside ({e}); nothing was changed").into());
+        }
+
+        // ---- 3 and 4 ----
+        match rewrite(config, &data_dir, ns, &before).await {
+            Ok(new_key) => {
+                fs::remove_dir_all(&aside)?;
+                println!("  new key {}", describe_key(&new_key));
+            }
+            Err(e) => {
+                println!("  rotating failed: {e}");
+                return Err(match roll_back(config, &data_dir, ns, &aside, &old_key).await {
+                    Ok(()) => format!("{ns} is back on its old key, as it was").into(),
+                    Err(r) => format!(
+                        "{ns} couldn't be put back either ({r}); its old tables are in {}, \
+                         but the old key was never written out, so they can't be read again",
+                        aside.to_string_lossy()
+                    )
+                    .into(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespaces_are_found_from_their_table_stores() {
+        let dir = std::env::temp_dir().join(format!("store-test-{}", std::process::id()));
+        let tables = dir.join(".veilid/table_store");
+        fs::create_dir_all(&tables).unwrap();
+        assert_eq!(started_namespaces(&dir), Vec::<String>::new());
+        for name in [
+            "veilid-example-ver2___veilid_all_tables",
+            "veilid-example-ver1___veilid_all_tables",
+            "veilid-example-ver1_routing_table",
+            "___veilid_all_tables",
+        ] {
+            fs::write(tables.join(name), b"").unwrap();
+        }
+        assert_eq!(started_namespaces(&dir), vec!["veilid-example-ver1", "veilid-example-ver2"]);
+        assert_eq!(table_files(&dir, "veilid-example-ver1").len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn device_keys_are_described_without_showing_them() {
+        let mut bare = b"VLD0".to_vec();
+        bare.extend([7u8; 32]);
+        let shown = describe_key(&bare);
+        assert!(shown.starts_with("VLD0, not sealed"), "{shown}");
+        assert!(!shown.contains("0707"));
+
+        // sealed: the key, the AEAD tag and the nonce
+        let mut sealed = b"VLD0".to_vec();
+        sealed.extend([7u8; 32 + 16 + 24]);
+        assert!(describe_key(&sealed).contains("sealed with the password"));
+        let fingerprint = |stored: &[u8]| describe_key(stored).rsplit(' ').next().unwrap().to_string();
+        assert_ne!(fingerprint(&sealed), fingerprint(&bare));
+        assert_eq!(describe_key(b"VL"), "not a device encryption key");
+    }
+}
@@ -0,0 +1,123 @@
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	Running the daemon as a systemd service (Linux).
+//
+//	sd_notify: with `Type=notify` in the unit file, systemd waits for us to
+//	say READY=1 (sent once we're attached and the record is open) and is told
+//	STOPPING=1 on the way out. With `WatchdogSec=` set we also send WATCHDOG=1
+//	from the main loop, which wakes at least every 30s, so keep WatchdogSec
+//	above that. Outside systemd ($NOTIFY_SOCKET unset) it does nothing.
+//
+//	journald (--journald): log lines go straight to the journal with
+//	structured fields (VEILID_MODE, VEILID_ROLE, VEILID_RECORD, ...) so you can
+//	`journalctl VEILID_ROLE=alt` instead of grepping text.
+//
+//	Both are one datagram on a unix socket, so no extra crates are needed.
+//	On other platforms everything here is a no-op.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+
+// -------------------------------------------------------------------------
+// sd_notify
+// -------------------------------------------------------------------------
+
+pub fn notify(state: &str) {
+    #[cfg(unix)]
+    {
+        let Ok(path) = std::env::var("NOTIFY_SOCKET") else {
+            return;
+        };
+        let Ok(sock) = UnixDatagram::unbound() else {
+            return;
+        };
+        let _ = send_to_path(&sock, &path, state.as_bytes());
+    }
+    #[cfg(not(unix))]
+    let _ = state;
+}
+
+// Did systemd ask for watchdog pings? (WatchdogSec= in the unit)
+pub fn watchdog_enabled() -> bool {
+    std::env::var("WATCHDOG_USEC").is_ok_and(|v| v.parse::<u64>().is_ok_and(|us| us > 0))
+}
+
+#[cfg(unix)]
+fn send_to_path(sock: &UnixDatagram, path: &str, data: &[u8]) -> std::io::Result<usize> {
+    // "@name" is a Linux abstract socket, anything else a path on disk
+    #[cfg(target_os = "linux")]
+    if let Some(name) = path.strip_prefix('@') {
+        use std::os::linux::net::SocketAddrExt;
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+        return sock.send_to_addr(data, &addr);
+    }
+    sock.send_to(data, path)
+}
+
+// -------------------------------------------------------------------------
+// journald
+// -------------------------------------------------------------------------
+
+#[cfg(unix)]
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+pub struct Journal {
+    #[cfg(unix)]
+    sock: UnixDatagram,
+    identifier: String,
+}
+
+// syslog "informational", which is all our log lines are
+pub const PRIORITY_INFO: u8 = 6;
+
+impl Journal {
+    pub fn open(identifier: &str) -> std::io::Result<Journal> {
+        #[cfg(unix)]
+        {
+            let sock = UnixDatagram::unbound()?;
+            sock.connect(JOURNAL_SOCKET)?;
+            Ok(Journal {
+                sock,
+                identifier: identifier.to_string(),
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = identifier;
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "journald is only available on Linux",
+            ))
+        }
+    }
+
+    // One entry: MESSAGE, PRIORITY and SYSLOG_IDENTIFIER plus any extra fields.
+    // Field names must be upper case letters, digits and '_'.
+    pub fn send(&self, priority: u8, message: &str, fields: &[(&str, String)]) {
+        let mut entry = Vec::new();
+        add_field(&mut entry, "MESSAGE", message);
+        add_field(&mut entry, "PRIORITY", &priority.to_string());
+        add_field(&mut entry, "SYSLOG_IDENTIFIER", &self.identifier);
+        for (name, value) in fields {
+            add_field(&mut entry, name, value);
+        }
+        #[cfg(unix)]
+        let _ = self.sock.send(&entry);
+    }
+}
+
+// journald's native format: NAME=value\n, or for values containing a newline
+// NAME\n<64-bit little endian length><value>\n
+fn add_field(entry: &mut Vec<u8>, name: &str, value: &str) {
+    entry.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use veilid_core::*;
+
+use crate::cli::Options;
+use crate::config::AppConfig;
+use crate::fields;
+use crate::mailbox::MailboxInfo;
+use crate::metadata::{self, RecordMetadata, RosterEntry};
+use crate::progress;
+use crate::receipts::RECEIPTS_SUBKEY;
+use crate::record::{start_tool_node, FreshWriters};
+use crate::shortcode::ShortcodeBook;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`record new --template <t>`: a record laid out for a kind of app, so
+//	nobody has to work out owner and member counts by hand.
+//
+//	  chat        subkey 0 metadata; 4 members with 8 chat subkeys each,
+//	              a ring of messages per person
+//	  kvstore     subkey 0 metadata; one writer with 32 subkeys, one key each
+//	  statuspage  subkey 0 metadata, 1 the page's overall presence; 8
+//	              services with one presence subkey each
+//	  mailbox     subkey 0 metadata, 1 delivery receipts; an inbox of 8
+//	              drop slots anyone holding the drop key can leave mail in
+//
+//	The metadata block is written straight away with the roster (who
+//	writes where, under the names above) and the payload types of each
+//	subkey, which joiners take as their own unless their config says
+//	otherwise (see payloads.rs). Every keypair is printed once at the end,
+//	like `record clone` does.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Template {
+    Chat,
+    KvStore,
+    StatusPage,
+    Mailbox,
+}
+
+const TEMPLATES: [Template; 4] = [Template::Chat, Template::KvStore, Template::StatusPage, Template::Mailbox];
+
+// What a template makes.
+#[derive(Debug)]
+pub struct Layout {
+    pub owner_subkeys: u16,
+    // (roster name, subkey count) for each SMPL member, in subkey order
+    pub members: Vec<(String, u16)>,
+    // subkey -> payload type name
+    pub payload_types: BTreeMap<ValueSubkey, String>,
+    // which member holds the mailbox's drop slots
+    pub inbox: Option<usize>,
+}
+
+impl Layout {
+    fn first_member_subkey(&self, index: usize) -> ValueSubkey {
+        ValueSubkey::from(self.owner_subkeys)
+            + self.members[..index].iter().map(|(_, n)| ValueSubkey::from(*n)).sum::<ValueSubkey>()
+    }
+
+    fn total_subkeys(&self) -> ValueSubkey {
+        self.first_member_subkey(self.members.len())
+    }
+}
+
+impl Template {
+    pub fn name(self) -> &'static str {
+        match self {
+            Template::Chat => "chat",
+            Template::KvStore => "kvstore",
+            Template::StatusPage => "statuspage",
+            Template::Mailbox => "mailbox",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Template, String> {
+        TEMPLATES.into_iter().find(|t| t.name() == name).ok_or_else(|| {
+            let known: Vec<&str> = TEMPLATES.iter().map(|t| t.name()).collect();
+            format!("no template called '{name}' (there's {})", known.join(", "))
+        })
+    }
+
+    pub fn layout(self) -> Layout {
+        let numbered = |prefix: &str, count: usize, subkeys: u16| -> Vec<(String, u16)> {
+            (1..=count).map(|i| (format!("{prefix}-{i}"), subkeys)).collect()
+        };
+        let mut layout = match self {
+            Template::Chat => Layout {
+                owner_subkeys: 1,
+                members: numbered("member", 4, 8),
+                payload_types: BTreeMap::new(),
+                inbox: None,
+            },
+            Template::KvStore => Layout {
+                owner_subkeys: 1,
+                members: vec![("writer".to_string(), 32)],
+                payload_types: BTreeMap::new(),
+                inbox: None,
+            },
+            Template::StatusPage => Layout {
+                owner_subkeys: 2,
+                members: numbered("service", 8, 1),
+                payload_types: BTreeMap::from([(1, "presence".to_string())]),
+                inbox: None,
+            },
+            Template::Mailbox => Layout {
+                owner_subkeys: 2,
+                members: vec![("inbox".to_string(), 8)],
+                payload_types: BTreeMap::new(),
+                inbox: Some(0),
+            },
+        };
+        layout.payload_types.insert(metadata::METADATA_SUBKEY, "metadata".to_string());
+        let member_type = match self {
+            Template::Chat => Some("chat"),
+            Template::StatusPage => Some("presence"),
+            Template::KvStore | Template::Mailbox => None,
+        };
+        if let Some(t) = member_type {
+            for subkey in ValueSubkey::from(layout.owner_subkeys)..layout.total_subkeys() {
+                layout.payload_types.insert(subkey, t.to_string());
+            }
+        }
+        layout
+    }
+}
+
+// -------------------------------------------------------------------------
+// record new --template <t>
+// -------------------------------------------------------------------------
+
+pub async fn create(template: &str, options: &Options, config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let template = Template::from_name(template)?;
+    let layout = template.layout();
+    let data_dir = config.data_dir()?;
+    let mut book = ShortcodeBook::load(&data_dir)?;
+
+    let (veilid, rc) = start_tool_node(options, config).await?;
+    let counts: Vec<u16> = layout.members.iter().map(|(_, n)| *n).collect();
+    let writers = FreshWriters::for_shape(&veilid, layout.owner_subkeys, &counts)?;
+    let key = if rc.is_dry_run() {
+        rc.plan_create_dht_record(writers.schema.clone(), writers.owner.clone())
+            .await?
+    } else {
+        progress::spin(
+            &format!("Creating a {} record", template.name()),
+            rc.create_dht_record(CRYPTO_KIND_VLD0, writers.schema.clone(), Some(writers.owner.clone())),
+        )
+        .await?
+        .key()
+    };
+
+    let mut roster = vec![RosterEntry {
+        name: "record-owner".to_string(),
+        key: writers.owner.key().to_string(),
+        first_subkey: 0,
+        last_subkey: ValueSubkey::from(layout.owner_subkeys) - 1,
+        fields: Default::default(),
+    }];
+    for (i, ((name, _), (kp, first, count))) in layout.members.iter().zip(&writers.members).enumerate() {
+        let last = *first + ValueSubkey::from(*count) - 1;
+        // a member's plain writes go to its first subkey; the inbox is only for mail
+        let fields = if layout.inbox == Some(i) {
+            BTreeMap::new()
+        } else {
+            fields::layout(&config.shared_fields, *first, last, Some(*first))
+        };
+        roster.push(RosterEntry {
+            name: name.clone(),
+            key: kp.key().to_string(),
+            first_subkey: *first,
+            last_subkey: last,
+            fields,
+        });
+    }
+    let title = format!("{} ({})", config.record_title, template.name());
+    let mut meta = RecordMetadata::new(&title, &writers.schema, roster);
+    meta.payload_types = layout.payload_types.clone();
+    meta.mailbox = layout.inbox.map(|i| {
+        let (drop, first, count) = &writers.members[i];
+        MailboxInfo {
+            drop_writer: drop.to_string(),
+            recipient: writers.owner.key().to_string(),
+            first_subkey: *first,
+            last_subkey: *first + ValueSubkey::from(*count) - 1,
+            receipts_subkey: Some(RECEIPTS_SUBKEY),
+        }
+    });
+    let owner_opts = SetDHTValueOptions {
+        writer: Some(writers.owner.clone()),
+        allow_offline: None,
+    };
+    if let Err(e) = rc
+        .set_dht_value(key.clone(), metadata::METADATA_SUBKEY, meta.encode(), Some(owner_opts))
+        .await
+    {
+        println!("Made the record, but couldn't write its metadata to subkey 0: {e}");
+    }
+    let _ = rc.close_dht_record(key.clone()).await;
+    veilid.shutdown().await;
+
+    println!();
+    println!("Made a {} record:", template.name());
+    println!("{}", meta.display());
+    let types: Vec<String> = layout.payload_types.iter().map(|(s, t)| format!("{s}={t}")).collect();
+    println!("  payload types: {}", types.join(","));
+    let code = if rc.is_dry_run() {
+        crate::shortcode::shortcode(&key)
+    } else {
+        book.remember(&key)?
+    };
+    writers.print_credentials(&key, &code);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_template_types_only_its_own_subkeys() {
+        for template in TEMPLATES {
+            let layout = template.layout();
+            assert_eq!(Template::from_name(template.name()), Ok(template));
+            assert!(layout.owner_subkeys >= 1, "{template:?} has nowhere for the metadata block");
+            assert_eq!(layout.payload_types.get(&0).map(String::as_str), Some("metadata"));
+            assert!(layout.payload_types.keys().all(|&s| s < layout.total_subkeys()), "{template:?}");
+            assert!(crate::payloads::Registry::new(&layout.payload_types).is_ok(), "{template:?}");
+        }
+        let chat = Template::Chat.layout();
+        assert_eq!(chat.total_subkeys(), 33);
+        assert_eq!(chat.first_member_subkey(1), 9);
+        assert_eq!(chat.payload_types.get(&32).map(String::as_str), Some("chat"));
+        // the mailbox's receipts go in an owner subkey
+        let mailbox = Template::Mailbox.layout();
+        assert!(RECEIPTS_SUBKEY < ValueSubkey::from(mailbox.owner_subkeys));
+        assert!(Template::from_name("wiki").unwrap_err().contains("statuspage"));
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	--transcript <file>: what was typed at the node prompts and what they
+//	printed back, with the time, in a file that reads as Markdown, for
+//	writing a tutorial or attaching to a bug report:
+//
+//	  > 14:03:27 `alt> read 3`
+//
+//	      [read] subkey 3 (alice): hello
+//
+//	Each command is a quote line and what it printed is indented under it,
+//	so it shows as a code block. The commands on their own, to type again
+//	or turn into a scenario, are the quote lines:
+//
+//	  sed -n 's/^> [0-9:]* `[a-z]*> \(.*\)`$/\1/p' transcript.md
+//
+//	Only what the prompts print with say! goes in, not spinners or progress
+//	bars, and colour is taken out. The file is appended to, so one file can
+//	hold several runs, each under a heading of its own.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+static TRANSCRIPT: OnceLock<Mutex<File>> = OnceLock::new();
+
+// println!, and into the transcript if there is one.
+macro_rules! say {
+    () => {{
+        println!();
+        $crate::transcript::output("");
+    }};
+    ($($arg:tt)*) => {{
+        let text = format!($($arg)*);
+        println!("{text}");
+        $crate::transcript::output(&text);
+    }};
+}
+
+// "14:03:27" (UTC)
+fn clock(ms: u64) -> String {
+    let secs = ms / 1000 % 86_400;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+// Without the ANSI colour codes.
+fn plain(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // ESC [ ... m
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub fn start(path: &Path) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("couldn't open the transcript {}: {e}", path.to_string_lossy()))?;
+    let now = crate::audit::now_ms() as u64;
+    writeln!(file, "\n## Session at {} UTC (unix {})\n", clock(now), now / 1000).map_err(|e| e.to_string())?;
+    TRANSCRIPT
+        .set(Mutex::new(file))
+        .map_err(|_| "there's a transcript already".to_string())
+}
+
+fn with(write: impl FnOnce(&mut File) -> std::io::Result<()>) {
+    if let Some(transcript) = TRANSCRIPT.get() {
+        // a transcript that can't be written to isn't worth stopping the node for
+        let _ = write(&mut transcript.lock().unwrap());
+    }
+}
+
+pub fn command(prompt: &str, line: &str) {
+    with(|file| writeln!(file, "> {} `{prompt}{line}`\n", clock(crate::audit::now_ms() as u64)));
+}
+
+pub fn output(text: &str) {
+    with(|file| {
+        if text.is_empty() {
+            return writeln!(file);
+        }
+        for line in plain(text).lines() {
+            writeln!(file, "    {line}")?;
+        }
+        Ok(())
+    });
+}
+
+pub(crate) use say;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colour_is_taken_out_and_the_time_is_utc() {
+        assert_eq!(plain("\x1b[36m[read]\x1b[0m subkey 3"), "[read] subkey 3");
+        assert_eq!(plain("no colour"), "no colour");
+        assert_eq!(clock(3_723_000), "01:02:03");
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use veilid_core::*;
+
+use crate::audit::now_ms;
+use crate::stats::Latency;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`stats trend`: today's DHT call times against the days before, to see
+//	whether the network (or this machine's setup) has got slower over the
+//	weeks, not just in the last few calls as the prompt's arrows show.
+//
+//	Each call's time goes into a total per kind of call (get, set, ...)
+//	per day, kept in the role's table store and saved every SAVE_EVERY, on
+//	the way out and when the trend is asked for. KEEP_DAYS days are kept;
+//	all of them before today make the baseline.
+//
+//	  call     today               baseline
+//	  get      420ms 0% (57)       210ms 1% (1.9k)      slower, x2.0
+//
+//	Times are means, the percentage is calls that failed, and a call with
+//	fewer than MIN_CALLS on either side isn't judged.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const TABLE: &str = "metrics";
+const COL_DAYS: u32 = 0;
+const KEY: &[u8] = b"days";
+const KEEP_DAYS: u64 = 30;
+const DAY_MS: u128 = 24 * 60 * 60 * 1000;
+pub const SAVE_EVERY: Duration = Duration::from_secs(5 * 60);
+// how far today's mean can be from the baseline's and still be usual
+const DRIFT: f64 = 0.25;
+// how many more calls in a hundred can fail before it's worth saying
+const MORE_ERRORS: f64 = 0.05;
+const MIN_CALLS: u64 = 5;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpTotals {
+    pub calls: u64,
+    pub errors: u64,
+    pub total_ms: u64,
+}
+
+impl OpTotals {
+    pub fn add(&mut self, latency_ms: u128, ok: bool) {
+        self.calls += 1;
+        self.errors += u64::from(!ok);
+        self.total_ms = self.total_ms.saturating_add(u64::try_from(latency_ms).unwrap_or(u64::MAX));
+    }
+
+    fn merge(&mut self, other: &OpTotals) {
+        self.calls += other.calls;
+        self.errors += other.errors;
+        self.total_ms = self.total_ms.saturating_add(other.total_ms);
+    }
+
+    fn mean_ms(&self) -> f64 {
+        self.total_ms as f64 / self.calls.max(1) as f64
+    }
+
+    fn error_rate(&self) -> f64 {
+        self.errors as f64 / self.calls.max(1) as f64
+    }
+
+    // "210ms 1% (1.9k)"
+    fn describe(&self) -> String {
+        let calls = if self.calls >= 1000 {
+            format!("{:.1}k", self.calls as f64 / 1000.0)
+        } else {
+            self.calls.to_string()
+        };
+        let mean = self.mean_ms();
+        let mean = if mean < 1000.0 { format!("{mean:.0}ms") } else { format!("{:.1}s", mean / 1000.0) };
+        format!("{mean} {:.0}% ({calls})", self.error_rate() * 100.0)
+    }
+}
+
+// day (days since 1970, UTC) -> call -> totals
+type Days = BTreeMap<u64, BTreeMap<String, OpTotals>>;
+
+fn today() -> u64 {
+    (now_ms() / DAY_MS) as u64
+}
+
+// What today's totals say next to the baseline's.
+fn verdict(now: &OpTotals, base: &OpTotals) -> String {
+    if now.calls < MIN_CALLS || base.calls < MIN_CALLS {
+        return "too few calls to tell".to_string();
+    }
+    let mut parts = Vec::new();
+    let ratio = now.mean_ms() / base.mean_ms().max(1.0);
+    if ratio > 1.0 + DRIFT {
+        parts.push(format!("slower, x{ratio:.1}"));
+    } else if ratio < 1.0 - DRIFT {
+        parts.push(format!("faster, x{ratio:.1}"));
+    }
+    if now.error_rate() >= base.error_rate() + MORE_ERRORS {
+        parts.push("more failures".to_string());
+    }
+    if parts.is_empty() {
+        "as usual".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+// The table `stats trend` prints, for `today` against every day before it.
+fn trend(days: &Days, today: u64) -> String {
+    let mut baseline: BTreeMap<&str, OpTotals> = BTreeMap::new();
+    for (_, ops) in days.range(..today) {
+        for (op, totals) in ops {
+            baseline.entry(op.as_str()).or_default().merge(totals);
+        }
+    }
+    let empty = BTreeMap::new();
+    let now = days.get(&today).unwrap_or(&empty);
+    let ops: BTreeSet<&str> = baseline.keys().copied().chain(now.keys().map(String::as_str)).collect();
+    if ops.is_empty() {
+        return "No DHT calls kept yet; the trend builds up as the node is used".to_string();
+    }
+
+    let base_days = days.range(..today).count();
+    let mut lines = vec![
+        format!("DHT calls today against the {base_days} day(s) before (mean, failed, calls):"),
+        format!("  {:<8} {:<20} {:<20}", "call", "today", "baseline"),
+    ];
+    for op in ops {
+        let (now, base) = (now.get(op), baseline.get(op));
+        let show = |t: Option<&OpTotals>| t.map_or("-".to_string(), OpTotals::describe);
+        let judged = match (now, base) {
+            (Some(now), Some(base)) => verdict(now, base),
+            (Some(_), None) => "new today".to_string(),
+            (None, _) => "not called today".to_string(),
+        };
+        lines.push(format!("  {op:<8} {:<20} {:<20} {judged}", show(now), show(base)));
+    }
+    lines.join("\n")
+}
+
+// The days kept in the table store, and the Latency whose calls go into them.
+pub struct MetricsStore {
+    // None in tests: kept in memory only
+    db: Option<TableDB>,
+    days: Days,
+    latency: Arc<Latency>,
+}
+
+impl MetricsStore {
+    pub async fn open(api: &VeilidAPI, latency: Arc<Latency>) -> VeilidAPIResult<MetricsStore> {
+        let db = api.table_store()?.open(TABLE, 1).await?;
+        let days = db.load_json::<Days>(COL_DAYS, KEY).await?.unwrap_or_default();
+        Ok(MetricsStore { db: Some(db), days, latency })
+    }
+
+    #[cfg(test)]
+    fn in_memory(latency: Arc<Latency>) -> MetricsStore {
+        MetricsStore {
+            db: None,
+            days: Days::new(),
+            latency,
+        }
+    }
+
+    // Add the calls made since last time to today's totals and save them.
+    pub async fn save(&mut self) -> VeilidAPIResult<()> {
+        let today = today();
+        let day = self.days.entry(today).or_default();
+        for (op, totals) in self.latency.take_unsaved() {
+            day.entry(op).or_default().merge(&totals);
+        }
+        self.days.retain(|&d, _| d + KEEP_DAYS > today);
+        match &self.db {
+            Some(db) => db.store_json(COL_DAYS, KEY, &self.days).await,
+            None => Ok(()),
+        }
+    }
+
+    // `stats trend` at the prompt.
+    pub async fn report(&mut self) -> String {
+        let saved = self.save().await;
+        let mut out = trend(&self.days, today());
+        if let Err(e) = saved {
+            out.push_str(&format!("\n(couldn't save today's calls: {e})"));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totals(calls: u64, mean_ms: u64, errors: u64) -> OpTotals {
+        OpTotals {
+            calls,
+            errors,
+            total_ms: calls * mean_ms,
+        }
+    }
+
+    #[tokio::test]
+    async fn today_is_judged_against_the_days_before() {
+        let mut days = Days::new();
+        days.insert(10, BTreeMap::from([("get".to_string(), totals(100, 200, 1)), ("set".to_string(), totals(40, 500, 0))]));
+        days.insert(11, BTreeMap::from([("get".to_string(), totals(100, 220, 1)), ("watch".to_string(), totals(9, 300, 0))]));
+        days.insert(
+            12,
+            BTreeMap::from([
+                ("get".to_string(), totals(20, 630, 0)),
+                ("set".to_string(), totals(3, 100, 0)),
+                ("open".to_string(), totals(6, 50, 0)),
+            ]),
+        );
+
+        let text = trend(&days, 12);
+        assert!(text.contains("against the 2 day(s) before"), "{text}");
+        assert!(text.contains("get      630ms 0% (20)        210ms 1% (200)       slower, x3.0"), "{text}");
+        assert!(text.contains("set      100ms 0% (3)         500ms 0% (40)        too few calls to tell"), "{text}");
+        assert!(text.contains("open     50ms 0% (6)          -                    new today"), "{text}");
+        assert!(text.contains("watch    -                    300ms 0% (9)         not called today"), "{text}");
+        assert!(trend(&Days::new(), 12).starts_with("No DHT calls"));
+
+        // what the node's Latency saw goes into today's totals
+        let latency = Arc::new(Latency::new());
+        latency.record("get", 100, true);
+        latency.record("get", 300, false);
+        let mut store = MetricsStore::in_memory(latency.clone());
+        store.save().await.unwrap();
+        assert_eq!(store.days[&today()]["get"], totals(2, 200, 1));
+        assert!(latency.take_unsaved().is_empty());
+    }
+}
@@ -0,0 +1,249 @@
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use veilid_core::*;
+
+use crate::config::AppConfig;
+use crate::dht::Dht;
+use crate::envelope::{self, Envelope};
+use crate::node::VeilidNode;
+use crate::progress;
+use crate::watch::WatchSet;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	--tutorial: the walkthrough in app.rs's comments, run for real.
+//
+//	Two nodes are started in this one process, each in a namespace of its
+//	own (<default_namespace>-tutorial-a and -b), and taken through:
+//
+//	  1 attach        node A joins the network
+//	  2 create        A makes an SMPL record: one owner subkey, two member ones
+//	  3 write         A writes subkey 1 as the member and reads it back
+//	  4 join          node B starts, opens the record and reads what A wrote
+//	  5 watch         B watches subkey 1, A writes again, B hears about it
+//
+//	Each step says what's about to happen and which calls do it, waits for
+//	ENTER, then checks it actually worked before going on. A step that
+//	fails stops the tutorial with what went wrong and what usually fixes it.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// How long B waits for its value change before giving up.
+const WATCH_WAIT: Duration = Duration::from_secs(90);
+// How many times B tries to read A's value while it spreads across the DHT.
+const READ_TRIES: u32 = 20;
+
+struct Step {
+    number: u32,
+    title: &'static str,
+    explain: &'static str,
+    // what to try when it fails
+    hint: &'static str,
+}
+
+const STEPS: [Step; 5] = [
+    Step {
+        number: 1,
+        title: "Attach",
+        explain: "Veilid starts with api_startup_config(), given a VeilidConfig saying where its\n\
+                  stores live (see node::node_config). Then api.attach() asks it to join the\n\
+                  network, and VeilidUpdate::Attachment updates say how that's going. We wait\n\
+                  until it reports the node attached.",
+        hint: "check the machine is online; the first attach can take a minute or two",
+    },
+    Step {
+        number: 2,
+        title: "Create a record",
+        explain: "A DHT record has a schema saying who may write which subkeys. An SMPL schema\n\
+                  gives the owner some subkeys and each member (a keypair's member id) some\n\
+                  more. We make one with 1 owner subkey and 2 for a member, using\n\
+                  routing_context.create_dht_record(). The record key comes back in its\n\
+                  descriptor, along with the owner keypair Veilid made for it.",
+        hint: "the node may not be fully routable yet; wait a little and run the tutorial again",
+    },
+    Step {
+        number: 3,
+        title: "Write a value",
+        explain: "set_dht_value() writes one subkey. Subkey 1 is the member's, so the write is\n\
+                  signed with the member keypair (SetDHTValueOptions::writer). Then\n\
+                  get_dht_value(.., force_refresh = true) asks the network for it back.",
+        hint: "writes need peers to store them on; give the node a minute and try again",
+    },
+    Step {
+        number: 4,
+        title: "A second node joins",
+        explain: "Node B knows nothing but the record key (in the real program it comes from\n\
+                  owner_keys.txt or a share code). It opens the record with open_dht_record()\n\
+                  and no writer, so it can read but not write, and reads subkey 1.",
+        hint: "new values take a moment to spread; if it keeps failing, both nodes may be on a poor connection",
+    },
+    Step {
+        number: 5,
+        title: "Watch for changes",
+        explain: "watch_dht_values() asks the nodes holding the record to tell B when subkeys\n\
+                  change. The news arrives as VeilidUpdate::ValueChange, which this program\n\
+                  turns into a stream (see watch.rs). Node A writes subkey 1 again, and B\n\
+                  should hear about it without asking.",
+        hint: "watches need the record's holders to reach B; try again, or see `stats watch` in the alt node",
+    },
+];
+
+fn pause(step: &Step) {
+    println!();
+    println!("--- Step {} of {}: {} ---", step.number, STEPS.len(), step.title);
+    println!("{}", step.explain);
+    print!("Press ENTER to go on ");
+    let _ = io::stdout().flush();
+    let _ = io::stdin().lock().read_line(&mut String::new());
+}
+
+fn passed(detail: impl AsRef<str>) {
+    println!("  ok: {}", detail.as_ref());
+}
+
+fn failed(step: &Step, what: impl AsRef<str>) -> Box<dyn std::error::Error> {
+    println!("  FAILED: {}", what.as_ref());
+    println!("  usually: {}", step.hint);
+    format!("the tutorial stopped at step {} ({})", step.number, step.title).into()
+}
+
+pub async fn run(config: &AppConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = config.data_dir()?;
+    println!("Veilid DHT tutorial: two nodes, one record, a write and a watch.");
+    println!("Everything runs in this process; nothing touches your default or alt node's storage.");
+
+    // ---- 1: attach ----
+    let step = &STEPS[0];
+    pause(step);
+    let ns_a = format!("{}-tutorial-a", config.default_namespace);
+    let node_a = progress::spin("Attaching node A", VeilidNode::start_attached(config, &data_dir, &ns_a, |_| {}))
+        .await
+        .map_err(|e| failed(step, e.to_string()))?;
+    let state = node_a.api().get_state().await.map_err(|e| failed(step, e.to_string()))?;
+    passed(format!("node A is {:?}", state.attachment.state));
+    let rc_a = Dht::new(node_a.routing_context().get(), None, false, None);
+
+    // ---- 2: create ----
+    let step = &STEPS[1];
+    pause(step);
+    let member = Crypto::generate_keypair(CRYPTO_KIND_VLD0)?;
+    let member_id = node_a.api().generate_member_id(&member.key())?;
+    let schema = DHTSchema::smpl(
+        1,
+        vec![DHTSchemaSMPLMember {
+            m_key: member_id.into_value(),
+            m_cnt: 2,
+        }],
+    )?;
+    let desc = rc_a
+        .create_dht_record(CRYPTO_KIND_VLD0, schema, None)
+        .await
+        .map_err(|e| failed(step, e.to_string()))?;
+    let key = desc.key();
+    if desc.schema().max_subkey() != 2 {
+        return Err(failed(step, format!("the record has subkeys 0..={}, expected 0..=2", desc.schema().max_subkey())));
+    }
+    passed(format!("record {key}, subkeys 0..=2, owner {}", desc.owner()));
+
+    // ---- 3: write ----
+    let step = &STEPS[2];
+    pause(step);
+    let as_member = SetDHTValueOptions {
+        writer: Some(member.clone()),
+        allow_offline: None,
+    };
+    let first = "hello from node A";
+    rc_a.set_dht_value(key.clone(), 1, Envelope::text(first).encode(), Some(as_member.clone()))
+        .await
+        .map_err(|e| failed(step, e.to_string()))?;
+    match rc_a.get_dht_value(key.clone(), 1, true).await {
+        Ok(Some(v)) if envelope::display_value(v.data()) == first => passed(format!("subkey 1 reads back \"{first}\" (seq {:?})", v.seq())),
+        Ok(Some(v)) => return Err(failed(step, format!("read back \"{}\"", envelope::display_value(v.data())))),
+        Ok(None) => return Err(failed(step, "the network has no value for subkey 1")),
+        Err(e) => return Err(failed(step, e.to_string())),
+    }
+
+    // ---- 4: join ----
+    let step = &STEPS[3];
+    pause(step);
+    let ns_b = format!("{}-tutorial-b", config.default_namespace);
+    let node_b = progress::spin("Attaching node B", VeilidNode::start_attached(config, &data_dir, &ns_b, |_| {}))
+        .await
+        .map_err(|e| failed(step, e.to_string()))?;
+    let rc_b = Dht::new(node_b.routing_context().get(), None, false, None);
+    let _ = rc_b.open_dht_record(key.clone(), None)
+        .await
+        .map_err(|e| failed(step, e.to_string()))?;
+    let mut seen = None;
+    for _ in 0..READ_TRIES {
+        if let Ok(Some(v)) = rc_b.get_dht_value(key.clone(), 1, true).await {
+            seen = Some(envelope::display_value(v.data()));
+            if seen.as_deref() == Some(first) {
+                break;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    match seen {
+        Some(text) if text == first => passed(format!("node B reads \"{text}\"")),
+        Some(text) => return Err(failed(step, format!("node B reads \"{text}\", not what A wrote"))),
+        None => return Err(failed(step, "node B couldn't read subkey 1")),
+    }
+
+    // ---- 5: watch ----
+    let step = &STEPS[4];
+    pause(step);
+    let changes = WatchSet::new();
+    node_b
+        .watch(&changes, key.clone(), ValueSubkeyRangeSet::single(1))
+        .await
+        .map_err(|e| failed(step, e.to_string()))?;
+    passed("node B's watch is placed");
+    let second = "hello again from node A";
+    rc_a.set_dht_value(key.clone(), 1, Envelope::text(second).encode(), Some(as_member))
+        .await
+        .map_err(|e| failed(step, e.to_string()))?;
+    let waiting = progress::spinner("Node B waiting for the change...");
+    let change = tokio::time::timeout(WATCH_WAIT, async {
+        loop {
+            match changes.next().await {
+                Some(change) if !change.watch_died => return Some(change),
+                Some(_) => continue,
+                None => return None,
+            }
+        }
+    })
+    .await;
+    waiting.finish_and_clear();
+    match change {
+        Ok(Some(change)) => {
+            // Veilid doesn't always send the value along, so B reads it if not
+            let text = match change.value {
+                Some(value) => value.display(),
+                None => rc_b
+                    .get_dht_value(key.clone(), 1, true)
+                    .await
+                    .ok()
+                    .flatten()
+                    .map(|v| envelope::display_value(v.data()))
+                    .unwrap_or_default(),
+            };
+            passed(format!("node B's watch fired for subkeys {}: \"{text}\"", change.subkeys));
+        }
+        Ok(None) => return Err(failed(step, "the watch stream closed")),
+        Err(_) => return Err(failed(step, format!("nothing arrived in {}s", WATCH_WAIT.as_secs()))),
+    }
+
+    let _ = rc_b.close_dht_record(key.clone()).await;
+    let _ = rc_a.close_dht_record(key).await;
+    node_b.api().clone().shutdown().await;
+    node_a.api().clone().shutdown().await;
+
+    println!();
+    println!("That's the whole loop. The real thing is the same calls with a prompt on top:");
+    println!("run the program again without --tutorial, pick 1 for the default node (it");
+    println!("creates and writes), then in another terminal pick 2 for the alt node (it");
+    println!("joins and watches). Type 'help' at either prompt for the rest.");
+    Ok(())
+}
@@ -0,0 +1,372 @@
+use std::sync::Mutex;
+
+use tokio_stream::Stream;
+
+use veilid_core::*;
+
+use crate::envelope::{Envelope, EnvelopeError};
+use crate::shortcode;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	The watch manager: hands out ValueChange updates to whoever asked for them.
+//
+//	Veilid gives us one watch per record and delivers every change through
+//	the update callback. Anything that wants to follow records (the alt
+//	node's prompt, a GUI, a bridge, a test) makes a WatchSet, adds records to
+//	it with the subkeys it cares about, and gets one stream of decoded
+//	changes from all of them, each tagged with the record it came from:
+//
+//	  let changes = WatchSet::new();
+//	  node.watch(&changes, record_key, ValueSubkeyRangeSet::full()).await?;
+//	  while let Some(change) = changes.next().await { ... change.shortcode() ... }
+//
+//	A record's watch covers every subkey anyone asked for. Dropping the set
+//	unsubscribes (the watches themselves stay until they expire).
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// A ValueChange, cut down to the subkeys one subscriber asked for.
+#[derive(Clone, Debug)]
+pub struct DecodedChange {
+    pub record: RecordKey,
+    // the changed subkeys we're subscribed to (empty when the watch died)
+    pub subkeys: ValueSubkeyRangeSet,
+    // Veilid only sends the first changed subkey's value along, and not always that
+    pub value: Option<ChangedValue>,
+    // The network dropped the watch; nothing more arrives until it's placed again.
+    pub watch_died: bool,
+}
+
+impl DecodedChange {
+    // Which record this came from, in words.
+    pub fn shortcode(&self) -> String {
+        shortcode::shortcode(&self.record)
+    }
+
+    // The changed subkeys whose new value didn't come along, to be read.
+    pub fn unsent(&self) -> Vec<ValueSubkey> {
+        let sent = self.value.as_ref().map(|v| v.subkey);
+        self.subkeys.iter().filter(|s| Some(*s) != sent).collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChangedValue {
+    pub subkey: ValueSubkey,
+    pub seq: ValueSeqNum,
+    pub writer: PublicKey,
+    pub envelope: Result<Envelope, EnvelopeError>,
+}
+
+impl ChangedValue {
+    pub fn new(subkey: ValueSubkey, data: &ValueData) -> ChangedValue {
+        ChangedValue {
+            subkey,
+            seq: data.seq(),
+            writer: data.writer(),
+            envelope: Envelope::decode(data.data()),
+        }
+    }
+
+    // An expired value (or a janitor's tombstone) counts as gone. One that
+    // doesn't decode is still news.
+    pub fn is_live(&self, now_ms: u64) -> bool {
+        match &self.envelope {
+            Ok(env) => env.is_live(now_ms),
+            Err(_) => true,
+        }
+    }
+
+    // What to print for the value, the same as a read would show.
+    pub fn display(&self) -> String {
+        match &self.envelope {
+            Ok(env) if !env.is_live(crate::audit::now_ms() as u64) => "<expired>".to_string(),
+            Ok(env) => env.display(),
+            Err(e) => format!("<{e}>"),
+        }
+    }
+}
+
+struct Subscriber {
+    record: RecordKey,
+    subkeys: ValueSubkeyRangeSet,
+    tx: flume::Sender<DecodedChange>,
+}
+
+#[derive(Default)]
+pub struct WatchManager {
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl WatchManager {
+    pub fn new() -> WatchManager {
+        WatchManager::default()
+    }
+
+    // Register a subscriber. An empty `subkeys` means the whole record.
+    // Changes go down `tx`, which can be shared between records so one
+    // receiver hears from all of them. Gives back the subkeys the record's
+    // watch needs to cover now (None for all of them, which is how
+    // watch_dht_values wants it).
+    pub fn add(
+        &self,
+        tx: flume::Sender<DecodedChange>,
+        record: RecordKey,
+        subkeys: ValueSubkeyRangeSet,
+    ) -> Option<ValueSubkeyRangeSet> {
+        let subkeys = if subkeys.is_empty() {
+            ValueSubkeyRangeSet::full()
+        } else {
+            subkeys
+        };
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|s| !s.tx.is_disconnected());
+        subscribers.push(Subscriber {
+            record: record.clone(),
+            subkeys,
+            tx,
+        });
+
+        let watched = subscribers
+            .iter()
+            .filter(|s| s.record == record)
+            .fold(ValueSubkeyRangeSet::new(), |acc, s| acc.union(&s.subkeys));
+        (!watched.is_full()).then_some(watched)
+    }
+
+    // Records someone is still listening to.
+    pub fn records(&self) -> Vec<RecordKey> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|s| !s.tx.is_disconnected());
+        let mut records: Vec<RecordKey> = Vec::new();
+        for sub in subscribers.iter() {
+            if !records.contains(&sub.record) {
+                records.push(sub.record.clone());
+            }
+        }
+        records
+    }
+
+    // Call this from the update callback for every VeilidUpdate::ValueChange.
+    pub fn dispatch(&self, change: &VeilidValueChange) {
+        // An empty subkey range or a zero count means the watch has died.
+        let died = change.subkeys.is_empty() || change.count == 0;
+
+        let first = change.subkeys.nth_subkey(0);
+        let value = match (first, &change.value) {
+            (Some(subkey), Some(data)) if !died => Some(ChangedValue::new(subkey, data)),
+            _ => None,
+        };
+        // nobody should hear about a value that has already expired
+        if value.as_ref().is_some_and(|v| !v.is_live(crate::audit::now_ms() as u64)) {
+            return;
+        }
+
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|s| !s.tx.is_disconnected());
+        for sub in subscribers.iter().filter(|s| s.record == change.key) {
+            let decoded = if died {
+                DecodedChange {
+                    record: change.key.clone(),
+                    subkeys: ValueSubkeyRangeSet::new(),
+                    value: None,
+                    watch_died: true,
+                }
+            } else {
+                let subkeys = change.subkeys.intersect(&sub.subkeys);
+                if subkeys.is_empty() {
+                    continue;
+                }
+                DecodedChange {
+                    record: change.key.clone(),
+                    value: value
+                        .clone()
+                        .filter(|v| sub.subkeys.contains(v.subkey)),
+                    subkeys,
+                    watch_died: false,
+                }
+            };
+            let _ = sub.tx.send(decoded);
+        }
+    }
+}
+
+// Changes from any number of records, out of one channel.
+pub struct WatchSet {
+    tx: flume::Sender<DecodedChange>,
+    rx: flume::Receiver<DecodedChange>,
+}
+
+impl Default for WatchSet {
+    fn default() -> WatchSet {
+        let (tx, rx) = flume::unbounded();
+        WatchSet { tx, rx }
+    }
+}
+
+impl WatchSet {
+    pub fn new() -> WatchSet {
+        WatchSet::default()
+    }
+
+    // Start hearing about `record` too. Gives back what the record's watch
+    // needs to cover, as WatchManager::add does.
+    pub fn add(&self, watches: &WatchManager, record: RecordKey, subkeys: ValueSubkeyRangeSet) -> Option<ValueSubkeyRangeSet> {
+        watches.add(self.tx.clone(), record, subkeys)
+    }
+
+    // The next change from any of the records. Cancellation safe.
+    pub async fn next(&self) -> Option<DecodedChange> {
+        self.rx.recv_async().await.ok()
+    }
+
+    // The same changes as a stream, which ends once every record in the set
+    // has stopped being watched.
+    pub fn into_stream(self) -> impl Stream<Item = DecodedChange> + Unpin {
+        self.rx.into_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> RecordKey {
+        RecordKey::new(
+            CRYPTO_KIND_VLD0,
+            BareRecordKey::new(BareOpaqueRecordKey::new(&[byte; 32]), None),
+        )
+    }
+
+    fn subscribe(
+        watches: &WatchManager,
+        record: RecordKey,
+        subkeys: ValueSubkeyRangeSet,
+    ) -> (flume::Receiver<DecodedChange>, Option<ValueSubkeyRangeSet>) {
+        let (tx, rx) = flume::unbounded();
+        let watched = watches.add(tx, record, subkeys);
+        (rx, watched)
+    }
+
+    fn change(record: &RecordKey, subkeys: ValueSubkeyRangeSet, count: u32, text: Option<&str>) -> VeilidValueChange {
+        let writer = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap().key();
+        VeilidValueChange {
+            key: record.clone(),
+            subkeys,
+            count,
+            value: text.map(|t| {
+                ValueData::new_with_seq(ValueSeqNum::from(3), Envelope::text(t).encode(), writer).unwrap()
+            }),
+        }
+    }
+
+    #[test]
+    fn only_matching_records_and_subkeys_arrive() {
+        let watches = WatchManager::new();
+        let (all, watched) = subscribe(&watches, key(1), ValueSubkeyRangeSet::new());
+        assert!(watched.is_none());
+        let (only_two, _) = subscribe(&watches, key(1), ValueSubkeyRangeSet::single(2));
+        let (other, _) = subscribe(&watches, key(9), ValueSubkeyRangeSet::new());
+
+        watches.dispatch(&change(&key(1), ValueSubkeyRangeSet::single(0), 5, Some("hi")));
+
+        let got = all.try_recv().unwrap();
+        assert_eq!(got.record, key(1));
+        let value = got.value.unwrap();
+        assert_eq!(value.subkey, 0);
+        assert_eq!(value.seq, ValueSeqNum::from(3));
+        assert_eq!(value.display(), "hi");
+        assert!(only_two.try_recv().is_err());
+        assert!(other.try_recv().is_err());
+    }
+
+    #[test]
+    fn value_only_goes_to_subscribers_of_the_first_subkey() {
+        let watches = WatchManager::new();
+        let (only_two, watched) = subscribe(&watches, key(1), ValueSubkeyRangeSet::single(2));
+        assert_eq!(watched, Some(ValueSubkeyRangeSet::single(2)));
+
+        watches.dispatch(&change(&key(1), ValueSubkeyRangeSet::single_range(1, 3), 5, Some("hi")));
+
+        let got = only_two.try_recv().unwrap();
+        assert_eq!(got.subkeys, ValueSubkeyRangeSet::single(2));
+        assert!(got.value.is_none());
+        assert_eq!(got.unsent(), vec![2]);
+    }
+
+    #[test]
+    fn watch_covers_every_subscriber() {
+        let watches = WatchManager::new();
+        let _a = subscribe(&watches, key(1), ValueSubkeyRangeSet::single(0));
+        let (_b, watched) = subscribe(&watches, key(1), ValueSubkeyRangeSet::single(4));
+        assert_eq!(
+            watched,
+            Some(ValueSubkeyRangeSet::single(0).union(&ValueSubkeyRangeSet::single(4)))
+        );
+    }
+
+    #[test]
+    fn a_set_hears_from_every_record_in_it() {
+        let watches = WatchManager::new();
+        let set = WatchSet::new();
+        set.add(&watches, key(1), ValueSubkeyRangeSet::new());
+        set.add(&watches, key(2), ValueSubkeyRangeSet::single(0));
+        assert_eq!(watches.records(), vec![key(1), key(2)]);
+
+        watches.dispatch(&change(&key(2), ValueSubkeyRangeSet::single(0), 5, Some("two")));
+        watches.dispatch(&change(&key(1), ValueSubkeyRangeSet::single(3), 5, Some("one")));
+        watches.dispatch(&change(&key(9), ValueSubkeyRangeSet::single(0), 5, Some("nine")));
+
+        let first = set.rx.try_recv().unwrap();
+        assert_eq!(first.record, key(2));
+        assert_eq!(first.shortcode(), shortcode::shortcode(&key(2)));
+        assert_eq!(set.rx.try_recv().unwrap().record, key(1));
+        assert!(set.rx.try_recv().is_err());
+
+        drop(set);
+        assert!(watches.records().is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_set_reads_as_a_stream() {
+        use tokio_stream::StreamExt;
+
+        let watches = WatchManager::new();
+        let set = WatchSet::new();
+        set.add(&watches, key(1), ValueSubkeyRangeSet::new());
+        let mut stream = set.into_stream();
+        watches.dispatch(&change(&key(1), ValueSubkeyRangeSet::single(0), 5, Some("one")));
+        assert_eq!(stream.next().await.unwrap().record, key(1));
+    }
+
+    #[test]
+    fn expired_values_are_not_passed_on() {
+        let watches = WatchManager::new();
+        let (all, _) = subscribe(&watches, key(1), ValueSubkeyRangeSet::new());
+        let writer = Crypto::generate_keypair(CRYPTO_KIND_VLD0).unwrap().key();
+        for env in [Envelope::tombstone(), Envelope::text("hi")] {
+            watches.dispatch(&VeilidValueChange {
+                key: key(1),
+                subkeys: ValueSubkeyRangeSet::single(0),
+                count: 5,
+                value: Some(ValueData::new_with_seq(ValueSeqNum::from(3), env.encode(), writer.clone()).unwrap()),
+            });
+        }
+        assert_eq!(all.try_recv().unwrap().value.unwrap().display(), "hi");
+        assert!(all.try_recv().is_err());
+    }
+
+    #[test]
+    fn dead_watch_is_reported_and_dropped_streams_forgotten() {
+        let watches = WatchManager::new();
+        let (rx, _) = subscribe(&watches, key(1), ValueSubkeyRangeSet::single(2));
+        let (gone, _) = subscribe(&watches, key(1), ValueSubkeyRangeSet::new());
+        drop(gone);
+
+        watches.dispatch(&change(&key(1), ValueSubkeyRangeSet::new(), 0, None));
+
+        assert!(rx.try_recv().unwrap().watch_died);
+        assert_eq!(watches.subscribers.lock().unwrap().len(), 1);
+    }
+}
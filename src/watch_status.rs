@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use crate::node::{VeilidNode, WatchCheck};
+use crate::shortcode;
+use crate::stats::WatchStats;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`watch status` at the alt prompt: is each watch still there, and for
+//	how long?
+//
+//	"DHT watch active" is printed once, when the watch is placed, and
+//	nothing looked at it again. This asks Veilid about every watched record
+//	with the parameters the watch was placed with (VeilidNode::check_watch),
+//	and shows what it says next to the time left before watch_expiry_secs
+//	runs out and the count the last change said was remaining.
+//
+//	Veilid 0.5 answers from its own record of the watch and reconciles it
+//	with the nodes holding the record in the background, so "active" means
+//	Veilid is still keeping the watch up, not that a remote node just
+//	confirmed it; a watch the network dropped shows up as a dead change
+//	(count 0), which the count column picks up.
+//
+//	Typing `r` straight after renews every watch from now.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+// One watched record, as `watch status` shows it.
+pub struct Row {
+    pub code: String,
+    pub check: Result<WatchCheck, String>,
+    // changes delivered, and the remaining count the last one carried
+    pub delivered: u64,
+    pub remaining: Option<u32>,
+}
+
+fn countdown(left: Duration) -> String {
+    let secs = left.as_secs();
+    if secs == 0 {
+        "expired".to_string()
+    } else if secs >= 3600 {
+        format!("in {}h{:02}m", secs / 3600, secs % 3600 / 60)
+    } else {
+        format!("in {}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
+pub fn render(rows: &[Row]) -> String {
+    if rows.is_empty() {
+        return "No watches placed".to_string();
+    }
+    let mut lines = vec![format!(
+        "{:<22} {:<9} {:<10} {:<14} {:<9} {}",
+        "record", "veilid", "subkeys", "expires", "changes", "count left"
+    )];
+    for row in rows {
+        let remaining = match row.remaining {
+            Some(0) => "0 (dead)".to_string(),
+            Some(n) => n.to_string(),
+            None => "-".to_string(),
+        };
+        match &row.check {
+            Ok(check) => lines.push(format!(
+                "{:<22} {:<9} {:<10} {:<14} {:<9} {remaining}",
+                row.code,
+                if check.active { "active" } else { "GONE" },
+                check.subkeys.as_ref().map_or("all".to_string(), |s| s.to_string()),
+                check.expires_in.map_or("never".to_string(), countdown),
+                row.delivered
+            )),
+            Err(e) => lines.push(format!("{:<22} couldn't check: {e}", row.code)),
+        }
+    }
+    lines.push(String::new());
+    lines.push("Type r and ENTER to renew every watch from now".to_string());
+    lines.join("\n")
+}
+
+pub async fn status(node: &VeilidNode, stats: &WatchStats) -> String {
+    let mut rows = Vec::new();
+    for record in node.watched_records() {
+        let (delivered, remaining) = stats.counts(&record).unwrap_or((0, None));
+        rows.push(Row {
+            code: shortcode::shortcode(&record),
+            check: node.check_watch(&record).await.map_err(|e| e.to_string()),
+            delivered,
+            remaining,
+        });
+    }
+    render(&rows)
+}
+
+// The `r` after `watch status`.
+pub async fn renew(node: &VeilidNode) -> String {
+    let mut lines = Vec::new();
+    for record in node.watched_records() {
+        let code = shortcode::shortcode(&record);
+        match node.renew_watch(&record).await {
+            Ok(()) => lines.push(format!("Renewed the watch on {code}")),
+            Err(e) => lines.push(format!("Couldn't renew the watch on {code}: {e}")),
+        }
+    }
+    if lines.is_empty() {
+        return "No watches to renew".to_string();
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_show_veilids_answer_and_the_time_left() {
+        let rows = [
+            Row {
+                code: "harp-otter-coal-lime".to_string(),
+                check: Ok(WatchCheck {
+                    subkeys: None,
+                    expires_in: Some(Duration::from_secs(125)),
+                    active: true,
+                }),
+                delivered: 4,
+                remaining: Some(0),
+            },
+            Row {
+                code: "mint-fern-gold-ash".to_string(),
+                check: Err("no watch was placed on this record".to_string()),
+                delivered: 0,
+                remaining: None,
+            },
+        ];
+        let text = render(&rows);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[1], "harp-otter-coal-lime   active    all        in 2m05s       4         0 (dead)");
+        assert_eq!(lines[2], "mint-fern-gold-ash     couldn't check: no watch was placed on this record");
+        assert_eq!(countdown(Duration::from_secs(3 * 3600 + 60)), "in 3h01m");
+        assert_eq!(countdown(Duration::ZERO), "expired");
+    }
+}
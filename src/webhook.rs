@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::config::AppConfig;
+use crate::watch::DecodedChange;
+
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	webhook_url in the config: POST every watched change to a URL as JSON,
+//	so a CI job or a chat bot can hear about it without polling the DHT.
+//
+//	  {"event": "value_changed", "record": "VLD0:...", "shortcode": "...",
+//	   "subkey": 3, "seq": 12, "writer": "VLD0:...", "value": "hello",
+//	   "sent_ms": 1760000000000}
+//
+//	"watch_died" events carry no value, and a change Veilid didn't send the
+//	value for has "subkeys" instead.
+//
+//	With webhook_secret set, each POST has an X-Veilid-Signature header,
+//	"sha256=" and the hex of the body's HMAC-SHA256 under the secret, the
+//	form most webhook receivers already know how to check.
+//
+//	Plain http:// only, with no TLS; point it at something local, or a
+//	proxy that does https onwards. A POST that can't connect or gets a 5xx
+//	is tried again up to webhook_retries times, waiting longer each time;
+//	a 4xx isn't, since sending the same thing again won't change it. Each
+//	POST runs in its own task, so a slow receiver never holds up the prompt.
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    pub fn parse(url: &str) -> Result<Endpoint, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or(format!("webhook_url '{url}' should start with http:// (there's no TLS here)"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(at) => (&rest[..at], &rest[at..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| format!("webhook_url '{url}' has a bad port"))?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("webhook_url '{url}' has no host"));
+        }
+        Ok(Endpoint {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Webhook {
+    endpoint: Endpoint,
+    secret: Option<String>,
+    retries: u32,
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes a key of any length");
+    mac.update(body);
+    let hex: String = mac.finalize().into_bytes().iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256={hex}")
+}
+
+pub fn payload(change: &DecodedChange, sent_ms: u64) -> serde_json::Value {
+    let mut body = json!({
+        "event": if change.watch_died { "watch_died" } else { "value_changed" },
+        "record": change.record.to_string(),
+        "shortcode": change.shortcode(),
+        "sent_ms": sent_ms,
+    });
+    match &change.value {
+        Some(value) => {
+            body["subkey"] = json!(value.subkey);
+            body["seq"] = json!(value.seq.to_option());
+            body["writer"] = json!(value.writer.to_string());
+            body["value"] = json!(value.display());
+        }
+        None if !change.watch_died => body["subkeys"] = json!(change.subkeys.to_string()),
+        None => {}
+    }
+    body
+}
+
+impl Webhook {
+    pub fn from_config(config: &AppConfig) -> Result<Option<Webhook>, String> {
+        let Some(url) = &config.webhook_url else {
+            return Ok(None);
+        };
+        Ok(Some(Webhook {
+            endpoint: Endpoint::parse(url)?,
+            secret: config.webhook_secret.clone(),
+            retries: config.webhook_retries,
+        }))
+    }
+
+    // Sends the change in the background.
+    pub fn send(&self, change: &DecodedChange) {
+        let body = payload(change, crate::audit::now_ms() as u64).to_string();
+        let hook = self.clone();
+        let what = match &change.value {
+            Some(value) => format!("subkey {} of {}", value.subkey, change.shortcode()),
+            None => change.shortcode(),
+        };
+        tokio::spawn(async move {
+            if let Err(e) = hook.deliver(body.as_bytes()).await {
+                println!("[webhook] gave up on {what}: {e}");
+            }
+        });
+    }
+
+    async fn deliver(&self, body: &[u8]) -> Result<(), String> {
+        let mut wait = FIRST_RETRY;
+        let mut tries = 0;
+        loop {
+            tries += 1;
+            match self.post(body).await {
+                Ok(status) if (200..300).contains(&status) => return Ok(()),
+                Ok(status) if (400..500).contains(&status) => return Err(format!("the receiver said {status}")),
+                result if tries > self.retries => {
+                    let why = result.map_or_else(|e| e, |status| format!("the receiver said {status}"));
+                    return Err(format!("{why} (after {tries} tries)"));
+                }
+                _ => {}
+            }
+            tokio::time::sleep(wait).await;
+            wait *= 2;
+        }
+    }
+
+    // One POST; the response's status code.
+    async fn post(&self, body: &[u8]) -> Result<u16, String> {
+        let Endpoint { host, port, path } = &self.endpoint;
+        let mut stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((host.as_str(), *port)))
+            .await
+            .map_err(|_| format!("couldn't connect to {host}:{port} in time"))?
+            .map_err(|e| format!("couldn't connect to {host}:{port}: {e}"))?;
+        let mut head = format!(
+            "POST {path} HTTP/1.1\r\nHost: {host}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            body.len()
+        );
+        if let Some(secret) = &self.secret {
+            head.push_str(&format!("X-Veilid-Signature: {}\r\n", sign(secret, body)));
+        }
+        head.push_str("\r\n");
+        let sent = async {
+            stream.write_all(head.as_bytes()).await?;
+            stream.write_all(body).await?;
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).await?;
+            Ok::<_, std::io::Error>(response)
+        };
+        let response = tokio::time::timeout(CONNECT_TIMEOUT, sent)
+            .await
+            .map_err(|_| format!("{host}:{port} didn't answer in time"))?
+            .map_err(|e| format!("POST to {host}:{port} failed: {e}"))?;
+        // "HTTP/1.1 204 No Content"
+        String::from_utf8_lossy(&response)
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or(format!("{host}:{port} didn't answer with HTTP"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn only_plain_http_urls_are_taken() {
+        assert_eq!(
+            Endpoint::parse("http://localhost:8080/hooks/veilid"),
+            Ok(Endpoint {
+                host: "localhost".to_string(),
+                port: 8080,
+                path: "/hooks/veilid".to_string(),
+            })
+        );
+        assert_eq!(Endpoint::parse("http://ci.example").unwrap().path, "/");
+        assert!(Endpoint::parse("https://ci.example/").unwrap_err().contains("http://"));
+        assert!(Endpoint::parse("http://:80/").is_err());
+    }
+
+    #[test]
+    fn signatures_are_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_failed_post_is_retried_and_the_body_is_signed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let hook = Webhook {
+            endpoint: Endpoint::parse(&format!("http://127.0.0.1:{port}/hook")).unwrap(),
+            secret: Some("shh".to_string()),
+            retries: 2,
+        };
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for status in ["503 Service Unavailable", "204 No Content"] {
+                let (mut conn, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = conn.read(&mut buf).await.unwrap();
+                requests.push(String::from_utf8_lossy(&buf[..n]).into_owned());
+                conn.write_all(format!("HTTP/1.1 {status}\r\n\r\n").as_bytes()).await.unwrap();
+            }
+            requests
+        });
+        hook.deliver(br#"{"event":"value_changed"}"#).await.unwrap();
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].starts_with("POST /hook HTTP/1.1\r\n"));
+        let signature = sign("shh", br#"{"event":"value_changed"}"#);
+        assert!(requests[1].contains(&format!("X-Veilid-Signature: {signature}\r\n")), "{}", requests[1]);
+    }
+}
@@ -0,0 +1,109 @@
+/////////////////////////////////////////////////////////////////////////////////
+//
+//	`daemon --service`: run the daemon as a Windows service.
+//
+//	Register it once (from an admin prompt), with the arguments you want the
+//	daemon to run with after `--service`:
+//
+//	  sc create VeilidExample binPath= "C:\path\veilid_test_node.exe daemon --service --role alt --data-dir C:\veilid"
+//	  sc start VeilidExample
+//
+//	Windows starts the exe, we hand the thread to the service dispatcher,
+//	and it calls service_main() below. Stop and Shutdown events ask the
+//	daemon to stop, which runs the same teardown as Ctrl+C (detach, shut
+//	Veilid down, final summary) before we report the service stopped.
+//
+//	Services have no console, so keep an eye on daemon_<role>.log in the
+//	data folder instead (--data-dir helps, the service account's own data
+//	folder is somewhere under C:\Windows).
+//
+/////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(windows)]
+pub use imp::run;
+
+#[cfg(not(windows))]
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    Err("--service is only available on Windows (use systemd on Linux, see `daemon`)".into())
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::ffi::OsString;
+    use std::time::Duration;
+
+    use windows_service::service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+        ServiceType,
+    };
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle};
+    use windows_service::{define_windows_service, service_dispatcher};
+
+    use crate::cli::{self, Command};
+    use crate::config::AppConfig;
+    use crate::soak::{self, SoakRole};
+
+    const SERVICE_NAME: &str = "VeilidExample";
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    // Blocks until the service stops.
+    pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+        service_dispatcher::start(SERVICE_NAME, ffi_service_main)?;
+        Ok(())
+    }
+
+    fn service_main(_args: Vec<OsString>) {
+        // There's nowhere to print to, so the error goes to the event log via the exit code.
+        if run_service().is_err() {
+            std::process::exit(1);
+        }
+    }
+
+    fn set_state(handle: &ServiceStatusHandle, state: ServiceState, code: u32) -> windows_service::Result<()> {
+        let controls_accepted = if state == ServiceState::Running {
+            ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+        } else {
+            ServiceControlAccept::empty()
+        };
+        handle.set_service_status(ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted,
+            exit_code: ServiceExitCode::Win32(code),
+            checkpoint: 0,
+            // Veilid can take a while to shut down cleanly
+            wait_hint: Duration::from_secs(30),
+            process_id: None,
+        })
+    }
+
+    fn run_service() -> Result<(), Box<dyn std::error::Error>> {
+        let handle = service_control_handler::register(SERVICE_NAME, |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                soak::request_stop();
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        })?;
+        set_state(&handle, ServiceState::StartPending, 0)?;
+
+        // The service's own command line (from `sc create binPath=`) says what to run.
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+            let options = cli::parse(&args)?;
+            let config = AppConfig::load(&options)?;
+            let role = match options.command {
+                Command::Daemon { role } => role,
+                _ => SoakRole::Alt,
+            };
+            set_state(&handle, ServiceState::Running, 0)?;
+            let runtime = tokio::runtime::Runtime::new()?;
+            runtime.block_on(soak::run(None, role, &options, &config))
+        })();
+
+        set_state(&handle, ServiceState::Stopped, if result.is_ok() { 0 } else { 1 })?;
+        result
+    }
+}